@@ -0,0 +1,246 @@
+//! Opt-in crash reports for long-running scans.
+//!
+//! A scan that panics three hours in leaves little behind but a line of
+//! stderr nobody was watching. With `--crash-dir`, [`install`] registers a
+//! panic hook that writes a diagnostic bundle to that directory instead: a
+//! JSON file naming the command, a hash of its configuration, the phase the
+//! scan was in, the last [`MAX_EVENTS`] events and the backtrace.
+//!
+//! The configuration is only hashed, never written out: targets and notes
+//! stay out of a file that may be attached to a bug report, while two
+//! crashes can still be told apart as coming from the same command line.
+//! Nothing is recorded until [`install`] is called, so [`phase`] and
+//! [`event`] cost next to nothing when crash reports are off.
+
+use std::backtrace::Backtrace;
+use std::collections::VecDeque;
+use std::collections::hash_map::DefaultHasher;
+use std::fs;
+use std::hash::{Hash, Hasher};
+use std::panic::{self, PanicHookInfo};
+use std::path::{Path, PathBuf};
+use std::sync::{Mutex, OnceLock};
+use std::thread;
+
+use chrono::{DateTime, Utc};
+use serde::Serialize;
+
+/// Events kept for the report; older ones are dropped.
+pub const MAX_EVENTS: usize = 50;
+
+/// Version of the crash bundle layout, bumped on incompatible changes.
+pub const CRASH_FORMAT_VERSION: u32 = 1;
+
+/// What the panic hook knows about the scan so far.
+#[derive(Debug)]
+struct Recorder {
+    dir: PathBuf,
+    command: String,
+    config_hash: String,
+    phase: String,
+    events: VecDeque<CrashEvent>,
+}
+
+/// The recorder, once [`install`] has run.
+static RECORDER: OnceLock<Mutex<Recorder>> = OnceLock::new();
+
+/// One thing the scan did, as kept for the report.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+pub struct CrashEvent {
+    /// When it happened.
+    pub at: DateTime<Utc>,
+    /// What happened, e.g. `phase: port-scan` or `10.0.0.5: 3 open`.
+    pub message: String,
+}
+
+/// The diagnostic bundle written when the process panics.
+#[derive(Debug, Clone, Serialize)]
+pub struct CrashReport {
+    /// [`CRASH_FORMAT_VERSION`].
+    pub format_version: u32,
+    /// When the panic happened.
+    pub time: DateTime<Utc>,
+    /// The asphyxia version that crashed.
+    pub asphyxia_version: &'static str,
+    /// The subcommand, e.g. `sweep`.
+    pub command: String,
+    /// Hash of the full command-line configuration (see [`config_hash`]).
+    pub config_hash: String,
+    /// The phase the scan was in (see [`phase`]).
+    pub phase: String,
+    /// The thread that panicked.
+    pub thread: String,
+    /// The panic message.
+    pub message: String,
+    /// Where in the source the panic was raised, as `file:line:column`.
+    pub location: Option<String>,
+    /// The last events before the panic, oldest first.
+    pub events: Vec<CrashEvent>,
+    /// The panicking thread's backtrace.
+    pub backtrace: String,
+}
+
+/// A short, stable-within-a-build hash of `config` (e.g. the parsed
+/// command line), as 16 hex digits.
+///
+/// # Examples
+///
+/// ```
+/// use asphyxia::cli::crash::config_hash;
+///
+/// assert_eq!(config_hash(&("ps", 22)), config_hash(&("ps", 22)));
+/// assert_ne!(config_hash(&("ps", 22)), config_hash(&("ps", 23)));
+/// assert_eq!(config_hash(&"sweep").len(), 16);
+/// ```
+pub fn config_hash<T: std::fmt::Debug>(config: &T) -> String {
+    let mut hasher = DefaultHasher::new();
+    format!("{:?}", config).hash(&mut hasher);
+    format!("{:016x}", hasher.finish())
+}
+
+/// Write a crash report to `dir` whenever the process panics from now on,
+/// then carry on with the default panic handling (the message on stderr).
+///
+/// # Arguments
+///
+/// * `dir` - Directory the reports are written to; created if missing
+/// * `command` - The subcommand being run, e.g. `sweep`
+/// * `config` - The full configuration, hashed with [`config_hash`]
+///
+/// Only the first call installs the hook; later ones are ignored.
+pub fn install<T: std::fmt::Debug>(dir: PathBuf, command: &str, config: &T) {
+    let recorder = Recorder {
+        dir,
+        command: command.to_string(),
+        config_hash: config_hash(config),
+        phase: "startup".to_string(),
+        events: VecDeque::with_capacity(MAX_EVENTS),
+    };
+    if RECORDER.set(Mutex::new(recorder)).is_err() {
+        return;
+    }
+    let default_hook = panic::take_hook();
+    panic::set_hook(Box::new(move |info| {
+        if let Some(path) = write_report(info) {
+            eprintln!(
+                "asphyxia crashed; diagnostic report written to {}",
+                path.display()
+            );
+        }
+        default_hook(info);
+    }));
+}
+
+/// Record that the scan entered `name` (e.g. `discovery`, `port-scan`).
+pub fn phase(name: &str) {
+    record(|recorder| {
+        recorder.phase = name.to_string();
+        push(&mut recorder.events, format!("phase: {}", name));
+    });
+}
+
+/// Record that `message` happened, e.g. that a host finished scanning.
+pub fn event(message: impl Into<String>) {
+    record(|recorder| push(&mut recorder.events, message.into()));
+}
+
+/// Run `update` on the recorder, if crash reports are on.
+fn record<F: FnOnce(&mut Recorder)>(update: F) {
+    if let Some(recorder) = RECORDER.get() {
+        let mut recorder = recorder.lock().unwrap_or_else(|e| e.into_inner());
+        update(&mut recorder);
+    }
+}
+
+/// Append `message` to `events`, dropping the oldest beyond [`MAX_EVENTS`].
+fn push(events: &mut VecDeque<CrashEvent>, message: String) {
+    if events.len() == MAX_EVENTS {
+        events.pop_front();
+    }
+    events.push_back(CrashEvent {
+        at: Utc::now(),
+        message,
+    });
+}
+
+/// Build the report for the panic `info` and write it, returning its path.
+fn write_report(info: &PanicHookInfo<'_>) -> Option<PathBuf> {
+    // A panic while the recorder was locked leaves it poisoned, not gone.
+    let recorder = RECORDER.get()?.lock().unwrap_or_else(|e| e.into_inner());
+    let message = info
+        .payload()
+        .downcast_ref::<&str>()
+        .map(|text| text.to_string())
+        .or_else(|| info.payload().downcast_ref::<String>().cloned())
+        .unwrap_or_else(|| "(non-text panic payload)".to_string());
+    let report = CrashReport {
+        format_version: CRASH_FORMAT_VERSION,
+        time: Utc::now(),
+        asphyxia_version: env!("CARGO_PKG_VERSION"),
+        command: recorder.command.clone(),
+        config_hash: recorder.config_hash.clone(),
+        phase: recorder.phase.clone(),
+        thread: thread::current().name().unwrap_or("unnamed").to_string(),
+        message,
+        location: info
+            .location()
+            .map(|at| format!("{}:{}:{}", at.file(), at.line(), at.column())),
+        events: recorder.events.iter().cloned().collect(),
+        backtrace: Backtrace::force_capture().to_string(),
+    };
+    save_report(&recorder.dir, &report).ok()
+}
+
+/// Write `report` to `dir` as `asphyxia-crash-<time>-<pid>.json`.
+fn save_report(dir: &Path, report: &CrashReport) -> std::io::Result<PathBuf> {
+    fs::create_dir_all(dir)?;
+    let path = dir.join(format!(
+        "asphyxia-crash-{}-{}.json",
+        report.time.format("%Y%m%dT%H%M%SZ"),
+        std::process::id()
+    ));
+    fs::write(&path, serde_json::to_vec_pretty(report)?)?;
+    Ok(path)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_push_keeps_last_events() {
+        let mut events = VecDeque::new();
+        for i in 0..MAX_EVENTS + 5 {
+            push(&mut events, format!("event {}", i));
+        }
+        assert_eq!(events.len(), MAX_EVENTS);
+        assert_eq!(events.front().unwrap().message, "event 5");
+    }
+
+    #[test]
+    fn test_save_report_writes_json_bundle() {
+        let dir = std::env::temp_dir().join(format!("asphyxia-crash-test-{}", std::process::id()));
+        let report = CrashReport {
+            format_version: CRASH_FORMAT_VERSION,
+            time: "2026-05-04T09:00:00Z".parse().unwrap(),
+            asphyxia_version: "0.0.0",
+            command: "sweep".to_string(),
+            config_hash: config_hash(&"sweep"),
+            phase: "port-scan".to_string(),
+            thread: "main".to_string(),
+            message: "boom".to_string(),
+            location: Some("src/main.rs:1:1".to_string()),
+            events: Vec::new(),
+            backtrace: String::new(),
+        };
+        let path = save_report(&dir, &report).unwrap();
+        let written: serde_json::Value = serde_json::from_slice(&fs::read(&path).unwrap()).unwrap();
+        fs::remove_dir_all(&dir).unwrap();
+        assert!(path.ends_with(format!(
+            "asphyxia-crash-20260504T090000Z-{}.json",
+            std::process::id()
+        )));
+        assert_eq!(written["phase"], "port-scan");
+        assert_eq!(written["message"], "boom");
+    }
+}
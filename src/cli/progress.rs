@@ -0,0 +1,82 @@
+//! Terminal progress bars for the command-line interface.
+
+use indicatif::{ProgressBar, ProgressStyle};
+use std::sync::Mutex;
+
+use crate::cli::theme::Theme;
+use crate::scanner::progress::ProgressHandler;
+
+/// Build a styled progress bar for a scan of `total` items.
+///
+/// The `suffix` is appended after the `pos/len` counter (e.g. `"ports scanned"`
+/// or `"addresses scanned"`), so both the port and address scanners can share
+/// the same bar style. The bar is coloured by the current [`Theme`].
+///
+/// # Examples
+///
+/// ```
+/// use asphyxia::cli::progress::progress_bar;
+///
+/// let pb = progress_bar(100, "ports scanned");
+/// pb.finish_and_clear();
+/// ```
+pub fn progress_bar(total: u64, suffix: &str) -> ProgressBar {
+    let pb = ProgressBar::new(total);
+    let colors = Theme::current().bar_colors();
+    pb.set_style(
+        ProgressStyle::with_template(&format!(
+            "[{{elapsed_precise}}] {{bar:40{colors}}} {{pos}}/{{len}} {suffix}"
+        ))
+        .unwrap()
+        .progress_chars("=> "),
+    );
+    pb
+}
+
+/// A [`ProgressHandler`] drawing one terminal progress bar per scan phase.
+#[derive(Debug, Default)]
+pub struct BarProgress {
+    bar: Mutex<Option<ProgressBar>>,
+}
+
+impl BarProgress {
+    /// Create a handler; no bar is drawn until a phase starts.
+    pub fn new() -> BarProgress {
+        BarProgress::default()
+    }
+
+    fn bar(&self) -> Option<ProgressBar> {
+        // ProgressBar is a cheap handle; clone it out so the lock is not held
+        // while drawing.
+        self.bar.lock().unwrap_or_else(|e| e.into_inner()).clone()
+    }
+
+    /// Print `line` above the current bar, where it stays while the bar
+    /// keeps redrawing underneath.
+    ///
+    /// Like the bar itself, the line goes to stderr and is dropped when the
+    /// bar is hidden (stderr is not a terminal) or no phase has started yet.
+    pub fn println(&self, line: &str) {
+        if let Some(bar) = self.bar() {
+            bar.println(line);
+        }
+    }
+}
+
+impl ProgressHandler for BarProgress {
+    fn start(&self, total: u64, label: &str) {
+        *self.bar.lock().unwrap_or_else(|e| e.into_inner()) = Some(progress_bar(total, label));
+    }
+
+    fn inc(&self, delta: u64) {
+        if let Some(bar) = self.bar() {
+            bar.inc(delta);
+        }
+    }
+
+    fn finish(&self, message: &str) {
+        if let Some(bar) = self.bar() {
+            bar.finish_with_message(message.to_string());
+        }
+    }
+}
@@ -0,0 +1,227 @@
+//! Colours of the terminal output.
+//!
+//! Reports never pick a colour themselves. Each piece of text has a [`Role`]
+//! (a heading, an address, a warning, an open port) and is painted with
+//! [`Paint::paint`], which looks the role up in the current [`Theme`]. So
+//! one `--theme` switch recolours every report, message and progress bar
+//! alike, and what means the same thing always looks the same.
+//!
+//! The default dark theme uses bright colours for dark terminal
+//! backgrounds, the light theme plain ones that stay readable on white, and
+//! the mono theme no escape codes at all (for logs, screenshots and
+//! `NO_COLOR`).
+
+use clap::{Arg, ArgMatches, ValueEnum};
+use owo_colors::{OwoColorize, Style};
+use std::fmt;
+use std::sync::atomic::{AtomicU8, Ordering};
+
+/// The theme in use, as a [`Theme`] discriminant.
+static CURRENT: AtomicU8 = AtomicU8::new(Theme::Dark as u8);
+
+/// A set of colours for the terminal output.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default, ValueEnum)]
+pub enum Theme {
+    /// Bright colours for dark backgrounds (the default).
+    #[default]
+    Dark,
+    /// Plain colours that stay readable on light backgrounds.
+    Light,
+    /// No colours.
+    Mono,
+}
+
+/// What a piece of terminal output is, which decides its colour.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Role {
+    /// What a command is doing, e.g. `Started` or `Viewing`.
+    Heading,
+    /// The title of a section of a report, e.g. `Opened ports`.
+    Section,
+    /// The closing banner, `Game Over`.
+    Banner,
+    /// What is scanned, and the live hosts found.
+    Target,
+    /// The host a section is about.
+    Subject,
+    /// An address in front of a port.
+    Address,
+    /// A port and protocol, e.g. `22/tcp`.
+    Port,
+    /// A service or host name.
+    Name,
+    /// A product and version.
+    Product,
+    /// What a web server answered.
+    Web,
+    /// Labels, timestamps and other secondary details.
+    Muted,
+    /// Good news: a check passed, nothing changed.
+    Success,
+    /// Worth knowing, nothing to act on.
+    Info,
+    /// Worth a look: a certificate about to expire, partial results.
+    Warning,
+    /// Something failed or is wrong.
+    Error,
+    /// An open port or a host that is up.
+    Open,
+    /// A port whose probes got no answer.
+    Filtered,
+    /// A closed port or a host that is down.
+    Closed,
+}
+
+impl Theme {
+    /// The theme the output is painted with.
+    pub fn current() -> Theme {
+        match CURRENT.load(Ordering::Relaxed) {
+            x if x == Theme::Light as u8 => Theme::Light,
+            x if x == Theme::Mono as u8 => Theme::Mono,
+            _ => Theme::Dark,
+        }
+    }
+
+    /// Paint all further output with this theme.
+    pub fn set(self) {
+        CURRENT.store(self as u8, Ordering::Relaxed);
+    }
+
+    /// The theme to use when none is asked for: mono when the `NO_COLOR`
+    /// environment variable is set (see <https://no-color.org>), dark
+    /// otherwise.
+    pub fn from_env() -> Theme {
+        match std::env::var_os("NO_COLOR") {
+            Some(value) if !value.is_empty() => Theme::Mono,
+            _ => Theme::Dark,
+        }
+    }
+
+    /// How text of `role` is styled in this theme.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use asphyxia::cli::theme::{Role, Theme};
+    ///
+    /// assert!(Theme::Mono.style(Role::Error).is_plain());
+    /// assert_ne!(Theme::Dark.style(Role::Open), Theme::Dark.style(Role::Closed));
+    /// ```
+    pub fn style(self, role: Role) -> Style {
+        let style = Style::new();
+        match self {
+            Theme::Dark => match role {
+                Role::Heading | Role::Name => style.bright_blue(),
+                Role::Section | Role::Success | Role::Open => style.green(),
+                Role::Banner => style.bright_red(),
+                Role::Target | Role::Port => style.bright_green(),
+                Role::Subject => style.bright_yellow(),
+                Role::Address | Role::Info => style.bright_cyan(),
+                Role::Product => style.bright_white(),
+                Role::Web => style.bright_magenta(),
+                Role::Muted => style.dimmed(),
+                Role::Warning | Role::Filtered => style.yellow(),
+                Role::Error | Role::Closed => style.red(),
+            },
+            Theme::Light => match role {
+                Role::Heading | Role::Address | Role::Info => style.blue(),
+                Role::Section | Role::Success | Role::Open | Role::Target | Role::Port => {
+                    style.green()
+                }
+                Role::Banner | Role::Error | Role::Closed => style.red(),
+                Role::Subject | Role::Product => style.bold(),
+                Role::Name => style.cyan(),
+                Role::Web => style.magenta(),
+                Role::Muted => style.dimmed(),
+                Role::Warning | Role::Filtered => style.yellow(),
+            },
+            Theme::Mono => style,
+        }
+    }
+
+    /// Colours of the filled and empty parts of a progress bar, in
+    /// `indicatif` template syntax (e.g. `.cyan/blue`).
+    pub fn bar_colors(self) -> &'static str {
+        match self {
+            Theme::Dark => ".cyan/blue",
+            Theme::Light => ".blue/white",
+            Theme::Mono => "",
+        }
+    }
+}
+
+/// The role of a host or port `state` as reports spell it (`open`, `up`,
+/// `filtered`, `closed`, `down`).
+pub fn state_role(state: &str) -> Role {
+    match state {
+        "open" | "up" => Role::Open,
+        "filtered" => Role::Filtered,
+        _ => Role::Closed,
+    }
+}
+
+/// Text painted in the current [`Theme`].
+pub trait Paint {
+    /// This text, styled as `role` calls for in the current theme.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use asphyxia::cli::theme::{Paint, Role, Theme};
+    ///
+    /// Theme::Mono.set();
+    /// assert_eq!("22/tcp".paint(Role::Port), "22/tcp");
+    /// ```
+    fn paint(&self, role: Role) -> String;
+}
+
+impl<T: fmt::Display + ?Sized> Paint for T {
+    fn paint(&self, role: Role) -> String {
+        self.style(Theme::current().style(role)).to_string()
+    }
+}
+
+/// The `--theme` option, accepted before or after any subcommand.
+pub fn theme_arg() -> Arg {
+    Arg::new("theme")
+        .long("theme")
+        .global(true)
+        .value_name("THEME")
+        .value_parser(clap::value_parser!(Theme))
+        .help(
+            "Colours of the terminal output: dark (default), light or mono (default with NO_COLOR)",
+        )
+}
+
+/// The theme asked for with `--theme` in `matches`, or else the one from
+/// the environment (see [`Theme::from_env`]).
+pub fn from_matches(matches: &ArgMatches) -> Theme {
+    matches
+        .get_one::<Theme>("theme")
+        .copied()
+        .unwrap_or_else(Theme::from_env)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_every_theme_tells_states_apart() {
+        for theme in [Theme::Dark, Theme::Light] {
+            let open = theme.style(state_role("open"));
+            assert_eq!(open, theme.style(state_role("up")));
+            assert_ne!(open, theme.style(state_role("filtered")));
+            assert_ne!(open, theme.style(state_role("closed")));
+            assert_ne!(theme.style(Role::Filtered), theme.style(Role::Closed));
+        }
+    }
+
+    #[test]
+    fn test_mono_paints_nothing() {
+        for role in [Role::Heading, Role::Muted, Role::Error, Role::Open] {
+            assert!(Theme::Mono.style(role).is_plain());
+        }
+        assert!(Theme::Mono.bar_colors().is_empty());
+    }
+}
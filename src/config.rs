@@ -0,0 +1,410 @@
+//! Defaults for the scan commands, read from a TOML config file.
+//!
+//! Anyone who scans the same networks every day ends up retyping the same
+//! flags. A config file sets them once:
+//!
+//! ```toml
+//! timeout = "800ms"
+//! concurrency = 128
+//! output = "jsonl"
+//! operator = "jsmith"
+//! exclude_ports = "137-139,445"
+//! exclude_hosts = "10.0.0.1"
+//!
+//! [groups]
+//! dmz = ["10.0.1.0/24", "web.corp"]
+//!
+//! [rates]
+//! "10.9.0.0/16" = "10x"
+//!
+//! [templates.mail]
+//! ports = "25,465,587,993"
+//! tls_info = true
+//! ```
+//!
+//! Every key is optional. Flags given on the command line win over the
+//! file, except the exclusion lists, which are added to (a host or port the
+//! config excludes is never scanned). A group is scanned by naming it as a
+//! target with a leading `@`, e.g. `asphyxia as @dmz`. A subnet under
+//! `[rates]` is probed at a rate of its own (see [`RateOverride`]) instead of
+//! `--rate`, for networks we own outright. A template under `[templates]` is
+//! scanned with `ps --template <name>`, like the built-in ones (see
+//! [`templates`](crate::scanner::templates)), and replaces a built-in
+//! template of the same name.
+//!
+//! The file is read from `--config`, else from the path in `ASPHYXIA_CONFIG`
+//! (set it empty to ignore config files), else from the platform's config
+//! directory (see [`default_path`]).
+
+use serde::Deserialize;
+use std::collections::BTreeMap;
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::time::Duration;
+
+use clap::ValueEnum;
+
+use crate::output::OutputFormat;
+use crate::scanner::options::TimingTemplate;
+use crate::scanner::rate::{RateOverride, SubnetRate};
+use crate::scanner::templates::{ScanTemplate, TemplateProbes};
+use crate::utils::{Target, parse_ports, parse_target, parse_timeout};
+
+/// Environment variable naming the config file; set but empty, no config
+/// file is read.
+pub const CONFIG_ENV: &str = "ASPHYXIA_CONFIG";
+
+/// Defaults for `ps`, `as` and `sweep`.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct Config {
+    /// Timing preset (`--timing`).
+    pub timing: Option<TimingTemplate>,
+    /// Connection timeout (`--timeout`).
+    pub timeout: Option<Duration>,
+    /// Maximum concurrent connection attempts (`--concurrency`).
+    pub concurrency: Option<usize>,
+    /// Extra attempts for silent probes (`--retries`).
+    pub retries: Option<u32>,
+    /// Output format (`--output`).
+    pub output: Option<OutputFormat>,
+    /// Who runs the scans (`--operator`).
+    pub operator: Option<String>,
+    /// Ports never scanned, in `--exclude-ports` syntax.
+    pub exclude_ports: Option<String>,
+    /// Hosts and subnets never probed, in `--exclude` syntax.
+    pub exclude_hosts: Option<String>,
+    /// Named lists of targets, scanned as `@name`.
+    pub groups: BTreeMap<String, Vec<Target>>,
+    /// Subnets probed at a rate of their own.
+    pub rates: Vec<SubnetRate>,
+    /// The user's scan templates, by name.
+    pub templates: Vec<ScanTemplate>,
+}
+
+/// A rate as written: attempts per second, or text such as `10x`.
+#[derive(Deserialize)]
+#[serde(untagged)]
+enum RawRate {
+    PerSecond(u32),
+    Text(String),
+}
+
+/// A `[templates]` entry as written.
+#[derive(Deserialize)]
+#[serde(deny_unknown_fields)]
+struct RawTemplate {
+    description: Option<String>,
+    ports: String,
+    #[serde(default)]
+    tls_info: bool,
+    #[serde(default)]
+    http_probe: bool,
+    #[serde(default)]
+    version_detect: bool,
+}
+
+/// The file as written, before its values are checked.
+#[derive(Deserialize)]
+#[serde(deny_unknown_fields)]
+struct RawConfig {
+    timing: Option<String>,
+    timeout: Option<String>,
+    concurrency: Option<usize>,
+    retries: Option<u32>,
+    output: Option<String>,
+    operator: Option<String>,
+    exclude_ports: Option<String>,
+    exclude_hosts: Option<String>,
+    #[serde(default)]
+    groups: BTreeMap<String, Vec<String>>,
+    #[serde(default)]
+    rates: BTreeMap<String, RawRate>,
+    #[serde(default)]
+    templates: BTreeMap<String, RawTemplate>,
+}
+
+impl Config {
+    /// Read and parse a config file.
+    pub fn load(path: &Path) -> Result<Config, String> {
+        let contents = fs::read_to_string(path)
+            .map_err(|e| format!("Cannot read config file {}: {}", path.display(), e))?;
+        Config::from_toml(&contents)
+            .map_err(|e| format!("Invalid config file {}: {}", path.display(), e))
+    }
+
+    /// Read the config file named by `--config`, or else the default one
+    /// if it exists.
+    ///
+    /// # Arguments
+    ///
+    /// * `path` - The `--config` path, if one was given
+    ///
+    /// # Returns
+    ///
+    /// * `Result<Config, String>` - The config (empty if there is no file),
+    ///   or an error if the file cannot be read or parsed. A missing file is
+    ///   only an error when it was named explicitly.
+    pub fn discover(path: Option<&Path>) -> Result<Config, String> {
+        if let Some(path) = path {
+            return Config::load(path);
+        }
+        if let Ok(path) = std::env::var(CONFIG_ENV) {
+            if path.is_empty() {
+                return Ok(Config::default());
+            }
+            return Config::load(Path::new(&path));
+        }
+        match default_path() {
+            Some(path) if path.is_file() => Config::load(&path),
+            _ => Ok(Config::default()),
+        }
+    }
+
+    /// Parse a config from TOML text (see the module docs for the keys).
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use asphyxia::config::Config;
+    /// use std::time::Duration;
+    ///
+    /// let config = Config::from_toml(r#"
+    ///     timeout = "500ms"
+    ///     [groups]
+    ///     lab = ["10.9.0.0/24"]
+    /// "#).unwrap();
+    /// assert_eq!(config.timeout, Some(Duration::from_millis(500)));
+    /// assert_eq!(config.group("lab").unwrap().len(), 1);
+    /// assert!(Config::from_toml("timeuot = \"1s\"").is_err());
+    /// ```
+    pub fn from_toml(text: &str) -> Result<Config, String> {
+        let raw: RawConfig = toml::from_str(text).map_err(|e| e.message().to_string())?;
+        let invalid = |key: &str, value: &str| format!("{}: invalid value {:?}", key, value);
+        let mut groups = BTreeMap::new();
+        for (name, targets) in raw.groups {
+            let targets = targets
+                .iter()
+                .map(|target| parse_target(target))
+                .collect::<Result<Vec<Target>, String>>()
+                .map_err(|e| format!("group {}: {}", name, e))?;
+            groups.insert(name, targets);
+        }
+        let mut rates = Vec::new();
+        for (subnet, rate) in raw.rates {
+            let network = subnet
+                .trim()
+                .parse()
+                .map_err(|_| format!("rates: invalid subnet {:?}", subnet))?;
+            let rate = match rate {
+                RawRate::PerSecond(n) => n.to_string().parse::<RateOverride>(),
+                RawRate::Text(text) => text.parse(),
+            }
+            .map_err(|e| format!("rates {}: {}", subnet, e))?;
+            rates.push(SubnetRate { network, rate });
+        }
+        let mut templates = Vec::new();
+        for (name, template) in raw.templates {
+            let ports =
+                parse_ports(&template.ports).map_err(|e| format!("template {}: {}", name, e))?;
+            templates.push(ScanTemplate {
+                name,
+                description: template.description.unwrap_or_default(),
+                ports,
+                probes: TemplateProbes {
+                    tls_info: template.tls_info,
+                    http_probe: template.http_probe,
+                    version_detect: template.version_detect,
+                },
+            });
+        }
+        Ok(Config {
+            timing: raw
+                .timing
+                .map(|s| TimingTemplate::from_str(&s, true).map_err(|_| invalid("timing", &s)))
+                .transpose()?,
+            timeout: raw
+                .timeout
+                .map(|s| parse_timeout(&s).map_err(|e| format!("timeout: {}", e)))
+                .transpose()?,
+            concurrency: match raw.concurrency {
+                Some(0) => return Err("concurrency: must be at least 1".to_string()),
+                concurrency => concurrency,
+            },
+            retries: raw.retries,
+            output: raw
+                .output
+                .map(|s| OutputFormat::from_str(&s, true).map_err(|_| invalid("output", &s)))
+                .transpose()?,
+            operator: raw.operator,
+            exclude_ports: raw.exclude_ports,
+            exclude_hosts: raw.exclude_hosts,
+            groups,
+            rates,
+            templates,
+        })
+    }
+
+    /// The targets of a named group.
+    pub fn group(&self, name: &str) -> Option<&[Target]> {
+        self.groups.get(name).map(Vec::as_slice)
+    }
+
+    /// `targets` with every `@group` replaced by the group's targets.
+    ///
+    /// # Returns
+    ///
+    /// * `Result<Vec<Target>, String>` - The expanded targets, or an error
+    ///   naming a group the config does not define
+    pub fn expand_groups(&self, targets: Vec<Target>) -> Result<Vec<Target>, String> {
+        let mut expanded = Vec::with_capacity(targets.len());
+        for target in targets {
+            match &target {
+                Target::Hostname(name) if name.starts_with('@') => {
+                    let group = self
+                        .group(&name[1..])
+                        .ok_or_else(|| format!("Unknown target group: {}", name))?;
+                    expanded.extend_from_slice(group);
+                }
+                _ => expanded.push(target),
+            }
+        }
+        Ok(expanded)
+    }
+}
+
+/// Where the config file is looked for when neither `--config` nor
+/// `ASPHYXIA_CONFIG` names one: `asphyxia/config.toml` in
+/// `$XDG_CONFIG_HOME` (default `~/.config`), or in `%APPDATA%` on Windows.
+pub fn default_path() -> Option<PathBuf> {
+    let env_dir = |name: &str| std::env::var_os(name).filter(|dir| !dir.is_empty());
+    let config_dir = if cfg!(windows) {
+        env_dir("APPDATA").map(PathBuf::from)
+    } else {
+        env_dir("XDG_CONFIG_HOME")
+            .map(PathBuf::from)
+            .or_else(|| env_dir("HOME").map(|home| Path::new(&home).join(".config")))
+    };
+    config_dir.map(|dir| dir.join("asphyxia").join("config.toml"))
+}
+
+/// Join an exclusion list from the command line with the config's, so both
+/// apply.
+pub fn merge_exclusions(cli: Option<String>, config: Option<&str>) -> Option<String> {
+    match (cli, config) {
+        (Some(cli), Some(config)) => Some(format!("{},{}", cli, config)),
+        (cli, config) => cli.or_else(|| config.map(str::to_string)),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_parse_every_key() {
+        let config = Config::from_toml(
+            r#"
+            timing = "polite"
+            timeout = "2s"
+            concurrency = 64
+            retries = 2
+            output = "JSONL"
+            operator = "jsmith"
+            exclude_ports = "445"
+            exclude_hosts = "10.0.0.1"
+
+            [groups]
+            dmz = ["10.0.1.0/24", "web.corp", "10.0.2.1-10.0.2.9"]
+
+            [rates]
+            "10.9.0.0/16" = "10x"
+            "10.8.0.0/24" = 2000
+
+            [templates.mail]
+            description = "Mail servers"
+            ports = "smtp,587,993"
+            tls_info = true
+            "#,
+        )
+        .unwrap();
+        assert_eq!(config.timing, Some(TimingTemplate::Polite));
+        assert_eq!(config.timeout, Some(Duration::from_secs(2)));
+        assert_eq!(config.concurrency, Some(64));
+        assert_eq!(config.retries, Some(2));
+        assert_eq!(config.output, Some(OutputFormat::Jsonl));
+        assert_eq!(config.operator.as_deref(), Some("jsmith"));
+        assert_eq!(config.group("dmz").unwrap().len(), 3);
+        assert_eq!(
+            config.rates,
+            [
+                SubnetRate {
+                    network: "10.8.0.0/24".parse().unwrap(),
+                    rate: RateOverride::PerSecond(2000),
+                },
+                SubnetRate {
+                    network: "10.9.0.0/16".parse().unwrap(),
+                    rate: RateOverride::Times(10),
+                },
+            ]
+        );
+        assert_eq!(
+            config.templates,
+            [ScanTemplate {
+                name: "mail".to_string(),
+                description: "Mail servers".to_string(),
+                ports: vec![25, 587, 993],
+                probes: TemplateProbes {
+                    tls_info: true,
+                    ..TemplateProbes::default()
+                },
+            }]
+        );
+        assert_eq!(Config::from_toml("").unwrap(), Config::default());
+    }
+
+    #[test]
+    fn test_invalid_values_name_the_key() {
+        for (toml, key) in [
+            ("timeout = \"soon\"", "timeout"),
+            ("output = \"csv\"", "output"),
+            ("concurrency = 0", "concurrency"),
+            ("[groups]\nlab = [\"10.0.0.0/33\"]", "group lab"),
+            ("[rates]\n\"lab\" = 10", "rates"),
+            ("[rates]\n\"10.9.0.0/16\" = \"fast\"", "rates"),
+            ("[templates.mail]\nports = \"25,nonsense\"", "template mail"),
+        ] {
+            let err = Config::from_toml(toml).unwrap_err();
+            assert!(err.starts_with(key), "{}: {}", toml, err);
+        }
+    }
+
+    #[test]
+    fn test_expand_groups() {
+        let config = Config::from_toml("[groups]\nlab = [\"10.9.0.1\", \"10.9.0.2\"]").unwrap();
+        let targets = vec![
+            parse_target("@lab").unwrap(),
+            parse_target("db.corp").unwrap(),
+        ];
+        let expanded: Vec<String> = config
+            .expand_groups(targets)
+            .unwrap()
+            .iter()
+            .map(Target::to_string)
+            .collect();
+        assert_eq!(expanded, ["10.9.0.1", "10.9.0.2", "db.corp"]);
+        let err = config
+            .expand_groups(vec![parse_target("@dmz").unwrap()])
+            .unwrap_err();
+        assert_eq!(err, "Unknown target group: @dmz");
+    }
+
+    #[test]
+    fn test_merge_exclusions() {
+        assert_eq!(
+            merge_exclusions(Some("22".into()), Some("445")).as_deref(),
+            Some("22,445")
+        );
+        assert_eq!(merge_exclusions(None, Some("445")).as_deref(), Some("445"));
+        assert_eq!(merge_exclusions(None, None), None);
+    }
+}
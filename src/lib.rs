@@ -14,8 +14,44 @@
 //!
 //! - `scanner::port`: Port scanning functionality
 //! - `scanner::address`: Address and subnet scanning functionality
+//! - `scanner::annotations`: Notes, custom attributes and probe limits for hosts and subnets, from a YAML file (`--annotations`)
+//! - `scanner::arp`: ARP probes that find filtered hosts on the local link (`--probe arp`)
+//! - `scanner::banner`: Read identifying banners from open services
+//! - `scanner::cancel`: `CancelToken` for stopping a scan early with partial results
+//! - `scanner::fingerprint`: Passive TCP/IP stack fingerprints and a coarse OS guess (`--os`)
+//! - `scanner::http_probe`: HTTP(S) status code, server header and page title of open web ports (`--http-probe`)
+//! - `scanner::icmp`: ICMP echo probes used for ping-based host discovery
+//! - `scanner::listen`: Accept and log inbound connections for firewall reflection tests (`listen`)
+//! - `scanner::monitor`: Re-check hosts and ports on an interval and report what changed (`monitor`)
+//! - `scanner::options`: `ScanOptions` (and its `ScanOptionsBuilder`) shared by the `*_with_options` scan functions, and `TimingTemplate` presets (`--timing`)
+//! - `scanner::order`: Shuffle the order ports and hosts are probed in, reproducibly from a seed (`--randomize`, `--seed`)
+//! - `scanner::outage`: Pause probing while the scanning host has lost its route to the targets, and record each outage (`--outage-window`)
+//! - `scanner::ping`: Ping a host repeatedly over ICMP (or TCP) for loss and round-trip statistics (`ping`)
+//! - `scanner::pmtu`: Path MTU and MTU black-hole discovery for live hosts (`--pmtu`)
+//! - `scanner::async`: Tokio-based port and subnet scanning with bounded concurrency
+//! - `scanner::progress`: `ProgressHandler` callbacks and a queryable `ProgressHandle` for rendering scan progress
+//! - `scanner::proxy`: Probe ports through a chain of HTTP CONNECT and SOCKS5 proxies instead of connecting directly (`--proxy`)
+//! - `scanner::rate`: `RateLimiter` throttling connection attempts per second
+//! - `scanner::replay`: Reconstruct probe outcomes from a pcap taken during a scan (`replay`)
+//! - `scanner::router`: Report a gateway's management services and exposure (`router-info`)
+//! - `scanner::stream`: `ScanStream` iterators over the results of a scan as they arrive
+//! - `scanner::sweep`: Discover live hosts on a subnet and port-scan each one
+//! - `scanner::syn`: Half-open SYN port probes for `--technique syn` (needs raw sockets)
+//! - `scanner::templates`: Built-in port lists for recurring sweeps (`--template`)
+//! - `scanner::tls`: Subject, issuer, alternative names and expiry of TLS certificates on open ports (`--tls-info`)
+//! - `scanner::top_ports`: Frequency-ordered list of commonly open ports (`--top-ports`)
+//! - `scanner::topology`: Merge traceroute paths into shared gateways and segments (DOT/JSON)
+//! - `scanner::traceroute`: ICMP traceroute to discovered hosts (`--trace-discovered`)
+//! - `scanner::ttl`: Estimate hop counts from the TTL of SYN-ACK and ICMP answers
+//! - `scanner::udp_path`: UDP latency, jitter and loss against an echo peer (`udp-probe`)
+//! - `scanner::usage`: Peak memory, sockets, traffic and CPU time of a scan
+//! - `scanner::validate`: Compare probes with what a remote `listen --control` received (`validate`)
+//! - `scanner::version`: Name the service, product and version behind open ports with protocol probes (`--version-detect`)
+//! - `storage`: SQLite history of every scan run, behind the `history` feature (`history`)
+//! - `config`: Defaults and named target groups for the scan commands, read from a TOML config file (`--config`)
+//! - `update`: Replace the running binary with a newer signed release (`self-update`)
 //! - `utils`: Utility functions for parsing and validation
-//! - `cli`: Command-line interface implementation
+//! - `cli`: Command-line interface implementation, including the terminal progress bars, colour themes (`--theme`) and opt-in crash reports (`--crash-dir`)
 //!
 //! ## Examples
 //!
@@ -24,15 +60,16 @@
 //! use asphyxia::scan_port;
 //!
 //! // Scan a single port (default timeout)
-//! if let Some(hit) = scan_port("example.com".to_string(), 80, None) {
-//!     println!("Port {} is open ({} ms)", hit.port, hit.latency.as_millis());
+//! let result = scan_port("example.com".to_string(), 80, None).unwrap();
+//! if result.is_open() {
+//!     println!("Port {} is open ({} ms)", result.port, result.latency.as_millis());
 //! }
 //!
-//! // Scan multiple ports
+//! // Scan multiple ports, telling closed ports apart from filtered ones
 //! let ports = vec![80, 443, 8080];
 //! for port in ports {
-//!     if let Some(hit) = scan_port("example.com".to_string(), port, None) {
-//!         println!("Port {} is open", hit.port);
+//!     if let Ok(result) = scan_port("example.com".to_string(), port, None) {
+//!         println!("Port {} is {}", result.port, result.state.as_str());
 //!     }
 //! }
 //! ```
@@ -46,7 +83,7 @@
 //! // Check if a host is available (IPv4 or IPv6)
 //! let ip: IpAddr = "192.168.1.1".parse().unwrap();
 //! let timeout = Duration::from_secs(1);
-//! if let Some(_) = scan_address(ip, Some(timeout)) {
+//! if scan_address(ip, Some(timeout)).is_up() {
 //!     println!("Host is available");
 //! }
 //!
@@ -62,6 +99,60 @@
 //! println!("Found {} hosts in range", hosts.len());
 //! ```
 //!
+//! ### Asynchronous Scanning
+//!
+//! For very large scans (e.g. a `/16`, or thousands of ports), the tokio-based
+//! scanners keep a bounded number of connections in flight without dedicating
+//! an OS thread to each one.
+//!
+//! ```no_run
+//! use asphyxia::{scan_ports_async, scan_subnet_async};
+//! use std::net::IpAddr;
+//!
+//! # async fn run() {
+//! let subnet = "10.0.0.0/16".parse().unwrap();
+//! let hosts = scan_subnet_async(subnet, None, 2048).await;
+//!
+//! let host: IpAddr = "10.0.0.1".parse().unwrap();
+//! let open = scan_ports_async(host, (1..=65535).collect(), None, 2048).await;
+//! println!("{} hosts up, {} ports open", hosts.len(), open.len());
+//! # }
+//! ```
+//!
+//! ### Reporting Progress
+//!
+//! The scanners draw nothing by themselves. Set [`ScanOptions::progress`] to a
+//! [`ProgressHandler`] to follow a scan from your own UI; the CLI plugs in
+//! [`cli::progress::BarProgress`] to draw its terminal progress bars.
+//!
+//! ```no_run
+//! use asphyxia::{ProgressHandler, ScanOptions, scan_subnet_with_options};
+//! use std::sync::Arc;
+//!
+//! struct Log;
+//!
+//! impl ProgressHandler for Log {
+//!     fn start(&self, total: u64, label: &str) {
+//!         eprintln!("0/{total} {label}");
+//!     }
+//!     fn inc(&self, _delta: u64) {}
+//!     fn finish(&self, message: &str) {
+//!         eprintln!("{message}");
+//!     }
+//! }
+//!
+//! let options = ScanOptions {
+//!     progress: Some(Arc::new(Log)),
+//!     ..ScanOptions::default()
+//! };
+//! let hosts = scan_subnet_with_options("192.168.1.0/24".parse().unwrap(), &options);
+//! ```
+//!
+//! To poll progress instead (phase, done/total, rate and ETA), ask a streaming
+//! scan for its [`ProgressHandle`] with [`ScanStream::progress`], or pass a
+//! handle of your own as the handler and read its
+//! [`snapshot`](ProgressHandle::snapshot) whenever you redraw.
+//!
 //! ### Using Utility Functions
 //! ```rust
 //! use asphyxia::{parse_ports, parse_ip, parse_subnet};
@@ -91,12 +182,34 @@
 //! scanning functions which are optimized for scanning multiple hosts.
 
 pub mod cli;
+pub mod config;
 pub mod output;
 pub mod scanner;
+#[cfg(feature = "history")]
+pub mod storage;
+pub mod update;
 pub mod utils;
 
-pub use output::{OutputFormat, ScanRecord};
-pub use scanner::address::{HostHit, scan_address, scan_ip_range, scan_subnet};
+pub use cli::progress::progress_bar;
+pub use output::{FORMAT_VERSION, OutputFormat, ScanRecord, read_records};
+pub use scanner::address::{
+    COMMON_DISCOVERY_PORTS, HostScanResult, HostState, HostStream, ProbeMethod, scan_address,
+    scan_address_multi, scan_address_with_options, scan_ip_range, scan_ip_range_iter,
+    scan_ip_range_with_options, scan_subnet, scan_subnet_iter, scan_subnet_with_options,
+};
+pub use scanner::r#async::{scan_ports_async, scan_subnet_async};
+pub use scanner::cancel::CancelToken;
+pub use scanner::options::{ScanOptions, ScanOptionsBuilder};
 /// Re-export commonly used types and functions
-pub use scanner::port::{PortHit, is_resolvable, resolve_host, scan_port};
-pub use utils::{init_scan_pool, parse_ip, parse_ports, parse_subnet, progress_bar};
+pub use scanner::port::{
+    PortScanResult, PortState, PortStream, is_resolvable, probe_ports_iter, resolve_host,
+    scan_port, scan_port_with_timeout, scan_ports_iter,
+};
+pub use scanner::progress::{Progress, ProgressHandle, ProgressHandler};
+pub use scanner::stream::ScanStream;
+pub use scanner::sweep::{HostReport, scan_network, scan_network_with_options};
+pub use scanner::top_ports::{PortSet, common_ports};
+pub use utils::{
+    Target, init_scan_pool, parse_ip, parse_ports, parse_subnet, parse_target, parse_targets_file,
+    resolve_target,
+};
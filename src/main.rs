@@ -1,40 +1,258 @@
+use chrono::{DateTime, SubsecRound, Utc};
+use clap::{CommandFactory, FromArgMatches};
+use std::collections::BTreeMap;
+use std::fs::{self, File};
+use std::io::{self, BufWriter, Write};
+use std::net::{IpAddr, ToSocketAddrs, UdpSocket};
+use std::path::Path;
+use std::sync::Arc;
 use std::time::Duration;
 
-use clap::Parser;
-use owo_colors::OwoColorize;
-use rayon::prelude::*;
+use asphyxia::cli::Args;
+use asphyxia::cli::crash;
+use asphyxia::cli::progress::BarProgress;
+use asphyxia::cli::theme::{self, Paint, Role};
+use asphyxia::output::ack::{Ack, AckList};
+use asphyxia::output::latency::{LatencyRegression, LatencySummary, latency_regressions};
+use asphyxia::output::merge::merge_reports;
+use asphyxia::output::nmap_xml::write_nmap_xml;
+use asphyxia::output::notify;
+use asphyxia::output::timeline::{Snapshot, TimelineFormat, vega_lite, write_dot};
+use asphyxia::output::{
+    FORMAT_VERSION, OutputFormat, ScanRecord, diff, format_timestamp, read_records, sort_records,
+    write_json, write_jsonl,
+};
+use asphyxia::scanner::address::ProbeMethod;
+use asphyxia::scanner::annotations::{Annotation, Annotations};
+use asphyxia::scanner::cancel::CancelToken;
+use asphyxia::scanner::fingerprint::{FingerprintCache, StackFingerprint};
+use asphyxia::scanner::http_probe::HttpInfo;
+use asphyxia::scanner::listen::ListenOptions;
+use asphyxia::scanner::options::ScanOptions;
+use asphyxia::scanner::outage::{Outage, OutageWatch};
+use asphyxia::scanner::ping::PingMethod;
+use asphyxia::scanner::pmtu::PathMtu;
+use asphyxia::scanner::port::{PortScanResult, ScanTechnique, StateCounts};
+use asphyxia::scanner::tls::CertInfo;
+use asphyxia::scanner::top_ports::PortSet;
+use asphyxia::scanner::usage::TargetTraffic;
+use asphyxia::scanner::validate::Verdict;
+use asphyxia::scanner::version::ServiceVersion;
+use asphyxia::scanner::{
+    address, arp, fingerprint, http_probe, icmp, listen, monitor, order, ping, pmtu, port, proxy,
+    replay, router, services, sweep, syn, tls, top_ports, traceroute, ttl, udp_path, usage,
+    validate, version,
+};
+use asphyxia::update;
+use asphyxia::utils::netif::{
+    Interface, default_gateway, is_local_subnet, list_interfaces, primary_network,
+};
+use asphyxia::utils::{
+    Target, init_scan_pool, parse_exclusions, parse_ip, parse_ports, parse_subnet,
+    parse_targets_file, resolve_target,
+};
+#[cfg(feature = "history")]
+use asphyxia::{cli::HistoryCommand, storage};
 
-mod cli;
-mod output;
-mod scanner;
-mod utils;
+/// Shown when a SYN scan is requested without raw-socket privileges.
+const SYN_PRIVILEGES: &str =
+    "SYN scans need raw-socket privileges: run as root or grant CAP_NET_RAW";
 
-use cli::Args;
-use output::{OutputFormat, ScanRecord, print_json, print_jsonl};
-use scanner::{address, port};
-use utils::{init_scan_pool, parse_ip, parse_ports, parse_subnet, progress_bar};
+/// Shown when decoys are requested for probes that cannot carry them.
+const DECOYS_NEED_SYN: &str =
+    "--decoy needs --technique syn: only raw SYN probes can be sent from a spoofed address";
+
+/// Shown when ARP probes are requested where they cannot run.
+const ARP_UNAVAILABLE: &str = "ARP probes need Linux (the neighbor table in /proc/net/arp)";
+
+/// Certificates expiring within this many days are highlighted by `--tls-info`.
+const CERT_EXPIRY_WARNING_DAYS: i64 = 30;
+
+/// Most common ports `sweep` scans on each live host without `-p` or
+/// `--top-ports`; fewer than `ps` does, as every host of a subnet gets them.
+const SWEEP_TOP_PORTS: usize = 100;
 
 fn main() {
-    let args = Args::parse();
+    let matches = Args::command().arg(theme::theme_arg()).get_matches();
+    theme::from_matches(&matches).set();
+    let mut args = Args::from_arg_matches(&matches).unwrap_or_else(|e| e.exit());
+    if let Err(e) = args.apply_config(&matches) {
+        eprintln!("{}", e.paint(Role::Error));
+        return;
+    }
 
     // Size the global rayon pool for I/O-bound scanning before any scan runs.
     init_scan_pool(args.concurrency());
 
     let format = args.output_format();
+    let local_time = args.local_time();
+    let output_file = args.output_file().cloned();
+    let operator = args.operator().map(str::to_string);
+    let note = args.note().map(str::to_string);
+
+    if output_file.is_some() && format == OutputFormat::Text {
+        eprintln!(
+            "{}",
+            "--output-file requires a machine-readable format (-o json, jsonl or nmap-xml)"
+                .paint(Role::Warning)
+        );
+        return;
+    }
+
+    let acks = match args.ack_file() {
+        Some(path) => match AckList::load(path) {
+            Ok(acks) => acks,
+            Err(e) => {
+                eprintln!("{}", e.paint(Role::Error));
+                return;
+            }
+        },
+        None => AckList::default(),
+    };
+
+    let annotations = match args.annotations() {
+        Some(path) => match Annotations::load(path) {
+            Ok(annotations) => Some(Arc::new(annotations)),
+            Err(e) => {
+                eprintln!("{}", e.paint(Role::Error));
+                return;
+            }
+        },
+        None => None,
+    };
+
+    let subnet_rates = args.subnet_rates().to_vec();
+    let (source_ip, interface) = args.probe_origin();
+    let interface = match probe_interface(source_ip, interface) {
+        Ok(interface) => interface,
+        Err(e) => {
+            eprintln!("{}", e.paint(Role::Error));
+            return;
+        }
+    };
+
+    if let Some(dir) = args.crash_dir() {
+        crash::install(dir.clone(), args.command_name(), &args);
+    }
+
+    let audit_file = args.audit_file().cloned();
+    if audit_file.is_some() {
+        usage::start_audit();
+    }
+
+    // Ctrl-C stops the scan and reports what was found so far.
+    let cancel = CancelToken::new();
+    install_interrupt_handler(cancel.clone());
 
     match args {
         Args::PortScan {
+            targets,
             host,
+            input_file,
             range,
             specific,
+            top_ports,
+            scan_template,
+            all_ports,
+            exclude_ports,
+            technique,
+            source_port,
+            decoy,
+            os,
+            http_probe,
+            tls_info,
+            version_detect,
+            timing,
             timeout,
+            rate,
+            retries,
+            randomize,
+            seed,
+            outage_window,
+            dscp,
+            proxy,
+            verbose,
             ..
         } => {
-            let timeout = Some(Duration::from_millis(timeout));
+            if !proxy.is_empty()
+                && let Some(flag) = [
+                    (technique == ScanTechnique::Syn, "--technique syn"),
+                    (source_port.is_some(), "--source-port"),
+                    (source_ip.is_some(), "--source-ip"),
+                    (interface.is_some(), "--interface"),
+                    (os, "--os"),
+                    (http_probe, "--http-probe"),
+                    (tls_info, "--tls-info"),
+                    (version_detect, "--version-detect"),
+                ]
+                .into_iter()
+                .find_map(|(set, flag)| set.then_some(flag))
+            {
+                eprintln!(
+                    "{}",
+                    format!("{} connects directly and cannot be used with --proxy", flag)
+                        .paint(Role::Error)
+                );
+                return;
+            }
+            if !decoy.is_empty() && technique != ScanTechnique::Syn {
+                eprintln!("{}", DECOYS_NEED_SYN.paint(Role::Error));
+                return;
+            }
+            if (technique == ScanTechnique::Syn || os) && !syn::syn_available() {
+                eprintln!("{}", SYN_PRIVILEGES.paint(Role::Error));
+                return;
+            }
+
+            let hosts: Vec<String> = match input_file {
+                Some(path) => match parse_targets_file(&path).and_then(|t| port_scan_hosts(&t)) {
+                    Ok(hosts) => hosts,
+                    Err(e) => {
+                        eprintln!("{}", e.paint(Role::Error));
+                        return;
+                    }
+                },
+                // clap requires -t unless --input-file or targets are given.
+                None if targets.is_empty() => host.into_iter().collect(),
+                None => match port_scan_hosts(&targets) {
+                    Ok(hosts) => hosts,
+                    Err(e) => {
+                        eprintln!("{}", e.paint(Role::Error));
+                        return;
+                    }
+                },
+            };
 
-            // Make sure the host resolves before we try to scan it.
-            if !port::is_resolvable(&host) {
-                eprintln!("{}", format!("Could not resolve host: {}", host).red());
+            // Resolve every host to an IP once, so the parallel scans below do
+            // not issue a DNS lookup for every single port. Hosts that do not
+            // resolve are reported and skipped. A proxy resolves names itself,
+            // and may know hosts that local DNS does not.
+            let mut targets: Vec<(String, String)> = hosts
+                .into_iter()
+                .filter_map(|host| {
+                    if !proxy.is_empty() {
+                        return Some((host.clone(), host));
+                    }
+                    match port::resolve_host(&host) {
+                        Some(ip) => Some((host, ip.to_string())),
+                        None => {
+                            eprintln!(
+                                "{}",
+                                format!("Could not resolve host: {}", host).paint(Role::Error)
+                            );
+                            None
+                        }
+                    }
+                })
+                .collect();
+            if targets.is_empty() {
+                return;
+            }
+            // Every probe would fail the same way; say so once.
+            if !proxy.is_empty()
+                && let Err(e) = proxy::check_chain(&proxy, timeout)
+            {
+                eprintln!("{}", e.paint(Role::Error));
                 return;
             }
 
@@ -43,7 +261,7 @@ fn main() {
                 let start = range[0];
                 let end = range[1];
                 if start > end {
-                    eprintln!("{}", "Start port must be <= end port".yellow());
+                    eprintln!("{}", "Start port must be <= end port".paint(Role::Warning));
                     return;
                 }
                 (start..=end).collect()
@@ -51,130 +269,476 @@ fn main() {
                 match parse_ports(&spec) {
                     Ok(ports) => ports,
                     Err(e) => {
-                        eprintln!("{}", e.red());
+                        eprintln!("{}", e.paint(Role::Error));
                         return;
                     }
                 }
+            } else if let Some(n) = top_ports {
+                top_ports::common_ports(n)
+            } else if let Some(template) = scan_template {
+                template.ports
+            } else if all_ports {
+                PortSet::All.ports()
             } else {
-                eprintln!("{}", "Please specify either -r or -s".yellow());
-                return;
+                PortSet::Default.ports()
             };
 
-            // Resolve the host to an IP once, so the parallel scan below does
-            // not issue a DNS lookup for every single port.
-            let scan_host = match port::resolve_host(&host) {
-                Some(ip) => ip.to_string(),
-                None => {
-                    eprintln!("{}", format!("Could not resolve host: {}", host).red());
+            let exclude_ports = match exclude_ports.as_deref().map(parse_ports) {
+                Some(Ok(ports)) => ports,
+                Some(Err(e)) => {
+                    eprintln!("{}", e.paint(Role::Error));
                     return;
                 }
+                None => Vec::new(),
             };
 
-            let total_ports = ports.len();
+            // SYN scans fill the cache as they go; --os only probes hosts
+            // it has no fingerprint for.
+            let fingerprints = os.then(|| Arc::new(FingerprintCache::new()));
+            let bar = Arc::new(BarProgress::new());
+            let outages = outage_watch(outage_window);
+            let defaults = ScanOptions::from_timing_template(timing.unwrap_or_default());
+            let options = ScanOptions {
+                timeout: timeout.or(defaults.timeout),
+                technique,
+                source_port,
+                decoys: decoy.into_iter().map(IpAddr::V4).collect(),
+                exclude_ports,
+                rate_limit: rate,
+                subnet_rates: subnet_rates.clone(),
+                retries: retries.unwrap_or(defaults.retries),
+                shuffle_seed: randomize.then(|| seed.unwrap_or_else(order::random_seed)),
+                outages: outages.clone(),
+                annotations: annotations.clone(),
+                source_ip,
+                interface: interface.clone(),
+                dscp,
+                proxies: proxy.clone(),
+                fingerprints: fingerprints.clone(),
+                progress: Some(bar.clone()),
+                cancel: Some(cancel.clone()),
+                ..defaults
+            };
 
+            if let Some(seed) = options.shuffle_seed {
+                order::shuffle(&mut targets, seed);
+            }
             if format == OutputFormat::Text {
-                println!(
-                    "\n##### {} scanning ports on host: {} #####\n",
-                    "Started".bright_blue(),
-                    host.bright_green()
-                );
+                print_shuffle_seed(options.shuffle_seed);
             }
 
-            let pb = progress_bar(total_ports as u64, "ports scanned");
+            let probe_timeout = options.timeout.unwrap_or(port::CONNECT_TIMEOUT);
 
-            let mut opened: Vec<port::PortHit> = ports
-                .into_par_iter()
-                .filter_map(|port| {
-                    let open_port = port::scan_port(scan_host.clone(), port, timeout);
-                    pb.inc(1);
-                    open_port
-                })
-                .collect();
+            // Acks may be keyed by the resolved IP or by the host as typed.
+            let port_ack = |host: &str, scan_host: &str, port: u16| {
+                acks.find(scan_host, Some(port))
+                    .or_else(|| acks.find(host, Some(port)))
+            };
 
-            pb.finish_with_message("Scan completed");
+            let mut scanned = Vec::new();
+            for (host, scan_host) in &targets {
+                if cancel.is_cancelled() {
+                    break;
+                }
+                crash::phase("port-scan");
+                crash::event(format!("{}: scanning {} ports", host, ports.len()));
+                let annotation = port::resolve_host(scan_host)
+                    .and_then(|ip| host_annotation(annotations.as_deref(), ip));
+                if annotation.is_some_and(|annotation| annotation.skip) {
+                    eprintln!(
+                        "{}",
+                        format!(
+                            "Skipping {}: the annotations file says to leave it alone",
+                            host
+                        )
+                        .paint(Role::Warning)
+                    );
+                    continue;
+                }
+                if format == OutputFormat::Text {
+                    println!(
+                        "\n##### {} scanning ports on host: {}{} #####\n",
+                        "Started".paint(Role::Heading),
+                        host.paint(Role::Target),
+                        annotation_note(annotation)
+                    );
+                }
 
-            opened.sort_by_key(|hit| hit.port);
+                let stream = port::probe_ports_iter(scan_host, ports.clone(), options.clone());
+                let mut probed = Vec::new();
+                for result in stream {
+                    // Report open ports as they turn up, so a long scan is
+                    // not a silent bar; the sorted list follows at the end.
+                    if format == OutputFormat::Text && result.is_open() {
+                        bar.println(&format!(
+                            "{} {}:{}{}",
+                            "Discovered open port".paint(Role::Open),
+                            host.paint(Role::Address),
+                            format!("{}/tcp", result.port).paint(Role::Port),
+                            service_note(services::service_name_for_port(result.port, "tcp"))
+                        ));
+                    }
+                    probed.push(result);
+                }
+                probed.sort_by_key(|result| result.port);
+                let counts = StateCounts::of(&probed);
+                // -vv keeps closed and filtered ports too, to show their evidence.
+                if verbose < 2 {
+                    probed.retain(PortScanResult::is_open);
+                }
+                crash::phase("service-probes");
+                let open: Vec<u16> = probed
+                    .iter()
+                    .filter(|result| result.is_open())
+                    .map(|result| result.port)
+                    .collect();
+                let http = if http_probe {
+                    http_probe::probe_web_ports(host, &open, probe_timeout)
+                } else {
+                    BTreeMap::new()
+                };
+                let certs = if tls_info {
+                    tls::probe_tls_ports(host, &open, probe_timeout)
+                } else {
+                    BTreeMap::new()
+                };
+                let versions = match port::resolve_host(scan_host) {
+                    Some(ip) if version_detect => {
+                        version::detect_versions(ip, &open, probe_timeout)
+                    }
+                    _ => BTreeMap::new(),
+                };
 
-            match format {
-                OutputFormat::Text => {
+                if format == OutputFormat::Text {
+                    let opened: Vec<&PortScanResult> =
+                        probed.iter().filter(|result| result.is_open()).collect();
                     if !opened.is_empty() {
                         println!(
-                            "\n-- {} for {} --\n",
-                            "Opened ports".green(),
-                            host.bright_yellow()
+                            "\n-- {} for {}{} --\n",
+                            "Opened ports".paint(Role::Section),
+                            host.paint(Role::Subject),
+                            hops_note(opened.iter().find_map(|result| result.ttl))
                         );
-                        for hit in &opened {
+                        for result in &opened {
                             println!(
-                                "{}:{}",
-                                host.bright_cyan(),
-                                hit.port.to_string().bright_green()
+                                "{}:{}{}{}{}{}{}{}",
+                                host.paint(Role::Address),
+                                format!("{}/tcp", result.port).paint(Role::Port),
+                                service_note(port_service(result.port, versions.get(&result.port))),
+                                version_note(versions.get(&result.port)),
+                                http_note(http.get(&result.port)),
+                                tls_note(certs.get(&result.port)),
+                                evidence_note(verbose, result),
+                                ack_note(port_ack(host, scan_host, result.port))
                             );
                         }
                     } else {
-                        println!("\n{}", "No open ports found 😕".yellow());
+                        println!("\n{}", "No open ports found 😕".paint(Role::Warning));
+                    }
+                    print_state_counts(counts);
+                    if verbose >= 2 {
+                        print_port_evidence(host, &probed);
                     }
-
-                    println!("\n##### {} #####\n", "Game Over".bright_red());
                 }
-                OutputFormat::Json | OutputFormat::Jsonl => {
-                    let records: Vec<ScanRecord> = opened
-                        .iter()
-                        .map(|hit| ScanRecord {
-                            ip: scan_host.clone(),
-                            port: Some(hit.port),
-                            proto: "tcp",
-                            latency_ms: hit.latency.as_millis(),
-                            status: "open",
+                let fingerprint = fingerprints
+                    .as_deref()
+                    .and_then(|cache| host_fingerprint(cache, scan_host, &probed, &options));
+                if os && format == OutputFormat::Text {
+                    print_os_guess(fingerprint);
+                }
+                crash::event(format!("{}: {} open", host, open.len()));
+                scanned.push((host, scan_host, probed, fingerprint, http, certs, versions));
+            }
+            crash::phase("report");
+
+            warn_if_interrupted(&cancel);
+            let completed = Utc::now().trunc_subsecs(0);
+            let traffic = save_audit(audit_file.as_deref());
+
+            let timestamp = Some(completed);
+            let (operator, note) = (&operator, &note);
+            let mut records: Vec<ScanRecord> = scanned
+                .iter()
+                .flat_map(
+                    |(host, scan_host, probed, fingerprint, http, certs, versions)| {
+                        let os_guess = fingerprint
+                            .and_then(|fingerprint| fingerprint.os_guess())
+                            .map(str::to_string);
+                        let annotation = port::resolve_host(scan_host)
+                            .and_then(|ip| host_annotation(annotations.as_deref(), ip));
+                        probed.iter().map(move |result| {
+                            let http = http.get(&result.port);
+                            let cert = certs.get(&result.port);
+                            let detected = versions.get(&result.port);
+                            ScanRecord {
+                                format_version: FORMAT_VERSION,
+                                ip: scan_host.to_string(),
+                                hostname: None,
+                                mac: None,
+                                port: Some(result.port),
+                                proto: "tcp".to_string(),
+                                service: port_service(result.port, detected).map(str::to_string),
+                                latency_ms: result.latency.as_millis(),
+                                status: result.state.as_str().to_string(),
+                                timestamp,
+                                ack: port_ack(host, scan_host, result.port).cloned(),
+                                hops: None,
+                                path_mtu: None,
+                                ttl: result.ttl,
+                                hop_estimate: result.ttl.map(ttl::estimate_hops),
+                                evidence: Some(result.evidence.as_str().to_string()),
+                                attempts: Some(result.attempts),
+                                os_guess: os_guess.clone(),
+                                product: detected.and_then(|found| found.product.clone()),
+                                version: detected.and_then(|found| found.version.clone()),
+                                http_status: http.map(|info| info.status),
+                                http_server: http.and_then(|info| info.server.clone()),
+                                http_title: http.and_then(|info| info.title.clone()),
+                                tls_subject: cert.map(|cert| cert.subject.clone()),
+                                tls_issuer: cert.map(|cert| cert.issuer.clone()),
+                                tls_sans: cert.map(|cert| cert.sans.clone()),
+                                tls_expires: cert.map(|cert| cert.not_after),
+                                operator: operator.clone(),
+                                note: note.clone(),
+                                outages: None,
+                                annotation: annotation.cloned(),
+                            }
                         })
-                        .collect();
-                    if format == OutputFormat::Json {
-                        print_json(&records);
-                    } else {
-                        print_jsonl(&records);
-                    }
+                    },
+                )
+                .collect();
+            let outages = mark_outages(outages.as_deref(), &mut records);
+
+            match format {
+                OutputFormat::Text => {
+                    print_latency_summary(scanned.iter().flat_map(|(_, _, probed, _, _, _, _)| {
+                        probed
+                            .iter()
+                            .filter(|result| result.is_open())
+                            .map(|result| result.latency)
+                    }));
+                    print_outages(&outages, local_time);
+                    print_audit(&traffic);
+                    print_resource_usage();
+                    println!(
+                        "\n{} {}",
+                        "Completed at".paint(Role::Muted),
+                        format_timestamp(completed, local_time)
+                    );
+                    print_engagement(operator.as_deref(), note.as_deref());
+                    println!("\n##### {} #####\n", "Game Over".paint(Role::Banner));
+                }
+                OutputFormat::Json | OutputFormat::Jsonl | OutputFormat::NmapXml => {
+                    emit_records(format, output_file.as_deref(), &records);
                 }
             }
+            record_history("ps", completed, &records);
         }
         Args::AddressScan {
+            targets,
             subnet,
             target,
             range,
+            input_file,
+            local,
+            probe,
+            discovery_ports,
+            timing,
             timeout,
+            rate,
+            retries,
+            randomize,
+            seed,
+            outage_window,
+            dscp,
+            resolve,
+            arp_cache,
+            trace_discovered,
+            pmtu,
+            exclude,
+            max_hosts,
+            i_know_what_im_doing,
             ..
         } => {
-            let timeout = Some(Duration::from_millis(timeout));
+            let subnet = if local {
+                let Some((interface, network)) = primary_network() else {
+                    eprintln!(
+                        "{}",
+                        "No interface with an IPv4 address to take the local subnet from"
+                            .paint(Role::Error)
+                    );
+                    return;
+                };
+                if format == OutputFormat::Text {
+                    println!(
+                        "{}",
+                        format!("Local subnet of {}: {}", interface.name, network)
+                            .paint(Role::Muted)
+                    );
+                }
+                Some(network.to_string())
+            } else {
+                subnet
+            };
+            let probe = choose_probe(probe, subnet.as_deref());
+            if probe == ProbeMethod::Arp && !arp::arp_available() {
+                eprintln!("{}", ARP_UNAVAILABLE.paint(Role::Error));
+                return;
+            }
+            let probe = icmp_fallback(probe);
+            if (trace_discovered || pmtu) && !icmp::icmp_available() {
+                eprintln!(
+                    "{}",
+                    "Tracing and path MTU discovery need raw-socket privileges: run as root or grant CAP_NET_RAW"
+                        .paint(Role::Error)
+                );
+                return;
+            }
+
+            let discovery_ports = match parse_ports(&discovery_ports) {
+                Ok(ports) => ports,
+                Err(e) => {
+                    eprintln!("{}", e.paint(Role::Error));
+                    return;
+                }
+            };
+
+            let exclude_hosts = match exclude.as_deref().map(parse_exclusions) {
+                Some(Ok(hosts)) => hosts,
+                Some(Err(e)) => {
+                    eprintln!("{}", e.paint(Role::Error));
+                    return;
+                }
+                None => Vec::new(),
+            };
+
+            let outages = outage_watch(outage_window);
+            let defaults = ScanOptions::from_timing_template(timing.unwrap_or_default());
+            let options = ScanOptions {
+                timeout: timeout.or(defaults.timeout),
+                probe,
+                discovery_ports,
+                rate_limit: rate,
+                subnet_rates: subnet_rates.clone(),
+                retries: retries.unwrap_or(defaults.retries),
+                shuffle_seed: randomize.then(|| seed.unwrap_or_else(order::random_seed)),
+                outages: outages.clone(),
+                annotations: annotations.clone(),
+                source_ip,
+                interface: interface.clone(),
+                dscp,
+                resolve_hostnames: resolve,
+                neighbor_cache: arp_cache,
+                exclude_hosts,
+                max_hosts: host_limit(max_hosts, i_know_what_im_doing),
+                progress: Some(Arc::new(BarProgress::new())),
+                cancel: Some(cancel.clone()),
+                ..defaults
+            };
+
+            let host_record = |result: &address::HostScanResult,
+                               timestamp: DateTime<Utc>,
+                               hops: Option<Vec<Option<String>>>,
+                               path_mtu: Option<u16>| ScanRecord {
+                format_version: FORMAT_VERSION,
+                ip: result.ip.to_string(),
+                hostname: result.hostname.clone(),
+                mac: result.mac.clone(),
+                port: None,
+                proto: probe.proto().to_string(),
+                service: None,
+                latency_ms: result.rtt.as_millis(),
+                status: result.state.as_str().to_string(),
+                timestamp: Some(timestamp),
+                ack: acks.find(&result.ip.to_string(), None).cloned(),
+                hops,
+                path_mtu,
+                ttl: result.ttl,
+                hop_estimate: result.ttl.map(ttl::estimate_hops),
+                evidence: None,
+                attempts: None,
+                os_guess: None,
+                product: None,
+                version: None,
+                http_status: None,
+                http_server: None,
+                http_title: None,
+                tls_subject: None,
+                tls_issuer: None,
+                tls_sans: None,
+                tls_expires: None,
+                operator: operator.clone(),
+                note: note.clone(),
+                outages: None,
+                annotation: host_annotation(annotations.as_deref(), result.ip).cloned(),
+            };
+            // JSON Lines needs nothing from the finished scan, so subnet and
+            // range scans write each live host as it answers, unless routes
+            // or MTUs have to be added to the records first.
+            let stream_jsonl = format == OutputFormat::Jsonl && !trace_discovered && !pmtu;
+            let streamed = stream_jsonl && (subnet.is_some() || range.is_some());
+
+            if format == OutputFormat::Text {
+                print_shuffle_seed(options.shuffle_seed);
+            }
 
-            let available: Vec<address::HostHit> = if let Some(subnet_str) = subnet {
-                match parse_subnet(&subnet_str) {
+            crash::phase("discovery");
+            let available: Vec<address::HostScanResult> = if !targets.is_empty() {
+                if format == OutputFormat::Text {
+                    let names: Vec<String> = targets.iter().map(ToString::to_string).collect();
+                    println!(
+                        "\n##### {} scanning: {} #####\n",
+                        "Started".paint(Role::Heading),
+                        names.join(" ").paint(Role::Target)
+                    );
+                }
+                scan_targets(&targets, &options)
+            } else if let Some(subnet_str) = subnet {
+                match parse_subnet(&subnet_str).and_then(|network| {
+                    options.check_host_count(address::subnet_size(network))?;
+                    Ok(network)
+                }) {
                     Ok(network) => {
                         if format == OutputFormat::Text {
                             println!(
                                 "\n##### {} scanning subnet: {} #####\n",
-                                "Started".bright_blue(),
-                                subnet_str.as_str().bright_green()
+                                "Started".paint(Role::Heading),
+                                subnet_str.as_str().paint(Role::Target)
                             );
                         }
-                        address::scan_subnet(network, timeout)
+                        if stream_jsonl {
+                            let hosts = address::scan_subnet_iter(network, options.clone());
+                            stream_host_records(hosts, output_file.as_deref(), |host, at| {
+                                host_record(host, at, None, None)
+                            })
+                        } else {
+                            address::scan_subnet_with_options(network, &options)
+                        }
                     }
                     Err(e) => {
-                        eprintln!("{}", e.red());
+                        eprintln!("{}", e.paint(Role::Error));
                         return;
                     }
                 }
             } else if let Some(target_str) = target {
-                match parse_ip(&target_str) {
-                    Ok(ip) => {
+                match resolve_target(&target_str) {
+                    Ok(ips) => {
                         if format == OutputFormat::Text {
                             println!(
                                 "\n##### {} scanning target: {} #####\n",
-                                "Started".bright_blue(),
-                                target_str.as_str().bright_green()
+                                "Started".paint(Role::Heading),
+                                target_str.as_str().paint(Role::Target)
                             );
                         }
-                        address::scan_address(ip, timeout).into_iter().collect()
+                        // A hostname may resolve to several addresses; scan them all.
+                        ips.into_iter()
+                            .filter(|&ip| !options.is_excluded(ip))
+                            .map(|ip| address::scan_address_with_options(ip, &options))
+                            .filter(|result| result.is_up())
+                            .collect()
                     }
                     Err(e) => {
-                        eprintln!("{}", e.red());
+                        eprintln!("{}", e.paint(Role::Error));
                         return;
                     }
                 }
@@ -182,57 +746,2293 @@ fn main() {
                 // clap enforces exactly two values via `num_args = 2`.
                 match (parse_ip(&range_vec[0]), parse_ip(&range_vec[1])) {
                     (Ok(start), Ok(end)) => {
+                        let count = address::range_size(start, end).unwrap_or(0);
+                        if let Err(e) = options.check_host_count(count) {
+                            eprintln!("{}", e.paint(Role::Error));
+                            return;
+                        }
                         if format == OutputFormat::Text {
                             println!(
                                 "\n##### {} scanning range: {} - {} #####\n",
-                                "Started".bright_blue(),
-                                range_vec[0].as_str().bright_green(),
-                                range_vec[1].as_str().bright_green()
+                                "Started".paint(Role::Heading),
+                                range_vec[0].as_str().paint(Role::Target),
+                                range_vec[1].as_str().paint(Role::Target)
                             );
                         }
-                        address::scan_ip_range(start, end, timeout)
+                        if stream_jsonl {
+                            let hosts = address::scan_ip_range_iter(start, end, options.clone());
+                            stream_host_records(hosts, output_file.as_deref(), |host, at| {
+                                host_record(host, at, None, None)
+                            })
+                        } else {
+                            address::scan_ip_range_with_options(start, end, &options)
+                        }
                     }
                     (Err(e), _) | (_, Err(e)) => {
-                        eprintln!("{}", e.red());
+                        eprintln!("{}", e.paint(Role::Error));
+                        return;
+                    }
+                }
+            } else if let Some(path) = input_file {
+                match parse_targets_file(&path) {
+                    Ok(targets) => {
+                        if format == OutputFormat::Text {
+                            println!(
+                                "\n##### {} scanning targets from: {} #####\n",
+                                "Started".paint(Role::Heading),
+                                path.display().paint(Role::Target)
+                            );
+                        }
+                        scan_targets(&targets, &options)
+                    }
+                    Err(e) => {
+                        eprintln!("{}", e.paint(Role::Error));
                         return;
                     }
                 }
             } else {
-                eprintln!("{}", "Please specify either -s, -t, or -r".yellow());
+                eprintln!(
+                    "{}",
+                    "Please specify either -s, -t, or -r (or --input-file or --local)"
+                        .paint(Role::Warning)
+                );
                 return;
             };
 
+            crash::event(format!("{} hosts up", available.len()));
+            let live: Vec<IpAddr> = available.iter().map(|result| result.ip).collect();
+            let routes = trace_routes(trace_discovered, &live, &options, &cancel);
+            let mtus = discover_mtus(pmtu, &live, &options, &cancel);
+            crash::phase("report");
+
+            warn_if_interrupted(&cancel);
+            let completed = Utc::now().trunc_subsecs(0);
+            let traffic = save_audit(audit_file.as_deref());
+
+            let mut records: Vec<ScanRecord> = available
+                .iter()
+                .map(|result| {
+                    host_record(
+                        result,
+                        completed,
+                        record_hops(&routes, result.ip),
+                        mtus.get(&result.ip).map(|path| path.mtu),
+                    )
+                })
+                .collect();
+            let outages = mark_outages(outages.as_deref(), &mut records);
+
             match format {
                 OutputFormat::Text => {
                     if !available.is_empty() {
-                        println!("\n-- {} --\n", "Available hosts".green());
-                        for hit in &available {
-                            println!("{}", hit.ip.to_string().bright_green());
+                        println!("\n-- {} --\n", "Available hosts".paint(Role::Section));
+                        for result in &available {
+                            let ip = result.ip.to_string();
+                            println!(
+                                "{}{}{}{}{}{}",
+                                ip.paint(Role::Target),
+                                hostname_note(result.hostname.as_deref()),
+                                mac_note(result.mac.as_deref()),
+                                hops_note(result.ttl),
+                                annotation_note(host_annotation(annotations.as_deref(), result.ip)),
+                                ack_note(acks.find(&ip, None))
+                            );
                         }
+                        print_routes(&routes);
+                        print_mtus(&mtus);
+                        print_latency_summary(available.iter().map(|result| result.rtt));
                     } else {
-                        println!("\n{}", "No available hosts found 😕".yellow());
+                        println!("\n{}", "No available hosts found 😕".paint(Role::Warning));
                     }
 
-                    println!("\n##### {} #####\n", "Game Over".bright_red());
+                    print_outages(&outages, local_time);
+                    print_audit(&traffic);
+                    print_resource_usage();
+                    println!(
+                        "\n{} {}",
+                        "Completed at".paint(Role::Muted),
+                        format_timestamp(completed, local_time)
+                    );
+                    print_engagement(operator.as_deref(), note.as_deref());
+                    println!("\n##### {} #####\n", "Game Over".paint(Role::Banner));
                 }
-                OutputFormat::Json | OutputFormat::Jsonl => {
-                    let records: Vec<ScanRecord> = available
-                        .iter()
-                        .map(|hit| ScanRecord {
-                            ip: hit.ip.to_string(),
-                            port: None,
-                            proto: "tcp",
-                            latency_ms: hit.latency.as_millis(),
-                            status: "up",
-                        })
-                        .collect();
-                    if format == OutputFormat::Json {
-                        print_json(&records);
+                OutputFormat::Jsonl if streamed => {}
+                OutputFormat::Json | OutputFormat::Jsonl | OutputFormat::NmapXml => {
+                    emit_records(format, output_file.as_deref(), &records);
+                }
+            }
+            record_history("as", completed, &records);
+        }
+        Args::Sweep {
+            subnet,
+            ports,
+            top_ports,
+            probe,
+            technique,
+            source_port,
+            decoy,
+            discovery_ports,
+            timing,
+            timeout,
+            rate,
+            retries,
+            randomize,
+            seed,
+            outage_window,
+            dscp,
+            resolve,
+            arp_cache,
+            trace_discovered,
+            http_probe,
+            tls_info,
+            pmtu,
+            exclude,
+            max_hosts,
+            i_know_what_im_doing,
+            ..
+        } => {
+            let probe = choose_probe(probe, Some(&subnet));
+            if probe == ProbeMethod::Arp && !arp::arp_available() {
+                eprintln!("{}", ARP_UNAVAILABLE.paint(Role::Error));
+                return;
+            }
+            let probe = icmp_fallback(probe);
+            if (trace_discovered || pmtu) && !icmp::icmp_available() {
+                eprintln!(
+                    "{}",
+                    "Tracing and path MTU discovery need raw-socket privileges: run as root or grant CAP_NET_RAW"
+                        .paint(Role::Error)
+                );
+                return;
+            }
+            if !decoy.is_empty() && technique != ScanTechnique::Syn {
+                eprintln!("{}", DECOYS_NEED_SYN.paint(Role::Error));
+                return;
+            }
+            if technique == ScanTechnique::Syn && !syn::syn_available() {
+                eprintln!("{}", SYN_PRIVILEGES.paint(Role::Error));
+                return;
+            }
+
+            let network = match parse_subnet(&subnet) {
+                Ok(network) => network,
+                Err(e) => {
+                    eprintln!("{}", e.paint(Role::Error));
+                    return;
+                }
+            };
+
+            let ports = match ports {
+                Some(spec) => match parse_ports(&spec) {
+                    Ok(ports) => ports,
+                    Err(e) => {
+                        eprintln!("{}", e.paint(Role::Error));
+                        return;
+                    }
+                },
+                None => top_ports::common_ports(top_ports.unwrap_or(SWEEP_TOP_PORTS)),
+            };
+
+            let discovery_ports = match parse_ports(&discovery_ports) {
+                Ok(ports) => ports,
+                Err(e) => {
+                    eprintln!("{}", e.paint(Role::Error));
+                    return;
+                }
+            };
+
+            let exclude_hosts = match exclude.as_deref().map(parse_exclusions) {
+                Some(Ok(hosts)) => hosts,
+                Some(Err(e)) => {
+                    eprintln!("{}", e.paint(Role::Error));
+                    return;
+                }
+                None => Vec::new(),
+            };
+
+            let outages = outage_watch(outage_window);
+            let defaults = ScanOptions::from_timing_template(timing.unwrap_or_default());
+            let options = ScanOptions {
+                timeout: timeout.or(defaults.timeout),
+                probe,
+                technique,
+                source_port,
+                decoys: decoy.into_iter().map(IpAddr::V4).collect(),
+                discovery_ports,
+                rate_limit: rate,
+                subnet_rates: subnet_rates.clone(),
+                retries: retries.unwrap_or(defaults.retries),
+                shuffle_seed: randomize.then(|| seed.unwrap_or_else(order::random_seed)),
+                outages: outages.clone(),
+                annotations: annotations.clone(),
+                source_ip,
+                interface: interface.clone(),
+                dscp,
+                resolve_hostnames: resolve,
+                neighbor_cache: arp_cache,
+                exclude_hosts,
+                max_hosts: host_limit(max_hosts, i_know_what_im_doing),
+                progress: Some(Arc::new(BarProgress::new())),
+                cancel: Some(cancel.clone()),
+                ..defaults
+            };
+
+            if format == OutputFormat::Text {
+                print_shuffle_seed(options.shuffle_seed);
+                println!(
+                    "\n##### {} sweeping subnet: {} #####\n",
+                    "Started".paint(Role::Heading),
+                    subnet.as_str().paint(Role::Target)
+                );
+            }
+
+            if let Err(e) = options.check_host_count(address::subnet_size(network)) {
+                eprintln!("{}", e.paint(Role::Error));
+                return;
+            }
+
+            crash::phase("sweep");
+            let reports = sweep::scan_network_with_options(network, &ports, &options);
+            crash::event(format!("{} live hosts", reports.len()));
+            let live: Vec<IpAddr> = reports.iter().map(|report| report.host.ip).collect();
+            let routes = trace_routes(trace_discovered, &live, &options, &cancel);
+            let mtus = discover_mtus(pmtu, &live, &options, &cancel);
+            let http = probe_open_services(
+                http_probe,
+                &reports,
+                &options,
+                &cancel,
+                http_probe::probe_web_ports,
+            );
+            let certs =
+                probe_open_services(tls_info, &reports, &options, &cancel, tls::probe_tls_ports);
+            crash::phase("report");
+
+            warn_if_interrupted(&cancel);
+            let completed = Utc::now().trunc_subsecs(0);
+            let traffic = save_audit(audit_file.as_deref());
+
+            let timestamp = Some(completed);
+            let mut records = Vec::new();
+            for report in &reports {
+                let ip = report.host.ip.to_string();
+                records.push(ScanRecord {
+                    format_version: FORMAT_VERSION,
+                    ip: ip.clone(),
+                    hostname: report.host.hostname.clone(),
+                    mac: report.host.mac.clone(),
+                    port: None,
+                    proto: probe.proto().to_string(),
+                    service: None,
+                    latency_ms: report.host.rtt.as_millis(),
+                    status: report.host.state.as_str().to_string(),
+                    timestamp,
+                    ack: acks.find(&ip, None).cloned(),
+                    hops: record_hops(&routes, report.host.ip),
+                    path_mtu: mtus.get(&report.host.ip).map(|path| path.mtu),
+                    ttl: host_ttl(report),
+                    hop_estimate: host_ttl(report).map(ttl::estimate_hops),
+                    evidence: None,
+                    attempts: None,
+                    os_guess: None,
+                    product: None,
+                    version: None,
+                    http_status: None,
+                    http_server: None,
+                    http_title: None,
+                    tls_subject: None,
+                    tls_issuer: None,
+                    tls_sans: None,
+                    tls_expires: None,
+                    operator: operator.clone(),
+                    note: note.clone(),
+                    outages: None,
+                    annotation: host_annotation(annotations.as_deref(), report.host.ip).cloned(),
+                });
+                records.extend(report.open_ports.iter().map(|result| {
+                    let http = http
+                        .get(&report.host.ip)
+                        .and_then(|ports| ports.get(&result.port));
+                    let cert = certs
+                        .get(&report.host.ip)
+                        .and_then(|ports| ports.get(&result.port));
+                    ScanRecord {
+                        format_version: FORMAT_VERSION,
+                        ip: ip.clone(),
+                        hostname: report.host.hostname.clone(),
+                        mac: None,
+                        port: Some(result.port),
+                        proto: "tcp".to_string(),
+                        service: services::service_name_for_port(result.port, "tcp")
+                            .map(str::to_string),
+                        latency_ms: result.latency.as_millis(),
+                        status: result.state.as_str().to_string(),
+                        timestamp,
+                        ack: acks.find(&ip, Some(result.port)).cloned(),
+                        hops: None,
+                        path_mtu: None,
+                        ttl: result.ttl,
+                        hop_estimate: result.ttl.map(ttl::estimate_hops),
+                        evidence: Some(result.evidence.as_str().to_string()),
+                        attempts: Some(result.attempts),
+                        os_guess: None,
+                        product: None,
+                        version: None,
+                        http_status: http.map(|info| info.status),
+                        http_server: http.and_then(|info| info.server.clone()),
+                        http_title: http.and_then(|info| info.title.clone()),
+                        tls_subject: cert.map(|cert| cert.subject.clone()),
+                        tls_issuer: cert.map(|cert| cert.issuer.clone()),
+                        tls_sans: cert.map(|cert| cert.sans.clone()),
+                        tls_expires: cert.map(|cert| cert.not_after),
+                        operator: operator.clone(),
+                        note: note.clone(),
+                        outages: None,
+                        annotation: host_annotation(annotations.as_deref(), report.host.ip)
+                            .cloned(),
+                    }
+                }));
+            }
+            let outages = mark_outages(outages.as_deref(), &mut records);
+
+            match format {
+                OutputFormat::Text => {
+                    if !reports.is_empty() {
+                        println!("\n-- {} --\n", "Sweep results".paint(Role::Section));
+                        for report in &reports {
+                            let ip = report.host.ip.to_string();
+                            println!(
+                                "{}{}{}{}{}{}",
+                                ip.paint(Role::Target),
+                                hostname_note(report.host.hostname.as_deref()),
+                                mac_note(report.host.mac.as_deref()),
+                                hops_note(host_ttl(report)),
+                                annotation_note(host_annotation(
+                                    annotations.as_deref(),
+                                    report.host.ip
+                                )),
+                                ack_note(acks.find(&ip, None))
+                            );
+                            for result in &report.open_ports {
+                                println!(
+                                    "  {}{}{}{}{}",
+                                    format!("{}/tcp", result.port).paint(Role::Port),
+                                    service_note(services::service_name_for_port(
+                                        result.port,
+                                        "tcp"
+                                    )),
+                                    http_note(
+                                        http.get(&report.host.ip)
+                                            .and_then(|ports| ports.get(&result.port))
+                                    ),
+                                    tls_note(
+                                        certs
+                                            .get(&report.host.ip)
+                                            .and_then(|ports| ports.get(&result.port))
+                                    ),
+                                    ack_note(acks.find(&ip, Some(result.port)))
+                                );
+                            }
+                        }
+                        print_routes(&routes);
+                        print_mtus(&mtus);
+                        print_latency_summary(reports.iter().map(|report| report.host.rtt));
                     } else {
-                        print_jsonl(&records);
+                        println!("\n{}", "No available hosts found 😕".paint(Role::Warning));
+                    }
+
+                    print_outages(&outages, local_time);
+                    print_audit(&traffic);
+                    print_resource_usage();
+                    println!(
+                        "\n{} {}",
+                        "Completed at".paint(Role::Muted),
+                        format_timestamp(completed, local_time)
+                    );
+                    print_engagement(operator.as_deref(), note.as_deref());
+                    println!("\n##### {} #####\n", "Game Over".paint(Role::Banner));
+                }
+                OutputFormat::Json | OutputFormat::Jsonl | OutputFormat::NmapXml => {
+                    emit_records(format, output_file.as_deref(), &records);
+                }
+            }
+            record_history("sweep", completed, &records);
+        }
+        Args::RouterInfo { gateway, timeout } => {
+            let Some(gateway) = gateway.or_else(default_gateway) else {
+                eprintln!(
+                    "{}",
+                    "Could not determine the default gateway; pass --gateway <IP>"
+                        .paint(Role::Warning)
+                );
+                return;
+            };
+
+            println!(
+                "\n##### {} router: {} #####\n",
+                "Checking".paint(Role::Heading),
+                gateway.paint(Role::Target)
+            );
+
+            let options = ScanOptions {
+                timeout: Some(timeout),
+                progress: Some(Arc::new(BarProgress::new())),
+                cancel: Some(cancel.clone()),
+                ..ScanOptions::default()
+            };
+            let report = router::probe_router(gateway, &options);
+            warn_if_interrupted(&cancel);
+
+            let udp_services = [
+                (router::SNMP_PORT, "snmp", &report.snmp),
+                (router::SSDP_PORT, "ssdp", &report.ssdp),
+            ];
+            let udp_answered = udp_services.iter().any(|(_, _, answer)| answer.is_some());
+            if !report.services.is_empty() || udp_answered {
+                println!("\n-- {} --\n", "Management services".paint(Role::Section));
+                for service in &report.services {
+                    let banner = service
+                        .banner
+                        .as_deref()
+                        .map(|b| format!("  {}", b.paint(Role::Muted)))
+                        .unwrap_or_default();
+                    println!(
+                        "{}:{} {}{}",
+                        gateway.paint(Role::Address),
+                        format!("{}/tcp", service.port).paint(Role::Port),
+                        service.name.paint(Role::Name),
+                        banner
+                    );
+                }
+                for (port, name, answer) in udp_services {
+                    if let Some(answer) = answer {
+                        println!(
+                            "{}:{} {}  {}",
+                            gateway.paint(Role::Address),
+                            format!("{}/udp", port).paint(Role::Port),
+                            name.paint(Role::Name),
+                            answer.paint(Role::Muted)
+                        );
+                    }
+                }
+            }
+
+            println!("\n-- {} --\n", "Findings".paint(Role::Section));
+            for finding in report.findings() {
+                println!("- {}", finding);
+            }
+            println!("\n##### {} #####\n", "Game Over".paint(Role::Banner));
+        }
+        Args::View {
+            files,
+            timeline,
+            latency_threshold,
+            status,
+            sort,
+            ..
+        } => {
+            let mut reports = Vec::new();
+            for file in &files {
+                match load_report(file) {
+                    Ok(records) => reports.push((file, records)),
+                    Err(e) => {
+                        eprintln!("{}", e.paint(Role::Error));
+                        return;
+                    }
+                }
+            }
+
+            if let Some(timeline) = timeline {
+                let snapshots: Vec<Snapshot> = reports
+                    .into_iter()
+                    .map(|(file, records)| Snapshot::new(records, &file.display().to_string()))
+                    .collect();
+                let result = match timeline {
+                    TimelineFormat::Dot => write_dot(io::stdout().lock(), &snapshots),
+                    TimelineFormat::VegaLite => {
+                        let spec = vega_lite(&snapshots);
+                        serde_json::to_writer_pretty(io::stdout().lock(), &spec)
+                            .map_err(io::Error::other)
+                            .map(|()| println!())
+                    }
+                };
+                if let Err(e) = result {
+                    eprintln!(
+                        "{}",
+                        format!("Cannot write timeline: {}", e).paint(Role::Error)
+                    );
+                }
+                return;
+            }
+
+            if let Some(threshold) = latency_threshold {
+                if reports.len() < 2 {
+                    eprintln!(
+                        "{}",
+                        "--latency-threshold needs at least two results files, oldest first"
+                            .paint(Role::Warning)
+                    );
+                    return;
+                }
+                let snapshots: Vec<Snapshot> = reports
+                    .into_iter()
+                    .map(|(file, records)| Snapshot::new(records, &file.display().to_string()))
+                    .collect();
+                print_latency_regressions(&latency_regressions(&snapshots, threshold), threshold);
+                return;
+            }
+
+            for (_, records) in &mut reports {
+                if !status.is_empty() {
+                    records.retain(|record| status.contains(&record.status));
+                }
+                if let Some(order) = sort {
+                    sort_records(records, order);
+                }
+            }
+
+            for (file, records) in &reports {
+                println!(
+                    "\n##### {} results: {} #####\n",
+                    "Viewing".paint(Role::Heading),
+                    file.display().paint(Role::Target)
+                );
+
+                if !records.is_empty() {
+                    for record in records {
+                        print_record(record, &acks, local_time);
+                    }
+                } else if status.is_empty() {
+                    println!("{}", "No results recorded 😕".paint(Role::Warning));
+                } else {
+                    println!(
+                        "{}",
+                        "No results with the requested status 😕".paint(Role::Warning)
+                    );
+                }
+                if let Some(record) = records
+                    .iter()
+                    .find(|r| r.operator.is_some() || r.note.is_some())
+                {
+                    println!();
+                    print_engagement(record.operator.as_deref(), record.note.as_deref());
+                }
+            }
+
+            println!("\n##### {} #####\n", "Game Over".paint(Role::Banner));
+        }
+        Args::Merge { files, .. } => {
+            if format == OutputFormat::Text {
+                eprintln!(
+                    "{}",
+                    "merge writes a machine-readable report (-o json, jsonl or nmap-xml)"
+                        .paint(Role::Warning)
+                );
+                return;
+            }
+            let mut reports = Vec::new();
+            for file in &files {
+                match load_report(file) {
+                    Ok(records) => reports.push(records),
+                    Err(e) => {
+                        eprintln!("{}", e.paint(Role::Error));
+                        return;
                     }
                 }
             }
+            emit_records(format, output_file.as_deref(), &merge_reports(reports));
         }
+        Args::Diff { old, new, .. } => {
+            if matches!(format, OutputFormat::Jsonl | OutputFormat::NmapXml) {
+                eprintln!(
+                    "{}",
+                    "diff writes text or json (-o text or json)".paint(Role::Warning)
+                );
+                return;
+            }
+            let (before, after) = match (load_report(&old), load_report(&new)) {
+                (Ok(before), Ok(after)) => (before, after),
+                (Err(e), _) | (_, Err(e)) => {
+                    eprintln!("{}", e.paint(Role::Error));
+                    return;
+                }
+            };
+            let changes = diff(&before, &after);
+
+            if format == OutputFormat::Json {
+                let result = match &output_file {
+                    Some(path) => File::create(path).and_then(|file| {
+                        let mut out = BufWriter::new(file);
+                        serde_json::to_writer_pretty(&mut out, &changes)
+                            .map_err(io::Error::other)?;
+                        out.flush()
+                    }),
+                    None => serde_json::to_writer_pretty(io::stdout().lock(), &changes)
+                        .map_err(io::Error::other)
+                        .map(|()| println!()),
+                };
+                if let Err(e) = result {
+                    eprintln!(
+                        "{}",
+                        format!("Could not write the diff: {}", e).paint(Role::Error)
+                    );
+                }
+                return;
+            }
+
+            if changes.is_empty() {
+                println!(
+                    "{}",
+                    format!("No changes between {} and {}", old.display(), new.display())
+                        .paint(Role::Success)
+                );
+                return;
+            }
+            for (title, hosts, sign) in [
+                ("New hosts", &changes.new_hosts, "+".paint(Role::Success)),
+                (
+                    "Vanished hosts",
+                    &changes.vanished_hosts,
+                    "-".paint(Role::Error),
+                ),
+            ] {
+                if !hosts.is_empty() {
+                    println!("\n-- {} --\n", title.paint(Role::Section));
+                    for host in hosts {
+                        println!("{} {}", sign, host.paint(Role::Address));
+                    }
+                }
+            }
+            for (title, ports, sign) in [
+                (
+                    "Opened ports",
+                    &changes.opened_ports,
+                    "+".paint(Role::Success),
+                ),
+                (
+                    "Closed ports",
+                    &changes.closed_ports,
+                    "-".paint(Role::Error),
+                ),
+            ] {
+                if !ports.is_empty() {
+                    println!("\n-- {} --\n", title.paint(Role::Section));
+                    for change in ports {
+                        println!(
+                            "{} {}:{} {} -> {}",
+                            sign,
+                            change.ip.paint(Role::Address),
+                            format!("{}/{}", change.port, change.proto).paint(Role::Port),
+                            color_state(&change.from),
+                            color_state(&change.to)
+                        );
+                    }
+                }
+            }
+        }
+        #[cfg(feature = "history")]
+        Args::History { command } => {
+            let history = match storage::default_path()
+                .ok_or_else(|| {
+                    format!(
+                        "No history database: set {} to its path",
+                        storage::HISTORY_ENV
+                    )
+                })
+                .and_then(|path| storage::History::open(&path))
+            {
+                Ok(history) => history,
+                Err(e) => {
+                    eprintln!("{}", e.paint(Role::Error));
+                    return;
+                }
+            };
+            match command {
+                HistoryCommand::List { limit, .. } => {
+                    let runs = match history.list(limit) {
+                        Ok(runs) => runs,
+                        Err(e) => {
+                            eprintln!("{}", e.paint(Role::Error));
+                            return;
+                        }
+                    };
+                    if runs.is_empty() {
+                        println!("{}", "No scans recorded yet 😕".paint(Role::Warning));
+                    }
+                    for run in runs {
+                        println!(
+                            "{:>5}  {}  {} hosts, {} open  {}",
+                            run.id.paint(Role::Address),
+                            format_timestamp(run.time, local_time).paint(Role::Muted),
+                            run.live_hosts,
+                            run.open_ports.paint(Role::Target),
+                            run.arguments
+                        );
+                    }
+                }
+                HistoryCommand::Show { id, .. } => {
+                    let (run, records) = match history.show(id) {
+                        Ok(Some(found)) => found,
+                        Ok(None) => {
+                            eprintln!(
+                                "{}",
+                                format!("No scan {} in the history", id).paint(Role::Error)
+                            );
+                            return;
+                        }
+                        Err(e) => {
+                            eprintln!("{}", e.paint(Role::Error));
+                            return;
+                        }
+                    };
+                    if format != OutputFormat::Text {
+                        emit_records(format, output_file.as_deref(), &records);
+                        return;
+                    }
+                    println!(
+                        "\n##### {} scan {}: {} #####\n",
+                        "Viewing".paint(Role::Heading),
+                        run.id,
+                        run.arguments.paint(Role::Target)
+                    );
+                    if records.is_empty() {
+                        println!("{}", "No results recorded 😕".paint(Role::Warning));
+                    }
+                    for record in &records {
+                        print_record(record, &acks, local_time);
+                    }
+                    println!(
+                        "\n{} {}",
+                        "Completed at".paint(Role::Muted),
+                        format_timestamp(run.time, local_time)
+                    );
+                    println!("\n##### {} #####\n", "Game Over".paint(Role::Banner));
+                }
+            }
+        }
+        #[cfg(not(feature = "history"))]
+        Args::History { .. } => {
+            eprintln!(
+                "{}",
+                "This build keeps no scan history: reinstall with `cargo install asphyxia --features history`"
+                    .paint(Role::Warning)
+            );
+        }
+        Args::Listen {
+            ports,
+            bind,
+            log,
+            control,
+            udp_echo,
+            duration,
+            ..
+        } => {
+            let ports = match ports.as_deref().map(parse_ports) {
+                Some(Ok(ports)) => ports,
+                Some(Err(e)) => {
+                    eprintln!("{}", e.paint(Role::Error));
+                    return;
+                }
+                None => Vec::new(),
+            };
+
+            let names: Vec<String> = ports.iter().map(ToString::to_string).collect();
+            let mut listening = Vec::new();
+            if !names.is_empty() {
+                listening.push(format!("port(s) {}", names.join(",")));
+            }
+            if let Some(port) = udp_echo {
+                listening.push(format!("UDP echo {}", port));
+            }
+            println!(
+                "\n##### {} on: {} {} #####\n",
+                "Listening".paint(Role::Heading),
+                bind.paint(Role::Target),
+                listening.join(", ").paint(Role::Target)
+            );
+            if duration.is_none() {
+                println!("{}", "Press Ctrl-C to stop".paint(Role::Muted));
+            }
+
+            let listen_options = ListenOptions {
+                bind,
+                ports,
+                control,
+                udp_echo,
+                duration,
+            };
+            let arrivals = match listen::listen(&listen_options, &cancel, |arrival| {
+                if log {
+                    println!(
+                        "{}  {} -> {}",
+                        format_timestamp(arrival.timestamp, local_time).paint(Role::Muted),
+                        arrival.source.paint(Role::Address),
+                        format!("{}/tcp", arrival.port).paint(Role::Port)
+                    );
+                }
+            }) {
+                Ok(arrivals) => arrivals,
+                Err(e) => {
+                    eprintln!("{}", e.paint(Role::Error));
+                    return;
+                }
+            };
+
+            if !listen_options.ports.is_empty() {
+                println!("\n-- {} --\n", "Connections received".paint(Role::Section));
+            }
+            for port in &listen_options.ports {
+                let mut sources: Vec<IpAddr> = arrivals
+                    .iter()
+                    .filter(|arrival| arrival.port == *port)
+                    .map(|arrival| arrival.source.ip())
+                    .collect();
+                let count = sources.len();
+                sources.sort_unstable();
+                sources.dedup();
+                let sources: Vec<String> = sources.iter().map(ToString::to_string).collect();
+                if count > 0 {
+                    println!(
+                        "{}  {} from {}",
+                        format!("{}/tcp", port).paint(Role::Port),
+                        count,
+                        sources.join(", ").paint(Role::Address)
+                    );
+                } else {
+                    println!(
+                        "{}  {}",
+                        format!("{}/tcp", port).paint(Role::Port),
+                        "none".paint(Role::Muted)
+                    );
+                }
+            }
+            println!("\n##### {} #####\n", "Game Over".paint(Role::Banner));
+        }
+        Args::Validate {
+            peer,
+            ports,
+            technique,
+            timeout,
+            retries,
+        } => {
+            if technique == ScanTechnique::Syn && !syn::syn_available() {
+                eprintln!("{}", SYN_PRIVILEGES.paint(Role::Error));
+                return;
+            }
+            let peer_addr = match peer.to_socket_addrs().map(|mut addrs| addrs.next()) {
+                Ok(Some(addr)) => addr,
+                Ok(None) | Err(_) => {
+                    eprintln!(
+                        "{}",
+                        format!("Invalid peer address (expected HOST:PORT): {}", peer)
+                            .paint(Role::Error)
+                    );
+                    return;
+                }
+            };
+            let ports = match ports.as_deref().map(parse_ports) {
+                Some(Ok(ports)) => Some(ports),
+                Some(Err(e)) => {
+                    eprintln!("{}", e.paint(Role::Error));
+                    return;
+                }
+                None => None,
+            };
+
+            println!(
+                "\n##### {} path to: {} #####\n",
+                "Validating".paint(Role::Heading),
+                peer_addr.ip().paint(Role::Target)
+            );
+
+            let options = ScanOptions {
+                timeout: Some(timeout),
+                technique,
+                retries,
+                cancel: Some(cancel.clone()),
+                ..ScanOptions::default()
+            };
+            let results = match validate::validate(peer_addr, ports.as_deref(), &options) {
+                Ok(results) => results,
+                Err(e) => {
+                    eprintln!("{}", e.paint(Role::Error));
+                    return;
+                }
+            };
+
+            for result in &results {
+                let verdict = format!("{:<11}", result.verdict.as_str());
+                let verdict = match result.verdict {
+                    Verdict::Passed => verdict.paint(Role::Success),
+                    _ => verdict.paint(Role::Error),
+                };
+                println!(
+                    "{}  {}  {} {}",
+                    format!("{}/tcp", result.port).paint(Role::Port),
+                    verdict,
+                    result.verdict.describe(),
+                    format!(
+                        "(scan: {}, arrived: {})",
+                        result.state.as_str(),
+                        result.arrived
+                    )
+                    .paint(Role::Muted)
+                );
+            }
+            println!("\n##### {} #####\n", "Game Over".paint(Role::Banner));
+        }
+        Args::UdpProbe {
+            peer,
+            duration,
+            interval,
+            timeout,
+        } => {
+            let peer_addr = match peer.to_socket_addrs().map(|mut addrs| addrs.next()) {
+                Ok(Some(addr)) => addr,
+                Ok(None) | Err(_) => {
+                    eprintln!(
+                        "{}",
+                        format!("Invalid peer address (expected HOST:PORT): {}", peer)
+                            .paint(Role::Error)
+                    );
+                    return;
+                }
+            };
+
+            println!(
+                "\n##### {} UDP path to: {} #####\n",
+                "Probing".paint(Role::Heading),
+                peer_addr.paint(Role::Target)
+            );
+
+            let report = match udp_path::measure(peer_addr, duration, interval, timeout, &cancel) {
+                Ok(report) => report,
+                Err(e) => {
+                    eprintln!(
+                        "{}",
+                        format!("UDP probe to {} failed: {}", peer_addr, e).paint(Role::Error)
+                    );
+                    return;
+                }
+            };
+
+            println!(
+                "{}  {} sent, {} received, {:.1}% lost",
+                "Packets".paint(Role::Muted),
+                report.sent,
+                report.received(),
+                report.loss_percent()
+            );
+            if let Some(summary) = LatencySummary::from_latencies(report.rtts.iter().copied()) {
+                println!(
+                    "{}  min {}, avg {}, max {} (round trip)",
+                    "Latency".paint(Role::Muted),
+                    format_ms(summary.min),
+                    format_ms(summary.avg),
+                    format_ms(summary.max)
+                );
+            }
+            if let Some(one_way) = report.one_way() {
+                println!("{}  ~{}", "One way".paint(Role::Muted), format_ms(one_way));
+            }
+            println!(
+                "{}  {}",
+                "Jitter".paint(Role::Muted),
+                format_ms(report.jitter)
+            );
+
+            let issues = report.voip_issues();
+            if issues.is_empty() {
+                println!("\n{}", "Fit for VoIP 👍".paint(Role::Success));
+            } else {
+                println!(
+                    "\n{} {}",
+                    "Not fit for VoIP:".paint(Role::Error),
+                    issues.join(", ")
+                );
+            }
+            println!("\n##### {} #####\n", "Game Over".paint(Role::Banner));
+        }
+        Args::Ping {
+            host,
+            count,
+            interval,
+            timeout,
+            tcp,
+            port: tcp_port,
+        } => {
+            let Some(ip) = port::resolve_host(&host) else {
+                eprintln!(
+                    "{}",
+                    format!("Cannot resolve host: {}", host).paint(Role::Error)
+                );
+                return;
+            };
+            let method = if tcp {
+                PingMethod::Tcp(tcp_port)
+            } else {
+                PingMethod::best_available(Some(tcp_port))
+            };
+            let over = match method {
+                PingMethod::Icmp => "ICMP".to_string(),
+                PingMethod::Tcp(port) => format!("TCP port {}", port),
+            };
+
+            println!(
+                "\n##### {} {} ({}) over {} #####\n",
+                "Pinging".paint(Role::Heading),
+                host.paint(Role::Target),
+                ip,
+                over
+            );
+
+            let report = ping::ping_host(
+                ip,
+                method,
+                (count > 0).then_some(count),
+                interval,
+                timeout,
+                &cancel,
+                |sequence, rtt| match rtt {
+                    Some(rtt) => println!(
+                        "{} from {}: seq={} time={}",
+                        "Reply".paint(Role::Open),
+                        ip,
+                        sequence,
+                        format_ms(rtt)
+                    ),
+                    None => println!("{} seq={}", "No answer".paint(Role::Closed), sequence),
+                },
+            );
+            let report = match report {
+                Ok(report) => report,
+                Err(e) => {
+                    eprintln!(
+                        "{}",
+                        format!("Ping to {} failed: {}", ip, e).paint(Role::Error)
+                    );
+                    return;
+                }
+            };
+
+            println!(
+                "\n{}  {} sent, {} received, {:.1}% lost",
+                "Packets".paint(Role::Muted),
+                report.sent,
+                report.received(),
+                report.loss_percent()
+            );
+            if let Some(summary) = LatencySummary::from_latencies(report.rtts.iter().copied()) {
+                println!(
+                    "{}  min {}, avg {}, max {}, stddev {}",
+                    "Latency".paint(Role::Muted),
+                    format_ms(summary.min),
+                    format_ms(summary.avg),
+                    format_ms(summary.max),
+                    format_ms(summary.stddev)
+                );
+            }
+            println!("\n##### {} #####\n", "Game Over".paint(Role::Banner));
+        }
+        Args::Monitor {
+            targets,
+            ports,
+            probe,
+            interval,
+            timeout,
+            rounds,
+            webhook,
+            exec,
+            ..
+        } => {
+            if interval.is_zero() {
+                eprintln!(
+                    "{}",
+                    "--interval must be greater than zero".paint(Role::Error)
+                );
+                return;
+            }
+            if probe == ProbeMethod::Arp && !arp::arp_available() {
+                eprintln!("{}", ARP_UNAVAILABLE.paint(Role::Error));
+                return;
+            }
+            let probe = icmp_fallback(probe);
+            let ports = match ports.as_deref().map(parse_ports) {
+                Some(Ok(ports)) => ports,
+                Some(Err(e)) => {
+                    eprintln!("{}", e.paint(Role::Error));
+                    return;
+                }
+                None => Vec::new(),
+            };
+            if let Some(host) = targets
+                .iter()
+                .find(|host| port::resolve_host(host).is_none())
+            {
+                eprintln!(
+                    "{}",
+                    format!("Cannot resolve host: {}", host).paint(Role::Error)
+                );
+                return;
+            }
+
+            println!(
+                "\n##### {} {} hosts, {} ports every {:?} #####\n",
+                "Monitoring".paint(Role::Heading),
+                targets.len().paint(Role::Target),
+                ports.len().paint(Role::Target),
+                interval
+            );
+
+            let options = ScanOptions {
+                timeout: Some(timeout),
+                probe,
+                cancel: Some(cancel.clone()),
+                ..ScanOptions::default()
+            };
+            crash::phase("monitor");
+            monitor::monitor(
+                &targets,
+                &ports,
+                &options,
+                interval,
+                rounds,
+                &cancel,
+                |round, known, changes| {
+                    crash::event(format!("round {}: {} changes", round, changes.len()));
+                    if round == 1 {
+                        let now = format_timestamp(Utc::now(), local_time);
+                        for (target, state) in known {
+                            println!(
+                                "{}  {} {}",
+                                now.paint(Role::Muted),
+                                target,
+                                color_state(state)
+                            );
+                        }
+                        return;
+                    }
+                    for change in changes {
+                        println!(
+                            "{}  {} {} -> {}",
+                            format_timestamp(change.time, local_time).paint(Role::Muted),
+                            change.target,
+                            change.from.as_deref().map(color_state).unwrap_or_default(),
+                            color_state(&change.to)
+                        );
+                        if let Some(command) = &exec
+                            && let Err(e) = notify::run_command(command, change)
+                        {
+                            eprintln!("{}", e.paint(Role::Warning));
+                        }
+                    }
+                    if let Some(url) = &webhook
+                        && !changes.is_empty()
+                        && let Err(e) = notify::post_webhook(url, changes, timeout)
+                    {
+                        eprintln!("{}", e.paint(Role::Warning));
+                    }
+                },
+            );
+            println!("\n##### {} #####\n", "Game Over".paint(Role::Banner));
+        }
+        Args::Replay { file, .. } => {
+            let packets = match fs::read(&file)
+                .map_err(|e| format!("Cannot read capture {}: {}", file.display(), e))
+                .and_then(|bytes| replay::read_pcap(&bytes))
+            {
+                Ok(packets) => packets,
+                Err(e) => {
+                    eprintln!("{}", e.paint(Role::Error));
+                    return;
+                }
+            };
+            let replayed = replay::replay(&packets);
+
+            if format != OutputFormat::Text {
+                let hosts = replayed
+                    .hosts
+                    .iter()
+                    .filter(|host| host.result.is_up())
+                    .map(|host| ScanRecord {
+                        format_version: FORMAT_VERSION,
+                        ip: host.result.ip.to_string(),
+                        hostname: None,
+                        mac: None,
+                        port: None,
+                        proto: ProbeMethod::Icmp.proto().to_string(),
+                        service: None,
+                        latency_ms: host.result.rtt.as_millis(),
+                        status: host.result.state.as_str().to_string(),
+                        timestamp: Some(host.time.trunc_subsecs(0)),
+                        ack: None,
+                        hops: None,
+                        path_mtu: None,
+                        ttl: host.result.ttl,
+                        hop_estimate: host.result.ttl.map(ttl::estimate_hops),
+                        evidence: None,
+                        attempts: None,
+                        os_guess: None,
+                        product: None,
+                        version: None,
+                        http_status: None,
+                        http_server: None,
+                        http_title: None,
+                        tls_subject: None,
+                        tls_issuer: None,
+                        tls_sans: None,
+                        tls_expires: None,
+                        operator: None,
+                        note: None,
+                        outages: None,
+                        annotation: None,
+                    });
+                let ports = replayed.ports.iter().map(|found| {
+                    let result = &found.result;
+                    ScanRecord {
+                        format_version: FORMAT_VERSION,
+                        ip: found.ip.to_string(),
+                        hostname: None,
+                        mac: None,
+                        port: Some(result.port),
+                        proto: "tcp".to_string(),
+                        service: services::service_name_for_port(result.port, "tcp")
+                            .map(str::to_string),
+                        latency_ms: result.latency.as_millis(),
+                        status: result.state.as_str().to_string(),
+                        timestamp: Some(found.time.trunc_subsecs(0)),
+                        ack: None,
+                        hops: None,
+                        path_mtu: None,
+                        ttl: result.ttl,
+                        hop_estimate: result.ttl.map(ttl::estimate_hops),
+                        evidence: Some(result.evidence.as_str().to_string()),
+                        attempts: Some(result.attempts),
+                        os_guess: result
+                            .fingerprint
+                            .and_then(|fingerprint| fingerprint.os_guess())
+                            .map(str::to_string),
+                        product: None,
+                        version: None,
+                        http_status: None,
+                        http_server: None,
+                        http_title: None,
+                        tls_subject: None,
+                        tls_issuer: None,
+                        tls_sans: None,
+                        tls_expires: None,
+                        operator: None,
+                        note: None,
+                        outages: None,
+                        annotation: None,
+                    }
+                });
+                let records: Vec<ScanRecord> = hosts.chain(ports).collect();
+                emit_records(format, output_file.as_deref(), &records);
+                return;
+            }
+
+            println!(
+                "\n##### {} capture: {} #####\n",
+                "Replaying".paint(Role::Heading),
+                file.display().paint(Role::Target)
+            );
+            println!(
+                "{} packets, {} port probes, {} hosts pinged",
+                packets.len(),
+                replayed.ports.len(),
+                replayed.hosts.len()
+            );
+
+            if !replayed.hosts.is_empty() {
+                println!();
+            }
+            for host in &replayed.hosts {
+                let result = &host.result;
+                if result.is_up() {
+                    println!(
+                        "{}  echo reply in {}{}",
+                        result.ip.paint(Role::Target),
+                        format_ms(result.rtt),
+                        hops_note(result.ttl)
+                    );
+                } else {
+                    println!(
+                        "{}  {}",
+                        result.ip.paint(Role::Closed),
+                        "no echo reply".paint(Role::Muted)
+                    );
+                }
+            }
+            if !replayed.ports.is_empty() {
+                println!();
+            }
+            for found in &replayed.ports {
+                let result = &found.result;
+                println!(
+                    "{}:{}{} {} {}{}",
+                    found.ip.paint(Role::Address),
+                    format!("{}/tcp", result.port).paint(Role::Port),
+                    service_note(services::service_name_for_port(result.port, "tcp")),
+                    color_state(result.state.as_str()),
+                    result.explain().paint(Role::Muted),
+                    format!(" at {}", format_timestamp(found.time, local_time)).paint(Role::Muted)
+                );
+            }
+            if replayed.hosts.is_empty() && replayed.ports.is_empty() {
+                println!(
+                    "{}",
+                    "No SYN or ICMP echo probes in the capture 😕".paint(Role::Warning)
+                );
+            }
+            println!("\n##### {} #####\n", "Game Over".paint(Role::Banner));
+        }
+        Args::SelfUpdate {
+            channel,
+            check,
+            feed,
+            public_key,
+            timeout,
+        } => {
+            // Nothing is fetched for an install that could not be verified.
+            let key = match public_key.as_deref().or(update::BUILTIN_KEY) {
+                _ if check => None,
+                Some(hex) => match update::parse_public_key(hex) {
+                    Ok(key) => Some(key),
+                    Err(e) => {
+                        eprintln!("{}", e.paint(Role::Error));
+                        return;
+                    }
+                },
+                None => {
+                    eprintln!(
+                        "{}",
+                        "This build has no release key to verify updates with; pass --public-key"
+                            .paint(Role::Error)
+                    );
+                    return;
+                }
+            };
+            let current = update::current_version();
+            let release = match update::check(&feed, channel, timeout) {
+                Ok(Some(release)) => release,
+                Ok(None) => {
+                    println!(
+                        "{}",
+                        format!("asphyxia {} is the newest {} release 👍", current, channel)
+                            .paint(Role::Success)
+                    );
+                    return;
+                }
+                Err(e) => {
+                    eprintln!("{}", e.paint(Role::Error));
+                    return;
+                }
+            };
+            // Only `--check` goes without a key.
+            let Some(key) = key else {
+                println!(
+                    "{} asphyxia {} -> {} ({} channel); run self-update to install it",
+                    "Update available".paint(Role::Info),
+                    current,
+                    release.version.paint(Role::Product),
+                    channel
+                );
+                return;
+            };
+            let exe = match std::env::current_exe() {
+                Ok(exe) => exe,
+                Err(e) => {
+                    eprintln!(
+                        "{}",
+                        format!("Cannot locate the running binary: {}", e).paint(Role::Error)
+                    );
+                    return;
+                }
+            };
+            println!(
+                "{} asphyxia {} ({} channel) over {}",
+                "Installing".paint(Role::Heading),
+                release.version.paint(Role::Product),
+                channel,
+                exe.display().paint(Role::Target)
+            );
+            match update::install(&release, &key, &exe, timeout) {
+                Ok(()) => println!(
+                    "{}",
+                    format!("Updated asphyxia {} -> {} 👍", current, release.version)
+                        .paint(Role::Success)
+                ),
+                Err(e) => eprintln!("{}", e.paint(Role::Error)),
+            }
+        }
+        Args::Interfaces { all } => {
+            let interfaces: Vec<Interface> = list_interfaces()
+                .into_iter()
+                .filter(|interface| all || (interface.up && !interface.addresses.is_empty()))
+                .collect();
+            if interfaces.is_empty() {
+                println!("{}", "No network interfaces found 😕".paint(Role::Warning));
+                return;
+            }
+            println!("\n-- {} --\n", "Interfaces".paint(Role::Section));
+            for interface in &interfaces {
+                print_interface(interface);
+            }
+            println!(
+                "\n{}",
+                "Scan a subnet with: asphyxia as -s <SUBNET>".paint(Role::Muted)
+            );
+        }
+    }
+}
+
+/// Print an interface and its addresses as lines of the `if` listing.
+fn print_interface(interface: &Interface) {
+    let flags: Vec<&str> = [(!interface.up, "down"), (interface.loopback, "loopback")]
+        .into_iter()
+        .filter_map(|(set, flag)| set.then_some(flag))
+        .collect();
+    let flags = if flags.is_empty() {
+        String::new()
+    } else {
+        format!("  ({})", flags.join(", "))
+            .paint(Role::Muted)
+            .to_string()
+    };
+    println!("{}{}", interface.name.paint(Role::Target), flags);
+    for (address, network) in interface.addresses.iter().zip(interface.networks()) {
+        println!(
+            "  {}  {}",
+            address.paint(Role::Address),
+            format!("subnet {}", network).paint(Role::Muted)
+        );
+    }
+}
+
+/// `state` painted by what it means: open, filtered or closed (see
+/// [`theme::state_role`]).
+fn color_state(state: &str) -> String {
+    state.paint(theme::state_role(state))
+}
+
+/// Cancel `cancel` on the first Ctrl-C so the running scan winds down and
+/// its partial results are still reported; a second Ctrl-C exits at once.
+fn install_interrupt_handler(cancel: CancelToken) {
+    let result = ctrlc::set_handler(move || {
+        if cancel.is_cancelled() {
+            std::process::exit(130);
+        }
+        cancel.cancel();
+        eprintln!(
+            "{}",
+            "Interrupted: finishing probes in flight (press Ctrl-C again to quit)"
+                .paint(Role::Warning)
+        );
+    });
+    if let Err(e) = result {
+        eprintln!(
+            "{}",
+            format!("Could not install the Ctrl-C handler: {}", e).paint(Role::Warning)
+        );
+    }
+}
+
+/// The [`ScanOptions::max_hosts`] a scan asked for with `--max-hosts` and
+/// `--i-know-what-im-doing`.
+fn host_limit(max_hosts: Option<u64>, unlimited: bool) -> Option<u64> {
+    if unlimited {
+        None
+    } else {
+        Some(max_hosts.unwrap_or(address::DEFAULT_MAX_HOSTS))
+    }
+}
+
+/// The hosts a port scan of `targets` covers: hostnames and addresses as
+/// given, subnets and ranges expanded to every address.
+fn port_scan_hosts(targets: &[Target]) -> Result<Vec<String>, String> {
+    let mut hosts = Vec::new();
+    for target in targets {
+        match target {
+            Target::Cidr(_) | Target::Range(..) => {
+                hosts.extend(target.addresses()?.iter().map(ToString::to_string));
+            }
+            _ => hosts.push(target.to_string()),
+        }
+    }
+    Ok(hosts)
+}
+
+/// Address-scan every target and return the live hosts, sorted and without
+/// duplicates. Targets that cannot be scanned are reported and skipped.
+fn scan_targets(targets: &[Target], options: &ScanOptions) -> Vec<address::HostScanResult> {
+    let mut targets = targets.to_vec();
+    if let Some(seed) = options.shuffle_seed {
+        order::shuffle(&mut targets, seed);
+    }
+    let mut available = Vec::new();
+    for target in &targets {
+        if options
+            .cancel
+            .as_ref()
+            .is_some_and(CancelToken::is_cancelled)
+        {
+            break;
+        }
+        let count = match target {
+            Target::Cidr(network) => address::subnet_size(*network),
+            Target::Range(start, end) => address::range_size(*start, *end).unwrap_or(0),
+            _ => 1,
+        };
+        if let Err(e) = options.check_host_count(count) {
+            eprintln!("{}", format!("{}: {}", target, e).paint(Role::Error));
+            continue;
+        }
+        match target {
+            Target::Cidr(network) => {
+                available.extend(address::scan_subnet_with_options(*network, options));
+            }
+            Target::Range(start, end) => {
+                available.extend(address::scan_ip_range_with_options(*start, *end, options));
+            }
+            _ => match target.addresses() {
+                Ok(ips) => available.extend(
+                    ips.into_iter()
+                        .filter(|&ip| !options.is_excluded(ip))
+                        .map(|ip| address::scan_address_with_options(ip, options))
+                        .filter(|result| result.is_up()),
+                ),
+                Err(e) => eprintln!("{}", e.paint(Role::Error)),
+            },
+        }
+    }
+    available.sort_by_key(|host| host.ip);
+    available.dedup_by_key(|host| host.ip);
+    available
+}
+
+/// Tell the user the report only covers part of the targets.
+fn warn_if_interrupted(cancel: &CancelToken) {
+    if cancel.is_cancelled() {
+        eprintln!(
+            "{}",
+            "Scan interrupted: results are partial".paint(Role::Warning)
+        );
+    }
+}
+
+/// Traceroute `hosts` if `enabled`, keyed by host. A failure is reported
+/// and yields no routes, so the discovery results are still printed.
+fn trace_routes(
+    enabled: bool,
+    hosts: &[IpAddr],
+    options: &ScanOptions,
+    cancel: &CancelToken,
+) -> BTreeMap<IpAddr, Vec<Option<IpAddr>>> {
+    if !enabled || hosts.is_empty() || cancel.is_cancelled() {
+        return BTreeMap::new();
+    }
+    crash::phase("traceroute");
+    match traceroute::trace_all(hosts, options) {
+        Ok(paths) => paths
+            .into_iter()
+            .map(|path| (path.target, path.hops))
+            .collect(),
+        Err(e) => {
+            eprintln!("{}", format!("Traceroute failed: {}", e).paint(Role::Error));
+            BTreeMap::new()
+        }
+    }
+}
+
+/// Print the "Routes" section of the text report, one line per traced host.
+fn print_routes(routes: &BTreeMap<IpAddr, Vec<Option<IpAddr>>>) {
+    if routes.is_empty() {
+        return;
+    }
+    println!("\n-- {} --\n", "Routes".paint(Role::Section));
+    for (ip, hops) in routes {
+        let hops: Vec<String> = hops
+            .iter()
+            .map(|hop| hop.map_or("*".to_string(), |hop| hop.to_string()))
+            .collect();
+        println!("{}  {}", ip.paint(Role::Target), hops.join(" -> "));
+    }
+}
+
+/// Find the path MTU to `hosts` if `enabled`, keyed by host. A failure is
+/// reported and yields nothing, so the discovery results are still printed.
+fn discover_mtus(
+    enabled: bool,
+    hosts: &[IpAddr],
+    options: &ScanOptions,
+    cancel: &CancelToken,
+) -> BTreeMap<IpAddr, PathMtu> {
+    if !enabled || hosts.is_empty() || cancel.is_cancelled() {
+        return BTreeMap::new();
+    }
+    crash::phase("pmtu");
+    match pmtu::discover_all(hosts, options) {
+        Ok(paths) => paths.into_iter().collect(),
+        Err(e) => {
+            eprintln!(
+                "{}",
+                format!("Path MTU discovery failed: {}", e).paint(Role::Error)
+            );
+            BTreeMap::new()
+        }
+    }
+}
+
+/// Print the "Path MTU" section of the text report, one line per host that
+/// answered the probes.
+fn print_mtus(mtus: &BTreeMap<IpAddr, PathMtu>) {
+    if mtus.is_empty() {
+        return;
+    }
+    println!("\n-- {} --\n", "Path MTU".paint(Role::Section));
+    for (ip, path) in mtus {
+        let note = if path.black_hole {
+            format!(
+                "  {}",
+                "larger packets vanish without \"fragmentation needed\": MTU black hole"
+                    .paint(Role::Warning)
+            )
+        } else if path.mtu == pmtu::MAX_MTU {
+            format!("  {}", "or more".paint(Role::Muted))
+        } else {
+            String::new()
+        };
+        println!("{}  {}{}", ip.paint(Role::Target), path.mtu, note);
+    }
+}
+
+/// The `hops` of `ip`'s host record, if it was traced.
+fn record_hops(
+    routes: &BTreeMap<IpAddr, Vec<Option<IpAddr>>>,
+    ip: IpAddr,
+) -> Option<Vec<Option<String>>> {
+    routes.get(&ip).map(|hops| {
+        hops.iter()
+            .map(|hop| hop.map(|hop| hop.to_string()))
+            .collect()
+    })
+}
+
+/// The host probe to use: the one asked for, else ARP when `subnet` lies on a
+/// directly attached link (where it also finds hosts that filter everything),
+/// else TCP.
+fn choose_probe(probe: Option<ProbeMethod>, subnet: Option<&str>) -> ProbeMethod {
+    probe.unwrap_or_else(|| {
+        let local = subnet
+            .and_then(|subnet| parse_subnet(subnet).ok())
+            .is_some_and(is_local_subnet);
+        if local && arp::arp_available() {
+            ProbeMethod::Arp
+        } else {
+            ProbeMethod::Tcp
+        }
+    })
+}
+
+/// `probe`, unless it is ICMP and this process can open neither a raw nor a
+/// datagram ICMP socket: then TCP, with a warning.
+fn icmp_fallback(probe: ProbeMethod) -> ProbeMethod {
+    if probe == ProbeMethod::Icmp && icmp::icmp_socket().is_none() {
+        eprintln!(
+            "{}",
+            "ICMP probes need root, CAP_NET_RAW or a group in net.ipv4.ping_group_range: probing over TCP instead"
+                .paint(Role::Warning)
+        );
+        return ProbeMethod::Tcp;
+    }
+    probe
+}
+
+/// Print the lowest, mean and highest of `latencies`, if there are any.
+fn print_latency_summary<I: IntoIterator<Item = Duration>>(latencies: I) {
+    let Some(summary) = LatencySummary::from_latencies(latencies) else {
+        return;
+    };
+    println!(
+        "\n{} min {}, avg {}, max {}",
+        "Latency".paint(Role::Muted),
+        format_ms(summary.min),
+        format_ms(summary.avg),
+        format_ms(summary.max)
+    );
+}
+
+/// Print the seed of a `--randomize` scan, so `--seed` can repeat its
+/// order; nothing for a sequential scan.
+fn print_shuffle_seed(seed: Option<u64>) {
+    if let Some(seed) = seed {
+        println!(
+            "{} (seed {}; repeat with --seed {})",
+            "Randomized scan order".paint(Role::Muted),
+            seed,
+            seed
+        );
+    }
+}
+
+/// Print how many ports were open, closed and filtered; a closed port
+/// proves the host answered, a filtered one that something dropped the
+/// probe.
+fn print_state_counts(counts: StateCounts) {
+    println!(
+        "\n{} {} open, {} closed, {} filtered",
+        "Ports".paint(Role::Muted),
+        counts.open.to_string().paint(Role::Open),
+        counts.closed.to_string().paint(Role::Closed),
+        counts.filtered.to_string().paint(Role::Filtered)
+    );
+}
+
+/// The watch that pauses probing through network outages of up to
+/// `window` (`--outage-window`); none for a zero window.
+fn outage_watch(window: Duration) -> Option<Arc<OutageWatch>> {
+    (!window.is_zero()).then(|| Arc::new(OutageWatch::new(window)))
+}
+
+/// The outages `watch` saw, also marked on every record of the report.
+fn mark_outages(watch: Option<&OutageWatch>, records: &mut [ScanRecord]) -> Vec<Outage> {
+    let outages = watch.map(OutageWatch::outages).unwrap_or_default();
+    if !outages.is_empty() {
+        for record in records {
+            record.outages = Some(outages.clone());
+        }
+    }
+    outages
+}
+
+/// Print when the scanning host was offline and probing paused.
+fn print_outages(outages: &[Outage], local: bool) {
+    if outages.is_empty() {
+        return;
+    }
+    println!("\n-- {} --\n", "Network outages".paint(Role::Section));
+    for outage in outages {
+        let started = format_timestamp(outage.started, local);
+        match (outage.ended, outage.duration()) {
+            (Some(ended), Some(lasted)) => println!(
+                "{} to {}  probing paused for {:?}",
+                started,
+                format_timestamp(ended, local),
+                Duration::from_secs(lasted.as_secs())
+            ),
+            _ => println!(
+                "{}  {}",
+                started,
+                "no route to the targets within --outage-window; the scan stopped"
+                    .paint(Role::Warning)
+            ),
+        }
+    }
+}
+
+/// Write the traffic of every target probed to `path` as JSON, for
+/// `--audit-file`, and return it for the text report; nothing without a
+/// path.
+fn save_audit(path: Option<&Path>) -> Vec<TargetTraffic> {
+    let Some(path) = path else {
+        return Vec::new();
+    };
+    let traffic = usage::audit();
+    let written = serde_json::to_string_pretty(&traffic)
+        .map_err(|e| e.to_string())
+        .and_then(|json| fs::write(path, json + "\n").map_err(|e| e.to_string()));
+    if let Err(e) = written {
+        eprintln!(
+            "{}",
+            format!("Cannot write audit file {}: {}", path.display(), e).paint(Role::Error)
+        );
+    }
+    traffic
+}
+
+/// Print the probes and bytes each target was sent and answered with.
+fn print_audit(traffic: &[TargetTraffic]) {
+    if traffic.is_empty() {
+        return;
+    }
+    println!("\n-- {} --\n", "Traffic per target".paint(Role::Section));
+    for target in traffic {
+        println!(
+            "{}  {} probes, {} sent; {} answers, {} received",
+            target.ip.paint(Role::Address),
+            target.probes_sent,
+            format_bytes(target.bytes_sent),
+            target.responses_received,
+            format_bytes(target.bytes_received)
+        );
+    }
+}
+
+/// Print what the scan cost: peak memory, peak open sockets, traffic and
+/// CPU time, for sizing scan hosts.
+fn print_resource_usage() {
+    let usage = usage::current();
+    let mut parts = Vec::new();
+    if let Some(memory) = usage.peak_memory {
+        parts.push(format!("peak memory {}", format_bytes(memory)));
+    }
+    parts.push(format!("peak sockets {}", usage.peak_sockets));
+    parts.push(format!("sent {}", format_bytes(usage.bytes_sent)));
+    parts.push(format!("received {}", format_bytes(usage.bytes_received)));
+    if let Some(cpu) = usage.cpu_time {
+        parts.push(format!("CPU {:.2} s", cpu.as_secs_f64()));
+    }
+    println!("\n{} {}", "Resources".paint(Role::Muted), parts.join(", "));
+}
+
+/// A byte count in the largest binary unit that keeps it at least 1, e.g.
+/// `512 B` or `18.4 MiB`.
+fn format_bytes(bytes: u64) -> String {
+    const UNITS: [&str; 4] = ["KiB", "MiB", "GiB", "TiB"];
+    if bytes < 1024 {
+        return format!("{} B", bytes);
+    }
+    let mut value = bytes as f64 / 1024.0;
+    let mut unit = 0;
+    while value >= 1024.0 && unit < UNITS.len() - 1 {
+        value /= 1024.0;
+        unit += 1;
+    }
+    format!("{:.1} {}", value, UNITS[unit])
+}
+
+/// A latency in milliseconds with one decimal, e.g. `12.3 ms`.
+fn format_ms(latency: Duration) -> String {
+    format!("{:.1} ms", latency.as_secs_f64() * 1000.0)
+}
+
+/// Print the hosts that got slower than `threshold` percent, worst first.
+fn print_latency_regressions(regressions: &[LatencyRegression], threshold: u32) {
+    if regressions.is_empty() {
+        println!(
+            "\n{}",
+            format!("No host got more than {}% slower 👍", threshold).paint(Role::Success)
+        );
+        return;
+    }
+    println!("\n-- {} --\n", "Latency regressions".paint(Role::Warning));
+    for regression in regressions {
+        println!(
+            "{}  {} ms -> {} ms {}",
+            regression.ip.paint(Role::Target),
+            regression.baseline_ms,
+            regression.latest_ms,
+            format!("(+{}%)", regression.increase_percent()).paint(Role::Error)
+        );
+    }
+    println!();
+}
+
+/// Hostname shown after an address in the text report, e.g. ` (nas.lan)`.
+fn hostname_note(hostname: Option<&str>) -> String {
+    match hostname {
+        Some(name) => format!(" ({})", name.paint(Role::Name)),
+        None => String::new(),
+    }
+}
+
+/// Estimated distance shown after a host in the text report, e.g.
+/// ` (~12 hops)`, or ` (local link)` when no router is in between.
+fn hops_note(ttl: Option<u8>) -> String {
+    match ttl.map(ttl::estimate_hops) {
+        Some(0) => " (local link)".paint(Role::Muted),
+        Some(hops) => format!(" (~{} hops)", hops).paint(Role::Muted),
+        None => String::new(),
+    }
+}
+
+/// Suffix giving the evidence behind a port's state with `-v`, or an empty
+/// string otherwise.
+fn evidence_note(verbose: u8, result: &PortScanResult) -> String {
+    if verbose == 0 {
+        return String::new();
+    }
+    format!("  [{}]", result.explain()).paint(Role::Muted)
+}
+
+/// The stack fingerprint of a port-scanned host for `--os`: the one its
+/// SYN-ACKs left in `cache` during the scan, or else one from an extra SYN
+/// probe to its first open port.
+fn host_fingerprint(
+    cache: &FingerprintCache,
+    scan_host: &str,
+    probed: &[PortScanResult],
+    options: &ScanOptions,
+) -> Option<StackFingerprint> {
+    let ip: IpAddr = scan_host.parse().ok()?;
+    let port = probed.iter().find(|result| result.is_open())?.port;
+    let timeout = options.timeout.unwrap_or(port::CONNECT_TIMEOUT);
+    fingerprint::fingerprint_host(ip, port, cache, timeout)
+        .ok()
+        .flatten()
+}
+
+/// Print the OS guess for a port-scanned host (`--os`).
+fn print_os_guess(fingerprint: Option<StackFingerprint>) {
+    let Some(fingerprint) = fingerprint else {
+        println!(
+            "\n{} {}",
+            "OS guess:".paint(Role::Section),
+            "none (no SYN-ACK to fingerprint)".paint(Role::Muted)
+        );
+        return;
+    };
+    let mss = fingerprint
+        .mss
+        .map(|mss| format!(", MSS {}", mss))
+        .unwrap_or_default();
+    println!(
+        "\n{} {} {}",
+        "OS guess:".paint(Role::Section),
+        fingerprint
+            .os_guess()
+            .unwrap_or("unknown")
+            .paint(Role::Target),
+        format!(
+            "(initial TTL {}, window {}{})",
+            fingerprint.initial_ttl, fingerprint.window, mss
+        )
+        .paint(Role::Muted)
+    );
+}
+
+/// List every probed port of `host` with its state and evidence (`-vv`).
+fn print_port_evidence(host: &str, probed: &[PortScanResult]) {
+    if probed.is_empty() {
+        return;
+    }
+    println!(
+        "\n-- {} for {} --\n",
+        "Probe evidence".paint(Role::Section),
+        host.paint(Role::Subject)
+    );
+    for result in probed {
+        println!(
+            "{}:{}/tcp {:<8} {}",
+            host.paint(Role::Address),
+            result.port,
+            result.state.as_str(),
+            result.explain().paint(Role::Muted)
+        );
+    }
+}
+
+/// The TTL of a swept host's answers: its ICMP reply, else any SYN answer
+/// from one of its ports.
+fn host_ttl(report: &sweep::HostReport) -> Option<u8> {
+    report
+        .host
+        .ttl
+        .or_else(|| report.open_ports.iter().find_map(|result| result.ttl))
+}
+
+/// Hardware address shown after a host in the text report, e.g.
+/// ` [02:fc:00:00:00:05]`.
+fn mac_note(mac: Option<&str>) -> String {
+    match mac {
+        Some(mac) => format!(" [{}]", mac).paint(Role::Muted),
+        None => String::new(),
+    }
+}
+
+/// Service name shown after a port in the text report, e.g. ` ssh`.
+fn service_note(service: Option<&str>) -> String {
+    match service {
+        Some(name) => format!(" {}", name.paint(Role::Name)),
+        None => String::new(),
+    }
+}
+
+/// The service on a TCP `port`: the one version detection found, if it
+/// ran and matched, else the one registered for the port.
+fn port_service(port: u16, detected: Option<&ServiceVersion>) -> Option<&str> {
+    detected
+        .map(|found| found.service.as_str())
+        .or_else(|| services::service_name_for_port(port, "tcp"))
+}
+
+/// Suffix with the product and version version detection found, if any.
+fn version_note(detected: Option<&ServiceVersion>) -> String {
+    match detected.and_then(ServiceVersion::label) {
+        Some(label) => format!(" {}", label.paint(Role::Product)),
+        None => String::new(),
+    }
+}
+
+/// Run `probe` (e.g. [`http_probe::probe_web_ports`]) on the open ports of
+/// every swept host when `enabled`, keyed by host and port. Stops early once
+/// `cancel` is triggered.
+fn probe_open_services<T>(
+    enabled: bool,
+    reports: &[sweep::HostReport],
+    options: &ScanOptions,
+    cancel: &CancelToken,
+    probe: fn(&str, &[u16], Duration) -> BTreeMap<u16, T>,
+) -> BTreeMap<IpAddr, BTreeMap<u16, T>> {
+    if !enabled {
+        return BTreeMap::new();
+    }
+    let timeout = options.timeout.unwrap_or(port::CONNECT_TIMEOUT);
+    reports
+        .iter()
+        .take_while(|_| !cancel.is_cancelled())
+        .map(|report| {
+            let open: Vec<u16> = report.open_ports.iter().map(|result| result.port).collect();
+            let host = report.host.ip.to_string();
+            (report.host.ip, probe(&host, &open, timeout))
+        })
+        .collect()
+}
+
+/// Suffix with the answer of a web port to `GET /`: status, server and
+/// title.
+fn http_note(info: Option<&HttpInfo>) -> String {
+    let Some(info) = info else {
+        return String::new();
+    };
+    let mut note = format!("HTTP {}", info.status);
+    if let Some(server) = &info.server {
+        note.push_str(&format!(" {}", server));
+    }
+    if let Some(title) = &info.title {
+        note.push_str(&format!(" \"{}\"", title));
+    }
+    format!(" [{}]", note.paint(Role::Web))
+}
+
+/// Suffix with the certificate of a TLS port: subject, issuer, alternative
+/// names and expiry, in yellow when it expires within
+/// [`CERT_EXPIRY_WARNING_DAYS`] and in red once it has.
+fn tls_note(cert: Option<&CertInfo>) -> String {
+    let Some(cert) = cert else {
+        return String::new();
+    };
+    let mut note = format!("TLS {}", cert.subject);
+    if cert.issuer == cert.subject {
+        note.push_str("; self-signed");
+    } else {
+        note.push_str(&format!("; issuer {}", cert.issuer));
+    }
+    if !cert.sans.is_empty() {
+        note.push_str(&format!("; SAN {}", cert.sans.join(", ")));
+    }
+    let expiry = cert.not_after.format("%Y-%m-%d");
+    let days = cert.days_left(Utc::now());
+    if days < 0 {
+        note.push_str(&format!("; expired {} ({} days ago)", expiry, -days));
+        format!(" [{}]", note.paint(Role::Error))
+    } else {
+        note.push_str(&format!("; expires {} ({} days)", expiry, days));
+        if days < CERT_EXPIRY_WARNING_DAYS {
+            format!(" [{}]", note.paint(Role::Warning))
+        } else {
+            format!(" [{}]", note.paint(Role::Info))
+        }
+    }
+}
+
+/// Print who ran the scan and under which note, when given.
+fn print_engagement(operator: Option<&str>, note: Option<&str>) {
+    if let Some(operator) = operator {
+        println!("{} {}", "Operator".paint(Role::Muted), operator);
+    }
+    if let Some(note) = note {
+        println!("{} {}", "Note".paint(Role::Muted), note);
+    }
+}
+
+/// Suffix marking an acknowledged finding in the text report, or an empty
+/// string when the finding is unexpected.
+fn ack_note(ack: Option<&Ack>) -> String {
+    match ack {
+        Some(ack) => format!("  (acknowledged by {}: {})", ack.by, ack.reason)
+            .paint(Role::Muted)
+            .to_string(),
+        None => String::new(),
+    }
+}
+
+/// Check that `source_ip` (if any) belongs to this host, and look up the
+/// interface called `interface` (if any), for the probes to be sent from.
+fn probe_interface(
+    source_ip: Option<IpAddr>,
+    interface: Option<&str>,
+) -> Result<Option<Interface>, String> {
+    if let Some(ip) = source_ip {
+        // Binding fails for an address the host does not have.
+        UdpSocket::bind((ip, 0)).map_err(|e| format!("Cannot send probes from {}: {}", ip, e))?;
+    }
+    interface.map(Interface::named).transpose()
+}
+
+/// The annotation of `ip` in the annotations file, if there is one.
+fn host_annotation(annotations: Option<&Annotations>, ip: IpAddr) -> Option<&Annotation> {
+    annotations?.find(ip)
+}
+
+/// Suffix with a host's note, attributes and probe limit from the
+/// annotations file, or an empty string for a host it does not mention.
+fn annotation_note(annotation: Option<&Annotation>) -> String {
+    let Some(annotation) = annotation else {
+        return String::new();
+    };
+    let mut parts: Vec<String> = annotation.note.iter().cloned().collect();
+    parts.extend(
+        annotation
+            .attributes
+            .iter()
+            .map(|(key, value)| format!("{}={}", key, value)),
+    );
+    if let Some(max) = annotation.max_port {
+        parts.push(format!("ports up to {} only", max));
+    }
+    if parts.is_empty() {
+        return String::new();
+    }
+    format!("  [{}]", parts.join("; "))
+        .paint(Role::Muted)
+        .to_string()
+}
+
+/// Read the records of a stored JSON or JSONL report.
+fn load_report(file: &Path) -> Result<Vec<ScanRecord>, String> {
+    let contents = fs::read_to_string(file)
+        .map_err(|e| format!("Cannot read results file {}: {}", file.display(), e))?;
+    read_records(&contents).map_err(|e| format!("Invalid results file {}: {}", file.display(), e))
+}
+
+/// Print one stored record as a line of the `view` report.
+fn print_record(record: &ScanRecord, acks: &AckList, local_time: bool) {
+    // An ack recorded at scan time wins; otherwise consult the ack file.
+    let ack = record
+        .ack
+        .as_ref()
+        .or_else(|| acks.find(&record.ip, record.port));
+    let target = match record.port {
+        Some(port) => format!(
+            "{}:{}{}",
+            record.ip.paint(Role::Address),
+            format!("{}/{}", port, record.proto).paint(Role::Port),
+            service_note(record.service.as_deref())
+        ),
+        None => format!(
+            "{}{}{}",
+            record.ip.paint(Role::Target),
+            hostname_note(record.hostname.as_deref()),
+            mac_note(record.mac.as_deref())
+        ),
+    };
+    let seen = record
+        .timestamp
+        .map(|ts| format!(" at {}", format_timestamp(ts, local_time)))
+        .unwrap_or_default();
+    println!(
+        "{}  {} {} ms{}{}{}",
+        target,
+        record.status,
+        record.latency_ms,
+        seen.paint(Role::Muted),
+        annotation_note(record.annotation.as_ref()),
+        ack_note(ack)
+    );
+}
+
+/// Add a finished scan to the history database (see [`storage::default_path`]).
+/// Failures are reported on stderr but do not fail the scan.
+#[cfg(feature = "history")]
+fn record_history(command: &str, completed: DateTime<Utc>, records: &[ScanRecord]) {
+    let Some(path) = storage::default_path() else {
+        return;
+    };
+    let arguments = storage::redact_arguments(std::env::args().skip(1));
+    if let Err(e) = storage::History::open(&path)
+        .and_then(|mut history| history.record(command, &arguments.join(" "), completed, records))
+    {
+        eprintln!(
+            "{}",
+            format!("Scan not added to history: {}", e).paint(Role::Warning)
+        );
+    }
+}
+
+/// Builds without the `history` feature keep no history.
+#[cfg(not(feature = "history"))]
+fn record_history(_command: &str, _completed: DateTime<Utc>, _records: &[ScanRecord]) {}
+
+/// Write machine-readable records to `path`, or to stdout when no path is
+/// given. Failures are reported on stderr.
+fn emit_records(format: OutputFormat, path: Option<&Path>, records: &[ScanRecord]) {
+    let result = match path {
+        Some(path) => File::create(path).and_then(|file| {
+            let mut out = BufWriter::new(file);
+            write_records(format, &mut out, records)?;
+            // Dropping the writer would swallow a failed final write.
+            out.flush()
+        }),
+        None => write_records(format, io::stdout().lock(), records),
+    };
+    if let Err(e) = result {
+        let target = path.map_or("stdout".to_string(), |p| p.display().to_string());
+        eprintln!(
+            "{}",
+            format!("Could not write results to {}: {}", target, e).paint(Role::Error)
+        );
+    }
+}
+
+/// Write a JSON Lines record for every host of `hosts` as it arrives, to
+/// `path` or stdout, and return the hosts sorted. Each record is stamped
+/// with the time its host answered.
+fn stream_host_records(
+    hosts: address::HostStream,
+    path: Option<&Path>,
+    to_record: impl Fn(&address::HostScanResult, DateTime<Utc>) -> ScanRecord,
+) -> Vec<address::HostScanResult> {
+    let target = path.map_or("stdout".to_string(), |p| p.display().to_string());
+    let mut out: Box<dyn io::Write> = match path.map(File::create).transpose() {
+        Ok(Some(file)) => Box::new(file),
+        Ok(None) => Box::new(io::stdout()),
+        Err(e) => {
+            eprintln!(
+                "{}",
+                format!("Could not write results to {}: {}", target, e).paint(Role::Error)
+            );
+            Box::new(io::sink())
+        }
+    };
+    let mut found = Vec::new();
+    let mut failed = false;
+    for host in hosts {
+        let record = to_record(&host, Utc::now().trunc_subsecs(0));
+        if !failed && let Err(e) = write_jsonl(&mut out, &[record]).and_then(|()| out.flush()) {
+            eprintln!(
+                "{}",
+                format!("Could not write results to {}: {}", target, e).paint(Role::Error)
+            );
+            failed = true;
+        }
+        found.push(host);
+    }
+    found.sort_by_key(|host| host.ip);
+    found
+}
+
+/// Write records in the given machine-readable `format`.
+fn write_records<W: io::Write>(
+    format: OutputFormat,
+    out: W,
+    records: &[ScanRecord],
+) -> io::Result<()> {
+    match format {
+        OutputFormat::Json => write_json(out, records),
+        OutputFormat::Jsonl => write_jsonl(out, records),
+        OutputFormat::NmapXml => write_nmap_xml(out, records),
+        OutputFormat::Text => Ok(()),
     }
 }
@@ -0,0 +1,178 @@
+//! Acknowledged findings.
+//!
+//! Some exposure is expected — the bastion host's SSH port, the public web
+//! server's 443. An ack file records those findings together with who
+//! accepted them, why, and until when, so reports can tell expected results
+//! apart from the ones that deserve attention.
+//!
+//! The file is YAML, keyed by `host:port` for a port-scan finding or by the
+//! bare host for an address-scan finding (IPv6 hosts are bracketed when a
+//! port follows, e.g. `[2001:db8::1]:22`):
+//!
+//! ```yaml
+//! "10.0.0.5:22":
+//!   by: alice
+//!   reason: bastion host, reviewed in SEC-1042
+//!   expires: 2026-12-31
+//! "10.0.0.9":
+//!   by: bob
+//!   reason: lab printer
+//! ```
+//!
+//! An ack stops applying after its `expires` date, so the finding resurfaces
+//! and has to be reviewed again.
+
+use chrono::{Local, NaiveDate};
+use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
+use std::fs;
+use std::net::Ipv6Addr;
+use std::path::Path;
+
+/// Who accepted a finding, why, and for how long.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct Ack {
+    /// Person or team that acknowledged the finding.
+    pub by: String,
+    /// Why the finding is expected.
+    pub reason: String,
+    /// Last day (inclusive) the acknowledgement applies; `None` never expires.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub expires: Option<NaiveDate>,
+}
+
+impl Ack {
+    /// Whether the acknowledgement still applies on `today`.
+    pub fn is_active(&self, today: NaiveDate) -> bool {
+        self.expires.is_none_or(|expires| today <= expires)
+    }
+}
+
+/// A set of acknowledged findings loaded from an ack file.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct AckList {
+    entries: HashMap<String, Ack>,
+}
+
+impl AckList {
+    /// Read and parse an ack file.
+    pub fn load(path: &Path) -> Result<AckList, String> {
+        let contents = fs::read_to_string(path)
+            .map_err(|e| format!("Cannot read ack file {}: {}", path.display(), e))?;
+        AckList::from_yaml(&contents)
+            .map_err(|e| format!("Invalid ack file {}: {}", path.display(), e))
+    }
+
+    /// Parse acknowledgements from YAML text (see the module docs for the format).
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use asphyxia::output::ack::AckList;
+    ///
+    /// let acks = AckList::from_yaml("\"10.0.0.5:22\": {by: alice, reason: bastion}").unwrap();
+    /// assert!(acks.find("10.0.0.5", Some(22)).is_some());
+    /// assert!(acks.find("10.0.0.5", Some(23)).is_none());
+    /// ```
+    pub fn from_yaml(yaml: &str) -> Result<AckList, String> {
+        if yaml.trim().is_empty() {
+            return Ok(AckList::default());
+        }
+        let entries: HashMap<String, Ack> =
+            serde_yaml::from_str(yaml).map_err(|e| e.to_string())?;
+        Ok(AckList { entries })
+    }
+
+    /// The acknowledgement for a finding, if one is active today.
+    pub fn find(&self, ip: &str, port: Option<u16>) -> Option<&Ack> {
+        self.find_on(ip, port, Local::now().date_naive())
+    }
+
+    /// The acknowledgement for a finding, if one is active on `today`.
+    pub fn find_on(&self, ip: &str, port: Option<u16>, today: NaiveDate) -> Option<&Ack> {
+        self.entries
+            .get(&ack_key(ip, port))
+            .filter(|ack| ack.is_active(today))
+    }
+}
+
+/// The ack-file key for a finding: `host:port`, or the bare host when there
+/// is no port. IPv6 hosts are bracketed before a port.
+///
+/// # Examples
+///
+/// ```
+/// use asphyxia::output::ack::ack_key;
+///
+/// assert_eq!(ack_key("10.0.0.5", Some(22)), "10.0.0.5:22");
+/// assert_eq!(ack_key("2001:db8::1", Some(22)), "[2001:db8::1]:22");
+/// assert_eq!(ack_key("10.0.0.9", None), "10.0.0.9");
+/// ```
+pub fn ack_key(ip: &str, port: Option<u16>) -> String {
+    match port {
+        Some(port) if ip.parse::<Ipv6Addr>().is_ok() => format!("[{}]:{}", ip, port),
+        Some(port) => format!("{}:{}", ip, port),
+        None => ip.to_string(),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const FILE: &str = r#"
+"10.0.0.5:22":
+  by: alice
+  reason: bastion host
+  expires: 2026-12-31
+"[2001:db8::1]:443":
+  by: bob
+  reason: public web
+"10.0.0.9":
+  by: carol
+  reason: lab printer
+"#;
+
+    fn day(s: &str) -> NaiveDate {
+        s.parse().unwrap()
+    }
+
+    #[test]
+    fn test_find_matches_host_port_and_host_keys() {
+        let acks = AckList::from_yaml(FILE).unwrap();
+        let today = day("2026-01-01");
+        assert_eq!(
+            acks.find_on("10.0.0.5", Some(22), today).unwrap().by,
+            "alice"
+        );
+        assert_eq!(
+            acks.find_on("2001:db8::1", Some(443), today).unwrap().by,
+            "bob"
+        );
+        assert_eq!(acks.find_on("10.0.0.9", None, today).unwrap().by, "carol");
+        assert!(acks.find_on("10.0.0.9", Some(80), today).is_none());
+    }
+
+    #[test]
+    fn test_expired_ack_no_longer_applies() {
+        let acks = AckList::from_yaml(FILE).unwrap();
+        assert!(
+            acks.find_on("10.0.0.5", Some(22), day("2026-12-31"))
+                .is_some()
+        );
+        assert!(
+            acks.find_on("10.0.0.5", Some(22), day("2027-01-01"))
+                .is_none()
+        );
+    }
+
+    #[test]
+    fn test_from_yaml_rejects_missing_fields() {
+        assert!(AckList::from_yaml("\"10.0.0.5:22\": {by: alice}").is_err());
+    }
+
+    #[test]
+    fn test_empty_file_is_empty_list() {
+        assert_eq!(AckList::from_yaml("\n").unwrap(), AckList::default());
+    }
+}
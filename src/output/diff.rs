@@ -0,0 +1,267 @@
+//! Differences between two saved scans.
+//!
+//! Change detection comes down to one question: what is different since the
+//! last scan? [`diff`] answers it for two reports of the same network, as the
+//! hosts that came up or went away and the ports that opened or closed.
+//!
+//! Hosts are matched by their [`host_identities`], as in a
+//! [`timeline`](super::timeline), so a device that only changed address
+//! between the scans is not reported as one host vanishing and another
+//! appearing.
+
+use serde::Serialize;
+use std::collections::{BTreeSet, HashMap};
+
+use super::identity::host_identities;
+use super::timeline::ABSENT;
+use super::{ScanRecord, compare_ips};
+
+/// What changed between two reports.
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize)]
+pub struct ScanDiff {
+    /// Addresses of hosts that answered in the new report but not the old.
+    pub new_hosts: Vec<String>,
+    /// Addresses of hosts that answered in the old report but not the new.
+    pub vanished_hosts: Vec<String>,
+    /// Ports open in the new report that were not open in the old.
+    pub opened_ports: Vec<PortChange>,
+    /// Ports open in the old report that are not open in the new.
+    pub closed_ports: Vec<PortChange>,
+}
+
+impl ScanDiff {
+    /// Whether the two reports agree on every host and open port.
+    pub fn is_empty(&self) -> bool {
+        self.new_hosts.is_empty()
+            && self.vanished_hosts.is_empty()
+            && self.opened_ports.is_empty()
+            && self.closed_ports.is_empty()
+    }
+}
+
+/// A port whose state changed between two reports.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+pub struct PortChange {
+    /// Address of the host (as of the new report, if the host is in it).
+    pub ip: String,
+    /// The port.
+    pub port: u16,
+    /// Transport protocol of the port.
+    pub proto: String,
+    /// State in the old report, or `absent` if the port was not in it.
+    pub from: String,
+    /// State in the new report, or `absent` if the port is not in it.
+    pub to: String,
+}
+
+/// A port of a host identity: (identity, port, protocol).
+type PortKey<'a> = (String, u16, &'a str);
+
+/// What one report says about the network.
+#[derive(Default)]
+struct Findings<'a> {
+    /// The address of every host identity.
+    addresses: HashMap<String, &'a str>,
+    /// Identities of the hosts that answered: a host record that is up, or
+    /// an open or closed port (a RST comes from a live host too).
+    live: BTreeSet<String>,
+    /// The state of every port.
+    ports: HashMap<PortKey<'a>, &'a str>,
+}
+
+impl<'a> Findings<'a> {
+    fn new(records: &'a [ScanRecord]) -> Findings<'a> {
+        let identities = host_identities(records);
+        let mut findings = Findings::default();
+        for record in records {
+            let host = identities[record.ip.as_str()].clone();
+            findings
+                .addresses
+                .entry(host.clone())
+                .or_insert(record.ip.as_str());
+            let answered = match record.port {
+                Some(port) => {
+                    findings
+                        .ports
+                        .insert((host.clone(), port, &record.proto), &record.status);
+                    matches!(record.status.as_str(), "open" | "closed")
+                }
+                None => record.status == "up",
+            };
+            if answered {
+                findings.live.insert(host);
+            }
+        }
+        findings
+    }
+}
+
+/// Compare two reports of the same network.
+///
+/// A port counts as opened when it is open in `new` but was closed,
+/// filtered or missing in `old`, and as closed the other way round; ports
+/// that were never open are not reported. Hosts, ports and port changes are
+/// ordered by address, then port.
+///
+/// # Arguments
+///
+/// * `old` - Records of the earlier report
+/// * `new` - Records of the later report
+///
+/// # Returns
+///
+/// * `ScanDiff` - The new and vanished hosts and the opened and closed ports
+///
+/// # Examples
+///
+/// ```
+/// use asphyxia::output::{diff, read_records};
+///
+/// let monday = read_records(concat!(
+///     r#"{"ip":"10.0.0.5","port":22,"proto":"tcp","latency_ms":3,"status":"open"}"#, "\n",
+///     r#"{"ip":"10.0.0.9","proto":"tcp","latency_ms":1,"status":"up"}"#,
+/// ))
+/// .unwrap();
+/// let tuesday = read_records(
+///     r#"{"ip":"10.0.0.5","port":443,"proto":"tcp","latency_ms":3,"status":"open"}"#,
+/// )
+/// .unwrap();
+///
+/// let changes = diff(&monday, &tuesday);
+/// assert_eq!(changes.vanished_hosts, ["10.0.0.9"]);
+/// assert_eq!(changes.opened_ports[0].port, 443);
+/// assert_eq!(changes.closed_ports[0].port, 22);
+/// ```
+pub fn diff(old: &[ScanRecord], new: &[ScanRecord]) -> ScanDiff {
+    let (old, new) = (Findings::new(old), Findings::new(new));
+    let mut changes = ScanDiff {
+        new_hosts: new
+            .live
+            .difference(&old.live)
+            .map(|host| new.addresses[host].to_string())
+            .collect(),
+        vanished_hosts: old
+            .live
+            .difference(&new.live)
+            .map(|host| old.addresses[host].to_string())
+            .collect(),
+        ..ScanDiff::default()
+    };
+
+    let ports: BTreeSet<&PortKey> = old.ports.keys().chain(new.ports.keys()).collect();
+    for key in ports {
+        let (host, port, proto) = key;
+        let from = old.ports.get(key).copied().unwrap_or(ABSENT);
+        let to = new.ports.get(key).copied().unwrap_or(ABSENT);
+        let change = PortChange {
+            ip: new
+                .addresses
+                .get(host)
+                .or_else(|| old.addresses.get(host))
+                .map_or(host.as_str(), |address| address)
+                .to_string(),
+            port: *port,
+            proto: proto.to_string(),
+            from: from.to_string(),
+            to: to.to_string(),
+        };
+        match (from == "open", to == "open") {
+            (false, true) => changes.opened_ports.push(change),
+            (true, false) => changes.closed_ports.push(change),
+            _ => {}
+        }
+    }
+
+    changes.new_hosts.sort_by(|a, b| compare_ips(a, b));
+    changes.vanished_hosts.sort_by(|a, b| compare_ips(a, b));
+    for ports in [&mut changes.opened_ports, &mut changes.closed_ports] {
+        ports.sort_by(|a, b| compare_ips(&a.ip, &b.ip).then(a.port.cmp(&b.port)));
+    }
+    changes
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::output::read_records;
+
+    fn records(jsonl: &str) -> Vec<ScanRecord> {
+        read_records(jsonl).unwrap()
+    }
+
+    #[test]
+    fn test_identical_reports_have_no_changes() {
+        let report = records(concat!(
+            r#"{"ip":"10.0.0.5","proto":"tcp","latency_ms":1,"status":"up"}"#,
+            "\n",
+            r#"{"ip":"10.0.0.5","port":22,"proto":"tcp","latency_ms":3,"status":"open"}"#,
+        ));
+        assert!(diff(&report, &report).is_empty());
+    }
+
+    #[test]
+    fn test_port_states_and_absent_ports() {
+        let old = records(concat!(
+            r#"{"ip":"10.0.0.5","port":22,"proto":"tcp","latency_ms":3,"status":"open"}"#,
+            "\n",
+            r#"{"ip":"10.0.0.5","port":80,"proto":"tcp","latency_ms":3,"status":"filtered"}"#,
+            "\n",
+            r#"{"ip":"10.0.0.5","port":8080,"proto":"tcp","latency_ms":3,"status":"open"}"#,
+        ));
+        let new = records(concat!(
+            r#"{"ip":"10.0.0.5","port":22,"proto":"tcp","latency_ms":3,"status":"closed"}"#,
+            "\n",
+            r#"{"ip":"10.0.0.5","port":80,"proto":"tcp","latency_ms":3,"status":"open"}"#,
+            "\n",
+            r#"{"ip":"10.0.0.5","port":443,"proto":"tcp","latency_ms":3,"status":"open"}"#,
+        ));
+        let changes = diff(&old, &new);
+        let opened: Vec<(u16, &str)> = changes
+            .opened_ports
+            .iter()
+            .map(|c| (c.port, c.from.as_str()))
+            .collect();
+        let closed: Vec<(u16, &str)> = changes
+            .closed_ports
+            .iter()
+            .map(|c| (c.port, c.to.as_str()))
+            .collect();
+        assert_eq!(opened, vec![(80, "filtered"), (443, "absent")]);
+        assert_eq!(closed, vec![(22, "closed"), (8080, "absent")]);
+        // Port answers show the host was up in both.
+        assert!(changes.new_hosts.is_empty() && changes.vanished_hosts.is_empty());
+    }
+
+    #[test]
+    fn test_hosts_come_and_go_in_address_order() {
+        let old = records(concat!(
+            r#"{"ip":"10.0.0.10","proto":"tcp","latency_ms":1,"status":"up"}"#,
+            "\n",
+            r#"{"ip":"10.0.0.3","port":22,"proto":"tcp","latency_ms":1,"status":"filtered"}"#,
+        ));
+        let new = records(concat!(
+            r#"{"ip":"10.0.0.20","proto":"tcp","latency_ms":1,"status":"up"}"#,
+            "\n",
+            r#"{"ip":"10.0.0.9","proto":"icmp","latency_ms":1,"status":"up"}"#,
+        ));
+        let changes = diff(&old, &new);
+        assert_eq!(changes.new_hosts, ["10.0.0.9", "10.0.0.20"]);
+        // Only filtered ports: 10.0.0.3 never answered.
+        assert_eq!(changes.vanished_hosts, ["10.0.0.10"]);
+    }
+
+    #[test]
+    fn test_renumbered_host_is_not_a_change() {
+        let old = records(
+            r#"{"ip":"10.0.0.5","mac":"aa:bb:cc:00:11:22","proto":"arp","latency_ms":1,"status":"up"}"#,
+        );
+        let new = records(concat!(
+            r#"{"ip":"10.0.0.8","mac":"AA:BB:CC:00:11:22","proto":"arp","latency_ms":1,"status":"up"}"#,
+            "\n",
+            r#"{"ip":"10.0.0.8","port":22,"proto":"tcp","latency_ms":3,"status":"open"}"#,
+        ));
+        let changes = diff(&old, &new);
+        assert!(changes.new_hosts.is_empty() && changes.vanished_hosts.is_empty());
+        assert_eq!(changes.opened_ports[0].ip, "10.0.0.8");
+    }
+}
@@ -0,0 +1,88 @@
+//! Stable host identities across saved scans.
+//!
+//! DHCP hands devices a different address now and then, so comparing scans
+//! by IP alone makes a renumbered laptop look like one asset vanishing and
+//! a new one appearing. Where a report knows more about a host than its
+//! address, that is a better key: the MAC address (found through ARP) names
+//! the network card itself, and a reverse-DNS hostname usually follows the
+//! device across leases. [`host_identities`] picks the most durable one
+//! available for every address in a report.
+
+use std::collections::HashMap;
+
+use super::ScanRecord;
+
+/// The identity of every host in `records`, by address: its MAC address
+/// when any record of the address carries one, else its hostname, else the
+/// address itself. MACs and hostnames are lowercased, so the same device
+/// matches however a report spelled them.
+///
+/// Only the records of one report should be passed: identities are only
+/// stable across reports, not within one where two addresses could share a
+/// hostname.
+///
+/// # Examples
+///
+/// ```
+/// use asphyxia::output::identity::host_identities;
+/// use asphyxia::output::read_records;
+///
+/// let records = read_records(concat!(
+///     r#"{"ip":"10.0.0.5","mac":"AA:BB:CC:00:11:22","proto":"arp","latency_ms":1,"status":"up"}"#, "\n",
+///     r#"{"ip":"10.0.0.5","port":22,"proto":"tcp","latency_ms":3,"status":"open"}"#, "\n",
+///     r#"{"ip":"10.0.0.9","proto":"tcp","latency_ms":1,"status":"up"}"#,
+/// ))
+/// .unwrap();
+/// let ids = host_identities(&records);
+/// assert_eq!(ids["10.0.0.5"], "aa:bb:cc:00:11:22");
+/// assert_eq!(ids["10.0.0.9"], "10.0.0.9");
+/// ```
+pub fn host_identities(records: &[ScanRecord]) -> HashMap<&str, String> {
+    let mut macs: HashMap<&str, &str> = HashMap::new();
+    let mut hostnames: HashMap<&str, &str> = HashMap::new();
+    for record in records {
+        if let Some(mac) = &record.mac {
+            macs.entry(&record.ip).or_insert(mac);
+        }
+        if let Some(hostname) = &record.hostname {
+            hostnames.entry(&record.ip).or_insert(hostname);
+        }
+    }
+    records
+        .iter()
+        .map(|record| {
+            let ip = record.ip.as_str();
+            let id = macs
+                .get(ip)
+                .or_else(|| hostnames.get(ip))
+                .map_or_else(|| ip.to_string(), |id| id.to_ascii_lowercase());
+            (ip, id)
+        })
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::output::read_records;
+
+    #[test]
+    fn test_hostname_identifies_host_without_mac() {
+        let records = read_records(concat!(
+            r#"{"ip":"10.0.0.7","hostname":"NAS.lan","proto":"tcp","latency_ms":1,"status":"up"}"#,
+            "\n",
+            r#"{"ip":"10.0.0.7","port":445,"proto":"tcp","latency_ms":2,"status":"open"}"#,
+        ))
+        .unwrap();
+        assert_eq!(host_identities(&records)["10.0.0.7"], "nas.lan");
+    }
+
+    #[test]
+    fn test_mac_wins_over_hostname() {
+        let records = read_records(
+            r#"{"ip":"10.0.0.7","hostname":"nas.lan","mac":"aa:bb:cc:00:11:22","proto":"arp","latency_ms":1,"status":"up"}"#,
+        )
+        .unwrap();
+        assert_eq!(host_identities(&records)["10.0.0.7"], "aa:bb:cc:00:11:22");
+    }
+}
@@ -0,0 +1,306 @@
+//! Latency summaries and regressions across saved scans.
+//!
+//! Every answered probe records how long the host took to answer. A
+//! [`LatencySummary`] condenses those figures for one scan, which makes
+//! distant or overloaded hosts stand out during a sweep.
+//!
+//! Routine scans of the same network double as a cheap performance monitor:
+//! every answered probe records how long the host took. Comparing a host's
+//! latency in the latest scan against its history flags hosts that have
+//! become markedly slower (an overloaded server, a congested link, a
+//! failing NIC) before anyone reports it.
+
+use std::collections::BTreeMap;
+use std::time::Duration;
+
+use super::identity::host_identities;
+use super::timeline::Snapshot;
+
+/// Smallest rise, in milliseconds, that counts as a regression however large
+/// it is in percent, so a host going from 1 ms to 3 ms is not flagged.
+pub const MIN_INCREASE_MS: u128 = 5;
+
+/// Lowest, mean and highest latency over a set of probes, and how much it
+/// varies.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct LatencySummary {
+    /// The fastest answer.
+    pub min: Duration,
+    /// The mean over all answers.
+    pub avg: Duration,
+    /// The slowest answer.
+    pub max: Duration,
+    /// The (population) standard deviation from the mean.
+    pub stddev: Duration,
+}
+
+impl LatencySummary {
+    /// Summarize `latencies`, or return `None` if there are none.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use asphyxia::output::latency::LatencySummary;
+    /// use std::time::Duration;
+    ///
+    /// let ms = Duration::from_millis;
+    /// let summary = LatencySummary::from_latencies([ms(4), ms(1), ms(7)]).unwrap();
+    /// assert_eq!((summary.min, summary.avg, summary.max), (ms(1), ms(4), ms(7)));
+    /// assert_eq!(summary.stddev.as_millis(), 2);
+    /// assert_eq!(LatencySummary::from_latencies([]), None);
+    /// ```
+    pub fn from_latencies<I: IntoIterator<Item = Duration>>(latencies: I) -> Option<Self> {
+        let latencies: Vec<Duration> = latencies.into_iter().collect();
+        let count = latencies.len() as u32;
+        if count == 0 {
+            return None;
+        }
+        let avg = latencies.iter().sum::<Duration>() / count;
+        let variance = latencies
+            .iter()
+            .map(|latency| (latency.as_secs_f64() - avg.as_secs_f64()).powi(2))
+            .sum::<f64>()
+            / f64::from(count);
+        Some(LatencySummary {
+            min: latencies.iter().copied().min()?,
+            avg,
+            max: latencies.iter().copied().max()?,
+            stddev: Duration::from_secs_f64(variance.sqrt()),
+        })
+    }
+}
+
+/// A host whose latency in the latest scan rose beyond the threshold.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct LatencyRegression {
+    /// The host's address in the latest scan.
+    pub ip: String,
+    /// Median latency over the earlier scans, in milliseconds.
+    pub baseline_ms: u128,
+    /// Median latency in the latest scan, in milliseconds.
+    pub latest_ms: u128,
+}
+
+impl LatencyRegression {
+    /// How much slower the host got, in percent of its baseline.
+    pub fn increase_percent(&self) -> u128 {
+        (self.latest_ms - self.baseline_ms) * 100 / self.baseline_ms.max(1)
+    }
+}
+
+/// Compare every host's latency in the last snapshot against the earlier
+/// ones and return the hosts that got more than `threshold_percent` slower
+/// (and at least [`MIN_INCREASE_MS`] slower), the worst first.
+///
+/// A host's latency in one scan is the median over its answered records
+/// (the host itself and its open or closed ports); filtered ports and down
+/// hosts only measure the timeout and are ignored. Its baseline is the
+/// median of its per-scan latencies over all earlier snapshots. Hosts are
+/// matched across snapshots by their
+/// [`host_identities`](super::identity::host_identities), so a renumbered
+/// device keeps its history. Hosts missing from the last snapshot or from
+/// every earlier one are skipped.
+///
+/// # Examples
+///
+/// ```
+/// use asphyxia::output::latency::latency_regressions;
+/// use asphyxia::output::read_records;
+/// use asphyxia::output::timeline::Snapshot;
+///
+/// let scan = |ms: u32| {
+///     let line = format!(r#"{{"ip":"10.0.0.5","proto":"tcp","latency_ms":{ms},"status":"up"}}"#);
+///     Snapshot::new(read_records(&line).unwrap(), "scan")
+/// };
+/// let regressions = latency_regressions(&[scan(10), scan(12), scan(40)], 50);
+/// assert_eq!(regressions[0].baseline_ms, 11);
+/// assert_eq!(regressions[0].latest_ms, 40);
+/// ```
+pub fn latency_regressions(
+    snapshots: &[Snapshot],
+    threshold_percent: u32,
+) -> Vec<LatencyRegression> {
+    let Some((latest, earlier)) = snapshots.split_last() else {
+        return Vec::new();
+    };
+
+    let mut history: BTreeMap<String, Vec<u128>> = BTreeMap::new();
+    for snapshot in earlier {
+        for (host, (_, latency)) in host_latencies(snapshot) {
+            history.entry(host).or_default().push(latency);
+        }
+    }
+
+    let mut regressions: Vec<LatencyRegression> = host_latencies(latest)
+        .into_iter()
+        .filter_map(|(host, (ip, latest_ms))| {
+            let baseline_ms = median(history.remove(&host)?)?;
+            let increase = latest_ms.checked_sub(baseline_ms)?;
+            let beyond_threshold = increase * 100 > baseline_ms * u128::from(threshold_percent);
+            (increase >= MIN_INCREASE_MS && beyond_threshold).then(|| LatencyRegression {
+                ip: ip.to_string(),
+                baseline_ms,
+                latest_ms,
+            })
+        })
+        .collect();
+    regressions.sort_by(|a, b| {
+        b.increase_percent()
+            .cmp(&a.increase_percent())
+            .then_with(|| a.ip.cmp(&b.ip))
+    });
+    regressions
+}
+
+/// The address and median answered latency of every host in `snapshot`,
+/// by host identity.
+fn host_latencies(snapshot: &Snapshot) -> BTreeMap<String, (&str, u128)> {
+    let hosts = host_identities(&snapshot.records);
+    let mut samples: BTreeMap<String, (&str, Vec<u128>)> = BTreeMap::new();
+    for record in &snapshot.records {
+        if matches!(record.status.as_str(), "up" | "open" | "closed") {
+            samples
+                .entry(hosts[record.ip.as_str()].clone())
+                .or_insert_with(|| (&record.ip, Vec::new()))
+                .1
+                .push(record.latency_ms);
+        }
+    }
+    samples
+        .into_iter()
+        .filter_map(|(host, (ip, latencies))| Some((host, (ip, median(latencies)?))))
+        .collect()
+}
+
+/// The median of `values` (the mean of the middle two for an even count).
+fn median(mut values: Vec<u128>) -> Option<u128> {
+    values.sort_unstable();
+    let mid = values.len() / 2;
+    match values.len() {
+        0 => None,
+        len if len % 2 == 1 => Some(values[mid]),
+        _ => Some((values[mid - 1] + values[mid]) / 2),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::output::ScanRecord;
+
+    fn record(ip: &str, port: Option<u16>, latency_ms: u128, status: &str) -> ScanRecord {
+        ScanRecord {
+            format_version: 1,
+            ip: ip.to_string(),
+            hostname: None,
+            mac: None,
+            port,
+            proto: "tcp".to_string(),
+            service: None,
+            latency_ms,
+            status: status.to_string(),
+            timestamp: None,
+            ack: None,
+            hops: None,
+            path_mtu: None,
+            ttl: None,
+            hop_estimate: None,
+            evidence: None,
+            attempts: None,
+            os_guess: None,
+            product: None,
+            version: None,
+            http_status: None,
+            http_server: None,
+            http_title: None,
+            tls_subject: None,
+            tls_issuer: None,
+            tls_sans: None,
+            tls_expires: None,
+            operator: None,
+            note: None,
+            outages: None,
+            annotation: None,
+        }
+    }
+
+    fn scan(records: Vec<ScanRecord>) -> Snapshot {
+        Snapshot::new(records, "scan")
+    }
+
+    #[test]
+    fn test_summary_of_one_latency() {
+        let latency = Duration::from_micros(1500);
+        let summary = LatencySummary::from_latencies([latency]).unwrap();
+        assert_eq!(summary.min, latency);
+        assert_eq!(summary.avg, latency);
+        assert_eq!(summary.max, latency);
+        assert_eq!(summary.stddev, Duration::ZERO);
+    }
+
+    #[test]
+    fn test_median_of_odd_and_even_counts() {
+        assert_eq!(median(vec![5, 1, 3]), Some(3));
+        assert_eq!(median(vec![4, 1, 3, 2]), Some(2));
+        assert_eq!(median(Vec::new()), None);
+    }
+
+    #[test]
+    fn test_filtered_ports_do_not_count() {
+        let snapshot = scan(vec![
+            record("10.0.0.5", None, 4, "up"),
+            record("10.0.0.5", Some(22), 6, "open"),
+            record("10.0.0.5", Some(23), 2000, "filtered"),
+        ]);
+        assert_eq!(host_latencies(&snapshot)["10.0.0.5"], ("10.0.0.5", 5));
+    }
+
+    #[test]
+    fn test_flags_only_rises_beyond_threshold() {
+        let snapshots = [
+            scan(vec![
+                record("10.0.0.5", None, 10, "up"),
+                record("10.0.0.6", None, 10, "up"),
+                record("10.0.0.7", None, 1, "up"),
+            ]),
+            scan(vec![
+                record("10.0.0.5", None, 30, "up"),
+                record("10.0.0.6", None, 14, "up"),
+                record("10.0.0.7", None, 4, "up"),
+                record("10.0.0.8", None, 90, "up"),
+            ]),
+        ];
+        let regressions = latency_regressions(&snapshots, 50);
+        // .6 rose 40%, .7 rose only 3 ms, and .8 has no history.
+        assert_eq!(
+            regressions,
+            vec![LatencyRegression {
+                ip: "10.0.0.5".to_string(),
+                baseline_ms: 10,
+                latest_ms: 30,
+            }]
+        );
+        assert_eq!(regressions[0].increase_percent(), 200);
+    }
+
+    #[test]
+    fn test_single_snapshot_has_no_baseline() {
+        let snapshots = [scan(vec![record("10.0.0.5", None, 10, "up")])];
+        assert!(latency_regressions(&snapshots, 0).is_empty());
+    }
+
+    #[test]
+    fn test_renumbered_host_keeps_its_history() {
+        let with_mac = |ip: &str, latency_ms| ScanRecord {
+            mac: Some("aa:bb:cc:00:11:22".to_string()),
+            ..record(ip, None, latency_ms, "up")
+        };
+        let snapshots = [
+            scan(vec![with_mac("10.0.0.5", 10)]),
+            scan(vec![with_mac("10.0.0.23", 40)]),
+        ];
+        let regressions = latency_regressions(&snapshots, 50);
+        assert_eq!(regressions.len(), 1);
+        assert_eq!(regressions[0].ip, "10.0.0.23");
+    }
+}
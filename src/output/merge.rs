@@ -0,0 +1,104 @@
+//! Combining several stored reports into one.
+//!
+//! Large networks are often scanned in pieces: different port ranges, or
+//! different subnets from different vantage points. [`merge_reports`] joins
+//! the pieces into a single report with one record per finding, so the
+//! result reads (and diffs) like one scan of the whole.
+
+use std::collections::HashMap;
+
+use super::{RecordOrder, ScanRecord, sort_records};
+
+/// Merge the records of several reports, oldest report first.
+///
+/// A finding is identified by its address, port and protocol (a host record
+/// has no port). When several reports contain the same finding, the record
+/// with the most recent `timestamp` wins; records without a timestamp count
+/// as oldest, and between equally recent records the one from the later
+/// report wins. The merged records are ordered by address, then port.
+///
+/// # Examples
+///
+/// ```
+/// use asphyxia::output::merge::merge_reports;
+/// use asphyxia::output::read_records;
+///
+/// let monday = read_records(
+///     r#"{"ip":"10.0.0.5","port":22,"proto":"tcp","latency_ms":3,"status":"open","timestamp":"2026-05-04T09:00:00Z"}"#,
+/// )
+/// .unwrap();
+/// let tuesday = read_records(
+///     r#"{"ip":"10.0.0.5","port":22,"proto":"tcp","latency_ms":2,"status":"closed","timestamp":"2026-05-05T09:00:00Z"}"#,
+/// )
+/// .unwrap();
+/// let merged = merge_reports(vec![monday, tuesday]);
+/// assert_eq!(merged.len(), 1);
+/// assert_eq!(merged[0].status, "closed");
+/// ```
+pub fn merge_reports(reports: Vec<Vec<ScanRecord>>) -> Vec<ScanRecord> {
+    let mut latest: HashMap<(String, Option<u16>, String), ScanRecord> = HashMap::new();
+    for record in reports.into_iter().flatten() {
+        let key = (record.ip.clone(), record.port, record.proto.clone());
+        match latest.get(&key) {
+            Some(kept) if kept.timestamp > record.timestamp => {}
+            _ => {
+                latest.insert(key, record);
+            }
+        }
+    }
+    let mut merged: Vec<ScanRecord> = latest.into_values().collect();
+    sort_records(&mut merged, RecordOrder::Ip);
+    merged
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::output::read_records;
+
+    fn records(jsonl: &str) -> Vec<ScanRecord> {
+        read_records(jsonl).unwrap()
+    }
+
+    #[test]
+    fn test_merge_keeps_disjoint_findings() {
+        let low =
+            records(r#"{"ip":"10.0.0.5","port":22,"proto":"tcp","latency_ms":3,"status":"open"}"#);
+        let high = records(concat!(
+            r#"{"ip":"10.0.0.5","port":8080,"proto":"tcp","latency_ms":3,"status":"open"}"#,
+            "\n",
+            r#"{"ip":"10.0.0.10","proto":"tcp","latency_ms":1,"status":"up"}"#,
+        ));
+        let merged = merge_reports(vec![high, low]);
+        let keys: Vec<(&str, Option<u16>)> =
+            merged.iter().map(|r| (r.ip.as_str(), r.port)).collect();
+        assert_eq!(
+            keys,
+            vec![
+                ("10.0.0.5", Some(22)),
+                ("10.0.0.5", Some(8080)),
+                ("10.0.0.10", None)
+            ]
+        );
+    }
+
+    #[test]
+    fn test_merge_prefers_newer_record_over_later_report() {
+        let newer = records(
+            r#"{"ip":"10.0.0.5","port":22,"proto":"tcp","latency_ms":3,"status":"open","timestamp":"2026-05-05T09:00:00Z"}"#,
+        );
+        let older = records(
+            r#"{"ip":"10.0.0.5","port":22,"proto":"tcp","latency_ms":3,"status":"filtered","timestamp":"2026-05-04T09:00:00Z"}"#,
+        );
+        let merged = merge_reports(vec![newer, older]);
+        assert_eq!(merged.len(), 1);
+        assert_eq!(merged[0].status, "open");
+    }
+
+    #[test]
+    fn test_merge_without_timestamps_takes_later_report() {
+        let first = records(r#"{"ip":"10.0.0.5","proto":"tcp","latency_ms":9,"status":"up"}"#);
+        let second = records(r#"{"ip":"10.0.0.5","proto":"tcp","latency_ms":2,"status":"up"}"#);
+        assert_eq!(merge_reports(vec![first, second])[0].latency_ms, 2);
+    }
+}
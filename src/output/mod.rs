@@ -5,13 +5,49 @@
 //! scanner can act as the first stage of a pipeline (e.g. feeding a network
 //! map or coverage analyzer) rather than only being read by a human.
 //!
-//! Machine output goes to stdout, one self-contained stream:
+//! Machine output goes to stdout (or a file), one self-contained stream:
 //! [`OutputFormat::Json`] emits a single JSON array, [`OutputFormat::Jsonl`]
-//! emits one JSON object per line (JSON Lines). The progress bar stays on
+//! emits one JSON object per line (JSON Lines), and [`OutputFormat::NmapXml`]
+//! emits an nmap-compatible XML document (see [`nmap_xml`]). The progress bar stays on
 //! stderr, so a consumer reading stdout sees only records.
+//!
+//! Every record carries a `format_version`. Tools built on top of asphyxia
+//! should read reports back through [`read_records`], which accepts every
+//! version this build knows about and upgrades it to the current
+//! [`ScanRecord`] shape, so stored reports keep working as the model evolves.
+//! Two stored reports of the same network can be compared with [`diff()`],
+//! and several over time with [`timeline`], checked for hosts that got slower with [`latency`], and
+//! combined into one report with [`merge`]. A running monitor reports what
+//! changed to webhooks and commands through [`notify`].
+
+pub mod ack;
+pub mod diff;
+pub mod identity;
+pub mod latency;
+pub mod merge;
+pub mod nmap_xml;
+pub mod notify;
+pub mod timeline;
 
+use chrono::{DateTime, Local, SecondsFormat, Utc};
 use clap::ValueEnum;
-use serde::Serialize;
+use serde::{Deserialize, Serialize};
+use serde_json::Value;
+use std::io::{self, Write};
+use std::net::IpAddr;
+
+use crate::scanner::annotations::Annotation;
+use crate::scanner::outage::Outage;
+use ack::Ack;
+
+pub use diff::diff;
+
+/// Version of the record schema written by this build.
+///
+/// Bumped whenever a field is removed, renamed, or changes meaning; adding an
+/// optional field does not require a bump. Reports written before the field
+/// existed carry no `format_version` and are read as version 1.
+pub const FORMAT_VERSION: u32 = 1;
 
 /// How scan results are rendered to stdout.
 #[derive(Debug, Clone, Copy, PartialEq, Eq, ValueEnum)]
@@ -22,36 +58,404 @@ pub enum OutputFormat {
     Json,
     /// JSON Lines: one [`ScanRecord`] object per line.
     Jsonl,
+    /// nmap-compatible XML, for tools that already read nmap results.
+    NmapXml,
 }
 
 /// One scan result in a normalized, machine-readable shape.
 ///
 /// The fields are a superset of what a port scan and an address scan each
 /// produce: `port` is present for port scans and omitted for host discovery.
-#[derive(Debug, Serialize)]
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
 pub struct ScanRecord {
+    /// Schema version of this record (see [`FORMAT_VERSION`]).
+    #[serde(default)]
+    pub format_version: u32,
     /// Target address (resolved IP for a port scan, host IP for discovery).
     pub ip: String,
+    /// Hostname from a reverse DNS lookup (`--resolve`), when one was found.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub hostname: Option<String>,
+    /// Hardware (MAC) address, when the host was found through ARP.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub mac: Option<String>,
     /// Open port; omitted for address (host-availability) scans.
-    #[serde(skip_serializing_if = "Option::is_none")]
+    #[serde(default, skip_serializing_if = "Option::is_none")]
     pub port: Option<u16>,
     /// Transport protocol of the probe.
-    pub proto: &'static str,
+    pub proto: String,
+    /// Registered service name for the port (e.g. `ssh`), or the service
+    /// version detection found listening on it (`--version-detect`);
+    /// omitted for unassigned ports and address scans.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub service: Option<String>,
     /// Wall-clock latency of the probe, in milliseconds.
     pub latency_ms: u128,
-    /// `"open"` for an open port, `"up"` for an available host.
-    pub status: &'static str,
+    /// `"open"` for an open port (`"closed"` or `"filtered"` too with
+    /// `ps -vv`), `"up"` for an available host.
+    pub status: String,
+    /// When the scan that produced the record completed (RFC 3339, UTC).
+    /// Absent in reports written before timestamps were recorded.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub timestamp: Option<DateTime<Utc>>,
+    /// Present when the finding matches an active entry in the ack file.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub ack: Option<Ack>,
+    /// Route to the host (`--trace-discovered`): the address that answered
+    /// at each TTL, `null` where none did. Only on host records.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub hops: Option<Vec<Option<String>>>,
+    /// Path MTU to the host in bytes (`--pmtu`). Only on host records.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub path_mtu: Option<u16>,
+    /// TTL the answer arrived with, when the probe could see it (SYN port
+    /// probes and ICMP host probes over IPv4).
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub ttl: Option<u8>,
+    /// Routers between the scanner and the host, estimated from `ttl`
+    /// (see [`estimate_hops`](crate::scanner::ttl::estimate_hops)).
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub hop_estimate: Option<u8>,
+    /// What the probe saw that decided `status` (see
+    /// [`Evidence::as_str`](crate::scanner::port::Evidence::as_str), e.g.
+    /// `syn-ack` or `no-response`). Only on port records.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub evidence: Option<String>,
+    /// How many probes the port took, retries included. Only on port
+    /// records.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub attempts: Option<u32>,
+    /// The host's likely OS family (`--os`), from its stack fingerprint
+    /// (see [`os_guess`](crate::scanner::fingerprint::StackFingerprint::os_guess)).
+    /// Only on port records.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub os_guess: Option<String>,
+    /// Software behind the port, as named by its answers to version
+    /// detection probes (`--version-detect`), e.g. `OpenSSH`.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub product: Option<String>,
+    /// Version of that software (`--version-detect`), e.g. `9.6p1`.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub version: Option<String>,
+    /// Status code of the answer to `GET /` (`--http-probe`). Only on
+    /// records of open web ports.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub http_status: Option<u16>,
+    /// `Server` header of that answer (`--http-probe`).
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub http_server: Option<String>,
+    /// Page title of that answer (`--http-probe`).
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub http_title: Option<String>,
+    /// Subject of the certificate presented in a TLS handshake
+    /// (`--tls-info`). Only on records of open ports that speak TLS.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub tls_subject: Option<String>,
+    /// Issuer of that certificate (`--tls-info`).
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub tls_issuer: Option<String>,
+    /// DNS names and addresses the certificate is valid for (`--tls-info`).
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub tls_sans: Option<Vec<String>>,
+    /// When the certificate expires (`--tls-info`).
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub tls_expires: Option<DateTime<Utc>>,
+    /// Who ran the scan (`--operator`).
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub operator: Option<String>,
+    /// Free-form note on the engagement (`--note`), e.g. the change ticket
+    /// the scan was run under.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub note: Option<String>,
+    /// Spans of the scan during which the scanning host was offline and
+    /// probing paused (see [`outage`](crate::scanner::outage)). Only present
+    /// when there was one.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub outages: Option<Vec<Outage>>,
+    /// What the annotations file (`--annotations`) says about the host:
+    /// its note, custom attributes and probe limits.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub annotation: Option<Annotation>,
+}
+
+/// Render a timestamp as RFC 3339 for human-readable output.
+///
+/// Timestamps are UTC (`...Z`) by default; with `local` they are shown in the
+/// machine's local time zone with its offset, e.g. `2026-05-01T11:30:00+02:00`.
+/// Either form names the same instant, so it can be correlated with other logs.
+///
+/// # Examples
+///
+/// ```
+/// use asphyxia::output::format_timestamp;
+///
+/// let ts = "2026-05-01T09:30:00Z".parse().unwrap();
+/// assert_eq!(format_timestamp(ts, false), "2026-05-01T09:30:00Z");
+/// ```
+pub fn format_timestamp(timestamp: DateTime<Utc>, local: bool) -> String {
+    if local {
+        timestamp
+            .with_timezone(&Local)
+            .to_rfc3339_opts(SecondsFormat::Secs, true)
+    } else {
+        timestamp.to_rfc3339_opts(SecondsFormat::Secs, true)
+    }
+}
+
+/// How [`sort_records`] orders the records of a stored report.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, ValueEnum)]
+pub enum RecordOrder {
+    /// By address (numerically), then port.
+    Ip,
+    /// By port, then address; host records (no port) first.
+    Port,
+    /// Slowest first.
+    Latency,
+    /// Oldest first; records without a timestamp first.
+    Time,
+}
+
+/// Sort `records` in place by `order`. The sort is stable, so records that
+/// compare equal keep the order they were stored in.
+///
+/// # Examples
+///
+/// ```
+/// use asphyxia::output::{RecordOrder, read_records, sort_records};
+///
+/// let mut records = read_records(concat!(
+///     r#"{"ip":"10.0.0.10","proto":"tcp","latency_ms":3,"status":"up"}"#, "\n",
+///     r#"{"ip":"10.0.0.9","proto":"tcp","latency_ms":8,"status":"up"}"#,
+/// ))
+/// .unwrap();
+/// sort_records(&mut records, RecordOrder::Ip);
+/// assert_eq!(records[0].ip, "10.0.0.9");
+/// ```
+pub fn sort_records(records: &mut [ScanRecord], order: RecordOrder) {
+    match order {
+        RecordOrder::Ip => {
+            records.sort_by(|a, b| compare_ips(&a.ip, &b.ip).then(a.port.cmp(&b.port)))
+        }
+        RecordOrder::Port => {
+            records.sort_by(|a, b| a.port.cmp(&b.port).then_with(|| compare_ips(&a.ip, &b.ip)))
+        }
+        RecordOrder::Latency => records.sort_by_key(|record| std::cmp::Reverse(record.latency_ms)),
+        RecordOrder::Time => records.sort_by_key(|record| record.timestamp),
+    }
+}
+
+/// Compare two record addresses numerically where both parse as IPs,
+/// falling back to their text.
+fn compare_ips(a: &str, b: &str) -> std::cmp::Ordering {
+    match (a.parse::<IpAddr>(), b.parse::<IpAddr>()) {
+        (Ok(a), Ok(b)) => a.cmp(&b),
+        _ => a.cmp(b),
+    }
 }
 
 /// Print all records as a single JSON array. An empty slice prints `[]`.
 pub fn print_json(records: &[ScanRecord]) {
-    // Serializing a slice of serializable records cannot fail.
-    println!("{}", serde_json::to_string(records).unwrap());
+    // Writing to stdout only fails if stdout is closed, like `println!`.
+    write_json(io::stdout().lock(), records).expect("failed to write to stdout");
 }
 
 /// Print one JSON object per line (JSON Lines). An empty slice prints nothing.
 pub fn print_jsonl(records: &[ScanRecord]) {
+    write_jsonl(io::stdout().lock(), records).expect("failed to write to stdout");
+}
+
+/// Write all records to `out` as a single JSON array followed by a newline.
+pub fn write_json<W: Write>(mut out: W, records: &[ScanRecord]) -> io::Result<()> {
+    // Serializing a slice of serializable records cannot fail.
+    writeln!(out, "{}", serde_json::to_string(records).unwrap())?;
+    out.flush()
+}
+
+/// Write one JSON object per line (JSON Lines) to `out`.
+pub fn write_jsonl<W: Write>(mut out: W, records: &[ScanRecord]) -> io::Result<()> {
     for record in records {
-        println!("{}", serde_json::to_string(record).unwrap());
+        writeln!(out, "{}", serde_json::to_string(record).unwrap())?;
+    }
+    out.flush()
+}
+
+/// Read records back from a stored JSON or JSON Lines report.
+///
+/// The input may be a JSON array (as written by [`OutputFormat::Json`]) or one
+/// object per line (as written by [`OutputFormat::Jsonl`]); blank lines are
+/// ignored. Records of any supported `format_version` — including unversioned
+/// version 1 reports — are upgraded to the current [`ScanRecord`] shape.
+/// Records from a newer, unknown version are rejected rather than guessed at.
+///
+/// # Examples
+///
+/// ```
+/// use asphyxia::output::{FORMAT_VERSION, read_records};
+///
+/// // An unversioned record from an older release is still readable.
+/// let records = read_records(
+///     r#"{"ip":"10.0.0.1","port":22,"proto":"tcp","latency_ms":3,"status":"open"}"#,
+/// )
+/// .unwrap();
+/// assert_eq!(records[0].port, Some(22));
+/// assert_eq!(records[0].format_version, FORMAT_VERSION);
+/// ```
+pub fn read_records(input: &str) -> Result<Vec<ScanRecord>, String> {
+    let input = input.trim_start();
+    let values: Vec<Value> = if input.starts_with('[') {
+        serde_json::from_str(input).map_err(|e| format!("Invalid JSON report: {}", e))?
+    } else {
+        input
+            .lines()
+            .enumerate()
+            .filter(|(_, line)| !line.trim().is_empty())
+            .map(|(i, line)| {
+                serde_json::from_str(line)
+                    .map_err(|e| format!("Invalid JSON on line {}: {}", i + 1, e))
+            })
+            .collect::<Result<_, _>>()?
+    };
+    values.into_iter().map(upgrade_record).collect()
+}
+
+/// Bring a single stored record up to the current [`FORMAT_VERSION`].
+fn upgrade_record(value: Value) -> Result<ScanRecord, String> {
+    let version = match value.get("format_version") {
+        // Written before records were versioned.
+        None => 1,
+        Some(v) => v
+            .as_u64()
+            .ok_or_else(|| format!("Invalid format_version: {}", v))?,
+    };
+    if version == 0 || version > u64::from(FORMAT_VERSION) {
+        return Err(format!(
+            "Unsupported format_version {} (this build reads 1 to {})",
+            version, FORMAT_VERSION
+        ));
+    }
+    // Every supported version still shares the version 1 layout; migrations
+    // from older layouts belong here once the schema changes.
+    let mut record: ScanRecord =
+        serde_json::from_value(value).map_err(|e| format!("Invalid scan record: {}", e))?;
+    record.format_version = FORMAT_VERSION;
+    Ok(record)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn record() -> ScanRecord {
+        ScanRecord {
+            format_version: FORMAT_VERSION,
+            ip: "10.0.0.1".to_string(),
+            hostname: None,
+            mac: None,
+            port: Some(443),
+            proto: "tcp".to_string(),
+            service: Some("https".to_string()),
+            latency_ms: 12,
+            status: "open".to_string(),
+            timestamp: Some("2026-01-02T03:04:05Z".parse().unwrap()),
+            ack: None,
+            hops: None,
+            path_mtu: None,
+            ttl: None,
+            hop_estimate: None,
+            evidence: None,
+            attempts: None,
+            os_guess: None,
+            product: None,
+            version: None,
+            http_status: None,
+            http_server: None,
+            http_title: None,
+            tls_subject: None,
+            tls_issuer: None,
+            tls_sans: None,
+            tls_expires: None,
+            operator: None,
+            note: None,
+            outages: None,
+            annotation: None,
+        }
+    }
+
+    #[test]
+    fn test_format_timestamp_local_names_same_instant() {
+        let ts: DateTime<Utc> = "2026-01-02T03:04:05Z".parse().unwrap();
+        let local: DateTime<Utc> = format_timestamp(ts, true).parse().unwrap();
+        assert_eq!(local, ts);
+    }
+
+    #[test]
+    fn test_records_carry_format_version() {
+        let json = serde_json::to_string(&record()).unwrap();
+        assert!(json.contains(&format!("\"format_version\":{}", FORMAT_VERSION)));
+    }
+
+    #[test]
+    fn test_read_records_round_trips_json_and_jsonl() {
+        let records = vec![record(), record()];
+        let json = serde_json::to_string(&records).unwrap();
+        assert_eq!(read_records(&json).unwrap(), records);
+
+        let jsonl: String = records
+            .iter()
+            .map(|r| serde_json::to_string(r).unwrap() + "\n")
+            .collect();
+        assert_eq!(read_records(&jsonl).unwrap(), records);
+    }
+
+    #[test]
+    fn test_timestamp_is_rfc3339_utc() {
+        let json = serde_json::to_string(&record()).unwrap();
+        assert!(json.contains("\"timestamp\":\"2026-01-02T03:04:05Z\""));
+    }
+
+    #[test]
+    fn test_read_records_accepts_unversioned_v1() {
+        let v1 = r#"[{"ip":"10.0.0.2","proto":"tcp","latency_ms":1,"status":"up"}]"#;
+        let records = read_records(v1).unwrap();
+        assert_eq!(records[0].format_version, FORMAT_VERSION);
+        assert_eq!(records[0].port, None);
+    }
+
+    #[test]
+    fn test_read_records_rejects_future_version() {
+        let future =
+            r#"{"format_version":999,"ip":"10.0.0.1","proto":"tcp","latency_ms":1,"status":"up"}"#;
+        assert!(read_records(future).unwrap_err().contains("Unsupported"));
+    }
+
+    #[test]
+    fn test_read_records_reports_bad_line() {
+        let err = read_records("{}\nnot json\n").unwrap_err();
+        assert!(err.contains("line 2"));
+    }
+
+    #[test]
+    fn test_sort_records_by_port_puts_hosts_first() {
+        let host = ScanRecord {
+            port: None,
+            ..record()
+        };
+        let ssh = ScanRecord {
+            ip: "10.0.0.2".to_string(),
+            port: Some(22),
+            latency_ms: 30,
+            ..record()
+        };
+        let mut records = vec![record(), ssh.clone(), host.clone()];
+        sort_records(&mut records, RecordOrder::Port);
+        assert_eq!(records, vec![host.clone(), ssh.clone(), record()]);
+        sort_records(&mut records, RecordOrder::Latency);
+        assert_eq!(records[0], ssh);
+    }
+
+    #[test]
+    fn test_compare_ips_is_numeric() {
+        assert!(compare_ips("10.0.0.9", "10.0.0.10").is_lt());
+        assert!(compare_ips("10.0.0.9", "host.example").is_lt());
     }
 }
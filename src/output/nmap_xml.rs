@@ -0,0 +1,395 @@
+//! nmap-compatible XML output.
+//!
+//! Many tools (Metasploit's `db_import`, ndiff, EyeWitness, ...) already
+//! consume nmap's XML format. Writing the same shape lets them ingest
+//! asphyxia results without a converter. Only the elements those tools rely
+//! on are produced: one `<host>` per address with its status, addresses,
+//! hostname, ports and service names, plus the closing `<runstats>`.
+
+use chrono::{DateTime, Utc};
+use std::collections::HashMap;
+use std::io::{self, Write};
+use std::net::IpAddr;
+
+use super::ScanRecord;
+
+/// nmap XML schema version the output follows.
+const XML_OUTPUT_VERSION: &str = "1.05";
+
+/// Write records as an nmap XML document.
+///
+/// Records are grouped by `ip` in order of first appearance. A host with no
+/// record of its own (a port scan only reports ports) is still marked up,
+/// since an open port proves it answered. The operator and note of the scan
+/// (`--operator`, `--note`), which nmap's schema has no place for, go in a
+/// comment before the first host.
+///
+/// # Examples
+///
+/// ```
+/// use asphyxia::output::ScanRecord;
+/// use asphyxia::output::nmap_xml::write_nmap_xml;
+///
+/// let record = ScanRecord {
+///     format_version: 1,
+///     ip: "10.0.0.5".to_string(),
+///     hostname: None,
+///     mac: None,
+///     port: Some(22),
+///     proto: "tcp".to_string(),
+///     service: Some("ssh".to_string()),
+///     latency_ms: 3,
+///     status: "open".to_string(),
+///     timestamp: None,
+///     ack: None,
+///     hops: None,
+///     path_mtu: None,
+///     ttl: None,
+///     hop_estimate: None,
+///     evidence: None,
+///     attempts: None,
+///     os_guess: None,
+///     product: None,
+///     version: None,
+///     http_status: None,
+///     http_server: None,
+///     http_title: None,
+///     tls_subject: None,
+///     tls_issuer: None,
+///     tls_sans: None,
+///     tls_expires: None,
+///     operator: None,
+///     note: None,
+///     outages: None,
+///     annotation: None,
+/// };
+/// let mut xml = Vec::new();
+/// write_nmap_xml(&mut xml, &[record]).unwrap();
+/// let xml = String::from_utf8(xml).unwrap();
+/// assert!(xml.contains(r#"<port protocol="tcp" portid="22"><state state="open""#));
+/// ```
+pub fn write_nmap_xml<W: Write>(mut out: W, records: &[ScanRecord]) -> io::Result<()> {
+    let finished = records.iter().filter_map(|r| r.timestamp).max();
+    let started = records.iter().filter_map(|r| r.timestamp).min();
+
+    writeln!(out, r#"<?xml version="1.0" encoding="UTF-8"?>"#)?;
+    writeln!(out, "<!DOCTYPE nmaprun>")?;
+    write!(
+        out,
+        r#"<nmaprun scanner="asphyxia" version="{}" xmloutputversion="{}""#,
+        env!("CARGO_PKG_VERSION"),
+        XML_OUTPUT_VERSION
+    )?;
+    if let Some(started) = started {
+        write!(
+            out,
+            r#" start="{}" startstr="{}""#,
+            started.timestamp(),
+            timestr(started)
+        )?;
+    }
+    writeln!(out, ">")?;
+    if let Some(comment) = engagement_comment(records) {
+        writeln!(out, "<!-- {} -->", comment)?;
+    }
+
+    let hosts = group_by_host(records);
+    for host in &hosts {
+        write_host(&mut out, host)?;
+    }
+
+    write!(out, "<runstats><finished")?;
+    if let Some(finished) = finished {
+        write!(
+            out,
+            r#" time="{}" timestr="{}""#,
+            finished.timestamp(),
+            timestr(finished)
+        )?;
+    }
+    writeln!(out, r#" exit="success"/>"#)?;
+    writeln!(
+        out,
+        r#"<hosts up="{}" down="0" total="{}"/></runstats>"#,
+        hosts.len(),
+        hosts.len()
+    )?;
+    writeln!(out, "</nmaprun>")?;
+    out.flush()
+}
+
+/// The operator and note of the first record that has either, as XML
+/// comment text (which must not contain `--`).
+fn engagement_comment(records: &[ScanRecord]) -> Option<String> {
+    let record = records
+        .iter()
+        .find(|r| r.operator.is_some() || r.note.is_some())?;
+    let mut parts = Vec::new();
+    if let Some(operator) = &record.operator {
+        parts.push(format!("operator: {}", operator));
+    }
+    if let Some(note) = &record.note {
+        parts.push(format!("note: {}", note));
+    }
+    let mut comment = parts.join("; ");
+    while comment.contains("--") {
+        comment = comment.replace("--", "- -");
+    }
+    Some(comment)
+}
+
+/// All records for one address.
+struct Host<'a> {
+    ip: &'a str,
+    records: Vec<&'a ScanRecord>,
+}
+
+/// Group records by address, keeping the order in which addresses first appear.
+fn group_by_host(records: &[ScanRecord]) -> Vec<Host<'_>> {
+    let mut index: HashMap<&str, usize> = HashMap::new();
+    let mut hosts: Vec<Host<'_>> = Vec::new();
+    for record in records {
+        let slot = *index.entry(record.ip.as_str()).or_insert_with(|| {
+            hosts.push(Host {
+                ip: &record.ip,
+                records: Vec::new(),
+            });
+            hosts.len() - 1
+        });
+        hosts[slot].records.push(record);
+    }
+    hosts
+}
+
+fn write_host<W: Write>(out: &mut W, host: &Host<'_>) -> io::Result<()> {
+    let addrtype = match host.ip.parse::<IpAddr>() {
+        Ok(IpAddr::V6(_)) => "ipv6",
+        _ => "ipv4",
+    };
+    let hostname = host.records.iter().find_map(|r| r.hostname.as_deref());
+    let mac = host.records.iter().find_map(|r| r.mac.as_deref());
+    let ports: Vec<&&ScanRecord> = host.records.iter().filter(|r| r.port.is_some()).collect();
+
+    write!(out, "<host")?;
+    if let Some(ts) = host.records.iter().filter_map(|r| r.timestamp).max() {
+        write!(out, r#" endtime="{}""#, ts.timestamp())?;
+    }
+    writeln!(out, ">")?;
+    writeln!(
+        out,
+        r#"<status state="up" reason="{}"/>"#,
+        status_reason(host)
+    )?;
+    writeln!(
+        out,
+        r#"<address addr="{}" addrtype="{}"/>"#,
+        escape(host.ip),
+        addrtype
+    )?;
+    if let Some(mac) = mac {
+        writeln!(
+            out,
+            r#"<address addr="{}" addrtype="mac"/>"#,
+            escape(&mac.to_uppercase())
+        )?;
+    }
+    match hostname {
+        Some(name) => writeln!(
+            out,
+            r#"<hostnames><hostname name="{}" type="PTR"/></hostnames>"#,
+            escape(name)
+        )?,
+        None => writeln!(out, "<hostnames/>")?,
+    }
+    if !ports.is_empty() {
+        writeln!(out, "<ports>")?;
+        for record in ports {
+            write_port(out, record)?;
+        }
+        writeln!(out, "</ports>")?;
+    }
+    writeln!(out, "</host>")
+}
+
+fn write_port<W: Write>(out: &mut W, record: &ScanRecord) -> io::Result<()> {
+    // nmap has no separate reason for a completed connect handshake.
+    let reason = match (record.evidence.as_deref(), record.status.as_str()) {
+        (Some("handshake"), _) | (None, "open") => "syn-ack",
+        (Some(evidence), _) => evidence,
+        (None, "closed") => "reset",
+        (None, _) => "no-response",
+    };
+    write!(
+        out,
+        r#"<port protocol="{}" portid="{}"><state state="{}" reason="{}" reason_ttl="0"/>"#,
+        escape(&record.proto),
+        record.port.unwrap_or_default(),
+        escape(&record.status),
+        escape(reason)
+    )?;
+    if let Some(service) = &record.service {
+        write!(out, r#"<service name="{}""#, escape(service))?;
+        if let Some(product) = &record.product {
+            write!(out, r#" product="{}""#, escape(product))?;
+        }
+        if let Some(version) = &record.version {
+            write!(out, r#" version="{}""#, escape(version))?;
+        }
+        // A product or version means version detection matched an answer;
+        // otherwise the name only comes from the port number.
+        if record.product.is_some() || record.version.is_some() {
+            write!(out, r#" method="probed" conf="10"/>"#)?;
+        } else {
+            write!(out, r#" method="table" conf="3"/>"#)?;
+        }
+    }
+    writeln!(out, "</port>")
+}
+
+/// Why the host is considered up, in nmap's vocabulary.
+fn status_reason(host: &Host<'_>) -> &'static str {
+    let probe = host
+        .records
+        .iter()
+        .find(|r| r.port.is_none())
+        .map(|r| r.proto.as_str());
+    match probe {
+        Some("icmp") => "echo-reply",
+        Some("arp") => "arp-response",
+        _ => "syn-ack",
+    }
+}
+
+/// nmap's `timestr` rendering, e.g. `Fri May  1 09:30:00 2026`.
+fn timestr(ts: DateTime<Utc>) -> String {
+    ts.format("%a %b %e %H:%M:%S %Y").to_string()
+}
+
+/// Escape text for use in an XML attribute value.
+fn escape(text: &str) -> String {
+    let mut escaped = String::with_capacity(text.len());
+    for c in text.chars() {
+        match c {
+            '&' => escaped.push_str("&amp;"),
+            '<' => escaped.push_str("&lt;"),
+            '>' => escaped.push_str("&gt;"),
+            '"' => escaped.push_str("&quot;"),
+            '\'' => escaped.push_str("&apos;"),
+            c => escaped.push(c),
+        }
+    }
+    escaped
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn record(ip: &str, port: Option<u16>, status: &str) -> ScanRecord {
+        ScanRecord {
+            format_version: 1,
+            ip: ip.to_string(),
+            hostname: None,
+            mac: None,
+            port,
+            proto: "tcp".to_string(),
+            service: None,
+            latency_ms: 1,
+            status: status.to_string(),
+            timestamp: Some("2026-05-01T09:30:00Z".parse().unwrap()),
+            ack: None,
+            hops: None,
+            path_mtu: None,
+            ttl: None,
+            hop_estimate: None,
+            evidence: None,
+            attempts: None,
+            os_guess: None,
+            product: None,
+            version: None,
+            http_status: None,
+            http_server: None,
+            http_title: None,
+            tls_subject: None,
+            tls_issuer: None,
+            tls_sans: None,
+            tls_expires: None,
+            operator: None,
+            note: None,
+            outages: None,
+            annotation: None,
+        }
+    }
+
+    fn render(records: &[ScanRecord]) -> String {
+        let mut xml = Vec::new();
+        write_nmap_xml(&mut xml, records).unwrap();
+        String::from_utf8(xml).unwrap()
+    }
+
+    #[test]
+    fn test_groups_ports_under_one_host() {
+        let xml = render(&[
+            record("10.0.0.5", Some(22), "open"),
+            record("2001:db8::1", None, "up"),
+            record("10.0.0.5", Some(80), "open"),
+        ]);
+        assert_eq!(xml.matches("</host>").count(), 2);
+        assert!(xml.contains(r#"<address addr="2001:db8::1" addrtype="ipv6"/>"#));
+        assert!(xml.contains(r#"<hosts up="2" down="0" total="2"/>"#));
+        assert!(xml.contains(r#"start="1777627800" startstr="Fri May  1 09:30:00 2026""#));
+        let first = xml.find(r#"portid="22""#).unwrap();
+        let second = xml.find(r#"portid="80""#).unwrap();
+        assert!(first < second);
+    }
+
+    #[test]
+    fn test_port_reason_follows_evidence() {
+        let mut filtered = record("10.0.0.5", Some(443), "filtered");
+        filtered.evidence = Some("host-unreach".to_string());
+        let mut open = record("10.0.0.5", Some(22), "open");
+        open.evidence = Some("handshake".to_string());
+        let xml = render(&[open, filtered]);
+        assert!(xml.contains(r#"state="open" reason="syn-ack""#));
+        assert!(xml.contains(r#"state="filtered" reason="host-unreach""#));
+    }
+
+    #[test]
+    fn test_detected_version_marks_service_probed() {
+        let mut ssh = record("10.0.0.5", Some(22), "open");
+        ssh.service = Some("ssh".to_string());
+        ssh.product = Some("OpenSSH".to_string());
+        ssh.version = Some("9.6p1".to_string());
+        let mut web = record("10.0.0.5", Some(80), "open");
+        web.service = Some("http".to_string());
+        let xml = render(&[ssh, web]);
+        assert!(xml.contains(
+            r#"<service name="ssh" product="OpenSSH" version="9.6p1" method="probed" conf="10"/>"#
+        ));
+        assert!(xml.contains(r#"<service name="http" method="table" conf="3"/>"#));
+    }
+
+    #[test]
+    fn test_records_engagement_in_comment() {
+        let mut r = record("10.0.0.5", Some(22), "open");
+        r.operator = Some("jsmith".to_string());
+        r.note = Some("CHG--1234".to_string());
+        let xml = render(&[r]);
+        assert!(xml.contains("<!-- operator: jsmith; note: CHG- -1234 -->"));
+        assert!(!render(&[record("10.0.0.5", None, "up")]).contains("<!--"));
+    }
+
+    #[test]
+    fn test_escapes_attribute_values() {
+        let mut r = record("10.0.0.5", None, "up");
+        r.hostname = Some("a&b\"<c>".to_string());
+        assert!(render(&[r]).contains(r#"name="a&amp;b&quot;&lt;c&gt;""#));
+    }
+
+    #[test]
+    fn test_empty_report_is_well_formed() {
+        let xml = render(&[]);
+        assert!(xml.contains(r#"<hosts up="0" down="0" total="0"/>"#));
+        assert!(xml.trim_end().ends_with("</nmaprun>"));
+    }
+}
@@ -0,0 +1,259 @@
+//! Telling other systems about monitor state changes.
+//!
+//! A [`monitor`](crate::scanner::monitor) that only prints is watched by
+//! nobody at 3 a.m. Each round's changes can instead be posted to a webhook
+//! as JSON (chat tools and alerting systems accept those), or handed to a
+//! command — a mail script, a pager — one run per change.
+//!
+//! Unlike the probes, which accept any certificate, webhooks over HTTPS
+//! verify the server against the Mozilla root certificates: what is posted
+//! describes the network, and should only reach the intended receiver.
+
+use std::io::{Read, Write};
+use std::net::SocketAddr;
+use std::process::Command;
+use std::sync::{Arc, OnceLock};
+use std::time::Duration;
+
+use rustls::crypto::ring;
+use rustls::pki_types::ServerName;
+use rustls::{ClientConfig, ClientConnection, RootCertStore, StreamOwned};
+use serde_json::json;
+
+use crate::scanner::monitor::StateChange;
+use crate::scanner::port::resolve_host;
+use crate::scanner::socket::{self, Purpose};
+use crate::utils::url::HttpUrl;
+
+/// Parse the URL of a webhook, as given to `monitor --webhook`.
+///
+/// # Examples
+///
+/// ```
+/// use asphyxia::output::notify::parse_webhook_url;
+///
+/// assert_eq!(parse_webhook_url("https://hooks.example.com/T0").unwrap().port, 443);
+/// assert!(parse_webhook_url("ftp://example.com/").is_err());
+/// ```
+pub fn parse_webhook_url(s: &str) -> Result<HttpUrl, String> {
+    s.parse()
+        .map_err(|_| format!("Invalid webhook URL: {} (expected http:// or https://)", s))
+}
+
+/// Post `changes` to `url` as `{"changes": [...]}`, each change with its
+/// `time`, `target`, `from` and `to`.
+///
+/// # Returns
+///
+/// * `Result<(), String>` - `Ok` once the receiver answered with a 2xx
+///   status, or why the post failed
+///
+/// # Examples
+///
+/// ```no_run
+/// use asphyxia::output::notify::post_webhook;
+/// use asphyxia::scanner::monitor::StateChange;
+/// use std::time::Duration;
+///
+/// let change = StateChange {
+///     time: chrono::Utc::now(),
+///     target: "10.0.0.5:22/tcp".to_string(),
+///     from: Some("open".to_string()),
+///     to: "closed".to_string(),
+/// };
+/// let url = "https://hooks.example.com/asphyxia".parse().unwrap();
+/// if let Err(e) = post_webhook(&url, &[change], Duration::from_secs(5)) {
+///     eprintln!("{}", e);
+/// }
+/// ```
+pub fn post_webhook(
+    url: &HttpUrl,
+    changes: &[StateChange],
+    timeout: Duration,
+) -> Result<(), String> {
+    let failed = |reason: String| format!("Webhook {} failed: {}", url, reason);
+    let body = json!({ "changes": changes }).to_string();
+    let ip = resolve_host(&url.host).ok_or_else(|| failed("cannot resolve host".to_string()))?;
+    let stream = socket::connect(
+        SocketAddr::new(ip, url.port),
+        Purpose::Exchange.profile(),
+        timeout,
+    )
+    .map_err(|e| failed(e.to_string()))?;
+    stream
+        .set_read_timeout(Some(timeout))
+        .and_then(|()| stream.set_write_timeout(Some(timeout)))
+        .map_err(|e| failed(e.to_string()))?;
+    let host = if url.host.contains(':') {
+        format!("[{}]", url.host)
+    } else {
+        url.host.clone()
+    };
+    let request = format!(
+        "POST {} HTTP/1.1\r\nHost: {}\r\nUser-Agent: asphyxia/{}\r\nContent-Type: application/json\r\nContent-Length: {}\r\nConnection: close\r\n\r\n{}",
+        url.path,
+        host,
+        env!("CARGO_PKG_VERSION"),
+        body.len(),
+        body
+    );
+    let status = if url.tls {
+        let name = ServerName::try_from(url.host.clone()).map_err(|e| failed(e.to_string()))?;
+        let connection =
+            ClientConnection::new(verified_config(), name).map_err(|e| failed(e.to_string()))?;
+        send(StreamOwned::new(connection, stream), &request)
+    } else {
+        send(stream, &request)
+    }
+    .map_err(failed)?;
+    if (200..300).contains(&status) {
+        Ok(())
+    } else {
+        Err(failed(format!("HTTP {}", status)))
+    }
+}
+
+/// Send `request` and read the status code of the response.
+fn send<S: Read + Write>(mut stream: S, request: &str) -> Result<u16, String> {
+    stream
+        .write_all(request.as_bytes())
+        .and_then(|()| stream.flush())
+        .map_err(|e| e.to_string())?;
+    let mut head = Vec::new();
+    let mut buf = [0u8; 512];
+    while !head.contains(&b'\n') {
+        match stream.read(&mut buf) {
+            Ok(0) => break,
+            Ok(n) => head.extend_from_slice(&buf[..n]),
+            Err(e) => return Err(e.to_string()),
+        }
+    }
+    let line = String::from_utf8_lossy(&head);
+    line.split_whitespace()
+        .nth(1)
+        .and_then(|code| code.parse().ok())
+        .ok_or_else(|| "no HTTP answer".to_string())
+}
+
+/// TLS client settings for webhooks and updates: servers are verified
+/// against the Mozilla root certificates.
+pub(crate) fn verified_config() -> Arc<ClientConfig> {
+    static CONFIG: OnceLock<Arc<ClientConfig>> = OnceLock::new();
+    CONFIG
+        .get_or_init(|| {
+            let roots = RootCertStore {
+                roots: webpki_roots::TLS_SERVER_ROOTS.to_vec(),
+            };
+            let config = ClientConfig::builder_with_provider(Arc::new(ring::default_provider()))
+                .with_safe_default_protocol_versions()
+                .expect("the ring provider supports the default TLS versions")
+                .with_root_certificates(roots)
+                .with_no_client_auth();
+            Arc::new(config)
+        })
+        .clone()
+}
+
+/// Run `command` through the shell for `change`, which it finds in the
+/// environment: `ASPHYXIA_TARGET`, `ASPHYXIA_FROM` (empty for a target not
+/// seen before), `ASPHYXIA_TO` and `ASPHYXIA_TIME` (RFC 3339).
+///
+/// # Returns
+///
+/// * `Result<(), String>` - `Ok` if the command ran and exited with status
+///   0, or why it did not
+///
+/// # Examples
+///
+/// ```no_run
+/// use asphyxia::output::notify::run_command;
+/// use asphyxia::scanner::monitor::StateChange;
+///
+/// let change = StateChange {
+///     time: chrono::Utc::now(),
+///     target: "10.0.0.5".to_string(),
+///     from: Some("up".to_string()),
+///     to: "down".to_string(),
+/// };
+/// run_command(r#"logger "asphyxia: $ASPHYXIA_TARGET is $ASPHYXIA_TO""#, &change).unwrap();
+/// ```
+pub fn run_command(command: &str, change: &StateChange) -> Result<(), String> {
+    let mut shell = if cfg!(windows) {
+        let mut shell = Command::new("cmd");
+        shell.arg("/C");
+        shell
+    } else {
+        let mut shell = Command::new("sh");
+        shell.arg("-c");
+        shell
+    };
+    let status = shell
+        .arg(command)
+        .env("ASPHYXIA_TARGET", &change.target)
+        .env("ASPHYXIA_FROM", change.from.as_deref().unwrap_or_default())
+        .env("ASPHYXIA_TO", &change.to)
+        .env("ASPHYXIA_TIME", super::format_timestamp(change.time, false))
+        .status()
+        .map_err(|e| format!("Cannot run {}: {}", command, e))?;
+    if status.success() {
+        Ok(())
+    } else {
+        Err(format!("{} exited with {}", command, status))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::net::TcpListener;
+    use std::thread;
+
+    fn change() -> StateChange {
+        StateChange {
+            time: "2026-05-04T09:00:00Z".parse().unwrap(),
+            target: "10.0.0.5:22/tcp".to_string(),
+            from: Some("open".to_string()),
+            to: "closed".to_string(),
+        }
+    }
+
+    #[test]
+    fn test_post_webhook_sends_changes_as_json() {
+        let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+        let port = listener.local_addr().unwrap().port();
+        let server = thread::spawn(move || {
+            let (mut stream, _) = listener.accept().unwrap();
+            let mut request = Vec::new();
+            let mut buf = [0u8; 4096];
+            while !String::from_utf8_lossy(&request).contains("\"closed\"") {
+                let n = stream.read(&mut buf).unwrap();
+                request.extend_from_slice(&buf[..n]);
+            }
+            stream
+                .write_all(b"HTTP/1.1 204 No Content\r\n\r\n")
+                .unwrap();
+            String::from_utf8(request).unwrap()
+        });
+        let url = format!("http://127.0.0.1:{}/hook", port).parse().unwrap();
+        post_webhook(&url, &[change()], Duration::from_secs(2)).unwrap();
+        let request = server.join().unwrap();
+        assert!(request.starts_with("POST /hook HTTP/1.1\r\n"));
+        assert!(request.contains(
+            r#"{"changes":[{"from":"open","target":"10.0.0.5:22/tcp","time":"2026-05-04T09:00:00Z","to":"closed"}]}"#
+        ));
+    }
+
+    #[test]
+    fn test_post_webhook_reports_error_status() {
+        let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+        let port = listener.local_addr().unwrap().port();
+        thread::spawn(move || {
+            let (mut stream, _) = listener.accept().unwrap();
+            let _ = stream.read(&mut [0u8; 4096]);
+            let _ = stream.write_all(b"HTTP/1.1 500 Internal Server Error\r\n\r\n");
+        });
+        let url = format!("http://127.0.0.1:{}/", port).parse().unwrap();
+        let err = post_webhook(&url, &[change()], Duration::from_secs(2)).unwrap_err();
+        assert!(err.ends_with("HTTP 500"), "{}", err);
+    }
+}
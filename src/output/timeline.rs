@@ -0,0 +1,255 @@
+//! Timelines of state changes across saved scans.
+//!
+//! Given several reports of the same network taken over time, a timeline
+//! shows when each host appeared or vanished and when each port opened or
+//! closed. Two formats are generated, both plain data with no rendering
+//! dependency: Graphviz DOT (render with `dot -Tsvg`) and a Vega-Lite
+//! specification (open it in any Vega-Lite viewer).
+
+use clap::ValueEnum;
+use serde_json::{Value, json};
+use std::collections::BTreeMap;
+use std::io::{self, Write};
+
+use super::ScanRecord;
+use super::identity::host_identities;
+
+/// State given to a target missing from a scan.
+pub(super) const ABSENT: &str = "absent";
+
+/// How a timeline is rendered.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, ValueEnum)]
+pub enum TimelineFormat {
+    /// Graphviz DOT: one chain of state changes per host or port.
+    Dot,
+    /// Vega-Lite JSON: a target-by-time grid coloured by state.
+    VegaLite,
+}
+
+/// One saved scan: its records and a label for its point in time.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Snapshot {
+    /// When the scan ran (or, without timestamps, where it came from).
+    pub label: String,
+    /// The scan's records.
+    pub records: Vec<ScanRecord>,
+}
+
+impl Snapshot {
+    /// Build a snapshot labelled with the latest record timestamp, falling
+    /// back to `fallback` (e.g. the file name) for reports without one.
+    pub fn new(records: Vec<ScanRecord>, fallback: &str) -> Snapshot {
+        let label = records
+            .iter()
+            .filter_map(|r| r.timestamp)
+            .max()
+            .map(|ts| super::format_timestamp(ts, false))
+            .unwrap_or_else(|| fallback.to_string());
+        Snapshot { label, records }
+    }
+}
+
+/// The state of every target in every snapshot, keyed by target name
+/// (`10.0.0.5` for a host, `10.0.0.5:22/tcp` for a port) in sorted order.
+///
+/// Hosts are named by their [`host_identities`], so a device that changed
+/// address between scans (but kept its MAC address or hostname) stays one
+/// target.
+fn states(snapshots: &[Snapshot]) -> BTreeMap<String, Vec<String>> {
+    let mut states: BTreeMap<String, Vec<String>> = BTreeMap::new();
+    for (index, snapshot) in snapshots.iter().enumerate() {
+        let hosts = host_identities(&snapshot.records);
+        for record in &snapshot.records {
+            let host = &hosts[record.ip.as_str()];
+            let target = match record.port {
+                Some(port) => format!("{}:{}/{}", host, port, record.proto),
+                None => host.clone(),
+            };
+            states
+                .entry(target)
+                .or_insert_with(|| vec![ABSENT.to_string(); snapshots.len()])[index] =
+                record.status.clone();
+        }
+    }
+    states
+}
+
+/// Write a Graphviz DOT timeline.
+///
+/// Each target gets a left-to-right chain of nodes: one for its first state
+/// and one for every later change, labelled with the new state and the scan
+/// it was first seen in. Targets whose state never changed get one node.
+///
+/// # Examples
+///
+/// ```
+/// use asphyxia::output::read_records;
+/// use asphyxia::output::timeline::{Snapshot, write_dot};
+///
+/// let before = read_records(r#"{"ip":"10.0.0.5","port":22,"proto":"tcp","latency_ms":3,"status":"open"}"#).unwrap();
+/// let snapshots = [Snapshot::new(before, "monday.jsonl"), Snapshot::new(vec![], "tuesday.jsonl")];
+/// let mut dot = Vec::new();
+/// write_dot(&mut dot, &snapshots).unwrap();
+/// let dot = String::from_utf8(dot).unwrap();
+/// assert!(dot.contains(r#""10.0.0.5:22/tcp #1" [label="10.0.0.5:22/tcp\nabsent\ntuesday.jsonl"];"#));
+/// ```
+pub fn write_dot<W: Write>(mut out: W, snapshots: &[Snapshot]) -> io::Result<()> {
+    writeln!(out, "digraph timeline {{")?;
+    writeln!(out, "  rankdir=LR;")?;
+    writeln!(out, "  node [shape=box];")?;
+    for (target, states) in states(snapshots) {
+        let mut previous: Option<(usize, &str)> = None;
+        for (index, state) in states.iter().enumerate() {
+            if previous.is_some_and(|(_, p)| p == state) {
+                continue;
+            }
+            writeln!(
+                out,
+                r#"  "{target} #{index}" [label="{target}\n{state}\n{}"];"#,
+                escape(&snapshots[index].label)
+            )?;
+            if let Some((from, _)) = previous {
+                writeln!(out, r#"  "{target} #{from}" -> "{target} #{index}";"#)?;
+            }
+            previous = Some((index, state));
+        }
+    }
+    writeln!(out, "}}")?;
+    out.flush()
+}
+
+/// Build a Vega-Lite specification of the timeline: one row per target, one
+/// column per scan, each cell coloured by the target's state in that scan.
+///
+/// # Examples
+///
+/// ```
+/// use asphyxia::output::timeline::{Snapshot, vega_lite};
+///
+/// let spec = vega_lite(&[Snapshot::new(vec![], "empty.json")]);
+/// assert_eq!(spec["mark"], "rect");
+/// ```
+pub fn vega_lite(snapshots: &[Snapshot]) -> Value {
+    let values: Vec<Value> = states(snapshots)
+        .into_iter()
+        .flat_map(|(target, states)| {
+            states.into_iter().enumerate().map(move |(index, state)| {
+                json!({
+                    "scan": snapshots[index].label,
+                    "target": target,
+                    "state": state,
+                })
+            })
+        })
+        .collect();
+    let scans: Vec<&str> = snapshots.iter().map(|s| s.label.as_str()).collect();
+    json!({
+        "$schema": "https://vega.github.io/schema/vega-lite/v5.json",
+        "description": "Host and port states across scans",
+        "data": { "values": values },
+        "mark": "rect",
+        "encoding": {
+            "x": { "field": "scan", "type": "ordinal", "sort": scans, "title": "Scan" },
+            "y": { "field": "target", "type": "nominal", "title": "Target" },
+            "color": { "field": "state", "type": "nominal", "title": "State" },
+        },
+    })
+}
+
+/// Escape text for use inside a quoted DOT string.
+fn escape(text: &str) -> String {
+    text.replace('\\', "\\\\").replace('"', "\\\"")
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn record(ip: &str, port: Option<u16>, status: &str) -> ScanRecord {
+        ScanRecord {
+            format_version: 1,
+            ip: ip.to_string(),
+            hostname: None,
+            mac: None,
+            port,
+            proto: "tcp".to_string(),
+            service: None,
+            latency_ms: 1,
+            status: status.to_string(),
+            timestamp: None,
+            ack: None,
+            hops: None,
+            path_mtu: None,
+            ttl: None,
+            hop_estimate: None,
+            evidence: None,
+            attempts: None,
+            os_guess: None,
+            product: None,
+            version: None,
+            http_status: None,
+            http_server: None,
+            http_title: None,
+            tls_subject: None,
+            tls_issuer: None,
+            tls_sans: None,
+            tls_expires: None,
+            operator: None,
+            note: None,
+            outages: None,
+            annotation: None,
+        }
+    }
+
+    fn snapshots() -> Vec<Snapshot> {
+        vec![
+            Snapshot::new(vec![record("10.0.0.5", Some(22), "open")], "a"),
+            Snapshot::new(vec![record("10.0.0.5", Some(22), "open")], "b"),
+            Snapshot::new(vec![record("10.0.0.7", None, "up")], "c"),
+        ]
+    }
+
+    #[test]
+    fn test_states_mark_missing_targets_absent() {
+        let states = states(&snapshots());
+        assert_eq!(states["10.0.0.5:22/tcp"], vec!["open", "open", "absent"]);
+        assert_eq!(states["10.0.0.7"], vec!["absent", "absent", "up"]);
+    }
+
+    #[test]
+    fn test_states_follow_host_across_addresses() {
+        let named = |ip: &str, port| ScanRecord {
+            hostname: Some("printer.lan".to_string()),
+            ..record(ip, port, "open")
+        };
+        let states = states(&[
+            Snapshot::new(
+                vec![named("10.0.0.5", None), named("10.0.0.5", Some(631))],
+                "a",
+            ),
+            Snapshot::new(
+                vec![named("10.0.0.31", None), named("10.0.0.31", Some(631))],
+                "b",
+            ),
+        ]);
+        assert_eq!(states.len(), 2);
+        assert_eq!(states["printer.lan:631/tcp"], vec!["open", "open"]);
+    }
+
+    #[test]
+    fn test_dot_only_draws_changes() {
+        let mut dot = Vec::new();
+        write_dot(&mut dot, &snapshots()).unwrap();
+        let dot = String::from_utf8(dot).unwrap();
+        assert!(dot.contains(r#""10.0.0.5:22/tcp #0" -> "10.0.0.5:22/tcp #2";"#));
+        assert!(!dot.contains(r#""10.0.0.5:22/tcp #1""#));
+        assert!(dot.contains(r#""10.0.0.7 #0" -> "10.0.0.7 #2";"#));
+    }
+
+    #[test]
+    fn test_vega_lite_has_a_cell_per_target_and_scan() {
+        let spec = vega_lite(&snapshots());
+        assert_eq!(spec["data"]["values"].as_array().unwrap().len(), 6);
+        assert_eq!(spec["encoding"]["x"]["sort"], json!(["a", "b", "c"]));
+    }
+}
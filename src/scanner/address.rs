@@ -1,35 +1,116 @@
+use clap::ValueEnum;
 use ipnetwork::IpNetwork;
 use rayon::prelude::*;
-use std::io::ErrorKind;
-use std::net::{IpAddr, Ipv4Addr, Ipv6Addr, SocketAddr, TcpStream};
+use std::collections::HashMap;
+use std::io::{self, ErrorKind};
+use std::net::{IpAddr, Ipv4Addr, Ipv6Addr, SocketAddr};
 use std::time::{Duration, Instant};
 
-use crate::utils::progress_bar;
+use crate::scanner::options::ScanOptions;
+use crate::scanner::rate::RateLimiter;
+use crate::scanner::socket::{self, Purpose, SocketProfile};
+use crate::scanner::stream::{self, Found, ScanStream};
+use crate::scanner::{arp, icmp};
+use crate::utils::netif::neighbors;
+use crate::utils::reverse_lookup;
 
-/// An available host together with how long the availability probe took.
-///
-/// The latency is the wall-clock time the [`PROBE_PORT`] connection spent
-/// before succeeding or being refused/reset — a rough proxy for distance.
-#[derive(Debug, Clone, Copy, PartialEq, Eq)]
-pub struct HostHit {
+/// Whether a host answered its availability probe.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum HostState {
+    /// The host answered: a TCP probe connected or was refused/reset, or an
+    /// ICMP echo was answered.
+    Up,
+    /// No answer before the timeout, or the host/network was unreachable.
+    Down,
+}
+
+impl HostState {
+    /// Lowercase name used in machine-readable output (`"up"` / `"down"`).
+    pub fn as_str(self) -> &'static str {
+        match self {
+            HostState::Up => "up",
+            HostState::Down => "down",
+        }
+    }
+}
+
+/// The outcome of probing a single host for availability.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct HostScanResult {
+    /// The probed address.
     pub ip: IpAddr,
-    pub latency: Duration,
+    /// Whether the host answered.
+    pub state: HostState,
+    /// Round-trip time of the answering probe — a rough proxy for distance.
+    /// For a down host, how long the probe waited before giving up.
+    pub rtt: Duration,
+    /// Name from a reverse (PTR) lookup, when
+    /// [`ScanOptions::resolve_hostnames`] is set and the host has one.
+    pub hostname: Option<String>,
+    /// Hardware (MAC) address, when the host was found through ARP (an
+    /// [`ProbeMethod::Arp`] probe or [`ScanOptions::neighbor_cache`]).
+    pub mac: Option<String>,
+    /// TTL the answer arrived with (see [`ttl`](crate::scanner::ttl)), when
+    /// the probe saw its IP header: ICMP probes of IPv4 hosts.
+    pub ttl: Option<u8>,
+}
+
+impl HostScanResult {
+    /// Whether the host answered its probe.
+    pub fn is_up(&self) -> bool {
+        self.state == HostState::Up
+    }
+}
+
+/// How an address scan decides whether a host is up.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, ValueEnum)]
+pub enum ProbeMethod {
+    /// Unprivileged TCP connect to [`PROBE_PORT`]; a refusal also counts as up.
+    #[default]
+    Tcp,
+    /// ICMP echo request; needs raw-socket privileges or ICMP datagram
+    /// sockets (see [`icmp::icmp_socket`]).
+    Icmp,
+    /// ARP resolution; only finds IPv4 hosts on a directly attached link,
+    /// but finds them even when they filter everything (see [`arp`]).
+    Arp,
+}
+
+impl ProbeMethod {
+    /// Protocol name reported in machine-readable output.
+    pub fn proto(self) -> &'static str {
+        match self {
+            ProbeMethod::Tcp => "tcp",
+            ProbeMethod::Icmp => "icmp",
+            ProbeMethod::Arp => "arp",
+        }
+    }
 }
 
 /// TCP port used to probe a host when checking availability.
 ///
 /// The port does not need to be open — see [`scan_address`] for how the
 /// connection outcome is interpreted.
-const PROBE_PORT: u16 = 80;
+pub const PROBE_PORT: u16 = 80;
 
-/// Upper bound on the number of addresses enumerated for a single subnet or
-/// range scan when the family is IPv6.
+/// A broader set of discovery ports for [`scan_address_multi`]: web, SSH,
+/// SMB, and RDP between them answer on most servers and workstations.
+pub const COMMON_DISCOVERY_PORTS: &[u16] = &[80, 443, 22, 445, 3389];
+
+/// Upper bound on the number of IPv6 addresses collected into a list, for
+/// [`Target::addresses`](crate::utils::Target::addresses) and the async
+/// subnet scan.
 ///
 /// IPv6 address spaces are astronomically large (a single `/64` holds 2^64
-/// addresses), so scans wider than this are refused rather than attempted.
-/// IPv4 scans are not capped — their address space is small enough to walk.
+/// addresses), so lists wider than this are refused rather than attempted.
+/// Subnet and range scans stream their addresses instead and are capped by
+/// [`ScanOptions::max_hosts`].
 pub const MAX_IPV6_HOSTS: u128 = 1 << 16; // 65_536 addresses (e.g. a /112)
 
+/// Default for [`ScanOptions::max_hosts`]: the most addresses one subnet or
+/// range scan covers unless told otherwise, a `/16`.
+pub const DEFAULT_MAX_HOSTS: u64 = 1 << 16;
+
 /// Scan a single IP address for availability.
 ///
 /// Availability is inferred from how the host reacts to a TCP probe on
@@ -51,7 +132,7 @@ pub const MAX_IPV6_HOSTS: u128 = 1 << 16; // 65_536 addresses (e.g. a /112)
 ///
 /// # Returns
 ///
-/// * `Option<HostHit>` - The host and its probe latency if up, `None` otherwise
+/// * `HostScanResult` - Whether the host is up, and the probe's round-trip time
 ///
 /// # Examples
 ///
@@ -61,66 +142,318 @@ pub const MAX_IPV6_HOSTS: u128 = 1 << 16; // 65_536 addresses (e.g. a /112)
 /// use std::time::Duration;
 ///
 /// let ip: IpAddr = "192.168.1.1".parse().unwrap();
-/// if let Some(hit) = scan_address(ip, Some(Duration::from_millis(500))) {
-///     println!("Host {} is up ({} ms)", hit.ip, hit.latency.as_millis());
+/// let result = scan_address(ip, Some(Duration::from_millis(500)));
+/// if result.is_up() {
+///     println!("Host {} is up ({} ms)", result.ip, result.rtt.as_millis());
 /// }
 /// ```
-pub fn scan_address(ip: IpAddr, timeout: Option<Duration>) -> Option<HostHit> {
-    let timeout = timeout.unwrap_or(crate::scanner::port::CONNECT_TIMEOUT);
+pub fn scan_address(ip: IpAddr, timeout: Option<Duration>) -> HostScanResult {
+    scan_address_with_options(
+        ip,
+        &ScanOptions {
+            timeout,
+            ..ScanOptions::default()
+        },
+    )
+}
+
+/// Scan a single IP address for availability using the given [`ScanOptions`].
+///
+/// With [`ProbeMethod::Tcp`] this behaves exactly like [`scan_address`]. With
+/// [`ProbeMethod::Icmp`] the host is up when it answers an ICMP echo request;
+/// if the echo cannot be sent (e.g. no ICMP socket may be opened) the host is
+/// reported as down. A host that stays silent is re-probed up to
+/// `options.retries` more times before it is declared down.
+///
+/// # Examples
+///
+/// ```no_run
+/// use asphyxia::scanner::address::{ProbeMethod, scan_address_with_options};
+/// use asphyxia::scanner::options::ScanOptions;
+///
+/// let options = ScanOptions {
+///     probe: ProbeMethod::Icmp,
+///     ..ScanOptions::default()
+/// };
+/// let up = scan_address_with_options("192.168.1.1".parse().unwrap(), &options).is_up();
+/// println!("Host answers pings: {}", up);
+/// ```
+pub fn scan_address_with_options(ip: IpAddr, options: &ScanOptions) -> HostScanResult {
+    probe_host(ip, options, None)
+}
+
+/// Probe `ip` as configured by `options`, retrying silent hosts, and wait on
+/// `limiter` (if any) before every attempt.
+fn probe_host(ip: IpAddr, options: &ScanOptions, limiter: Option<&RateLimiter>) -> HostScanResult {
+    let timeout = options
+        .timeout
+        .unwrap_or(crate::scanner::port::CONNECT_TIMEOUT);
+    // Each TCP discovery port costs one connection attempt.
+    let discovery_ports = discovery_ports(ip, options);
+    let attempts = match options.probe {
+        ProbeMethod::Tcp => discovery_ports.len() as u32,
+        ProbeMethod::Icmp | ProbeMethod::Arp => 1,
+    };
+    let profile = Purpose::Probe
+        .profile()
+        .marked(options.dscp)
+        .bound(options.origin());
     let start = Instant::now();
-    match TcpStream::connect_timeout(&SocketAddr::new(ip, PROBE_PORT), timeout) {
+    // With every discovery port off limits, the host is not probed at all.
+    if attempts == 0 {
+        return down_host(ip, start.elapsed());
+    }
+    for attempt in 0..=options.retries {
+        if attempt > 0 && options.is_cancelled() {
+            break;
+        }
+        if let Some(limiter) = limiter {
+            limiter.acquire_n(attempts);
+        }
+        let answered = match options.probe {
+            ProbeMethod::Tcp => {
+                probe_ports(ip, &discovery_ports, profile, timeout).map(|rtt| (rtt, None, None))
+            }
+            ProbeMethod::Icmp => icmp::ping_marked(ip, options.dscp, options.origin(), timeout)
+                .ok()
+                .flatten()
+                .map(|reply| (reply.rtt, None, reply.ttl)),
+            ProbeMethod::Arp => arp::probe(ip, timeout).map(|(rtt, mac)| (rtt, Some(mac), None)),
+        };
+        if let Some((rtt, mac, ttl)) = answered {
+            return HostScanResult {
+                ttl,
+                ..up_host(ip, rtt, mac, options)
+            };
+        }
+    }
+    // Silence while the scanning host was offline says nothing about the
+    // host; probe it again once the network is back.
+    if options.rode_out_outage(ip) {
+        return probe_host(ip, options, limiter);
+    }
+    down_host(ip, start.elapsed())
+}
+
+/// The result for a host that did not answer within `rtt`.
+fn down_host(ip: IpAddr, rtt: Duration) -> HostScanResult {
+    HostScanResult {
+        ip,
+        state: HostState::Down,
+        rtt,
+        hostname: None,
+        mac: None,
+        ttl: None,
+    }
+}
+
+/// The TCP ports to discover `ip` on: the scan's discovery ports (or
+/// [`PROBE_PORT`] when none are set) that the host's annotation allows.
+///
+/// When the annotation rules them all out, [`PROBE_PORT`] is probed instead
+/// if it is allowed; otherwise the list is empty and the host is not probed.
+fn discovery_ports(ip: IpAddr, options: &ScanOptions) -> Vec<u16> {
+    let ports = match options.discovery_ports.as_slice() {
+        [] => &[PROBE_PORT][..],
+        ports => ports,
+    };
+    let allowed: Vec<u16> = ports
+        .iter()
+        .copied()
+        .filter(|&port| options.allows_port(ip, port))
+        .collect();
+    if allowed.is_empty() && options.allows_port(ip, PROBE_PORT) {
+        vec![PROBE_PORT]
+    } else {
+        allowed
+    }
+}
+
+/// The result for a host known to be up, with its hostname looked up if
+/// `options` asks for it.
+fn up_host(
+    ip: IpAddr,
+    rtt: Duration,
+    mac: Option<String>,
+    options: &ScanOptions,
+) -> HostScanResult {
+    HostScanResult {
+        ip,
+        state: HostState::Up,
+        rtt,
+        hostname: options
+            .resolve_hostnames
+            .then(|| reverse_lookup(ip))
+            .flatten(),
+        mac,
+        ttl: None,
+    }
+}
+
+/// Scan a single IP address for availability by probing several TCP ports.
+///
+/// Each port is probed exactly like [`scan_address`] probes [`PROBE_PORT`],
+/// all at the same time, and the host is up as soon as any of them answers
+/// (connects or actively refuses). The reported latency is that of the first
+/// answer. A host that answers on none of the ports within `timeout` is down,
+/// so probing more ports does not make unreachable hosts any slower to scan.
+///
+/// # Arguments
+///
+/// * `ip` - The IP address to scan (IPv4 or IPv6)
+/// * `ports` - The TCP ports to probe, e.g. [`COMMON_DISCOVERY_PORTS`]
+/// * `timeout` - Optional timeout duration (defaults to [`crate::scanner::port::CONNECT_TIMEOUT`])
+///
+/// # Returns
+///
+/// * `HostScanResult` - Whether the host is up, and the first answer's round-trip time
+///
+/// # Examples
+///
+/// ```no_run
+/// use asphyxia::scanner::address::{COMMON_DISCOVERY_PORTS, scan_address_multi};
+///
+/// let ip = "192.168.1.20".parse().unwrap();
+/// if scan_address_multi(ip, COMMON_DISCOVERY_PORTS, None).is_up() {
+///     println!("Host is up");
+/// }
+/// ```
+pub fn scan_address_multi(ip: IpAddr, ports: &[u16], timeout: Option<Duration>) -> HostScanResult {
+    scan_address_with_options(
+        ip,
+        &ScanOptions {
+            timeout,
+            discovery_ports: ports.to_vec(),
+            ..ScanOptions::default()
+        },
+    )
+}
+
+/// Probe `ip` on every port in `ports` at once and return the round-trip
+/// time of the first answer. An empty list falls back to [`PROBE_PORT`].
+///
+/// The attempts run on the calling thread (see [`socket::first_answer`]),
+/// so a scan never holds more threads than its concurrency.
+fn probe_ports(
+    ip: IpAddr,
+    ports: &[u16],
+    profile: SocketProfile,
+    timeout: Duration,
+) -> Option<Duration> {
+    match ports {
+        [] => tcp_probe(ip, PROBE_PORT, profile, timeout),
+        [port] => tcp_probe(ip, *port, profile, timeout),
+        _ => {
+            let destinations: Vec<SocketAddr> = ports
+                .iter()
+                .map(|&port| SocketAddr::new(ip, port))
+                .collect();
+            socket::first_answer(&destinations, profile, timeout)
+        }
+    }
+}
+
+/// Probe `ip` with a TCP connection to `port` (see [`scan_address`]) over a
+/// socket tuned as `profile` says, and return the round-trip time if the
+/// host answered.
+pub(crate) fn tcp_probe(
+    ip: IpAddr,
+    port: u16,
+    profile: SocketProfile,
+    timeout: Duration,
+) -> Option<Duration> {
+    let start = Instant::now();
+    match socket::connect(SocketAddr::new(ip, port), profile, timeout) {
         // Port is open: the host is unambiguously up.
-        Ok(_) => Some(HostHit {
-            ip,
-            latency: start.elapsed(),
-        }),
+        Ok(_) => Some(start.elapsed()),
         // The host replied with a reset — it is up, the port is just closed.
-        Err(e)
-            if matches!(
-                e.kind(),
-                ErrorKind::ConnectionRefused | ErrorKind::ConnectionReset
-            ) =>
-        {
-            Some(HostHit {
-                ip,
-                latency: start.elapsed(),
-            })
-        }
+        Err(e) if host_answered(&e) => Some(start.elapsed()),
         // Timeout, unreachable, or anything else: treat the host as down.
         Err(_) => None,
     }
 }
 
-/// Scan every address yielded by `addrs` in parallel and return the ones that
-/// are available, sorted ascending.
+/// Whether a failed TCP probe still proves the host is up, because it actively
+/// refused or reset the connection rather than staying silent.
+pub(crate) fn host_answered(e: &io::Error) -> bool {
+    matches!(
+        e.kind(),
+        ErrorKind::ConnectionRefused | ErrorKind::ConnectionReset
+    )
+}
+
+/// Scan every address yielded by `addrs` in parallel and hand each available
+/// one to `found` as soon as it answers, in no particular order.
 ///
 /// This is the shared engine behind subnet and range scans: it owns the
-/// progress bar and the parallel fan-out so callers only have to describe
-/// which addresses to probe.
-fn scan_all<I>(addrs: I, total: u64, timeout: Option<Duration>, finish_msg: &str) -> Vec<HostHit>
-where
+/// progress reporting and the parallel fan-out so callers only have to describe
+/// which addresses to probe. Addresses in `options.exclude_hosts` are skipped,
+/// and with `options.neighbor_cache`, addresses already in the OS ARP table
+/// are reported up without a probe.
+fn scan_each<I>(
+    addrs: I,
+    total: u64,
+    options: &ScanOptions,
+    finish_msg: &str,
+    found: Found<HostScanResult>,
+) where
     I: ParallelIterator<Item = IpAddr>,
 {
-    let pb = progress_bar(total, "addresses scanned");
+    let progress = options.progress();
+    progress.start(total, "addresses scanned");
+    let rates = options.rate_classes();
+    let neighbors: HashMap<IpAddr, String> = if options.neighbor_cache {
+        neighbors()
+            .into_iter()
+            .map(|neighbor| (neighbor.ip, neighbor.mac))
+            .collect()
+    } else {
+        HashMap::new()
+    };
 
-    let mut result: Vec<HostHit> = addrs
-        .filter_map(|ip| {
-            let available = scan_address(ip, timeout);
-            pb.inc(1);
-            available
+    // An `Err` stops the parallel iteration once `found` gives up.
+    let _ = options.install(|| {
+        addrs.try_for_each(|ip| {
+            if options.is_cancelled() {
+                return Ok(());
+            }
+            if options.is_excluded(ip) {
+                progress.inc(1);
+                return Ok(());
+            }
+            let result = if let Some(mac) = neighbors.get(&ip) {
+                up_host(ip, Duration::ZERO, Some(mac.clone()), options)
+            } else {
+                probe_host(ip, options, rates.classify(ip))
+            };
+            progress.inc(1);
+            if result.is_up() && !found(result) {
+                return Err(());
+            }
+            Ok(())
         })
-        .collect();
+    });
+
+    progress.finish(finish_msg);
+}
 
-    pb.finish_with_message(finish_msg.to_string());
-    result.sort_by_key(|h| h.ip);
-    result
+/// Run `scan` and return every host it found, sorted ascending.
+fn collect_sorted(scan: impl FnOnce(Found<HostScanResult>)) -> Vec<HostScanResult> {
+    let mut hosts = stream::collect(scan);
+    hosts.sort_by_key(|h| h.ip);
+    hosts
 }
 
+/// Live hosts of a scan running in the background, yielded as they answer.
+/// Returned by [`scan_subnet_iter`] and [`scan_ip_range_iter`].
+pub type HostStream = ScanStream<HostScanResult>;
+
 /// Scan an entire subnet for available hosts
 ///
-/// IPv4 subnets are scanned in full. IPv6 subnets are scanned only when they
-/// contain at most [`MAX_IPV6_HOSTS`] addresses; wider IPv6 subnets are refused
-/// (a warning is printed and an empty vector is returned).
+/// Subnets are scanned in full when they contain at most
+/// [`ScanOptions::max_hosts`] addresses (by default [`DEFAULT_MAX_HOSTS`]);
+/// wider subnets are refused (a warning is printed and an empty vector is
+/// returned). Addresses are generated as they are probed, never collected.
 ///
 /// # Arguments
 ///
@@ -129,7 +462,7 @@ where
 ///
 /// # Returns
 ///
-/// * `Vec<HostHit>` - A vector of available hosts with their probe latency
+/// * `Vec<HostScanResult>` - The hosts that are up, with their probe round-trip time
 ///
 /// # Examples
 ///
@@ -141,41 +474,90 @@ where
 /// let available_hosts = scan_subnet(subnet, None);
 /// println!("Found {} available hosts", available_hosts.len());
 /// ```
-pub fn scan_subnet(subnet: IpNetwork, timeout: Option<Duration>) -> Vec<HostHit> {
+pub fn scan_subnet(subnet: IpNetwork, timeout: Option<Duration>) -> Vec<HostScanResult> {
+    scan_subnet_with_options(
+        subnet,
+        &ScanOptions {
+            timeout,
+            ..ScanOptions::default()
+        },
+    )
+}
+
+/// Scan an entire subnet for available hosts using the given [`ScanOptions`].
+///
+/// See [`scan_subnet`] for how subnets are enumerated and
+/// [`scan_address_with_options`] for how each host is probed. Addresses in
+/// `options.exclude_hosts` are never probed.
+pub fn scan_subnet_with_options(subnet: IpNetwork, options: &ScanOptions) -> Vec<HostScanResult> {
+    collect_sorted(|found| subnet_each(subnet, options, found))
+}
+
+/// Scan a subnet like [`scan_subnet_with_options`], but yield each live host
+/// as soon as it answers instead of waiting for the whole subnet.
+///
+/// The scan runs on a background thread; hosts arrive in no particular
+/// order. Useful for large subnets (a `/16`), where the first live hosts
+/// can be acted on long before the last address is probed.
+///
+/// # Examples
+///
+/// ```no_run
+/// use asphyxia::scanner::address::scan_subnet_iter;
+/// use asphyxia::scanner::options::ScanOptions;
+///
+/// let subnet = "10.0.0.0/16".parse().unwrap();
+/// for host in scan_subnet_iter(subnet, ScanOptions::default()) {
+///     println!("{} is up", host.ip);
+/// }
+/// ```
+pub fn scan_subnet_iter(subnet: IpNetwork, options: ScanOptions) -> HostStream {
+    stream::spawn(options, move |options, found| {
+        subnet_each(subnet, options, found)
+    })
+}
+
+/// Scan `subnet`, handing each live host to `found`.
+fn subnet_each(subnet: IpNetwork, options: &ScanOptions, found: Found<HostScanResult>) {
+    let count = subnet_size(subnet);
+    if let Err(e) = options.check_host_count(count) {
+        eprintln!("{}", e);
+        return;
+    }
+    let total = u64::try_from(count).unwrap_or(u64::MAX);
     match (subnet.network(), subnet.broadcast()) {
         (IpAddr::V4(network), IpAddr::V4(broadcast)) => {
-            let start = u32::from(network);
-            let end = u32::from(broadcast);
-            let total = u64::from(end - start) + 1;
-            scan_all(
-                (start..=end).into_par_iter().map(ipv4),
+            let (start, end) = (u32::from(network), u32::from(broadcast));
+            scan_each(
+                ordered(start.into(), (end - start).into(), options, |n| {
+                    ipv4(n as u32)
+                }),
                 total,
-                timeout,
+                options,
                 "Subnet scan completed",
+                found,
+            )
+        }
+        (IpAddr::V6(network), IpAddr::V6(broadcast)) => {
+            let (start, end) = (u128::from(network), u128::from(broadcast));
+            scan_each(
+                ordered(start, end - start, options, ipv6),
+                total,
+                options,
+                "Subnet scan completed",
+                found,
             )
         }
-        (IpAddr::V6(network), IpAddr::V6(broadcast)) => match ipv6_hosts(network, broadcast) {
-            Some(hosts) => {
-                let total = hosts.len() as u64;
-                scan_all(
-                    hosts.into_par_iter(),
-                    total,
-                    timeout,
-                    "Subnet scan completed",
-                )
-            }
-            None => Vec::new(),
-        },
         // network() and broadcast() always share the subnet's family.
-        _ => Vec::new(),
+        _ => {}
     }
 }
 
 /// Scan a range of IP addresses for available hosts
 ///
 /// The `start` and `end` addresses must belong to the same family (both IPv4 or
-/// both IPv6). IPv6 ranges are scanned only when they span at most
-/// [`MAX_IPV6_HOSTS`] addresses; wider ranges are refused.
+/// both IPv6). Ranges are scanned only when they span at most
+/// [`ScanOptions::max_hosts`] addresses; wider ranges are refused.
 ///
 /// # Arguments
 ///
@@ -185,7 +567,7 @@ pub fn scan_subnet(subnet: IpNetwork, timeout: Option<Duration>) -> Vec<HostHit>
 ///
 /// # Returns
 ///
-/// * `Vec<HostHit>` - A vector of available hosts with their probe latency
+/// * `Vec<HostScanResult>` - The hosts that are up, with their probe round-trip time
 ///
 /// # Examples
 ///
@@ -198,52 +580,148 @@ pub fn scan_subnet(subnet: IpNetwork, timeout: Option<Duration>) -> Vec<HostHit>
 /// let available_hosts = scan_ip_range(start, end, None);
 /// println!("Found {} available hosts", available_hosts.len());
 /// ```
-pub fn scan_ip_range(start: IpAddr, end: IpAddr, timeout: Option<Duration>) -> Vec<HostHit> {
+pub fn scan_ip_range(start: IpAddr, end: IpAddr, timeout: Option<Duration>) -> Vec<HostScanResult> {
+    scan_ip_range_with_options(
+        start,
+        end,
+        &ScanOptions {
+            timeout,
+            ..ScanOptions::default()
+        },
+    )
+}
+
+/// Scan a range of IP addresses for available hosts using the given
+/// [`ScanOptions`].
+///
+/// See [`scan_ip_range`] for the range rules and [`scan_address_with_options`]
+/// for how each host is probed.
+pub fn scan_ip_range_with_options(
+    start: IpAddr,
+    end: IpAddr,
+    options: &ScanOptions,
+) -> Vec<HostScanResult> {
+    collect_sorted(|found| range_each(start, end, options, found))
+}
+
+/// Scan a range like [`scan_ip_range_with_options`], but yield each live
+/// host as soon as it answers (see [`scan_subnet_iter`]).
+///
+/// # Examples
+///
+/// ```no_run
+/// use asphyxia::scanner::address::scan_ip_range_iter;
+/// use asphyxia::scanner::options::ScanOptions;
+///
+/// let start = "10.0.0.1".parse().unwrap();
+/// let end = "10.0.9.254".parse().unwrap();
+/// for host in scan_ip_range_iter(start, end, ScanOptions::default()) {
+///     println!("{} is up", host.ip);
+/// }
+/// ```
+pub fn scan_ip_range_iter(start: IpAddr, end: IpAddr, options: ScanOptions) -> HostStream {
+    stream::spawn(options, move |options, found| {
+        range_each(start, end, options, found)
+    })
+}
+
+/// Scan the range from `start` to `end`, handing each live host to `found`.
+fn range_each(start: IpAddr, end: IpAddr, options: &ScanOptions, found: Found<HostScanResult>) {
+    let Some(count) = range_size(start, end) else {
+        eprintln!("Range start and end must be the same IP family");
+        return;
+    };
+    if count == 0 {
+        return;
+    }
+    if let Err(e) = options.check_host_count(count) {
+        eprintln!("{}", e);
+        return;
+    }
+    let total = u64::try_from(count).unwrap_or(u64::MAX);
     match (start, end) {
         (IpAddr::V4(start), IpAddr::V4(end)) => {
-            let start = u32::from(start);
-            let end = u32::from(end);
-            if start > end {
-                return Vec::new();
-            }
-            let total = u64::from(end - start) + 1;
-            scan_all(
-                (start..=end).into_par_iter().map(ipv4),
+            let (start, end) = (u32::from(start), u32::from(end));
+            scan_each(
+                ordered(start.into(), (end - start).into(), options, |n| {
+                    ipv4(n as u32)
+                }),
                 total,
-                timeout,
+                options,
                 "Range scan completed",
+                found,
             )
         }
         (IpAddr::V6(start), IpAddr::V6(end)) => {
-            if start > end {
-                return Vec::new();
-            }
-            match ipv6_hosts(start, end) {
-                Some(hosts) => {
-                    let total = hosts.len() as u64;
-                    scan_all(
-                        hosts.into_par_iter(),
-                        total,
-                        timeout,
-                        "Range scan completed",
-                    )
-                }
-                None => Vec::new(),
-            }
-        }
-        _ => {
-            eprintln!("Range start and end must be the same IP family");
-            Vec::new()
+            let (start, end) = (u128::from(start), u128::from(end));
+            scan_each(
+                ordered(start, end - start, options, ipv6),
+                total,
+                options,
+                "Range scan completed",
+                found,
+            )
         }
+        // range_size() has already refused mixed families.
+        _ => {}
     }
 }
 
+/// How many addresses `subnet` holds. An IPv6 `/0` holds 2^128, one more
+/// than fits, and is counted as `u128::MAX`.
+///
+/// # Examples
+///
+/// ```
+/// use asphyxia::scanner::address::subnet_size;
+///
+/// assert_eq!(subnet_size("10.0.0.0/24".parse().unwrap()), 256);
+/// assert_eq!(subnet_size("0.0.0.0/0".parse().unwrap()), 1 << 32);
+/// assert_eq!(subnet_size("::/0".parse().unwrap()), u128::MAX);
+/// ```
+pub fn subnet_size(subnet: IpNetwork) -> u128 {
+    let bits = match subnet {
+        IpNetwork::V4(_) => 32,
+        IpNetwork::V6(_) => 128,
+    };
+    1u128
+        .checked_shl(bits - u32::from(subnet.prefix()))
+        .unwrap_or(u128::MAX)
+}
+
+/// How many addresses the inclusive range from `start` to `end` holds: 0
+/// if `start` comes after `end`, `None` if they are of different families.
+/// Like [`subnet_size`], the full IPv6 space is counted as `u128::MAX`.
+///
+/// # Examples
+///
+/// ```
+/// use asphyxia::scanner::address::range_size;
+///
+/// let range = |start: &str, end: &str| range_size(start.parse().unwrap(), end.parse().unwrap());
+/// assert_eq!(range("10.0.0.1", "10.0.0.50"), Some(50));
+/// assert_eq!(range("0.0.0.0", "255.255.255.255"), Some(1 << 32));
+/// assert_eq!(range("10.0.0.9", "10.0.0.1"), Some(0));
+/// assert_eq!(range("10.0.0.1", "::1"), None);
+/// ```
+pub fn range_size(start: IpAddr, end: IpAddr) -> Option<u128> {
+    let (start, end) = match (start, end) {
+        (IpAddr::V4(start), IpAddr::V4(end)) => {
+            (u128::from(u32::from(start)), u128::from(u32::from(end)))
+        }
+        (IpAddr::V6(start), IpAddr::V6(end)) => (u128::from(start), u128::from(end)),
+        _ => return None,
+    };
+    Some(match end.checked_sub(start) {
+        Some(span) => span.saturating_add(1),
+        None => 0,
+    })
+}
+
 /// Build the inclusive list of IPv6 addresses between `start` and `end`,
 /// or `None` (after warning) if the span exceeds [`MAX_IPV6_HOSTS`].
-fn ipv6_hosts(start: Ipv6Addr, end: Ipv6Addr) -> Option<Vec<IpAddr>> {
-    let start = u128::from(start);
-    let end = u128::from(end);
-    let count = end - start + 1;
+pub(crate) fn ipv6_hosts(start: Ipv6Addr, end: Ipv6Addr) -> Option<Vec<IpAddr>> {
+    let count = range_size(IpAddr::V6(start), IpAddr::V6(end)).unwrap_or(0);
     if count > MAX_IPV6_HOSTS {
         eprintln!(
             "Refusing to scan {} IPv6 addresses (limit is {}); narrow the range or prefix",
@@ -251,18 +729,33 @@ fn ipv6_hosts(start: Ipv6Addr, end: Ipv6Addr) -> Option<Vec<IpAddr>> {
         );
         return None;
     }
-    Some(
-        (start..=end)
-            .map(|n| IpAddr::V6(Ipv6Addr::from(n)))
-            .collect(),
-    )
+    Some((u128::from(start)..=u128::from(end)).map(ipv6).collect())
+}
+
+/// The addresses from `first` to `first + last` (packed as numbers and
+/// unpacked with `address`), in the order `options` probes them in.
+fn ordered(
+    first: u128,
+    last: u128,
+    options: &ScanOptions,
+    address: fn(u128) -> IpAddr,
+) -> impl ParallelIterator<Item = IpAddr> {
+    let order = options.order(last);
+    (0..=last)
+        .into_par_iter()
+        .map(move |step| address(first + order.at(step)))
 }
 
 /// Convert a packed `u32` into an [`IpAddr::V4`].
-fn ipv4(n: u32) -> IpAddr {
+pub(crate) fn ipv4(n: u32) -> IpAddr {
     IpAddr::V4(Ipv4Addr::from(n))
 }
 
+/// Convert a packed `u128` into an [`IpAddr::V6`].
+fn ipv6(n: u128) -> IpAddr {
+    IpAddr::V6(Ipv6Addr::from(n))
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
@@ -272,7 +765,7 @@ mod tests {
 
     // Helper function to check if localhost is available
     fn is_localhost_available() -> bool {
-        scan_address("127.0.0.1".parse().unwrap(), TEST_TIMEOUT).is_some()
+        scan_address("127.0.0.1".parse().unwrap(), TEST_TIMEOUT).is_up()
     }
 
     #[test]
@@ -284,15 +777,67 @@ mod tests {
         }
 
         let ip: IpAddr = "127.0.0.1".parse().unwrap();
-        let hit = scan_address(ip, TEST_TIMEOUT).expect("localhost should be up");
-        assert_eq!(hit.ip, ip);
+        let result = scan_address(ip, TEST_TIMEOUT);
+        assert!(result.is_up(), "localhost should be up");
+        assert_eq!(result.ip, ip);
     }
 
     #[test]
     fn test_scan_address_unavailable() {
         // Test with an address that's very unlikely to be available
         let ip: IpAddr = "192.168.255.255".parse().unwrap();
-        assert!(scan_address(ip, TEST_TIMEOUT).is_none());
+        assert_eq!(scan_address(ip, TEST_TIMEOUT).state, HostState::Down);
+    }
+
+    #[test]
+    fn test_scan_address_multi_any_port_answers() {
+        // Nothing listens on these loopback ports, but the refusals still
+        // prove the host is up.
+        let ip: IpAddr = "127.0.0.1".parse().unwrap();
+        let result = scan_address_multi(ip, &[1, 2, 3], TEST_TIMEOUT);
+        assert!(result.is_up(), "loopback is up");
+        assert_eq!(result.ip, ip);
+    }
+
+    #[test]
+    fn test_scan_address_multi_empty_uses_probe_port() {
+        let ip: IpAddr = "127.0.0.1".parse().unwrap();
+        assert_eq!(
+            scan_address_multi(ip, &[], TEST_TIMEOUT).state,
+            scan_address(ip, TEST_TIMEOUT).state
+        );
+    }
+
+    #[test]
+    fn test_subnet_scan_skips_excluded_hosts() {
+        let options = ScanOptions {
+            timeout: TEST_TIMEOUT,
+            exclude_hosts: vec![
+                "127.0.0.0/31".parse().unwrap(),
+                "127.0.0.3".parse().unwrap(),
+            ],
+            ..ScanOptions::default()
+        };
+        let subnet = "127.0.0.0/30".parse().unwrap();
+        let ips: Vec<IpAddr> = scan_subnet_with_options(subnet, &options)
+            .into_iter()
+            .map(|host| host.ip)
+            .collect();
+        assert_eq!(ips, vec!["127.0.0.2".parse::<IpAddr>().unwrap()]);
+    }
+
+    #[test]
+    fn test_cancelled_subnet_scan_skips_remaining_hosts() {
+        let token = crate::scanner::cancel::CancelToken::new();
+        token.cancel();
+        let options = ScanOptions {
+            timeout: TEST_TIMEOUT,
+            cancel: Some(token),
+            ..ScanOptions::default()
+        };
+        // Loopback would answer, so an empty result means nothing was probed.
+        let subnet = "127.0.0.0/30".parse().unwrap();
+        assert!(scan_subnet_with_options(subnet, &options).is_empty());
     }
 
     #[test]
@@ -332,6 +877,34 @@ mod tests {
         assert!(results.windows(2).all(|w| w[0].ip <= w[1].ip));
     }
 
+    #[test]
+    fn test_scan_subnet_iter_streams_live_hosts() {
+        if !is_localhost_available() {
+            println!(
+                "Skipping test_scan_subnet_iter_streams_live_hosts: localhost is not available"
+            );
+            return;
+        }
+        let subnet = "127.0.0.0/30".parse::<IpNetwork>().unwrap();
+        let options = ScanOptions {
+            timeout: TEST_TIMEOUT,
+            ..ScanOptions::default()
+        };
+        let streamed: Vec<IpAddr> = scan_subnet_iter(subnet, options.clone())
+            .map(|h| h.ip)
+            .collect();
+        assert!(streamed.contains(&"127.0.0.1".parse().unwrap()));
+
+        // Dropping the stream early must not hang or panic the scan thread.
+        let mut stream = scan_ip_range_iter(
+            "127.0.0.1".parse().unwrap(),
+            "127.0.0.3".parse().unwrap(),
+            options,
+        );
+        assert!(stream.next().is_some());
+        drop(stream);
+    }
+
     #[test]
     fn test_scan_empty_range() {
         // Test with an invalid range (start > end)
@@ -367,4 +940,41 @@ mod tests {
             panic!("expected IPv6 network");
         }
     }
+
+    #[test]
+    fn test_sizes_of_whole_address_spaces_do_not_overflow() {
+        assert_eq!(subnet_size("0.0.0.0/0".parse().unwrap()), 1 << 32);
+        assert_eq!(subnet_size("255.255.255.255/32".parse().unwrap()), 1);
+        assert_eq!(subnet_size("::/0".parse().unwrap()), u128::MAX);
+        let all_v4 = range_size(
+            "0.0.0.0".parse().unwrap(),
+            "255.255.255.255".parse().unwrap(),
+        );
+        assert_eq!(all_v4, Some(1 << 32));
+        assert!(ipv6_hosts(Ipv6Addr::UNSPECIFIED, Ipv6Addr::from(u128::MAX)).is_none());
+        let everything: crate::utils::Target = "0.0.0.0/0".parse().unwrap();
+        assert!(everything.addresses().is_err());
+    }
+
+    #[test]
+    fn test_subnet_scan_refuses_more_hosts_than_allowed() {
+        let subnet = "127.0.0.0/29".parse::<IpNetwork>().unwrap();
+        let options = |max_hosts| ScanOptions {
+            timeout: TEST_TIMEOUT,
+            max_hosts,
+            ..ScanOptions::default()
+        };
+        assert!(scan_subnet_with_options(subnet, &options(Some(4))).is_empty());
+        let top = "255.255.255.254".parse().unwrap();
+        let last = "255.255.255.255".parse().unwrap();
+        assert!(scan_ip_range_with_options(top, last, &options(Some(1))).is_empty());
+        if is_localhost_available() {
+            let hosts = scan_subnet_with_options(subnet, &options(Some(8)));
+            assert!(
+                hosts
+                    .iter()
+                    .any(|host| host.ip == IpAddr::from([127, 0, 0, 1]))
+            );
+        }
+    }
 }
@@ -0,0 +1,195 @@
+//! Notes on hosts, from an annotations file.
+//!
+//! Whoever knows a network best rarely runs the scan. An annotations file
+//! carries what they know into the report: a note and free-form attributes
+//! for each host or subnet, and, where a device cannot take a full scan,
+//! limits on what may be probed.
+//!
+//! The file is YAML, keyed by address or subnet:
+//!
+//! ```yaml
+//! "10.0.0.7":
+//!   note: the PDU, do not scan ports above 1024
+//!   max_port: 1024
+//!   attributes:
+//!     owner: facilities
+//!     rack: B4
+//! "10.0.9.0/24":
+//!   note: building controllers, hands off
+//!   skip: true
+//! ```
+//!
+//! A host takes the annotation of the most specific key that contains it,
+//! so a host entry overrides the subnet around it. `max_port` keeps probes
+//! to ports up to that number, and `skip` keeps them off the host entirely.
+
+use ipnetwork::IpNetwork;
+use serde::{Deserialize, Deserializer, Serialize};
+use std::collections::{BTreeMap, HashMap};
+use std::fs;
+use std::net::IpAddr;
+use std::path::Path;
+
+/// What is known about a host or subnet, and what it may be probed with.
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct Annotation {
+    /// Free-text note, e.g. what the device is.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub note: Option<String>,
+    /// Custom attributes, e.g. `owner` or `rack`; numbers and booleans are
+    /// kept as text.
+    #[serde(
+        default,
+        skip_serializing_if = "BTreeMap::is_empty",
+        deserialize_with = "scalars"
+    )]
+    pub attributes: BTreeMap<String, String>,
+    /// Highest port that may be probed; `None` allows every port.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub max_port: Option<u16>,
+    /// Never probe the host.
+    #[serde(default, skip_serializing_if = "std::ops::Not::not")]
+    pub skip: bool,
+}
+
+impl Annotation {
+    /// Whether `port` may be probed on an annotated host.
+    pub fn allows_port(&self, port: u16) -> bool {
+        !self.skip && self.max_port.is_none_or(|max| port <= max)
+    }
+}
+
+/// The annotations of an annotations file, most specific first.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct Annotations {
+    entries: Vec<(IpNetwork, Annotation)>,
+}
+
+impl Annotations {
+    /// Read and parse an annotations file.
+    pub fn load(path: &Path) -> Result<Annotations, String> {
+        let contents = fs::read_to_string(path)
+            .map_err(|e| format!("Cannot read annotations file {}: {}", path.display(), e))?;
+        Annotations::from_yaml(&contents)
+            .map_err(|e| format!("Invalid annotations file {}: {}", path.display(), e))
+    }
+
+    /// Parse annotations from YAML text (see the module docs for the format).
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use asphyxia::scanner::annotations::Annotations;
+    ///
+    /// let annotations = Annotations::from_yaml(
+    ///     "10.0.0.0/24: {note: lab}\n10.0.0.7: {note: PDU, max_port: 1024}",
+    /// )
+    /// .unwrap();
+    /// let pdu = annotations.find("10.0.0.7".parse().unwrap()).unwrap();
+    /// assert_eq!(pdu.note.as_deref(), Some("PDU"));
+    /// assert!(!pdu.allows_port(8080));
+    /// let other = annotations.find("10.0.0.8".parse().unwrap()).unwrap();
+    /// assert_eq!(other.note.as_deref(), Some("lab"));
+    /// assert!(annotations.find("10.0.1.1".parse().unwrap()).is_none());
+    /// ```
+    pub fn from_yaml(yaml: &str) -> Result<Annotations, String> {
+        if yaml.trim().is_empty() {
+            return Ok(Annotations::default());
+        }
+        let raw: HashMap<String, Annotation> =
+            serde_yaml::from_str(yaml).map_err(|e| e.to_string())?;
+        let mut entries = raw
+            .into_iter()
+            .map(|(key, annotation)| {
+                key.trim()
+                    .parse::<IpNetwork>()
+                    .map(|network| (network, annotation))
+                    .map_err(|_| format!("Invalid host: {} (expected an IP or CIDR)", key))
+            })
+            .collect::<Result<Vec<_>, String>>()?;
+        entries.sort_by_key(|(network, _)| std::cmp::Reverse(network.prefix()));
+        Ok(Annotations { entries })
+    }
+
+    /// The annotation of the most specific key that contains `ip`, if any.
+    pub fn find(&self, ip: IpAddr) -> Option<&Annotation> {
+        self.entries
+            .iter()
+            .find(|(network, _)| network.contains(ip))
+            .map(|(_, annotation)| annotation)
+    }
+
+    /// Whether the file annotates no host at all.
+    pub fn is_empty(&self) -> bool {
+        self.entries.is_empty()
+    }
+}
+
+/// Read a map of YAML scalars as text.
+fn scalars<'de, D>(deserializer: D) -> Result<BTreeMap<String, String>, D::Error>
+where
+    D: Deserializer<'de>,
+{
+    use serde::de::Error;
+    use serde_yaml::Value;
+
+    BTreeMap::<String, Value>::deserialize(deserializer)?
+        .into_iter()
+        .map(|(key, value)| {
+            let text = match value {
+                Value::String(text) => text,
+                Value::Number(number) => number.to_string(),
+                Value::Bool(flag) => flag.to_string(),
+                _ => {
+                    return Err(D::Error::custom(format!(
+                        "attribute {} must be text, a number or a boolean",
+                        key
+                    )));
+                }
+            };
+            Ok((key, text))
+        })
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const FILE: &str = r#"
+"10.0.0.7":
+  note: the PDU
+  max_port: 1024
+  attributes:
+    owner: facilities
+    rack: 4
+"10.0.9.0/24":
+  skip: true
+"#;
+
+    #[test]
+    fn test_attributes_keep_numbers_as_text() {
+        let annotations = Annotations::from_yaml(FILE).unwrap();
+        let pdu = annotations.find("10.0.0.7".parse().unwrap()).unwrap();
+        assert_eq!(pdu.attributes["rack"], "4");
+        assert_eq!(pdu.attributes["owner"], "facilities");
+    }
+
+    #[test]
+    fn test_restrictions_limit_ports() {
+        let annotations = Annotations::from_yaml(FILE).unwrap();
+        let pdu = annotations.find("10.0.0.7".parse().unwrap()).unwrap();
+        assert!(pdu.allows_port(1024));
+        assert!(!pdu.allows_port(1025));
+        let skipped = annotations.find("10.0.9.20".parse().unwrap()).unwrap();
+        assert!(!skipped.allows_port(22));
+    }
+
+    #[test]
+    fn test_from_yaml_rejects_bad_keys_and_fields() {
+        assert!(Annotations::from_yaml("printer: {note: x}").is_err());
+        assert!(Annotations::from_yaml("10.0.0.7: {max_ports: 10}").is_err());
+        assert!(Annotations::from_yaml("10.0.0.7: {attributes: {a: [1]}}").is_err());
+    }
+}
@@ -0,0 +1,105 @@
+//! ARP probes for hosts on the local link.
+//!
+//! A TCP probe misses hosts whose every port is filtered, and an ICMP probe
+//! misses hosts that drop pings. On a directly attached IPv4 link, though,
+//! every host must answer ARP to talk at all. An ARP probe asks the kernel
+//! to resolve the address (by sending it a single UDP datagram, which cannot
+//! leave until the address resolves) and then waits for the answer to appear
+//! in the kernel's neighbor table, which also yields the host's MAC address.
+//!
+//! No raw socket is needed. The neighbor table is read from `/proc/net/arp`,
+//! so ARP probes only work on Linux; see [`arp_available`].
+
+use std::net::{IpAddr, SocketAddr, UdpSocket};
+use std::thread;
+use std::time::{Duration, Instant};
+
+use crate::scanner::usage;
+use crate::utils::netif::neighbors;
+
+/// Port the resolving datagram is sent to (the "discard" service), so a
+/// host that does receive it has nothing to act on.
+const DISCARD_PORT: u16 = 9;
+
+/// How often the neighbor table is re-read while waiting for an answer.
+const POLL_INTERVAL: Duration = Duration::from_millis(50);
+
+/// Whether ARP probes can run on this system (Linux with `/proc/net/arp`).
+///
+/// # Examples
+///
+/// ```
+/// use asphyxia::scanner::arp::arp_available;
+///
+/// if !arp_available() {
+///     eprintln!("ARP probes need Linux");
+/// }
+/// ```
+pub fn arp_available() -> bool {
+    cfg!(target_os = "linux") && std::path::Path::new("/proc/net/arp").exists()
+}
+
+/// Resolve `ip` through ARP and wait up to `timeout` for the answer.
+///
+/// # Arguments
+///
+/// * `ip` - The address to resolve; it must be on a directly attached IPv4
+///   link (IPv6 and routed addresses always return `None`)
+/// * `timeout` - How long to wait for the host to answer
+///
+/// # Returns
+///
+/// * `Option<(Duration, String)>` - How long the answer took and the host's
+///   MAC address, or `None` if the host did not answer
+///
+/// # Examples
+///
+/// ```no_run
+/// use asphyxia::scanner::arp::probe;
+/// use std::time::Duration;
+///
+/// if let Some((rtt, mac)) = probe("192.168.1.20".parse().unwrap(), Duration::from_secs(1)) {
+///     println!("up at {} ({} ms)", mac, rtt.as_millis());
+/// }
+/// ```
+pub fn probe(ip: IpAddr, timeout: Duration) -> Option<(Duration, String)> {
+    if !ip.is_ipv4() {
+        return None;
+    }
+    let start = Instant::now();
+    let socket = UdpSocket::bind("0.0.0.0:0").ok()?;
+    socket
+        .send_to(&[], SocketAddr::new(ip, DISCARD_PORT))
+        .ok()?;
+    usage::probed(ip, 0);
+
+    loop {
+        if let Some(neighbor) = neighbors().into_iter().find(|n| n.ip == ip) {
+            usage::answered(ip, 0);
+            return Some((start.elapsed(), neighbor.mac));
+        }
+        let remaining = timeout.saturating_sub(start.elapsed());
+        if remaining.is_zero() {
+            return None;
+        }
+        thread::sleep(remaining.min(POLL_INTERVAL));
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_probe_skips_ipv6() {
+        assert_eq!(probe("::1".parse().unwrap(), Duration::from_secs(1)), None);
+    }
+
+    #[test]
+    fn test_probe_loopback_is_not_in_arp_table() {
+        let timeout = Duration::from_millis(100);
+        let start = Instant::now();
+        assert_eq!(probe("127.0.0.1".parse().unwrap(), timeout), None);
+        assert!(start.elapsed() >= timeout);
+    }
+}
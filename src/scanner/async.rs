@@ -0,0 +1,256 @@
+//! Asynchronous scanning engine built on tokio.
+//!
+//! The blocking scanners in [`crate::scanner::port`] and
+//! [`crate::scanner::address`] park one rayon thread per in-flight connection,
+//! so their parallelism is capped by how many OS threads the process can
+//! afford. The functions here instead drive every probe as a lightweight
+//! tokio task, so thousands of connections can be in flight at once.
+//!
+//! Concurrency is bounded explicitly: at most `concurrency` probes (and thus
+//! sockets) are open at any moment, and new probes are only spawned as
+//! earlier ones finish. Keep the limit below the process file-descriptor
+//! limit (`ulimit -n`).
+//!
+//! These are `async fn`s and must be awaited inside a tokio runtime that has
+//! the time and I/O drivers enabled.
+
+use ipnetwork::IpNetwork;
+use std::future::Future;
+use std::net::{IpAddr, SocketAddr};
+use std::sync::Arc;
+use std::time::{Duration, Instant};
+use tokio::net::TcpStream;
+use tokio::sync::Semaphore;
+use tokio::task::JoinSet;
+use tokio::time::timeout as with_timeout;
+
+use crate::scanner::address::{self, HostScanResult, HostState, PROBE_PORT};
+use crate::scanner::port::{CONNECT_TIMEOUT, Evidence, PortScanResult, PortState};
+
+/// Default upper bound on in-flight probes for the async scanners.
+pub const DEFAULT_ASYNC_CONCURRENCY: usize = 1024;
+
+/// Attempt a TCP connection to `addr`, giving up after `timeout`.
+async fn connect(addr: SocketAddr, timeout: Duration) -> Option<std::io::Result<TcpStream>> {
+    with_timeout(timeout, TcpStream::connect(addr)).await.ok()
+}
+
+/// Run `probe` for every item, keeping at most `concurrency` probes in flight,
+/// and collect the successful results.
+async fn run_bounded<T, R, F, Fut>(
+    items: impl IntoIterator<Item = T>,
+    concurrency: usize,
+    probe: F,
+) -> Vec<R>
+where
+    T: Send + 'static,
+    R: Send + 'static,
+    F: Fn(T) -> Fut,
+    Fut: Future<Output = Option<R>> + Send + 'static,
+{
+    let permits = Arc::new(Semaphore::new(concurrency.max(1)));
+    let mut tasks = JoinSet::new();
+    let mut found = Vec::new();
+
+    for item in items {
+        // Wait for a free slot before spawning, so a huge target list never
+        // materialises as a huge set of pending tasks.
+        let permit = Arc::clone(&permits)
+            .acquire_owned()
+            .await
+            .expect("scan semaphore is never closed");
+        let fut = probe(item);
+        tasks.spawn(async move {
+            let result = fut.await;
+            drop(permit);
+            result
+        });
+
+        // Reap whatever has already finished to keep memory flat.
+        while let Some(done) = tasks.try_join_next() {
+            found.extend(done.ok().flatten());
+        }
+    }
+
+    while let Some(done) = tasks.join_next().await {
+        found.extend(done.ok().flatten());
+    }
+    found
+}
+
+/// Scan `ports` on `host` concurrently and return the open ones, sorted by port.
+///
+/// Only open ports are reported, so every result has [`PortState::Open`].
+///
+/// `host` is an already-resolved address; use
+/// [`resolve_host`](crate::scanner::port::resolve_host) to turn a hostname into
+/// one. At most `concurrency` connection attempts are in flight at once.
+///
+/// # Arguments
+///
+/// * `host` - The IP address to scan (IPv4 or IPv6)
+/// * `ports` - The ports to probe
+/// * `timeout` - Optional per-connection timeout (defaults to [`CONNECT_TIMEOUT`])
+/// * `concurrency` - Maximum number of simultaneous connection attempts
+///
+/// # Examples
+///
+/// ```no_run
+/// use asphyxia::scanner::r#async::scan_ports_async;
+/// use std::net::IpAddr;
+///
+/// # async fn run() {
+/// let host: IpAddr = "192.168.1.1".parse().unwrap();
+/// let open = scan_ports_async(host, (1..=1024).collect(), None, 512).await;
+/// for result in open {
+///     println!("{} is open", result.port);
+/// }
+/// # }
+/// ```
+pub async fn scan_ports_async(
+    host: IpAddr,
+    ports: Vec<u16>,
+    timeout: Option<Duration>,
+    concurrency: usize,
+) -> Vec<PortScanResult> {
+    let timeout = timeout.unwrap_or(CONNECT_TIMEOUT);
+    let mut open = run_bounded(ports, concurrency, move |port| async move {
+        let start = Instant::now();
+        match connect(SocketAddr::new(host, port), timeout).await {
+            Some(Ok(_)) => Some(PortScanResult {
+                port,
+                state: PortState::Open,
+                evidence: Evidence::Handshake,
+                attempts: 1,
+                latency: start.elapsed(),
+                ttl: None,
+                fingerprint: None,
+            }),
+            _ => None,
+        }
+    })
+    .await;
+    open.sort_by_key(|result| result.port);
+    open
+}
+
+/// Scan an entire subnet for available hosts concurrently.
+///
+/// Availability is decided exactly as in
+/// [`scan_address`](crate::scanner::address::scan_address): a host is up when
+/// the probe connection succeeds or is actively refused. IPv6 subnets wider
+/// than [`MAX_IPV6_HOSTS`](crate::scanner::address::MAX_IPV6_HOSTS) are
+/// refused and yield an empty vector.
+///
+/// # Arguments
+///
+/// * `subnet` - The subnet to scan in CIDR notation
+/// * `timeout` - Optional per-host timeout (defaults to [`CONNECT_TIMEOUT`])
+/// * `concurrency` - Maximum number of simultaneous connection attempts
+///
+/// # Examples
+///
+/// ```no_run
+/// use asphyxia::scanner::r#async::{DEFAULT_ASYNC_CONCURRENCY, scan_subnet_async};
+///
+/// # async fn run() {
+/// let subnet = "10.0.0.0/16".parse().unwrap();
+/// let hosts = scan_subnet_async(subnet, None, DEFAULT_ASYNC_CONCURRENCY).await;
+/// println!("Found {} available hosts", hosts.len());
+/// # }
+/// ```
+pub async fn scan_subnet_async(
+    subnet: IpNetwork,
+    timeout: Option<Duration>,
+    concurrency: usize,
+) -> Vec<HostScanResult> {
+    let hosts: Box<dyn Iterator<Item = IpAddr> + Send> =
+        match (subnet.network(), subnet.broadcast()) {
+            (IpAddr::V4(network), IpAddr::V4(broadcast)) => {
+                Box::new((u32::from(network)..=u32::from(broadcast)).map(address::ipv4))
+            }
+            (IpAddr::V6(network), IpAddr::V6(broadcast)) => {
+                match address::ipv6_hosts(network, broadcast) {
+                    Some(hosts) => Box::new(hosts.into_iter()),
+                    None => return Vec::new(),
+                }
+            }
+            // network() and broadcast() always share the subnet's family.
+            _ => return Vec::new(),
+        };
+
+    let timeout = timeout.unwrap_or(CONNECT_TIMEOUT);
+    let mut available = run_bounded(hosts, concurrency, move |ip| async move {
+        let start = Instant::now();
+        let answered = match connect(SocketAddr::new(ip, PROBE_PORT), timeout).await {
+            Some(Ok(_)) => true,
+            Some(Err(e)) => address::host_answered(&e),
+            None => false,
+        };
+        answered.then(|| HostScanResult {
+            ip,
+            state: HostState::Up,
+            rtt: start.elapsed(),
+            hostname: None,
+            mac: None,
+            ttl: None,
+        })
+    })
+    .await;
+    available.sort_by_key(|result| result.ip);
+    available
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::net::TcpListener;
+
+    const TEST_TIMEOUT: Option<Duration> = Some(Duration::from_millis(100));
+
+    #[tokio::test]
+    async fn test_scan_ports_async_finds_listener() {
+        let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+        let open_port = listener.local_addr().unwrap().port();
+        let host: IpAddr = "127.0.0.1".parse().unwrap();
+
+        let hits = scan_ports_async(host, vec![1, open_port], TEST_TIMEOUT, 4).await;
+
+        assert_eq!(hits.len(), 1);
+        assert_eq!(hits[0].port, open_port);
+    }
+
+    #[tokio::test]
+    async fn test_scan_ports_async_sorted_with_tiny_limit() {
+        let a = TcpListener::bind("127.0.0.1:0").unwrap();
+        let b = TcpListener::bind("127.0.0.1:0").unwrap();
+        let mut ports = vec![
+            a.local_addr().unwrap().port(),
+            b.local_addr().unwrap().port(),
+        ];
+        let host: IpAddr = "127.0.0.1".parse().unwrap();
+
+        // A limit of one still scans everything, just serially.
+        let hits = scan_ports_async(host, ports.clone(), TEST_TIMEOUT, 1).await;
+
+        ports.sort();
+        assert_eq!(hits.iter().map(|h| h.port).collect::<Vec<_>>(), ports);
+    }
+
+    #[tokio::test]
+    async fn test_scan_subnet_async_loopback() {
+        // Loopback refuses port 80, which still counts as "up".
+        let subnet = "127.0.0.0/30".parse::<IpNetwork>().unwrap();
+        let results = scan_subnet_async(subnet, TEST_TIMEOUT, 8).await;
+
+        let localhost: IpAddr = "127.0.0.1".parse().unwrap();
+        assert!(results.iter().any(|h| h.ip == localhost));
+        assert!(results.windows(2).all(|w| w[0].ip <= w[1].ip));
+    }
+
+    #[tokio::test]
+    async fn test_scan_subnet_async_refuses_wide_ipv6() {
+        let subnet = "2001:db8::/64".parse::<IpNetwork>().unwrap();
+        assert!(scan_subnet_async(subnet, TEST_TIMEOUT, 8).await.is_empty());
+    }
+}
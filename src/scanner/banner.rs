@@ -0,0 +1,127 @@
+//! Banner grabbing for identifying services.
+//!
+//! Many services announce themselves as soon as a client connects (SSH,
+//! Telnet, FTP, SMTP), and web servers name themselves in the `Server`
+//! header. Reading that first bit of text is often enough to tell which
+//! device or firmware is behind an open port.
+
+use std::io::{Read, Write};
+use std::net::{IpAddr, SocketAddr};
+use std::time::Duration;
+
+use crate::scanner::socket::{self, Purpose};
+
+/// Ports spoken to as plain HTTP when grabbing a banner.
+const HTTP_PORTS: &[u16] = &[80, 5000, 8000, 8080, 49152];
+
+/// Most bytes read from a service while looking for its banner.
+const MAX_BANNER_BYTES: usize = 1024;
+
+/// Connect to `port` on `ip` and return the service's identifying banner.
+///
+/// For plain HTTP ports a `HEAD /` request is sent and the `Server` header
+/// (or, without one, the status line) is returned; for everything else the
+/// first line the service sends on its own is returned. TLS services such as
+/// HTTPS send nothing before a handshake, so they yield `None`, as does a
+/// service that stays silent for `timeout`.
+///
+/// # Examples
+///
+/// ```no_run
+/// use asphyxia::scanner::banner::grab_banner;
+/// use std::time::Duration;
+///
+/// let ip = "192.168.1.1".parse().unwrap();
+/// if let Some(banner) = grab_banner(ip, 22, Duration::from_secs(2)) {
+///     println!("ssh: {}", banner);
+/// }
+/// ```
+pub fn grab_banner(ip: IpAddr, port: u16, timeout: Duration) -> Option<String> {
+    let mut stream = socket::connect(
+        SocketAddr::new(ip, port),
+        Purpose::Exchange.profile(),
+        timeout,
+    )
+    .ok()?;
+    stream.set_read_timeout(Some(timeout)).ok()?;
+    stream.set_write_timeout(Some(timeout)).ok()?;
+
+    let http = HTTP_PORTS.contains(&port);
+    if http {
+        let request = format!("HEAD / HTTP/1.0\r\nHost: {}\r\n\r\n", ip);
+        stream.write_all(request.as_bytes()).ok()?;
+    }
+
+    let mut buf = [0u8; MAX_BANNER_BYTES];
+    let n = stream.read(&mut buf).ok()?;
+    let text = String::from_utf8_lossy(&buf[..n]);
+    if http {
+        http_banner(&text)
+    } else {
+        text.lines().next().and_then(clean)
+    }
+}
+
+/// The `Server` header of an HTTP response, or its status line.
+fn http_banner(response: &str) -> Option<String> {
+    response
+        .lines()
+        .find_map(|line| {
+            let (name, value) = line.split_once(':')?;
+            name.eq_ignore_ascii_case("server").then_some(value)
+        })
+        .or_else(|| response.lines().next())
+        .and_then(clean)
+}
+
+/// Trim a banner and drop control characters so it is safe to print.
+fn clean(line: &str) -> Option<String> {
+    let cleaned: String = line.trim().chars().filter(|c| !c.is_control()).collect();
+    (!cleaned.is_empty()).then_some(cleaned)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::net::TcpListener;
+    use std::thread;
+
+    /// Serve `reply` to the first client on a loopback port, after reading
+    /// its request if `read_first` is set.
+    fn serve_once(reply: &'static str, read_first: bool) -> u16 {
+        let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+        let port = listener.local_addr().unwrap().port();
+        thread::spawn(move || {
+            let (mut stream, _) = listener.accept().unwrap();
+            if read_first {
+                let mut buf = [0u8; 256];
+                let _ = stream.read(&mut buf);
+            }
+            stream.write_all(reply.as_bytes()).unwrap();
+        });
+        port
+    }
+
+    #[test]
+    fn test_grab_banner_reads_first_line() {
+        let port = serve_once("SSH-2.0-dropbear_2022.83\r\nmore\r\n", false);
+        let banner = grab_banner("127.0.0.1".parse().unwrap(), port, Duration::from_secs(1));
+        assert_eq!(banner.as_deref(), Some("SSH-2.0-dropbear_2022.83"));
+    }
+
+    #[test]
+    fn test_http_banner_prefers_server_header() {
+        let response = "HTTP/1.0 200 OK\r\nDate: today\r\nserver: lighttpd/1.4.59\r\n\r\n";
+        assert_eq!(http_banner(response).as_deref(), Some("lighttpd/1.4.59"));
+        assert_eq!(
+            http_banner("HTTP/1.1 401 Unauthorized\r\n\r\n").as_deref(),
+            Some("HTTP/1.1 401 Unauthorized")
+        );
+    }
+
+    #[test]
+    fn test_clean_drops_control_characters() {
+        assert_eq!(clean(" \u{1b}[0mlogin: ").as_deref(), Some("[0mlogin:"));
+        assert_eq!(clean("\r\n"), None);
+    }
+}
@@ -0,0 +1,94 @@
+//! Cancellation of running scans.
+//!
+//! A `/16` address scan or a full 65535-port scan can run for a long time. A
+//! [`CancelToken`] lets a Ctrl-C handler or an embedding application stop such
+//! a scan early: probes that have not started yet are skipped, probes already
+//! in flight finish, and the scan returns whatever it found so far.
+
+use std::sync::Arc;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::thread;
+use std::time::{Duration, Instant};
+
+/// Longest a [`CancelToken::sleep`] goes without looking at the flag.
+const SLEEP_SLICE: Duration = Duration::from_millis(50);
+
+/// A shared flag that asks a running scan to stop.
+///
+/// Clones share the same flag, so keep one clone and hand the other to the
+/// scan through [`ScanOptions::cancel`](crate::scanner::options::ScanOptions::cancel).
+///
+/// # Examples
+///
+/// ```
+/// use asphyxia::scanner::cancel::CancelToken;
+/// use asphyxia::scanner::options::ScanOptions;
+/// use asphyxia::scanner::port::scan_ports_with_options;
+///
+/// let token = CancelToken::new();
+/// let options = ScanOptions {
+///     cancel: Some(token.clone()),
+///     ..ScanOptions::default()
+/// };
+///
+/// // Cancelled before it starts: no probe is sent and nothing is found.
+/// token.cancel();
+/// assert!(scan_ports_with_options("127.0.0.1", &[22, 80], &options).is_empty());
+/// ```
+#[derive(Debug, Clone, Default)]
+pub struct CancelToken {
+    cancelled: Arc<AtomicBool>,
+}
+
+impl CancelToken {
+    /// Create a token that has not been cancelled.
+    pub fn new() -> CancelToken {
+        CancelToken::default()
+    }
+
+    /// Ask every scan holding this token to stop.
+    pub fn cancel(&self) {
+        self.cancelled.store(true, Ordering::Relaxed);
+    }
+
+    /// Whether [`cancel`](CancelToken::cancel) has been called.
+    pub fn is_cancelled(&self) -> bool {
+        self.cancelled.load(Ordering::Relaxed)
+    }
+
+    /// Sleep for `duration`, waking early once the token is cancelled; for
+    /// the pauses between rounds of a repeated check.
+    pub fn sleep(&self, duration: Duration) {
+        let deadline = Instant::now() + duration;
+        while !self.is_cancelled() {
+            let left = deadline.saturating_duration_since(Instant::now());
+            if left.is_zero() {
+                break;
+            }
+            thread::sleep(left.min(SLEEP_SLICE));
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_clones_share_the_flag() {
+        let token = CancelToken::new();
+        let clone = token.clone();
+        assert!(!clone.is_cancelled());
+        token.cancel();
+        assert!(clone.is_cancelled());
+    }
+
+    #[test]
+    fn test_sleep_returns_once_cancelled() {
+        let token = CancelToken::new();
+        token.cancel();
+        let start = Instant::now();
+        token.sleep(Duration::from_secs(10));
+        assert!(start.elapsed() < Duration::from_secs(1));
+    }
+}
@@ -0,0 +1,278 @@
+//! Passive TCP/IP stack fingerprints and a coarse OS guess.
+//!
+//! Operating systems answer a SYN with a few telltale values: the initial
+//! TTL (see [`ttl`](crate::scanner::ttl)), the TCP window size, the maximum
+//! segment size option and the IP "don't fragment" bit. A SYN scan sees all
+//! of them in every SYN-ACK it receives, so a scan given a
+//! [`FingerprintCache`] (see
+//! [`ScanOptions::fingerprints`](crate::scanner::options::ScanOptions::fingerprints))
+//! fills it as it goes, and [`fingerprint_host`] only sends a probe of its
+//! own for hosts the scan did not already cover.
+//!
+//! The guess is deliberately coarse (an OS family, not a version): a few
+//! header fields cannot tell more apart, and tuned or proxied stacks fool it.
+
+use std::collections::HashMap;
+use std::io;
+use std::net::IpAddr;
+use std::sync::Mutex;
+use std::time::Duration;
+
+use crate::scanner::syn;
+use crate::scanner::ttl::{initial_ttl, ipv4_ttl};
+
+/// Length of a TCP header without options.
+const TCP_HEADER_LEN: usize = 20;
+
+/// TCP option kinds.
+const OPTION_END: u8 = 0;
+const OPTION_NOP: u8 = 1;
+const OPTION_MSS: u8 = 2;
+
+/// What a host's SYN-ACK revealed about its TCP/IP stack.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct StackFingerprint {
+    /// The TTL the answer most likely started with (see
+    /// [`initial_ttl`](crate::scanner::ttl::initial_ttl)).
+    pub initial_ttl: u8,
+    /// The advertised TCP window size.
+    pub window: u16,
+    /// The maximum segment size option, if the answer carried one.
+    pub mss: Option<u16>,
+    /// Whether the IP "don't fragment" bit was set.
+    pub dont_fragment: bool,
+}
+
+impl StackFingerprint {
+    /// Read the fingerprint from a SYN-ACK received on an IPv4 raw socket,
+    /// IP header included. `None` if the packet is too short to tell.
+    pub(crate) fn from_ipv4_syn_ack(packet: &[u8]) -> Option<StackFingerprint> {
+        let ttl = ipv4_ttl(packet)?;
+        let dont_fragment = packet.get(6)? & 0x40 != 0;
+        let header_len = usize::from(packet[0] & 0x0f) * 4;
+        let segment = packet.get(header_len..)?;
+        if segment.len() < TCP_HEADER_LEN {
+            return None;
+        }
+        let options_end = (usize::from(segment[12] >> 4) * 4).min(segment.len());
+        Some(StackFingerprint {
+            initial_ttl: initial_ttl(ttl),
+            window: u16::from_be_bytes([segment[14], segment[15]]),
+            mss: segment
+                .get(TCP_HEADER_LEN..options_end)
+                .and_then(mss_option),
+            dont_fragment,
+        })
+    }
+
+    /// The operating system family these values are typical of, or `None`
+    /// when they match none.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use asphyxia::scanner::fingerprint::StackFingerprint;
+    ///
+    /// let fingerprint = StackFingerprint {
+    ///     initial_ttl: 128,
+    ///     window: 64240,
+    ///     mss: Some(1460),
+    ///     dont_fragment: true,
+    /// };
+    /// assert_eq!(fingerprint.os_guess(), Some("Windows"));
+    /// ```
+    pub fn os_guess(&self) -> Option<&'static str> {
+        match (self.initial_ttl, self.window) {
+            // macOS and the BSDs offer the full unscaled window.
+            (64, 65535) => Some("macOS or BSD"),
+            (64, _) => Some("Linux"),
+            (128, _) => Some("Windows"),
+            (255, _) => Some("network device or Solaris"),
+            _ => None,
+        }
+    }
+}
+
+/// The value of the MSS option among TCP `options`.
+fn mss_option(options: &[u8]) -> Option<u16> {
+    let mut rest = options;
+    loop {
+        match rest {
+            [] | [OPTION_END, ..] => return None,
+            [OPTION_NOP, tail @ ..] => rest = tail,
+            [OPTION_MSS, 4, high, low, ..] => return Some(u16::from_be_bytes([*high, *low])),
+            [_, len, ..] if *len >= 2 => rest = rest.get(usize::from(*len)..)?,
+            _ => return None,
+        }
+    }
+}
+
+/// Fingerprints gathered during a scan, one per host (the first seen).
+#[derive(Debug, Default)]
+pub struct FingerprintCache {
+    hosts: Mutex<HashMap<IpAddr, StackFingerprint>>,
+}
+
+impl FingerprintCache {
+    /// An empty cache.
+    pub fn new() -> FingerprintCache {
+        FingerprintCache::default()
+    }
+
+    /// Remember `fingerprint` for `ip`, unless one is already known.
+    pub fn insert(&self, ip: IpAddr, fingerprint: StackFingerprint) {
+        self.hosts
+            .lock()
+            .unwrap_or_else(|e| e.into_inner())
+            .entry(ip)
+            .or_insert(fingerprint);
+    }
+
+    /// The fingerprint known for `ip`, if any.
+    pub fn get(&self, ip: IpAddr) -> Option<StackFingerprint> {
+        self.hosts
+            .lock()
+            .unwrap_or_else(|e| e.into_inner())
+            .get(&ip)
+            .copied()
+    }
+}
+
+/// The fingerprint of `ip`: from `cache` if a scan already saw a SYN-ACK
+/// from it, otherwise from one SYN probe to `port` (which should be open),
+/// whose answer is then cached.
+///
+/// # Arguments
+///
+/// * `ip` - The host to fingerprint (IPv4; IPv6 answers carry no TTL here)
+/// * `port` - An open TCP port on the host, probed only on a cache miss
+/// * `cache` - Fingerprints gathered so far
+/// * `timeout` - How long to wait for the answer to a probe
+///
+/// # Returns
+///
+/// * `io::Result<Option<StackFingerprint>>` - The fingerprint, `None` if
+///   the port did not answer with a SYN-ACK, or an error if the probe could
+///   not be sent (most often a lack of raw-socket privileges)
+///
+/// # Examples
+///
+/// ```no_run
+/// use asphyxia::scanner::fingerprint::{FingerprintCache, fingerprint_host};
+/// use std::time::Duration;
+///
+/// let cache = FingerprintCache::new();
+/// let ip = "10.0.0.5".parse().unwrap();
+/// if let Some(fingerprint) = fingerprint_host(ip, 22, &cache, Duration::from_secs(2)).unwrap() {
+///     println!("{:?}", fingerprint.os_guess());
+/// }
+/// ```
+pub fn fingerprint_host(
+    ip: IpAddr,
+    port: u16,
+    cache: &FingerprintCache,
+    timeout: Duration,
+) -> io::Result<Option<StackFingerprint>> {
+    if let Some(fingerprint) = cache.get(ip) {
+        return Ok(Some(fingerprint));
+    }
+    let fingerprint = syn::probe(ip, port, timeout)?.fingerprint;
+    if let Some(fingerprint) = fingerprint {
+        cache.insert(ip, fingerprint);
+    }
+    Ok(fingerprint)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// An IPv4 SYN-ACK with the given TTL, window and TCP options.
+    fn syn_ack(ttl: u8, window: u16, options: &[u8]) -> Vec<u8> {
+        let mut packet = vec![0u8; 20];
+        packet[0] = 0x45;
+        packet[6] = 0x40;
+        packet[8] = ttl;
+        let mut segment = vec![0u8; TCP_HEADER_LEN];
+        segment[12] = (((TCP_HEADER_LEN + options.len()) / 4) as u8) << 4;
+        segment[14..16].copy_from_slice(&window.to_be_bytes());
+        segment.extend_from_slice(options);
+        packet.extend(segment);
+        packet
+    }
+
+    #[test]
+    fn test_reads_syn_ack_fields() {
+        let options = [
+            OPTION_NOP, OPTION_NOP, 2, 4, 0x05, 0xb4, OPTION_NOP, OPTION_NOP,
+        ];
+        let packet = syn_ack(57, 64240, &options);
+        let fingerprint = StackFingerprint::from_ipv4_syn_ack(&packet).unwrap();
+        assert_eq!(fingerprint.initial_ttl, 64);
+        assert_eq!(fingerprint.window, 64240);
+        assert_eq!(fingerprint.mss, Some(1460));
+        assert!(fingerprint.dont_fragment);
+        assert_eq!(fingerprint.os_guess(), Some("Linux"));
+        assert_eq!(StackFingerprint::from_ipv4_syn_ack(&packet[..30]), None);
+    }
+
+    #[test]
+    fn test_mss_option_skips_other_options() {
+        assert_eq!(mss_option(&[4, 2, 2, 4, 0x02, 0x18]), Some(536));
+        assert_eq!(mss_option(&[OPTION_END, 2, 4, 0, 1]), None);
+        assert_eq!(mss_option(&[8, 0]), None);
+    }
+
+    #[test]
+    fn test_cache_keeps_first_fingerprint() {
+        let cache = FingerprintCache::new();
+        let ip = "10.0.0.5".parse().unwrap();
+        let first = StackFingerprint {
+            initial_ttl: 64,
+            window: 65535,
+            mss: None,
+            dont_fragment: false,
+        };
+        cache.insert(ip, first);
+        cache.insert(
+            ip,
+            StackFingerprint {
+                initial_ttl: 128,
+                ..first
+            },
+        );
+        assert_eq!(cache.get(ip), Some(first));
+        assert_eq!(cache.get("10.0.0.6".parse().unwrap()), None);
+    }
+
+    #[test]
+    fn test_fingerprint_host_uses_cache_without_probing() {
+        let cache = FingerprintCache::new();
+        // TEST-NET-1 never answers, so a probe would come back empty.
+        let ip = "192.0.2.99".parse().unwrap();
+        let known = StackFingerprint {
+            initial_ttl: 255,
+            window: 4128,
+            mss: Some(536),
+            dont_fragment: false,
+        };
+        cache.insert(ip, known);
+        let found = fingerprint_host(ip, 22, &cache, Duration::from_millis(10)).unwrap();
+        assert_eq!(found, Some(known));
+    }
+
+    #[test]
+    fn test_fingerprint_host_probes_on_cache_miss() {
+        if !syn::syn_available() {
+            println!("Skipping: raw sockets are not permitted");
+            return;
+        }
+        let listener = std::net::TcpListener::bind("127.0.0.1:0").unwrap();
+        let port = listener.local_addr().unwrap().port();
+        let ip = "127.0.0.1".parse().unwrap();
+        let cache = FingerprintCache::new();
+        let found = fingerprint_host(ip, port, &cache, Duration::from_millis(500)).unwrap();
+        assert_eq!(found.map(|f| f.initial_ttl), Some(64));
+        assert_eq!(cache.get(ip), found);
+    }
+}
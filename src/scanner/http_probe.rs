@@ -0,0 +1,253 @@
+//! HTTP(S) probing of open web ports.
+//!
+//! An open 80 or 443 says little on its own: it may be a router's admin
+//! panel, a REST API or a default server page. One `GET /` tells them apart
+//! by the status code, the `Server` header and the page title. HTTPS is
+//! spoken on the usual TLS ports, and certificates are never verified (see
+//! [`tls`](crate::scanner::tls)) — the self-signed ones of management
+//! interfaces are exactly what a scan wants to find, and nothing is sent
+//! that needs protecting.
+
+use std::collections::BTreeMap;
+use std::io::{self, Read, Write};
+use std::net::{IpAddr, SocketAddr};
+use std::time::Duration;
+
+use rayon::prelude::*;
+use rustls::pki_types::ServerName;
+use rustls::{ClientConnection, StreamOwned};
+
+use crate::scanner::port::resolve_host;
+use crate::scanner::socket::{self, Purpose};
+use crate::scanner::tls::client_config;
+
+/// Ports commonly serving HTTP or HTTPS.
+pub const WEB_PORTS: &[u16] = &[
+    80, 81, 443, 591, 3000, 4443, 5000, 8000, 8008, 8080, 8081, 8443, 8888, 9000, 9090, 9443,
+];
+
+/// Web ports that speak TLS; the others are tried as plain HTTP first.
+const TLS_PORTS: &[u16] = &[443, 4443, 8443, 9443];
+
+/// Most bytes read from a response; titles sit near the top of the page.
+const MAX_RESPONSE_BYTES: usize = 64 * 1024;
+
+/// Longest page title kept, in characters.
+const MAX_TITLE_CHARS: usize = 120;
+
+/// What a web server answered to `GET /`.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct HttpInfo {
+    /// The response status code (e.g. 200, 401, 302).
+    pub status: u16,
+    /// The `Server` header, if the response carried one.
+    pub server: Option<String>,
+    /// The text of the page's `<title>`, if it had one.
+    pub title: Option<String>,
+    /// Whether the answer came over TLS.
+    pub tls: bool,
+}
+
+/// Whether `port` is one of the [`WEB_PORTS`].
+pub fn is_web_port(port: u16) -> bool {
+    WEB_PORTS.contains(&port)
+}
+
+/// Send `GET /` to `port` on `host` and read the status, server and title.
+///
+/// TLS ports are tried over HTTPS first and the others over plain HTTP;
+/// when that gets no HTTP answer the other scheme is tried once.
+///
+/// # Arguments
+///
+/// * `host` - Hostname or IP address; a hostname is sent as the `Host`
+///   header (and TLS server name), so virtual hosts answer as they would
+///   to a browser
+/// * `port` - An open TCP port on the host
+/// * `timeout` - Connect and read timeout for each attempt
+///
+/// # Returns
+///
+/// * `Option<HttpInfo>` - The answer, or `None` if the port does not speak
+///   HTTP (or stayed silent for `timeout`)
+///
+/// # Examples
+///
+/// ```no_run
+/// use asphyxia::scanner::http_probe::http_probe;
+/// use std::time::Duration;
+///
+/// if let Some(info) = http_probe("192.168.1.1", 443, Duration::from_secs(2)) {
+///     println!("{} {:?}", info.status, info.title);
+/// }
+/// ```
+pub fn http_probe(host: &str, port: u16, timeout: Duration) -> Option<HttpInfo> {
+    let ip = resolve_host(host)?;
+    let tls_first = TLS_PORTS.contains(&port);
+    fetch(host, ip, port, tls_first, timeout).or_else(|| fetch(host, ip, port, !tls_first, timeout))
+}
+
+/// Probe every web port among `ports` on `host` in parallel (see
+/// [`http_probe`]), returning the answers by port.
+pub fn probe_web_ports(host: &str, ports: &[u16], timeout: Duration) -> BTreeMap<u16, HttpInfo> {
+    ports
+        .par_iter()
+        .filter(|&&port| is_web_port(port))
+        .filter_map(|&port| Some((port, http_probe(host, port, timeout)?)))
+        .collect()
+}
+
+/// One `GET /` over plain HTTP or TLS.
+fn fetch(host: &str, ip: IpAddr, port: u16, tls: bool, timeout: Duration) -> Option<HttpInfo> {
+    let stream = socket::connect(
+        SocketAddr::new(ip, port),
+        Purpose::Exchange.profile(),
+        timeout,
+    )
+    .ok()?;
+    stream.set_read_timeout(Some(timeout)).ok()?;
+    stream.set_write_timeout(Some(timeout)).ok()?;
+    let request = format!(
+        "GET / HTTP/1.1\r\nHost: {}\r\nUser-Agent: asphyxia/{}\r\nAccept: text/html\r\nConnection: close\r\n\r\n",
+        host_header(host, port, tls),
+        env!("CARGO_PKG_VERSION")
+    );
+    let response = if tls {
+        let name = ServerName::try_from(host.to_string()).ok()?;
+        let connection = ClientConnection::new(client_config(), name).ok()?;
+        exchange(StreamOwned::new(connection, stream), &request)
+    } else {
+        exchange(stream, &request)
+    }?;
+    parse_response(&response, tls)
+}
+
+/// Send `request` and read the response until the server closes the
+/// connection, [`MAX_RESPONSE_BYTES`] arrive or the read times out.
+fn exchange<S: Read + Write>(mut stream: S, request: &str) -> Option<Vec<u8>> {
+    stream.write_all(request.as_bytes()).ok()?;
+    let mut response = Vec::new();
+    let mut buf = [0u8; 4096];
+    while response.len() < MAX_RESPONSE_BYTES {
+        match stream.read(&mut buf) {
+            Ok(0) => break,
+            Ok(n) => response.extend_from_slice(&buf[..n]),
+            Err(e) if e.kind() == io::ErrorKind::Interrupted => continue,
+            // A timeout or a missing TLS close_notify still leaves what
+            // arrived so far.
+            Err(_) => break,
+        }
+    }
+    (!response.is_empty()).then_some(response)
+}
+
+/// The `Host` header for `host`: IPv6 addresses in brackets, and the port
+/// unless it is the scheme's default.
+fn host_header(host: &str, port: u16, tls: bool) -> String {
+    let host = match host.parse::<IpAddr>() {
+        Ok(IpAddr::V6(ip)) => format!("[{}]", ip),
+        _ => host.to_string(),
+    };
+    match (port, tls) {
+        (80, false) | (443, true) => host,
+        _ => format!("{}:{}", host, port),
+    }
+}
+
+/// Read the status, `Server` header and title from a raw HTTP response.
+pub(crate) fn parse_response(response: &[u8], tls: bool) -> Option<HttpInfo> {
+    let text = String::from_utf8_lossy(response);
+    let (head, body) = text.split_once("\r\n\r\n").unwrap_or((&text, ""));
+    let mut lines = head.lines();
+    let status = lines
+        .next()?
+        .strip_prefix("HTTP/")?
+        .split_whitespace()
+        .nth(1)?
+        .parse()
+        .ok()?;
+    let server = lines
+        .find_map(|line| {
+            let (name, value) = line.split_once(':')?;
+            name.eq_ignore_ascii_case("server").then_some(value)
+        })
+        .and_then(clean);
+    Some(HttpInfo {
+        status,
+        server,
+        title: page_title(body),
+        tls,
+    })
+}
+
+/// The text of the first `<title>` element in `body`, whitespace collapsed.
+fn page_title(body: &str) -> Option<String> {
+    // ASCII lowercasing keeps byte offsets, so they apply to `body` too.
+    let lower = body.to_ascii_lowercase();
+    let open = lower.find("<title")?;
+    let start = open + lower[open..].find('>')? + 1;
+    let end = start + lower[start..].find("</title")?;
+    let title: String = body[start..end]
+        .split_whitespace()
+        .collect::<Vec<_>>()
+        .join(" ")
+        .chars()
+        .take(MAX_TITLE_CHARS)
+        .collect();
+    clean(&title)
+}
+
+/// Trim a header or title and drop control characters so it is safe to print.
+fn clean(text: &str) -> Option<String> {
+    let cleaned: String = text.trim().chars().filter(|c| !c.is_control()).collect();
+    (!cleaned.is_empty()).then_some(cleaned)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::net::TcpListener;
+    use std::thread;
+
+    #[test]
+    fn test_parse_response_reads_status_server_and_title() {
+        let response = b"HTTP/1.1 401 Unauthorized\r\nServer: lighttpd/1.4.59\r\n\r\n\
+            <html><head><TITLE>\n  Router   Login </title></head></html>";
+        let info = parse_response(response, true).unwrap();
+        assert_eq!(info.status, 401);
+        assert_eq!(info.server.as_deref(), Some("lighttpd/1.4.59"));
+        assert_eq!(info.title.as_deref(), Some("Router Login"));
+        assert!(info.tls);
+    }
+
+    #[test]
+    fn test_parse_response_rejects_non_http() {
+        assert_eq!(parse_response(b"SSH-2.0-OpenSSH_9.6\r\n", false), None);
+        let bare = parse_response(b"HTTP/1.0 204 No Content\r\n\r\n", false).unwrap();
+        assert_eq!((bare.status, bare.server, bare.title), (204, None, None));
+    }
+
+    #[test]
+    fn test_host_header_omits_default_port() {
+        assert_eq!(host_header("example.com", 80, false), "example.com");
+        assert_eq!(host_header("example.com", 443, false), "example.com:443");
+        assert_eq!(host_header("2001:db8::1", 8443, true), "[2001:db8::1]:8443");
+    }
+
+    #[test]
+    fn test_http_probe_reads_plain_http() {
+        let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+        let port = listener.local_addr().unwrap().port();
+        thread::spawn(move || {
+            let (mut stream, _) = listener.accept().unwrap();
+            let mut buf = [0u8; 512];
+            let _ = stream.read(&mut buf);
+            stream
+                .write_all(b"HTTP/1.1 200 OK\r\n\r\n<title>Grafana</title>")
+                .unwrap();
+        });
+        let info = http_probe("127.0.0.1", port, Duration::from_secs(1)).unwrap();
+        assert_eq!(info.title.as_deref(), Some("Grafana"));
+        assert!(!info.tls);
+    }
+}
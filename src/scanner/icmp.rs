@@ -0,0 +1,426 @@
+//! ICMP echo ("ping") probes.
+//!
+//! A TCP probe only proves a host is up when something answers on the probe
+//! port, so hosts that silently drop that port look offline. An ICMP echo
+//! request reaches most live hosts regardless of which services they run.
+//!
+//! Crafting ICMP normally takes a raw socket, which is a privileged
+//! operation on every mainstream OS: run as root (or, on Linux, grant the
+//! binary `CAP_NET_RAW`). Linux and macOS also offer ICMP datagram sockets,
+//! which send echo requests and nothing else without privileges — always on
+//! macOS, and on Linux for the groups in `net.ipv4.ping_group_range`. Echo
+//! probes use a raw socket when the process may open one (only it reports
+//! the reply's TTL) and a datagram socket otherwise; [`icmp_socket`] tells
+//! which, up front. Everything else built on ICMP (traceroute, path MTU
+//! discovery) needs the raw socket, see [`icmp_available`].
+
+use socket2::{Domain, Protocol, SockAddr, Socket, Type};
+use std::io::{self, Read};
+use std::net::{IpAddr, SocketAddr};
+use std::sync::OnceLock;
+use std::sync::atomic::{AtomicU16, Ordering};
+use std::time::{Duration, Instant};
+
+use crate::scanner::options::Dscp;
+use crate::scanner::socket::{self, Origin};
+use crate::scanner::ttl::ipv4_ttl;
+use crate::scanner::usage::{self, OpenSocket};
+
+/// ICMPv4 message types used by the echo probe.
+pub(crate) const ICMPV4_ECHO_REQUEST: u8 = 8;
+pub(crate) const ICMPV4_ECHO_REPLY: u8 = 0;
+
+/// ICMPv6 message types used by the echo probe.
+pub(crate) const ICMPV6_ECHO_REQUEST: u8 = 128;
+pub(crate) const ICMPV6_ECHO_REPLY: u8 = 129;
+
+/// Payload carried by every echo request, so replies are easy to spot in a
+/// packet capture.
+const PAYLOAD: &[u8] = b"asphyxia";
+
+/// Source of per-probe identifiers, so concurrent probes (each of which sees
+/// every ICMP packet delivered to the host) only accept their own replies.
+static NEXT_IDENT: AtomicU16 = AtomicU16::new(0);
+
+/// The kind of socket echo requests are sent on.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum IcmpSocket {
+    /// A raw socket: needs privileges, and sees the reply's IP header.
+    Raw,
+    /// An ICMP datagram socket ("ping socket"): unprivileged where the
+    /// system allows it, but the kernel keeps the IP header to itself.
+    Datagram,
+}
+
+impl IcmpSocket {
+    /// The socket type to open for this kind.
+    fn socket_type(self) -> Type {
+        match self {
+            IcmpSocket::Raw => Type::RAW,
+            IcmpSocket::Datagram => Type::DGRAM,
+        }
+    }
+}
+
+/// Whether this process may open the raw sockets ICMP probes need.
+///
+/// Echo probes alone can do without (see [`icmp_socket`]); traceroute and
+/// path MTU discovery cannot.
+///
+/// # Examples
+///
+/// ```
+/// use asphyxia::scanner::icmp::icmp_available;
+///
+/// if !icmp_available() {
+///     eprintln!("ICMP probes need root or CAP_NET_RAW");
+/// }
+/// ```
+pub fn icmp_available() -> bool {
+    Socket::new(Domain::IPV4, Type::RAW, Some(Protocol::ICMPV4)).is_ok()
+}
+
+/// The socket echo probes go out on in this process: a raw socket when it
+/// may open one, else an ICMP datagram socket where the system allows those
+/// (Linux and macOS), else `None` — then only TCP probes can find hosts.
+///
+/// The answer is worked out once and kept, since it cannot change while the
+/// process runs.
+///
+/// # Examples
+///
+/// ```
+/// use asphyxia::scanner::icmp::{IcmpSocket, icmp_socket};
+///
+/// match icmp_socket() {
+///     Some(IcmpSocket::Raw) => println!("pinging with a raw socket"),
+///     Some(IcmpSocket::Datagram) => println!("pinging without privileges"),
+///     None => eprintln!("ICMP is not available, probing over TCP"),
+/// }
+/// ```
+pub fn icmp_socket() -> Option<IcmpSocket> {
+    static KIND: OnceLock<Option<IcmpSocket>> = OnceLock::new();
+    *KIND.get_or_init(|| {
+        if icmp_available() {
+            Some(IcmpSocket::Raw)
+        } else if datagram_available() {
+            Some(IcmpSocket::Datagram)
+        } else {
+            None
+        }
+    })
+}
+
+/// Whether this process may open ICMP datagram sockets.
+#[cfg(any(target_os = "linux", target_os = "android", target_os = "macos"))]
+fn datagram_available() -> bool {
+    Socket::new(Domain::IPV4, Type::DGRAM, Some(Protocol::ICMPV4)).is_ok()
+}
+
+/// ICMP datagram sockets only exist on Linux and macOS.
+#[cfg(not(any(target_os = "linux", target_os = "android", target_os = "macos")))]
+fn datagram_available() -> bool {
+    false
+}
+
+/// An answered echo request.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct EchoReply {
+    /// Time from sending the request to receiving the reply.
+    pub rtt: Duration,
+    /// TTL the reply arrived with (see [`ttl`](crate::scanner::ttl)); IPv4
+    /// raw sockets only, since the others do not deliver the IP header.
+    pub ttl: Option<u8>,
+}
+
+/// Send one ICMP echo request to `ip` and wait up to `timeout` for the reply.
+///
+/// Like [`ping`], but returns only the round-trip time.
+///
+/// # Arguments
+///
+/// * `ip` - The address to ping (IPv4 or IPv6)
+/// * `timeout` - How long to wait for the echo reply
+///
+/// # Returns
+///
+/// * `io::Result<Option<Duration>>` - The round-trip time if the host replied,
+///   `Ok(None)` if no reply arrived in time, or an error if the probe could
+///   not be sent (most often because [`icmp_socket`] found no way to send it)
+///
+/// # Examples
+///
+/// ```no_run
+/// use asphyxia::scanner::icmp::echo;
+/// use std::time::Duration;
+///
+/// match echo("192.168.1.1".parse().unwrap(), Duration::from_secs(1)) {
+///     Ok(Some(rtt)) => println!("reply in {} ms", rtt.as_millis()),
+///     Ok(None) => println!("no reply"),
+///     Err(e) => eprintln!("cannot ping: {}", e),
+/// }
+/// ```
+pub fn echo(ip: IpAddr, timeout: Duration) -> io::Result<Option<Duration>> {
+    Ok(ping(ip, timeout)?.map(|reply| reply.rtt))
+}
+
+/// Send one ICMP echo request to `ip` and wait up to `timeout` for the
+/// reply, reporting its round-trip time and TTL.
+///
+/// # Returns
+///
+/// * `io::Result<Option<EchoReply>>` - The reply if the host answered,
+///   `Ok(None)` if no reply arrived in time, or an error if the probe could
+///   not be sent
+///
+/// # Examples
+///
+/// ```no_run
+/// use asphyxia::scanner::icmp::ping;
+/// use asphyxia::scanner::ttl::estimate_hops;
+/// use std::time::Duration;
+///
+/// if let Some(reply) = ping("8.8.8.8".parse().unwrap(), Duration::from_secs(1)).unwrap()
+///     && let Some(ttl) = reply.ttl
+/// {
+///     println!("about {} hops away", estimate_hops(ttl));
+/// }
+/// ```
+pub fn ping(ip: IpAddr, timeout: Duration) -> io::Result<Option<EchoReply>> {
+    ping_marked(ip, None, Origin::default(), timeout)
+}
+
+/// Like [`ping`], with the echo request marked with `dscp` if one is given
+/// and sent from `origin`.
+pub(crate) fn ping_marked(
+    ip: IpAddr,
+    dscp: Option<Dscp>,
+    origin: Origin,
+    timeout: Duration,
+) -> io::Result<Option<EchoReply>> {
+    let Some(kind) = icmp_socket() else {
+        return Err(io::Error::new(
+            io::ErrorKind::PermissionDenied,
+            "neither raw nor datagram ICMP sockets are permitted",
+        ));
+    };
+    ping_over(kind, ip, dscp, origin, timeout)
+}
+
+/// Send one echo request to `ip` over a socket of `kind`, bound to
+/// `origin`, and wait up to `timeout` for the reply.
+fn ping_over(
+    kind: IcmpSocket,
+    ip: IpAddr,
+    dscp: Option<Dscp>,
+    origin: Origin,
+    timeout: Duration,
+) -> io::Result<Option<EchoReply>> {
+    let (domain, protocol, request_type, reply_type) = match ip {
+        IpAddr::V4(_) => (
+            Domain::IPV4,
+            Protocol::ICMPV4,
+            ICMPV4_ECHO_REQUEST,
+            ICMPV4_ECHO_REPLY,
+        ),
+        IpAddr::V6(_) => (
+            Domain::IPV6,
+            Protocol::ICMPV6,
+            ICMPV6_ECHO_REQUEST,
+            ICMPV6_ECHO_REPLY,
+        ),
+    };
+
+    let mut socket = Socket::new(domain, kind.socket_type(), Some(protocol))?;
+    let _open = OpenSocket::new();
+    socket::mark(&socket, ip, dscp);
+    origin.bind(&socket, ip, None)?;
+    // A connected socket only receives packets from its peer.
+    socket.connect(&SockAddr::from(SocketAddr::new(ip, 0)))?;
+
+    let ident = next_ident();
+    let sequence = 1;
+    let request = echo_request(request_type, ident, sequence, ip.is_ipv4());
+
+    let start = Instant::now();
+    let sent = socket.send(&request)?;
+    usage::sent(sent);
+    usage::probed(ip, sent);
+    // Linux replaces the identifier of datagram echo requests with the
+    // socket's local port, which the replies then carry.
+    let ident = match kind {
+        IcmpSocket::Datagram if cfg!(any(target_os = "linux", target_os = "android")) => socket
+            .local_addr()?
+            .as_socket()
+            .map_or(ident, |local| local.port()),
+        _ => ident,
+    };
+
+    let mut buf = [0u8; 1500];
+    loop {
+        let Some(remaining) = timeout.checked_sub(start.elapsed()) else {
+            return Ok(None);
+        };
+        if remaining.is_zero() {
+            return Ok(None);
+        }
+        socket.set_read_timeout(Some(remaining))?;
+        let len = match socket.read(&mut buf) {
+            Ok(len) => len,
+            Err(e)
+                if matches!(
+                    e.kind(),
+                    io::ErrorKind::WouldBlock | io::ErrorKind::TimedOut
+                ) =>
+            {
+                return Ok(None);
+            }
+            Err(e) => return Err(e),
+        };
+        usage::received(len);
+        // IPv4 raw sockets deliver the IP header too, as do macOS datagram
+        // sockets; IPv6 ones never do.
+        let with_header = match kind {
+            IcmpSocket::Raw => ip.is_ipv4(),
+            IcmpSocket::Datagram => ip.is_ipv4() && buf[0] >> 4 == 4,
+        };
+        let message = if with_header {
+            strip_ipv4_header(&buf[..len])
+        } else {
+            Some(&buf[..len])
+        };
+        if message.is_some_and(|m| is_reply(m, reply_type, ident, sequence)) {
+            usage::answered(ip, len);
+            return Ok(Some(EchoReply {
+                rtt: start.elapsed(),
+                ttl: if kind == IcmpSocket::Raw {
+                    ipv4_ttl(&buf[..len])
+                } else {
+                    None
+                },
+            }));
+        }
+    }
+}
+
+/// A fresh echo identifier, distinct from those of concurrent probes in this
+/// process and, most likely, from other processes' pings.
+pub(crate) fn next_ident() -> u16 {
+    NEXT_IDENT.fetch_add(1, Ordering::Relaxed) ^ std::process::id() as u16
+}
+
+/// Build an echo request message. The ICMPv4 checksum is filled in here; for
+/// ICMPv6 the kernel computes it (it covers a pseudo-header we cannot see).
+pub(crate) fn echo_request(kind: u8, ident: u16, sequence: u16, with_checksum: bool) -> Vec<u8> {
+    let mut packet = Vec::with_capacity(8 + PAYLOAD.len());
+    packet.extend_from_slice(&[kind, 0, 0, 0]);
+    packet.extend_from_slice(&ident.to_be_bytes());
+    packet.extend_from_slice(&sequence.to_be_bytes());
+    packet.extend_from_slice(PAYLOAD);
+    if with_checksum {
+        let sum = checksum(&packet);
+        packet[2..4].copy_from_slice(&sum.to_be_bytes());
+    }
+    packet
+}
+
+/// Whether `message` is the echo reply matching our identifier and sequence.
+pub(crate) fn is_reply(message: &[u8], reply_type: u8, ident: u16, sequence: u16) -> bool {
+    message.len() >= 8
+        && message[0] == reply_type
+        && u16::from_be_bytes([message[4], message[5]]) == ident
+        && u16::from_be_bytes([message[6], message[7]]) == sequence
+}
+
+/// Skip the IPv4 header in front of a received ICMP message.
+pub(crate) fn strip_ipv4_header(packet: &[u8]) -> Option<&[u8]> {
+    let header_len = usize::from(packet.first()? & 0x0f) * 4;
+    packet.get(header_len..)
+}
+
+/// The Internet checksum (RFC 1071): one's complement of the one's complement
+/// sum of 16-bit words.
+pub(crate) fn checksum(data: &[u8]) -> u16 {
+    let mut sum: u32 = data
+        .chunks(2)
+        .map(|pair| u32::from(u16::from_be_bytes([pair[0], *pair.get(1).unwrap_or(&0)])))
+        .sum();
+    while sum > 0xffff {
+        sum = (sum & 0xffff) + (sum >> 16);
+    }
+    !(sum as u16)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_checksum_of_request_verifies() {
+        // Re-summing a packet that includes its own checksum yields zero.
+        let packet = echo_request(ICMPV4_ECHO_REQUEST, 0x1234, 1, true);
+        assert_eq!(checksum(&packet), 0);
+    }
+
+    #[test]
+    fn test_is_reply_matches_ident_and_sequence() {
+        let mut reply = echo_request(ICMPV4_ECHO_REPLY, 7, 1, true);
+        assert!(is_reply(&reply, ICMPV4_ECHO_REPLY, 7, 1));
+        assert!(!is_reply(&reply, ICMPV4_ECHO_REPLY, 8, 1));
+        reply[0] = ICMPV4_ECHO_REQUEST;
+        assert!(!is_reply(&reply, ICMPV4_ECHO_REPLY, 7, 1));
+    }
+
+    #[test]
+    fn test_strip_ipv4_header_uses_ihl() {
+        let mut packet = vec![0x45];
+        packet.extend_from_slice(&[0; 19]);
+        packet.extend_from_slice(&[ICMPV4_ECHO_REPLY, 0]);
+        assert_eq!(
+            strip_ipv4_header(&packet),
+            Some(&[ICMPV4_ECHO_REPLY, 0][..])
+        );
+    }
+
+    #[test]
+    fn test_echo_localhost() {
+        if icmp_socket().is_none() {
+            println!("Skipping test_echo_localhost: raw sockets are not permitted");
+            return;
+        }
+        let rtt = echo("127.0.0.1".parse().unwrap(), Duration::from_millis(500)).unwrap();
+        assert!(rtt.is_some());
+    }
+
+    #[test]
+    fn test_ping_localhost_over_datagram_socket() {
+        if !datagram_available() {
+            println!(
+                "Skipping test_ping_localhost_over_datagram_socket: ping_group_range excludes us"
+            );
+            return;
+        }
+        let reply = ping_over(
+            IcmpSocket::Datagram,
+            "127.0.0.1".parse().unwrap(),
+            None,
+            Origin::default(),
+            Duration::from_millis(500),
+        )
+        .unwrap()
+        .unwrap();
+        assert_eq!(reply.ttl, None);
+    }
+
+    #[test]
+    fn test_ping_localhost_reports_ttl() {
+        if !icmp_available() {
+            println!("Skipping test_ping_localhost_reports_ttl: raw sockets are not permitted");
+            return;
+        }
+        let reply = ping("127.0.0.1".parse().unwrap(), Duration::from_millis(500)).unwrap();
+        // Loopback answers from this very host, zero hops away.
+        let ttl = reply.and_then(|reply| reply.ttl).unwrap();
+        assert_eq!(crate::scanner::ttl::estimate_hops(ttl), 0);
+    }
+}
@@ -0,0 +1,455 @@
+//! Listening side of a firewall reflection test.
+//!
+//! A port scan only shows what the scanner saw; it cannot tell a port the
+//! firewall dropped from one nothing listened on. Running `asphyxia listen`
+//! on the far side closes that gap: it accepts connections on the chosen
+//! ports and records where each one came from and when, so the scan's view
+//! can be checked against what actually arrived, in both directions.
+//!
+//! Connections are accepted and closed at once; nothing is read or sent.
+//!
+//! With a control port, the listener also answers a small line-based
+//! protocol, so [`validate`](crate::scanner::validate) on the scanning side
+//! can ask which ports it listens on and what arrived. Each request is one
+//! connection carrying one line, of at most 64 bytes and sent within two
+//! seconds:
+//!
+//! * `PORTS` — answered with `OK <port>,<port>,...`
+//! * `COUNT` — answered with `OK <n>`, the number of connections so far
+//! * `SINCE <n>` — answered with one `ARRIVAL <port> <source ip>` line for
+//!   every connection after the first `n`, then `END`
+//!
+//! Anything else is answered with `ERR <reason>`.
+
+use chrono::{DateTime, SubsecRound, Utc};
+use std::io::{self, Read, Write};
+use std::net::{IpAddr, Ipv4Addr, SocketAddr, TcpListener, TcpStream, UdpSocket};
+use std::thread;
+use std::time::{Duration, Instant};
+
+use crate::scanner::cancel::CancelToken;
+
+/// How long the listener sleeps when no connection is waiting.
+const POLL_INTERVAL: Duration = Duration::from_millis(20);
+
+/// How long a control client may take to send its request, and to take
+/// each part of the answer. Requests are served on the accept loop, so a
+/// client must not hold it up for longer.
+const CONTROL_TIMEOUT: Duration = Duration::from_secs(2);
+
+/// The longest control request read; valid ones are far shorter.
+const MAX_CONTROL_REQUEST: usize = 64;
+
+/// One inbound connection.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Arrival {
+    /// The local port it arrived on.
+    pub port: u16,
+    /// Who connected.
+    pub source: SocketAddr,
+    /// When it was accepted.
+    pub timestamp: DateTime<Utc>,
+}
+
+/// What [`listen`] listens on, and for how long.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ListenOptions {
+    /// The local address to listen on (`0.0.0.0` or `::` for all).
+    pub bind: IpAddr,
+    /// The TCP ports whose connections are recorded.
+    pub ports: Vec<u16>,
+    /// The TCP port to answer control requests on, if any; it must not be
+    /// one of `ports`.
+    pub control: Option<u16>,
+    /// The UDP port on which every datagram is echoed back to its sender,
+    /// if any, for [`udp_path`](crate::scanner::udp_path) measurements.
+    pub udp_echo: Option<u16>,
+    /// How long to listen, or `None` to listen until cancelled.
+    pub duration: Option<Duration>,
+}
+
+impl Default for ListenOptions {
+    fn default() -> Self {
+        ListenOptions {
+            bind: IpAddr::V4(Ipv4Addr::UNSPECIFIED),
+            ports: Vec::new(),
+            control: None,
+            udp_echo: None,
+            duration: None,
+        }
+    }
+}
+
+/// Listen as described by `options`, reporting each connection to
+/// `on_arrival` as it is accepted, answering control requests (see the
+/// [module documentation](self)) and echoing UDP datagrams if asked to.
+///
+/// Runs until `options.duration` has passed (if given) or `cancel` is
+/// cancelled, then returns every connection in arrival order.
+///
+/// # Arguments
+///
+/// * `options` - Where to listen and for how long
+/// * `cancel` - Stops the listener when cancelled
+/// * `on_arrival` - Called for every connection as it is accepted
+///
+/// # Returns
+///
+/// * `Result<Vec<Arrival>, String>` - Every connection received, or an error
+///   if a port could not be listened on (already in use, or below 1024
+///   without privileges)
+///
+/// # Examples
+///
+/// ```
+/// use asphyxia::scanner::cancel::CancelToken;
+/// use asphyxia::scanner::listen::{ListenOptions, listen};
+/// use std::time::Duration;
+///
+/// let options = ListenOptions {
+///     bind: "127.0.0.1".parse().unwrap(),
+///     ports: vec![0],
+///     duration: Some(Duration::from_millis(50)),
+///     ..ListenOptions::default()
+/// };
+/// let arrivals = listen(&options, &CancelToken::new(), |_| {}).unwrap();
+/// assert!(arrivals.is_empty());
+/// ```
+pub fn listen<F: FnMut(&Arrival)>(
+    options: &ListenOptions,
+    cancel: &CancelToken,
+    mut on_arrival: F,
+) -> Result<Vec<Arrival>, String> {
+    let ListenOptions {
+        bind,
+        ref ports,
+        control,
+        udp_echo,
+        duration,
+    } = *options;
+    if let Some(control) = control
+        && ports.contains(&control)
+    {
+        return Err(format!(
+            "The control port {} must differ from the listening ports",
+            control
+        ));
+    }
+    let control = control
+        .map(|port| {
+            bind_listener(bind, port)
+                .map_err(|e| format!("Cannot listen for control on port {}: {}", port, e))
+        })
+        .transpose()?;
+    let echo = udp_echo
+        .map(|port| {
+            bind_echo(bind, port).map_err(|e| format!("Cannot echo UDP on port {}: {}", port, e))
+        })
+        .transpose()?;
+    let listeners = ports
+        .iter()
+        .map(|&port| {
+            bind_listener(bind, port)
+                .map(|listener| (port, listener))
+                .map_err(|e| format!("Cannot listen on port {}: {}", port, e))
+        })
+        .collect::<Result<Vec<_>, String>>()?;
+
+    let start = Instant::now();
+    let running = || !cancel.is_cancelled() && duration.is_none_or(|d| start.elapsed() < d);
+    thread::scope(|scope| {
+        // Echoes get their own blocking thread: the accept loop's idle
+        // sleep would otherwise be added to every measured round trip.
+        if let Some(echo) = &echo {
+            scope.spawn(|| serve_echo(echo, &running));
+        }
+        accept_loop(
+            &listeners,
+            control.as_ref(),
+            ports,
+            &running,
+            &mut on_arrival,
+        )
+    })
+}
+
+/// Accept connections (and control requests) until `running` turns false.
+fn accept_loop<F: FnMut(&Arrival)>(
+    listeners: &[(u16, TcpListener)],
+    control: Option<&TcpListener>,
+    ports: &[u16],
+    running: &impl Fn() -> bool,
+    on_arrival: &mut F,
+) -> Result<Vec<Arrival>, String> {
+    let mut arrivals = Vec::new();
+    while running() {
+        let mut idle = true;
+        for (port, listener) in listeners {
+            // Drain every waiting connection; errors such as a peer that
+            // reset before being accepted only lose that connection.
+            while let Ok((stream, source)) = listener.accept() {
+                drop(stream);
+                idle = false;
+                let arrival = Arrival {
+                    port: *port,
+                    source,
+                    timestamp: Utc::now().trunc_subsecs(0),
+                };
+                on_arrival(&arrival);
+                arrivals.push(arrival);
+            }
+        }
+        // Control requests are served after the ports are drained, so an
+        // answer covers every connection that completed before it.
+        if let Some(control) = control {
+            while let Ok((stream, _)) = control.accept() {
+                idle = false;
+                // A client that hangs up or stalls only loses its own answer.
+                let _ = serve_control(stream, ports, &arrivals);
+            }
+        }
+        if idle {
+            thread::sleep(POLL_INTERVAL);
+        }
+    }
+    Ok(arrivals)
+}
+
+/// Send every datagram arriving on `echo` back to its sender until
+/// `running` turns false.
+fn serve_echo(echo: &UdpSocket, running: &impl Fn() -> bool) {
+    let mut buf = [0u8; 2048];
+    while running() {
+        if let Ok((len, source)) = echo.recv_from(&mut buf) {
+            // A lost echo is exactly what the measurement is counting.
+            let _ = echo.send_to(&buf[..len], source);
+        }
+    }
+}
+
+/// Read one control request from `stream` and write the answer.
+fn serve_control(stream: TcpStream, ports: &[u16], arrivals: &[Arrival]) -> io::Result<()> {
+    // Accepted sockets inherit non-blocking mode on some platforms.
+    stream.set_nonblocking(false)?;
+    stream.set_write_timeout(Some(CONTROL_TIMEOUT))?;
+    let request = read_request(&stream)?;
+    (&stream).write_all(control_reply(request.trim(), ports, arrivals).as_bytes())
+}
+
+/// The first line of a control request, read within [`CONTROL_TIMEOUT`] in
+/// all and cut at [`MAX_CONTROL_REQUEST`] bytes, so neither a client that
+/// sends a byte at a time nor one that never ends its line stalls the
+/// listener.
+fn read_request(stream: &TcpStream) -> io::Result<String> {
+    let deadline = Instant::now() + CONTROL_TIMEOUT;
+    let mut request = Vec::with_capacity(MAX_CONTROL_REQUEST);
+    let mut buf = [0u8; MAX_CONTROL_REQUEST];
+    while !request.contains(&b'\n') && request.len() < MAX_CONTROL_REQUEST {
+        let left = deadline.saturating_duration_since(Instant::now());
+        if left.is_zero() {
+            return Err(io::ErrorKind::TimedOut.into());
+        }
+        stream.set_read_timeout(Some(left))?;
+        let len = (&*stream).read(&mut buf[..MAX_CONTROL_REQUEST - request.len()])?;
+        if len == 0 {
+            break;
+        }
+        request.extend_from_slice(&buf[..len]);
+    }
+    let line = request.split(|&b| b == b'\n').next().unwrap_or_default();
+    Ok(String::from_utf8_lossy(line).into_owned())
+}
+
+/// The answer to the control `request`, newline-terminated.
+fn control_reply(request: &str, ports: &[u16], arrivals: &[Arrival]) -> String {
+    let mut words = request.split_whitespace();
+    match (words.next(), words.next()) {
+        (Some("PORTS"), None) => {
+            let ports: Vec<String> = ports.iter().map(ToString::to_string).collect();
+            format!("OK {}\n", ports.join(","))
+        }
+        (Some("COUNT"), None) => format!("OK {}\n", arrivals.len()),
+        (Some("SINCE"), Some(n)) => match n.parse::<usize>() {
+            Ok(n) => {
+                let mut reply: String = arrivals
+                    .iter()
+                    .skip(n)
+                    .map(|a| format!("ARRIVAL {} {}\n", a.port, a.source.ip()))
+                    .collect();
+                reply.push_str("END\n");
+                reply
+            }
+            Err(_) => format!("ERR invalid count: {}\n", n),
+        },
+        _ => format!("ERR unknown request: {}\n", request),
+    }
+}
+
+/// A UDP socket on `bind:port` for echoing datagrams, whose reads wake up
+/// every [`POLL_INTERVAL`] to notice when listening stops.
+fn bind_echo(bind: IpAddr, port: u16) -> io::Result<UdpSocket> {
+    let socket = UdpSocket::bind(SocketAddr::new(bind, port))?;
+    socket.set_read_timeout(Some(POLL_INTERVAL))?;
+    Ok(socket)
+}
+
+/// A non-blocking listener on `bind:port`.
+fn bind_listener(bind: IpAddr, port: u16) -> io::Result<TcpListener> {
+    let listener = TcpListener::bind(SocketAddr::new(bind, port))?;
+    listener.set_nonblocking(true)?;
+    Ok(listener)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::net::TcpStream;
+
+    fn free_port() -> u16 {
+        TcpListener::bind("127.0.0.1:0")
+            .unwrap()
+            .local_addr()
+            .unwrap()
+            .port()
+    }
+
+    fn on_loopback(ports: &[u16], duration: Option<Duration>) -> ListenOptions {
+        ListenOptions {
+            bind: "127.0.0.1".parse().unwrap(),
+            ports: ports.to_vec(),
+            duration,
+            ..ListenOptions::default()
+        }
+    }
+
+    #[test]
+    fn test_listen_records_arrivals() {
+        let port = free_port();
+        let connector = thread::spawn(move || {
+            thread::sleep(Duration::from_millis(100));
+            TcpStream::connect(("127.0.0.1", port)).unwrap();
+        });
+        let mut seen = 0;
+        let options = on_loopback(&[port], Some(Duration::from_millis(400)));
+        let arrivals = listen(&options, &CancelToken::new(), |_| seen += 1).unwrap();
+        connector.join().unwrap();
+        assert_eq!(seen, 1);
+        assert_eq!(arrivals.len(), 1);
+        assert_eq!(arrivals[0].port, port);
+        assert_eq!(
+            arrivals[0].source.ip(),
+            "127.0.0.1".parse::<IpAddr>().unwrap()
+        );
+    }
+
+    #[test]
+    fn test_listen_reports_busy_port() {
+        let taken = TcpListener::bind("127.0.0.1:0").unwrap();
+        let port = taken.local_addr().unwrap().port();
+        let options = on_loopback(&[port], Some(Duration::ZERO));
+        let err = listen(&options, &CancelToken::new(), |_| {}).unwrap_err();
+        assert!(err.contains(&format!("Cannot listen on port {}", port)));
+    }
+
+    #[test]
+    fn test_cancelled_listener_returns_at_once() {
+        let cancel = CancelToken::new();
+        cancel.cancel();
+        let arrivals = listen(&on_loopback(&[0], None), &cancel, |_| {});
+        assert_eq!(arrivals, Ok(Vec::new()));
+    }
+
+    #[test]
+    fn test_control_port_must_differ() {
+        let options = ListenOptions {
+            control: Some(9000),
+            ..on_loopback(&[9000], None)
+        };
+        let err = listen(&options, &CancelToken::new(), |_| {}).unwrap_err();
+        assert!(err.contains("control port 9000"));
+    }
+
+    #[test]
+    fn test_udp_echo_returns_datagrams() {
+        let port = UdpSocket::bind("127.0.0.1:0")
+            .unwrap()
+            .local_addr()
+            .unwrap()
+            .port();
+        let options = ListenOptions {
+            udp_echo: Some(port),
+            ..on_loopback(&[], Some(Duration::from_millis(500)))
+        };
+        let listener = thread::spawn(move || listen(&options, &CancelToken::new(), |_| {}));
+        thread::sleep(Duration::from_millis(100));
+
+        let client = UdpSocket::bind("127.0.0.1:0").unwrap();
+        client
+            .set_read_timeout(Some(Duration::from_millis(300)))
+            .unwrap();
+        client.send_to(b"ping", ("127.0.0.1", port)).unwrap();
+        let mut buf = [0u8; 16];
+        let (len, _) = client.recv_from(&mut buf).unwrap();
+        assert_eq!(&buf[..len], b"ping");
+        assert_eq!(listener.join().unwrap(), Ok(Vec::new()));
+    }
+
+    /// A connected pair of loopback sockets: (client, server side).
+    fn control_pair() -> (TcpStream, TcpStream) {
+        let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+        let client = TcpStream::connect(listener.local_addr().unwrap()).unwrap();
+        (client, listener.accept().unwrap().0)
+    }
+
+    #[test]
+    fn test_control_request_is_capped() {
+        let (mut client, server) = control_pair();
+        client.write_all(&[b'A'; 4096]).unwrap();
+        assert_eq!(
+            read_request(&server).unwrap(),
+            "A".repeat(MAX_CONTROL_REQUEST)
+        );
+
+        let (mut client, server) = control_pair();
+        client.write_all(b"COUNT\nPORTS\n").unwrap();
+        assert_eq!(read_request(&server).unwrap(), "COUNT");
+    }
+
+    #[test]
+    fn test_slow_control_client_is_cut_off() {
+        let (mut client, server) = control_pair();
+        let trickle = thread::spawn(move || {
+            // One byte at a time, each well inside the deadline.
+            for _ in 0..12 {
+                if client.write_all(b"P").is_err() {
+                    break;
+                }
+                thread::sleep(Duration::from_millis(250));
+            }
+        });
+        let started = Instant::now();
+        let err = serve_control(server, &[9000], &[]).unwrap_err();
+        assert_eq!(err.kind(), io::ErrorKind::TimedOut);
+        assert!(started.elapsed() < CONTROL_TIMEOUT + Duration::from_millis(500));
+        trickle.join().unwrap();
+    }
+
+    #[test]
+    fn test_control_replies() {
+        let arrival = |port| Arrival {
+            port,
+            source: "10.0.0.7:50000".parse().unwrap(),
+            timestamp: Utc::now(),
+        };
+        let arrivals = [arrival(9000), arrival(9001)];
+        let ports = [9000, 9001];
+        assert_eq!(control_reply("PORTS", &ports, &arrivals), "OK 9000,9001\n");
+        assert_eq!(control_reply("COUNT", &ports, &arrivals), "OK 2\n");
+        assert_eq!(
+            control_reply("SINCE 1", &ports, &arrivals),
+            "ARRIVAL 9001 10.0.0.7\nEND\n"
+        );
+        assert!(control_reply("SINCE x", &ports, &arrivals).starts_with("ERR"));
+        assert!(control_reply("HELLO", &ports, &arrivals).starts_with("ERR"));
+    }
+}
@@ -1,10 +1,80 @@
 //! Network scanning functionality
 //!
 //! This module provides functionality for scanning networks and ports.
-//! It is split into two submodules:
+//! It is split into the following submodules:
 //!
 //! * `port` - Port scanning functionality
 //! * `address` - Address scanning functionality
+//! * `annotations` - Per-host notes, attributes and probe limits from a file
+//! * `arp` - ARP probes for hosts on the local link
+//! * `async` - Tokio-based scanners for very large scans
+//! * `banner` - Banner grabbing for identifying services
+//! * `cancel` - Stopping long-running scans early
+//! * `fingerprint` - Passive TCP/IP stack fingerprints and a coarse OS guess
+//! * `http_probe` - HTTP(S) status, server and title of open web ports
+//! * `icmp` - ICMP echo probes for host discovery
+//! * `listen` - Accepting and logging inbound connections for firewall tests
+//! * `monitor` - Watching hosts and ports for state changes
+//! * `options` - Settings shared by the scan functions
+//! * `order` - Sequential or seeded shuffled order of ports and hosts
+//! * `outage` - Pausing probes while the scanning host is offline
+//! * `ping` - Repeated reachability checks with loss and round-trip statistics
+//! * `pmtu` - Path MTU discovery with don't-fragment probes
+//! * `progress` - Progress callbacks for scans
+//! * `proxy` - Port probes through chained HTTP CONNECT and SOCKS5 proxies
+//! * `rate` - Throttling for connection attempts
+//! * `replay` - Scan results reconstructed from a packet capture
+//! * `router` - Management-exposure report for a gateway
+//! * `services` - Service names for well-known ports
+//! * `socket` - Outgoing TCP connections with per-platform socket tuning
+//! * `stream` - Scan results yielded as they arrive
+//! * `sweep` - Host discovery followed by a port scan of every live host
+//! * `syn` - Half-open TCP SYN port probes
+//! * `templates` - Named port lists for recurring sweeps
+//! * `tls` - TLS certificate inspection of open ports
+//! * `top_ports` - The most commonly open TCP ports
+//! * `topology` - Network topology inferred from traceroute paths
+//! * `traceroute` - ICMP traceroute to discovered hosts
+//! * `ttl` - Hop-count estimation from the TTL of answers
+//! * `udp_path` - UDP latency, jitter and loss measurement for VoIP readiness
+//! * `usage` - Memory, socket, traffic and CPU accounting for scans
+//! * `validate` - Two-sided firewall validation against a remote listener
+//! * `version` - Service and version detection with protocol probes
 
 pub mod address;
+pub mod annotations;
+pub mod arp;
+pub mod r#async;
+pub mod banner;
+pub mod cancel;
+pub mod fingerprint;
+pub mod http_probe;
+pub mod icmp;
+pub mod listen;
+pub mod monitor;
+pub mod options;
+pub mod order;
+pub mod outage;
+pub mod ping;
+pub mod pmtu;
 pub mod port;
+pub mod progress;
+pub mod proxy;
+pub mod rate;
+pub mod replay;
+pub mod router;
+pub mod services;
+pub(crate) mod socket;
+pub mod stream;
+pub mod sweep;
+pub mod syn;
+pub mod templates;
+pub mod tls;
+pub mod top_ports;
+pub mod topology;
+pub mod traceroute;
+pub mod ttl;
+pub mod udp_path;
+pub mod usage;
+pub mod validate;
+pub mod version;
@@ -0,0 +1,247 @@
+//! Watching hosts and ports for state changes.
+//!
+//! A scan tells what the network looks like now; a monitor tells when that
+//! changes. [`monitor`] re-checks a fixed set of hosts and ports every
+//! interval with the regular scanners and hands each round's
+//! [`StateChange`]s to a callback — a host going down or coming back, a port
+//! opening or closing — which makes a lightweight availability watcher.
+//!
+//! Targets are named as in [`timeline`](crate::output::timeline):
+//! `10.0.0.5` for a host (`up` or `down`) and `10.0.0.5:22/tcp` for a port
+//! (`open` or `closed`; a filtered port counts as closed, so a firewall
+//! that alternates between dropping and rejecting does not flap). The ports
+//! of a host that is down are not probed, and keep their last known state
+//! until it comes back.
+
+use std::collections::BTreeMap;
+use std::fmt;
+use std::time::{Duration, Instant};
+
+use chrono::{DateTime, Utc};
+use serde::Serialize;
+
+use crate::scanner::address;
+use crate::scanner::cancel::CancelToken;
+use crate::scanner::options::ScanOptions;
+use crate::scanner::port;
+
+/// The state of every watched target, by target name.
+pub type Observation = BTreeMap<String, String>;
+
+/// A target whose state differs from the previous round.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+pub struct StateChange {
+    /// When the change was seen.
+    pub time: DateTime<Utc>,
+    /// The host or port, e.g. `10.0.0.5` or `10.0.0.5:22/tcp`.
+    pub target: String,
+    /// Its state in the previous round; `None` if it was never seen before.
+    pub from: Option<String>,
+    /// Its state now.
+    pub to: String,
+}
+
+impl fmt::Display for StateChange {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match &self.from {
+            Some(from) => write!(f, "{} {} -> {}", self.target, from, self.to),
+            None => write!(f, "{} {}", self.target, self.to),
+        }
+    }
+}
+
+/// Check every host in `hosts` once, and every port in `ports` on the hosts
+/// that are up.
+///
+/// A host is up when it answers the probe `options` asks for, or when any
+/// of the watched ports is open.
+///
+/// # Examples
+///
+/// ```no_run
+/// use asphyxia::scanner::monitor::observe;
+/// use asphyxia::scanner::options::ScanOptions;
+///
+/// let now = observe(&["10.0.0.5".to_string()], &[22, 443], &ScanOptions::default());
+/// println!("{:?}", now.get("10.0.0.5:22/tcp"));
+/// ```
+pub fn observe(hosts: &[String], ports: &[u16], options: &ScanOptions) -> Observation {
+    let mut observation = Observation::new();
+    for host in hosts {
+        let open: Vec<u16> = if ports.is_empty() {
+            Vec::new()
+        } else {
+            port::scan_ports_with_options(host, ports, options)
+                .iter()
+                .map(|result| result.port)
+                .collect()
+        };
+        let up = !open.is_empty()
+            || port::resolve_host(host)
+                .is_some_and(|ip| address::scan_address_with_options(ip, options).is_up());
+        observation.insert(host.clone(), if up { "up" } else { "down" }.to_string());
+        if up {
+            for &port in ports {
+                let state = if open.contains(&port) {
+                    "open"
+                } else {
+                    "closed"
+                };
+                observation.insert(format!("{}:{}/tcp", host, port), state.to_string());
+            }
+        }
+    }
+    observation
+}
+
+/// The targets of `current` whose state differs from `known`, stamped with
+/// `time`. Targets `current` does not mention (the ports of a host that is
+/// down) are not changes.
+///
+/// # Examples
+///
+/// ```
+/// use asphyxia::scanner::monitor::{Observation, changes};
+/// use chrono::Utc;
+///
+/// let before = Observation::from([("10.0.0.5:22/tcp".to_string(), "open".to_string())]);
+/// let after = Observation::from([("10.0.0.5:22/tcp".to_string(), "closed".to_string())]);
+/// let found = changes(&before, &after, Utc::now());
+/// assert_eq!(found[0].to_string(), "10.0.0.5:22/tcp open -> closed");
+/// ```
+pub fn changes(
+    known: &Observation,
+    current: &Observation,
+    time: DateTime<Utc>,
+) -> Vec<StateChange> {
+    current
+        .iter()
+        .filter(|(target, state)| known.get(*target) != Some(state))
+        .map(|(target, state)| StateChange {
+            time,
+            target: target.clone(),
+            from: known.get(target).cloned(),
+            to: state.clone(),
+        })
+        .collect()
+}
+
+/// Watch `hosts` and `ports` every `interval` until `cancel` is triggered
+/// or `rounds` checks have run.
+///
+/// # Arguments
+///
+/// * `hosts` - Hostnames or IP addresses to watch
+/// * `ports` - TCP ports to watch on each host; may be empty
+/// * `options` - Probe, timeout and retry settings for each check
+/// * `interval` - Time from the start of one check to the next
+/// * `rounds` - How many checks to run; `None` runs until `cancel`
+/// * `cancel` - Stops watching after the current check
+/// * `on_round` - Called after every check with its number (from 1), the
+///   state of every target so far, and what changed since the previous
+///   check (nothing for the first)
+///
+/// # Examples
+///
+/// ```no_run
+/// use asphyxia::scanner::cancel::CancelToken;
+/// use asphyxia::scanner::monitor::monitor;
+/// use asphyxia::scanner::options::ScanOptions;
+/// use std::time::Duration;
+///
+/// monitor(
+///     &["10.0.0.5".to_string()],
+///     &[22, 443],
+///     &ScanOptions::default(),
+///     Duration::from_secs(60),
+///     None,
+///     &CancelToken::new(),
+///     |_, _, changes| {
+///         for change in changes {
+///             println!("{}", change);
+///         }
+///     },
+/// );
+/// ```
+pub fn monitor<F>(
+    hosts: &[String],
+    ports: &[u16],
+    options: &ScanOptions,
+    interval: Duration,
+    rounds: Option<u32>,
+    cancel: &CancelToken,
+    mut on_round: F,
+) where
+    F: FnMut(u32, &Observation, &[StateChange]),
+{
+    let mut known = Observation::new();
+    let mut round = 0;
+    while !cancel.is_cancelled() && rounds.is_none_or(|rounds| round < rounds) {
+        let start = Instant::now();
+        let current = observe(hosts, ports, options);
+        round += 1;
+        let found = if round == 1 {
+            Vec::new()
+        } else {
+            changes(&known, &current, Utc::now())
+        };
+        known.extend(current);
+        on_round(round, &known, &found);
+        if rounds.is_some_and(|rounds| round >= rounds) {
+            break;
+        }
+        cancel.sleep(interval.saturating_sub(start.elapsed()));
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::net::TcpListener;
+
+    fn observation(states: &[(&str, &str)]) -> Observation {
+        states
+            .iter()
+            .map(|(target, state)| (target.to_string(), state.to_string()))
+            .collect()
+    }
+
+    #[test]
+    fn test_changes_ignore_targets_not_checked() {
+        let known = observation(&[("10.0.0.5", "up"), ("10.0.0.5:22/tcp", "open")]);
+        let current = observation(&[("10.0.0.5", "down")]);
+        let found = changes(&known, &current, Utc::now());
+        assert_eq!(found.len(), 1);
+        assert_eq!(found[0].to_string(), "10.0.0.5 up -> down");
+    }
+
+    #[test]
+    fn test_changes_report_new_targets() {
+        let found = changes(
+            &observation(&[]),
+            &observation(&[("10.0.0.9", "up")]),
+            Utc::now(),
+        );
+        assert_eq!(found[0].from, None);
+        assert_eq!(found[0].to_string(), "10.0.0.9 up");
+    }
+
+    #[test]
+    fn test_observe_marks_host_up_by_open_port() {
+        let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+        let open = listener.local_addr().unwrap().port();
+        let closed = TcpListener::bind("127.0.0.1:0")
+            .unwrap()
+            .local_addr()
+            .unwrap()
+            .port();
+        let options = ScanOptions {
+            timeout: Some(Duration::from_millis(500)),
+            ..ScanOptions::default()
+        };
+        let now = observe(&["127.0.0.1".to_string()], &[open, closed], &options);
+        assert_eq!(now["127.0.0.1"], "up");
+        assert_eq!(now[&format!("127.0.0.1:{}/tcp", open)], "open");
+        assert_eq!(now[&format!("127.0.0.1:{}/tcp", closed)], "closed");
+    }
+}
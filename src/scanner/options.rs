@@ -0,0 +1,819 @@
+//! Settings shared by the scan functions.
+//!
+//! The plain scan functions (e.g. [`scan_subnet`](crate::scanner::address::scan_subnet))
+//! take just a timeout. Their `*_with_options` counterparts accept a
+//! [`ScanOptions`] instead, so new knobs can be added without changing every
+//! function signature. Build one with struct-update syntax or, from outside
+//! the crate, with the chainable [`ScanOptions::builder`].
+//!
+//! A [`TimingTemplate`] bundles the timing knobs into one of six presets,
+//! from `paranoid` (one probe every five minutes) to `insane`. A [`Dscp`]
+//! marks probe traffic for the QoS policy of the networks it crosses.
+
+use clap::ValueEnum;
+use ipnetwork::IpNetwork;
+use std::fmt;
+use std::net::IpAddr;
+use std::str::FromStr;
+use std::sync::Arc;
+use std::time::Duration;
+
+use crate::scanner::address::{DEFAULT_MAX_HOSTS, PROBE_PORT, ProbeMethod};
+use crate::scanner::annotations::{Annotation, Annotations};
+use crate::scanner::cancel::CancelToken;
+use crate::scanner::fingerprint::FingerprintCache;
+use crate::scanner::order::{self, Permutation};
+use crate::scanner::outage::OutageWatch;
+use crate::scanner::port::ScanTechnique;
+use crate::scanner::progress::{NoProgress, ProgressHandler};
+use crate::scanner::proxy::Proxy;
+use crate::scanner::rate::{RateClasses, RateLimiter, RateOverride, SubnetRate};
+use crate::scanner::socket::Origin;
+use crate::utils::MAX_CONCURRENCY;
+use crate::utils::netif::Interface;
+
+/// A preset of timeout, parallelism, retries and delay between probes, from
+/// the stealthiest to the fastest (the nmap `-T0` to `-T5` scale).
+///
+/// [`Normal`](TimingTemplate::Normal) matches the defaults of the CLI and
+/// of [`ScanOptions::default`].
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, ValueEnum)]
+pub enum TimingTemplate {
+    /// One probe every five minutes, to slip past intrusion detection.
+    Paranoid,
+    /// One probe every 15 seconds.
+    Sneaky,
+    /// One probe at a time, 400 ms apart, sparing fragile devices and links.
+    Polite,
+    /// The default balance of speed and reliability.
+    #[default]
+    Normal,
+    /// Shorter timeouts and more parallelism, for fast, reliable networks.
+    Aggressive,
+    /// Very short timeouts and maximum parallelism; misses slow hosts.
+    Insane,
+}
+
+impl TimingTemplate {
+    /// Per-probe timeout.
+    pub fn timeout(self) -> Duration {
+        match self {
+            TimingTemplate::Paranoid | TimingTemplate::Sneaky => Duration::from_secs(10),
+            TimingTemplate::Polite => Duration::from_secs(5),
+            TimingTemplate::Normal => Duration::from_secs(2),
+            TimingTemplate::Aggressive => Duration::from_secs(1),
+            TimingTemplate::Insane => Duration::from_millis(300),
+        }
+    }
+
+    /// Maximum number of probes in flight at once.
+    pub fn concurrency(self) -> usize {
+        match self {
+            TimingTemplate::Paranoid | TimingTemplate::Sneaky | TimingTemplate::Polite => 1,
+            TimingTemplate::Normal => 256,
+            TimingTemplate::Aggressive => 512,
+            TimingTemplate::Insane => 1024,
+        }
+    }
+
+    /// Extra attempts for probes that got no answer.
+    pub fn retries(self) -> u32 {
+        match self {
+            TimingTemplate::Paranoid | TimingTemplate::Sneaky | TimingTemplate::Polite => 1,
+            TimingTemplate::Normal | TimingTemplate::Aggressive | TimingTemplate::Insane => 0,
+        }
+    }
+
+    /// Minimum time between the start of two probes, if any.
+    pub fn probe_delay(self) -> Option<Duration> {
+        match self {
+            TimingTemplate::Paranoid => Some(Duration::from_secs(300)),
+            TimingTemplate::Sneaky => Some(Duration::from_secs(15)),
+            TimingTemplate::Polite => Some(Duration::from_millis(400)),
+            TimingTemplate::Normal | TimingTemplate::Aggressive | TimingTemplate::Insane => None,
+        }
+    }
+}
+
+/// A DiffServ code point (0 to 63) carried in the IP header of probes, so
+/// QoS-aware networks can queue scan traffic accordingly.
+///
+/// Parsed from a class name — `cs0` to `cs7`, `af11` to `af43`, `ef` or
+/// `le` (lower effort, RFC 8622) — or a number. [`Dscp::CS1`], the
+/// scavenger class, asks congested links to drop probes before anything
+/// else.
+///
+/// # Examples
+///
+/// ```
+/// use asphyxia::scanner::options::Dscp;
+///
+/// assert_eq!("cs1".parse::<Dscp>(), Ok(Dscp::CS1));
+/// assert_eq!("af21".parse::<Dscp>().unwrap().value(), 18);
+/// assert_eq!(Dscp::CS1.tos(), 0x20);
+/// assert!("64".parse::<Dscp>().is_err());
+/// ```
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Dscp(u8);
+
+impl Dscp {
+    /// Class selector 1, the low-priority "scavenger" class.
+    pub const CS1: Dscp = Dscp(8);
+
+    /// The code point `value`, or `None` if it does not fit in six bits.
+    pub fn new(value: u8) -> Option<Dscp> {
+        (value < 64).then_some(Dscp(value))
+    }
+
+    /// The six-bit code point.
+    pub fn value(self) -> u8 {
+        self.0
+    }
+
+    /// The code point as an `IP_TOS` / traffic class byte (the two ECN bits
+    /// left clear).
+    pub fn tos(self) -> u32 {
+        u32::from(self.0) << 2
+    }
+}
+
+impl FromStr for Dscp {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Dscp, String> {
+        let name = s.trim().to_ascii_lowercase();
+        let value = match name.as_str() {
+            "ef" => Some(46),
+            "le" => Some(1),
+            _ => {
+                if let Some(class) = name.strip_prefix("cs") {
+                    class.parse::<u8>().ok().filter(|&c| c <= 7).map(|c| c << 3)
+                } else if let Some(class) = name.strip_prefix("af") {
+                    match class.as_bytes() {
+                        [c @ b'1'..=b'4', d @ b'1'..=b'3'] => {
+                            Some(((c - b'0') << 3) | ((d - b'0') << 1))
+                        }
+                        _ => None,
+                    }
+                } else {
+                    name.parse::<u8>().ok()
+                }
+            }
+        };
+        value.and_then(Dscp::new).ok_or_else(|| {
+            format!(
+                "Invalid DSCP '{}': expected cs0-cs7, af11-af43, ef, le or 0-63",
+                s
+            )
+        })
+    }
+}
+
+impl fmt::Display for Dscp {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}", self.0)
+    }
+}
+
+/// Settings for a scan. `ScanOptions::default()` matches the behaviour of the
+/// plain scan functions.
+///
+/// # Examples
+///
+/// ```
+/// use asphyxia::scanner::address::ProbeMethod;
+/// use asphyxia::scanner::options::ScanOptions;
+/// use std::time::Duration;
+///
+/// let options = ScanOptions {
+///     timeout: Some(Duration::from_millis(500)),
+///     probe: ProbeMethod::Icmp,
+///     rate_limit: Some(200),
+///     ..ScanOptions::default()
+/// };
+/// assert_eq!(options.probe, ProbeMethod::Icmp);
+/// ```
+#[derive(Clone)]
+pub struct ScanOptions {
+    /// Per-probe timeout (defaults to [`crate::scanner::port::CONNECT_TIMEOUT`]).
+    pub timeout: Option<Duration>,
+    /// How host availability is probed in address scans.
+    pub probe: ProbeMethod,
+    /// How port scans and sweeps probe each port. Defaults to
+    /// [`ScanTechnique::Connect`].
+    pub technique: ScanTechnique,
+    /// TCP ports probed by [`ProbeMethod::Tcp`]; a host is up if any answers.
+    /// Defaults to just [`PROBE_PORT`].
+    pub discovery_ports: Vec<u16>,
+    /// Ports never probed by port scans and sweeps, even when a broader
+    /// list or range asks for them (e.g. to honour a scanning policy).
+    pub exclude_ports: Vec<u16>,
+    /// Addresses and subnets never probed by subnet, range and sweep scans
+    /// (e.g. gateways, printers or out-of-scope hosts inside a subnet).
+    pub exclude_hosts: Vec<IpNetwork>,
+    /// Most addresses a single subnet or range scan may cover; wider ones
+    /// are refused (see [`check_host_count`](Self::check_host_count)), so a
+    /// mistyped `0.0.0.0/0` does not set out to probe the whole internet.
+    /// Defaults to [`DEFAULT_MAX_HOSTS`]; `None` lifts the limit.
+    pub max_hosts: Option<u64>,
+    /// Maximum connection attempts per second across the whole scan
+    /// (see [`RateLimiter`](crate::scanner::rate::RateLimiter)); `None` is unthrottled.
+    pub rate_limit: Option<u32>,
+    /// Minimum time between the start of two probes across the whole scan,
+    /// for pacing slower than [`rate_limit`](Self::rate_limit) can express.
+    /// When both are set, the slower pace applies. `None` adds no delay.
+    pub probe_delay: Option<Duration>,
+    /// Rates of their own for the targets of some subnets, e.g. a lab
+    /// scanned ten times as fast; the most specific subnet containing a
+    /// target wins (see [`RateClasses`]). Empty (the default) paces every
+    /// target alike.
+    pub subnet_rates: Vec<SubnetRate>,
+    /// Seed of the order ports and hosts are probed in (see
+    /// [`order`](crate::scanner::order)); the same seed gives the same order.
+    /// `None` (the default) probes them in sequence.
+    pub shuffle_seed: Option<u64>,
+    /// Extra attempts for probes that got no answer (a filtered port or a
+    /// silent host) before giving up; useful on lossy links. Defaults to 0.
+    pub retries: u32,
+    /// Look up the hostname (PTR record) of every host found up in an
+    /// address scan. Defaults to `false`.
+    pub resolve_hostnames: bool,
+    /// Count hosts found in the OS ARP table (see
+    /// [`neighbor_hosts`](crate::utils::netif::neighbor_hosts)) as up
+    /// without probing them in subnet and range scans. Defaults to `false`.
+    pub neighbor_cache: bool,
+    /// Maximum number of probes in flight at once, clamped to
+    /// `1..=`[`MAX_CONCURRENCY`]. The scan then runs on a thread pool of its
+    /// own; `None` (the default) runs it on the current rayon pool (see
+    /// [`init_scan_pool`](crate::utils::init_scan_pool)).
+    pub concurrency: Option<usize>,
+    /// Local port that port probes are sent from, for paths that only let
+    /// traffic from certain ports through (e.g. 53 or 20). `None` (the
+    /// default) lets each probe pick its own.
+    pub source_port: Option<u16>,
+    /// Local address that probes are sent from, on hosts with several. It
+    /// must belong to the scanning host. `None` (the default) lets the
+    /// routing table pick one.
+    pub source_ip: Option<IpAddr>,
+    /// Interface that probes are sent through, whatever route the target
+    /// would otherwise take (e.g. a VPN tunnel that is not the default
+    /// route). `None` (the default) follows the routing table.
+    pub interface: Option<Interface>,
+    /// IPv4 addresses that every SYN probe to an IPv4 target is also sent
+    /// from, spoofed, so the target sees the scan come from all of them
+    /// (see [`syn`](crate::scanner::syn)). Answers to decoys go to the
+    /// decoys. Empty (the default) sends none.
+    pub decoys: Vec<IpAddr>,
+    /// DiffServ code point that IPv4 probes are marked with (connect, SYN
+    /// and ICMP probes alike). `None` (the default) leaves the marking to
+    /// the OS.
+    pub dscp: Option<Dscp>,
+    /// Chain of proxies that port probes go through, first hop first (see
+    /// [`proxy`](crate::scanner::proxy)), whatever the
+    /// [`technique`](Self::technique). Empty (the default) probes directly.
+    pub proxies: Vec<Proxy>,
+    /// Collects the stack fingerprint of every host that answers a SYN probe
+    /// with a SYN-ACK, for [`fingerprint_host`](crate::scanner::fingerprint::fingerprint_host)
+    /// to reuse without probing again. `None` (the default) keeps none.
+    pub fingerprints: Option<Arc<FingerprintCache>>,
+    /// Receives progress updates while the scan runs; `None` (the default)
+    /// reports nothing.
+    pub progress: Option<Arc<dyn ProgressHandler>>,
+    /// Stops the scan early when cancelled; the scan then returns what it
+    /// found so far. `None` (the default) runs to completion.
+    pub cancel: Option<CancelToken>,
+    /// Pauses probing while the scanning host has lost its route to the
+    /// targets, probes again once it is back, and records each outage (see
+    /// [`outage`](crate::scanner::outage)). `None` (the default) takes every
+    /// failed probe as it is.
+    pub outages: Option<Arc<OutageWatch>>,
+    /// Notes on hosts whose probe limits the scan keeps to: a `skip`ped
+    /// host counts as excluded, and ports above a host's `max_port` are not
+    /// probed (see [`annotations`](crate::scanner::annotations)). `None`
+    /// (the default) limits nothing.
+    pub annotations: Option<Arc<Annotations>>,
+}
+
+impl Default for ScanOptions {
+    fn default() -> Self {
+        ScanOptions {
+            timeout: None,
+            probe: ProbeMethod::default(),
+            technique: ScanTechnique::default(),
+            discovery_ports: vec![PROBE_PORT],
+            exclude_ports: Vec::new(),
+            exclude_hosts: Vec::new(),
+            max_hosts: Some(DEFAULT_MAX_HOSTS),
+            rate_limit: None,
+            probe_delay: None,
+            subnet_rates: Vec::new(),
+            shuffle_seed: None,
+            retries: 0,
+            resolve_hostnames: false,
+            neighbor_cache: false,
+            concurrency: None,
+            source_port: None,
+            source_ip: None,
+            interface: None,
+            decoys: Vec::new(),
+            dscp: None,
+            proxies: Vec::new(),
+            fingerprints: None,
+            progress: None,
+            cancel: None,
+            outages: None,
+            annotations: None,
+        }
+    }
+}
+
+impl ScanOptions {
+    /// Options with the timeout, retries and probe delay of `template`, and
+    /// defaults for everything else.
+    ///
+    /// The template's parallelism is left to the current rayon thread pool;
+    /// set [`concurrency`](Self::concurrency) to apply it to this scan alone.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use asphyxia::scanner::options::{ScanOptions, TimingTemplate};
+    /// use std::time::Duration;
+    ///
+    /// let options = ScanOptions::from_timing_template(TimingTemplate::Polite);
+    /// assert_eq!(options.probe_delay, Some(Duration::from_millis(400)));
+    /// assert_eq!(options.retries, 1);
+    /// ```
+    pub fn from_timing_template(template: TimingTemplate) -> ScanOptions {
+        ScanOptions {
+            timeout: Some(template.timeout()),
+            retries: template.retries(),
+            probe_delay: template.probe_delay(),
+            ..ScanOptions::default()
+        }
+    }
+
+    /// Start building options from the defaults.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use asphyxia::scanner::address::ProbeMethod;
+    /// use asphyxia::scanner::options::ScanOptions;
+    /// use std::time::Duration;
+    ///
+    /// let options = ScanOptions::builder()
+    ///     .timeout(Duration::from_millis(500))
+    ///     .probe(ProbeMethod::Icmp)
+    ///     .retries(2)
+    ///     .concurrency(64)
+    ///     .rate_limit(200)
+    ///     .source_port(53)
+    ///     .build();
+    /// assert_eq!(options.source_port, Some(53));
+    /// ```
+    pub fn builder() -> ScanOptionsBuilder {
+        ScanOptionsBuilder::default()
+    }
+
+    /// Run `scan` on a pool sized by [`concurrency`](Self::concurrency), or
+    /// on the current pool if none is set or the pool cannot be created.
+    pub(crate) fn install<R: Send>(&self, scan: impl FnOnce() -> R + Send) -> R {
+        let pool = self.concurrency.and_then(|concurrency| {
+            rayon::ThreadPoolBuilder::new()
+                .num_threads(concurrency.clamp(1, MAX_CONCURRENCY))
+                .stack_size(512 * 1024)
+                .build()
+                .ok()
+        });
+        match pool {
+            Some(pool) => pool.install(scan),
+            None => scan(),
+        }
+    }
+
+    /// The progress handler to report to, or a silent one if none was set.
+    pub(crate) fn progress(&self) -> &dyn ProgressHandler {
+        self.progress.as_deref().unwrap_or(&NoProgress)
+    }
+
+    /// `ports` without the excluded ones, in the order they are probed: as
+    /// given, or shuffled by [`shuffle_seed`](Self::shuffle_seed).
+    pub(crate) fn included_ports(&self, ports: &[u16]) -> Vec<u16> {
+        let mut ports: Vec<u16> = ports
+            .iter()
+            .copied()
+            .filter(|port| !self.exclude_ports.contains(port))
+            .collect();
+        if let Some(seed) = self.shuffle_seed {
+            order::shuffle(&mut ports, seed);
+        }
+        ports
+    }
+
+    /// The order targets numbered `0..=last` are probed in (see
+    /// [`shuffle_seed`](Self::shuffle_seed)).
+    pub(crate) fn order(&self, last: u128) -> Permutation {
+        Permutation::new(last, self.shuffle_seed)
+    }
+
+    /// Whether `ip` falls inside one of the excluded addresses or subnets.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use asphyxia::scanner::options::ScanOptions;
+    ///
+    /// let options = ScanOptions {
+    ///     exclude_hosts: vec!["192.168.1.0/30".parse().unwrap()],
+    ///     ..ScanOptions::default()
+    /// };
+    /// assert!(options.is_excluded("192.168.1.2".parse().unwrap()));
+    /// assert!(!options.is_excluded("192.168.1.4".parse().unwrap()));
+    /// ```
+    pub fn is_excluded(&self, ip: IpAddr) -> bool {
+        self.exclude_hosts
+            .iter()
+            .any(|network| network.contains(ip))
+            || self
+                .annotation(ip)
+                .is_some_and(|annotation| annotation.skip)
+    }
+
+    /// The annotation of `ip`, if [`annotations`](Self::annotations) has one.
+    pub(crate) fn annotation(&self, ip: IpAddr) -> Option<&Annotation> {
+        self.annotations.as_deref()?.find(ip)
+    }
+
+    /// Whether the annotation of `ip` (if any) lets `port` be probed.
+    pub(crate) fn allows_port(&self, ip: IpAddr, port: u16) -> bool {
+        self.annotation(ip)
+            .is_none_or(|annotation| annotation.allows_port(port))
+    }
+
+    /// Check that a subnet or range of `count` addresses is within
+    /// [`max_hosts`](Self::max_hosts).
+    ///
+    /// # Arguments
+    ///
+    /// * `count` - How many addresses the subnet or range holds (see
+    ///   [`subnet_size`](crate::scanner::address::subnet_size) and
+    ///   [`range_size`](crate::scanner::address::range_size))
+    ///
+    /// # Returns
+    ///
+    /// * `Result<(), String>` - An error saying how far over the limit the
+    ///   scan is
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use asphyxia::scanner::address::subnet_size;
+    /// use asphyxia::scanner::options::ScanOptions;
+    ///
+    /// let options = ScanOptions::default();
+    /// assert!(options.check_host_count(subnet_size("10.0.0.0/16".parse().unwrap())).is_ok());
+    /// assert!(options.check_host_count(subnet_size("0.0.0.0/0".parse().unwrap())).is_err());
+    ///
+    /// let unlimited = ScanOptions { max_hosts: None, ..ScanOptions::default() };
+    /// assert!(unlimited.check_host_count(subnet_size("0.0.0.0/0".parse().unwrap())).is_ok());
+    /// ```
+    pub fn check_host_count(&self, count: u128) -> Result<(), String> {
+        match self.max_hosts {
+            Some(limit) if count > u128::from(limit) => Err(format!(
+                "Refusing to scan {} addresses (limit is {}); narrow the target, raise --max-hosts or pass --i-know-what-im-doing",
+                count, limit
+            )),
+            _ => Ok(()),
+        }
+    }
+
+    /// The limiter pacing the scan's probes, honouring both
+    /// [`rate_limit`](Self::rate_limit) and [`probe_delay`](Self::probe_delay),
+    /// or `None` if neither is set.
+    pub(crate) fn rate_limiter(&self) -> Option<RateLimiter> {
+        let by_rate = self.rate_limit.map(RateLimiter::new);
+        match (by_rate, self.probe_delay) {
+            (Some(limiter), Some(delay)) if delay <= limiter.interval() => Some(limiter),
+            (_, Some(delay)) => Some(RateLimiter::with_interval(delay)),
+            (by_rate, None) => by_rate,
+        }
+    }
+
+    /// The limiters pacing the scan's probes: [`rate_limiter`](Self::rate_limiter)
+    /// for targets at large, and one for each of the
+    /// [`subnet_rates`](Self::subnet_rates).
+    pub(crate) fn rate_classes(&self) -> RateClasses {
+        RateClasses::new(self.rate_limiter(), &self.subnet_rates)
+    }
+
+    /// Whether the scan has been asked to stop.
+    pub(crate) fn is_cancelled(&self) -> bool {
+        self.cancel.as_ref().is_some_and(CancelToken::is_cancelled)
+    }
+
+    /// Where probes leave from: [`source_ip`](Self::source_ip) and
+    /// [`interface`](Self::interface).
+    pub(crate) fn origin(&self) -> Origin {
+        Origin {
+            ip: self.source_ip,
+            interface: self.interface.as_ref().map(|interface| interface.index),
+        }
+    }
+
+    /// Whether a probe to `target` that got no answer failed only because
+    /// the scanning host was offline, and should be sent again now that it
+    /// is back (see [`OutageWatch::ride_out`]).
+    pub(crate) fn rode_out_outage(&self, target: IpAddr) -> bool {
+        self.outages
+            .as_ref()
+            .is_some_and(|watch| watch.ride_out(target, self.cancel.as_ref()))
+    }
+}
+
+impl fmt::Debug for ScanOptions {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("ScanOptions")
+            .field("timeout", &self.timeout)
+            .field("probe", &self.probe)
+            .field("technique", &self.technique)
+            .field("discovery_ports", &self.discovery_ports)
+            .field("exclude_ports", &self.exclude_ports)
+            .field("exclude_hosts", &self.exclude_hosts)
+            .field("max_hosts", &self.max_hosts)
+            .field("rate_limit", &self.rate_limit)
+            .field("probe_delay", &self.probe_delay)
+            .field("subnet_rates", &self.subnet_rates)
+            .field("shuffle_seed", &self.shuffle_seed)
+            .field("retries", &self.retries)
+            .field("resolve_hostnames", &self.resolve_hostnames)
+            .field("neighbor_cache", &self.neighbor_cache)
+            .field("concurrency", &self.concurrency)
+            .field("source_port", &self.source_port)
+            .field("source_ip", &self.source_ip)
+            .field("interface", &self.interface)
+            .field("decoys", &self.decoys)
+            .field("dscp", &self.dscp)
+            .field("proxies", &self.proxies)
+            .field("fingerprints", &self.fingerprints.is_some())
+            .field("progress", &self.progress.is_some())
+            .field("cancel", &self.cancel)
+            .field("outages", &self.outages)
+            .field("annotations", &self.annotations)
+            .finish()
+    }
+}
+
+/// Chainable construction of [`ScanOptions`], started with
+/// [`ScanOptions::builder`]. Every setter matches the field of the same name.
+#[derive(Debug, Clone, Default)]
+pub struct ScanOptionsBuilder {
+    options: ScanOptions,
+}
+
+impl ScanOptionsBuilder {
+    /// Apply the timeout, retries and probe delay of `template` (see
+    /// [`ScanOptions::from_timing_template`]), and its parallelism.
+    pub fn timing(mut self, template: TimingTemplate) -> Self {
+        self.options.timeout = Some(template.timeout());
+        self.options.retries = template.retries();
+        self.options.probe_delay = template.probe_delay();
+        self.options.concurrency = Some(template.concurrency());
+        self
+    }
+
+    /// Per-probe timeout.
+    pub fn timeout(mut self, timeout: Duration) -> Self {
+        self.options.timeout = Some(timeout);
+        self
+    }
+
+    /// How host availability is probed in address scans.
+    pub fn probe(mut self, probe: ProbeMethod) -> Self {
+        self.options.probe = probe;
+        self
+    }
+
+    /// How port scans and sweeps probe each port.
+    pub fn technique(mut self, technique: ScanTechnique) -> Self {
+        self.options.technique = technique;
+        self
+    }
+
+    /// TCP ports probed by [`ProbeMethod::Tcp`].
+    pub fn discovery_ports(mut self, ports: Vec<u16>) -> Self {
+        self.options.discovery_ports = ports;
+        self
+    }
+
+    /// Ports never probed.
+    pub fn exclude_ports(mut self, ports: Vec<u16>) -> Self {
+        self.options.exclude_ports = ports;
+        self
+    }
+
+    /// Addresses and subnets never probed.
+    pub fn exclude_hosts(mut self, hosts: Vec<IpNetwork>) -> Self {
+        self.options.exclude_hosts = hosts;
+        self
+    }
+
+    /// Most addresses a subnet or range scan may cover; `None` for no limit.
+    pub fn max_hosts(mut self, limit: Option<u64>) -> Self {
+        self.options.max_hosts = limit;
+        self
+    }
+
+    /// Maximum connection attempts per second.
+    pub fn rate_limit(mut self, per_second: u32) -> Self {
+        self.options.rate_limit = Some(per_second);
+        self
+    }
+
+    /// Minimum time between the start of two probes.
+    pub fn probe_delay(mut self, delay: Duration) -> Self {
+        self.options.probe_delay = Some(delay);
+        self
+    }
+
+    /// Rate for the targets of `network`; calling it again adds a subnet.
+    pub fn subnet_rate(mut self, network: IpNetwork, rate: RateOverride) -> Self {
+        self.options.subnet_rates.push(SubnetRate { network, rate });
+        self
+    }
+
+    /// Probe ports and hosts in the shuffled order `seed` gives.
+    pub fn shuffle_seed(mut self, seed: u64) -> Self {
+        self.options.shuffle_seed = Some(seed);
+        self
+    }
+
+    /// Extra attempts for probes that got no answer.
+    pub fn retries(mut self, retries: u32) -> Self {
+        self.options.retries = retries;
+        self
+    }
+
+    /// Look up the hostname of every host found up.
+    pub fn resolve_hostnames(mut self, resolve: bool) -> Self {
+        self.options.resolve_hostnames = resolve;
+        self
+    }
+
+    /// Count hosts in the OS ARP table as up without probing them.
+    pub fn neighbor_cache(mut self, enabled: bool) -> Self {
+        self.options.neighbor_cache = enabled;
+        self
+    }
+
+    /// Maximum number of probes in flight at once.
+    pub fn concurrency(mut self, concurrency: usize) -> Self {
+        self.options.concurrency = Some(concurrency);
+        self
+    }
+
+    /// Local port that port probes are sent from.
+    pub fn source_port(mut self, port: u16) -> Self {
+        self.options.source_port = Some(port);
+        self
+    }
+
+    /// Local address that probes are sent from.
+    pub fn source_ip(mut self, ip: IpAddr) -> Self {
+        self.options.source_ip = Some(ip);
+        self
+    }
+
+    /// Interface that probes are sent through.
+    pub fn interface(mut self, interface: Interface) -> Self {
+        self.options.interface = Some(interface);
+        self
+    }
+
+    /// Spoofed source of extra SYN probes; calling it again adds a decoy.
+    pub fn decoy(mut self, ip: IpAddr) -> Self {
+        self.options.decoys.push(ip);
+        self
+    }
+
+    /// DiffServ code point to mark probes with.
+    pub fn dscp(mut self, dscp: Dscp) -> Self {
+        self.options.dscp = Some(dscp);
+        self
+    }
+
+    /// Proxy to send port probes through; each call adds a hop after the
+    /// ones before it.
+    pub fn proxy(mut self, proxy: Proxy) -> Self {
+        self.options.proxies.push(proxy);
+        self
+    }
+
+    /// Cache of stack fingerprints to fill.
+    pub fn fingerprints(mut self, cache: Arc<FingerprintCache>) -> Self {
+        self.options.fingerprints = Some(cache);
+        self
+    }
+
+    /// Receiver of progress updates.
+    pub fn progress(mut self, progress: Arc<dyn ProgressHandler>) -> Self {
+        self.options.progress = Some(progress);
+        self
+    }
+
+    /// Token that stops the scan early.
+    pub fn cancel(mut self, cancel: CancelToken) -> Self {
+        self.options.cancel = Some(cancel);
+        self
+    }
+
+    /// Host annotations whose probe limits to keep to.
+    pub fn annotations(mut self, annotations: Arc<Annotations>) -> Self {
+        self.options.annotations = Some(annotations);
+        self
+    }
+
+    /// Watch that pauses probing through network outages.
+    pub fn outages(mut self, watch: Arc<OutageWatch>) -> Self {
+        self.options.outages = Some(watch);
+        self
+    }
+
+    /// The finished options.
+    pub fn build(self) -> ScanOptions {
+        self.options
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_dscp_parses_class_names_and_numbers() {
+        let parse = |s: &str| s.parse::<Dscp>().map(Dscp::value);
+        assert_eq!(parse("CS1"), Ok(8));
+        assert_eq!(parse("cs7"), Ok(56));
+        assert_eq!(parse("af11"), Ok(10));
+        assert_eq!(parse("af43"), Ok(38));
+        assert_eq!(parse("ef"), Ok(46));
+        assert_eq!(parse("le"), Ok(1));
+        assert_eq!(parse("63"), Ok(63));
+        assert!(parse("cs8").is_err());
+        assert!(parse("af44").is_err());
+        assert!(parse("gold").is_err());
+    }
+
+    #[test]
+    fn test_normal_template_matches_defaults() {
+        let options = ScanOptions::from_timing_template(TimingTemplate::Normal);
+        assert_eq!(options.retries, ScanOptions::default().retries);
+        assert_eq!(options.probe_delay, None);
+        assert_eq!(options.timeout, Some(crate::scanner::port::CONNECT_TIMEOUT));
+    }
+
+    #[test]
+    fn test_rate_limiter_takes_the_slower_pace() {
+        let options = ScanOptions {
+            rate_limit: Some(10),
+            probe_delay: Some(Duration::from_secs(1)),
+            ..ScanOptions::default()
+        };
+        assert_eq!(
+            options.rate_limiter().unwrap().interval(),
+            Duration::from_secs(1)
+        );
+        let options = ScanOptions {
+            rate_limit: Some(1),
+            probe_delay: Some(Duration::from_millis(10)),
+            ..ScanOptions::default()
+        };
+        assert_eq!(
+            options.rate_limiter().unwrap().interval(),
+            Duration::from_secs(1)
+        );
+        assert!(ScanOptions::default().rate_limiter().is_none());
+    }
+
+    #[test]
+    fn test_builder_sets_fields() {
+        let options = ScanOptions::builder()
+            .timing(TimingTemplate::Polite)
+            .timeout(Duration::from_millis(50))
+            .exclude_ports(vec![22])
+            .build();
+        assert_eq!(options.timeout, Some(Duration::from_millis(50)));
+        assert_eq!(options.probe_delay, Some(Duration::from_millis(400)));
+        assert_eq!(options.concurrency, Some(1));
+        assert_eq!(options.exclude_ports, vec![22]);
+        assert_eq!(options.discovery_ports, vec![PROBE_PORT]);
+    }
+
+    #[test]
+    fn test_install_sizes_the_pool() {
+        let options = ScanOptions::builder().concurrency(3).build();
+        assert_eq!(options.install(rayon::current_num_threads), 3);
+        let options = ScanOptions::builder().concurrency(0).build();
+        assert_eq!(options.install(rayon::current_num_threads), 1);
+    }
+}
@@ -0,0 +1,179 @@
+//! The order targets are probed in: sequential, or shuffled (`--randomize`).
+//!
+//! Probing ports 1, 2, 3... or addresses .1, .2, .3... one after another is
+//! the first pattern an IDS looks for, and it loads one corner of a subnet
+//! (one switch, one rack) at a time. A shuffled scan probes the same targets
+//! in an order drawn from a seed; the same seed gives the same order, so a
+//! scan can be repeated exactly.
+//!
+//! Address spans are too large to list and shuffle (a `/8` holds 16 million
+//! addresses), so a [`Permutation`] maps each step of the scan to the index
+//! of the target probed at that step instead, one at a time and without
+//! memory: a keyed Feistel network shuffles the next power of four at or
+//! above the span, and steps that land outside the span walk on until they
+//! land inside it.
+
+use std::time::{SystemTime, UNIX_EPOCH};
+
+/// Feistel rounds; four make a good shuffle of every bit.
+const ROUNDS: usize = 4;
+
+/// The order of a span of targets, numbered `0..=last`.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Permutation {
+    last: u128,
+    /// Bits in each half of a Feistel block, or `None` for sequential order.
+    half: Option<u32>,
+    keys: [u64; ROUNDS],
+}
+
+impl Permutation {
+    /// The order of the targets numbered `0..=last`: shuffled by `seed`, or
+    /// sequential without one.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use asphyxia::scanner::order::Permutation;
+    ///
+    /// let order = Permutation::new(9, Some(42));
+    /// let mut visited: Vec<u128> = (0..=9).map(|step| order.at(step)).collect();
+    /// assert_ne!(visited, (0..=9).collect::<Vec<_>>());
+    /// visited.sort();
+    /// assert_eq!(visited, (0..=9).collect::<Vec<_>>());
+    ///
+    /// assert_eq!(Permutation::new(9, None).at(3), 3);
+    /// ```
+    pub fn new(last: u128, seed: Option<u64>) -> Permutation {
+        let Some(seed) = seed else {
+            return Permutation {
+                last,
+                half: None,
+                keys: [0; ROUNDS],
+            };
+        };
+        let bits = 128 - last.leading_zeros();
+        let mut random = SplitMix64(seed);
+        Permutation {
+            last,
+            half: Some(bits.div_ceil(2)),
+            keys: [(); ROUNDS].map(|()| random.next()),
+        }
+    }
+
+    /// The target probed at `step` (in `0..=last`).
+    pub fn at(&self, step: u128) -> u128 {
+        let Some(half) = self.half else {
+            return step;
+        };
+        // Each round is a bijection on the block, so walking from a step
+        // inside the span always returns to the span.
+        let mut index = step;
+        loop {
+            index = self.feistel(index, half);
+            if index <= self.last {
+                return index;
+            }
+        }
+    }
+
+    /// One pass of the Feistel network over a block of `2 * half` bits.
+    fn feistel(&self, block: u128, half: u32) -> u128 {
+        if half == 0 {
+            return 0;
+        }
+        let mask = u64::MAX >> (64 - half);
+        let (mut left, mut right) = ((block >> half) as u64 & mask, block as u64 & mask);
+        for key in self.keys {
+            let mixed = SplitMix64(right ^ key).next() & mask;
+            (left, right) = (right, left ^ mixed);
+        }
+        (u128::from(left) << half) | u128::from(right)
+    }
+}
+
+/// Shuffle `items` in place, in the order `seed` gives.
+///
+/// # Examples
+///
+/// ```
+/// use asphyxia::scanner::order::shuffle;
+///
+/// let mut ports = vec![22, 80, 443, 3389, 8080];
+/// shuffle(&mut ports, 7);
+/// let mut again = vec![22, 80, 443, 3389, 8080];
+/// shuffle(&mut again, 7);
+/// assert_eq!(ports, again);
+/// ```
+pub fn shuffle<T>(items: &mut [T], seed: u64) {
+    let mut random = SplitMix64(seed);
+    for i in (1..items.len()).rev() {
+        let j = (random.next() % (i as u64 + 1)) as usize;
+        items.swap(i, j);
+    }
+}
+
+/// A seed for a shuffled scan nobody asked to repeat, from the clock and
+/// the process ID.
+pub fn random_seed() -> u64 {
+    let nanos = SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .map_or(0, |elapsed| elapsed.as_nanos() as u64);
+    SplitMix64(nanos ^ u64::from(std::process::id()).rotate_left(32)).next()
+}
+
+/// The SplitMix64 generator: tiny, fast and good enough to shuffle with
+/// (not for secrets).
+struct SplitMix64(u64);
+
+impl SplitMix64 {
+    fn next(&mut self) -> u64 {
+        self.0 = self.0.wrapping_add(0x9e37_79b9_7f4a_7c15);
+        let mut z = self.0;
+        z = (z ^ (z >> 30)).wrapping_mul(0xbf58_476d_1ce4_e5b9);
+        z = (z ^ (z >> 27)).wrapping_mul(0x94d0_49bb_1331_11eb);
+        z ^ (z >> 31)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_permutation_visits_every_target_once() {
+        for last in [0, 1, 2, 254, 1000, 65_535] {
+            let order = Permutation::new(last, Some(3));
+            let mut visited: Vec<u128> = (0..=last).map(|step| order.at(step)).collect();
+            visited.sort_unstable();
+            assert!(visited.iter().copied().eq(0..=last), "span 0..={}", last);
+        }
+    }
+
+    #[test]
+    fn test_permutation_depends_on_the_seed() {
+        let order = |seed| -> Vec<u128> {
+            let order = Permutation::new(255, Some(seed));
+            (0..=255).map(|step| order.at(step)).collect()
+        };
+        assert_eq!(order(1), order(1));
+        assert_ne!(order(1), order(2));
+    }
+
+    #[test]
+    fn test_permutation_handles_the_widest_spans() {
+        let order = Permutation::new(u128::MAX, Some(9));
+        assert_ne!(order.at(0), order.at(1));
+        let order = Permutation::new(u128::from(u32::MAX), Some(9));
+        assert!(order.at(12_345) <= u128::from(u32::MAX));
+    }
+
+    #[test]
+    fn test_shuffle_keeps_every_item() {
+        let mut ports: Vec<u16> = (1..=1000).collect();
+        shuffle(&mut ports, 11);
+        assert_ne!(ports, (1..=1000).collect::<Vec<_>>());
+        ports.sort_unstable();
+        assert_eq!(ports, (1..=1000).collect::<Vec<_>>());
+    }
+}
@@ -0,0 +1,277 @@
+//! Riding out network outages on the scanning host.
+//!
+//! When the scanner's own link goes away mid-scan (an interface flap, a VPN
+//! that drops), every probe sent until it comes back fails, and each failure
+//! would be reported as a filtered port or a down host. An [`OutageWatch`]
+//! tells those failures apart from real answers: after a probe that got
+//! nothing back, it asks the kernel whether it still has a route to the
+//! target. If the route was there before (the target had one on an earlier
+//! failure, or the default route the scan started with is gone too), the
+//! failure says nothing about the target, so probing pauses until the route
+//! returns (up to a window), the probe is sent again, and the outage is
+//! recorded for the report. A target that never had a route (a broadcast
+//! address, a network nothing leads to) fails at once like any other.
+//!
+//! Checking the route sends no packet: connecting a UDP socket only makes
+//! the kernel look the destination up in its routing table. A link that
+//! stays configured but carries nothing (an unplugged cable on a host with
+//! a static address) keeps its routes, and is not noticed.
+
+use crate::scanner::cancel::CancelToken;
+use chrono::{DateTime, SubsecRound, Utc};
+use serde::{Deserialize, Serialize};
+use std::collections::HashSet;
+use std::net::{IpAddr, Ipv4Addr, Ipv6Addr, UdpSocket};
+use std::sync::Mutex;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::time::{Duration, Instant};
+
+/// How long the CLI waits for the network to come back before giving up.
+pub const DEFAULT_OUTAGE_WINDOW: Duration = Duration::from_secs(60);
+
+/// How often a paused probe checks whether the route is back.
+const POLL_INTERVAL: Duration = Duration::from_millis(500);
+
+/// Addresses off every local network, so only the default route leads to
+/// them: while it is there, the scanning host is online. Documentation
+/// prefixes, and looking a route up sends nothing anyway.
+const REFERENCES: [IpAddr; 2] = [
+    IpAddr::V4(Ipv4Addr::new(192, 0, 2, 1)),
+    IpAddr::V6(Ipv6Addr::new(0x2001, 0xdb8, 0, 0, 0, 0, 0, 1)),
+];
+
+/// A span of time the scanning host had no route to its targets.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct Outage {
+    /// When a probe first found the route gone.
+    pub started: DateTime<Utc>,
+    /// When the route came back and probing resumed; `None` when it did not
+    /// come back within the window and the scan stopped.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub ended: Option<DateTime<Utc>>,
+}
+
+impl Outage {
+    /// How long the outage lasted, if it ended.
+    pub fn duration(&self) -> Option<Duration> {
+        self.ended
+            .and_then(|ended| (ended - self.started).to_std().ok())
+    }
+}
+
+/// Pauses the probes of a scan while the scanning host is offline, and
+/// keeps a record of each outage.
+///
+/// Share one watch between the probes of a scan through
+/// [`ScanOptions::outages`](crate::scanner::options::ScanOptions::outages).
+///
+/// # Examples
+///
+/// ```
+/// use asphyxia::scanner::outage::OutageWatch;
+/// use std::time::Duration;
+///
+/// let watch = OutageWatch::new(Duration::from_secs(30));
+/// // Loopback is always routable: a failed probe to it is a real answer.
+/// assert!(!watch.ride_out("127.0.0.1".parse().unwrap(), None));
+/// assert!(watch.outages().is_empty());
+/// ```
+#[derive(Debug)]
+pub struct OutageWatch {
+    window: Duration,
+    /// Targets, and [`REFERENCES`], seen with a route: losing it is an
+    /// outage.
+    routed: Mutex<HashSet<IpAddr>>,
+    /// When the outage in progress started, if there is one.
+    current: Mutex<Option<Instant>>,
+    outages: Mutex<Vec<Outage>>,
+    /// An outage outlasted the window; later failures are taken as they are.
+    expired: AtomicBool,
+}
+
+impl OutageWatch {
+    /// A watch that waits up to `window` for the network to come back.
+    ///
+    /// Create it when the scan starts: the default routes it finds then
+    /// are the ones whose loss means the host went offline.
+    pub fn new(window: Duration) -> OutageWatch {
+        let routed = REFERENCES.into_iter().filter(|&ip| has_route(ip)).collect();
+        OutageWatch {
+            window,
+            routed: Mutex::new(routed),
+            current: Mutex::new(None),
+            outages: Mutex::new(Vec::new()),
+            expired: AtomicBool::new(false),
+        }
+    }
+
+    /// Called after a probe to `target` got no answer: whether the scanning
+    /// host had lost its route to `target` and got it back, so the probe
+    /// should be sent again.
+    ///
+    /// Returns `false` straight away when the route is there, or when
+    /// `target` never had one (the failure stands). Otherwise it waits for
+    /// the route, up to the window counted from the start of the outage;
+    /// when the window runs out, `cancel` (if any) stops the scan, so it
+    /// reports what it found before the outage instead of a run of false
+    /// failures.
+    pub fn ride_out(&self, target: IpAddr, cancel: Option<&CancelToken>) -> bool {
+        if self.expired.load(Ordering::Relaxed) {
+            return false;
+        }
+        if has_route(target) {
+            self.routed().insert(target);
+            return false;
+        }
+        if !self.lost_route(target) {
+            return false;
+        }
+        let started = self.begin();
+        loop {
+            if cancel.is_some_and(CancelToken::is_cancelled) {
+                return false;
+            }
+            if has_route(target) {
+                self.end();
+                return true;
+            }
+            // The host is back online, and `target` was never reachable.
+            if !self.lost_route(target) {
+                self.end();
+                return false;
+            }
+            if started.elapsed() >= self.window {
+                self.expired.store(true, Ordering::Relaxed);
+                if let Some(cancel) = cancel {
+                    cancel.cancel();
+                }
+                return false;
+            }
+            std::thread::sleep(POLL_INTERVAL);
+        }
+    }
+
+    /// Every outage seen so far, oldest first.
+    pub fn outages(&self) -> Vec<Outage> {
+        self.outages
+            .lock()
+            .unwrap_or_else(|e| e.into_inner())
+            .clone()
+    }
+
+    /// Whether `target` had a route that is now gone: it had one itself,
+    /// or the default route for its family did and no longer does.
+    fn lost_route(&self, target: IpAddr) -> bool {
+        let routed = self.routed();
+        routed.contains(&target)
+            || REFERENCES.into_iter().any(|reference| {
+                reference.is_ipv4() == target.is_ipv4()
+                    && routed.contains(&reference)
+                    && !has_route(reference)
+            })
+    }
+
+    /// The targets and references seen with a route.
+    fn routed(&self) -> std::sync::MutexGuard<'_, HashSet<IpAddr>> {
+        self.routed.lock().unwrap_or_else(|e| e.into_inner())
+    }
+
+    /// The start of the outage in progress, recording a new one if no
+    /// other probe has yet.
+    fn begin(&self) -> Instant {
+        let mut current = self.current.lock().unwrap_or_else(|e| e.into_inner());
+        *current.get_or_insert_with(|| {
+            self.outages
+                .lock()
+                .unwrap_or_else(|e| e.into_inner())
+                .push(Outage {
+                    started: Utc::now().trunc_subsecs(0),
+                    ended: None,
+                });
+            Instant::now()
+        })
+    }
+
+    /// Close the outage in progress, unless another probe already has.
+    fn end(&self) {
+        let ended = self
+            .current
+            .lock()
+            .unwrap_or_else(|e| e.into_inner())
+            .take()
+            .is_some();
+        if ended
+            && let Some(outage) = self
+                .outages
+                .lock()
+                .unwrap_or_else(|e| e.into_inner())
+                .last_mut()
+        {
+            outage.ended = Some(Utc::now().trunc_subsecs(0));
+        }
+    }
+}
+
+/// Whether the kernel has a route to `target`, looked up by connecting a
+/// UDP socket (which sends nothing).
+pub fn has_route(target: IpAddr) -> bool {
+    let local: IpAddr = match target {
+        IpAddr::V4(_) => Ipv4Addr::UNSPECIFIED.into(),
+        IpAddr::V6(_) => Ipv6Addr::UNSPECIFIED.into(),
+    };
+    UdpSocket::bind((local, 0)).is_ok_and(|socket| socket.connect((target, 9)).is_ok())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_loopback_always_has_a_route() {
+        assert!(has_route("127.0.0.1".parse().unwrap()));
+    }
+
+    // Linux refuses to connect a UDP socket to the broadcast address
+    // without SO_BROADCAST, which reads as no route.
+    #[cfg(target_os = "linux")]
+    #[test]
+    fn test_never_routable_target_fails_at_once() {
+        let cancel = CancelToken::new();
+        let watch = OutageWatch::new(Duration::from_secs(60));
+        let started = Instant::now();
+        assert!(!watch.ride_out(IpAddr::V4(Ipv4Addr::BROADCAST), Some(&cancel)));
+        assert!(started.elapsed() < Duration::from_secs(1));
+        assert!(!cancel.is_cancelled());
+        assert!(watch.outages().is_empty());
+    }
+
+    #[cfg(target_os = "linux")]
+    #[test]
+    fn test_outage_past_the_window_stops_the_scan() {
+        let cancel = CancelToken::new();
+        let watch = OutageWatch::new(Duration::ZERO);
+        let unroutable = IpAddr::V4(Ipv4Addr::BROADCAST);
+        // As if an earlier failure had found a route to it.
+        watch.routed().insert(unroutable);
+        assert!(!watch.ride_out(unroutable, Some(&cancel)));
+        assert!(cancel.is_cancelled());
+        let outages = watch.outages();
+        assert_eq!(outages.len(), 1);
+        assert_eq!(outages[0].ended, None);
+        // Once the window has run out, failures are taken as they are.
+        assert!(!watch.ride_out(unroutable, None));
+        assert_eq!(watch.outages().len(), 1);
+    }
+
+    #[test]
+    fn test_outage_is_recorded_once_and_closed() {
+        let watch = OutageWatch::new(Duration::from_secs(1));
+        watch.begin();
+        watch.begin();
+        watch.end();
+        let outages = watch.outages();
+        assert_eq!(outages.len(), 1);
+        assert!(outages[0].ended.is_some());
+        assert!(outages[0].duration().is_some());
+    }
+}
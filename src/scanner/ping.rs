@@ -0,0 +1,219 @@
+//! Repeated reachability checks with loss and round-trip statistics.
+//!
+//! An address scan asks each host once whether it is up; [`ping_host`] keeps
+//! asking one host, at a steady pace, and counts what comes back. That is
+//! what tells a flaky link from a dead one, and, left running without a
+//! count, it doubles as a continuous reachability monitor: every answer (or
+//! silence) is handed to a callback as soon as it is known.
+//!
+//! Pings are ICMP echo requests when the process can send them (see
+//! [`icmp::icmp_socket`]) and TCP connects otherwise, where a refusal proves
+//! the host is up just as well as an accepted connection.
+
+use std::io;
+use std::net::IpAddr;
+use std::time::{Duration, Instant};
+
+use crate::scanner::address::{PROBE_PORT, tcp_probe};
+use crate::scanner::cancel::CancelToken;
+use crate::scanner::icmp;
+use crate::scanner::socket::Purpose;
+
+/// How a host is pinged.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum PingMethod {
+    /// An ICMP echo request.
+    Icmp,
+    /// A TCP connect to this port; a refusal also counts as an answer.
+    Tcp(u16),
+}
+
+impl PingMethod {
+    /// ICMP if this process can send echo requests, else TCP to `port` (or
+    /// [`PROBE_PORT`] if none is given).
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use asphyxia::scanner::icmp::icmp_socket;
+    /// use asphyxia::scanner::ping::PingMethod;
+    ///
+    /// let method = PingMethod::best_available(Some(22));
+    /// if icmp_socket().is_none() {
+    ///     assert_eq!(method, PingMethod::Tcp(22));
+    /// }
+    /// ```
+    pub fn best_available(port: Option<u16>) -> PingMethod {
+        if icmp::icmp_socket().is_some() {
+            PingMethod::Icmp
+        } else {
+            PingMethod::Tcp(port.unwrap_or(PROBE_PORT))
+        }
+    }
+}
+
+/// The outcome of one [`ping_host`] run.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct PingReport {
+    /// How many pings were sent.
+    pub sent: u32,
+    /// The round-trip time of every answered ping, in order.
+    pub rtts: Vec<Duration>,
+}
+
+impl PingReport {
+    /// How many pings were answered.
+    pub fn received(&self) -> u32 {
+        self.rtts.len() as u32
+    }
+
+    /// Share of pings that went unanswered, in percent.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use asphyxia::scanner::ping::PingReport;
+    /// use std::time::Duration;
+    ///
+    /// let report = PingReport {
+    ///     sent: 4,
+    ///     rtts: vec![Duration::from_millis(3); 3],
+    /// };
+    /// assert_eq!(report.loss_percent(), 25.0);
+    /// ```
+    pub fn loss_percent(&self) -> f64 {
+        if self.sent == 0 {
+            return 0.0;
+        }
+        f64::from(self.sent - self.received()) * 100.0 / f64::from(self.sent)
+    }
+}
+
+/// Ping `ip` once with `method`, waiting up to `timeout` for the answer.
+///
+/// # Returns
+///
+/// * `io::Result<Option<Duration>>` - The round-trip time if the host
+///   answered, `Ok(None)` if it stayed silent, or an error if an ICMP ping
+///   could not be sent
+pub fn ping_once(
+    ip: IpAddr,
+    method: PingMethod,
+    timeout: Duration,
+) -> io::Result<Option<Duration>> {
+    match method {
+        PingMethod::Icmp => icmp::echo(ip, timeout),
+        PingMethod::Tcp(port) => Ok(tcp_probe(ip, port, Purpose::Probe.profile(), timeout)),
+    }
+}
+
+/// Ping `ip` every `interval`, `count` times or until `cancel` is triggered,
+/// and report how many answered and how fast.
+///
+/// # Arguments
+///
+/// * `ip` - The host to ping
+/// * `method` - ICMP or TCP, see [`PingMethod::best_available`]
+/// * `count` - How many pings to send; `None` keeps pinging until `cancel`
+/// * `interval` - Time from one ping to the next
+/// * `timeout` - How long to wait for each answer
+/// * `cancel` - Stops pinging; the answers so far are still reported
+/// * `on_reply` - Called after every ping with its sequence number (from 1)
+///   and round-trip time, or `None` if it went unanswered
+///
+/// # Returns
+///
+/// * `io::Result<PingReport>` - The pings sent and answered, or an error if
+///   an ICMP ping could not be sent
+///
+/// # Examples
+///
+/// ```no_run
+/// use asphyxia::scanner::cancel::CancelToken;
+/// use asphyxia::scanner::ping::{PingMethod, ping_host};
+/// use std::time::Duration;
+///
+/// let report = ping_host(
+///     "192.168.1.1".parse().unwrap(),
+///     PingMethod::best_available(None),
+///     Some(4),
+///     Duration::from_secs(1),
+///     Duration::from_secs(1),
+///     &CancelToken::new(),
+///     |sequence, rtt| println!("{}: {:?}", sequence, rtt),
+/// )
+/// .unwrap();
+/// println!("{:.1}% lost", report.loss_percent());
+/// ```
+pub fn ping_host<F>(
+    ip: IpAddr,
+    method: PingMethod,
+    count: Option<u32>,
+    interval: Duration,
+    timeout: Duration,
+    cancel: &CancelToken,
+    mut on_reply: F,
+) -> io::Result<PingReport>
+where
+    F: FnMut(u32, Option<Duration>),
+{
+    let mut report = PingReport {
+        sent: 0,
+        rtts: Vec::new(),
+    };
+    while !cancel.is_cancelled() && count.is_none_or(|count| report.sent < count) {
+        let start = Instant::now();
+        let rtt = ping_once(ip, method, timeout)?;
+        report.sent += 1;
+        report.rtts.extend(rtt);
+        on_reply(report.sent, rtt);
+        if count.is_some_and(|count| report.sent >= count) {
+            break;
+        }
+        cancel.sleep(interval.saturating_sub(start.elapsed()));
+    }
+    Ok(report)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::net::TcpListener;
+
+    #[test]
+    fn test_tcp_ping_counts_every_answer() {
+        let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+        let port = listener.local_addr().unwrap().port();
+        let mut seen = Vec::new();
+        let report = ping_host(
+            "127.0.0.1".parse().unwrap(),
+            PingMethod::Tcp(port),
+            Some(3),
+            Duration::from_millis(10),
+            Duration::from_millis(500),
+            &CancelToken::new(),
+            |sequence, rtt| seen.push((sequence, rtt.is_some())),
+        )
+        .unwrap();
+        assert_eq!(report.sent, 3);
+        assert_eq!(report.received(), 3);
+        assert_eq!(report.loss_percent(), 0.0);
+        assert_eq!(seen, vec![(1, true), (2, true), (3, true)]);
+    }
+
+    #[test]
+    fn test_cancel_stops_endless_ping() {
+        let cancel = CancelToken::new();
+        let report = ping_host(
+            "127.0.0.1".parse().unwrap(),
+            PingMethod::Tcp(1),
+            None,
+            Duration::from_secs(10),
+            Duration::from_millis(500),
+            &cancel,
+            |_, _| cancel.cancel(),
+        )
+        .unwrap();
+        assert_eq!(report.sent, 1);
+    }
+}
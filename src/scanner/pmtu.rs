@@ -0,0 +1,439 @@
+//! Path MTU discovery.
+//!
+//! The path MTU is the largest IP packet that reaches a host without being
+//! fragmented. [`discover`] finds it with ICMP echo requests that carry the
+//! "don't fragment" (DF) flag: a probe that fits comes back as an echo
+//! reply, and a router that cannot forward one should answer "fragmentation
+//! needed", naming the MTU of its next hop. When the router stays silent
+//! instead (often because a firewall drops that ICMP message), large packets
+//! simply vanish: an MTU black hole, behind many "small requests work, big
+//! downloads hang" problems. Probing sizes in between pins down the MTU
+//! either way, and [`PathMtu::black_hole`] tells the two cases apart.
+//!
+//! Probes are built with their own IPv4 header to set the DF flag, which
+//! needs raw-socket privileges (root, or `CAP_NET_RAW` on Linux), like
+//! [`icmp`](crate::scanner::icmp). Only IPv4 is probed: IPv6 routers never
+//! fragment, and report oversized packets whatever the sender asks.
+
+use rayon::prelude::*;
+use socket2::{Domain, Protocol, SockAddr, Socket, Type};
+use std::io::{self, Read};
+use std::net::{IpAddr, Ipv4Addr, SocketAddr};
+use std::time::{Duration, Instant};
+
+use crate::scanner::icmp::{
+    ICMPV4_ECHO_REPLY, ICMPV4_ECHO_REQUEST, checksum, is_reply, next_ident, strip_ipv4_header,
+};
+use crate::scanner::options::ScanOptions;
+use crate::scanner::port::CONNECT_TIMEOUT;
+use crate::scanner::usage::{self, OpenSocket};
+
+/// Smallest MTU every IPv4 link must carry (RFC 791).
+pub const MIN_MTU: u16 = 68;
+
+/// Largest MTU probed: jumbo frames. A path that carries this much is
+/// reported as [`MAX_MTU`] even if it could carry more.
+pub const MAX_MTU: u16 = 9000;
+
+/// The usual Ethernet MTU, tried early since most paths have it.
+const ETHERNET_MTU: u16 = 1500;
+
+/// Most hosts probed at once; every raw ICMP socket receives a copy of every
+/// ICMP packet the host gets.
+const MAX_PARALLEL_PROBES: usize = 16;
+
+/// Length of the IPv4 header written in front of every probe.
+const IPV4_HEADER_LEN: usize = 20;
+
+/// Length of the ICMP echo header.
+const ICMP_HEADER_LEN: usize = 8;
+
+/// The "don't fragment" bit of the IPv4 flags and fragment offset field.
+const DONT_FRAGMENT: u16 = 0x4000;
+
+/// ICMPv4 "destination unreachable", and its "fragmentation needed" code.
+const ICMPV4_UNREACHABLE: u8 = 3;
+const FRAGMENTATION_NEEDED: u8 = 4;
+
+/// `EMSGSIZE` (Linux, then the BSDs and macOS): the packet exceeds our own
+/// interface's MTU.
+#[cfg(target_os = "linux")]
+const EMSGSIZE: i32 = 90;
+#[cfg(not(target_os = "linux"))]
+const EMSGSIZE: i32 = 40;
+
+/// The path MTU to one host.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct PathMtu {
+    /// The largest packet, in bytes (IP header included), that reached the
+    /// host and came back.
+    pub mtu: u16,
+    /// Whether a larger packet was dropped without a "fragmentation needed"
+    /// answer: a black hole that breaks the sender's own path MTU discovery.
+    pub black_hole: bool,
+}
+
+/// What became of one probe.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum Probe {
+    /// The echo reply came back: the packet fits.
+    Fits,
+    /// A router (or our own interface) refused it, naming the next-hop MTU
+    /// when it could.
+    TooBig(Option<u16>),
+    /// Nothing came back.
+    Lost,
+}
+
+/// Find the path MTU to `ip`.
+///
+/// Every probe waits up to `options.timeout` (default [`CONNECT_TIMEOUT`])
+/// and a lost one is retried `options.retries` times, since a single loss
+/// is not yet a black hole. Sizes between [`MIN_MTU`] and [`MAX_MTU`] are
+/// searched, jumping straight to the MTU a "fragmentation needed" answer
+/// names.
+///
+/// # Returns
+///
+/// * `io::Result<Option<PathMtu>>` - The path MTU, `Ok(None)` if the host
+///   does not answer pings (or is IPv6), or an error if the probes could not
+///   be sent (most often a lack of raw-socket privileges)
+///
+/// # Examples
+///
+/// ```no_run
+/// use asphyxia::scanner::options::ScanOptions;
+/// use asphyxia::scanner::pmtu::discover;
+///
+/// match discover("10.0.0.5".parse().unwrap(), &ScanOptions::default()).unwrap() {
+///     Some(path) if path.black_hole => println!("MTU {} (black hole above)", path.mtu),
+///     Some(path) => println!("MTU {}", path.mtu),
+///     None => println!("no answer to pings"),
+/// }
+/// ```
+pub fn discover(ip: IpAddr, options: &ScanOptions) -> io::Result<Option<PathMtu>> {
+    let IpAddr::V4(target) = ip else {
+        return Ok(None);
+    };
+    let timeout = options.timeout.unwrap_or(CONNECT_TIMEOUT);
+    let socket = Socket::new(Domain::IPV4, Type::RAW, Some(Protocol::ICMPV4))?;
+    let _open = OpenSocket::new();
+    socket.set_header_included_v4(true)?;
+    let destination = SockAddr::from(SocketAddr::new(ip, 0));
+    let ident = next_ident();
+    let mut sequence = 0u16;
+
+    let mut send = |size: u16| -> io::Result<Probe> {
+        for _ in 0..=options.retries {
+            if options.is_cancelled() {
+                break;
+            }
+            sequence = sequence.wrapping_add(1);
+            let packet = probe_packet(target, size, ident, sequence);
+            match socket.send_to(&packet, &destination) {
+                Ok(n) => {
+                    usage::sent(n);
+                    usage::probed(ip, n);
+                }
+                // Larger than our own interface's MTU.
+                Err(e) if e.raw_os_error() == Some(EMSGSIZE) => return Ok(Probe::TooBig(None)),
+                Err(e) => return Err(e),
+            }
+            let answer = await_answer(&socket, target, ident, sequence, timeout)?;
+            if answer != Probe::Lost {
+                return Ok(answer);
+            }
+        }
+        Ok(Probe::Lost)
+    };
+
+    if send(MIN_MTU)? != Probe::Fits {
+        return Ok(None);
+    }
+    search(send).map(Some)
+}
+
+/// Find the path MTU to every host in `hosts`, a bounded number at a time.
+///
+/// Progress is reported as one step per host. Hosts that do not answer
+/// pings are missing from the result. Fails only if probing cannot start at
+/// all, which in practice means the process lacks raw-socket privileges.
+///
+/// # Examples
+///
+/// ```no_run
+/// use asphyxia::scanner::options::ScanOptions;
+/// use asphyxia::scanner::pmtu::discover_all;
+///
+/// let hosts = ["10.1.0.5".parse().unwrap(), "10.2.0.9".parse().unwrap()];
+/// for (ip, path) in discover_all(&hosts, &ScanOptions::default()).unwrap() {
+///     println!("{}  {}", ip, path.mtu);
+/// }
+/// ```
+pub fn discover_all(hosts: &[IpAddr], options: &ScanOptions) -> io::Result<Vec<(IpAddr, PathMtu)>> {
+    let progress = options.progress();
+    progress.start(hosts.len() as u64, "hosts probed for MTU");
+
+    let pool = rayon::ThreadPoolBuilder::new()
+        .num_threads(MAX_PARALLEL_PROBES.min(rayon::current_num_threads()))
+        .build()
+        .map_err(io::Error::other)?;
+    let paths = pool.install(|| {
+        hosts
+            .par_iter()
+            .map(|&ip| {
+                let path = discover(ip, options).map(|path| path.map(|path| (ip, path)));
+                progress.inc(1);
+                path
+            })
+            .collect::<io::Result<Vec<_>>>()
+    });
+
+    progress.finish("Path MTU discovery completed");
+    Ok(paths?.into_iter().flatten().collect())
+}
+
+/// Search the MTU between [`MIN_MTU`] (known to fit) and [`MAX_MTU`] with
+/// `send`, which probes one packet size.
+///
+/// Starts from [`MAX_MTU`], tries [`ETHERNET_MTU`] next when that is in
+/// range, jumps to any next-hop MTU a router names, and otherwise halves the
+/// range between the largest size that fits and the smallest that does not.
+fn search<F>(mut send: F) -> io::Result<PathMtu>
+where
+    F: FnMut(u16) -> io::Result<Probe>,
+{
+    let mut fits = MIN_MTU;
+    let mut too_big = MAX_MTU + 1;
+    let mut black_hole = false;
+    let mut size = MAX_MTU;
+    loop {
+        let mut hint = None;
+        match send(size)? {
+            Probe::Fits => fits = size,
+            Probe::TooBig(next_hop) => {
+                too_big = size;
+                // Nothing larger than the next hop's MTU gets through.
+                if let Some(next_hop) = next_hop.filter(|&mtu| mtu > fits && mtu < size) {
+                    too_big = next_hop + 1;
+                    hint = Some(next_hop);
+                }
+            }
+            Probe::Lost => {
+                too_big = size;
+                black_hole = true;
+            }
+        }
+        if too_big - fits <= 1 {
+            break;
+        }
+        size = match hint {
+            Some(next_hop) => next_hop,
+            None if fits < ETHERNET_MTU && ETHERNET_MTU < too_big => ETHERNET_MTU,
+            None => fits + (too_big - fits) / 2,
+        };
+    }
+    Ok(PathMtu {
+        mtu: fits,
+        black_hole,
+    })
+}
+
+/// An echo request of `size` bytes in total, behind an IPv4 header with the
+/// DF flag set. The kernel fills in the source address, the packet ID and
+/// the header checksum.
+fn probe_packet(target: Ipv4Addr, size: u16, ident: u16, sequence: u16) -> Vec<u8> {
+    let mut packet = vec![0u8; usize::from(size)];
+    packet[0] = 0x45;
+    packet[2..4].copy_from_slice(&size.to_be_bytes());
+    packet[6..8].copy_from_slice(&DONT_FRAGMENT.to_be_bytes());
+    packet[8] = 64;
+    packet[9] = 1;
+    packet[16..20].copy_from_slice(&target.octets());
+
+    let icmp = &mut packet[IPV4_HEADER_LEN..];
+    icmp[0] = ICMPV4_ECHO_REQUEST;
+    icmp[4..6].copy_from_slice(&ident.to_be_bytes());
+    icmp[6..8].copy_from_slice(&sequence.to_be_bytes());
+    for (i, byte) in icmp[ICMP_HEADER_LEN..].iter_mut().enumerate() {
+        *byte = i as u8;
+    }
+    let sum = checksum(icmp);
+    icmp[2..4].copy_from_slice(&sum.to_be_bytes());
+    packet
+}
+
+/// Wait up to `timeout` for the answer to probe `sequence`.
+fn await_answer(
+    socket: &Socket,
+    target: Ipv4Addr,
+    ident: u16,
+    sequence: u16,
+    timeout: Duration,
+) -> io::Result<Probe> {
+    let start = Instant::now();
+    let mut buf = vec![0u8; usize::from(MAX_MTU) + 100];
+    loop {
+        let remaining = timeout.saturating_sub(start.elapsed());
+        if remaining.is_zero() {
+            return Ok(Probe::Lost);
+        }
+        socket.set_read_timeout(Some(remaining))?;
+        let len = match (&*socket).read(&mut buf) {
+            Ok(len) => len,
+            Err(e)
+                if matches!(
+                    e.kind(),
+                    io::ErrorKind::WouldBlock | io::ErrorKind::TimedOut
+                ) =>
+            {
+                return Ok(Probe::Lost);
+            }
+            Err(e) => return Err(e),
+        };
+        usage::received(len);
+        let from_target = buf.get(12..16) == Some(&target.octets()[..]);
+        if let Some(message) = strip_ipv4_header(&buf[..len])
+            && let Some(answer) = classify(message, from_target, ident, sequence)
+        {
+            if from_target {
+                usage::answered(IpAddr::V4(target), len);
+            }
+            return Ok(answer);
+        }
+    }
+}
+
+/// Classify an ICMP `message` as the answer to the probe carrying `ident`
+/// and `sequence`, or `None` if it answers something else. Echo replies
+/// only count when they come `from_target`.
+fn classify(message: &[u8], from_target: bool, ident: u16, sequence: u16) -> Option<Probe> {
+    match *message.first()? {
+        ICMPV4_ECHO_REPLY => (from_target && is_reply(message, ICMPV4_ECHO_REPLY, ident, sequence))
+            .then_some(Probe::Fits),
+        ICMPV4_UNREACHABLE if message.get(1) == Some(&FRAGMENTATION_NEEDED) => {
+            let quoted = strip_ipv4_header(message.get(ICMP_HEADER_LEN..)?)?;
+            if !is_reply(quoted, ICMPV4_ECHO_REQUEST, ident, sequence) {
+                return None;
+            }
+            // Routers predating RFC 1191 leave the next-hop MTU at zero.
+            let next_hop = u16::from_be_bytes([message[6], message[7]]);
+            Some(Probe::TooBig((next_hop >= MIN_MTU).then_some(next_hop)))
+        }
+        _ => None,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::scanner::icmp::icmp_available;
+
+    /// Probe a simulated path whose MTU is `mtu`: larger packets are
+    /// refused with the MTU named if `reports`, else silently dropped.
+    fn simulate(mtu: u16, reports: bool) -> (PathMtu, usize) {
+        let mut probes = 0;
+        let path = search(|size| {
+            probes += 1;
+            Ok(match size {
+                size if size <= mtu => Probe::Fits,
+                _ if reports => Probe::TooBig(Some(mtu)),
+                _ => Probe::Lost,
+            })
+        })
+        .unwrap();
+        (path, probes)
+    }
+
+    #[test]
+    fn test_search_follows_fragmentation_needed() {
+        let (path, probes) = simulate(1400, true);
+        assert_eq!(
+            path,
+            PathMtu {
+                mtu: 1400,
+                black_hole: false
+            }
+        );
+        // 9000 is refused naming 1400, which fits.
+        assert_eq!(probes, 2);
+    }
+
+    #[test]
+    fn test_search_finds_black_hole() {
+        let (path, _) = simulate(1436, false);
+        assert_eq!(
+            path,
+            PathMtu {
+                mtu: 1436,
+                black_hole: true
+            }
+        );
+    }
+
+    #[test]
+    fn test_search_caps_at_max_mtu() {
+        assert_eq!(
+            simulate(65535, false).0,
+            PathMtu {
+                mtu: MAX_MTU,
+                black_hole: false
+            }
+        );
+    }
+
+    #[test]
+    fn test_probe_packet_sets_dont_fragment() {
+        let packet = probe_packet(Ipv4Addr::new(10, 0, 0, 5), 1500, 7, 3);
+        assert_eq!(packet.len(), 1500);
+        assert_eq!(u16::from_be_bytes([packet[2], packet[3]]), 1500);
+        assert_eq!(packet[6] & 0x40, 0x40);
+        let icmp = strip_ipv4_header(&packet).unwrap();
+        assert_eq!(checksum(icmp), 0);
+        assert!(is_reply(icmp, ICMPV4_ECHO_REQUEST, 7, 3));
+    }
+
+    #[test]
+    fn test_classify_fragmentation_needed() {
+        let probe = probe_packet(Ipv4Addr::new(10, 0, 0, 5), 1500, 7, 3);
+        let mut message = vec![
+            ICMPV4_UNREACHABLE,
+            FRAGMENTATION_NEEDED,
+            0,
+            0,
+            0,
+            0,
+            0x05,
+            0x78,
+        ];
+        message.extend_from_slice(&probe[..IPV4_HEADER_LEN + ICMP_HEADER_LEN]);
+        assert_eq!(
+            classify(&message, false, 7, 3),
+            Some(Probe::TooBig(Some(1400)))
+        );
+        assert_eq!(classify(&message, false, 7, 4), None);
+        message[1] = 1;
+        assert_eq!(classify(&message, false, 7, 3), None);
+    }
+
+    #[test]
+    fn test_discover_localhost() {
+        if !icmp_available() {
+            println!("Skipping test_discover_localhost: raw sockets are not permitted");
+            return;
+        }
+        let options = ScanOptions {
+            timeout: Some(Duration::from_millis(500)),
+            ..ScanOptions::default()
+        };
+        let path = discover("127.0.0.1".parse().unwrap(), &options).unwrap();
+        // Loopback carries far more than jumbo frames.
+        assert_eq!(
+            path,
+            Some(PathMtu {
+                mtu: MAX_MTU,
+                black_hole: false
+            })
+        );
+    }
+}
@@ -1,17 +1,273 @@
-use std::net::{IpAddr, Ipv6Addr, TcpStream, ToSocketAddrs};
+use std::fmt;
+use std::io::ErrorKind;
+use std::net::{IpAddr, Ipv6Addr, ToSocketAddrs};
 use std::time::{Duration, Instant};
 
+use clap::ValueEnum;
+use rayon::prelude::*;
+
+use crate::scanner::fingerprint::StackFingerprint;
+use crate::scanner::options::ScanOptions;
+use crate::scanner::proxy::{HopFailure, ProxyAnswer};
+use crate::scanner::rate::RateLimiter;
+use crate::scanner::socket::{self, Purpose, SocketProfile};
+use crate::scanner::stream::{self, Found, ScanStream};
+use crate::scanner::{proxy, syn};
+
 /// Default timeout for a single TCP connection attempt.
 pub const CONNECT_TIMEOUT: Duration = Duration::from_secs(2);
 
-/// An open port together with how long the TCP handshake took.
+/// How a port scan probes each TCP port.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, ValueEnum)]
+pub enum ScanTechnique {
+    /// Unprivileged full TCP connect; every open port costs a connection.
+    #[default]
+    Connect,
+    /// Half-open SYN probe; needs raw-socket privileges (see
+    /// [`syn::syn_available`]).
+    Syn,
+}
+
+/// The state of a TCP port as seen by a connect probe.
+///
+/// "Closed" and "filtered" mean very different things: a closed port proves
+/// the host is reachable and simply has nothing listening, while a filtered
+/// port means something (usually a firewall) swallowed the probe.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum PortState {
+    /// The TCP handshake completed.
+    Open,
+    /// The host refused or reset the connection (RST).
+    Closed,
+    /// No answer before the timeout, or the host/network was unreachable.
+    Filtered,
+}
+
+impl PortState {
+    /// Lowercase name used in machine-readable output.
+    pub fn as_str(self) -> &'static str {
+        match self {
+            PortState::Open => "open",
+            PortState::Closed => "closed",
+            PortState::Filtered => "filtered",
+        }
+    }
+}
+
+/// What a probe actually saw, from which its [`PortState`] follows.
 ///
-/// The latency is the wall-clock time spent in [`TcpStream::connect_timeout`]
-/// for the successful connection — a rough proxy for how close the target is.
+/// Kept with every result so a disputed state can be checked against the
+/// raw observation without rerunning the scan under a packet capture.
 #[derive(Debug, Clone, Copy, PartialEq, Eq)]
-pub struct PortHit {
+pub enum Evidence {
+    /// A connect probe completed the TCP handshake.
+    Handshake,
+    /// A SYN probe got a SYN-ACK back.
+    SynAck,
+    /// The host answered with a RST (a refused connection).
+    Reset,
+    /// An ICMP error reported the host unreachable, e.g. a firewall's
+    /// "administratively prohibited".
+    HostUnreachable,
+    /// An ICMP error reported the network unreachable.
+    NetworkUnreachable,
+    /// Nothing came back before the timeout.
+    Timeout,
+    /// A proxy connected to the port (HTTP 2xx, SOCKS5 success).
+    ProxyConnected,
+    /// A proxy could not connect, with this answer (HTTP 502 or 503, SOCKS5
+    /// "connection refused"): the target refused it.
+    ProxyRefused(ProxyAnswer),
+    /// A proxy turned the request down with this answer (e.g. HTTP 403 for
+    /// a port its policy forbids); the port itself stays unknown.
+    ProxyDenied(ProxyAnswer),
+    /// A proxy gave up waiting for the target (HTTP 504, SOCKS5 "TTL
+    /// expired").
+    ProxyTimeout(ProxyAnswer),
+    /// A hop of a proxy chain (counted from 1) failed before the target
+    /// was asked about; the port itself stays unknown.
+    ProxyHopFailed {
+        /// The hop that failed.
+        hop: usize,
+        /// How it failed.
+        failure: HopFailure,
+    },
+    /// The probe failed locally with this error.
+    Error(ErrorKind),
+}
+
+impl Evidence {
+    /// Short name used in machine-readable output (nmap's reason names
+    /// where one exists).
+    pub fn as_str(self) -> &'static str {
+        match self {
+            Evidence::Handshake => "handshake",
+            Evidence::SynAck => "syn-ack",
+            Evidence::Reset => "reset",
+            Evidence::HostUnreachable => "host-unreach",
+            Evidence::NetworkUnreachable => "net-unreach",
+            Evidence::Timeout => "no-response",
+            Evidence::ProxyConnected => "proxy-connected",
+            Evidence::ProxyRefused(_) => "proxy-refused",
+            Evidence::ProxyDenied(_) => "proxy-denied",
+            Evidence::ProxyTimeout(_) => "proxy-timeout",
+            Evidence::ProxyHopFailed { .. } => "proxy-hop-failed",
+            Evidence::Error(_) => "error",
+        }
+    }
+
+    /// The port state this evidence leads to.
+    pub fn state(self) -> PortState {
+        match self {
+            Evidence::Handshake | Evidence::SynAck | Evidence::ProxyConnected => PortState::Open,
+            Evidence::Reset | Evidence::ProxyRefused(_) => PortState::Closed,
+            Evidence::HostUnreachable
+            | Evidence::NetworkUnreachable
+            | Evidence::Timeout
+            | Evidence::ProxyDenied(_)
+            | Evidence::ProxyTimeout(_)
+            | Evidence::ProxyHopFailed { .. }
+            | Evidence::Error(_) => PortState::Filtered,
+        }
+    }
+
+    /// The evidence left by a connection attempt.
+    pub(crate) fn from_connect<T>(result: &std::io::Result<T>) -> Evidence {
+        match result {
+            Ok(_) => Evidence::Handshake,
+            Err(e) => match e.kind() {
+                ErrorKind::ConnectionRefused | ErrorKind::ConnectionReset => Evidence::Reset,
+                ErrorKind::HostUnreachable => Evidence::HostUnreachable,
+                ErrorKind::NetworkUnreachable => Evidence::NetworkUnreachable,
+                ErrorKind::TimedOut | ErrorKind::WouldBlock => Evidence::Timeout,
+                kind => Evidence::Error(kind),
+            },
+        }
+    }
+
+    /// The evidence left by a proxy's answer to a request to connect to
+    /// the port.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use asphyxia::scanner::port::{Evidence, PortState};
+    /// use asphyxia::scanner::proxy::ProxyAnswer;
+    ///
+    /// let state = |answer| Evidence::from_proxy_answer(answer).state();
+    /// assert_eq!(state(ProxyAnswer::Http(200)), PortState::Open);
+    /// assert_eq!(state(ProxyAnswer::Http(502)), PortState::Closed);
+    /// assert_eq!(state(ProxyAnswer::Socks(5)), PortState::Closed);
+    /// assert_eq!(state(ProxyAnswer::Http(403)), PortState::Filtered);
+    /// ```
+    pub fn from_proxy_answer(answer: ProxyAnswer) -> Evidence {
+        match answer {
+            _ if answer.connected() => Evidence::ProxyConnected,
+            ProxyAnswer::Http(502 | 503) | ProxyAnswer::Socks(5) => Evidence::ProxyRefused(answer),
+            ProxyAnswer::Http(504) | ProxyAnswer::Socks(6) => Evidence::ProxyTimeout(answer),
+            _ => Evidence::ProxyDenied(answer),
+        }
+    }
+}
+
+impl fmt::Display for Evidence {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Evidence::Handshake => write!(f, "handshake completed"),
+            Evidence::SynAck => write!(f, "SYN-ACK received"),
+            Evidence::Reset => write!(f, "RST received"),
+            Evidence::HostUnreachable => write!(f, "ICMP host unreachable"),
+            Evidence::NetworkUnreachable => write!(f, "ICMP network unreachable"),
+            Evidence::Timeout => write!(f, "no answer"),
+            Evidence::ProxyConnected => write!(f, "proxy connected"),
+            Evidence::ProxyRefused(answer) => write!(f, "proxy could not connect ({})", answer),
+            Evidence::ProxyDenied(answer) => {
+                write!(f, "proxy turned the connection down ({})", answer)
+            }
+            Evidence::ProxyTimeout(answer) => write!(f, "proxy timed out ({})", answer),
+            Evidence::ProxyHopFailed { hop, failure } => {
+                write!(f, "proxy hop {} failed: {}", hop, failure)
+            }
+            Evidence::Error(kind) => write!(f, "error: {}", kind),
+        }
+    }
+}
+
+/// The outcome of probing a single TCP port.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct PortScanResult {
+    /// The probed port.
     pub port: u16,
+    /// What the probe found.
+    pub state: PortState,
+    /// What the last attempt saw, which decided [`state`](Self::state).
+    pub evidence: Evidence,
+    /// How many probes were sent, retries included.
+    pub attempts: u32,
+    /// Wall-clock time spent connecting — for an open
+    /// port, the handshake time, a rough proxy for how close the target is.
     pub latency: Duration,
+    /// TTL the answer arrived with (see [`ttl`](crate::scanner::ttl)), when
+    /// the probe saw its IP header: SYN probes of IPv4 hosts. `None` for
+    /// connect probes, whose answers the kernel consumes.
+    pub ttl: Option<u8>,
+    /// The host's stack fingerprint, read from the SYN-ACK of a SYN probe
+    /// of an IPv4 host; `None` otherwise.
+    pub fingerprint: Option<StackFingerprint>,
+}
+
+impl PortScanResult {
+    /// Whether the port accepted the connection.
+    pub fn is_open(&self) -> bool {
+        self.state == PortState::Open
+    }
+
+    /// How the state was reached, e.g. `no answer after 3 attempts`.
+    pub fn explain(&self) -> String {
+        if self.attempts > 1 {
+            format!("{} after {} attempts", self.evidence, self.attempts)
+        } else {
+            self.evidence.to_string()
+        }
+    }
+}
+
+/// How many ports of a scan ended up open, closed and filtered.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct StateCounts {
+    /// Ports that accepted the connection.
+    pub open: usize,
+    /// Ports the host refused or reset.
+    pub closed: usize,
+    /// Ports whose probes got no answer, or an unreachable.
+    pub filtered: usize,
+}
+
+impl StateCounts {
+    /// Count the states of `results`.
+    ///
+    /// # Examples
+    ///
+    /// ```no_run
+    /// use asphyxia::scanner::options::ScanOptions;
+    /// use asphyxia::scanner::port::{StateCounts, probe_ports_with_options};
+    ///
+    /// let ports: Vec<u16> = (1..=1024).collect();
+    /// let results = probe_ports_with_options("192.168.1.1", &ports, &ScanOptions::default());
+    /// let counts = StateCounts::of(&results);
+    /// println!("{} open, {} closed, {} filtered", counts.open, counts.closed, counts.filtered);
+    /// ```
+    pub fn of<'a>(results: impl IntoIterator<Item = &'a PortScanResult>) -> StateCounts {
+        let mut counts = StateCounts::default();
+        for result in results {
+            match result.state {
+                PortState::Open => counts.open += 1,
+                PortState::Closed => counts.closed += 1,
+                PortState::Filtered => counts.filtered += 1,
+            }
+        }
+        counts
+    }
 }
 
 /// Format a `host:port` authority, wrapping bare IPv6 literals in brackets so
@@ -89,46 +345,564 @@ pub fn is_resolvable(host: &str) -> bool {
 ///
 /// # Returns
 ///
-/// * `Option<PortHit>` - The open port and its connect latency, or `None` if closed
+/// * `Result<PortScanResult, String>` - The port's state and the probe latency,
+///   or an error message if the host could not be resolved
 ///
 /// # Examples
 ///
 /// ```no_run
 /// use asphyxia::scanner::port::scan_port;
 ///
-/// if let Some(hit) = scan_port("example.com".to_string(), 80, None) {
-///     println!("Port {} is open ({} ms)", hit.port, hit.latency.as_millis());
+/// let result = scan_port("example.com".to_string(), 80, None).unwrap();
+/// if result.is_open() {
+///     println!("Port {} is open ({} ms)", result.port, result.latency.as_millis());
+/// } else {
+///     println!("Port {} is {}", result.port, result.state.as_str());
 /// }
 /// ```
-pub fn scan_port(host: String, port: u16, timeout: Option<Duration>) -> Option<PortHit> {
-    let socket_addr = host_port(&host, port).to_socket_addrs().ok()?.next()?;
+pub fn scan_port(
+    host: String,
+    port: u16,
+    timeout: Option<Duration>,
+) -> Result<PortScanResult, String> {
+    connect_probe(&host, port, timeout, None, Purpose::Probe.profile())
+}
+
+/// Probe `port` on `host` with a TCP connection over a socket tuned as
+/// `profile` says, from `source_port` if one is given. Fails if the host does
+/// not resolve or the socket cannot be bound.
+fn connect_probe(
+    host: &str,
+    port: u16,
+    timeout: Option<Duration>,
+    source_port: Option<u16>,
+    profile: SocketProfile,
+) -> Result<PortScanResult, String> {
+    let socket_addr = host_port(host, port)
+        .to_socket_addrs()
+        .ok()
+        .and_then(|mut addrs| addrs.next())
+        .ok_or_else(|| format!("Could not resolve host: {}", host))?;
+    let timeout = timeout.unwrap_or(CONNECT_TIMEOUT);
+    let socket = if source_port.is_some() || profile.origin.is_bound() {
+        Some(
+            socket::open(socket_addr, profile, source_port)
+                .map_err(|e| format!("Cannot bind probe socket for {}: {}", socket_addr, e))?,
+        )
+    } else {
+        None
+    };
     let start = Instant::now();
-    match TcpStream::connect_timeout(&socket_addr, timeout.unwrap_or(CONNECT_TIMEOUT)) {
-        Ok(_) => Some(PortHit {
-            port,
-            latency: start.elapsed(),
-        }),
-        Err(_) => None,
+    let result = match socket {
+        Some(bound) => socket::connect_socket(bound, socket_addr, timeout),
+        None => socket::connect(socket_addr, profile, timeout),
+    };
+    let evidence = Evidence::from_connect(&result);
+    Ok(PortScanResult {
+        port,
+        state: evidence.state(),
+        evidence,
+        attempts: 1,
+        latency: start.elapsed(),
+        ttl: None,
+        fingerprint: None,
+    })
+}
+
+/// Scan a specific port on a host with an explicit connection timeout
+///
+/// Equivalent to [`scan_port`] with `Some(timeout)`. On a fast LAN a short
+/// timeout (e.g. 100 ms) keeps closed or filtered ports from each costing the
+/// full [`CONNECT_TIMEOUT`].
+///
+/// # Arguments
+///
+/// * `host` - The hostname or IP address to scan (IPv4 or IPv6)
+/// * `port` - The port number to scan
+/// * `timeout` - How long to wait for the TCP handshake
+///
+/// # Returns
+///
+/// * `Result<PortScanResult, String>` - The port's state and the probe latency,
+///   or an error message if the host could not be resolved
+///
+/// # Examples
+///
+/// ```no_run
+/// use asphyxia::scanner::port::scan_port_with_timeout;
+/// use std::time::Duration;
+///
+/// let result =
+///     scan_port_with_timeout("192.168.1.10".to_string(), 22, Duration::from_millis(100)).unwrap();
+/// println!("ssh is {}", result.state.as_str());
+/// ```
+pub fn scan_port_with_timeout(
+    host: String,
+    port: u16,
+    timeout: Duration,
+) -> Result<PortScanResult, String> {
+    scan_port(host, port, Some(timeout))
+}
+
+/// Scan a specific port on a host using the given [`ScanOptions`].
+///
+/// Behaves like [`scan_port`] with `options.timeout` (or a SYN probe when
+/// `options.technique` is [`ScanTechnique::Syn`]), except that a port that
+/// gave no answer ([`PortState::Filtered`]) is re-probed up to
+/// `options.retries` more times before it is reported as filtered, which
+/// avoids false negatives on lossy links.
+///
+/// # Examples
+///
+/// ```no_run
+/// use asphyxia::scanner::options::ScanOptions;
+/// use asphyxia::scanner::port::scan_port_with_options;
+///
+/// let options = ScanOptions {
+///     retries: 2,
+///     ..ScanOptions::default()
+/// };
+/// let result = scan_port_with_options("192.168.1.10".to_string(), 22, &options).unwrap();
+/// println!("ssh is {}", result.state.as_str());
+/// ```
+pub fn scan_port_with_options(
+    host: String,
+    port: u16,
+    options: &ScanOptions,
+) -> Result<PortScanResult, String> {
+    probe_port(&host, port, options, None)
+}
+
+/// Scan `ports` on `host` in parallel using the given [`ScanOptions`] and
+/// return the open ones, sorted by port.
+///
+/// Ports in `options.exclude_ports` are skipped. Progress is reported to
+/// `options.progress`, `options.cancel` (if set)
+/// stops the scan early with the ports found so far, and `options.rate_limit` and
+/// `options.probe_delay` (if set) pace connection attempts across all ports, retries
+/// included.
+///
+/// # Examples
+///
+/// ```no_run
+/// use asphyxia::scanner::options::ScanOptions;
+/// use asphyxia::scanner::port::scan_ports_with_options;
+///
+/// let open = scan_ports_with_options("192.168.1.10", &[22, 80, 443], &ScanOptions::default());
+/// println!("{} open ports", open.len());
+/// ```
+pub fn scan_ports_with_options(
+    host: &str,
+    ports: &[u16],
+    options: &ScanOptions,
+) -> Vec<PortScanResult> {
+    collect_sorted(|found| scan_ports_each(host, ports, options, PortScanResult::is_open, found))
+}
+
+/// Like [`scan_ports_with_options`], but return every probed port, closed
+/// and filtered ones included, each with the [`Evidence`] behind its state.
+///
+/// # Examples
+///
+/// ```no_run
+/// use asphyxia::scanner::options::ScanOptions;
+/// use asphyxia::scanner::port::probe_ports_with_options;
+///
+/// for result in probe_ports_with_options("192.168.1.10", &[22, 80], &ScanOptions::default()) {
+///     println!("{}: {} ({})", result.port, result.state.as_str(), result.explain());
+/// }
+/// ```
+pub fn probe_ports_with_options(
+    host: &str,
+    ports: &[u16],
+    options: &ScanOptions,
+) -> Vec<PortScanResult> {
+    collect_sorted(|found| scan_ports_each(host, ports, options, |_| true, found))
+}
+
+/// Ports of a scan running in the background, yielded as they are probed.
+/// Returned by [`scan_ports_iter`] and [`probe_ports_iter`].
+pub type PortStream = ScanStream<PortScanResult>;
+
+/// Scan ports like [`scan_ports_with_options`], but yield each open port as
+/// soon as it is found instead of waiting for the whole scan.
+///
+/// The scan runs on a background thread; ports arrive in no particular
+/// order. Useful for long scans (all 65535 ports), where the first open
+/// ports can be reported long before the last one is probed.
+///
+/// # Examples
+///
+/// ```no_run
+/// use asphyxia::scanner::options::ScanOptions;
+/// use asphyxia::scanner::port::scan_ports_iter;
+///
+/// let ports: Vec<u16> = (1..=u16::MAX).collect();
+/// for result in scan_ports_iter("192.168.1.10", ports, ScanOptions::default()) {
+///     println!("{} is open", result.port);
+/// }
+/// ```
+pub fn scan_ports_iter(host: &str, ports: Vec<u16>, options: ScanOptions) -> PortStream {
+    let host = host.to_string();
+    stream::spawn(options, move |options, found| {
+        scan_ports_each(&host, &ports, options, PortScanResult::is_open, found)
+    })
+}
+
+/// Like [`scan_ports_iter`], but yield every probed port, closed and
+/// filtered ones included (see [`probe_ports_with_options`]).
+pub fn probe_ports_iter(host: &str, ports: Vec<u16>, options: ScanOptions) -> PortStream {
+    let host = host.to_string();
+    stream::spawn(options, move |options, found| {
+        scan_ports_each(&host, &ports, options, |_| true, found)
+    })
+}
+
+/// Run `scan` and return every port it found, sorted by port.
+fn collect_sorted(scan: impl FnOnce(Found<PortScanResult>)) -> Vec<PortScanResult> {
+    let mut results = stream::collect(scan);
+    results.sort_by_key(|result| result.port);
+    results
+}
+
+/// Scan `ports` on `host`, handing each result that passes `keep` to
+/// `found`.
+fn scan_ports_each(
+    host: &str,
+    ports: &[u16],
+    options: &ScanOptions,
+    keep: impl Fn(&PortScanResult) -> bool + Sync,
+    found: Found<PortScanResult>,
+) {
+    let mut ports = options.included_ports(ports);
+    if let Some(ip) = options
+        .annotations
+        .as_ref()
+        .and_then(|_| resolve_host(host))
+    {
+        ports.retain(|&port| options.allows_port(ip, port));
+    }
+    let progress = options.progress();
+    progress.start(ports.len() as u64, "ports scanned");
+    let rates = options.rate_classes();
+    // Only a host in a subnet with a rate of its own needs looking up.
+    let limiter = if options.subnet_rates.is_empty() {
+        rates.fallback()
+    } else {
+        resolve_host(host).map_or(rates.fallback(), |ip| rates.classify(ip))
+    };
+
+    // An `Err` stops the parallel iteration once `found` gives up.
+    let _ = options.install(|| {
+        ports.par_iter().try_for_each(|&port| {
+            if options.is_cancelled() {
+                return Ok(());
+            }
+            let result = probe_port(host, port, options, limiter);
+            progress.inc(1);
+            match result {
+                Ok(result) if keep(&result) && !found(result) => Err(()),
+                _ => Ok(()),
+            }
+        })
+    });
+
+    progress.finish("Scan completed");
+}
+
+/// Probe one port, retrying while it stays silent, and wait on `limiter`
+/// (if any) before every attempt.
+pub(crate) fn probe_port(
+    host: &str,
+    port: u16,
+    options: &ScanOptions,
+    limiter: Option<&RateLimiter>,
+) -> Result<PortScanResult, String> {
+    let mut attempts = 0;
+    loop {
+        if let Some(limiter) = limiter {
+            limiter.acquire();
+        }
+        let result = match (options.proxies.is_empty(), options.technique) {
+            (false, _) => proxy::probe(&options.proxies, host, port, options.timeout)?,
+            (true, ScanTechnique::Connect) => connect_probe(
+                host,
+                port,
+                options.timeout,
+                options.source_port,
+                Purpose::Probe
+                    .profile()
+                    .marked(options.dscp)
+                    .bound(options.origin()),
+            )?,
+            (true, ScanTechnique::Syn) => {
+                let ip = resolve_host(host)
+                    .ok_or_else(|| format!("Could not resolve host: {}", host))?;
+                let timeout = options.timeout.unwrap_or(CONNECT_TIMEOUT);
+                let result = match syn::probe_from(
+                    ip,
+                    port,
+                    options.source_port,
+                    options.origin(),
+                    options.dscp,
+                    &options.decoys,
+                    timeout,
+                ) {
+                    Ok(result) => result,
+                    Err(_) if options.rode_out_outage(ip) => continue,
+                    Err(e) => return Err(format!("SYN probe to {} failed: {}", host, e)),
+                };
+                if let (Some(cache), Some(fingerprint)) =
+                    (&options.fingerprints, result.fingerprint)
+                {
+                    cache.insert(ip, fingerprint);
+                }
+                result
+            }
+        };
+        // A probe sent while the scanning host was offline says nothing
+        // about the port; send it again once the network is back.
+        if result.state == PortState::Filtered
+            && options.proxies.is_empty()
+            && options.outages.is_some()
+            && resolve_host(host).is_some_and(|ip| options.rode_out_outage(ip))
+        {
+            continue;
+        }
+        attempts += 1;
+        if result.state != PortState::Filtered
+            || attempts > options.retries
+            || options.is_cancelled()
+        {
+            return Ok(PortScanResult { attempts, ..result });
+        }
     }
 }
 
 #[cfg(test)]
 mod tests {
     use super::*;
+    use std::io;
 
     const TEST_TIMEOUT: Option<Duration> = Some(Duration::from_millis(100));
 
     #[test]
     fn test_scan_port_success() {
         // localhost:0 is always invalid, used only for testing
-        let result = scan_port("127.0.0.1".to_string(), 0, TEST_TIMEOUT);
-        assert!(result.is_none());
+        let result = scan_port("127.0.0.1".to_string(), 0, TEST_TIMEOUT).unwrap();
+        assert!(!result.is_open());
     }
 
     #[test]
     fn test_scan_port_failure() {
-        let result = scan_port("127.0.0.1".to_string(), 1, TEST_TIMEOUT); // Non-existent port
-        assert!(result.is_none());
+        let result = scan_port("127.0.0.1".to_string(), 1, TEST_TIMEOUT).unwrap(); // Non-existent port
+        assert!(!result.is_open());
+    }
+
+    #[test]
+    fn test_scan_port_refused_is_closed() {
+        // Nothing listens on loopback port 1, so the kernel answers with RST.
+        let result = scan_port("127.0.0.1".to_string(), 1, TEST_TIMEOUT).unwrap();
+        assert_eq!(result.state, PortState::Closed);
+    }
+
+    #[test]
+    fn test_scan_port_unresolvable_host_is_error() {
+        assert!(scan_port(String::new(), 80, TEST_TIMEOUT).is_err());
+    }
+
+    #[test]
+    fn test_closed_port_is_not_retried() {
+        let options = ScanOptions {
+            timeout: TEST_TIMEOUT,
+            retries: 50,
+            // One attempt per second: a retry would make this test crawl.
+            rate_limit: Some(1),
+            ..ScanOptions::default()
+        };
+        let start = Instant::now();
+        let result = scan_port_with_options("127.0.0.1".to_string(), 1, &options).unwrap();
+        assert_eq!(result.state, PortState::Closed);
+        assert!(start.elapsed() < Duration::from_secs(1));
+    }
+
+    #[test]
+    fn test_scan_ports_with_options_returns_open_ports() {
+        let listener = std::net::TcpListener::bind("127.0.0.1:0").unwrap();
+        let open_port = listener.local_addr().unwrap().port();
+        let options = ScanOptions {
+            timeout: TEST_TIMEOUT,
+            ..ScanOptions::default()
+        };
+        let open = scan_ports_with_options("127.0.0.1", &[1, open_port], &options);
+        assert_eq!(open.len(), 1);
+        assert_eq!(open[0].port, open_port);
+    }
+
+    #[test]
+    fn test_scan_ports_iter_streams_open_ports() {
+        let listener = std::net::TcpListener::bind("127.0.0.1:0").unwrap();
+        let open_port = listener.local_addr().unwrap().port();
+        let options = ScanOptions {
+            timeout: TEST_TIMEOUT,
+            ..ScanOptions::default()
+        };
+        let ports: Vec<u16> = scan_ports_iter("127.0.0.1", vec![1, open_port], options)
+            .map(|result| result.port)
+            .collect();
+        assert_eq!(ports, vec![open_port]);
+    }
+
+    #[test]
+    fn test_state_counts_tell_closed_from_filtered() {
+        let listener = std::net::TcpListener::bind("127.0.0.1:0").unwrap();
+        let open_port = listener.local_addr().unwrap().port();
+        let options = ScanOptions {
+            timeout: TEST_TIMEOUT,
+            ..ScanOptions::default()
+        };
+        let mut results = probe_ports_with_options("127.0.0.1", &[1, open_port], &options);
+        results.push(PortScanResult {
+            port: 2,
+            state: PortState::Filtered,
+            evidence: Evidence::Timeout,
+            attempts: 1,
+            latency: Duration::ZERO,
+            ttl: None,
+            fingerprint: None,
+        });
+        assert_eq!(
+            StateCounts::of(&results),
+            StateCounts {
+                open: 1,
+                closed: 1,
+                filtered: 1,
+            }
+        );
+    }
+
+    #[test]
+    fn test_probe_ports_records_evidence() {
+        let listener = std::net::TcpListener::bind("127.0.0.1:0").unwrap();
+        let open_port = listener.local_addr().unwrap().port();
+        let options = ScanOptions {
+            timeout: TEST_TIMEOUT,
+            retries: 2,
+            ..ScanOptions::default()
+        };
+        let results = probe_ports_with_options("127.0.0.1", &[1, open_port], &options);
+        assert_eq!(results.len(), 2);
+        assert_eq!(results[0].evidence, Evidence::Reset);
+        assert_eq!(results[0].attempts, 1);
+        assert_eq!(results[1].evidence, Evidence::Handshake);
+        assert_eq!(results[1].explain(), "handshake completed");
+    }
+
+    #[test]
+    fn test_evidence_decides_state() {
+        let timed_out: io::Result<()> = Err(ErrorKind::TimedOut.into());
+        assert_eq!(Evidence::from_connect(&timed_out), Evidence::Timeout);
+        let unreachable: io::Result<()> = Err(ErrorKind::HostUnreachable.into());
+        assert_eq!(
+            Evidence::from_connect(&unreachable).state(),
+            PortState::Filtered
+        );
+        let result = PortScanResult {
+            port: 443,
+            state: PortState::Filtered,
+            evidence: Evidence::Timeout,
+            attempts: 3,
+            latency: Duration::ZERO,
+            ttl: None,
+            fingerprint: None,
+        };
+        assert_eq!(result.explain(), "no answer after 3 attempts");
+    }
+
+    #[test]
+    fn test_scan_ports_from_source_port() {
+        let listener = std::net::TcpListener::bind("127.0.0.1:0").unwrap();
+        let open_port = listener.local_addr().unwrap().port();
+        let source_port = std::net::TcpListener::bind("127.0.0.1:0")
+            .unwrap()
+            .local_addr()
+            .unwrap()
+            .port();
+        let options = ScanOptions::builder()
+            .timeout(Duration::from_millis(100))
+            .source_port(source_port)
+            .concurrency(2)
+            .build();
+        let open = scan_ports_with_options("127.0.0.1", &[1, open_port], &options);
+        assert_eq!(open.len(), 1);
+        let (_, peer) = listener.accept().unwrap();
+        assert_eq!(peer.port(), source_port);
+    }
+
+    #[test]
+    fn test_scan_ports_with_options_skips_excluded_ports() {
+        let listener = std::net::TcpListener::bind("127.0.0.1:0").unwrap();
+        let open_port = listener.local_addr().unwrap().port();
+        let options = ScanOptions {
+            timeout: TEST_TIMEOUT,
+            exclude_ports: vec![open_port],
+            ..ScanOptions::default()
+        };
+        assert!(scan_ports_with_options("127.0.0.1", &[1, open_port], &options).is_empty());
+    }
+
+    #[test]
+    fn test_scan_ports_with_options_reports_progress() {
+        use crate::scanner::progress::ProgressHandler;
+        use std::sync::Mutex;
+
+        #[derive(Default)]
+        struct Recorder(Mutex<Vec<String>>);
+
+        impl ProgressHandler for Recorder {
+            fn start(&self, total: u64, label: &str) {
+                self.0
+                    .lock()
+                    .unwrap()
+                    .push(format!("start {total} {label}"));
+            }
+            fn inc(&self, delta: u64) {
+                self.0.lock().unwrap().push(format!("inc {delta}"));
+            }
+            fn finish(&self, message: &str) {
+                self.0.lock().unwrap().push(format!("finish {message}"));
+            }
+        }
+
+        let recorder = std::sync::Arc::new(Recorder::default());
+        let options = ScanOptions {
+            timeout: TEST_TIMEOUT,
+            progress: Some(recorder.clone()),
+            ..ScanOptions::default()
+        };
+        scan_ports_with_options("127.0.0.1", &[1, 1], &options);
+
+        let events = recorder.0.lock().unwrap();
+        assert_eq!(
+            *events,
+            vec![
+                "start 2 ports scanned",
+                "inc 1",
+                "inc 1",
+                "finish Scan completed"
+            ]
+        );
+    }
+
+    #[test]
+    fn test_scan_port_with_timeout_open_port() {
+        let listener = std::net::TcpListener::bind("127.0.0.1:0").unwrap();
+        let port = listener.local_addr().unwrap().port();
+        let result =
+            scan_port_with_timeout("127.0.0.1".to_string(), port, Duration::from_millis(100))
+                .unwrap();
+        assert!(result.is_open(), "listening port should be open");
+        assert_eq!(result.port, port);
     }
 
     #[test]
@@ -0,0 +1,243 @@
+//! Progress reporting for scans.
+//!
+//! The scanners never draw anything themselves. They report progress through
+//! [`ProgressHandler`], so the CLI can render a terminal progress bar while
+//! GUI or server embedders render their own — or nothing at all, which is
+//! what happens when [`ScanOptions::progress`](crate::scanner::options::ScanOptions::progress)
+//! is left unset.
+//!
+//! Embedders that would rather ask than be told can poll a
+//! [`ProgressHandle`] instead: every [`ScanStream`](crate::scanner::stream::ScanStream)
+//! carries one, and it can be passed as the handler of any other scan.
+
+use std::sync::{Arc, Mutex};
+use std::time::{Duration, Instant};
+
+/// Receives progress updates from a running scan.
+///
+/// A scan may run several phases (a sweep discovers hosts, then scans
+/// ports); each phase calls [`start`](ProgressHandler::start), then
+/// [`inc`](ProgressHandler::inc) once per completed probe, then
+/// [`finish`](ProgressHandler::finish). Probes complete on many threads at
+/// once, so implementations must be thread-safe.
+///
+/// # Examples
+///
+/// ```
+/// use asphyxia::scanner::progress::ProgressHandler;
+/// use std::sync::atomic::{AtomicU64, Ordering};
+///
+/// #[derive(Default)]
+/// struct Counter(AtomicU64);
+///
+/// impl ProgressHandler for Counter {
+///     fn inc(&self, delta: u64) {
+///         self.0.fetch_add(delta, Ordering::Relaxed);
+///     }
+/// }
+/// ```
+pub trait ProgressHandler: Send + Sync {
+    /// A phase of `total` probes is starting; `label` describes what is
+    /// counted (e.g. `"ports scanned"`).
+    fn start(&self, total: u64, label: &str) {
+        let _ = (total, label);
+    }
+
+    /// `delta` more probes have completed.
+    fn inc(&self, delta: u64);
+
+    /// The current phase is done; `message` summarises it.
+    fn finish(&self, message: &str) {
+        let _ = message;
+    }
+}
+
+/// The handler used when no progress reporting was requested.
+pub(crate) struct NoProgress;
+
+impl ProgressHandler for NoProgress {
+    fn inc(&self, _delta: u64) {}
+}
+
+/// Where a scan stands, as read from a [`ProgressHandle`].
+#[derive(Debug, Clone, PartialEq)]
+pub struct Progress {
+    /// What the current phase counts (e.g. `"ports scanned"`); `None`
+    /// before the first phase starts.
+    pub phase: Option<String>,
+    /// Probes of the current phase completed so far.
+    pub done: u64,
+    /// Probes the current phase will run in all.
+    pub total: u64,
+    /// Probes completed per second since the phase started.
+    pub rate: f64,
+    /// Time left until the phase completes at the current rate; `None`
+    /// until a probe has completed.
+    pub eta: Option<Duration>,
+    /// Whether the scan is over: its last phase finished, or, for a
+    /// stream, the scan stopped.
+    pub finished: bool,
+}
+
+#[derive(Debug, Default)]
+struct State {
+    phase: Option<String>,
+    done: u64,
+    total: u64,
+    started: Option<Instant>,
+    finished: bool,
+}
+
+/// A shared, queryable view of a scan's progress.
+///
+/// A handle is a [`ProgressHandler`] that keeps count rather than drawing:
+/// hand it to [`ScanOptions::progress`](crate::scanner::options::ScanOptions::progress)
+/// and read [`snapshot`](ProgressHandle::snapshot) from any thread, e.g. on
+/// each tick of a GUI timer. Clones share the same count. The streaming
+/// scans return one through [`ScanStream::progress`](crate::scanner::stream::ScanStream::progress),
+/// still forwarding every update to the handler set in the options.
+///
+/// # Examples
+///
+/// ```no_run
+/// use asphyxia::{ProgressHandle, ScanOptions, scan_subnet_with_options};
+/// use std::sync::Arc;
+/// use std::thread;
+///
+/// let progress = ProgressHandle::new();
+/// let options = ScanOptions {
+///     progress: Some(Arc::new(progress.clone())),
+///     ..ScanOptions::default()
+/// };
+/// let scan = thread::spawn(move || {
+///     scan_subnet_with_options("192.168.1.0/24".parse().unwrap(), &options)
+/// });
+/// while !scan.is_finished() {
+///     let now = progress.snapshot();
+///     println!("{}/{} (ETA {:?})", now.done, now.total, now.eta);
+///     thread::sleep(std::time::Duration::from_secs(1));
+/// }
+/// ```
+#[derive(Clone, Default)]
+pub struct ProgressHandle {
+    state: Arc<Mutex<State>>,
+    inner: Option<Arc<dyn ProgressHandler>>,
+}
+
+impl ProgressHandle {
+    /// Create a handle; it reports no phase until a scan starts one.
+    pub fn new() -> ProgressHandle {
+        ProgressHandle::default()
+    }
+
+    /// A handle that also forwards every update to `inner`.
+    pub(crate) fn wrapping(inner: Option<Arc<dyn ProgressHandler>>) -> ProgressHandle {
+        ProgressHandle {
+            inner,
+            ..ProgressHandle::default()
+        }
+    }
+
+    /// Where the scan stands now.
+    pub fn snapshot(&self) -> Progress {
+        self.snapshot_at(Instant::now())
+    }
+
+    fn snapshot_at(&self, now: Instant) -> Progress {
+        let state = self.state.lock().unwrap_or_else(|e| e.into_inner());
+        let elapsed = state.started.map_or(Duration::ZERO, |started| {
+            now.saturating_duration_since(started)
+        });
+        let rate = if elapsed.is_zero() {
+            0.0
+        } else {
+            state.done as f64 / elapsed.as_secs_f64()
+        };
+        let eta = (rate > 0.0)
+            .then(|| Duration::from_secs_f64(state.total.saturating_sub(state.done) as f64 / rate));
+        Progress {
+            phase: state.phase.clone(),
+            done: state.done,
+            total: state.total,
+            rate,
+            eta,
+            finished: state.finished,
+        }
+    }
+
+    /// Mark the scan as over, whether or not its phase finished.
+    pub(crate) fn close(&self) {
+        self.state
+            .lock()
+            .unwrap_or_else(|e| e.into_inner())
+            .finished = true;
+    }
+}
+
+impl std::fmt::Debug for ProgressHandle {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("ProgressHandle")
+            .field("progress", &self.snapshot())
+            .finish()
+    }
+}
+
+impl ProgressHandler for ProgressHandle {
+    fn start(&self, total: u64, label: &str) {
+        *self.state.lock().unwrap_or_else(|e| e.into_inner()) = State {
+            phase: Some(label.to_string()),
+            total,
+            started: Some(Instant::now()),
+            ..State::default()
+        };
+        if let Some(inner) = &self.inner {
+            inner.start(total, label);
+        }
+    }
+
+    fn inc(&self, delta: u64) {
+        self.state.lock().unwrap_or_else(|e| e.into_inner()).done += delta;
+        if let Some(inner) = &self.inner {
+            inner.inc(delta);
+        }
+    }
+
+    fn finish(&self, message: &str) {
+        self.close();
+        if let Some(inner) = &self.inner {
+            inner.finish(message);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_handle_reports_rate_and_eta_of_the_phase() {
+        let progress = ProgressHandle::new();
+        assert_eq!(progress.snapshot().phase, None);
+        progress.start(100, "ports scanned");
+        progress.inc(25);
+        let started = progress.state.lock().unwrap().started.unwrap();
+        let now = progress.snapshot_at(started + Duration::from_secs(5));
+        assert_eq!(now.phase.as_deref(), Some("ports scanned"));
+        assert_eq!((now.done, now.total), (25, 100));
+        assert_eq!(now.rate, 5.0);
+        assert_eq!(now.eta, Some(Duration::from_secs(15)));
+        assert!(!now.finished);
+        progress.finish("Scan completed");
+        assert!(progress.snapshot().finished);
+    }
+
+    #[test]
+    fn test_handle_forwards_to_the_wrapped_handler() {
+        let inner = ProgressHandle::new();
+        let progress = ProgressHandle::wrapping(Some(Arc::new(inner.clone())));
+        progress.start(4, "addresses scanned");
+        progress.inc(3);
+        assert_eq!(inner.snapshot().done, 3);
+        assert_eq!(progress.snapshot().done, 3);
+    }
+}
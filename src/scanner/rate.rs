@@ -0,0 +1,284 @@
+//! Throttling for connection attempts.
+//!
+//! An unthrottled parallel scan opens connections as fast as the thread pool
+//! allows, which trips intrusion-detection alarms and can overwhelm small
+//! embedded devices. A [`RateLimiter`] spaces attempts out evenly so a scan
+//! never exceeds a fixed number of attempts per second, no matter how many
+//! workers share it.
+//!
+//! A conservative rate suits networks we do not own, and wastes time on
+//! those we do. A [`SubnetRate`] gives the targets of one subnet a rate of
+//! their own (a fixed one, a multiple of the scan's, or none at all), and
+//! [`RateClasses`] classifies each target into the most specific subnet
+//! that contains it, so its probes wait on that subnet's limiter instead.
+
+use ipnetwork::IpNetwork;
+use std::fmt;
+use std::net::IpAddr;
+use std::str::FromStr;
+use std::sync::Mutex;
+use std::thread;
+use std::time::{Duration, Instant};
+
+/// Spaces out connection attempts to at most `per_second` per second.
+///
+/// The limiter is shared by reference between scan workers; each call to
+/// [`acquire`](RateLimiter::acquire) reserves the next free slot and sleeps
+/// until it arrives.
+///
+/// # Examples
+///
+/// ```
+/// use asphyxia::scanner::rate::RateLimiter;
+///
+/// let limiter = RateLimiter::new(1000);
+/// for _ in 0..3 {
+///     limiter.acquire();
+///     // ... open a connection ...
+/// }
+/// ```
+#[derive(Debug)]
+pub struct RateLimiter {
+    interval: Duration,
+    next_slot: Mutex<Option<Instant>>,
+}
+
+impl RateLimiter {
+    /// Create a limiter allowing `per_second` attempts per second. A rate of
+    /// zero is treated as one.
+    pub fn new(per_second: u32) -> RateLimiter {
+        RateLimiter {
+            interval: Duration::from_secs(1) / per_second.max(1),
+            next_slot: Mutex::new(None),
+        }
+    }
+
+    /// Create a limiter allowing one attempt every `interval`, for rates
+    /// slower than one attempt per second.
+    pub fn with_interval(interval: Duration) -> RateLimiter {
+        RateLimiter {
+            interval,
+            next_slot: Mutex::new(None),
+        }
+    }
+
+    /// The time between two attempts.
+    pub fn interval(&self) -> Duration {
+        self.interval
+    }
+
+    /// Block until one more attempt may start.
+    pub fn acquire(&self) {
+        self.acquire_n(1);
+    }
+
+    /// Block until `n` more attempts may start, e.g. before probing a host on
+    /// several ports at once.
+    pub fn acquire_n(&self, n: u32) {
+        if n == 0 {
+            return;
+        }
+        let now = Instant::now();
+        let slot = {
+            let mut next = self.next_slot.lock().unwrap_or_else(|e| e.into_inner());
+            let slot = next.map_or(now, |next| next.max(now));
+            *next = Some(slot + self.interval * n);
+            slot
+        };
+        if let Some(wait) = slot.checked_duration_since(now).filter(|w| !w.is_zero()) {
+            thread::sleep(wait);
+        }
+    }
+}
+
+/// How fast the targets of a [`SubnetRate`] are probed.
+///
+/// Parses from `500` (attempts per second), `10x` (ten times the scan's
+/// rate) or `unlimited`.
+///
+/// # Examples
+///
+/// ```
+/// use asphyxia::scanner::rate::RateOverride;
+///
+/// assert_eq!("500".parse(), Ok(RateOverride::PerSecond(500)));
+/// assert_eq!("10x".parse(), Ok(RateOverride::Times(10)));
+/// assert_eq!("unlimited".parse(), Ok(RateOverride::Unlimited));
+/// assert!("fast".parse::<RateOverride>().is_err());
+/// ```
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum RateOverride {
+    /// A rate of its own, in attempts per second.
+    PerSecond(u32),
+    /// A multiple of the scan's pace; a scan that is not throttled leaves
+    /// these targets unthrottled too.
+    Times(u32),
+    /// No throttling at all.
+    Unlimited,
+}
+
+impl FromStr for RateOverride {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<RateOverride, String> {
+        let s = s.trim();
+        let invalid = || {
+            format!(
+                "invalid rate {:?} (expected attempts per second, e.g. 500, a multiple, e.g. 10x, or unlimited)",
+                s
+            )
+        };
+        if s.eq_ignore_ascii_case("unlimited") {
+            return Ok(RateOverride::Unlimited);
+        }
+        let (number, times) = match s.strip_suffix(['x', 'X']) {
+            Some(number) => (number, true),
+            None => (s, false),
+        };
+        match number.parse::<u32>() {
+            Ok(0) | Err(_) => Err(invalid()),
+            Ok(n) if times => Ok(RateOverride::Times(n)),
+            Ok(n) => Ok(RateOverride::PerSecond(n)),
+        }
+    }
+}
+
+impl fmt::Display for RateOverride {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            RateOverride::PerSecond(n) => write!(f, "{}/s", n),
+            RateOverride::Times(n) => write!(f, "{}x", n),
+            RateOverride::Unlimited => f.write_str("unlimited"),
+        }
+    }
+}
+
+/// A rate for the targets of one subnet, in place of the scan's own.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct SubnetRate {
+    /// The targets it applies to.
+    pub network: IpNetwork,
+    /// How fast they are probed.
+    pub rate: RateOverride,
+}
+
+/// The limiters of a scan: one for its targets at large, and one for each
+/// subnet with a [`SubnetRate`]. Each subnet is paced on its own, so a fast
+/// lab does not eat into the budget of the rest of the scan.
+///
+/// # Examples
+///
+/// ```
+/// use asphyxia::scanner::rate::{RateClasses, RateLimiter, RateOverride, SubnetRate};
+/// use std::time::Duration;
+///
+/// let lab = SubnetRate {
+///     network: "10.9.0.0/16".parse().unwrap(),
+///     rate: RateOverride::Times(10),
+/// };
+/// let classes = RateClasses::new(Some(RateLimiter::new(100)), &[lab]);
+/// let lab_host = classes.classify("10.9.1.1".parse().unwrap()).unwrap();
+/// assert_eq!(lab_host.interval(), Duration::from_millis(1));
+/// let other = classes.classify("10.0.0.1".parse().unwrap()).unwrap();
+/// assert_eq!(other.interval(), Duration::from_millis(10));
+/// ```
+#[derive(Debug, Default)]
+pub struct RateClasses {
+    default: Option<RateLimiter>,
+    /// Most specific subnet first; `None` leaves its targets unthrottled.
+    subnets: Vec<(IpNetwork, Option<RateLimiter>)>,
+}
+
+impl RateClasses {
+    /// Classes pacing targets at large with `default` (`None` for no
+    /// throttling), and the targets of each of `subnets` at its own rate.
+    pub fn new(default: Option<RateLimiter>, subnets: &[SubnetRate]) -> RateClasses {
+        let mut subnets: Vec<(IpNetwork, Option<RateLimiter>)> = subnets
+            .iter()
+            .map(|subnet| {
+                let limiter = match subnet.rate {
+                    RateOverride::PerSecond(n) => Some(RateLimiter::new(n)),
+                    RateOverride::Times(n) => default
+                        .as_ref()
+                        .map(|limiter| RateLimiter::with_interval(limiter.interval() / n.max(1))),
+                    RateOverride::Unlimited => None,
+                };
+                (subnet.network, limiter)
+            })
+            .collect();
+        subnets.sort_by_key(|(network, _)| std::cmp::Reverse(network.prefix()));
+        RateClasses { default, subnets }
+    }
+
+    /// The limiter for targets outside every subnet with a rate of its own.
+    pub fn fallback(&self) -> Option<&RateLimiter> {
+        self.default.as_ref()
+    }
+
+    /// The limiter the probes of `ip` wait on: that of the most specific
+    /// subnet containing it, else the default. `None` means no throttling.
+    pub fn classify(&self, ip: IpAddr) -> Option<&RateLimiter> {
+        match self
+            .subnets
+            .iter()
+            .find(|(network, _)| network.contains(ip))
+        {
+            Some((_, limiter)) => limiter.as_ref(),
+            None => self.fallback(),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_acquire_spaces_attempts() {
+        let limiter = RateLimiter::new(100);
+        let start = Instant::now();
+        for _ in 0..5 {
+            limiter.acquire();
+        }
+        // The first attempt is immediate; the next four wait 10 ms each.
+        assert!(start.elapsed() >= Duration::from_millis(40));
+    }
+
+    #[test]
+    fn test_classify_prefers_the_most_specific_subnet() {
+        let subnet = |network: &str, rate| SubnetRate {
+            network: network.parse().unwrap(),
+            rate,
+        };
+        let classes = RateClasses::new(
+            Some(RateLimiter::new(10)),
+            &[
+                subnet("10.0.0.0/8", RateOverride::PerSecond(1000)),
+                subnet("10.0.5.0/24", RateOverride::Unlimited),
+            ],
+        );
+        let interval = |ip: &str| {
+            classes
+                .classify(ip.parse().unwrap())
+                .map(RateLimiter::interval)
+        };
+        assert_eq!(interval("10.1.2.3"), Some(Duration::from_millis(1)));
+        assert_eq!(interval("10.0.5.9"), None);
+        assert_eq!(interval("192.168.0.1"), Some(Duration::from_millis(100)));
+    }
+
+    #[test]
+    fn test_multiple_of_no_rate_is_unthrottled() {
+        let lab = SubnetRate {
+            network: "10.9.0.0/16".parse().unwrap(),
+            rate: RateOverride::Times(10),
+        };
+        let classes = RateClasses::new(None, &[lab]);
+        assert!(classes.classify("10.9.0.1".parse().unwrap()).is_none());
+    }
+
+    #[test]
+    fn test_zero_rate_is_treated_as_one() {
+        assert_eq!(RateLimiter::new(0).interval, Duration::from_secs(1));
+    }
+}
@@ -0,0 +1,701 @@
+//! Reconstructing scan results from a packet capture.
+//!
+//! A report says a port was open; a capture taken while the scan ran shows
+//! why. [`read_pcap`] reads a libpcap file (as written by `tcpdump -w`) and
+//! [`replay`] works out from its packets what each probe got back, without
+//! sending anything: every SYN is a probe, answered by the SYN-ACK or RST
+//! that acknowledges it or by an ICMP error quoting it, and every ICMP echo
+//! request is a host probe, answered by the matching reply. That lets a
+//! disputed finding be checked against the wire long after the scan, and a
+//! capture be re-analysed with the same rules the scanner applies live.
+//!
+//! Captures from Ethernet, loopback, raw IP and Linux "any" interfaces are
+//! understood; pcapng files must be converted first (e.g. with
+//! `editcap -F pcap`).
+
+use std::collections::{BTreeMap, HashMap};
+use std::net::{IpAddr, Ipv4Addr, Ipv6Addr};
+use std::time::Duration;
+
+use chrono::{DateTime, Utc};
+
+use crate::scanner::address::{HostScanResult, HostState};
+use crate::scanner::fingerprint::StackFingerprint;
+use crate::scanner::port::{Evidence, PortScanResult};
+use crate::scanner::syn::{TCP_ACK, TCP_HEADER_LEN, TCP_SYN, classify};
+
+/// Magic number of a pcap file with microsecond timestamps.
+const PCAP_MICROS: u32 = 0xa1b2_c3d4;
+/// Magic number of a pcap file with nanosecond timestamps.
+const PCAP_NANOS: u32 = 0xa1b2_3c4d;
+/// Block type that opens a pcapng file.
+const PCAPNG: u32 = 0x0a0d_0d0a;
+
+/// Length of the pcap file header and of each record header.
+const FILE_HEADER_LEN: usize = 24;
+const RECORD_HEADER_LEN: usize = 16;
+
+/// Link-layer types understood, as numbered by libpcap.
+const LINKTYPE_NULL: u32 = 0;
+const LINKTYPE_ETHERNET: u32 = 1;
+const LINKTYPE_RAW: u32 = 101;
+const LINKTYPE_LOOP: u32 = 108;
+const LINKTYPE_LINUX_SLL: u32 = 113;
+const LINKTYPE_IPV4: u32 = 228;
+const LINKTYPE_IPV6: u32 = 229;
+const LINKTYPE_LINUX_SLL2: u32 = 276;
+
+/// EtherTypes of the frames kept.
+const ETHERTYPE_IPV4: u16 = 0x0800;
+const ETHERTYPE_IPV6: u16 = 0x86dd;
+const ETHERTYPE_VLAN: u16 = 0x8100;
+
+/// IP protocol numbers of the packets replayed.
+const PROTO_ICMP: u8 = 1;
+const PROTO_TCP: u8 = 6;
+const PROTO_ICMPV6: u8 = 58;
+
+/// One IP packet from a capture.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct CapturedPacket {
+    /// When it was captured.
+    pub time: DateTime<Utc>,
+    /// The IPv4 or IPv6 packet, link-layer header removed.
+    pub data: Vec<u8>,
+}
+
+/// A port probe found in a capture, and what it got back.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct ReplayedPort {
+    /// The probed host.
+    pub ip: IpAddr,
+    /// When the answer arrived, or the last probe was sent if none did.
+    pub time: DateTime<Utc>,
+    /// The outcome, as the scanner would have reported it.
+    pub result: PortScanResult,
+}
+
+/// A host probe (ICMP echo) found in a capture, and whether it was answered.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ReplayedHost {
+    /// When the first reply arrived, or the last request was sent if none did.
+    pub time: DateTime<Utc>,
+    /// The outcome, as the scanner would have reported it.
+    pub result: HostScanResult,
+}
+
+/// Everything [`replay`] reconstructed from a capture, ordered by address
+/// (and port).
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct Replay {
+    /// Every probed port.
+    pub ports: Vec<ReplayedPort>,
+    /// Every host sent an echo request.
+    pub hosts: Vec<ReplayedHost>,
+}
+
+/// Read the IP packets of a pcap capture; frames of other protocols (ARP,
+/// for instance) are skipped.
+///
+/// A capture cut short mid-record, as happens when `tcpdump` is killed, is
+/// read up to the last complete packet.
+///
+/// # Returns
+///
+/// * `Result<Vec<CapturedPacket>, String>` - The packets in capture order,
+///   or why the file cannot be read as a pcap capture
+///
+/// # Examples
+///
+/// ```
+/// use asphyxia::scanner::replay::read_pcap;
+///
+/// assert!(read_pcap(b"not a capture").is_err());
+/// ```
+pub fn read_pcap(bytes: &[u8]) -> Result<Vec<CapturedPacket>, String> {
+    let header = bytes
+        .get(..FILE_HEADER_LEN)
+        .ok_or("Not a pcap capture: file too short")?;
+    let magic = u32::from_le_bytes([header[0], header[1], header[2], header[3]]);
+    let (big_endian, nanos) = match magic {
+        PCAP_MICROS => (false, false),
+        PCAP_NANOS => (false, true),
+        m if m.swap_bytes() == PCAP_MICROS => (true, false),
+        m if m.swap_bytes() == PCAP_NANOS => (true, true),
+        PCAPNG => {
+            return Err(
+                "pcapng captures are not supported: convert with `editcap -F pcap` or capture with `tcpdump -w`"
+                    .to_string(),
+            );
+        }
+        _ => return Err("Not a pcap capture: unknown magic number".to_string()),
+    };
+    let read_u32 = |at: &[u8]| {
+        let word = [at[0], at[1], at[2], at[3]];
+        if big_endian {
+            u32::from_be_bytes(word)
+        } else {
+            u32::from_le_bytes(word)
+        }
+    };
+    let link_type = read_u32(&header[20..]) & 0x0fff_ffff;
+    if !matches!(
+        link_type,
+        LINKTYPE_NULL
+            | LINKTYPE_ETHERNET
+            | LINKTYPE_RAW
+            | LINKTYPE_LOOP
+            | LINKTYPE_LINUX_SLL
+            | LINKTYPE_IPV4
+            | LINKTYPE_IPV6
+            | LINKTYPE_LINUX_SLL2
+    ) {
+        return Err(format!("Unsupported pcap link type {}", link_type));
+    }
+
+    let mut packets = Vec::new();
+    let mut rest = &bytes[FILE_HEADER_LEN..];
+    while let Some(record) = rest.get(..RECORD_HEADER_LEN) {
+        let seconds = read_u32(record);
+        let fraction = read_u32(&record[4..]);
+        let captured = read_u32(&record[8..]) as usize;
+        // A record running past the end of the file (or of the address
+        // space) ends the readable part of the capture.
+        let Some(frame) = RECORD_HEADER_LEN
+            .checked_add(captured)
+            .and_then(|end| rest.get(RECORD_HEADER_LEN..end))
+        else {
+            break;
+        };
+        rest = &rest[RECORD_HEADER_LEN + frame.len()..];
+        let nanoseconds = if nanos {
+            fraction
+        } else {
+            fraction.saturating_mul(1000)
+        };
+        let Some(time) = DateTime::from_timestamp(i64::from(seconds), nanoseconds) else {
+            continue;
+        };
+        if let Some(data) = strip_link_header(link_type, frame) {
+            packets.push(CapturedPacket {
+                time,
+                data: data.to_vec(),
+            });
+        }
+    }
+    Ok(packets)
+}
+
+/// The IP packet inside `frame`, or `None` if it carries something else.
+fn strip_link_header(link_type: u32, frame: &[u8]) -> Option<&[u8]> {
+    let packet = match link_type {
+        LINKTYPE_ETHERNET => {
+            let mut ethertype = u16::from_be_bytes([*frame.get(12)?, *frame.get(13)?]);
+            let mut offset = 14;
+            if ethertype == ETHERTYPE_VLAN {
+                ethertype = u16::from_be_bytes([*frame.get(16)?, *frame.get(17)?]);
+                offset = 18;
+            }
+            if ethertype != ETHERTYPE_IPV4 && ethertype != ETHERTYPE_IPV6 {
+                return None;
+            }
+            frame.get(offset..)?
+        }
+        // A 4-byte address family, in whatever byte order the capturing
+        // host used; the IP version nibble tells the family just as well.
+        LINKTYPE_NULL | LINKTYPE_LOOP => frame.get(4..)?,
+        LINKTYPE_LINUX_SLL => frame.get(16..)?,
+        LINKTYPE_LINUX_SLL2 => frame.get(20..)?,
+        _ => frame,
+    };
+    matches!(packet.first()? >> 4, 4 | 6).then_some(packet)
+}
+
+/// The parts of an IP packet replay looks at.
+#[derive(Debug, Clone, Copy)]
+struct Datagram<'a> {
+    source: IpAddr,
+    destination: IpAddr,
+    protocol: u8,
+    hop_limit: u8,
+    payload: &'a [u8],
+}
+
+/// Parse the IPv4 or IPv6 header of `packet`. Non-first fragments, whose
+/// payload does not start with a transport header, are skipped. The packet
+/// may be truncated, as the one quoted in an ICMP error is.
+fn parse_ip(packet: &[u8]) -> Option<Datagram<'_>> {
+    match packet.first()? >> 4 {
+        4 => {
+            let header_len = usize::from(packet[0] & 0x0f) * 4;
+            if header_len < 20 || packet.len() < header_len {
+                return None;
+            }
+            if u16::from_be_bytes([packet[6], packet[7]]) & 0x1fff != 0 {
+                return None;
+            }
+            let total = usize::from(u16::from_be_bytes([packet[2], packet[3]]));
+            let octets = |at: usize| {
+                Ipv4Addr::new(packet[at], packet[at + 1], packet[at + 2], packet[at + 3])
+            };
+            Some(Datagram {
+                source: IpAddr::V4(octets(12)),
+                destination: IpAddr::V4(octets(16)),
+                protocol: packet[9],
+                hop_limit: packet[8],
+                payload: &packet[header_len..total.clamp(header_len, packet.len())],
+            })
+        }
+        6 => {
+            let header = packet.get(..40)?;
+            let address = |at: usize| {
+                let octets: [u8; 16] = header[at..at + 16].try_into().unwrap();
+                IpAddr::V6(Ipv6Addr::from(octets))
+            };
+            // Skip hop-by-hop, routing, fragment and destination options
+            // headers to reach the transport header.
+            let mut next = header[6];
+            let mut payload = &packet[40..];
+            loop {
+                match next {
+                    0 | 43 | 60 => {
+                        let len = (usize::from(*payload.get(1)?) + 1) * 8;
+                        next = payload[0];
+                        payload = payload.get(len..)?;
+                    }
+                    44 => {
+                        let fragment = payload.get(..8)?;
+                        if u16::from_be_bytes([fragment[2], fragment[3]]) & 0xfff8 != 0 {
+                            return None;
+                        }
+                        next = fragment[0];
+                        payload = &payload[8..];
+                    }
+                    _ => break,
+                }
+            }
+            Some(Datagram {
+                source: address(8),
+                destination: address(24),
+                protocol: next,
+                hop_limit: header[7],
+                payload,
+            })
+        }
+        _ => None,
+    }
+}
+
+/// A TCP connection attempt: prober address and port, target address and
+/// port.
+type Flow = (IpAddr, u16, IpAddr, u16);
+
+/// One SYN seen on a flow.
+#[derive(Debug, Clone, Copy)]
+struct Probe {
+    sent: DateTime<Utc>,
+    sequence: u32,
+    answer: Option<Answer>,
+}
+
+/// What came back for a [`Probe`].
+#[derive(Debug, Clone, Copy)]
+struct Answer {
+    evidence: Evidence,
+    at: DateTime<Utc>,
+    ttl: Option<u8>,
+    fingerprint: Option<StackFingerprint>,
+}
+
+/// Echo requests seen, by sender, target, identifier and sequence.
+type Pings = HashMap<(IpAddr, IpAddr, u16, u16), DateTime<Utc>>;
+
+/// Work out what every probe in `packets` got back.
+///
+/// Retries of a probe (more SYNs to the same port, from any source port)
+/// count as attempts; the answer to the last answered one decides the
+/// port's state, and a port whose probes all went unanswered is filtered.
+///
+/// # Examples
+///
+/// ```no_run
+/// use asphyxia::scanner::replay::{read_pcap, replay};
+///
+/// let packets = read_pcap(&std::fs::read("scan.pcap").unwrap()).unwrap();
+/// for port in replay(&packets).ports {
+///     println!("{}:{} {}", port.ip, port.result.port, port.result.explain());
+/// }
+/// ```
+pub fn replay(packets: &[CapturedPacket]) -> Replay {
+    let mut flows: HashMap<Flow, Vec<Probe>> = HashMap::new();
+    let mut pings = Pings::new();
+    let mut pinged: BTreeMap<IpAddr, DateTime<Utc>> = BTreeMap::new();
+    let mut replies: BTreeMap<IpAddr, (DateTime<Utc>, Duration, u8)> = BTreeMap::new();
+
+    for packet in packets {
+        let Some(datagram) = parse_ip(&packet.data) else {
+            continue;
+        };
+        match datagram.protocol {
+            PROTO_TCP => on_tcp(&mut flows, packet, &datagram),
+            PROTO_ICMP | PROTO_ICMPV6 => {
+                let message = datagram.payload;
+                if message.len() < 8 {
+                    continue;
+                }
+                let v6 = datagram.protocol == PROTO_ICMPV6;
+                let ident = u16::from_be_bytes([message[4], message[5]]);
+                let sequence = u16::from_be_bytes([message[6], message[7]]);
+                match (v6, message[0]) {
+                    (false, 8) | (true, 128) => {
+                        let key = (datagram.source, datagram.destination, ident, sequence);
+                        pings.insert(key, packet.time);
+                        pinged.insert(datagram.destination, packet.time);
+                    }
+                    (false, 0) | (true, 129) => {
+                        let key = (datagram.destination, datagram.source, ident, sequence);
+                        if let Some(sent) = pings.remove(&key) {
+                            let rtt = (packet.time - sent).to_std().unwrap_or_default();
+                            replies.entry(datagram.source).or_insert((
+                                packet.time,
+                                rtt,
+                                datagram.hop_limit,
+                            ));
+                        }
+                    }
+                    (false, 3) | (true, 1) => {
+                        let evidence = if message[1] == 0 {
+                            Evidence::NetworkUnreachable
+                        } else {
+                            Evidence::HostUnreachable
+                        };
+                        on_icmp_error(&mut flows, packet.time, &message[8..], evidence);
+                    }
+                    _ => {}
+                }
+            }
+            _ => {}
+        }
+    }
+
+    Replay {
+        ports: port_results(flows),
+        hosts: pinged
+            .into_iter()
+            .map(|(ip, last_sent)| {
+                let reply = replies.get(&ip);
+                ReplayedHost {
+                    time: reply.map_or(last_sent, |(at, _, _)| *at),
+                    result: HostScanResult {
+                        ip,
+                        state: if reply.is_some() {
+                            HostState::Up
+                        } else {
+                            HostState::Down
+                        },
+                        rtt: reply.map_or(Duration::ZERO, |(_, rtt, _)| *rtt),
+                        hostname: None,
+                        mac: None,
+                        ttl: reply.map(|(_, _, ttl)| *ttl),
+                    },
+                }
+            })
+            .collect(),
+    }
+}
+
+/// Record a TCP segment: a SYN starts a probe, a SYN-ACK or RST answers the
+/// latest unanswered probe it acknowledges.
+fn on_tcp(flows: &mut HashMap<Flow, Vec<Probe>>, packet: &CapturedPacket, datagram: &Datagram) {
+    let segment = datagram.payload;
+    if segment.len() < TCP_HEADER_LEN {
+        return;
+    }
+    let from_port = u16::from_be_bytes([segment[0], segment[1]]);
+    let to_port = u16::from_be_bytes([segment[2], segment[3]]);
+    let flags = segment[13];
+    if flags & TCP_SYN != 0 && flags & TCP_ACK == 0 {
+        let flow = (datagram.source, from_port, datagram.destination, to_port);
+        flows.entry(flow).or_default().push(Probe {
+            sent: packet.time,
+            sequence: u32::from_be_bytes([segment[4], segment[5], segment[6], segment[7]]),
+            answer: None,
+        });
+        return;
+    }
+    let flow = (datagram.destination, to_port, datagram.source, from_port);
+    let Some(probes) = flows.get_mut(&flow) else {
+        return;
+    };
+    for probe in probes
+        .iter_mut()
+        .rev()
+        .filter(|probe| probe.answer.is_none())
+    {
+        if let Some(evidence) = classify(segment, from_port, to_port, probe.sequence) {
+            let fingerprint = (evidence == Evidence::SynAck && datagram.source.is_ipv4())
+                .then(|| StackFingerprint::from_ipv4_syn_ack(&packet.data))
+                .flatten();
+            probe.answer = Some(Answer {
+                evidence,
+                at: packet.time,
+                ttl: Some(datagram.hop_limit),
+                fingerprint,
+            });
+            return;
+        }
+    }
+}
+
+/// Record an ICMP destination-unreachable error as the answer to the probe
+/// it quotes (`quoted`, the start of the probe's IP packet).
+fn on_icmp_error(
+    flows: &mut HashMap<Flow, Vec<Probe>>,
+    at: DateTime<Utc>,
+    quoted: &[u8],
+    evidence: Evidence,
+) {
+    let Some(probe) = parse_ip(quoted).filter(|probe| probe.protocol == PROTO_TCP) else {
+        return;
+    };
+    let Some(segment) = probe.payload.get(..8) else {
+        return;
+    };
+    let flow = (
+        probe.source,
+        u16::from_be_bytes([segment[0], segment[1]]),
+        probe.destination,
+        u16::from_be_bytes([segment[2], segment[3]]),
+    );
+    let sequence = u32::from_be_bytes([segment[4], segment[5], segment[6], segment[7]]);
+    if let Some(probe) = flows.get_mut(&flow).and_then(|probes| {
+        probes
+            .iter_mut()
+            .rev()
+            .find(|probe| probe.answer.is_none() && probe.sequence == sequence)
+    }) {
+        probe.answer = Some(Answer {
+            evidence,
+            at,
+            ttl: None,
+            fingerprint: None,
+        });
+    }
+}
+
+/// Combine the probes of every flow into one result per probed port.
+fn port_results(flows: HashMap<Flow, Vec<Probe>>) -> Vec<ReplayedPort> {
+    let mut by_port: BTreeMap<(IpAddr, u16), Vec<Probe>> = BTreeMap::new();
+    for ((_, _, ip, port), probes) in flows {
+        by_port.entry((ip, port)).or_default().extend(probes);
+    }
+    by_port
+        .into_iter()
+        .filter_map(|((ip, port), mut probes)| {
+            probes.sort_by_key(|probe| probe.sent);
+            let last = *probes.last()?;
+            let answered = probes.iter().rev().find(|probe| probe.answer.is_some());
+            let (time, evidence, latency, ttl, fingerprint) = match answered {
+                Some(Probe {
+                    sent,
+                    answer: Some(answer),
+                    ..
+                }) => (
+                    answer.at,
+                    answer.evidence,
+                    (answer.at - *sent).to_std().unwrap_or_default(),
+                    answer.ttl,
+                    answer.fingerprint,
+                ),
+                _ => (last.sent, Evidence::Timeout, Duration::ZERO, None, None),
+            };
+            Some(ReplayedPort {
+                ip,
+                time,
+                result: PortScanResult {
+                    port,
+                    state: evidence.state(),
+                    evidence,
+                    attempts: probes.len() as u32,
+                    latency,
+                    ttl,
+                    fingerprint,
+                },
+            })
+        })
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::scanner::port::PortState;
+    use crate::scanner::syn::TCP_RST;
+
+    const SCANNER: [u8; 4] = [10, 0, 0, 1];
+    const TARGET: [u8; 4] = [10, 0, 0, 5];
+
+    /// An IPv4 packet from `source` to `destination` carrying `payload`.
+    fn ipv4(
+        source: [u8; 4],
+        destination: [u8; 4],
+        protocol: u8,
+        ttl: u8,
+        payload: &[u8],
+    ) -> Vec<u8> {
+        let mut packet = vec![0x45, 0, 0, 0, 0, 0, 0x40, 0, ttl, protocol, 0, 0];
+        packet[2..4].copy_from_slice(&((20 + payload.len()) as u16).to_be_bytes());
+        packet.extend_from_slice(&source);
+        packet.extend_from_slice(&destination);
+        packet.extend_from_slice(payload);
+        packet
+    }
+
+    /// A TCP segment header.
+    fn tcp(from: u16, to: u16, sequence: u32, ack: u32, flags: u8) -> Vec<u8> {
+        let mut segment = vec![0u8; TCP_HEADER_LEN];
+        segment[0..2].copy_from_slice(&from.to_be_bytes());
+        segment[2..4].copy_from_slice(&to.to_be_bytes());
+        segment[4..8].copy_from_slice(&sequence.to_be_bytes());
+        segment[8..12].copy_from_slice(&ack.to_be_bytes());
+        segment[12] = 5 << 4;
+        segment[13] = flags;
+        segment[14..16].copy_from_slice(&64240u16.to_be_bytes());
+        segment
+    }
+
+    /// A little-endian, microsecond pcap file of Ethernet frames.
+    fn pcap(packets: &[(u32, Vec<u8>)]) -> Vec<u8> {
+        let mut file = Vec::new();
+        file.extend_from_slice(&PCAP_MICROS.to_le_bytes());
+        file.extend_from_slice(&[2, 0, 4, 0]);
+        file.extend_from_slice(&[0; 8]);
+        file.extend_from_slice(&65535u32.to_le_bytes());
+        file.extend_from_slice(&LINKTYPE_ETHERNET.to_le_bytes());
+        for (micros, packet) in packets {
+            let mut frame = vec![0u8; 12];
+            frame.extend_from_slice(&ETHERTYPE_IPV4.to_be_bytes());
+            frame.extend_from_slice(packet);
+            file.extend_from_slice(&1_777_885_200u32.to_le_bytes());
+            file.extend_from_slice(&micros.to_le_bytes());
+            file.extend_from_slice(&(frame.len() as u32).to_le_bytes());
+            file.extend_from_slice(&(frame.len() as u32).to_le_bytes());
+            file.extend_from_slice(&frame);
+        }
+        file
+    }
+
+    #[test]
+    fn test_read_pcap_strips_ethernet_and_skips_arp() {
+        let syn = ipv4(
+            SCANNER,
+            TARGET,
+            PROTO_TCP,
+            64,
+            &tcp(61000, 22, 7, 0, TCP_SYN),
+        );
+        let mut file = pcap(&[(0, syn.clone())]);
+        // An ARP frame, then a record cut short.
+        let mut arp = vec![0u8; 12];
+        arp.extend_from_slice(&0x0806u16.to_be_bytes());
+        arp.extend_from_slice(&[0; 28]);
+        file.extend_from_slice(&[0; 8]);
+        file.extend_from_slice(&(arp.len() as u32).to_le_bytes());
+        file.extend_from_slice(&(arp.len() as u32).to_le_bytes());
+        file.extend_from_slice(&arp);
+        file.extend_from_slice(&[0; 10]);
+        let packets = read_pcap(&file).unwrap();
+        assert_eq!(packets.len(), 1);
+        assert_eq!(packets[0].data, syn);
+        assert_eq!(packets[0].time.timestamp(), 1_777_885_200);
+    }
+
+    #[test]
+    fn test_read_pcap_rejects_pcapng() {
+        let mut file = PCAPNG.to_le_bytes().to_vec();
+        file.extend_from_slice(&[0; 24]);
+        assert!(read_pcap(&file).unwrap_err().contains("pcapng"));
+    }
+
+    #[test]
+    fn test_replay_classifies_port_probes() {
+        let from_target = |segment: Vec<u8>| ipv4(TARGET, SCANNER, PROTO_TCP, 52, &segment);
+        let to_target = |segment: Vec<u8>| ipv4(SCANNER, TARGET, PROTO_TCP, 64, &segment);
+        // The ICMP error quotes the probe's IP header and first 8 bytes.
+        let mut unreachable = vec![3, 13, 0, 0, 0, 0, 0, 0];
+        unreachable.extend_from_slice(&to_target(tcp(61003, 3389, 400, 0, TCP_SYN))[..28]);
+        let file = pcap(&[
+            (0, to_target(tcp(61000, 22, 100, 0, TCP_SYN))),
+            (10, to_target(tcp(61001, 23, 200, 0, TCP_SYN))),
+            (20, to_target(tcp(61002, 25, 300, 0, TCP_SYN))),
+            (30, to_target(tcp(61003, 3389, 400, 0, TCP_SYN))),
+            (
+                2_000,
+                from_target(tcp(22, 61000, 9, 101, TCP_SYN | TCP_ACK)),
+            ),
+            (
+                3_000,
+                from_target(tcp(23, 61001, 0, 201, TCP_RST | TCP_ACK)),
+            ),
+            // An answer to some other connection.
+            (
+                3_500,
+                from_target(tcp(25, 61002, 0, 999, TCP_RST | TCP_ACK)),
+            ),
+            (
+                4_000,
+                ipv4([10, 0, 0, 254], SCANNER, PROTO_ICMP, 255, &unreachable),
+            ),
+            (900_000, to_target(tcp(61004, 25, 500, 0, TCP_SYN))),
+        ]);
+        let ports = replay(&read_pcap(&file).unwrap()).ports;
+        let states: Vec<(u16, PortState, Evidence, u32)> = ports
+            .iter()
+            .map(|p| {
+                (
+                    p.result.port,
+                    p.result.state,
+                    p.result.evidence,
+                    p.result.attempts,
+                )
+            })
+            .collect();
+        assert_eq!(
+            states,
+            vec![
+                (22, PortState::Open, Evidence::SynAck, 1),
+                (23, PortState::Closed, Evidence::Reset, 1),
+                (25, PortState::Filtered, Evidence::Timeout, 2),
+                (3389, PortState::Filtered, Evidence::HostUnreachable, 1),
+            ]
+        );
+        assert_eq!(ports[0].result.latency, Duration::from_micros(2_000));
+        assert_eq!(ports[0].result.ttl, Some(52));
+        assert_eq!(
+            ports[0].result.fingerprint.unwrap().os_guess(),
+            Some("Linux")
+        );
+    }
+
+    #[test]
+    fn test_replay_matches_echo_replies() {
+        let echo = |kind: u8, sequence: u16| {
+            let mut message = vec![kind, 0, 0, 0, 0x12, 0x34];
+            message.extend_from_slice(&sequence.to_be_bytes());
+            message
+        };
+        let silent = [10, 0, 0, 6];
+        let file = pcap(&[
+            (0, ipv4(SCANNER, TARGET, PROTO_ICMP, 64, &echo(8, 1))),
+            (0, ipv4(SCANNER, silent, PROTO_ICMP, 64, &echo(8, 1))),
+            (1_500, ipv4(TARGET, SCANNER, PROTO_ICMP, 120, &echo(0, 1))),
+        ]);
+        let hosts = replay(&read_pcap(&file).unwrap()).hosts;
+        assert_eq!(hosts.len(), 2);
+        assert!(hosts[0].result.is_up());
+        assert_eq!(hosts[0].result.rtt, Duration::from_micros(1_500));
+        assert_eq!(hosts[0].result.ttl, Some(120));
+        assert_eq!(hosts[1].result.ip, IpAddr::from(silent));
+        assert!(!hosts[1].result.is_up());
+    }
+}
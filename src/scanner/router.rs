@@ -0,0 +1,479 @@
+//! Management-exposure report for a gateway or router.
+//!
+//! Field technicians check the same things on every site visit: which
+//! management services the router answers on, what it says it is, and
+//! whether any of that is risky (cleartext Telnet, web management without
+//! HTTPS, a reachable UPnP control endpoint, SNMP with the default
+//! community). [`probe_router`] does exactly that, over TCP for the
+//! management ports and over UDP for SNMP and SSDP (UPnP discovery).
+
+use rayon::prelude::*;
+use std::net::{IpAddr, Ipv4Addr, Ipv6Addr, SocketAddr, UdpSocket};
+use std::time::Duration;
+
+use crate::scanner::banner::grab_banner;
+use crate::scanner::options::ScanOptions;
+use crate::scanner::port::{self, CONNECT_TIMEOUT};
+
+/// TCP ports routers commonly expose for management, with what they are.
+pub const MANAGEMENT_PORTS: &[(u16, &str)] = &[
+    (22, "ssh"),
+    (23, "telnet"),
+    (80, "http"),
+    (443, "https"),
+    (5000, "upnp"),
+    (8080, "http-alt"),
+    (8443, "https-alt"),
+    (49152, "upnp"),
+];
+
+/// The UDP port SNMP agents answer on.
+pub const SNMP_PORT: u16 = 161;
+
+/// The UDP port UPnP devices answer SSDP discovery on.
+pub const SSDP_PORT: u16 = 1900;
+
+/// The community routers ship with, and the one asked for.
+const SNMP_COMMUNITY: &str = "public";
+
+/// `1.3.6.1.2.1.1.1.0` (sysDescr.0), BER-encoded.
+const SYS_DESCR_OID: &[u8] = &[0x2b, 6, 1, 2, 1, 1, 1, 0];
+
+/// The request-id of our GetRequest, to match the answer to it.
+const SNMP_REQUEST_ID: &[u8] = &[0x41, 0x53];
+
+/// A unicast SSDP search for the router's root device.
+const SSDP_SEARCH: &str = "M-SEARCH * HTTP/1.1\r\n\
+    HOST: 239.255.255.250:1900\r\n\
+    MAN: \"ssdp:discover\"\r\n\
+    MX: 1\r\n\
+    ST: upnp:rootdevice\r\n\r\n";
+
+/// An open management service on the router.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct RouterService {
+    /// The open TCP port.
+    pub port: u16,
+    /// What the port is used for (see [`MANAGEMENT_PORTS`]).
+    pub name: &'static str,
+    /// The service's identifying banner, if it sent one.
+    pub banner: Option<String>,
+}
+
+/// What a router exposes for management.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct RouterReport {
+    /// The probed router.
+    pub ip: IpAddr,
+    /// Its open management services, sorted by port.
+    pub services: Vec<RouterService>,
+    /// The router's sysDescr, if it answered an SNMP GetRequest with the
+    /// `public` community.
+    pub snmp: Option<String>,
+    /// What the router's SSDP answer says it is (its `SERVER` header, else
+    /// its `LOCATION`), if it answered an M-SEARCH.
+    pub ssdp: Option<String>,
+}
+
+impl RouterReport {
+    /// Plain-language findings about the router's exposure, most serious
+    /// first.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use asphyxia::scanner::router::{RouterReport, RouterService};
+    ///
+    /// let report = RouterReport {
+    ///     ip: "192.168.1.1".parse().unwrap(),
+    ///     services: vec![RouterService { port: 23, name: "telnet", banner: None }],
+    ///     snmp: None,
+    ///     ssdp: None,
+    /// };
+    /// assert!(report.findings()[0].contains("Telnet"));
+    /// ```
+    pub fn findings(&self) -> Vec<String> {
+        let open = |name: &str| self.services.iter().any(|s| s.name == name);
+        let mut findings = Vec::new();
+        if open("telnet") {
+            findings.push("Telnet is open: credentials cross the network in cleartext".to_string());
+        }
+        if let Some(descr) = &self.snmp {
+            findings.push(format!(
+                "SNMP answers with the default community \"public\": anyone on the network can read the device's configuration ({})",
+                descr
+            ));
+        }
+        if (open("http") || open("http-alt")) && !(open("https") || open("https-alt")) {
+            findings.push("Web management is only offered over plain HTTP".to_string());
+        }
+        if open("upnp") {
+            findings.push(
+                "A UPnP control endpoint is reachable: devices can open ports without review"
+                    .to_string(),
+            );
+        }
+        if let Some(server) = &self.ssdp {
+            findings.push(format!(
+                "UPnP discovery (SSDP) answers on 1900/udp: the router advertises its devices and services ({})",
+                server
+            ));
+        }
+        if open("ssh") {
+            findings.push("SSH management is enabled".to_string());
+        }
+        if self.services.is_empty() && self.snmp.is_none() && self.ssdp.is_none() {
+            findings.push("No management services answered".to_string());
+        }
+        findings
+    }
+}
+
+/// Probe `ip` on every [`MANAGEMENT_PORTS`] entry and grab the banners of the
+/// open ones, then ask it for its SNMP sysDescr and send it an SSDP search.
+///
+/// Port probing honours `options` (timeout, rate limit, retries,
+/// cancellation, progress); banners are read and the UDP answers waited for
+/// with the same timeout, and the UDP requests are sent once more for each
+/// retry.
+///
+/// # Examples
+///
+/// ```no_run
+/// use asphyxia::scanner::options::ScanOptions;
+/// use asphyxia::scanner::router::probe_router;
+///
+/// let report = probe_router("192.168.1.1".parse().unwrap(), &ScanOptions::default());
+/// for finding in report.findings() {
+///     println!("{}", finding);
+/// }
+/// ```
+pub fn probe_router(ip: IpAddr, options: &ScanOptions) -> RouterReport {
+    let ports: Vec<u16> = MANAGEMENT_PORTS.iter().map(|&(port, _)| port).collect();
+    let timeout = options.timeout.unwrap_or(CONNECT_TIMEOUT);
+    let open = port::scan_ports_with_options(&ip.to_string(), &ports, options);
+
+    let services = open
+        .par_iter()
+        .map(|result| RouterService {
+            port: result.port,
+            name: service_name(result.port),
+            banner: grab_banner(ip, result.port, timeout),
+        })
+        .collect();
+    let attempts = options.retries + 1;
+    let cancelled = || options.cancel.as_ref().is_some_and(|c| c.is_cancelled());
+    let (snmp, ssdp) = if cancelled() {
+        (None, None)
+    } else {
+        rayon::join(
+            || snmp_sys_descr(SocketAddr::new(ip, SNMP_PORT), timeout, attempts),
+            || ssdp_search(SocketAddr::new(ip, SSDP_PORT), timeout, attempts),
+        )
+    };
+    RouterReport {
+        ip,
+        services,
+        snmp,
+        ssdp,
+    }
+}
+
+/// Ask the SNMP agent at `peer` for its sysDescr with the `public`
+/// community.
+fn snmp_sys_descr(peer: SocketAddr, timeout: Duration, attempts: u32) -> Option<String> {
+    let answer = udp_exchange(peer, &snmp_get_request(), timeout, attempts)?;
+    parse_snmp_response(&answer)
+}
+
+/// Send an SSDP search to `peer` and return what its answer says it is.
+fn ssdp_search(peer: SocketAddr, timeout: Duration, attempts: u32) -> Option<String> {
+    let answer = udp_exchange(peer, SSDP_SEARCH.as_bytes(), timeout, attempts)?;
+    parse_ssdp_response(&String::from_utf8_lossy(&answer))
+}
+
+/// Send `request` to `peer` up to `attempts` times and return the first
+/// datagram that comes back from it within `timeout` of a send.
+fn udp_exchange(
+    peer: SocketAddr,
+    request: &[u8],
+    timeout: Duration,
+    attempts: u32,
+) -> Option<Vec<u8>> {
+    let local: IpAddr = match peer {
+        SocketAddr::V4(_) => Ipv4Addr::UNSPECIFIED.into(),
+        SocketAddr::V6(_) => Ipv6Addr::UNSPECIFIED.into(),
+    };
+    let socket = UdpSocket::bind((local, 0)).ok()?;
+    // Connected, so only the router's own answers are read.
+    socket.connect(peer).ok()?;
+    socket.set_read_timeout(Some(timeout)).ok()?;
+    let mut buf = [0u8; 2048];
+    for _ in 0..attempts {
+        socket.send(request).ok()?;
+        if let Ok(len) = socket.recv(&mut buf) {
+            return Some(buf[..len].to_vec());
+        }
+    }
+    None
+}
+
+/// An SNMPv2c GetRequest for sysDescr.0 with the `public` community.
+fn snmp_get_request() -> Vec<u8> {
+    let varbind = ber(0x30, &[ber(0x06, SYS_DESCR_OID), ber(0x05, &[])].concat());
+    let pdu = [
+        ber(0x02, SNMP_REQUEST_ID),
+        ber(0x02, &[0]), // error-status
+        ber(0x02, &[0]), // error-index
+        ber(0x30, &varbind),
+    ]
+    .concat();
+    let message = [
+        ber(0x02, &[1]), // version: 2c
+        ber(0x04, SNMP_COMMUNITY.as_bytes()),
+        ber(0xa0, &pdu),
+    ]
+    .concat();
+    ber(0x30, &message)
+}
+
+/// The sysDescr in an SNMP GetResponse to [`snmp_get_request`], if it
+/// carries one without error.
+fn parse_snmp_response(data: &[u8]) -> Option<String> {
+    let (0x30, message, _) = ber_next(data)? else {
+        return None;
+    };
+    let (0x02, _version, rest) = ber_next(message)? else {
+        return None;
+    };
+    let (0x04, _community, rest) = ber_next(rest)? else {
+        return None;
+    };
+    let (0xa2, pdu, _) = ber_next(rest)? else {
+        return None;
+    };
+    let (0x02, request_id, rest) = ber_next(pdu)? else {
+        return None;
+    };
+    let (0x02, error_status, rest) = ber_next(rest)? else {
+        return None;
+    };
+    if request_id != SNMP_REQUEST_ID || error_status.iter().any(|&b| b != 0) {
+        return None;
+    }
+    let (0x02, _error_index, rest) = ber_next(rest)? else {
+        return None;
+    };
+    let (0x30, varbinds, _) = ber_next(rest)? else {
+        return None;
+    };
+    let (0x30, varbind, _) = ber_next(varbinds)? else {
+        return None;
+    };
+    let (0x06, SYS_DESCR_OID, rest) = ber_next(varbind)? else {
+        return None;
+    };
+    let (0x04, descr, _) = ber_next(rest)? else {
+        return None;
+    };
+    Some(String::from_utf8_lossy(descr).trim().to_string())
+}
+
+/// A BER element: `tag`, the length of `content`, then `content`.
+fn ber(tag: u8, content: &[u8]) -> Vec<u8> {
+    let len = content.len();
+    let mut element = vec![tag];
+    match len {
+        0..=0x7f => element.push(len as u8),
+        0x80..=0xff => element.extend([0x81, len as u8]),
+        _ => element.extend([0x82, (len >> 8) as u8, len as u8]),
+    }
+    element.extend_from_slice(content);
+    element
+}
+
+/// The first BER element of `data`: its tag, its content and what follows
+/// it, or `None` if it is cut short.
+fn ber_next(data: &[u8]) -> Option<(u8, &[u8], &[u8])> {
+    let (&tag, rest) = data.split_first()?;
+    let (&first, rest) = rest.split_first()?;
+    let (len, rest) = match first {
+        0..=0x7f => (usize::from(first), rest),
+        0x81 => (usize::from(*rest.first()?), &rest[1..]),
+        0x82 => (
+            usize::from(u16::from_be_bytes([*rest.first()?, *rest.get(1)?])),
+            &rest[2..],
+        ),
+        _ => return None,
+    };
+    let content = rest.get(..len)?;
+    Some((tag, content, &rest[len..]))
+}
+
+/// The `SERVER` header of an SSDP answer, else its `LOCATION`, if the
+/// answer is a `200 OK`.
+fn parse_ssdp_response(text: &str) -> Option<String> {
+    let mut lines = text.lines();
+    let status = lines.next()?;
+    if !status.starts_with("HTTP/1.1 200") {
+        return None;
+    }
+    let header = |name: &str| {
+        text.lines().skip(1).find_map(|line| {
+            let (key, value) = line.split_once(':')?;
+            key.trim()
+                .eq_ignore_ascii_case(name)
+                .then(|| value.trim().to_string())
+        })
+    };
+    header("SERVER")
+        .or_else(|| header("LOCATION"))
+        .or_else(|| Some(status.trim().to_string()))
+}
+
+/// The [`MANAGEMENT_PORTS`] name of `port`.
+fn service_name(port: u16) -> &'static str {
+    MANAGEMENT_PORTS
+        .iter()
+        .find(|&&(p, _)| p == port)
+        .map_or("unknown", |&(_, name)| name)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn report(names: &[&'static str]) -> RouterReport {
+        RouterReport {
+            ip: "192.168.1.1".parse().unwrap(),
+            services: names
+                .iter()
+                .map(|&name| RouterService {
+                    port: MANAGEMENT_PORTS
+                        .iter()
+                        .find(|&&(_, n)| n == name)
+                        .unwrap()
+                        .0,
+                    name,
+                    banner: None,
+                })
+                .collect(),
+            snmp: None,
+            ssdp: None,
+        }
+    }
+
+    #[test]
+    fn test_findings_flag_cleartext_management() {
+        let findings = report(&["telnet", "http"]).findings();
+        assert_eq!(findings.len(), 2);
+        assert!(findings[0].contains("Telnet"));
+        assert!(findings[1].contains("plain HTTP"));
+    }
+
+    #[test]
+    fn test_findings_accept_http_alongside_https() {
+        let findings = report(&["http", "https", "ssh"]).findings();
+        assert_eq!(findings, vec!["SSH management is enabled".to_string()]);
+    }
+
+    #[test]
+    fn test_findings_for_quiet_router() {
+        assert_eq!(
+            report(&[]).findings(),
+            vec!["No management services answered".to_string()]
+        );
+    }
+
+    #[test]
+    fn test_findings_flag_snmp_and_ssdp() {
+        let findings = RouterReport {
+            snmp: Some("RouterOS RB750".to_string()),
+            ssdp: Some("miniupnpd/2.0".to_string()),
+            ..report(&["telnet", "ssh"])
+        }
+        .findings();
+        assert_eq!(findings.len(), 4);
+        assert!(findings[1].contains("\"public\"") && findings[1].contains("RouterOS RB750"));
+        assert!(findings[2].contains("SSDP") && findings[2].contains("miniupnpd/2.0"));
+    }
+
+    /// A GetResponse to our request carrying `descr`.
+    fn snmp_response(descr: &str) -> Vec<u8> {
+        let varbind = ber(
+            0x30,
+            &[ber(0x06, SYS_DESCR_OID), ber(0x04, descr.as_bytes())].concat(),
+        );
+        let pdu = [
+            ber(0x02, SNMP_REQUEST_ID),
+            ber(0x02, &[0]),
+            ber(0x02, &[0]),
+            ber(0x30, &varbind),
+        ]
+        .concat();
+        let message = [ber(0x02, &[1]), ber(0x04, b"public"), ber(0xa2, &pdu)].concat();
+        ber(0x30, &message)
+    }
+
+    #[test]
+    fn test_snmp_get_request_encoding() {
+        let request = snmp_get_request();
+        assert_eq!(request.len(), 41);
+        assert_eq!(&request[..2], [0x30, 39]);
+        assert!(request.ends_with(&[6, 8, 0x2b, 6, 1, 2, 1, 1, 1, 0, 5, 0]));
+    }
+
+    #[test]
+    fn test_parse_snmp_response() {
+        let long = "x".repeat(300);
+        assert_eq!(
+            parse_snmp_response(&snmp_response("Linux gw 5.4")).as_deref(),
+            Some("Linux gw 5.4")
+        );
+        assert_eq!(parse_snmp_response(&snmp_response(&long)), Some(long));
+        let response = snmp_response("Linux gw 5.4");
+        assert_eq!(parse_snmp_response(&response[..response.len() - 3]), None);
+        // Our own request is not an answer.
+        assert_eq!(parse_snmp_response(&snmp_get_request()), None);
+    }
+
+    #[test]
+    fn test_parse_ssdp_response() {
+        let answer = "HTTP/1.1 200 OK\r\nCACHE-CONTROL: max-age=120\r\n\
+            LOCATION: http://192.168.1.1:5000/rootDesc.xml\r\n\
+            Server: Linux UPnP/1.1 MiniUPnPd/2.2\r\n\r\n";
+        assert_eq!(
+            parse_ssdp_response(answer).as_deref(),
+            Some("Linux UPnP/1.1 MiniUPnPd/2.2")
+        );
+        assert_eq!(
+            parse_ssdp_response("HTTP/1.1 200 OK\r\nLOCATION: http://gw/\r\n").as_deref(),
+            Some("http://gw/")
+        );
+        assert_eq!(parse_ssdp_response("HTTP/1.1 404 Not Found\r\n"), None);
+    }
+
+    #[test]
+    fn test_snmp_over_loopback() {
+        let agent = UdpSocket::bind("127.0.0.1:0").unwrap();
+        let peer = agent.local_addr().unwrap();
+        let responder = std::thread::spawn(move || {
+            let mut buf = [0u8; 512];
+            let (len, from) = agent.recv_from(&mut buf).unwrap();
+            assert_eq!(&buf[..len], snmp_get_request());
+            agent.send_to(&snmp_response("Test router"), from).unwrap();
+        });
+        assert_eq!(
+            snmp_sys_descr(peer, Duration::from_secs(2), 1).as_deref(),
+            Some("Test router")
+        );
+        responder.join().unwrap();
+    }
+
+    #[test]
+    fn test_silent_udp_peer_yields_nothing() {
+        let silent = UdpSocket::bind("127.0.0.1:0").unwrap();
+        let peer = silent.local_addr().unwrap();
+        assert_eq!(ssdp_search(peer, Duration::from_millis(50), 2), None);
+    }
+}
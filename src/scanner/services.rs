@@ -0,0 +1,320 @@
+//! Service names for well-known ports.
+//!
+//! Operators tend to think in service names (`ssh`, `https`, `postgres`)
+//! rather than port numbers, so port lists may mention services by name. The
+//! names are resolved through a small table of well-known TCP services that
+//! is compiled into the binary, so lookups work the same on every platform.
+//! Where the system provides an `/etc/services` file, its TCP entries (and
+//! their aliases) take precedence over the embedded table.
+//!
+//! The same data works in reverse: [`service_name_for_port`] labels scan
+//! results, so a report reads `22/tcp ssh` instead of a bare port number.
+
+use std::collections::HashMap;
+use std::fs;
+use std::sync::OnceLock;
+
+/// Location of the system services database consulted as an override.
+const SYSTEM_SERVICES: &str = "/etc/services";
+
+/// Embedded table of well-known TCP services, as `(port, name)` pairs.
+///
+/// Names follow the IANA service name registry; a few widely used aliases
+/// (e.g. `postgres`, `www`) are listed explicitly so they resolve even on
+/// systems without `/etc/services`.
+const WELL_KNOWN_TCP: &[(u16, &str)] = &[
+    (7, "echo"),
+    (9, "discard"),
+    (13, "daytime"),
+    (19, "chargen"),
+    (20, "ftp-data"),
+    (21, "ftp"),
+    (22, "ssh"),
+    (23, "telnet"),
+    (25, "smtp"),
+    (37, "time"),
+    (43, "whois"),
+    (49, "tacacs"),
+    (53, "domain"),
+    (53, "dns"),
+    (67, "bootps"),
+    (69, "tftp"),
+    (70, "gopher"),
+    (79, "finger"),
+    (80, "http"),
+    (80, "www"),
+    (88, "kerberos"),
+    (102, "iso-tsap"),
+    (109, "pop2"),
+    (110, "pop3"),
+    (111, "sunrpc"),
+    (111, "rpcbind"),
+    (113, "auth"),
+    (119, "nntp"),
+    (123, "ntp"),
+    (135, "msrpc"),
+    (137, "netbios-ns"),
+    (138, "netbios-dgm"),
+    (139, "netbios-ssn"),
+    (143, "imap"),
+    (161, "snmp"),
+    (162, "snmptrap"),
+    (179, "bgp"),
+    (194, "irc"),
+    (389, "ldap"),
+    (427, "svrloc"),
+    (443, "https"),
+    (444, "snpp"),
+    (445, "microsoft-ds"),
+    (445, "smb"),
+    (464, "kpasswd"),
+    (465, "submissions"),
+    (465, "smtps"),
+    (500, "isakmp"),
+    (502, "modbus"),
+    (512, "exec"),
+    (513, "login"),
+    (514, "shell"),
+    (515, "printer"),
+    (543, "klogin"),
+    (544, "kshell"),
+    (548, "afp"),
+    (554, "rtsp"),
+    (587, "submission"),
+    (631, "ipp"),
+    (636, "ldaps"),
+    (646, "ldp"),
+    (873, "rsync"),
+    (902, "vmware-auth"),
+    (989, "ftps-data"),
+    (990, "ftps"),
+    (993, "imaps"),
+    (995, "pop3s"),
+    (1080, "socks"),
+    (1194, "openvpn"),
+    (1433, "ms-sql-s"),
+    (1433, "mssql"),
+    (1521, "oracle"),
+    (1723, "pptp"),
+    (1883, "mqtt"),
+    (2049, "nfs"),
+    (2181, "zookeeper"),
+    (2375, "docker"),
+    (2376, "docker-s"),
+    (2379, "etcd-client"),
+    (3128, "squid"),
+    (3260, "iscsi"),
+    (3306, "mysql"),
+    (3389, "ms-wbt-server"),
+    (3389, "rdp"),
+    (3690, "svn"),
+    (4369, "epmd"),
+    (5060, "sip"),
+    (5061, "sips"),
+    (5222, "xmpp-client"),
+    (5269, "xmpp-server"),
+    (5353, "mdns"),
+    (5432, "postgresql"),
+    (5432, "postgres"),
+    (5672, "amqp"),
+    (5900, "vnc"),
+    (5985, "wsman"),
+    (5986, "wsmans"),
+    (6379, "redis"),
+    (6443, "kube-apiserver"),
+    (6667, "ircd"),
+    (8000, "http-alt"),
+    (8080, "http-proxy"),
+    (8443, "https-alt"),
+    (8883, "secure-mqtt"),
+    (9000, "cslistener"),
+    (9090, "websm"),
+    (9092, "kafka"),
+    (9100, "jetdirect"),
+    (9200, "elasticsearch"),
+    (11211, "memcache"),
+    (15672, "rabbitmq-mgmt"),
+    (25565, "minecraft"),
+    (27017, "mongodb"),
+];
+
+/// Look up the TCP port registered for a service name.
+///
+/// The lookup is case-insensitive. Entries from `/etc/services` (including
+/// their aliases) override the embedded table; names found in neither yield
+/// `None`.
+///
+/// # Examples
+///
+/// ```
+/// use asphyxia::scanner::services::port_for_service;
+///
+/// assert_eq!(port_for_service("ssh"), Some(22));
+/// assert_eq!(port_for_service("HTTPS"), Some(443));
+/// assert_eq!(port_for_service("no-such-service"), None);
+/// ```
+pub fn port_for_service(name: &str) -> Option<u16> {
+    let name = name.to_ascii_lowercase();
+    system_tcp_services().get(&name).copied().or_else(|| {
+        WELL_KNOWN_TCP
+            .iter()
+            .find(|(_, known)| *known == name)
+            .map(|(port, _)| *port)
+    })
+}
+
+/// Look up the registered service name for a port.
+///
+/// `protocol` is the transport (`"tcp"` or `"udp"`, case-insensitive). The
+/// primary name from `/etc/services` wins; TCP ports fall back to the
+/// embedded table, which lists the IANA name before any alias. Unassigned
+/// ports yield `None`.
+///
+/// # Examples
+///
+/// ```
+/// use asphyxia::scanner::services::service_name_for_port;
+///
+/// assert_eq!(service_name_for_port(22, "tcp"), Some("ssh"));
+/// assert_eq!(service_name_for_port(443, "tcp"), Some("https"));
+/// assert_eq!(service_name_for_port(1, "bogus"), None);
+/// ```
+pub fn service_name_for_port(port: u16, protocol: &str) -> Option<&'static str> {
+    let protocol = protocol.to_ascii_lowercase();
+    let system = match protocol.as_str() {
+        "tcp" => system_service_names("tcp"),
+        "udp" => system_service_names("udp"),
+        _ => return None,
+    };
+    system.get(&port).map(String::as_str).or_else(|| {
+        (protocol == "tcp")
+            .then(|| {
+                WELL_KNOWN_TCP
+                    .iter()
+                    .find(|(known, _)| *known == port)
+                    .map(|(_, name)| *name)
+            })
+            .flatten()
+    })
+}
+
+/// TCP entries from `/etc/services`, read once and cached for the process.
+/// A missing or unreadable file simply yields an empty map.
+fn system_tcp_services() -> &'static HashMap<String, u16> {
+    static SYSTEM: OnceLock<HashMap<String, u16>> = OnceLock::new();
+    SYSTEM.get_or_init(|| {
+        fs::read_to_string(SYSTEM_SERVICES)
+            .map(|contents| parse_services(&contents))
+            .unwrap_or_default()
+    })
+}
+
+/// Primary service names from `/etc/services` for one protocol, read once and
+/// cached for the process. Only `"tcp"` and `"udp"` are cached.
+fn system_service_names(protocol: &'static str) -> &'static HashMap<u16, String> {
+    static TCP: OnceLock<HashMap<u16, String>> = OnceLock::new();
+    static UDP: OnceLock<HashMap<u16, String>> = OnceLock::new();
+    let cache = if protocol == "udp" { &UDP } else { &TCP };
+    cache.get_or_init(|| {
+        fs::read_to_string(SYSTEM_SERVICES)
+            .map(|contents| parse_service_names(&contents, protocol))
+            .unwrap_or_default()
+    })
+}
+
+/// Parse the TCP entries of a services(5) database into a name → port map.
+///
+/// Each line is `name port/protocol [aliases...] [# comment]`; the primary
+/// name and every alias map to the port. Malformed lines are skipped.
+fn parse_services(contents: &str) -> HashMap<String, u16> {
+    let mut services = HashMap::new();
+    for line in contents.lines() {
+        let line = line.split('#').next().unwrap_or_default();
+        let mut fields = line.split_whitespace();
+        let (Some(name), Some(port_proto)) = (fields.next(), fields.next()) else {
+            continue;
+        };
+        let Some((port, "tcp")) = port_proto.split_once('/') else {
+            continue;
+        };
+        let Ok(port) = port.parse::<u16>() else {
+            continue;
+        };
+        for alias in std::iter::once(name).chain(fields) {
+            services.entry(alias.to_ascii_lowercase()).or_insert(port);
+        }
+    }
+    services
+}
+
+/// Parse a services(5) database into a port → primary name map for one
+/// protocol. The first entry for a port wins; aliases are ignored.
+fn parse_service_names(contents: &str, protocol: &str) -> HashMap<u16, String> {
+    let mut names = HashMap::new();
+    for line in contents.lines() {
+        let line = line.split('#').next().unwrap_or_default();
+        let mut fields = line.split_whitespace();
+        let (Some(name), Some(port_proto)) = (fields.next(), fields.next()) else {
+            continue;
+        };
+        let Some((port, proto)) = port_proto.split_once('/') else {
+            continue;
+        };
+        let Ok(port) = port.parse::<u16>() else {
+            continue;
+        };
+        if proto == protocol {
+            names.entry(port).or_insert_with(|| name.to_string());
+        }
+    }
+    names
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_parse_services_reads_tcp_entries_and_aliases() {
+        let db = "\
+# comment line
+ssh\t\t22/tcp\t\t\t# SSH Remote Login Protocol
+http\t\t80/tcp\t\twww\t\t# WorldWideWeb HTTP
+ntp\t\t123/udp
+custom\t\t9999/tcp\tmy-alias
+garbage
+";
+        let services = parse_services(db);
+        assert_eq!(services.get("ssh"), Some(&22));
+        assert_eq!(services.get("www"), Some(&80));
+        assert_eq!(services.get("my-alias"), Some(&9999));
+        assert_eq!(services.get("ntp"), None);
+        assert_eq!(services.get("garbage"), None);
+    }
+
+    #[test]
+    fn test_parse_service_names_keeps_primary_name_per_protocol() {
+        let db = "\
+http\t\t80/tcp\t\twww
+http-alt\t80/tcp
+domain\t\t53/udp
+";
+        let tcp = parse_service_names(db, "tcp");
+        assert_eq!(tcp.get(&80).map(String::as_str), Some("http"));
+        assert_eq!(tcp.get(&53), None);
+        let udp = parse_service_names(db, "udp");
+        assert_eq!(udp.get(&53).map(String::as_str), Some("domain"));
+    }
+
+    #[test]
+    fn test_service_name_for_port_prefers_iana_name() {
+        assert_eq!(service_name_for_port(5432, "TCP"), Some("postgresql"));
+        assert_eq!(service_name_for_port(53, "tcp"), Some("domain"));
+    }
+
+    #[test]
+    fn test_embedded_table_covers_common_aliases() {
+        assert_eq!(port_for_service("postgres"), Some(5432));
+        assert_eq!(port_for_service("rdp"), Some(3389));
+    }
+}
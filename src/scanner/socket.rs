@@ -0,0 +1,502 @@
+//! Outgoing TCP connections, tuned for what they are used for.
+//!
+//! Every scanner that opens a TCP connection goes through [`connect`] (or
+//! [`open`] and [`connect_socket`] when the socket must be bound first), so
+//! socket options are set in one place rather than at each call site. The
+//! connect itself is non-blocking with a poll for the timeout on every
+//! platform, as `socket2` does it.
+//!
+//! A [`Purpose::Probe`] only asks whether a port accepts connections and is
+//! closed right away: it lingers for zero seconds, so closing sends a reset
+//! and a large scan leaves no sockets behind in `TIME_WAIT`. A
+//! [`Purpose::Exchange`] trades a few small messages (a banner, a `GET /`, a
+//! TLS handshake): Nagle's algorithm is disabled so each goes out at once,
+//! and it closes normally so nothing still in flight is lost. Where the
+//! platform honours `IP_TOS`, IPv4 packets ask for low delay, unless the
+//! scan marks its probes with a [`Dscp`] of its own (see [`mark`]). A
+//! profile may also pin down the [`Origin`] its packets leave from, for
+//! multi-homed hosts whose routing table picks the wrong link.
+//!
+//! A host probed on several ports at once goes through [`first_answer`],
+//! which connects on all of them without blocking and polls the sockets
+//! together, so the probe costs no thread of its own.
+//!
+//! Connections come back as a [`Connection`], which counts itself towards
+//! the scan's open sockets and the bytes it carries, in total and for its
+//! peer (see [`usage`](super::usage)). Each connection attempt counts as a
+//! probe of the peer, answered if the peer accepts or refuses it.
+
+use std::io::{self, Read, Write};
+use std::net::{IpAddr, Ipv4Addr, Ipv6Addr, SocketAddr, TcpStream};
+use std::num::NonZeroU32;
+use std::ops::{Deref, DerefMut};
+use std::time::{Duration, Instant};
+
+use socket2::{Domain, Protocol, Socket, Type};
+
+use crate::scanner::address::host_answered;
+use crate::scanner::options::Dscp;
+use crate::scanner::usage::{self, OpenSocket};
+
+/// `IPTOS_LOWDELAY`, the type-of-service bits asking for low delay.
+#[cfg(not(windows))]
+const TOS: Option<u32> = Some(0x10);
+/// Windows ignores `IP_TOS` unless told otherwise by policy, so it is not
+/// set there.
+#[cfg(windows)]
+const TOS: Option<u32> = None;
+
+/// What a connection is used for, which decides how it is tuned.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub(crate) enum Purpose {
+    /// Connect and close: only whether the port accepted matters.
+    Probe,
+    /// Connect and trade a few small messages.
+    Exchange,
+}
+
+/// The socket options a [`Purpose`] calls for on this platform.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub(crate) struct SocketProfile {
+    /// `TCP_NODELAY`: send small writes without waiting to coalesce them.
+    pub(crate) nodelay: bool,
+    /// `SO_LINGER`: `Some(ZERO)` resets the connection on close.
+    pub(crate) linger: Option<Duration>,
+    /// `IP_TOS` for IPv4 connections.
+    pub(crate) tos: Option<u32>,
+    /// Local address and interface to connect from.
+    pub(crate) origin: Origin,
+}
+
+/// Where outgoing packets leave from: a local address and an interface,
+/// each left to the routing table when `None`.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub(crate) struct Origin {
+    /// Local address to send from; it must belong to the scanning host.
+    pub(crate) ip: Option<IpAddr>,
+    /// Index of the interface to send through, whatever route the
+    /// destination would otherwise take.
+    pub(crate) interface: Option<NonZeroU32>,
+}
+
+impl Origin {
+    /// Whether the address or the interface is pinned down.
+    pub(crate) fn is_bound(self) -> bool {
+        self.ip.is_some() || self.interface.is_some()
+    }
+
+    /// Bind `socket`, which reaches `destination`, to this origin, and to
+    /// `port` if one is given.
+    pub(crate) fn bind(
+        self,
+        socket: &Socket,
+        destination: IpAddr,
+        port: Option<u16>,
+    ) -> io::Result<()> {
+        if let Some(interface) = self.interface {
+            bind_interface(socket, destination, interface)?;
+        }
+        if self.ip.is_some() || port.is_some() {
+            let local = self.ip.unwrap_or(match destination {
+                IpAddr::V4(_) => IpAddr::V4(Ipv4Addr::UNSPECIFIED),
+                IpAddr::V6(_) => IpAddr::V6(Ipv6Addr::UNSPECIFIED),
+            });
+            socket.bind(&SocketAddr::new(local, port.unwrap_or(0)).into())?;
+        }
+        Ok(())
+    }
+}
+
+/// Send everything `socket` sends through the interface numbered `index`.
+#[cfg(any(
+    target_os = "linux",
+    target_os = "android",
+    target_vendor = "apple",
+    target_os = "illumos",
+    target_os = "solaris"
+))]
+fn bind_interface(socket: &Socket, destination: IpAddr, index: NonZeroU32) -> io::Result<()> {
+    match destination {
+        IpAddr::V4(_) => socket.bind_device_by_index_v4(Some(index)),
+        IpAddr::V6(_) => socket.bind_device_by_index_v6(Some(index)),
+    }
+}
+
+/// Binding to an interface is not available on this platform.
+#[cfg(not(any(
+    target_os = "linux",
+    target_os = "android",
+    target_vendor = "apple",
+    target_os = "illumos",
+    target_os = "solaris"
+)))]
+fn bind_interface(_socket: &Socket, _destination: IpAddr, _index: NonZeroU32) -> io::Result<()> {
+    Err(io::Error::new(
+        io::ErrorKind::Unsupported,
+        "binding to an interface is not supported on this platform",
+    ))
+}
+
+impl Purpose {
+    /// The socket options for this purpose on this platform.
+    pub(crate) fn profile(self) -> SocketProfile {
+        SocketProfile {
+            nodelay: self == Purpose::Exchange,
+            linger: (self == Purpose::Probe).then_some(Duration::ZERO),
+            tos: TOS,
+            origin: Origin::default(),
+        }
+    }
+}
+
+impl SocketProfile {
+    /// This profile with `IP_TOS` set from `dscp`, when one is given.
+    pub(crate) fn marked(self, dscp: Option<Dscp>) -> SocketProfile {
+        SocketProfile {
+            tos: dscp.map(Dscp::tos).or(self.tos),
+            ..self
+        }
+    }
+
+    /// This profile connecting from `origin`.
+    pub(crate) fn bound(self, origin: Origin) -> SocketProfile {
+        SocketProfile { origin, ..self }
+    }
+}
+
+/// A connected TCP stream whose traffic is counted in the scan's resource
+/// usage. It dereferences to the [`TcpStream`] for everything else.
+#[derive(Debug)]
+pub(crate) struct Connection {
+    stream: TcpStream,
+    peer: IpAddr,
+    _open: OpenSocket,
+}
+
+impl Deref for Connection {
+    type Target = TcpStream;
+
+    fn deref(&self) -> &TcpStream {
+        &self.stream
+    }
+}
+
+impl DerefMut for Connection {
+    fn deref_mut(&mut self) -> &mut TcpStream {
+        &mut self.stream
+    }
+}
+
+impl Read for Connection {
+    fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+        let n = self.stream.read(buf)?;
+        usage::received(n);
+        usage::exchanged(self.peer, 0, n);
+        Ok(n)
+    }
+}
+
+impl Write for Connection {
+    fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+        let n = self.stream.write(buf)?;
+        usage::sent(n);
+        usage::exchanged(self.peer, n, 0);
+        Ok(n)
+    }
+
+    fn flush(&mut self) -> io::Result<()> {
+        self.stream.flush()
+    }
+}
+
+impl Read for &Connection {
+    fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+        let n = (&self.stream).read(buf)?;
+        usage::received(n);
+        usage::exchanged(self.peer, 0, n);
+        Ok(n)
+    }
+}
+
+impl Write for &Connection {
+    fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+        let n = (&self.stream).write(buf)?;
+        usage::sent(n);
+        usage::exchanged(self.peer, n, 0);
+        Ok(n)
+    }
+
+    fn flush(&mut self) -> io::Result<()> {
+        (&self.stream).flush()
+    }
+}
+
+/// A TCP socket for reaching `destination`, tuned as `profile` says, bound
+/// to its origin and to `source_port` if one is given.
+///
+/// Sockets bound to a source port set `SO_REUSEADDR`, since concurrent
+/// probes share the port, each towards another destination.
+pub(crate) fn open(
+    destination: SocketAddr,
+    profile: SocketProfile,
+    source_port: Option<u16>,
+) -> io::Result<Socket> {
+    let socket = Socket::new(
+        Domain::for_address(destination),
+        Type::STREAM,
+        Some(Protocol::TCP),
+    )?;
+    // Tuning is best effort: a connection works without it.
+    let _ = socket.set_tcp_nodelay(profile.nodelay);
+    let _ = socket.set_linger(profile.linger);
+    if let (Some(tos), SocketAddr::V4(_)) = (profile.tos, destination) {
+        let _ = socket.set_tos_v4(tos);
+    }
+    if source_port.is_some() {
+        socket.set_reuse_address(true)?;
+    }
+    profile
+        .origin
+        .bind(&socket, destination.ip(), source_port)?;
+    Ok(socket)
+}
+
+/// Connect an [`open`]ed `socket` to `destination` within `timeout`.
+pub(crate) fn connect_socket(
+    socket: Socket,
+    destination: SocketAddr,
+    timeout: Duration,
+) -> io::Result<Connection> {
+    // The socket counts as open while it connects, failed attempts too.
+    let open = OpenSocket::new();
+    let peer = destination.ip();
+    usage::probed(peer, 0);
+    if let Err(e) = socket.connect_timeout(&destination.into(), timeout) {
+        if host_answered(&e) {
+            usage::answered(peer, 0);
+        }
+        return Err(e);
+    }
+    usage::answered(peer, 0);
+    Ok(Connection {
+        stream: TcpStream::from(socket),
+        peer,
+        _open: open,
+    })
+}
+
+/// Connect to `destination` within `timeout` over a socket tuned as
+/// `profile` says; the replacement for [`TcpStream::connect_timeout`].
+pub(crate) fn connect(
+    destination: SocketAddr,
+    profile: SocketProfile,
+    timeout: Duration,
+) -> io::Result<Connection> {
+    connect_socket(open(destination, profile, None)?, destination, timeout)
+}
+
+/// Connect to all of `destinations` at once over sockets tuned as `profile`
+/// says, and return the round-trip time of the first attempt answered
+/// (accepted or refused, see [`host_answered`]) within `timeout`.
+///
+/// The attempts share the calling thread: the sockets connect without
+/// blocking and are polled together. Where `poll` is not available they are
+/// made one after another instead.
+pub(crate) fn first_answer(
+    destinations: &[SocketAddr],
+    profile: SocketProfile,
+    timeout: Duration,
+) -> Option<Duration> {
+    #[cfg(unix)]
+    {
+        poll_first_answer(destinations, profile, timeout)
+    }
+    #[cfg(not(unix))]
+    {
+        let start = Instant::now();
+        destinations.iter().find_map(
+            |&destination| match connect(destination, profile, timeout) {
+                Ok(_) => Some(start.elapsed()),
+                Err(e) if host_answered(&e) => Some(start.elapsed()),
+                Err(_) => None,
+            },
+        )
+    }
+}
+
+/// [`first_answer`] with non-blocking connects and `poll`.
+#[cfg(unix)]
+fn poll_first_answer(
+    destinations: &[SocketAddr],
+    profile: SocketProfile,
+    timeout: Duration,
+) -> Option<Duration> {
+    use std::os::fd::AsRawFd;
+
+    let start = Instant::now();
+    let mut pending: Vec<(Socket, SocketAddr, OpenSocket)> = Vec::new();
+    for &destination in destinations {
+        let Ok(socket) = open(destination, profile, None) else {
+            continue;
+        };
+        if socket.set_nonblocking(true).is_err() {
+            continue;
+        }
+        let open = OpenSocket::new();
+        usage::probed(destination.ip(), 0);
+        match socket.connect(&destination.into()) {
+            Ok(()) => {
+                usage::answered(destination.ip(), 0);
+                return Some(start.elapsed());
+            }
+            Err(e) if e.raw_os_error() == Some(libc::EINPROGRESS) => {
+                pending.push((socket, destination, open));
+            }
+            Err(e) if host_answered(&e) => {
+                usage::answered(destination.ip(), 0);
+                return Some(start.elapsed());
+            }
+            Err(_) => {}
+        }
+    }
+
+    while !pending.is_empty() {
+        let remaining = timeout.saturating_sub(start.elapsed());
+        if remaining.is_zero() {
+            return None;
+        }
+        let mut fds: Vec<libc::pollfd> = pending
+            .iter()
+            .map(|(socket, _, _)| libc::pollfd {
+                fd: socket.as_raw_fd(),
+                events: libc::POLLOUT,
+                revents: 0,
+            })
+            .collect();
+        // Round up, so a sub-millisecond remainder still waits.
+        let wait = remaining.as_micros().div_ceil(1000).min(i32::MAX as u128) as i32;
+        // SAFETY: `fds` is a live array of `fds.len()` pollfd structs, each
+        // naming a socket `pending` keeps open for the duration of the call.
+        let ready = unsafe { libc::poll(fds.as_mut_ptr(), fds.len() as libc::nfds_t, wait) };
+        if ready < 0 {
+            if io::Error::last_os_error().kind() == io::ErrorKind::Interrupted {
+                continue;
+            }
+            return None;
+        }
+        // Walk backwards so removing a failed attempt keeps the indices of
+        // the ones still to check.
+        for (index, fd) in fds.iter().enumerate().rev() {
+            if fd.revents == 0 {
+                continue;
+            }
+            let (socket, destination, _) = &pending[index];
+            match socket.take_error() {
+                Ok(None) => {
+                    usage::answered(destination.ip(), 0);
+                    return Some(start.elapsed());
+                }
+                Ok(Some(e)) if host_answered(&e) => {
+                    usage::answered(destination.ip(), 0);
+                    return Some(start.elapsed());
+                }
+                _ => {
+                    pending.swap_remove(index);
+                }
+            }
+        }
+    }
+    None
+}
+
+/// Mark the packets a raw `socket` sends to `destination` with `dscp`, if
+/// given. Only IPv4 is marked; failing to mark is not an error, the probe
+/// just goes out unmarked.
+pub(crate) fn mark(socket: &Socket, destination: IpAddr, dscp: Option<Dscp>) {
+    if let (Some(dscp), IpAddr::V4(_)) = (dscp, destination) {
+        let _ = socket.set_tos_v4(dscp.tos());
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use socket2::SockRef;
+    use std::net::TcpListener;
+
+    #[test]
+    fn test_probe_resets_on_close() {
+        let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+        let addr = listener.local_addr().unwrap();
+        let stream = connect(addr, Purpose::Probe.profile(), Duration::from_secs(1)).unwrap();
+        let socket = SockRef::from(&*stream);
+        assert_eq!(socket.linger().unwrap(), Some(Duration::ZERO));
+        assert!(!socket.tcp_nodelay().unwrap());
+    }
+
+    #[test]
+    fn test_exchange_disables_nagle() {
+        let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+        let addr = listener.local_addr().unwrap();
+        let stream = connect(addr, Purpose::Exchange.profile(), Duration::from_secs(1)).unwrap();
+        let socket = SockRef::from(&*stream);
+        assert!(socket.tcp_nodelay().unwrap());
+        assert_eq!(socket.linger().unwrap(), None);
+    }
+
+    #[test]
+    fn test_connect_reports_refusal() {
+        let port = TcpListener::bind("127.0.0.1:0")
+            .unwrap()
+            .local_addr()
+            .unwrap()
+            .port();
+        let addr = SocketAddr::from(([127, 0, 0, 1], port));
+        let err = connect(addr, Purpose::Probe.profile(), Duration::from_secs(1)).unwrap_err();
+        assert_eq!(err.kind(), io::ErrorKind::ConnectionRefused);
+    }
+
+    // Linux answers on all of 127.0.0.0/8; other systems only on 127.0.0.1.
+    #[cfg(target_os = "linux")]
+    #[test]
+    fn test_origin_binds_the_local_address() {
+        let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+        let addr = listener.local_addr().unwrap();
+        let origin = Origin {
+            ip: Some(IpAddr::V4(Ipv4Addr::new(127, 0, 0, 2))),
+            interface: None,
+        };
+        let profile = Purpose::Probe.profile().bound(origin);
+        let stream = connect(addr, profile, Duration::from_secs(1)).unwrap();
+        assert_eq!(stream.local_addr().unwrap().ip(), origin.ip.unwrap());
+    }
+
+    #[test]
+    fn test_first_answer_takes_any_answering_port() {
+        let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+        let open = listener.local_addr().unwrap();
+        let closed = || {
+            TcpListener::bind("127.0.0.1:0")
+                .unwrap()
+                .local_addr()
+                .unwrap()
+        };
+        let profile = Purpose::Probe.profile();
+        let timeout = Duration::from_secs(1);
+        assert!(first_answer(&[closed(), open], profile, timeout).is_some());
+        // Refusals prove the host is there too.
+        assert!(first_answer(&[closed(), closed()], profile, timeout).is_some());
+        assert!(first_answer(&[], profile, timeout).is_none());
+    }
+
+    #[test]
+    fn test_dscp_overrides_default_tos() {
+        let marked = Purpose::Probe.profile().marked(Some(Dscp::CS1));
+        assert_eq!(marked.tos, Some(0x20));
+        assert_eq!(marked.linger, Some(Duration::ZERO));
+        assert_eq!(
+            Purpose::Probe.profile().marked(None),
+            Purpose::Probe.profile()
+        );
+    }
+}
@@ -0,0 +1,121 @@
+//! Results of a scan running in the background, yielded as they arrive.
+//!
+//! The parallel scans hand every result to a callback as soon as it is
+//! known. The `*_with_options` functions collect them into a sorted `Vec`;
+//! the `*_iter` functions (e.g.
+//! [`scan_subnet_iter`](crate::scanner::address::scan_subnet_iter)) run the
+//! scan on a thread of its own and pass them on through a [`ScanStream`],
+//! so a caller can act on the first results of a long scan right away.
+
+use std::sync::{Arc, Mutex, mpsc};
+use std::thread;
+
+use crate::scanner::options::ScanOptions;
+use crate::scanner::progress::ProgressHandle;
+
+/// Receives each result of a scan as soon as it is known; returns `false`
+/// to stop the scan (e.g. because nobody is listening any more).
+pub(crate) type Found<'a, T> = &'a (dyn Fn(T) -> bool + Sync);
+
+/// Results of a scan running in the background, in the order they arrive.
+///
+/// Iteration blocks until the next result is found and ends when the scan
+/// completes (or is cancelled through
+/// [`ScanOptions::cancel`](crate::scanner::options::ScanOptions::cancel)).
+/// Dropping the stream stops the scan after the probes in flight.
+#[derive(Debug)]
+pub struct ScanStream<T> {
+    receiver: mpsc::Receiver<T>,
+    progress: ProgressHandle,
+}
+
+impl<T> ScanStream<T> {
+    /// A handle to poll how far the scan has got, e.g. from a UI thread
+    /// while another one drains the stream.
+    ///
+    /// Updates still reach the handler set in
+    /// [`ScanOptions::progress`](crate::scanner::options::ScanOptions::progress);
+    /// the snapshot reports `finished` once the scan has stopped.
+    ///
+    /// # Examples
+    ///
+    /// ```no_run
+    /// use asphyxia::{ScanOptions, scan_subnet_iter};
+    ///
+    /// let hosts = scan_subnet_iter("10.0.0.0/16".parse().unwrap(), ScanOptions::default());
+    /// let progress = hosts.progress();
+    /// for host in hosts {
+    ///     let now = progress.snapshot();
+    ///     println!("{} is up ({}/{} probed)", host.ip, now.done, now.total);
+    /// }
+    /// ```
+    pub fn progress(&self) -> ProgressHandle {
+        self.progress.clone()
+    }
+}
+
+impl<T> Iterator for ScanStream<T> {
+    type Item = T;
+
+    fn next(&mut self) -> Option<T> {
+        self.receiver.recv().ok()
+    }
+}
+
+/// Run `scan` with `options` on a background thread, streaming what it
+/// finds; its progress is tracked by the stream's [`ProgressHandle`].
+pub(crate) fn spawn<T, F>(mut options: ScanOptions, scan: F) -> ScanStream<T>
+where
+    T: Send + 'static,
+    F: FnOnce(&ScanOptions, Found<T>) + Send + 'static,
+{
+    let (sender, receiver) = mpsc::channel();
+    let progress = ProgressHandle::wrapping(options.progress.take());
+    options.progress = Some(Arc::new(progress.clone()));
+    let tracked = progress.clone();
+    thread::spawn(move || {
+        scan(&options, &|item| sender.send(item).is_ok());
+        tracked.close();
+    });
+    ScanStream { receiver, progress }
+}
+
+/// Run `scan` and return everything it found, in arrival order.
+pub(crate) fn collect<T: Send>(scan: impl FnOnce(Found<T>)) -> Vec<T> {
+    let found = Mutex::new(Vec::new());
+    scan(&|item| {
+        found.lock().unwrap_or_else(|e| e.into_inner()).push(item);
+        true
+    });
+    found.into_inner().unwrap_or_else(|e| e.into_inner())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_stream_yields_everything_sent() {
+        let stream = spawn(ScanOptions::default(), |_, found| {
+            for n in 0..3 {
+                found(n);
+            }
+        });
+        assert_eq!(stream.collect::<Vec<_>>(), vec![0, 1, 2]);
+    }
+
+    #[test]
+    fn test_found_reports_a_dropped_stream() {
+        let (done, finished) = mpsc::channel();
+        let mut stream = spawn(ScanOptions::default(), move |_, found| {
+            let mut sent = 0;
+            while found(sent) {
+                sent += 1;
+            }
+            done.send(sent).unwrap();
+        });
+        assert_eq!(stream.next(), Some(0));
+        drop(stream);
+        assert!(finished.recv().unwrap() >= 1);
+    }
+}
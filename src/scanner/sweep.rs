@@ -0,0 +1,207 @@
+//! Combined host discovery and port scanning.
+//!
+//! A sweep first finds the live hosts on a subnet, exactly like an address
+//! scan, and then port-scans each of them, so a single call answers "what is
+//! running on this network" without feeding address-scan results back into a
+//! port scan by hand.
+
+use ipnetwork::IpNetwork;
+use rayon::prelude::*;
+use std::net::IpAddr;
+use std::time::Duration;
+
+use crate::scanner::address::{self, HostScanResult};
+use crate::scanner::options::ScanOptions;
+use crate::scanner::port::{self, PortScanResult};
+
+/// A live host found by a sweep, together with its open ports.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct HostReport {
+    /// The discovery result for the host (always up).
+    pub host: HostScanResult,
+    /// The open ports found on the host, sorted by port.
+    pub open_ports: Vec<PortScanResult>,
+}
+
+/// Discover the live hosts on a subnet and port-scan each of them.
+///
+/// # Arguments
+///
+/// * `subnet` - The subnet to sweep in CIDR notation
+/// * `ports` - The TCP ports to scan on every live host
+/// * `timeout` - Optional per-probe timeout (defaults to [`crate::scanner::port::CONNECT_TIMEOUT`])
+///
+/// # Returns
+///
+/// * `Vec<HostReport>` - One report per live host, sorted by address
+///
+/// # Examples
+///
+/// ```no_run
+/// use asphyxia::scanner::sweep::scan_network;
+///
+/// let subnet = "192.168.1.0/24".parse().unwrap();
+/// for report in scan_network(subnet, &[22, 80, 443], None) {
+///     println!("{}: {} open ports", report.host.ip, report.open_ports.len());
+/// }
+/// ```
+pub fn scan_network(
+    subnet: IpNetwork,
+    ports: &[u16],
+    timeout: Option<Duration>,
+) -> Vec<HostReport> {
+    scan_network_with_options(
+        subnet,
+        ports,
+        &ScanOptions {
+            timeout,
+            ..ScanOptions::default()
+        },
+    )
+}
+
+/// Sweep a subnet using the given [`ScanOptions`].
+///
+/// Hosts are discovered with
+/// [`scan_subnet_with_options`](crate::scanner::address::scan_subnet_with_options);
+/// the options' timeout, rate limit, retries and cancel token also apply to
+/// the port scan, which skips `options.exclude_ports`.
+pub fn scan_network_with_options(
+    subnet: IpNetwork,
+    ports: &[u16],
+    options: &ScanOptions,
+) -> Vec<HostReport> {
+    let hosts = address::scan_subnet_with_options(subnet, options);
+    scan_hosts(hosts, ports, options)
+}
+
+/// Port-scan every discovered host and group the open ports per host.
+fn scan_hosts(hosts: Vec<HostScanResult>, ports: &[u16], options: &ScanOptions) -> Vec<HostReport> {
+    let ports = options.included_ports(ports);
+    let ports = ports.as_slice();
+    let total = (hosts.len() * ports.len()) as u64;
+    let progress = options.progress();
+    progress.start(total, "ports scanned");
+    let rates = options.rate_classes();
+
+    // Walk every host and port pair: host by host, or interleaved across
+    // the whole subnet when the scan is shuffled.
+    let order = options.order(u128::from(total.saturating_sub(1)));
+    let pair = |step: u64| {
+        let index = order.at(u128::from(step)) as usize;
+        (hosts[index / ports.len()].ip, ports[index % ports.len()])
+    };
+    let open: Vec<(IpAddr, PortScanResult)> = options.install(|| {
+        (0..total)
+            .into_par_iter()
+            .map(pair)
+            .filter_map(|(ip, port)| {
+                if options.is_cancelled() {
+                    return None;
+                }
+                if !options.allows_port(ip, port) {
+                    progress.inc(1);
+                    return None;
+                }
+                let result = port::probe_port(&ip.to_string(), port, options, rates.classify(ip));
+                progress.inc(1);
+                result.ok().filter(|r| r.is_open()).map(|r| (ip, r))
+            })
+            .collect()
+    });
+
+    progress.finish("Sweep completed");
+
+    let mut reports: Vec<HostReport> = hosts
+        .into_iter()
+        .map(|host| HostReport {
+            host,
+            open_ports: Vec::new(),
+        })
+        .collect();
+    for (ip, result) in open {
+        if let Some(report) = reports.iter_mut().find(|r| r.host.ip == ip) {
+            report.open_ports.push(result);
+        }
+    }
+    for report in &mut reports {
+        report.open_ports.sort_by_key(|r| r.port);
+    }
+    reports
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::scanner::address::HostState;
+    use std::net::TcpListener;
+
+    #[test]
+    fn test_scan_hosts_groups_open_ports_per_host() {
+        let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+        let open_port = listener.local_addr().unwrap().port();
+        let host = HostScanResult {
+            ip: "127.0.0.1".parse().unwrap(),
+            state: HostState::Up,
+            rtt: Duration::ZERO,
+            hostname: None,
+            mac: None,
+            ttl: None,
+        };
+
+        let reports = scan_hosts(
+            vec![host.clone()],
+            &[1, open_port],
+            &ScanOptions {
+                timeout: Some(Duration::from_millis(100)),
+                ..ScanOptions::default()
+            },
+        );
+
+        assert_eq!(reports.len(), 1);
+        assert_eq!(reports[0].host, host);
+        let ports: Vec<u16> = reports[0].open_ports.iter().map(|r| r.port).collect();
+        assert_eq!(ports, vec![open_port]);
+    }
+
+    #[test]
+    fn test_scan_hosts_shuffled_keeps_ports_with_their_host() {
+        let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+        let open_port = listener.local_addr().unwrap().port();
+        let host = |ip: &str| HostScanResult {
+            ip: ip.parse().unwrap(),
+            state: HostState::Up,
+            rtt: Duration::ZERO,
+            hostname: None,
+            mac: None,
+            ttl: None,
+        };
+
+        let reports = scan_hosts(
+            vec![host("127.0.0.1"), host("127.0.0.2")],
+            &[1, 2, 3, open_port],
+            &ScanOptions {
+                timeout: Some(Duration::from_millis(100)),
+                shuffle_seed: Some(7),
+                ..ScanOptions::default()
+            },
+        );
+
+        let ports: Vec<u16> = reports[0].open_ports.iter().map(|r| r.port).collect();
+        assert_eq!(ports, vec![open_port]);
+        assert!(reports[1].open_ports.is_empty());
+    }
+
+    #[test]
+    fn test_scan_network_loopback() {
+        let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+        let open_port = listener.local_addr().unwrap().port();
+        let subnet = "127.0.0.1/32".parse().unwrap();
+
+        let reports = scan_network(subnet, &[open_port], Some(Duration::from_millis(100)));
+
+        // Loopback refuses the discovery probe on port 80, which counts as up.
+        assert_eq!(reports.len(), 1);
+        assert_eq!(reports[0].open_ports[0].port, open_port);
+    }
+}
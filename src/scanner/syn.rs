@@ -0,0 +1,445 @@
+//! TCP SYN ("half-open") port probes.
+//!
+//! A connect probe completes the three-way handshake, so every open port
+//! costs a full connection that the target may log. A SYN probe sends only
+//! the first packet and reads the answer: a SYN-ACK means open, a RST means
+//! closed. The kernel, which knows nothing of the half-open connection,
+//! answers the SYN-ACK with a RST, so the handshake never completes.
+//!
+//! Every probe can be joined by decoys: the same SYN, sent to the same port
+//! with another (spoofed) IPv4 source address, so the target's logs and IDS
+//! see the scan come from several hosts at once and cannot tell which one
+//! is real. Decoys get the answers, not us; pick addresses that are up, or
+//! the target may be flooded with SYNs it cannot complete.
+//!
+//! Probes follow the routing table unless the scan pins down the local
+//! address or interface they leave from (see [`Origin`]); decoys leave
+//! through the same interface.
+//!
+//! Crafting TCP segments requires a raw socket, which is a privileged
+//! operation: run as root (or, on Linux, grant the binary `CAP_NET_RAW`).
+//! Use [`syn_available`] to check up front.
+
+use socket2::{Domain, Protocol, SockAddr, Socket, Type};
+use std::io::{self, Read};
+use std::net::{IpAddr, Ipv4Addr, SocketAddr};
+use std::sync::atomic::{AtomicU16, AtomicU32, Ordering};
+use std::time::{Duration, Instant, SystemTime, UNIX_EPOCH};
+
+use crate::scanner::fingerprint::StackFingerprint;
+use crate::scanner::icmp::{checksum, strip_ipv4_header};
+use crate::scanner::options::Dscp;
+use crate::scanner::port::{Evidence, PortScanResult};
+use crate::scanner::socket::{self, Origin};
+use crate::scanner::ttl::ipv4_ttl;
+use crate::scanner::usage::{self, OpenSocket};
+
+/// TCP header flags used by the probe.
+pub(crate) const TCP_SYN: u8 = 0x02;
+pub(crate) const TCP_RST: u8 = 0x04;
+pub(crate) const TCP_ACK: u8 = 0x10;
+
+/// Length of the option-less TCP header sent by the probe.
+pub(crate) const TCP_HEADER_LEN: usize = 20;
+
+/// Length of the option-less IPv4 header of a decoy probe.
+const IPV4_HEADER_LEN: usize = 20;
+
+/// TTL of decoy probes, a common OS default.
+const DECOY_TTL: u8 = 64;
+
+/// First source port used by probes. Linux hands out ephemeral ports below
+/// this, so answers to our probes do not reach a real socket.
+const FIRST_SOURCE_PORT: u16 = 61000;
+
+/// Source of per-probe source ports, so concurrent probes (each of which
+/// sees every TCP segment delivered to the host) only accept their own
+/// answers.
+static NEXT_SOURCE_PORT: AtomicU16 = AtomicU16::new(0);
+
+/// Source of per-probe sequence numbers.
+static NEXT_SEQUENCE: AtomicU32 = AtomicU32::new(0);
+
+/// Whether this process may open the raw sockets SYN probes need.
+///
+/// # Examples
+///
+/// ```
+/// use asphyxia::scanner::syn::syn_available;
+///
+/// if !syn_available() {
+///     eprintln!("SYN scans need root or CAP_NET_RAW");
+/// }
+/// ```
+pub fn syn_available() -> bool {
+    Socket::new(Domain::IPV4, Type::RAW, Some(Protocol::TCP)).is_ok()
+}
+
+/// Send one SYN to `port` on `ip` and wait up to `timeout` for the answer.
+///
+/// # Arguments
+///
+/// * `ip` - The address to probe (IPv4 or IPv6)
+/// * `port` - The TCP port to probe
+/// * `timeout` - How long to wait for a SYN-ACK or RST
+///
+/// # Returns
+///
+/// * `io::Result<PortScanResult>` - [`Evidence::SynAck`] (open) on a SYN-ACK,
+///   [`Evidence::Reset`] (closed) on a RST, [`Evidence::Timeout`] (filtered) if nothing
+///   answered in time, with the answer's TTL for IPv4 targets; an error if the probe could not be sent (most often a
+///   lack of raw-socket privileges)
+///
+/// # Examples
+///
+/// ```no_run
+/// use asphyxia::scanner::syn::probe;
+/// use std::time::Duration;
+///
+/// let result = probe("192.168.1.10".parse().unwrap(), 22, Duration::from_secs(1)).unwrap();
+/// println!("ssh is {}", result.state.as_str());
+/// ```
+pub fn probe(ip: IpAddr, port: u16, timeout: Duration) -> io::Result<PortScanResult> {
+    probe_from(ip, port, None, Origin::default(), None, &[], timeout)
+}
+
+/// Like [`probe`], but sent from `source_port` when one is given rather
+/// than from a fresh port of our own range, from `origin`, marked with
+/// `dscp`, and preceded by the same SYN from each of `decoys`.
+pub(crate) fn probe_from(
+    ip: IpAddr,
+    port: u16,
+    source_port: Option<u16>,
+    origin: Origin,
+    dscp: Option<Dscp>,
+    decoys: &[IpAddr],
+    timeout: Duration,
+) -> io::Result<PortScanResult> {
+    let domain = match ip {
+        IpAddr::V4(_) => Domain::IPV4,
+        IpAddr::V6(_) => Domain::IPV6,
+    };
+    let source = source_address(ip, port, origin)?;
+    let source_port = source_port.unwrap_or_else(|| {
+        FIRST_SOURCE_PORT
+            + NEXT_SOURCE_PORT.fetch_add(1, Ordering::Relaxed) % (u16::MAX - FIRST_SOURCE_PORT + 1)
+    });
+    let sequence = initial_sequence();
+
+    let socket = Socket::new(domain, Type::RAW, Some(Protocol::TCP))?;
+    let _open = OpenSocket::new();
+    socket::mark(&socket, ip, dscp);
+    origin.bind(&socket, ip, None)?;
+    let segment = syn_segment(source, ip, source_port, port, sequence);
+    send_decoys(ip, port, source_port, origin, decoys, dscp)?;
+
+    let start = Instant::now();
+    let sent = socket.send_to(&segment, &SockAddr::from(SocketAddr::new(ip, 0)))?;
+    usage::sent(sent);
+    usage::probed(ip, sent);
+
+    let mut buf = [0u8; 1500];
+    let (evidence, ttl, fingerprint) = loop {
+        let remaining = timeout.saturating_sub(start.elapsed());
+        if remaining.is_zero() {
+            break (Evidence::Timeout, None, None);
+        }
+        socket.set_read_timeout(Some(remaining))?;
+        let from = match socket.peek_sender() {
+            Ok(from) => from.as_socket().map(|addr| addr.ip()),
+            Err(e)
+                if matches!(
+                    e.kind(),
+                    io::ErrorKind::WouldBlock | io::ErrorKind::TimedOut
+                ) =>
+            {
+                break (Evidence::Timeout, None, None);
+            }
+            Err(e) => return Err(e),
+        };
+        let len = (&socket).read(&mut buf)?;
+        usage::received(len);
+        // IPv4 raw sockets deliver the IP header too; IPv6 ones do not.
+        let segment = if ip.is_ipv4() {
+            strip_ipv4_header(&buf[..len])
+        } else {
+            Some(&buf[..len])
+        };
+        if from == Some(ip)
+            && let Some(evidence) = segment.and_then(|s| classify(s, port, source_port, sequence))
+        {
+            usage::answered(ip, len);
+            let fingerprint = (ip.is_ipv4() && evidence == Evidence::SynAck)
+                .then(|| StackFingerprint::from_ipv4_syn_ack(&buf[..len]))
+                .flatten();
+            break (evidence, ipv4_ttl(&buf[..len]), fingerprint);
+        }
+    };
+    Ok(PortScanResult {
+        port,
+        state: evidence.state(),
+        evidence,
+        attempts: 1,
+        latency: start.elapsed(),
+        ttl,
+        fingerprint,
+    })
+}
+
+/// Send the SYN to `port` on `ip` from each of the IPv4 `decoys`, with the
+/// decoy as the source address, through the interface of `origin` if it
+/// has one. Nothing is sent to an IPv6 target, nor from an IPv6 decoy.
+fn send_decoys(
+    ip: IpAddr,
+    port: u16,
+    source_port: u16,
+    origin: Origin,
+    decoys: &[IpAddr],
+    dscp: Option<Dscp>,
+) -> io::Result<()> {
+    let IpAddr::V4(target) = ip else {
+        return Ok(());
+    };
+    let decoys: Vec<Ipv4Addr> = decoys
+        .iter()
+        .filter_map(|decoy| match decoy {
+            IpAddr::V4(decoy) => Some(*decoy),
+            IpAddr::V6(_) => None,
+        })
+        .collect();
+    if decoys.is_empty() {
+        return Ok(());
+    }
+    // The kernel only keeps a source address we write ourselves.
+    let socket = Socket::new(Domain::IPV4, Type::RAW, Some(Protocol::TCP))?;
+    let _open = OpenSocket::new();
+    socket.set_header_included_v4(true)?;
+    Origin { ip: None, ..origin }.bind(&socket, ip, None)?;
+    let destination = SockAddr::from(SocketAddr::new(ip, 0));
+    // A code point is six bits, so its TOS byte always fits.
+    let tos = dscp.map_or(0, |dscp| dscp.tos() as u8);
+    for decoy in decoys {
+        let packet = decoy_packet(decoy, target, source_port, port, tos);
+        let sent = socket.send_to(&packet, &destination)?;
+        usage::sent(sent);
+        usage::probed(ip, sent);
+    }
+    Ok(())
+}
+
+/// A SYN from `decoy:source_port` to `target:port` behind an IPv4 header
+/// carrying the decoy's address and `tos`. The kernel fills in the packet
+/// ID and the header checksum.
+fn decoy_packet(
+    decoy: Ipv4Addr,
+    target: Ipv4Addr,
+    source_port: u16,
+    port: u16,
+    tos: u8,
+) -> Vec<u8> {
+    let segment = syn_segment(
+        decoy.into(),
+        target.into(),
+        source_port,
+        port,
+        initial_sequence(),
+    );
+    let mut packet = vec![0u8; IPV4_HEADER_LEN];
+    packet[0] = 0x45;
+    packet[1] = tos;
+    packet[2..4].copy_from_slice(&((IPV4_HEADER_LEN + segment.len()) as u16).to_be_bytes());
+    packet[8] = DECOY_TTL;
+    packet[9] = 6;
+    packet[12..16].copy_from_slice(&decoy.octets());
+    packet[16..20].copy_from_slice(&target.octets());
+    packet.extend_from_slice(&segment);
+    packet
+}
+
+/// The local address a probe from `origin` reaches `ip` from: the origin's
+/// own, or the one the kernel would pick, found by "connecting" a UDP
+/// socket (which sends nothing). The TCP checksum covers it, so it must be
+/// known before the segment is built.
+fn source_address(ip: IpAddr, port: u16, origin: Origin) -> io::Result<IpAddr> {
+    if let Some(source) = origin.ip {
+        return Ok(source);
+    }
+    let destination = SocketAddr::new(ip, port);
+    let socket = Socket::new(Domain::for_address(destination), Type::DGRAM, None)?;
+    origin.bind(&socket, ip, None)?;
+    socket.connect(&destination.into())?;
+    socket
+        .local_addr()?
+        .as_socket()
+        .map(|local| local.ip())
+        .ok_or_else(|| io::Error::other("the probe socket has no local address"))
+}
+
+/// A hard-to-guess initial sequence number, so stray segments are not
+/// mistaken for answers.
+fn initial_sequence() -> u32 {
+    let nanos = SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .map_or(0, |d| d.subsec_nanos());
+    nanos ^ NEXT_SEQUENCE.fetch_add(0x9e37_79b9, Ordering::Relaxed)
+}
+
+/// Build a SYN segment from `source:source_port` to `destination:port`,
+/// checksum included.
+fn syn_segment(
+    source: IpAddr,
+    destination: IpAddr,
+    source_port: u16,
+    port: u16,
+    sequence: u32,
+) -> Vec<u8> {
+    let mut segment = Vec::with_capacity(TCP_HEADER_LEN);
+    segment.extend_from_slice(&source_port.to_be_bytes());
+    segment.extend_from_slice(&port.to_be_bytes());
+    segment.extend_from_slice(&sequence.to_be_bytes());
+    segment.extend_from_slice(&0u32.to_be_bytes());
+    // Data offset (5 words), then flags.
+    segment.extend_from_slice(&[(TCP_HEADER_LEN as u8 / 4) << 4, TCP_SYN]);
+    segment.extend_from_slice(&1024u16.to_be_bytes());
+    // Checksum and urgent pointer.
+    segment.extend_from_slice(&[0, 0, 0, 0]);
+
+    let mut summed = pseudo_header(source, destination, segment.len());
+    summed.extend_from_slice(&segment);
+    let sum = checksum(&summed);
+    segment[16..18].copy_from_slice(&sum.to_be_bytes());
+    segment
+}
+
+/// The IPv4 or IPv6 pseudo-header the TCP checksum covers.
+fn pseudo_header(source: IpAddr, destination: IpAddr, tcp_len: usize) -> Vec<u8> {
+    let protocol = 6u8;
+    match (source, destination) {
+        (IpAddr::V4(s), IpAddr::V4(d)) => {
+            let mut header = Vec::with_capacity(12);
+            header.extend_from_slice(&s.octets());
+            header.extend_from_slice(&d.octets());
+            header.extend_from_slice(&[0, protocol]);
+            header.extend_from_slice(&(tcp_len as u16).to_be_bytes());
+            header
+        }
+        (s, d) => {
+            let octets = |ip: IpAddr| match ip {
+                IpAddr::V4(v4) => v4.to_ipv6_mapped().octets(),
+                IpAddr::V6(v6) => v6.octets(),
+            };
+            let mut header = Vec::with_capacity(40);
+            header.extend_from_slice(&octets(s));
+            header.extend_from_slice(&octets(d));
+            header.extend_from_slice(&(tcp_len as u32).to_be_bytes());
+            header.extend_from_slice(&[0, 0, 0, protocol]);
+            header
+        }
+    }
+}
+
+/// Classify a TCP `segment` as the answer to our SYN from `source_port` to
+/// `port` with `sequence`, or `None` if it belongs to another connection.
+pub(crate) fn classify(
+    segment: &[u8],
+    port: u16,
+    source_port: u16,
+    sequence: u32,
+) -> Option<Evidence> {
+    if segment.len() < TCP_HEADER_LEN {
+        return None;
+    }
+    let from_port = u16::from_be_bytes([segment[0], segment[1]]);
+    let to_port = u16::from_be_bytes([segment[2], segment[3]]);
+    let ack = u32::from_be_bytes([segment[8], segment[9], segment[10], segment[11]]);
+    let flags = segment[13];
+    if from_port != port || to_port != source_port || flags & TCP_ACK == 0 {
+        return None;
+    }
+    if ack != sequence.wrapping_add(1) {
+        return None;
+    }
+    if flags & TCP_RST != 0 {
+        Some(Evidence::Reset)
+    } else if flags & TCP_SYN != 0 {
+        Some(Evidence::SynAck)
+    } else {
+        None
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::scanner::port::PortState;
+    use std::net::TcpListener;
+
+    /// A reply segment from `port` to `source_port` acknowledging `ack`.
+    fn reply(port: u16, source_port: u16, ack: u32, flags: u8) -> Vec<u8> {
+        let mut segment = vec![0u8; TCP_HEADER_LEN];
+        segment[0..2].copy_from_slice(&port.to_be_bytes());
+        segment[2..4].copy_from_slice(&source_port.to_be_bytes());
+        segment[8..12].copy_from_slice(&ack.to_be_bytes());
+        segment[13] = flags;
+        segment
+    }
+
+    #[test]
+    fn test_syn_segment_checksum_verifies() {
+        let source = "192.168.1.2".parse().unwrap();
+        let destination = "192.168.1.10".parse().unwrap();
+        let segment = syn_segment(source, destination, 61000, 22, 42);
+        let mut summed = pseudo_header(source, destination, segment.len());
+        summed.extend_from_slice(&segment);
+        assert_eq!(checksum(&summed), 0);
+        assert_eq!(segment[13], TCP_SYN);
+    }
+
+    #[test]
+    fn test_decoy_packet_comes_from_the_decoy() {
+        let decoy: Ipv4Addr = "10.0.0.77".parse().unwrap();
+        let target: Ipv4Addr = "10.0.0.5".parse().unwrap();
+        let packet = decoy_packet(decoy, target, 53, 443, 0x20);
+        assert_eq!(packet.len(), IPV4_HEADER_LEN + TCP_HEADER_LEN);
+        assert_eq!((packet[1], packet[9]), (0x20, 6));
+        assert_eq!(packet[12..16], decoy.octets());
+        assert_eq!(packet[16..20], target.octets());
+
+        let segment = strip_ipv4_header(&packet).unwrap();
+        let mut summed = pseudo_header(decoy.into(), target.into(), segment.len());
+        summed.extend_from_slice(segment);
+        assert_eq!(checksum(&summed), 0);
+        assert_eq!(segment[0..4], [0, 53, 1, 187]);
+    }
+
+    #[test]
+    fn test_classify_syn_ack_and_rst() {
+        let open = reply(22, 61000, 43, TCP_SYN | TCP_ACK);
+        assert_eq!(classify(&open, 22, 61000, 42), Some(Evidence::SynAck));
+        let closed = reply(22, 61000, 43, TCP_RST | TCP_ACK);
+        assert_eq!(classify(&closed, 22, 61000, 42), Some(Evidence::Reset));
+    }
+
+    #[test]
+    fn test_classify_ignores_other_connections() {
+        let answer = reply(22, 61000, 43, TCP_SYN | TCP_ACK);
+        assert_eq!(classify(&answer, 23, 61000, 42), None);
+        assert_eq!(classify(&answer, 22, 61001, 42), None);
+        assert_eq!(classify(&answer, 22, 61000, 7), None);
+        assert_eq!(classify(&answer[..10], 22, 61000, 42), None);
+    }
+
+    #[test]
+    fn test_probe_localhost() {
+        if !syn_available() {
+            println!("Skipping test_probe_localhost: raw sockets are not permitted");
+            return;
+        }
+        let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+        let port = listener.local_addr().unwrap().port();
+        let ip = "127.0.0.1".parse().unwrap();
+        let timeout = Duration::from_millis(500);
+        assert_eq!(probe(ip, port, timeout).unwrap().state, PortState::Open);
+        assert_eq!(probe(ip, 1, timeout).unwrap().state, PortState::Closed);
+    }
+}
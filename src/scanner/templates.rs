@@ -0,0 +1,178 @@
+//! Scan templates: built-in ones, and the user's own.
+//!
+//! Recurring audits tend to scan the same handful of ports every time, with
+//! the same probes. A template gives such a sweep a name, so
+//! `--template tls-audit` replaces typing out the port list and `--tls-info`,
+//! and keeps repeated audits consistent.
+//!
+//! Templates defined under `[templates]` in the config file (see
+//! [`config`](crate::config)) are offered next to the built-in ones, and
+//! replace a built-in template of the same name.
+
+/// Probes a template turns on, as if their flags had been given.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct TemplateProbes {
+    /// Read each open port's TLS certificate (`--tls-info`).
+    pub tls_info: bool,
+    /// Fetch each open port's HTTP status, server and title (`--http-probe`).
+    pub http_probe: bool,
+    /// Identify the product and version behind each open port
+    /// (`--version-detect`).
+    pub version_detect: bool,
+}
+
+/// A named port list, and the probes to run on it, for a recurring kind of
+/// sweep.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ScanTemplate {
+    /// Name used on the command line, e.g. `pci-external`.
+    pub name: String,
+    /// One-line description shown in docs.
+    pub description: String,
+    /// TCP ports scanned by the template, in ascending order.
+    pub ports: Vec<u16>,
+    /// Probes run on the open ports.
+    pub probes: TemplateProbes,
+}
+
+/// Templates shipped with asphyxia: name, description, ports and probes.
+const BUILTIN_TEMPLATES: &[(&str, &str, &[u16], TemplateProbes)] = &[
+    (
+        "pci-external",
+        "Externally reachable services commonly flagged in PCI DSS scans",
+        &[
+            21, 22, 23, 25, 53, 80, 110, 111, 135, 139, 143, 389, 443, 445, 465, 587, 636, 993,
+            995, 1433, 1521, 2049, 3306, 3389, 5432, 5900, 6379, 8080, 8443, 9200, 11211, 27017,
+        ],
+        TemplateProbes {
+            tls_info: false,
+            http_probe: false,
+            version_detect: true,
+        },
+    ),
+    (
+        "rdp-exposure",
+        "Remote desktop and remote administration services",
+        &[22, 23, 3389, 5900, 5901, 5985, 5986],
+        TemplateProbes {
+            tls_info: false,
+            http_probe: false,
+            version_detect: false,
+        },
+    ),
+    (
+        "tls-audit",
+        "Ports that usually speak TLS, with their certificates",
+        &[
+            443, 465, 636, 853, 989, 990, 993, 995, 5061, 6443, 8443, 8883,
+        ],
+        TemplateProbes {
+            tls_info: true,
+            http_probe: false,
+            version_detect: false,
+        },
+    ),
+];
+
+/// The templates shipped with asphyxia.
+pub fn builtin_templates() -> Vec<ScanTemplate> {
+    BUILTIN_TEMPLATES
+        .iter()
+        .map(|&(name, description, ports, probes)| ScanTemplate {
+            name: name.to_string(),
+            description: description.to_string(),
+            ports: ports.to_vec(),
+            probes,
+        })
+        .collect()
+}
+
+/// Look up a template by name (case-insensitive), among the user's
+/// templates first and then the built-in ones.
+///
+/// # Arguments
+///
+/// * `name` - The template's name, as given to `--template`
+/// * `user` - Templates from the config file
+///
+/// # Returns
+///
+/// * `Result<ScanTemplate, String>` - The template, or an error naming the
+///   available templates
+///
+/// # Examples
+///
+/// ```
+/// use asphyxia::scanner::templates::resolve_template;
+///
+/// let template = resolve_template("tls-audit", &[]).unwrap();
+/// assert!(template.ports.contains(&443));
+/// assert!(template.probes.tls_info);
+/// assert!(resolve_template("no-such-template", &[]).is_err());
+/// ```
+pub fn resolve_template(name: &str, user: &[ScanTemplate]) -> Result<ScanTemplate, String> {
+    let builtin = builtin_templates();
+    user.iter()
+        .chain(&builtin)
+        .find(|template| template.name.eq_ignore_ascii_case(name))
+        .cloned()
+        .ok_or_else(|| {
+            let mut names: Vec<&str> = user
+                .iter()
+                .chain(&builtin)
+                .map(|t| t.name.as_str())
+                .collect();
+            names.sort_unstable();
+            names.dedup();
+            format!(
+                "Unknown scan template: {} (available: {})",
+                name,
+                names.join(", ")
+            )
+        })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_template_ports_are_sorted_and_unique() {
+        for template in builtin_templates() {
+            assert!(
+                template.ports.windows(2).all(|w| w[0] < w[1]),
+                "{} ports must be ascending",
+                template.name
+            );
+        }
+    }
+
+    #[test]
+    fn test_unknown_template_lists_available_names() {
+        let err = resolve_template("nope", &[]).unwrap_err();
+        assert!(err.contains("pci-external"));
+        assert!(err.contains("tls-audit"));
+    }
+
+    #[test]
+    fn test_user_template_overrides_builtin() {
+        let user = [
+            ScanTemplate {
+                name: "tls-audit".to_string(),
+                description: String::new(),
+                ports: vec![443],
+                probes: TemplateProbes::default(),
+            },
+            ScanTemplate {
+                name: "mail".to_string(),
+                description: String::new(),
+                ports: vec![25, 587],
+                probes: TemplateProbes::default(),
+            },
+        ];
+        assert_eq!(resolve_template("TLS-Audit", &user).unwrap().ports, [443]);
+        assert_eq!(resolve_template("mail", &user).unwrap().ports, [25, 587]);
+        let err = resolve_template("nope", &user).unwrap_err();
+        assert!(err.contains("mail, pci-external, rdp-exposure, tls-audit)"));
+    }
+}
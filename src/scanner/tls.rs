@@ -0,0 +1,298 @@
+//! TLS certificate inspection of open ports.
+//!
+//! A TLS handshake hands over the server's certificate before anything else
+//! is said, so any TLS service — HTTPS, IMAPS, LDAPS, a management API on an
+//! odd port — can be asked who it claims to be, who vouched for that, and
+//! until when. [`probe_cert`] reads those answers; sweeping a subnet with it
+//! inventories the certificates that are about to expire.
+//!
+//! Certificates are never verified: an expired or self-signed one is a
+//! finding, not a reason to look away. The handshake is still checked to be
+//! signed by the certificate it presents.
+
+use std::collections::BTreeMap;
+use std::net::{IpAddr, Ipv4Addr, Ipv6Addr, SocketAddr};
+use std::sync::{Arc, OnceLock};
+use std::time::Duration;
+
+use chrono::{DateTime, Utc};
+use rayon::prelude::*;
+use rustls::client::danger::{HandshakeSignatureValid, ServerCertVerified, ServerCertVerifier};
+use rustls::crypto::{CryptoProvider, ring, verify_tls12_signature, verify_tls13_signature};
+use rustls::pki_types::{CertificateDer, ServerName, UnixTime};
+use rustls::{ClientConfig, ClientConnection, DigitallySignedStruct, SignatureScheme};
+use x509_parser::extensions::GeneralName;
+use x509_parser::prelude::{FromDer, X509Certificate};
+
+use crate::scanner::port::resolve_host;
+use crate::scanner::socket::{self, Purpose};
+
+/// What a server's TLS certificate says about it.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct CertInfo {
+    /// The subject's distinguished name, e.g. `O=Example, CN=example.com`.
+    pub subject: String,
+    /// The issuer's distinguished name; the same as the subject for a
+    /// self-signed certificate.
+    pub issuer: String,
+    /// DNS names and IP addresses from the subject alternative names.
+    pub sans: Vec<String>,
+    /// The end of the validity period (`notAfter`).
+    pub not_after: DateTime<Utc>,
+}
+
+impl CertInfo {
+    /// Whole days from `now` until the certificate expires; negative once
+    /// it has.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use asphyxia::scanner::tls::CertInfo;
+    ///
+    /// let cert = CertInfo {
+    ///     subject: "CN=example.com".to_string(),
+    ///     issuer: "CN=Example CA".to_string(),
+    ///     sans: vec!["example.com".to_string()],
+    ///     not_after: "2026-06-30T12:00:00Z".parse().unwrap(),
+    /// };
+    /// assert_eq!(cert.days_left("2026-06-01T12:00:00Z".parse().unwrap()), 29);
+    /// assert_eq!(cert.days_left("2026-07-02T12:00:00Z".parse().unwrap()), -2);
+    /// ```
+    pub fn days_left(&self, now: DateTime<Utc>) -> i64 {
+        (self.not_after - now).num_days()
+    }
+}
+
+/// Complete a TLS handshake with `port` on `host` and read the certificate
+/// the server presents.
+///
+/// # Arguments
+///
+/// * `host` - Hostname or IP address; a hostname is sent as the TLS server
+///   name, so a server hosting several names presents the matching one
+/// * `port` - An open TCP port on the host
+/// * `timeout` - Connect timeout, and read timeout for each step of the
+///   handshake
+///
+/// # Returns
+///
+/// * `Option<CertInfo>` - The leaf certificate, or `None` if the port does
+///   not speak TLS (or stayed silent for `timeout`)
+///
+/// # Examples
+///
+/// ```no_run
+/// use asphyxia::scanner::tls::probe_cert;
+/// use std::time::Duration;
+///
+/// if let Some(cert) = probe_cert("192.168.1.1", 443, Duration::from_secs(2)) {
+///     println!("{} expires {}", cert.subject, cert.not_after);
+/// }
+/// ```
+pub fn probe_cert(host: &str, port: u16, timeout: Duration) -> Option<CertInfo> {
+    let ip = resolve_host(host)?;
+    let mut stream = socket::connect(
+        SocketAddr::new(ip, port),
+        Purpose::Exchange.profile(),
+        timeout,
+    )
+    .ok()?;
+    stream.set_read_timeout(Some(timeout)).ok()?;
+    stream.set_write_timeout(Some(timeout)).ok()?;
+    let name = ServerName::try_from(host.to_string()).ok()?;
+    let mut connection = ClientConnection::new(client_config(), name).ok()?;
+    while connection.is_handshaking() {
+        connection.complete_io(&mut stream).ok()?;
+    }
+    parse_cert(connection.peer_certificates()?.first()?)
+}
+
+/// Read the certificate of every port among `ports` on `host` in parallel
+/// (see [`probe_cert`]), returning those that spoke TLS by port.
+pub fn probe_tls_ports(host: &str, ports: &[u16], timeout: Duration) -> BTreeMap<u16, CertInfo> {
+    ports
+        .par_iter()
+        .filter_map(|&port| Some((port, probe_cert(host, port, timeout)?)))
+        .collect()
+}
+
+/// Read the subject, issuer, alternative names and expiry from a
+/// DER-encoded certificate.
+pub(crate) fn parse_cert(der: &[u8]) -> Option<CertInfo> {
+    let (_, cert) = X509Certificate::from_der(der).ok()?;
+    let sans = match cert.subject_alternative_name() {
+        Ok(Some(extension)) => extension
+            .value
+            .general_names
+            .iter()
+            .filter_map(alternative_name)
+            .collect(),
+        _ => Vec::new(),
+    };
+    Some(CertInfo {
+        subject: cert.subject().to_string(),
+        issuer: cert.issuer().to_string(),
+        sans,
+        not_after: DateTime::from_timestamp(cert.validity().not_after.timestamp(), 0)?,
+    })
+}
+
+/// A subject alternative name as text, for the DNS and IP address kinds.
+fn alternative_name(name: &GeneralName) -> Option<String> {
+    match name {
+        GeneralName::DNSName(dns) => Some(dns.to_string()),
+        GeneralName::IPAddress(bytes) => {
+            let ip = match bytes.len() {
+                4 => IpAddr::V4(Ipv4Addr::from(<[u8; 4]>::try_from(*bytes).ok()?)),
+                16 => IpAddr::V6(Ipv6Addr::from(<[u8; 16]>::try_from(*bytes).ok()?)),
+                _ => return None,
+            };
+            Some(ip.to_string())
+        }
+        _ => None,
+    }
+}
+
+/// TLS client settings shared by every probe: any certificate is accepted.
+pub(crate) fn client_config() -> Arc<ClientConfig> {
+    static CONFIG: OnceLock<Arc<ClientConfig>> = OnceLock::new();
+    CONFIG
+        .get_or_init(|| {
+            let provider = Arc::new(ring::default_provider());
+            let config = ClientConfig::builder_with_provider(provider.clone())
+                .with_safe_default_protocol_versions()
+                .expect("the ring provider supports the default TLS versions")
+                .dangerous()
+                .with_custom_certificate_verifier(Arc::new(AcceptAnyCert(provider)))
+                .with_no_client_auth();
+            Arc::new(config)
+        })
+        .clone()
+}
+
+/// Accepts every server certificate, while still checking that the
+/// handshake is signed by it.
+#[derive(Debug)]
+struct AcceptAnyCert(Arc<CryptoProvider>);
+
+impl ServerCertVerifier for AcceptAnyCert {
+    fn verify_server_cert(
+        &self,
+        _end_entity: &CertificateDer<'_>,
+        _intermediates: &[CertificateDer<'_>],
+        _server_name: &ServerName<'_>,
+        _ocsp_response: &[u8],
+        _now: UnixTime,
+    ) -> Result<ServerCertVerified, rustls::Error> {
+        Ok(ServerCertVerified::assertion())
+    }
+
+    fn verify_tls12_signature(
+        &self,
+        message: &[u8],
+        cert: &CertificateDer<'_>,
+        dss: &DigitallySignedStruct,
+    ) -> Result<HandshakeSignatureValid, rustls::Error> {
+        verify_tls12_signature(
+            message,
+            cert,
+            dss,
+            &self.0.signature_verification_algorithms,
+        )
+    }
+
+    fn verify_tls13_signature(
+        &self,
+        message: &[u8],
+        cert: &CertificateDer<'_>,
+        dss: &DigitallySignedStruct,
+    ) -> Result<HandshakeSignatureValid, rustls::Error> {
+        verify_tls13_signature(
+            message,
+            cert,
+            dss,
+            &self.0.signature_verification_algorithms,
+        )
+    }
+
+    fn supported_verify_schemes(&self) -> Vec<SignatureScheme> {
+        self.0.signature_verification_algorithms.supported_schemes()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use rustls::pki_types::PrivateKeyDer;
+    use rustls::pki_types::pem::PemObject;
+    use rustls::{ServerConfig, ServerConnection};
+    use std::net::TcpListener;
+    use std::thread;
+
+    const CERT_PEM: &str = include_str!("../../tests/fixtures/tls-cert.pem");
+    const KEY_PEM: &str = include_str!("../../tests/fixtures/tls-key.pem");
+
+    fn cert_der() -> CertificateDer<'static> {
+        CertificateDer::from_pem_slice(CERT_PEM.as_bytes()).unwrap()
+    }
+
+    #[test]
+    fn test_parse_cert_reads_names_and_expiry() {
+        let cert = parse_cert(&cert_der()).unwrap();
+        assert_eq!(cert.subject, "O=Asphyxia Test, CN=asphyxia.test");
+        assert_eq!(cert.issuer, cert.subject);
+        assert_eq!(
+            cert.sans,
+            vec!["asphyxia.test", "www.asphyxia.test", "127.0.0.1"]
+        );
+        assert_eq!(cert.not_after.to_rfc3339(), "2126-09-22T15:37:28+00:00");
+    }
+
+    #[test]
+    fn test_parse_cert_rejects_garbage() {
+        assert_eq!(parse_cert(b"not a certificate"), None);
+    }
+
+    #[test]
+    fn test_probe_cert_reads_served_certificate() {
+        let provider = Arc::new(ring::default_provider());
+        let config = ServerConfig::builder_with_provider(provider)
+            .with_safe_default_protocol_versions()
+            .unwrap()
+            .with_no_client_auth()
+            .with_single_cert(
+                vec![cert_der()],
+                PrivateKeyDer::from_pem_slice(KEY_PEM.as_bytes()).unwrap(),
+            )
+            .unwrap();
+        let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+        let port = listener.local_addr().unwrap().port();
+        thread::spawn(move || {
+            let (mut stream, _) = listener.accept().unwrap();
+            let mut connection = ServerConnection::new(Arc::new(config)).unwrap();
+            while connection.is_handshaking() {
+                if connection.complete_io(&mut stream).is_err() {
+                    break;
+                }
+            }
+        });
+        let cert = probe_cert("127.0.0.1", port, Duration::from_secs(2)).unwrap();
+        assert_eq!(cert.subject, "O=Asphyxia Test, CN=asphyxia.test");
+    }
+
+    #[test]
+    fn test_probe_cert_ignores_plain_tcp() {
+        let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+        let port = listener.local_addr().unwrap().port();
+        thread::spawn(move || {
+            use std::io::Write;
+            let (mut stream, _) = listener.accept().unwrap();
+            let _ = stream.write_all(b"SSH-2.0-OpenSSH_9.6\r\n");
+        });
+        assert_eq!(
+            probe_cert("127.0.0.1", port, Duration::from_millis(500)),
+            None
+        );
+    }
+}
@@ -0,0 +1,265 @@
+//! Network topology inferred from traceroute paths.
+//!
+//! One traceroute shows the routers between the scanner and a single host.
+//! Many of them, taken together, show the network's shape: routers that
+//! several paths pass through are shared gateways, and hosts reached through
+//! the same last router sit on the same segment. [`infer_topology`] merges
+//! hop paths into that picture, which can be exported as Graphviz DOT
+//! ([`Topology::write_dot`]) or, through its `Serialize` implementation, as
+//! JSON.
+
+use serde::Serialize;
+use std::collections::{BTreeMap, BTreeSet};
+use std::io::{self, Write};
+use std::net::IpAddr;
+
+/// The route to one target, as reported by traceroute.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+pub struct TracePath {
+    /// The traced host.
+    pub target: IpAddr,
+    /// The address that answered at each TTL, starting at 1; `None` where no
+    /// router answered. The path may end with the target itself.
+    pub hops: Vec<Option<IpAddr>>,
+}
+
+/// A link between two adjacent addresses seen on one or more paths.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+pub struct Link {
+    /// The nearer end; `None` is the scanning host itself.
+    pub from: Option<IpAddr>,
+    /// The farther end.
+    pub to: IpAddr,
+    /// How many paths use the link.
+    pub paths: usize,
+}
+
+/// A router that paths to more than one target pass through.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+pub struct Gateway {
+    /// The router's address.
+    pub ip: IpAddr,
+    /// The targets reached through it, sorted.
+    pub targets: Vec<IpAddr>,
+}
+
+/// Targets reached through the same last router.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+pub struct Segment {
+    /// The last router before the targets; `None` when they answered at
+    /// the first hop (the scanner's own link) or no router answered.
+    pub gateway: Option<IpAddr>,
+    /// The targets on the segment, sorted.
+    pub hosts: Vec<IpAddr>,
+}
+
+/// The topology inferred from a set of trace paths.
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize)]
+pub struct Topology {
+    /// Every link seen, sorted by its ends.
+    pub links: Vec<Link>,
+    /// Routers shared by several paths, those serving the most targets first.
+    pub gateways: Vec<Gateway>,
+    /// Targets grouped by their last router, sorted by router.
+    pub segments: Vec<Segment>,
+}
+
+/// Merge `paths` into a [`Topology`].
+///
+/// Silent hops are skipped, so a link may span routers that did not answer;
+/// a router answering at several consecutive TTLs counts once.
+///
+/// # Examples
+///
+/// ```
+/// use asphyxia::scanner::topology::{TracePath, infer_topology};
+///
+/// let ip = |s: &str| s.parse().unwrap();
+/// let paths = [
+///     TracePath { target: ip("10.1.0.5"), hops: vec![Some(ip("192.168.1.1")), Some(ip("10.1.0.1")), Some(ip("10.1.0.5"))] },
+///     TracePath { target: ip("10.2.0.9"), hops: vec![Some(ip("192.168.1.1")), None, Some(ip("10.2.0.9"))] },
+/// ];
+/// let topology = infer_topology(&paths);
+/// assert_eq!(topology.gateways[0].ip, ip("192.168.1.1"));
+/// assert_eq!(topology.segments.len(), 2);
+/// ```
+pub fn infer_topology(paths: &[TracePath]) -> Topology {
+    let mut links: BTreeMap<(Option<IpAddr>, IpAddr), usize> = BTreeMap::new();
+    let mut behind: BTreeMap<IpAddr, BTreeSet<IpAddr>> = BTreeMap::new();
+    let mut segments: BTreeMap<Option<IpAddr>, BTreeSet<IpAddr>> = BTreeMap::new();
+
+    for path in paths {
+        let routers = routers(path);
+        let mut seen = BTreeSet::new();
+        let mut from = None;
+        for &to in routers.iter().chain([&path.target]) {
+            if seen.insert((from, to)) {
+                *links.entry((from, to)).or_default() += 1;
+            }
+            from = Some(to);
+        }
+        for &router in &routers {
+            behind.entry(router).or_default().insert(path.target);
+        }
+        segments
+            .entry(routers.last().copied())
+            .or_default()
+            .insert(path.target);
+    }
+
+    let mut gateways: Vec<Gateway> = behind
+        .into_iter()
+        .filter(|(_, targets)| targets.len() > 1)
+        .map(|(ip, targets)| Gateway {
+            ip,
+            targets: targets.into_iter().collect(),
+        })
+        .collect();
+    gateways.sort_by(|a, b| b.targets.len().cmp(&a.targets.len()).then(a.ip.cmp(&b.ip)));
+
+    Topology {
+        links: links
+            .into_iter()
+            .map(|((from, to), paths)| Link { from, to, paths })
+            .collect(),
+        gateways,
+        segments: segments
+            .into_iter()
+            .map(|(gateway, hosts)| Segment {
+                gateway,
+                hosts: hosts.into_iter().collect(),
+            })
+            .collect(),
+    }
+}
+
+/// The routers on `path` in order: answering hops other than the target,
+/// with consecutive repeats collapsed.
+fn routers(path: &TracePath) -> Vec<IpAddr> {
+    let mut routers: Vec<IpAddr> = path
+        .hops
+        .iter()
+        .flatten()
+        .copied()
+        .filter(|&hop| hop != path.target)
+        .collect();
+    routers.dedup();
+    routers
+}
+
+impl Topology {
+    /// Write the topology as a Graphviz DOT graph (render with `dot -Tsvg`).
+    ///
+    /// The scanner is drawn as a box, shared gateways as diamonds and every
+    /// other address as an ellipse; links used by several paths are
+    /// labelled with their count.
+    pub fn write_dot<W: Write>(&self, mut out: W) -> io::Result<()> {
+        writeln!(out, "digraph topology {{")?;
+        writeln!(out, "  rankdir=LR;")?;
+        writeln!(out, "  \"scanner\" [shape=box];")?;
+        for gateway in &self.gateways {
+            writeln!(out, "  \"{}\" [shape=diamond];", gateway.ip)?;
+        }
+        for link in &self.links {
+            let from = link.from.map_or("scanner".to_string(), |ip| ip.to_string());
+            if link.paths > 1 {
+                writeln!(
+                    out,
+                    "  \"{}\" -> \"{}\" [label=\"{}\"];",
+                    from, link.to, link.paths
+                )?;
+            } else {
+                writeln!(out, "  \"{}\" -> \"{}\";", from, link.to)?;
+            }
+        }
+        writeln!(out, "}}")?;
+        out.flush()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn ip(s: &str) -> IpAddr {
+        s.parse().unwrap()
+    }
+
+    fn path(target: &str, hops: &[Option<&str>]) -> TracePath {
+        TracePath {
+            target: ip(target),
+            hops: hops.iter().map(|hop| hop.map(ip)).collect(),
+        }
+    }
+
+    fn paths() -> Vec<TracePath> {
+        vec![
+            path(
+                "10.1.0.5",
+                &[Some("192.168.1.1"), Some("10.1.0.1"), Some("10.1.0.5")],
+            ),
+            path("10.1.0.6", &[Some("192.168.1.1"), Some("10.1.0.1"), None]),
+            path(
+                "10.2.0.9",
+                &[
+                    Some("192.168.1.1"),
+                    Some("192.168.1.1"),
+                    None,
+                    Some("10.2.0.9"),
+                ],
+            ),
+            path("192.168.1.20", &[Some("192.168.1.20")]),
+        ]
+    }
+
+    #[test]
+    fn test_links_are_counted_once_per_path() {
+        let topology = infer_topology(&paths());
+        let first = topology
+            .links
+            .iter()
+            .find(|l| l.from.is_none() && l.to == ip("192.168.1.1"))
+            .unwrap();
+        assert_eq!(first.paths, 3);
+        // The silent hop is bridged rather than breaking the path.
+        assert!(
+            topology
+                .links
+                .iter()
+                .any(|l| l.from == Some(ip("192.168.1.1")) && l.to == ip("10.2.0.9"))
+        );
+    }
+
+    #[test]
+    fn test_shared_gateways_sorted_by_reach() {
+        let gateways = infer_topology(&paths()).gateways;
+        assert_eq!(gateways.len(), 2);
+        assert_eq!(gateways[0].ip, ip("192.168.1.1"));
+        assert_eq!(gateways[0].targets.len(), 3);
+        assert_eq!(gateways[1].targets, vec![ip("10.1.0.5"), ip("10.1.0.6")]);
+    }
+
+    #[test]
+    fn test_segments_group_hosts_by_last_router() {
+        let segments = infer_topology(&paths()).segments;
+        assert_eq!(
+            segments[0],
+            Segment {
+                gateway: None,
+                hosts: vec![ip("192.168.1.20")]
+            }
+        );
+        assert_eq!(segments[1].gateway, Some(ip("10.1.0.1")));
+        assert_eq!(segments[1].hosts.len(), 2);
+    }
+
+    #[test]
+    fn test_write_dot_marks_gateways_and_shared_links() {
+        let mut dot = Vec::new();
+        infer_topology(&paths()).write_dot(&mut dot).unwrap();
+        let dot = String::from_utf8(dot).unwrap();
+        assert!(dot.contains("\"10.1.0.1\" [shape=diamond];"));
+        assert!(dot.contains("\"scanner\" -> \"192.168.1.1\" [label=\"3\"];"));
+        assert!(dot.contains("\"scanner\" -> \"192.168.1.20\";"));
+    }
+}
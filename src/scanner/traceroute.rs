@@ -0,0 +1,314 @@
+//! ICMP traceroute to discovered hosts.
+//!
+//! Each probe is an ICMP echo request sent with a growing time-to-live: the
+//! router where it expires answers with "time exceeded", naming one hop of
+//! the path, until the target itself replies. The resulting
+//! [`TracePath`]s feed [`infer_topology`](crate::scanner::topology::infer_topology).
+//!
+//! Like [`icmp`](crate::scanner::icmp), tracing needs raw-socket privileges
+//! (root, or `CAP_NET_RAW` on Linux).
+
+use rayon::prelude::*;
+use socket2::{Domain, Protocol, SockAddr, Socket, Type};
+use std::io::{self, Read};
+use std::net::{IpAddr, SocketAddr};
+use std::time::{Duration, Instant};
+
+use crate::scanner::icmp::{
+    ICMPV4_ECHO_REPLY, ICMPV4_ECHO_REQUEST, ICMPV6_ECHO_REPLY, ICMPV6_ECHO_REQUEST, echo_request,
+    is_reply, next_ident, strip_ipv4_header,
+};
+use crate::scanner::options::ScanOptions;
+use crate::scanner::port::CONNECT_TIMEOUT;
+use crate::scanner::topology::TracePath;
+use crate::scanner::usage::{self, OpenSocket};
+
+/// Highest time-to-live probed before a trace gives up.
+pub const MAX_HOPS: u8 = 30;
+
+/// Consecutive silent hops after which a trace gives up, so a target that
+/// drops pings does not cost [`MAX_HOPS`] timeouts.
+const MAX_SILENT_HOPS: usize = 5;
+
+/// Most traces run at once. Every raw ICMP socket receives a copy of every
+/// ICMP packet the host gets, so this stays well below the scan concurrency.
+const MAX_PARALLEL_TRACES: usize = 16;
+
+/// ICMP "time exceeded" and "destination unreachable" message types.
+const ICMPV4_TIME_EXCEEDED: u8 = 11;
+const ICMPV4_UNREACHABLE: u8 = 3;
+const ICMPV6_TIME_EXCEEDED: u8 = 3;
+const ICMPV6_UNREACHABLE: u8 = 1;
+
+/// Length of an IPv6 header, which ICMPv6 errors quote in full.
+const IPV6_HEADER_LEN: usize = 40;
+
+/// What an ICMP message says about one of our probes.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum Answer {
+    /// A router on the way dropped the probe: one more hop, keep going.
+    Hop,
+    /// The target replied, or a router declared it unreachable: stop.
+    Last,
+}
+
+/// Trace the route to `ip`, one hop at a time.
+///
+/// Each hop waits up to `options.timeout` (default
+/// [`CONNECT_TIMEOUT`]) for an answer. The trace stops when the target
+/// replies, a router reports it unreachable, [`MAX_HOPS`] is reached,
+/// several hops in a row stay silent, or the scan is cancelled.
+///
+/// # Examples
+///
+/// ```no_run
+/// use asphyxia::scanner::options::ScanOptions;
+/// use asphyxia::scanner::traceroute::trace;
+///
+/// let path = trace("8.8.8.8".parse().unwrap(), &ScanOptions::default()).unwrap();
+/// for (ttl, hop) in path.hops.iter().enumerate() {
+///     match hop {
+///         Some(ip) => println!("{:>2}  {}", ttl + 1, ip),
+///         None => println!("{:>2}  *", ttl + 1),
+///     }
+/// }
+/// ```
+pub fn trace(ip: IpAddr, options: &ScanOptions) -> io::Result<TracePath> {
+    let (domain, protocol, request_type) = match ip {
+        IpAddr::V4(_) => (Domain::IPV4, Protocol::ICMPV4, ICMPV4_ECHO_REQUEST),
+        IpAddr::V6(_) => (Domain::IPV6, Protocol::ICMPV6, ICMPV6_ECHO_REQUEST),
+    };
+    let timeout = options.timeout.unwrap_or(CONNECT_TIMEOUT);
+    let socket = Socket::new(domain, Type::RAW, Some(protocol))?;
+    let _open = OpenSocket::new();
+    let destination = SockAddr::from(SocketAddr::new(ip, 0));
+    let ident = next_ident();
+
+    let mut hops = Vec::new();
+    for ttl in 1..=MAX_HOPS {
+        if options.is_cancelled() {
+            break;
+        }
+        match ip {
+            IpAddr::V4(_) => socket.set_ttl_v4(u32::from(ttl))?,
+            IpAddr::V6(_) => socket.set_unicast_hops_v6(u32::from(ttl))?,
+        }
+        let sequence = u16::from(ttl);
+        let sent = socket.send_to(
+            &echo_request(request_type, ident, sequence, ip.is_ipv4()),
+            &destination,
+        )?;
+        usage::sent(sent);
+        // Every probe is addressed to the target, though most expire on
+        // the way; only the target's own answers count as its responses.
+        usage::probed(ip, sent);
+
+        let answer = await_answer(&socket, ip, ident, sequence, timeout)?;
+        hops.push(answer.map(|(from, _)| from));
+        if matches!(answer, Some((_, Answer::Last))) {
+            break;
+        }
+        if hops.len() >= MAX_SILENT_HOPS
+            && hops[hops.len() - MAX_SILENT_HOPS..]
+                .iter()
+                .all(Option::is_none)
+        {
+            break;
+        }
+    }
+    Ok(TracePath { target: ip, hops })
+}
+
+/// Trace the route to every host in `hosts`, a bounded number at a time.
+///
+/// Progress is reported as one step per finished trace. Fails only if a
+/// trace cannot be started at all, which in practice means the process lacks
+/// raw-socket privileges.
+///
+/// # Examples
+///
+/// ```no_run
+/// use asphyxia::scanner::options::ScanOptions;
+/// use asphyxia::scanner::topology::infer_topology;
+/// use asphyxia::scanner::traceroute::trace_all;
+///
+/// let hosts = ["10.1.0.5".parse().unwrap(), "10.2.0.9".parse().unwrap()];
+/// let paths = trace_all(&hosts, &ScanOptions::default()).unwrap();
+/// let topology = infer_topology(&paths);
+/// ```
+pub fn trace_all(hosts: &[IpAddr], options: &ScanOptions) -> io::Result<Vec<TracePath>> {
+    let progress = options.progress();
+    progress.start(hosts.len() as u64, "hosts traced");
+
+    let pool = rayon::ThreadPoolBuilder::new()
+        .num_threads(MAX_PARALLEL_TRACES.min(rayon::current_num_threads()))
+        .build()
+        .map_err(io::Error::other)?;
+    let paths = pool.install(|| {
+        hosts
+            .par_iter()
+            .map(|&ip| {
+                let path = trace(ip, options);
+                progress.inc(1);
+                path
+            })
+            .collect::<io::Result<Vec<_>>>()
+    });
+
+    progress.finish("Traceroute completed");
+    paths
+}
+
+/// Wait up to `timeout` for an answer to probe `sequence`, returning who
+/// answered and what it means.
+fn await_answer(
+    socket: &Socket,
+    target: IpAddr,
+    ident: u16,
+    sequence: u16,
+    timeout: Duration,
+) -> io::Result<Option<(IpAddr, Answer)>> {
+    let start = Instant::now();
+    let mut buf = [0u8; 1500];
+    loop {
+        let remaining = timeout.saturating_sub(start.elapsed());
+        if remaining.is_zero() {
+            return Ok(None);
+        }
+        socket.set_read_timeout(Some(remaining))?;
+        // Peek at the sender first: IPv6 raw sockets do not deliver the
+        // IP header, so the message alone does not say who sent it.
+        let from = match socket.peek_sender() {
+            Ok(from) => from.as_socket().map(|addr| addr.ip()),
+            Err(e)
+                if matches!(
+                    e.kind(),
+                    io::ErrorKind::WouldBlock | io::ErrorKind::TimedOut
+                ) =>
+            {
+                return Ok(None);
+            }
+            Err(e) => return Err(e),
+        };
+        let len = (&*socket).read(&mut buf)?;
+        usage::received(len);
+        let message = if target.is_ipv4() {
+            strip_ipv4_header(&buf[..len])
+        } else {
+            Some(&buf[..len])
+        };
+        if let (Some(from), Some(message)) = (from, message)
+            && let Some(answer) = classify(message, target.is_ipv4(), ident, sequence)
+        {
+            // Only the target's own echo reply ends the trace at the target;
+            // an echo reply from anyone else is not ours.
+            if answer == Answer::Hop || from == target || !is_echo_reply(message) {
+                if from == target {
+                    usage::answered(target, len);
+                }
+                return Ok(Some((from, answer)));
+            }
+        }
+    }
+}
+
+/// Whether `message` is an echo reply (of either family).
+fn is_echo_reply(message: &[u8]) -> bool {
+    matches!(
+        message.first(),
+        Some(&ICMPV4_ECHO_REPLY | &ICMPV6_ECHO_REPLY)
+    )
+}
+
+/// Classify an ICMP `message` as an answer to the echo request carrying
+/// `ident` and `sequence`, or `None` if it answers someone else's probe.
+///
+/// Echo replies carry the identifiers directly; "time exceeded" and
+/// "destination unreachable" errors quote the IP header and first bytes of
+/// the request that triggered them.
+fn classify(message: &[u8], ipv4: bool, ident: u16, sequence: u16) -> Option<Answer> {
+    let (reply, request, time_exceeded, unreachable) = if ipv4 {
+        (
+            ICMPV4_ECHO_REPLY,
+            ICMPV4_ECHO_REQUEST,
+            ICMPV4_TIME_EXCEEDED,
+            ICMPV4_UNREACHABLE,
+        )
+    } else {
+        (
+            ICMPV6_ECHO_REPLY,
+            ICMPV6_ECHO_REQUEST,
+            ICMPV6_TIME_EXCEEDED,
+            ICMPV6_UNREACHABLE,
+        )
+    };
+    let kind = *message.first()?;
+    if kind == reply {
+        return is_reply(message, reply, ident, sequence).then_some(Answer::Last);
+    }
+    let answer = match kind {
+        k if k == time_exceeded => Answer::Hop,
+        k if k == unreachable => Answer::Last,
+        _ => return None,
+    };
+    let quoted = message.get(8..)?;
+    let quoted = if ipv4 {
+        strip_ipv4_header(quoted)?
+    } else {
+        quoted.get(IPV6_HEADER_LEN..)?
+    };
+    is_reply(quoted, request, ident, sequence).then_some(answer)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// An ICMPv4 error of `kind` quoting our echo request.
+    fn icmpv4_error(kind: u8, ident: u16, sequence: u16) -> Vec<u8> {
+        let mut message = vec![kind, 0, 0, 0, 0, 0, 0, 0];
+        message.push(0x45);
+        message.extend_from_slice(&[0; 19]);
+        message.extend(echo_request(ICMPV4_ECHO_REQUEST, ident, sequence, true));
+        message
+    }
+
+    #[test]
+    fn test_classify_time_exceeded_quoting_our_probe() {
+        let message = icmpv4_error(ICMPV4_TIME_EXCEEDED, 7, 3);
+        assert_eq!(classify(&message, true, 7, 3), Some(Answer::Hop));
+        assert_eq!(classify(&message, true, 8, 3), None);
+        assert_eq!(classify(&message, true, 7, 4), None);
+    }
+
+    #[test]
+    fn test_classify_unreachable_and_reply_end_the_trace() {
+        let message = icmpv4_error(ICMPV4_UNREACHABLE, 7, 3);
+        assert_eq!(classify(&message, true, 7, 3), Some(Answer::Last));
+        let reply = echo_request(ICMPV4_ECHO_REPLY, 7, 3, true);
+        assert_eq!(classify(&reply, true, 7, 3), Some(Answer::Last));
+    }
+
+    #[test]
+    fn test_classify_icmpv6_error_skips_ipv6_header() {
+        let mut message = vec![ICMPV6_TIME_EXCEEDED, 0, 0, 0, 0, 0, 0, 0];
+        message.extend_from_slice(&[0; IPV6_HEADER_LEN]);
+        message.extend(echo_request(ICMPV6_ECHO_REQUEST, 9, 1, false));
+        assert_eq!(classify(&message, false, 9, 1), Some(Answer::Hop));
+        assert_eq!(classify(&message[..20], false, 9, 1), None);
+    }
+
+    #[test]
+    fn test_trace_localhost() {
+        if !crate::scanner::icmp::icmp_available() {
+            println!("Skipping test_trace_localhost: raw sockets are not permitted");
+            return;
+        }
+        let options = ScanOptions {
+            timeout: Some(Duration::from_millis(500)),
+            ..ScanOptions::default()
+        };
+        let path = trace("127.0.0.1".parse().unwrap(), &options).unwrap();
+        assert_eq!(path.hops, vec![Some("127.0.0.1".parse().unwrap())]);
+    }
+}
@@ -0,0 +1,76 @@
+//! Hop-count estimation from the TTL of answers.
+//!
+//! Every router on the way decrements a packet's time-to-live (the IPv6
+//! hop limit) by one. Operating systems start it at one of a few well-known
+//! values (64 for Linux, macOS and the BSDs, 128 for Windows, 255 for most
+//! network gear), so the TTL left on an answer tells how many routers it
+//! crossed — without sending a single extra probe, unlike a
+//! [`traceroute`](crate::scanner::traceroute). Answers that carry their IP
+//! header (SYN probes and ICMP echo replies over IPv4) provide it.
+
+/// Initial TTLs in common use, smallest first.
+const INITIAL_TTLS: [u8; 4] = [32, 64, 128, 255];
+
+/// The TTL the answering host most likely started with: the smallest
+/// common initial value not below `ttl`.
+///
+/// # Examples
+///
+/// ```
+/// use asphyxia::scanner::ttl::initial_ttl;
+///
+/// assert_eq!(initial_ttl(57), 64);
+/// assert_eq!(initial_ttl(120), 128);
+/// ```
+pub fn initial_ttl(ttl: u8) -> u8 {
+    INITIAL_TTLS
+        .into_iter()
+        .find(|&initial| initial >= ttl)
+        .unwrap_or(u8::MAX)
+}
+
+/// Estimate how many routers an answer that arrived with `ttl` crossed.
+///
+/// Zero means the host is on the local link. The estimate is off when the
+/// host starts from an unusual TTL, or is more than 32 hops away.
+///
+/// # Examples
+///
+/// ```
+/// use asphyxia::scanner::ttl::estimate_hops;
+///
+/// assert_eq!(estimate_hops(64), 0);
+/// assert_eq!(estimate_hops(115), 13);
+/// ```
+pub fn estimate_hops(ttl: u8) -> u8 {
+    initial_ttl(ttl) - ttl
+}
+
+/// The TTL of a received IPv4 packet, from its header.
+pub(crate) fn ipv4_ttl(packet: &[u8]) -> Option<u8> {
+    match packet.first() {
+        Some(first) if first >> 4 == 4 => packet.get(8).copied(),
+        _ => None,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_estimate_hops_at_boundaries() {
+        assert_eq!(estimate_hops(255), 0);
+        assert_eq!(estimate_hops(129), 126);
+        assert_eq!(estimate_hops(1), 31);
+    }
+
+    #[test]
+    fn test_ipv4_ttl_reads_header() {
+        let mut packet = vec![0x45];
+        packet.extend_from_slice(&[0; 7]);
+        packet.push(57);
+        assert_eq!(ipv4_ttl(&packet), Some(57));
+        assert_eq!(ipv4_ttl(&[0x60, 0, 0]), None);
+    }
+}
@@ -0,0 +1,344 @@
+//! UDP latency, jitter and loss measurement for VoIP readiness.
+//!
+//! Voice and video calls (RTP) send small UDP packets at a steady pace, and
+//! suffer when the path adds delay, varies that delay (jitter) or loses
+//! packets — none of which a TCP port scan shows. [`measure`] imitates such
+//! a stream: it sends numbered, timestamped datagrams to an echo peer (the
+//! classic echo service on port 7, or `asphyxia listen --udp-echo`) and
+//! times the echoes.
+//!
+//! Only the sender's clock is used, so latency is measured as a round trip;
+//! half of it is a fair estimate of the one-way delay on a symmetric path.
+
+use std::io;
+use std::net::{SocketAddr, UdpSocket};
+use std::time::{Duration, Instant};
+
+use crate::scanner::cancel::CancelToken;
+
+/// Marks datagrams sent by [`measure`], so stray traffic is not counted.
+const MAGIC: &[u8; 4] = b"ASPX";
+
+/// Length of a probe datagram: magic, sequence number, send offset.
+const PACKET_LEN: usize = 16;
+
+/// Highest one-way delay that keeps a call comfortable (ITU-T G.114).
+pub const VOIP_MAX_ONE_WAY: Duration = Duration::from_millis(150);
+
+/// Highest jitter a typical VoIP jitter buffer absorbs.
+pub const VOIP_MAX_JITTER: Duration = Duration::from_millis(30);
+
+/// Highest packet loss, in percent, that codecs conceal well.
+pub const VOIP_MAX_LOSS_PERCENT: f64 = 1.0;
+
+/// The outcome of one [`measure`] run.
+#[derive(Debug, Clone, PartialEq)]
+pub struct UdpPathReport {
+    /// How many datagrams were sent.
+    pub sent: u32,
+    /// The round-trip time of every datagram echoed back, in arrival order.
+    pub rtts: Vec<Duration>,
+    /// Interarrival jitter over the round-trip times, smoothed as in
+    /// RFC 3550.
+    pub jitter: Duration,
+}
+
+impl UdpPathReport {
+    /// How many datagrams came back.
+    pub fn received(&self) -> u32 {
+        self.rtts.len() as u32
+    }
+
+    /// Share of datagrams that never came back, in percent.
+    pub fn loss_percent(&self) -> f64 {
+        if self.sent == 0 {
+            return 0.0;
+        }
+        f64::from(self.sent - self.received()) * 100.0 / f64::from(self.sent)
+    }
+
+    /// Estimated one-way delay: half the mean round trip, or `None` if
+    /// nothing came back.
+    pub fn one_way(&self) -> Option<Duration> {
+        let count = self.received();
+        (count > 0).then(|| self.rtts.iter().sum::<Duration>() / count / 2)
+    }
+
+    /// What keeps the path from carrying calls well, against the
+    /// [`VOIP_MAX_ONE_WAY`], [`VOIP_MAX_JITTER`] and
+    /// [`VOIP_MAX_LOSS_PERCENT`] limits; empty if it is fit for VoIP.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use asphyxia::scanner::udp_path::UdpPathReport;
+    /// use std::time::Duration;
+    ///
+    /// let report = UdpPathReport {
+    ///     sent: 100,
+    ///     rtts: vec![Duration::from_millis(40); 95],
+    ///     jitter: Duration::from_millis(2),
+    /// };
+    /// assert_eq!(report.voip_issues(), vec!["5.0% packet loss (limit 1%)"]);
+    /// ```
+    pub fn voip_issues(&self) -> Vec<String> {
+        let mut issues = Vec::new();
+        match self.one_way() {
+            None => issues.push("no echoes came back".to_string()),
+            Some(one_way) if one_way > VOIP_MAX_ONE_WAY => issues.push(format!(
+                "{} ms one-way delay (limit {} ms)",
+                one_way.as_millis(),
+                VOIP_MAX_ONE_WAY.as_millis()
+            )),
+            Some(_) => {}
+        }
+        if self.jitter > VOIP_MAX_JITTER {
+            issues.push(format!(
+                "{} ms jitter (limit {} ms)",
+                self.jitter.as_millis(),
+                VOIP_MAX_JITTER.as_millis()
+            ));
+        }
+        let loss = self.loss_percent();
+        if self.sent > 0 && loss > VOIP_MAX_LOSS_PERCENT {
+            issues.push(format!(
+                "{:.1}% packet loss (limit {}%)",
+                loss, VOIP_MAX_LOSS_PERCENT
+            ));
+        }
+        issues
+    }
+}
+
+/// Send a datagram to `peer` every `interval` for `duration`, then wait up
+/// to `timeout` for the last echoes, and report latency, jitter and loss.
+///
+/// # Arguments
+///
+/// * `peer` - A UDP echo service, e.g. `asphyxia listen --udp-echo 9000`
+/// * `duration` - How long to keep sending
+/// * `interval` - Time between datagrams (20 ms is the usual RTP pace)
+/// * `timeout` - How long to wait for echoes after the last datagram
+/// * `cancel` - Stops sending early; the echoes so far are still reported
+///
+/// # Returns
+///
+/// * `io::Result<UdpPathReport>` - The measurement, or an error if the
+///   socket could not be set up
+///
+/// # Examples
+///
+/// ```no_run
+/// use asphyxia::scanner::cancel::CancelToken;
+/// use asphyxia::scanner::udp_path::measure;
+/// use std::time::Duration;
+///
+/// let report = measure(
+///     "10.0.0.9:7".parse().unwrap(),
+///     Duration::from_secs(10),
+///     Duration::from_millis(20),
+///     Duration::from_secs(1),
+///     &CancelToken::new(),
+/// )
+/// .unwrap();
+/// println!("{:.1}% lost, {} ms jitter", report.loss_percent(), report.jitter.as_millis());
+/// ```
+pub fn measure(
+    peer: SocketAddr,
+    duration: Duration,
+    interval: Duration,
+    timeout: Duration,
+    cancel: &CancelToken,
+) -> io::Result<UdpPathReport> {
+    let bind = match peer {
+        SocketAddr::V4(_) => "0.0.0.0:0",
+        SocketAddr::V6(_) => "[::]:0",
+    };
+    let socket = UdpSocket::bind(bind)?;
+    socket.connect(peer)?;
+
+    let start = Instant::now();
+    let mut sent = 0u32;
+    let mut next_send = start;
+    let mut stopped: Option<Instant> = None;
+    let mut echoed = Vec::new();
+    let mut rtts = Vec::new();
+    let mut jitter = JitterEstimator::default();
+    let mut buf = [0u8; 2048];
+    loop {
+        let now = Instant::now();
+        if stopped.is_none() && (cancel.is_cancelled() || now.duration_since(start) >= duration) {
+            stopped = Some(now);
+        }
+        let deadline = match stopped {
+            None if now >= next_send => {
+                socket.send(&packet(sent, now.duration_since(start)))?;
+                sent += 1;
+                echoed.push(false);
+                next_send += interval;
+                continue;
+            }
+            None => next_send,
+            Some(_) if echoed.iter().all(|&e| e) => break,
+            Some(stopped) => stopped + timeout,
+        };
+        let wait = deadline.saturating_duration_since(now);
+        if stopped.is_some() && wait.is_zero() {
+            break;
+        }
+        socket.set_read_timeout(Some(wait.max(Duration::from_millis(1))))?;
+        match socket.recv(&mut buf) {
+            Ok(len) => {
+                let received = start.elapsed();
+                if let Some((sequence, offset)) = parse_packet(&buf[..len])
+                    && let Some(seen) = echoed.get_mut(sequence as usize)
+                    && !*seen
+                {
+                    *seen = true;
+                    let rtt = received.saturating_sub(offset);
+                    jitter.add(rtt);
+                    rtts.push(rtt);
+                }
+            }
+            // Timeouts just mean nothing came back yet; a refusal (an ICMP
+            // port unreachable for an earlier datagram) counts as loss.
+            Err(e)
+                if matches!(
+                    e.kind(),
+                    io::ErrorKind::WouldBlock
+                        | io::ErrorKind::TimedOut
+                        | io::ErrorKind::ConnectionRefused
+                ) => {}
+            Err(e) => return Err(e),
+        }
+    }
+    Ok(UdpPathReport {
+        sent,
+        rtts,
+        jitter: jitter.value(),
+    })
+}
+
+/// The RFC 3550 interarrival jitter estimator, fed with round-trip times.
+#[derive(Debug, Default)]
+struct JitterEstimator {
+    previous: Option<Duration>,
+    jitter: f64,
+}
+
+impl JitterEstimator {
+    fn add(&mut self, rtt: Duration) {
+        if let Some(previous) = self.previous {
+            let delta = (rtt.as_secs_f64() - previous.as_secs_f64()).abs();
+            self.jitter += (delta - self.jitter) / 16.0;
+        }
+        self.previous = Some(rtt);
+    }
+
+    fn value(&self) -> Duration {
+        Duration::from_secs_f64(self.jitter)
+    }
+}
+
+/// A probe datagram carrying `sequence` and its send `offset` from the start.
+fn packet(sequence: u32, offset: Duration) -> [u8; PACKET_LEN] {
+    let mut packet = [0u8; PACKET_LEN];
+    packet[..4].copy_from_slice(MAGIC);
+    packet[4..8].copy_from_slice(&sequence.to_be_bytes());
+    let nanos = u64::try_from(offset.as_nanos()).unwrap_or(u64::MAX);
+    packet[8..].copy_from_slice(&nanos.to_be_bytes());
+    packet
+}
+
+/// The sequence number and send offset of an echoed probe datagram.
+fn parse_packet(data: &[u8]) -> Option<(u32, Duration)> {
+    if data.len() != PACKET_LEN || &data[..4] != MAGIC {
+        return None;
+    }
+    let sequence = u32::from_be_bytes(data[4..8].try_into().ok()?);
+    let nanos = u64::from_be_bytes(data[8..].try_into().ok()?);
+    Some((sequence, Duration::from_nanos(nanos)))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::thread;
+
+    #[test]
+    fn test_packet_round_trips() {
+        let offset = Duration::from_micros(1234);
+        assert_eq!(parse_packet(&packet(7, offset)), Some((7, offset)));
+        assert_eq!(parse_packet(b"hello"), None);
+    }
+
+    #[test]
+    fn test_steady_rtts_have_no_jitter() {
+        let mut jitter = JitterEstimator::default();
+        for _ in 0..10 {
+            jitter.add(Duration::from_millis(20));
+        }
+        assert_eq!(jitter.value(), Duration::ZERO);
+        jitter.add(Duration::from_millis(36));
+        let micros = jitter.value().as_micros();
+        assert!((999..=1001).contains(&micros), "{} µs", micros);
+    }
+
+    #[test]
+    fn test_voip_issues() {
+        let report = UdpPathReport {
+            sent: 10,
+            rtts: vec![Duration::from_millis(400); 10],
+            jitter: Duration::from_millis(50),
+        };
+        let issues = report.voip_issues();
+        assert_eq!(issues.len(), 2);
+        assert!(issues[0].contains("200 ms one-way"));
+        assert!(issues[1].contains("50 ms jitter"));
+    }
+
+    #[test]
+    fn test_measure_against_echo() {
+        let echo = UdpSocket::bind("127.0.0.1:0").unwrap();
+        let peer = echo.local_addr().unwrap();
+        echo.set_read_timeout(Some(Duration::from_millis(500)))
+            .unwrap();
+        let echoer = thread::spawn(move || {
+            let mut buf = [0u8; 64];
+            while let Ok((len, from)) = echo.recv_from(&mut buf) {
+                echo.send_to(&buf[..len], from).unwrap();
+            }
+        });
+
+        let report = measure(
+            peer,
+            Duration::from_millis(200),
+            Duration::from_millis(20),
+            Duration::from_millis(200),
+            &CancelToken::new(),
+        )
+        .unwrap();
+        echoer.join().unwrap();
+        assert!(report.sent >= 5);
+        assert_eq!(report.received(), report.sent);
+        assert_eq!(report.loss_percent(), 0.0);
+    }
+
+    #[test]
+    fn test_measure_counts_silence_as_loss() {
+        // Nothing echoes on this port.
+        let silent = UdpSocket::bind("127.0.0.1:0").unwrap();
+        let report = measure(
+            silent.local_addr().unwrap(),
+            Duration::from_millis(60),
+            Duration::from_millis(20),
+            Duration::from_millis(50),
+            &CancelToken::new(),
+        )
+        .unwrap();
+        assert!(report.sent > 0);
+        assert_eq!(report.received(), 0);
+        assert_eq!(report.loss_percent(), 100.0);
+        assert_eq!(report.one_way(), None);
+    }
+}
@@ -0,0 +1,270 @@
+//! Resources a scan used: memory, sockets, traffic and CPU time.
+//!
+//! Sizing a scan host, or the limits of the container it runs in, needs
+//! numbers rather than guesses. The probe engine counts as it goes: every
+//! TCP connection made through the [`socket`](super::socket) module and
+//! every raw socket of the SYN, ICMP, traceroute and path MTU probes is
+//! counted while open, and the bytes written to and read from them are
+//! added up. [`current`] combines these counts with the peak memory and CPU
+//! time the operating system reports for the process.
+//!
+//! The counts cover the whole process since it started, so a program that
+//! runs several scans sees their total. Bytes are what the scanner itself
+//! sends and receives: payloads on TCP connections, and whole packets on raw
+//! sockets. The handshake segments the kernel sends for a connect probe are
+//! not included.
+//!
+//! An audit, once [started](start_audit), also keeps these counts per
+//! target: how many probes each address was sent, how many it answered and
+//! the bytes both ways, so its owner can be told exactly what their systems
+//! received. A connect probe counts as one probe, answered when the port
+//! accepts or refuses it; traffic through a proxy is counted against the
+//! proxy, which is what the scanner connects to.
+
+use serde::Serialize;
+use std::collections::HashMap;
+use std::net::IpAddr;
+use std::sync::Mutex;
+use std::sync::atomic::{AtomicBool, AtomicU64, AtomicUsize, Ordering};
+use std::time::Duration;
+
+static OPEN_SOCKETS: AtomicUsize = AtomicUsize::new(0);
+static PEAK_SOCKETS: AtomicUsize = AtomicUsize::new(0);
+static BYTES_SENT: AtomicU64 = AtomicU64::new(0);
+static BYTES_RECEIVED: AtomicU64 = AtomicU64::new(0);
+static AUDITING: AtomicBool = AtomicBool::new(false);
+static AUDIT: Mutex<Option<HashMap<IpAddr, TargetTraffic>>> = Mutex::new(None);
+
+/// What the process has used so far.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct ResourceUsage {
+    /// Most memory the process has had resident at once, in bytes (`None`
+    /// where the platform does not report it).
+    pub peak_memory: Option<u64>,
+    /// Most sockets the scanner had open at once.
+    pub peak_sockets: usize,
+    /// Bytes the scanner sent.
+    pub bytes_sent: u64,
+    /// Bytes the scanner received.
+    pub bytes_received: u64,
+    /// User and system CPU time of the process (`None` where the platform
+    /// does not report it).
+    pub cpu_time: Option<Duration>,
+}
+
+/// The resources the process has used so far.
+///
+/// # Examples
+///
+/// ```
+/// use asphyxia::scanner::usage;
+///
+/// let usage = usage::current();
+/// println!("{} sockets at most, {} bytes sent", usage.peak_sockets, usage.bytes_sent);
+/// ```
+pub fn current() -> ResourceUsage {
+    let (peak_memory, cpu_time) = process_usage();
+    ResourceUsage {
+        peak_memory,
+        peak_sockets: PEAK_SOCKETS.load(Ordering::Relaxed),
+        bytes_sent: BYTES_SENT.load(Ordering::Relaxed),
+        bytes_received: BYTES_RECEIVED.load(Ordering::Relaxed),
+        cpu_time,
+    }
+}
+
+/// Counts a socket as open until it is dropped.
+#[derive(Debug)]
+pub(crate) struct OpenSocket(());
+
+impl OpenSocket {
+    pub(crate) fn new() -> OpenSocket {
+        let open = OPEN_SOCKETS.fetch_add(1, Ordering::Relaxed) + 1;
+        PEAK_SOCKETS.fetch_max(open, Ordering::Relaxed);
+        OpenSocket(())
+    }
+}
+
+impl Drop for OpenSocket {
+    fn drop(&mut self) {
+        OPEN_SOCKETS.fetch_sub(1, Ordering::Relaxed);
+    }
+}
+
+/// Count `bytes` as sent.
+pub(crate) fn sent(bytes: usize) {
+    BYTES_SENT.fetch_add(bytes as u64, Ordering::Relaxed);
+}
+
+/// Count `bytes` as received.
+pub(crate) fn received(bytes: usize) {
+    BYTES_RECEIVED.fetch_add(bytes as u64, Ordering::Relaxed);
+}
+
+/// The traffic one target received from the scanner and sent back.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+pub struct TargetTraffic {
+    /// The target's address.
+    pub ip: IpAddr,
+    /// Probes sent to the target: connection attempts and raw packets.
+    pub probes_sent: u64,
+    /// Bytes sent to the target.
+    pub bytes_sent: u64,
+    /// Probes the target answered.
+    pub responses_received: u64,
+    /// Bytes the target sent back.
+    pub bytes_received: u64,
+}
+
+impl TargetTraffic {
+    fn new(ip: IpAddr) -> TargetTraffic {
+        TargetTraffic {
+            ip,
+            probes_sent: 0,
+            bytes_sent: 0,
+            responses_received: 0,
+            bytes_received: 0,
+        }
+    }
+}
+
+/// Start keeping the traffic of every target, forgetting that of an earlier
+/// audit. Until this is called nothing is kept per target.
+pub fn start_audit() {
+    *AUDIT.lock().unwrap_or_else(|e| e.into_inner()) = Some(HashMap::new());
+    AUDITING.store(true, Ordering::Relaxed);
+}
+
+/// The traffic of every target probed since [`start_audit`], ordered by
+/// address; empty if no audit was started.
+///
+/// # Examples
+///
+/// ```no_run
+/// use asphyxia::scanner::usage;
+///
+/// usage::start_audit();
+/// // ... scan ...
+/// for target in usage::audit() {
+///     println!("{}: {} probes, {} bytes", target.ip, target.probes_sent, target.bytes_sent);
+/// }
+/// ```
+pub fn audit() -> Vec<TargetTraffic> {
+    let audit = AUDIT.lock().unwrap_or_else(|e| e.into_inner());
+    let mut targets: Vec<TargetTraffic> =
+        audit.iter().flat_map(|map| map.values().copied()).collect();
+    targets.sort_by_key(|target| target.ip);
+    targets
+}
+
+/// Add to the audited traffic of `ip`, if an audit is running.
+fn record(ip: IpAddr, count: impl FnOnce(&mut TargetTraffic)) {
+    if !AUDITING.load(Ordering::Relaxed) {
+        return;
+    }
+    let mut audit = AUDIT.lock().unwrap_or_else(|e| e.into_inner());
+    if let Some(targets) = audit.as_mut() {
+        count(targets.entry(ip).or_insert_with(|| TargetTraffic::new(ip)));
+    }
+}
+
+/// Count a probe of `bytes` sent to `ip` in the audit.
+pub(crate) fn probed(ip: IpAddr, bytes: usize) {
+    record(ip, |target| {
+        target.probes_sent += 1;
+        target.bytes_sent += bytes as u64;
+    });
+}
+
+/// Count an answer of `bytes` from `ip` in the audit.
+pub(crate) fn answered(ip: IpAddr, bytes: usize) {
+    record(ip, |target| {
+        target.responses_received += 1;
+        target.bytes_received += bytes as u64;
+    });
+}
+
+/// Count `sent` and `received` bytes of an established exchange with `ip`
+/// in the audit.
+pub(crate) fn exchanged(ip: IpAddr, sent: usize, received: usize) {
+    record(ip, |target| {
+        target.bytes_sent += sent as u64;
+        target.bytes_received += received as u64;
+    });
+}
+
+/// Peak resident memory and CPU time, from `getrusage`.
+#[cfg(unix)]
+fn process_usage() -> (Option<u64>, Option<Duration>) {
+    // SAFETY: getrusage only writes to the struct it is given.
+    let usage = unsafe {
+        let mut usage: libc::rusage = std::mem::zeroed();
+        if libc::getrusage(libc::RUSAGE_SELF, &mut usage) != 0 {
+            return (None, None);
+        }
+        usage
+    };
+    // macOS reports bytes, everyone else kilobytes.
+    let scale = if cfg!(target_os = "macos") { 1 } else { 1024 };
+    let peak_memory = u64::try_from(usage.ru_maxrss).ok().map(|rss| rss * scale);
+    let time = |tv: libc::timeval| {
+        Duration::from_secs(tv.tv_sec as u64) + Duration::from_micros(tv.tv_usec as u64)
+    };
+    (
+        peak_memory,
+        Some(time(usage.ru_utime) + time(usage.ru_stime)),
+    )
+}
+
+#[cfg(not(unix))]
+fn process_usage() -> (Option<u64>, Option<Duration>) {
+    (None, None)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_open_sockets_raise_the_peak() {
+        let before = current().peak_sockets;
+        let sockets: Vec<OpenSocket> = (0..before + 3).map(|_| OpenSocket::new()).collect();
+        assert!(current().peak_sockets >= sockets.len());
+        drop(sockets);
+        assert!(current().peak_sockets >= before + 3);
+    }
+
+    #[test]
+    fn test_traffic_adds_up() {
+        let before = current();
+        sent(40);
+        received(60);
+        let after = current();
+        assert!(after.bytes_sent >= before.bytes_sent + 40);
+        assert!(after.bytes_received >= before.bytes_received + 60);
+    }
+
+    #[test]
+    fn test_audit_counts_per_target() {
+        // Other tests probe too, so only this test's own address is checked.
+        let ip: IpAddr = "192.0.2.55".parse().unwrap();
+        start_audit();
+        probed(ip, 40);
+        probed(ip, 40);
+        answered(ip, 44);
+        exchanged(ip, 10, 100);
+        let target = audit().into_iter().find(|target| target.ip == ip).unwrap();
+        assert_eq!(target.probes_sent, 2);
+        assert_eq!(target.bytes_sent, 90);
+        assert_eq!(target.responses_received, 1);
+        assert_eq!(target.bytes_received, 144);
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn test_process_usage_is_reported() {
+        let usage = current();
+        assert!(usage.peak_memory.is_some_and(|bytes| bytes > 0));
+        assert!(usage.cpu_time.is_some());
+    }
+}
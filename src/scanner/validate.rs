@@ -0,0 +1,311 @@
+//! Two-sided firewall validation against a remote listener.
+//!
+//! A scan on its own sees only the answers that came back. When the far end
+//! runs `asphyxia listen --control <PORT>`, [`validate`] can also ask it
+//! what actually arrived (through the control protocol described in
+//! [`listen`](crate::scanner::listen)), and so tell apart a path that works
+//! from one where a middlebox answers, resets or silently drops the probe
+//! on the listener's behalf.
+
+use rayon::prelude::*;
+use std::collections::BTreeMap;
+use std::io::{BufRead, BufReader, Write};
+use std::net::{IpAddr, SocketAddr};
+use std::thread;
+use std::time::Duration;
+
+use crate::scanner::options::ScanOptions;
+use crate::scanner::port::{CONNECT_TIMEOUT, PortState, probe_port};
+use crate::scanner::socket::{self, Purpose};
+
+/// Grace period after the last probe before asking the listener what
+/// arrived, so connections still queued on its side are counted.
+const SETTLE: Duration = Duration::from_millis(100);
+
+/// What happened to the probes sent to one port.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Verdict {
+    /// The connection reached the listener: the path is open.
+    Passed,
+    /// The scan saw the port open, but nothing reached the listener:
+    /// something on the way (a proxy, a NAT or a load balancer) answered.
+    Intercepted,
+    /// Something answered with a reset before the listener saw anything,
+    /// e.g. a firewall `REJECT` rule.
+    Rejected,
+    /// No answer came back and nothing arrived: the probe was dropped on
+    /// the way or by the listener host's own firewall.
+    Dropped,
+}
+
+impl Verdict {
+    /// Short name used in reports.
+    pub fn as_str(self) -> &'static str {
+        match self {
+            Verdict::Passed => "passed",
+            Verdict::Intercepted => "intercepted",
+            Verdict::Rejected => "rejected",
+            Verdict::Dropped => "dropped",
+        }
+    }
+
+    /// One-line explanation of the verdict for the text report.
+    pub fn describe(self) -> &'static str {
+        match self {
+            Verdict::Passed => "reached the listener",
+            Verdict::Intercepted => "answered by something on the way, never reached the listener",
+            Verdict::Rejected => "reset before reaching the listener",
+            Verdict::Dropped => "no answer, never reached the listener",
+        }
+    }
+
+    /// The verdict for a port the scan found in `state` and that received
+    /// `arrived` connections at the listener.
+    fn classify(state: PortState, arrived: usize) -> Verdict {
+        match (state, arrived) {
+            (_, 1..) => Verdict::Passed,
+            (PortState::Open, 0) => Verdict::Intercepted,
+            (PortState::Closed, 0) => Verdict::Rejected,
+            (PortState::Filtered, 0) => Verdict::Dropped,
+        }
+    }
+}
+
+/// The outcome for one port.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct PortValidation {
+    /// The port tested.
+    pub port: u16,
+    /// What the scan saw.
+    pub state: PortState,
+    /// How many connections the listener received on the port during the
+    /// test, from any source (NAT may hide the scanner's address).
+    pub arrived: usize,
+    /// What the two sides together say about the path.
+    pub verdict: Verdict,
+}
+
+/// Probe the listener at `peer` and compare what the scan saw with what
+/// arrived.
+///
+/// `peer` is the listener's control address; its ports are probed on the
+/// same host. Without `ports`, every port the listener listens on is
+/// tested. Probes use `options` (timeout, retries, technique) like a port
+/// scan.
+///
+/// # Arguments
+///
+/// * `peer` - Control address of an `asphyxia listen --control` instance
+/// * `ports` - The ports to test, or `None` for all the listener's ports
+/// * `options` - Settings for the probes
+///
+/// # Returns
+///
+/// * `Result<Vec<PortValidation>, String>` - One outcome per port, sorted by
+///   port, or an error if the listener could not be reached or is not
+///   listening on a requested port
+///
+/// # Examples
+///
+/// ```no_run
+/// use asphyxia::scanner::options::ScanOptions;
+/// use asphyxia::scanner::validate::validate;
+///
+/// let peer = "10.0.0.9:9999".parse().unwrap();
+/// for result in validate(peer, None, &ScanOptions::default()).unwrap() {
+///     println!("{}/tcp {}", result.port, result.verdict.as_str());
+/// }
+/// ```
+pub fn validate(
+    peer: SocketAddr,
+    ports: Option<&[u16]>,
+    options: &ScanOptions,
+) -> Result<Vec<PortValidation>, String> {
+    let timeout = options.timeout.unwrap_or(CONNECT_TIMEOUT);
+    let listening = parse_ports_reply(&request(peer, "PORTS", timeout)?)?;
+    let ports = match ports {
+        Some(ports) => {
+            let missing: Vec<String> = ports
+                .iter()
+                .filter(|port| !listening.contains(port))
+                .map(ToString::to_string)
+                .collect();
+            if !missing.is_empty() {
+                return Err(format!(
+                    "The peer is not listening on port(s) {}",
+                    missing.join(",")
+                ));
+            }
+            ports.to_vec()
+        }
+        None => listening,
+    };
+    let before = parse_count_reply(&request(peer, "COUNT", timeout)?)?;
+
+    let host = peer.ip().to_string();
+    let mut states = ports
+        .par_iter()
+        .map(|&port| probe_port(&host, port, options, None).map(|result| (port, result.state)))
+        .collect::<Result<Vec<_>, String>>()?;
+    states.sort_by_key(|&(port, _)| port);
+
+    thread::sleep(SETTLE);
+    let arrived = parse_arrivals(&request(peer, &format!("SINCE {}", before), timeout)?)?;
+
+    Ok(states
+        .into_iter()
+        .map(|(port, state)| {
+            let arrived = arrived.get(&port).copied().unwrap_or(0);
+            PortValidation {
+                port,
+                state,
+                arrived,
+                verdict: Verdict::classify(state, arrived),
+            }
+        })
+        .collect())
+}
+
+/// Send one control request to `peer` and return the reply lines.
+fn request(peer: SocketAddr, line: &str, timeout: Duration) -> Result<Vec<String>, String> {
+    let unreachable = |e: std::io::Error| format!("Cannot reach the listener at {}: {}", peer, e);
+    let mut stream =
+        socket::connect(peer, Purpose::Exchange.profile(), timeout).map_err(unreachable)?;
+    stream
+        .set_read_timeout(Some(timeout))
+        .map_err(unreachable)?;
+    writeln!(stream, "{}", line).map_err(unreachable)?;
+    let lines = BufReader::new(&stream)
+        .lines()
+        .collect::<Result<Vec<_>, _>>()
+        .map_err(unreachable)?;
+    match lines.first() {
+        Some(first) if first.starts_with("ERR") => Err(format!(
+            "The listener at {} refused {}: {}",
+            peer, line, first
+        )),
+        _ => Ok(lines),
+    }
+}
+
+/// The single `OK <value>` line of a reply.
+fn ok_value(reply: &[String]) -> Result<&str, String> {
+    match reply {
+        [line] => line
+            .strip_prefix("OK ")
+            .ok_or_else(|| format!("Unexpected reply from the listener: {}", line)),
+        _ => Err("Unexpected reply from the listener".to_string()),
+    }
+}
+
+/// Parse the reply to `PORTS`.
+fn parse_ports_reply(reply: &[String]) -> Result<Vec<u16>, String> {
+    ok_value(reply)?
+        .split(',')
+        .map(|port| {
+            port.parse()
+                .map_err(|_| format!("Invalid port from the listener: {}", port))
+        })
+        .collect()
+}
+
+/// Parse the reply to `COUNT`.
+fn parse_count_reply(reply: &[String]) -> Result<usize, String> {
+    let count = ok_value(reply)?;
+    count
+        .parse()
+        .map_err(|_| format!("Invalid count from the listener: {}", count))
+}
+
+/// Parse the reply to `SINCE` into the number of connections per port.
+fn parse_arrivals(reply: &[String]) -> Result<BTreeMap<u16, usize>, String> {
+    if reply.last().map(String::as_str) != Some("END") {
+        return Err("Truncated arrival list from the listener".to_string());
+    }
+    let mut arrived = BTreeMap::new();
+    for line in &reply[..reply.len() - 1] {
+        let mut words = line.split_whitespace();
+        let port = match (words.next(), words.next(), words.next()) {
+            (Some("ARRIVAL"), Some(port), Some(source)) if source.parse::<IpAddr>().is_ok() => {
+                port.parse::<u16>().ok()
+            }
+            _ => None,
+        }
+        .ok_or_else(|| format!("Invalid arrival from the listener: {}", line))?;
+        *arrived.entry(port).or_insert(0) += 1;
+    }
+    Ok(arrived)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::scanner::cancel::CancelToken;
+    use crate::scanner::listen::{ListenOptions, listen};
+    use std::net::TcpListener;
+
+    fn lines(text: &str) -> Vec<String> {
+        text.lines().map(str::to_string).collect()
+    }
+
+    fn free_port() -> u16 {
+        TcpListener::bind("127.0.0.1:0")
+            .unwrap()
+            .local_addr()
+            .unwrap()
+            .port()
+    }
+
+    #[test]
+    fn test_classify() {
+        assert_eq!(Verdict::classify(PortState::Open, 1), Verdict::Passed);
+        assert_eq!(Verdict::classify(PortState::Open, 0), Verdict::Intercepted);
+        assert_eq!(Verdict::classify(PortState::Closed, 0), Verdict::Rejected);
+        assert_eq!(Verdict::classify(PortState::Filtered, 0), Verdict::Dropped);
+    }
+
+    #[test]
+    fn test_parse_replies() {
+        assert_eq!(parse_ports_reply(&lines("OK 22,9000")), Ok(vec![22, 9000]));
+        assert_eq!(parse_count_reply(&lines("OK 7")), Ok(7));
+        assert!(parse_count_reply(&lines("ERR nope")).is_err());
+        let arrived =
+            parse_arrivals(&lines("ARRIVAL 9000 10.0.0.7\nARRIVAL 9000 10.0.0.8\nEND")).unwrap();
+        assert_eq!(arrived[&9000], 2);
+        assert!(parse_arrivals(&lines("ARRIVAL 9000 10.0.0.7")).is_err());
+    }
+
+    #[test]
+    fn test_validate_against_local_listener() {
+        let (port, control) = (free_port(), free_port());
+        let cancel = CancelToken::new();
+        let listener = {
+            let cancel = cancel.clone();
+            thread::spawn(move || {
+                let options = ListenOptions {
+                    bind: "127.0.0.1".parse().unwrap(),
+                    ports: vec![port],
+                    control: Some(control),
+                    ..ListenOptions::default()
+                };
+                listen(&options, &cancel, |_| {})
+            })
+        };
+        thread::sleep(Duration::from_millis(100));
+
+        let peer = SocketAddr::from(([127, 0, 0, 1], control));
+        let options = ScanOptions {
+            timeout: Some(Duration::from_millis(500)),
+            ..ScanOptions::default()
+        };
+        let results = validate(peer, None, &options).unwrap();
+        assert!(validate(peer, Some(&[1]), &options).is_err());
+        cancel.cancel();
+        assert_eq!(listener.join().unwrap().unwrap().len(), 1);
+
+        assert_eq!(results.len(), 1);
+        assert_eq!(results[0].port, port);
+        assert_eq!(results[0].arrived, 1);
+        assert_eq!(results[0].verdict, Verdict::Passed);
+    }
+}
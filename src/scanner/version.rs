@@ -0,0 +1,365 @@
+//! Service and version detection with protocol probes.
+//!
+//! A [`banner`](crate::scanner::banner) is whatever a service says first,
+//! and many say nothing until spoken to. Version detection goes further:
+//! it listens for a greeting, then sends protocol-specific payloads (an
+//! HTTP request, a Redis `INFO`) and matches each answer against a table of
+//! signatures, nmap-style, to name the service and, where the answer gives
+//! it away, the product and version.
+//!
+//! Probes are tried in order, those meant for the port first, on a fresh
+//! connection each, and detection stops at the first signature that
+//! matches. The tables are small on purpose — HTTP, SSH, SMTP, Redis and
+//! MySQL — and answers that match nothing leave the port undetected.
+
+use std::collections::BTreeMap;
+use std::io::{Read, Write};
+use std::net::{IpAddr, SocketAddr};
+use std::sync::OnceLock;
+use std::time::Duration;
+
+use rayon::prelude::*;
+use regex::bytes::Regex;
+
+use crate::scanner::socket::{self, Purpose};
+
+/// Most bytes read from an answer; versions sit at the very start.
+const MAX_ANSWER_BYTES: usize = 4096;
+
+/// What version detection found out about a service.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ServiceVersion {
+    /// The service the answer belongs to (e.g. `ssh`, `http`).
+    pub service: String,
+    /// The software behind it (e.g. `OpenSSH`, `nginx`), if it said.
+    pub product: Option<String>,
+    /// The product's version (e.g. `9.6p1`), if it said.
+    pub version: Option<String>,
+}
+
+impl ServiceVersion {
+    /// Product and version as one label, e.g. `OpenSSH 9.6p1`; `None` if
+    /// only the service is known.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use asphyxia::scanner::version::ServiceVersion;
+    ///
+    /// let found = ServiceVersion {
+    ///     service: "ssh".to_string(),
+    ///     product: Some("OpenSSH".to_string()),
+    ///     version: Some("9.6p1".to_string()),
+    /// };
+    /// assert_eq!(found.label().as_deref(), Some("OpenSSH 9.6p1"));
+    /// ```
+    pub fn label(&self) -> Option<String> {
+        match (&self.product, &self.version) {
+            (Some(product), Some(version)) => Some(format!("{} {}", product, version)),
+            (Some(product), None) => Some(product.clone()),
+            (None, Some(version)) => Some(version.clone()),
+            (None, None) => None,
+        }
+    }
+}
+
+/// A payload to send, and the ports it is meant for.
+struct Probe {
+    /// Sent right after connecting; empty to only listen for a greeting.
+    payload: &'static [u8],
+    /// Ports this probe is tried on first; on other ports it follows the
+    /// probes meant for them.
+    ports: &'static [u16],
+}
+
+/// The probes, in the order they are tried on ports without a preference.
+const PROBES: &[Probe] = &[
+    // Greeting services: SSH, SMTP, MySQL.
+    Probe {
+        payload: b"",
+        ports: &[21, 22, 25, 110, 143, 465, 587, 2222, 3306],
+    },
+    Probe {
+        payload: b"GET / HTTP/1.0\r\nUser-Agent: asphyxia\r\nAccept: */*\r\n\r\n",
+        ports: &[
+            80, 81, 591, 3000, 5000, 8000, 8008, 8080, 8081, 8888, 9000, 9090,
+        ],
+    },
+    Probe {
+        payload: b"INFO server\r\n",
+        ports: &[6379],
+    },
+];
+
+/// A pattern an answer is matched against. The pattern's `product` and
+/// `version` groups, when present and matched, fill the result; `product`
+/// names the product for patterns without such a group.
+struct Signature {
+    service: &'static str,
+    product: Option<&'static str>,
+    pattern: &'static str,
+}
+
+/// The signatures, most specific first within each service.
+const SIGNATURES: &[Signature] = &[
+    Signature {
+        service: "ssh",
+        product: None,
+        pattern: r"^SSH-[\d.]+-(?<product>[A-Za-z][\w.-]*?)(?:[_-](?<version>[\w.]+))?[\s\r\n]",
+    },
+    Signature {
+        service: "smtp",
+        product: Some("Exim"),
+        pattern: r"^220[ -]\S+ E?SMTP Exim (?<version>[\d.]+)",
+    },
+    Signature {
+        service: "smtp",
+        product: Some("Postfix"),
+        pattern: r"^220[ -]\S+ E?SMTP Postfix",
+    },
+    Signature {
+        service: "smtp",
+        product: None,
+        pattern: r"^220[ -][^\r\n]*\bE?SMTP\b",
+    },
+    Signature {
+        service: "redis",
+        product: Some("Redis"),
+        pattern: r"redis_version:(?<version>[\d.]+)",
+    },
+    Signature {
+        service: "redis",
+        product: Some("Redis"),
+        pattern: r"^-(?:NOAUTH|DENIED Redis)",
+    },
+    // The MySQL handshake: a 4-byte packet header, protocol version 10, and
+    // the server version as a NUL-terminated string.
+    Signature {
+        service: "mysql",
+        product: Some("MariaDB"),
+        pattern: r"(?s-u)^.{4}\x0a(?:5\.5\.5-)?(?<version>[\d.]+)-MariaDB",
+    },
+    Signature {
+        service: "mysql",
+        product: Some("MySQL"),
+        pattern: r"(?s-u)^.{4}\x0a(?<version>[\d.]+)[\w.+-]*\x00",
+    },
+    Signature {
+        service: "mysql",
+        product: None,
+        pattern: r"(?s-u)^.{4}\xff.{2}Host .* is not allowed to connect to this (?<product>MySQL|MariaDB) server",
+    },
+    Signature {
+        service: "http",
+        product: None,
+        pattern: r"(?i-u)^HTTP/1\.[01] \d{3}[^\r\n]*\r\n(?:[^\r\n]+\r\n)*?Server: *(?<product>[^/\r\n ]+)(?:/(?<version>[^\s(]+))?",
+    },
+    Signature {
+        service: "http",
+        product: None,
+        pattern: r"^HTTP/1\.[01] \d{3}",
+    },
+];
+
+/// The compiled [`SIGNATURES`].
+fn signatures() -> &'static [(&'static Signature, Regex)] {
+    static COMPILED: OnceLock<Vec<(&'static Signature, Regex)>> = OnceLock::new();
+    COMPILED.get_or_init(|| {
+        SIGNATURES
+            .iter()
+            .map(|signature| {
+                let regex = Regex::new(signature.pattern).expect("signature patterns are valid");
+                (signature, regex)
+            })
+            .collect()
+    })
+}
+
+/// Name the service on `port` of `ip`, and its product and version when
+/// the answers give them away.
+///
+/// # Arguments
+///
+/// * `ip` - The host to probe
+/// * `port` - An open TCP port on the host
+/// * `timeout` - Connect timeout, and how long to wait for each answer
+///
+/// # Returns
+///
+/// * `Option<ServiceVersion>` - What was found, or `None` if no answer
+///   matched a signature
+///
+/// # Examples
+///
+/// ```no_run
+/// use asphyxia::scanner::version::detect_version;
+/// use std::time::Duration;
+///
+/// let ip = "192.168.1.10".parse().unwrap();
+/// if let Some(found) = detect_version(ip, 22, Duration::from_secs(2)) {
+///     println!("{} {:?}", found.service, found.label());
+/// }
+/// ```
+pub fn detect_version(ip: IpAddr, port: u16, timeout: Duration) -> Option<ServiceVersion> {
+    let (preferred, others): (Vec<&Probe>, Vec<&Probe>) =
+        PROBES.iter().partition(|probe| probe.ports.contains(&port));
+    preferred
+        .into_iter()
+        .chain(others)
+        .find_map(|probe| match_answer(&exchange(ip, port, probe.payload, timeout)?))
+}
+
+/// Detect the version of every port among `ports` on `ip` in parallel (see
+/// [`detect_version`]), returning what was found by port.
+pub fn detect_versions(
+    ip: IpAddr,
+    ports: &[u16],
+    timeout: Duration,
+) -> BTreeMap<u16, ServiceVersion> {
+    ports
+        .par_iter()
+        .filter_map(|&port| Some((port, detect_version(ip, port, timeout)?)))
+        .collect()
+}
+
+/// Send `payload` on a fresh connection and read the first answer.
+fn exchange(ip: IpAddr, port: u16, payload: &[u8], timeout: Duration) -> Option<Vec<u8>> {
+    let mut stream = socket::connect(
+        SocketAddr::new(ip, port),
+        Purpose::Exchange.profile(),
+        timeout,
+    )
+    .ok()?;
+    stream.set_read_timeout(Some(timeout)).ok()?;
+    stream.set_write_timeout(Some(timeout)).ok()?;
+    if !payload.is_empty() {
+        stream.write_all(payload).ok()?;
+    }
+    let mut buf = vec![0u8; MAX_ANSWER_BYTES];
+    let n = stream.read(&mut buf).ok()?;
+    buf.truncate(n);
+    (n > 0).then_some(buf)
+}
+
+/// The first signature `answer` matches, as a [`ServiceVersion`].
+pub(crate) fn match_answer(answer: &[u8]) -> Option<ServiceVersion> {
+    signatures().iter().find_map(|(signature, regex)| {
+        let captures = regex.captures(answer)?;
+        let group = |name: &str| {
+            captures
+                .name(name)
+                .map(|text| String::from_utf8_lossy(text.as_bytes()).into_owned())
+                .filter(|text| !text.is_empty())
+        };
+        Some(ServiceVersion {
+            service: signature.service.to_string(),
+            product: group("product").or(signature.product.map(str::to_string)),
+            version: group("version"),
+        })
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::net::TcpListener;
+    use std::thread;
+
+    fn found(service: &str, product: Option<&str>, version: Option<&str>) -> ServiceVersion {
+        ServiceVersion {
+            service: service.to_string(),
+            product: product.map(str::to_string),
+            version: version.map(str::to_string),
+        }
+    }
+
+    #[test]
+    fn test_matches_ssh_banners() {
+        assert_eq!(
+            match_answer(b"SSH-2.0-OpenSSH_9.6p1 Ubuntu-3ubuntu13\r\n"),
+            Some(found("ssh", Some("OpenSSH"), Some("9.6p1")))
+        );
+        assert_eq!(
+            match_answer(b"SSH-2.0-dropbear_2022.83\r\n"),
+            Some(found("ssh", Some("dropbear"), Some("2022.83")))
+        );
+    }
+
+    #[test]
+    fn test_matches_smtp_greetings() {
+        assert_eq!(
+            match_answer(b"220 mx.example.com ESMTP Exim 4.96 Mon, 04 May 2026\r\n"),
+            Some(found("smtp", Some("Exim"), Some("4.96")))
+        );
+        assert_eq!(
+            match_answer(b"220 mx.example.com ESMTP Postfix (Debian/GNU)\r\n"),
+            Some(found("smtp", Some("Postfix"), None))
+        );
+        assert_eq!(
+            match_answer(b"220-mx.example.com ESMTP ready\r\n"),
+            Some(found("smtp", None, None))
+        );
+    }
+
+    #[test]
+    fn test_matches_http_server_header() {
+        let answer = b"HTTP/1.1 200 OK\r\nDate: today\r\nServer: nginx/1.24.0 (Ubuntu)\r\n\r\n";
+        assert_eq!(
+            match_answer(answer),
+            Some(found("http", Some("nginx"), Some("1.24.0")))
+        );
+        assert_eq!(
+            match_answer(b"HTTP/1.0 404 Not Found\r\n\r\n"),
+            Some(found("http", None, None))
+        );
+    }
+
+    #[test]
+    fn test_matches_redis_and_mysql() {
+        assert_eq!(
+            match_answer(b"$1234\r\n# Server\r\nredis_version:7.2.4\r\nredis_git_sha1:0\r\n"),
+            Some(found("redis", Some("Redis"), Some("7.2.4")))
+        );
+        assert_eq!(
+            match_answer(b"-NOAUTH Authentication required.\r\n"),
+            Some(found("redis", Some("Redis"), None))
+        );
+        assert_eq!(
+            match_answer(b"J\x00\x00\x00\x0a8.0.36-0ubuntu0.22.04.1\x00\x08\x00\x00\x00"),
+            Some(found("mysql", Some("MySQL"), Some("8.0.36")))
+        );
+        assert_eq!(
+            match_answer(b"Y\x00\x00\x00\x0a5.5.5-10.11.6-MariaDB-0+deb12u1\x00"),
+            Some(found("mysql", Some("MariaDB"), Some("10.11.6")))
+        );
+    }
+
+    #[test]
+    fn test_unknown_answer_matches_nothing() {
+        assert_eq!(match_answer(b"+OK POP3 ready\r\n"), None);
+        assert_eq!(match_answer(b"\x16\x03\x01\x00\x05hello"), None);
+    }
+
+    #[test]
+    fn test_detect_version_sends_probes_to_silent_services() {
+        let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+        let port = listener.local_addr().unwrap().port();
+        thread::spawn(move || {
+            // Stay silent until spoken to, like a web server.
+            for stream in listener.incoming() {
+                let mut stream = stream.unwrap();
+                let mut buf = [0u8; 512];
+                if let Ok(n) = stream.read(&mut buf)
+                    && buf[..n].starts_with(b"GET / ")
+                {
+                    let _ = stream.write_all(b"HTTP/1.1 200 OK\r\nServer: Caddy\r\n\r\n");
+                }
+            }
+        });
+        let ip = "127.0.0.1".parse().unwrap();
+        assert_eq!(
+            detect_version(ip, port, Duration::from_millis(300)),
+            Some(found("http", Some("Caddy"), None))
+        );
+    }
+}
@@ -0,0 +1,435 @@
+//! Scan history in a local SQLite database.
+//!
+//! Built with the `history` feature, the CLI adds every finished port scan,
+//! address scan and sweep to a database (see [`default_path`]), so earlier
+//! results can be looked up with `asphyxia history` without having saved a
+//! report each time. [`History`] is the same store for library users.
+//!
+//! Each run keeps its command line and completion time, and each record is
+//! kept whole as JSON next to its address, port and status, so trends can
+//! also be queried with plain SQL:
+//!
+//! ```sql
+//! SELECT runs.time, records.status FROM records JOIN runs ON runs.id = records.run_id
+//! WHERE records.ip = '10.0.0.5' AND records.port = 22 ORDER BY runs.time;
+//! ```
+
+use chrono::{DateTime, Utc};
+use rusqlite::{Connection, params};
+use std::path::{Path, PathBuf};
+
+use crate::output::{ScanRecord, read_records};
+
+/// Environment variable naming the history database; set it empty to keep
+/// the CLI from recording scans.
+pub const HISTORY_ENV: &str = "ASPHYXIA_HISTORY";
+
+const SCHEMA: &str = "
+CREATE TABLE IF NOT EXISTS runs (
+    id INTEGER PRIMARY KEY AUTOINCREMENT,
+    time TEXT NOT NULL,
+    command TEXT NOT NULL,
+    arguments TEXT NOT NULL
+);
+CREATE TABLE IF NOT EXISTS records (
+    run_id INTEGER NOT NULL REFERENCES runs(id) ON DELETE CASCADE,
+    ip TEXT NOT NULL,
+    port INTEGER,
+    proto TEXT NOT NULL,
+    status TEXT NOT NULL,
+    record TEXT NOT NULL
+);
+CREATE INDEX IF NOT EXISTS records_by_run ON records(run_id);
+CREATE INDEX IF NOT EXISTS records_by_target ON records(ip, port);
+";
+
+/// One recorded scan, as listed by [`History::list`].
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ScanRun {
+    /// Identifier of the run, for [`History::show`].
+    pub id: i64,
+    /// When the scan completed.
+    pub time: DateTime<Utc>,
+    /// The subcommand that ran, e.g. `ps`.
+    pub command: String,
+    /// The full command line, without the program name.
+    pub arguments: String,
+    /// Number of records the scan produced.
+    pub records: usize,
+    /// Number of hosts that were up or had an open port.
+    pub live_hosts: usize,
+    /// Number of open ports.
+    pub open_ports: usize,
+}
+
+/// A history database.
+pub struct History {
+    conn: Connection,
+}
+
+impl History {
+    /// Open the database at `path`, creating it (and its directory) if needed.
+    ///
+    /// # Arguments
+    ///
+    /// * `path` - The database file
+    ///
+    /// # Returns
+    ///
+    /// * `Result<History, String>` - The database, or an error if it cannot
+    ///   be created or is not a history database
+    pub fn open(path: &Path) -> Result<History, String> {
+        if let Some(dir) = path.parent().filter(|dir| !dir.as_os_str().is_empty()) {
+            std::fs::create_dir_all(dir)
+                .map_err(|e| format!("Cannot create {}: {}", dir.display(), e))?;
+        }
+        let conn = Connection::open(path)
+            .map_err(|e| format!("Cannot open history {}: {}", path.display(), e))?;
+        History::init(conn).map_err(|e| format!("Invalid history {}: {}", path.display(), e))
+    }
+
+    /// A database that lives in memory only, e.g. for tests.
+    pub fn open_in_memory() -> Result<History, String> {
+        let conn = Connection::open_in_memory().map_err(|e| e.to_string())?;
+        History::init(conn).map_err(|e| e.to_string())
+    }
+
+    fn init(conn: Connection) -> rusqlite::Result<History> {
+        conn.execute_batch("PRAGMA foreign_keys = ON;")?;
+        conn.execute_batch(SCHEMA)?;
+        Ok(History { conn })
+    }
+
+    /// Add a finished scan and its records.
+    ///
+    /// # Arguments
+    ///
+    /// * `command` - The subcommand that ran, e.g. `ps`
+    /// * `arguments` - The full command line, without the program name
+    /// * `time` - When the scan completed
+    /// * `records` - The scan's results
+    ///
+    /// # Returns
+    ///
+    /// * `Result<i64, String>` - The new run's identifier
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use asphyxia::output::read_records;
+    /// use asphyxia::storage::History;
+    /// use chrono::Utc;
+    ///
+    /// let mut history = History::open_in_memory().unwrap();
+    /// let records = read_records(
+    ///     r#"{"ip":"10.0.0.5","port":22,"proto":"tcp","latency_ms":3,"status":"open"}"#,
+    /// )
+    /// .unwrap();
+    /// let id = history.record("ps", "ps -t 10.0.0.5 -p 22", Utc::now(), &records).unwrap();
+    /// assert_eq!(history.list(10).unwrap()[0].open_ports, 1);
+    /// assert_eq!(history.show(id).unwrap().unwrap().1, records);
+    /// ```
+    pub fn record(
+        &mut self,
+        command: &str,
+        arguments: &str,
+        time: DateTime<Utc>,
+        records: &[ScanRecord],
+    ) -> Result<i64, String> {
+        let write = |conn: &mut Connection| -> rusqlite::Result<i64> {
+            let tx = conn.transaction()?;
+            tx.execute(
+                "INSERT INTO runs (time, command, arguments) VALUES (?1, ?2, ?3)",
+                params![time.to_rfc3339(), command, arguments],
+            )?;
+            let id = tx.last_insert_rowid();
+            {
+                let mut insert = tx.prepare(
+                    "INSERT INTO records (run_id, ip, port, proto, status, record)
+                     VALUES (?1, ?2, ?3, ?4, ?5, ?6)",
+                )?;
+                for record in records {
+                    let json = serde_json::to_string(record)
+                        .map_err(|e| rusqlite::Error::ToSqlConversionFailure(Box::new(e)))?;
+                    insert.execute(params![
+                        id,
+                        record.ip,
+                        record.port,
+                        record.proto,
+                        record.status,
+                        json
+                    ])?;
+                }
+            }
+            tx.commit()?;
+            Ok(id)
+        };
+        write(&mut self.conn).map_err(|e| format!("Cannot record scan: {}", e))
+    }
+
+    /// The most recent runs, newest first.
+    ///
+    /// # Arguments
+    ///
+    /// * `limit` - How many runs to return at most
+    ///
+    /// # Returns
+    ///
+    /// * `Result<Vec<ScanRun>, String>` - The runs with their result counts
+    pub fn list(&self, limit: usize) -> Result<Vec<ScanRun>, String> {
+        self.runs(None, limit)
+            .map_err(|e| format!("Cannot read history: {}", e))
+    }
+
+    /// One run and its records, in the order they were recorded.
+    ///
+    /// # Arguments
+    ///
+    /// * `id` - The run's identifier, as listed by [`list`](Self::list)
+    ///
+    /// # Returns
+    ///
+    /// * `Result<Option<(ScanRun, Vec<ScanRecord>)>, String>` - The run and
+    ///   its records, or `None` if there is no run with this identifier
+    pub fn show(&self, id: i64) -> Result<Option<(ScanRun, Vec<ScanRecord>)>, String> {
+        let read = || -> rusqlite::Result<Option<(ScanRun, Vec<String>)>> {
+            let Some(run) = self.runs(Some(id), 1)?.pop() else {
+                return Ok(None);
+            };
+            let mut query = self
+                .conn
+                .prepare("SELECT record FROM records WHERE run_id = ?1 ORDER BY rowid")?;
+            let records = query
+                .query_map(params![id], |row| row.get(0))?
+                .collect::<rusqlite::Result<Vec<String>>>()?;
+            Ok(Some((run, records)))
+        };
+        let Some((run, stored)) = read().map_err(|e| format!("Cannot read history: {}", e))? else {
+            return Ok(None);
+        };
+        // Read back like a saved report, so records of older versions are upgraded.
+        let mut records = Vec::with_capacity(stored.len());
+        for json in &stored {
+            records.extend(
+                read_records(json).map_err(|e| format!("Invalid record in run {}: {}", id, e))?,
+            );
+        }
+        Ok(Some((run, records)))
+    }
+
+    /// Runs with their result counts, newest first: all of them, or only
+    /// the one with identifier `id`.
+    fn runs(&self, id: Option<i64>, limit: usize) -> rusqlite::Result<Vec<ScanRun>> {
+        let mut query = self.conn.prepare(
+            "SELECT runs.id, runs.time, runs.command, runs.arguments,
+                    COUNT(records.run_id),
+                    COUNT(DISTINCT CASE WHEN records.status IN ('up', 'open')
+                                        THEN records.ip END),
+                    COUNT(CASE WHEN records.port IS NOT NULL AND records.status = 'open'
+                               THEN 1 END)
+             FROM runs LEFT JOIN records ON records.run_id = runs.id
+             WHERE ?1 IS NULL OR runs.id = ?1
+             GROUP BY runs.id ORDER BY runs.id DESC LIMIT ?2",
+        )?;
+        let limit = i64::try_from(limit).unwrap_or(i64::MAX);
+        query
+            .query_map(params![id, limit], |row| {
+                Ok(ScanRun {
+                    id: row.get(0)?,
+                    time: parse_time(row.get(1)?)?,
+                    command: row.get(2)?,
+                    arguments: row.get(3)?,
+                    records: row.get(4)?,
+                    live_hosts: row.get(5)?,
+                    open_ports: row.get(6)?,
+                })
+            })?
+            .collect()
+    }
+}
+
+fn parse_time(text: String) -> rusqlite::Result<DateTime<Utc>> {
+    DateTime::parse_from_rfc3339(&text)
+        .map(|time| time.with_timezone(&Utc))
+        .map_err(|e| {
+            rusqlite::Error::FromSqlConversionFailure(1, rusqlite::types::Type::Text, Box::new(e))
+        })
+}
+
+/// Where the CLI keeps its history: `$ASPHYXIA_HISTORY` if set, else
+/// Options whose values are dropped from the recorded command line: proxy
+/// URLs carry passwords and webhook URLs are often secrets themselves.
+const SECRET_OPTIONS: [&str; 2] = ["--proxy", "--webhook"];
+
+/// `args` with secrets masked, ready to store as a run's command line: the
+/// values of `--proxy` and `--webhook` become `<redacted>`, as does the
+/// `user:pass@` part of any other URL.
+///
+/// # Arguments
+///
+/// * `args` - The command-line arguments, without the program name
+///
+/// # Returns
+///
+/// * `Vec<String>` - The arguments, safe to write to the database
+///
+/// # Examples
+///
+/// ```
+/// use asphyxia::storage::redact_arguments;
+///
+/// let args = ["ps", "--proxy", "http://bob:pw@proxy:3128", "-t", "https://bob:pw@web"];
+/// assert_eq!(
+///     redact_arguments(args.map(String::from)),
+///     ["ps", "--proxy", "<redacted>", "-t", "https://<redacted>@web"]
+/// );
+/// ```
+pub fn redact_arguments<I: IntoIterator<Item = String>>(args: I) -> Vec<String> {
+    let mut secret_next = false;
+    args.into_iter()
+        .map(|arg| {
+            if std::mem::take(&mut secret_next) {
+                return "<redacted>".to_string();
+            }
+            if SECRET_OPTIONS.contains(&arg.as_str()) {
+                secret_next = true;
+                return arg;
+            }
+            if let Some((option, _)) = arg.split_once('=')
+                && SECRET_OPTIONS.contains(&option)
+            {
+                return format!("{}=<redacted>", option);
+            }
+            redact_userinfo(&arg)
+        })
+        .collect()
+}
+
+/// `arg` with the userinfo of a URL in it replaced by `<redacted>`.
+fn redact_userinfo(arg: &str) -> String {
+    let Some(scheme_end) = arg.find("://") else {
+        return arg.to_string();
+    };
+    let authority_start = scheme_end + 3;
+    let authority_end = arg[authority_start..]
+        .find(['/', '?', '#'])
+        .map_or(arg.len(), |end| authority_start + end);
+    match arg[authority_start..authority_end].rfind('@') {
+        Some(at) => format!(
+            "{}<redacted>{}",
+            &arg[..authority_start],
+            &arg[authority_start + at..]
+        ),
+        None => arg.to_string(),
+    }
+}
+
+/// `history.db` in the user's data directory (`$XDG_DATA_HOME/asphyxia`,
+/// `~/.local/share/asphyxia`, or `%LOCALAPPDATA%\asphyxia` on Windows).
+///
+/// # Returns
+///
+/// * `Option<PathBuf>` - The database file, or `None` if `ASPHYXIA_HISTORY`
+///   is set empty or no data directory is known
+pub fn default_path() -> Option<PathBuf> {
+    if let Some(path) = std::env::var_os(HISTORY_ENV) {
+        return (!path.is_empty()).then(|| PathBuf::from(path));
+    }
+    let env_dir = |name: &str| std::env::var_os(name).filter(|dir| !dir.is_empty());
+    let data_dir = if cfg!(windows) {
+        env_dir("LOCALAPPDATA").map(PathBuf::from)
+    } else {
+        env_dir("XDG_DATA_HOME")
+            .map(PathBuf::from)
+            .or_else(|| env_dir("HOME").map(|home| Path::new(&home).join(".local/share")))
+    };
+    data_dir.map(|dir| dir.join("asphyxia").join("history.db"))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::output::read_records;
+    use chrono::TimeZone;
+
+    fn records(jsonl: &str) -> Vec<ScanRecord> {
+        read_records(jsonl).unwrap()
+    }
+
+    #[test]
+    fn test_redact_arguments() {
+        let redact = |args: &[&str]| redact_arguments(args.iter().map(|arg| arg.to_string()));
+        assert_eq!(
+            redact(&[
+                "ps",
+                "--proxy=socks5://u:p@jump",
+                "--webhook",
+                "https://h/t"
+            ]),
+            ["ps", "--proxy=<redacted>", "--webhook", "<redacted>"]
+        );
+        assert_eq!(
+            redact(&["-t", "http://u:p@web/a@b", "ftp://web", "a@b"]),
+            ["-t", "http://<redacted>@web/a@b", "ftp://web", "a@b"]
+        );
+        assert_eq!(redact(&["--proxy"]), ["--proxy"]);
+    }
+
+    #[test]
+    fn test_list_counts_and_orders_runs() {
+        let mut history = History::open_in_memory().unwrap();
+        let monday = Utc.with_ymd_and_hms(2026, 5, 4, 9, 0, 0).unwrap();
+        let sweep = records(concat!(
+            r#"{"ip":"10.0.0.5","proto":"tcp","latency_ms":1,"status":"up"}"#,
+            "\n",
+            r#"{"ip":"10.0.0.5","port":22,"proto":"tcp","latency_ms":3,"status":"open"}"#,
+            "\n",
+            r#"{"ip":"10.0.0.9","proto":"tcp","latency_ms":1,"status":"up"}"#,
+        ));
+        let first = history
+            .record("sweep", "sweep -s 10.0.0.0/24", monday, &sweep)
+            .unwrap();
+        let second = history.record("ps", "ps -t 10.0.0.7", monday, &[]).unwrap();
+
+        let runs = history.list(10).unwrap();
+        assert_eq!(
+            runs.iter().map(|run| run.id).collect::<Vec<_>>(),
+            vec![second, first]
+        );
+        assert_eq!(runs[1].time, monday);
+        assert_eq!(
+            (runs[1].records, runs[1].live_hosts, runs[1].open_ports),
+            (3, 2, 1)
+        );
+        assert_eq!((runs[0].records, runs[0].open_ports), (0, 0));
+        assert_eq!(history.list(1).unwrap().len(), 1);
+    }
+
+    #[test]
+    fn test_show_returns_records_in_order() {
+        let mut history = History::open_in_memory().unwrap();
+        let scan = records(concat!(
+            r#"{"ip":"10.0.0.5","port":443,"proto":"tcp","latency_ms":3,"status":"open","operator":"jsmith"}"#,
+            "\n",
+            r#"{"ip":"10.0.0.5","port":22,"proto":"tcp","latency_ms":3,"status":"closed"}"#,
+        ));
+        let id = history.record("ps", "ps", Utc::now(), &scan).unwrap();
+        let (run, shown) = history.show(id).unwrap().unwrap();
+        assert_eq!(run.command, "ps");
+        assert_eq!(shown, scan);
+        assert!(history.show(id + 1).unwrap().is_none());
+    }
+
+    #[test]
+    fn test_open_persists_to_file() {
+        let path = std::env::temp_dir()
+            .join(format!("asphyxia-history-{}", std::process::id()))
+            .join("history.db");
+        let id = History::open(&path)
+            .unwrap()
+            .record("as", "as -s 10.0.0.0/30", Utc::now(), &[])
+            .unwrap();
+        assert!(History::open(&path).unwrap().show(id).unwrap().is_some());
+        std::fs::remove_dir_all(path.parent().unwrap()).unwrap();
+    }
+}
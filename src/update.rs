@@ -0,0 +1,745 @@
+//! Replacing the running binary with a newer release (`self-update`).
+//!
+//! Scan tooling gets copied to many jump hosts and falls out of date there.
+//! `self-update` asks the release feed for the newest release of a
+//! [`Channel`], and if it is newer than the running binary, downloads the
+//! binary built for this platform and swaps it in place. Nothing talks to
+//! the feed unless `self-update` is run.
+//!
+//! A downloaded binary is only installed once it is proven to come from the
+//! project: every release carries, next to the bare binary of each target
+//! (`asphyxia-<target>`), a `SHA256SUMS` file listing their checksums and
+//! `SHA256SUMS.sig`, an Ed25519 signature of the release's version line
+//! followed by that file. The signature must verify against the release
+//! public key for the version the feed names, and the binary's SHA-256 must
+//! be the one listed for it, so the checksums of an older release cannot be
+//! passed off under a newer tag. Release builds carry the public key from
+//! `ASPHYXIA_UPDATE_KEY` at build time; other builds must be given one.
+//!
+//! The feed is the GitHub releases API of the project, or any mirror that
+//! serves the same JSON (see [`parse_feed`]), over HTTPS verified against
+//! the Mozilla root certificates. Plain HTTP is refused, for the feed and
+//! for every redirect on the way to a download.
+
+use std::cmp::Ordering;
+use std::collections::BTreeMap;
+use std::fmt;
+use std::fs;
+use std::io::{self, Read, Write};
+use std::net::SocketAddr;
+use std::path::{Path, PathBuf};
+use std::str::FromStr;
+use std::time::Duration;
+
+use clap::ValueEnum;
+use ring::digest::{SHA256, digest};
+use ring::signature::{ED25519, UnparsedPublicKey};
+use rustls::pki_types::ServerName;
+use rustls::{ClientConnection, StreamOwned};
+use serde::Deserialize;
+
+use crate::output::notify::verified_config;
+use crate::scanner::port::resolve_host;
+use crate::scanner::socket::{self, Purpose};
+use crate::utils::url::HttpUrl;
+
+/// The project's releases on GitHub.
+pub const DEFAULT_FEED: &str = "https://api.github.com/repos/jtprogru/asphyxia/releases";
+
+/// Name of the release asset listing the SHA-256 checksums of the binaries.
+pub const CHECKSUMS: &str = "SHA256SUMS";
+
+/// Name of the release asset holding the Ed25519 signature of
+/// [`CHECKSUMS`].
+pub const SIGNATURE: &str = "SHA256SUMS.sig";
+
+/// The release public key built into this binary, as 64 hex digits, from
+/// the `ASPHYXIA_UPDATE_KEY` environment variable at build time.
+pub const BUILTIN_KEY: Option<&str> = option_env!("ASPHYXIA_UPDATE_KEY");
+
+/// Most redirects followed for one download; release assets are served
+/// from a CDN the API redirects to.
+const MAX_REDIRECTS: usize = 5;
+
+/// Largest response accepted, well above the size of a release binary.
+const MAX_RESPONSE: usize = 256 * 1024 * 1024;
+
+/// Which releases to update to.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default, ValueEnum)]
+pub enum Channel {
+    /// Final releases only (the default).
+    #[default]
+    Stable,
+    /// Pre-releases too, for trying what comes next.
+    Beta,
+}
+
+impl fmt::Display for Channel {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Channel::Stable => write!(f, "stable"),
+            Channel::Beta => write!(f, "beta"),
+        }
+    }
+}
+
+/// A release version such as `0.6.0` or `0.7.0-beta.1`.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Version {
+    /// Major, minor and patch number.
+    pub number: (u64, u64, u64),
+    /// What follows the `-`, for pre-releases.
+    pub pre: Option<String>,
+}
+
+impl FromStr for Version {
+    type Err = String;
+
+    /// Parse a version, with or without a leading `v` as in release tags.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use asphyxia::update::Version;
+    ///
+    /// let beta: Version = "v0.7.0-beta.1".parse().unwrap();
+    /// assert!(beta < "0.7.0".parse().unwrap());
+    /// assert!(beta > "0.6.10".parse().unwrap());
+    /// ```
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let invalid = || format!("Invalid version: {}", s);
+        let plain = s.strip_prefix('v').unwrap_or(s);
+        let (number, pre) = match plain.split_once('-') {
+            Some((number, pre)) if !pre.is_empty() => (number, Some(pre.to_string())),
+            Some(_) => return Err(invalid()),
+            None => (plain, None),
+        };
+        let parts: Vec<u64> = number
+            .split('.')
+            .map(|part| part.parse().map_err(|_| invalid()))
+            .collect::<Result<_, _>>()?;
+        match parts[..] {
+            [major, minor, patch] => Ok(Version {
+                number: (major, minor, patch),
+                pre,
+            }),
+            _ => Err(invalid()),
+        }
+    }
+}
+
+impl fmt::Display for Version {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let (major, minor, patch) = self.number;
+        write!(f, "{}.{}.{}", major, minor, patch)?;
+        if let Some(pre) = &self.pre {
+            write!(f, "-{}", pre)?;
+        }
+        Ok(())
+    }
+}
+
+impl Ord for Version {
+    /// Versions order by number; a pre-release comes before its final
+    /// release, and pre-releases order by their dot-separated parts,
+    /// numerically where both are numbers.
+    fn cmp(&self, other: &Self) -> Ordering {
+        self.number
+            .cmp(&other.number)
+            .then_with(|| match (&self.pre, &other.pre) {
+                (None, None) => Ordering::Equal,
+                (None, Some(_)) => Ordering::Greater,
+                (Some(_), None) => Ordering::Less,
+                (Some(a), Some(b)) => {
+                    let part = |part: &str| (part.parse::<u64>().ok(), part.to_string());
+                    a.split('.').map(part).cmp(b.split('.').map(part))
+                }
+            })
+    }
+}
+
+impl PartialOrd for Version {
+    fn partial_cmp(&self, other: &Self) -> Option<Ordering> {
+        Some(self.cmp(other))
+    }
+}
+
+/// The version of the running binary.
+pub fn current_version() -> Version {
+    env!("CARGO_PKG_VERSION")
+        .parse()
+        .expect("the crate version is a valid version")
+}
+
+/// A published release and its downloadable assets.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Release {
+    /// The release's version, from its tag.
+    pub version: Version,
+    /// Whether the release is marked as a pre-release.
+    pub prerelease: bool,
+    /// Download URL of every asset, by asset name.
+    pub assets: BTreeMap<String, String>,
+}
+
+/// A release as the GitHub API describes it.
+#[derive(Deserialize)]
+struct FeedRelease {
+    tag_name: String,
+    #[serde(default)]
+    draft: bool,
+    #[serde(default)]
+    prerelease: bool,
+    #[serde(default)]
+    assets: Vec<FeedAsset>,
+}
+
+#[derive(Deserialize)]
+struct FeedAsset {
+    name: String,
+    browser_download_url: String,
+}
+
+/// Parse a release feed: a JSON array of releases as the GitHub API lists
+/// them, each with `tag_name`, `draft`, `prerelease` and `assets` (each
+/// with `name` and `browser_download_url`). Drafts and releases whose tag
+/// is not a version are left out.
+///
+/// # Examples
+///
+/// ```
+/// use asphyxia::update::parse_feed;
+///
+/// let feed = r#"[{"tag_name": "v0.6.0", "prerelease": false, "assets": [
+///     {"name": "SHA256SUMS", "browser_download_url": "https://example.com/SHA256SUMS"}]}]"#;
+/// let releases = parse_feed(feed).unwrap();
+/// assert_eq!(releases[0].version.to_string(), "0.6.0");
+/// assert!(releases[0].assets.contains_key("SHA256SUMS"));
+/// ```
+pub fn parse_feed(json: &str) -> Result<Vec<Release>, String> {
+    let feed: Vec<FeedRelease> =
+        serde_json::from_str(json).map_err(|e| format!("Invalid release feed: {}", e))?;
+    Ok(feed
+        .into_iter()
+        .filter(|release| !release.draft)
+        .filter_map(|release| {
+            Some(Release {
+                version: release.tag_name.parse().ok()?,
+                prerelease: release.prerelease,
+                assets: release
+                    .assets
+                    .into_iter()
+                    .map(|asset| (asset.name, asset.browser_download_url))
+                    .collect(),
+            })
+        })
+        .collect())
+}
+
+/// The newest of `releases` on `channel`: final releases only for
+/// [`Channel::Stable`], pre-releases too for [`Channel::Beta`].
+pub fn latest(releases: &[Release], channel: Channel) -> Option<&Release> {
+    releases
+        .iter()
+        .filter(|release| {
+            channel == Channel::Beta || (!release.prerelease && release.version.pre.is_none())
+        })
+        .max_by(|a, b| a.version.cmp(&b.version))
+}
+
+/// Name of the release asset holding the binary for this platform, e.g.
+/// `asphyxia-x86_64-unknown-linux-gnu`; `None` where no binaries are
+/// published.
+pub fn platform_asset() -> Option<String> {
+    let target = match std::env::consts::OS {
+        "linux" => "unknown-linux-gnu",
+        "macos" => "apple-darwin",
+        _ => return None,
+    };
+    Some(format!("asphyxia-{}-{}", std::env::consts::ARCH, target))
+}
+
+/// Parse an Ed25519 public key given as 64 hex digits.
+///
+/// # Examples
+///
+/// ```
+/// use asphyxia::update::parse_public_key;
+///
+/// assert!(parse_public_key(&"ab".repeat(32)).is_ok());
+/// assert!(parse_public_key("abcd").is_err());
+/// ```
+pub fn parse_public_key(hex: &str) -> Result<[u8; 32], String> {
+    let invalid = || "Invalid update public key (expected 64 hex digits)".to_string();
+    let hex = hex.trim();
+    if hex.len() != 64 || !hex.is_ascii() {
+        return Err(invalid());
+    }
+    let mut key = [0u8; 32];
+    for (byte, pair) in key.iter_mut().zip(hex.as_bytes().chunks(2)) {
+        let pair = std::str::from_utf8(pair).map_err(|_| invalid())?;
+        *byte = u8::from_str_radix(pair, 16).map_err(|_| invalid())?;
+    }
+    Ok(key)
+}
+
+/// Check that `signature` is the Ed25519 signature of `checksums` for
+/// release `version` by the holder of `key`.
+///
+/// The signed message is the version (as in `0.6.0`, without the tag's
+/// `v`) and a newline, followed by the checksums file, so a signature made
+/// for one release does not verify for another.
+pub fn verify_checksums(
+    version: &Version,
+    checksums: &[u8],
+    signature: &[u8],
+    key: &[u8; 32],
+) -> Result<(), String> {
+    let mut signed = format!("{}\n", version).into_bytes();
+    signed.extend_from_slice(checksums);
+    UnparsedPublicKey::new(&ED25519, key)
+        .verify(&signed, signature)
+        .map_err(|_| {
+            format!(
+                "{} is not signed by the release key for version {}",
+                CHECKSUMS, version
+            )
+        })
+}
+
+/// The checksum `checksums` (in `sha256sum` format) lists for `asset`.
+///
+/// # Examples
+///
+/// ```
+/// use asphyxia::update::listed_checksum;
+///
+/// let sums = "0123abcd  asphyxia-aarch64-apple-darwin\n4567ef01 *asphyxia-x86_64-unknown-linux-gnu\n";
+/// assert_eq!(listed_checksum(sums, "asphyxia-x86_64-unknown-linux-gnu"), Some("4567ef01"));
+/// assert_eq!(listed_checksum(sums, "asphyxia"), None);
+/// ```
+pub fn listed_checksum<'a>(checksums: &'a str, asset: &str) -> Option<&'a str> {
+    checksums.lines().find_map(|line| {
+        let (sum, name) = line.split_once(char::is_whitespace)?;
+        let name = name.trim_start();
+        (name.strip_prefix('*').unwrap_or(name) == asset).then_some(sum)
+    })
+}
+
+/// The SHA-256 of `data`, as lowercase hex.
+pub fn sha256_hex(data: &[u8]) -> String {
+    digest(&SHA256, data)
+        .as_ref()
+        .iter()
+        .map(|byte| format!("{:02x}", byte))
+        .collect()
+}
+
+/// The newest release on `channel` in the feed at `feed`, if it is newer
+/// than the running binary.
+///
+/// # Examples
+///
+/// ```no_run
+/// use asphyxia::update::{Channel, DEFAULT_FEED, check};
+/// use std::time::Duration;
+///
+/// match check(DEFAULT_FEED, Channel::Stable, Duration::from_secs(10)).unwrap() {
+///     Some(release) => println!("{} is available", release.version),
+///     None => println!("up to date"),
+/// }
+/// ```
+pub fn check(feed: &str, channel: Channel, timeout: Duration) -> Result<Option<Release>, String> {
+    let body = fetch(feed, timeout)?;
+    let releases = parse_feed(&String::from_utf8_lossy(&body))?;
+    Ok(latest(&releases, channel)
+        .filter(|release| release.version > current_version())
+        .cloned())
+}
+
+/// Download the binary of `release` for this platform, prove it with the
+/// signed checksums and `key`, and install it over `exe`.
+///
+/// # Returns
+///
+/// * `Result<(), String>` - `Ok` once the new binary is in place, or why
+///   nothing was changed
+pub fn install(
+    release: &Release,
+    key: &[u8; 32],
+    exe: &Path,
+    timeout: Duration,
+) -> Result<(), String> {
+    let asset = platform_asset().ok_or("No release binaries are published for this platform")?;
+    let url = |name: &str| {
+        release
+            .assets
+            .get(name)
+            .ok_or_else(|| format!("Release {} has no {}", release.version, name))
+    };
+    let checksums = fetch(url(CHECKSUMS)?, timeout)?;
+    let signature = fetch(url(SIGNATURE)?, timeout)?;
+    verify_checksums(&release.version, &checksums, &signature, key)?;
+    let checksums = String::from_utf8_lossy(&checksums);
+    let expected = listed_checksum(&checksums, &asset)
+        .ok_or_else(|| format!("{} does not list {}", CHECKSUMS, asset))?;
+    let binary = fetch(url(&asset)?, timeout)?;
+    if !sha256_hex(&binary).eq_ignore_ascii_case(expected) {
+        return Err(format!(
+            "Checksum of {} does not match {}",
+            asset, CHECKSUMS
+        ));
+    }
+    replace(exe, &binary).map_err(|e| format!("Cannot replace {}: {}", exe.display(), e))
+}
+
+/// Write `binary` next to `exe` and rename it over `exe`, so the old binary
+/// stays whole until the new one is complete.
+fn replace(exe: &Path, binary: &[u8]) -> io::Result<()> {
+    let mut staged = exe.as_os_str().to_owned();
+    staged.push(".new");
+    let staged = PathBuf::from(staged);
+    let written = fs::write(&staged, binary).and_then(|()| {
+        #[cfg(unix)]
+        {
+            use std::os::unix::fs::PermissionsExt;
+            fs::set_permissions(&staged, fs::Permissions::from_mode(0o755))?;
+        }
+        fs::rename(&staged, exe)
+    });
+    if written.is_err() {
+        let _ = fs::remove_file(&staged);
+    }
+    written
+}
+
+/// GET `url` over HTTPS, following redirects, and return the body of the
+/// 200 response.
+fn fetch(url: &str, timeout: Duration) -> Result<Vec<u8>, String> {
+    fetch_via(url, |url| get(url, timeout))
+}
+
+/// [`fetch`], sending each request with `get`.
+///
+/// Plain HTTP is refused, whether the URL asks for it or a redirect from an
+/// HTTPS server does.
+fn fetch_via(
+    url: &str,
+    get: impl Fn(&HttpUrl) -> Result<Vec<u8>, String>,
+) -> Result<Vec<u8>, String> {
+    let mut url = url.to_string();
+    for _ in 0..=MAX_REDIRECTS {
+        let parsed: HttpUrl = url
+            .parse()
+            .map_err(|_| format!("Invalid URL: {} (expected https://)", url))?;
+        if !parsed.tls {
+            return Err(format!(
+                "Refusing to download {} over plain HTTP (expected https://)",
+                parsed
+            ));
+        }
+        let failed = |reason: String| format!("Download of {} failed: {}", parsed, reason);
+        let response = get(&parsed).map_err(failed)?;
+        let (status, location, body) = parse_response(&response).map_err(failed)?;
+        match (status, location) {
+            (200, _) => return Ok(body),
+            (301 | 302 | 303 | 307 | 308, Some(location)) => {
+                url = redirect_target(&parsed, &location);
+            }
+            (status, _) => return Err(failed(format!("HTTP {}", status))),
+        }
+    }
+    Err(format!("Download of {} failed: too many redirects", url))
+}
+
+/// Where a `Location` header sends a request for `from`.
+fn redirect_target(from: &HttpUrl, location: &str) -> String {
+    if location.contains("://") {
+        return location.to_string();
+    }
+    let origin = HttpUrl {
+        path: String::new(),
+        ..from.clone()
+    };
+    format!("{}{}", origin, location)
+}
+
+/// Send a GET for `url` over TLS and read the whole response.
+///
+/// Only HTTPS URLs get here (see [`fetch_via`]).
+fn get(url: &HttpUrl, timeout: Duration) -> Result<Vec<u8>, String> {
+    let ip = resolve_host(&url.host).ok_or("cannot resolve host")?;
+    let stream = socket::connect(
+        SocketAddr::new(ip, url.port),
+        Purpose::Exchange.profile(),
+        timeout,
+    )
+    .map_err(|e| e.to_string())?;
+    stream
+        .set_read_timeout(Some(timeout))
+        .and_then(|()| stream.set_write_timeout(Some(timeout)))
+        .map_err(|e| e.to_string())?;
+    let host = if url.host.contains(':') {
+        format!("[{}]", url.host)
+    } else {
+        url.host.clone()
+    };
+    let request = format!(
+        "GET {} HTTP/1.1\r\nHost: {}\r\nUser-Agent: asphyxia/{}\r\nAccept: */*\r\nConnection: close\r\n\r\n",
+        url.path,
+        host,
+        env!("CARGO_PKG_VERSION")
+    );
+    let name = ServerName::try_from(url.host.clone()).map_err(|e| e.to_string())?;
+    let connection = ClientConnection::new(verified_config(), name).map_err(|e| e.to_string())?;
+    exchange(StreamOwned::new(connection, stream), &request)
+}
+
+/// Send `request` and read until the server closes the connection.
+fn exchange<S: Read + Write>(mut stream: S, request: &str) -> Result<Vec<u8>, String> {
+    stream
+        .write_all(request.as_bytes())
+        .and_then(|()| stream.flush())
+        .map_err(|e| e.to_string())?;
+    let mut response = Vec::new();
+    let mut buf = [0u8; 16 * 1024];
+    loop {
+        match stream.read(&mut buf) {
+            Ok(0) => break,
+            Ok(n) => response.extend_from_slice(&buf[..n]),
+            // Servers that close without a TLS close_notify have still
+            // sent everything; the body's length is checked below.
+            Err(e) if e.kind() == io::ErrorKind::UnexpectedEof => break,
+            Err(e) => return Err(e.to_string()),
+        }
+        if response.len() > MAX_RESPONSE {
+            return Err("response too large".to_string());
+        }
+    }
+    Ok(response)
+}
+
+/// Split an HTTP/1.1 `response` into its status, `Location` header and
+/// body, undoing chunked transfer encoding.
+fn parse_response(response: &[u8]) -> Result<(u16, Option<String>, Vec<u8>), String> {
+    let end = response
+        .windows(4)
+        .position(|window| window == b"\r\n\r\n")
+        .ok_or("no HTTP answer")?;
+    let head = String::from_utf8_lossy(&response[..end]);
+    let body = &response[end + 4..];
+    let mut lines = head.split("\r\n");
+    let status = lines
+        .next()
+        .and_then(|line| line.split_whitespace().nth(1))
+        .and_then(|code| code.parse().ok())
+        .ok_or("no HTTP answer")?;
+    let mut location = None;
+    let mut chunked = false;
+    let mut length = None;
+    for line in lines {
+        let Some((name, value)) = line.split_once(':') else {
+            continue;
+        };
+        let value = value.trim();
+        match name.trim().to_ascii_lowercase().as_str() {
+            "location" => location = Some(value.to_string()),
+            "transfer-encoding" => chunked = value.eq_ignore_ascii_case("chunked"),
+            "content-length" => length = value.parse::<usize>().ok(),
+            _ => {}
+        }
+    }
+    let body = if chunked {
+        dechunk(body)?
+    } else {
+        match length {
+            Some(length) if body.len() < length => return Err("response cut short".to_string()),
+            Some(length) => body[..length].to_vec(),
+            None => body.to_vec(),
+        }
+    };
+    Ok((status, location, body))
+}
+
+/// Join the chunks of a chunked body.
+fn dechunk(mut body: &[u8]) -> Result<Vec<u8>, String> {
+    let cut_short = || "response cut short".to_string();
+    let mut joined = Vec::new();
+    loop {
+        let line_end = body
+            .windows(2)
+            .position(|window| window == b"\r\n")
+            .ok_or_else(cut_short)?;
+        let size = String::from_utf8_lossy(&body[..line_end]);
+        let size = size.split(';').next().unwrap_or_default().trim();
+        let size = usize::from_str_radix(size, 16)
+            .ok()
+            .filter(|&size| size <= MAX_RESPONSE)
+            .ok_or("invalid chunk size")?;
+        body = &body[line_end + 2..];
+        if size == 0 {
+            return Ok(joined);
+        }
+        if body.len() < size.checked_add(2).ok_or("invalid chunk size")? {
+            return Err(cut_short());
+        }
+        joined.extend_from_slice(&body[..size]);
+        body = &body[size + 2..];
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use ring::rand::SystemRandom;
+    use ring::signature::{Ed25519KeyPair, KeyPair};
+
+    fn release(tag: &str, prerelease: bool) -> Release {
+        Release {
+            version: tag.parse().unwrap(),
+            prerelease,
+            assets: BTreeMap::new(),
+        }
+    }
+
+    #[test]
+    fn test_versions_order_pre_releases_first() {
+        let order = [
+            "0.5.0",
+            "0.6.0-alpha",
+            "0.6.0-beta.2",
+            "0.6.0-beta.10",
+            "0.6.0",
+            "0.10.0",
+        ];
+        let versions: Vec<Version> = order.iter().map(|v| v.parse().unwrap()).collect();
+        assert!(versions.windows(2).all(|pair| pair[0] < pair[1]));
+        assert!("0.6".parse::<Version>().is_err());
+        assert!("v1.2.x".parse::<Version>().is_err());
+    }
+
+    #[test]
+    fn test_latest_follows_the_channel() {
+        let releases = [
+            release("v0.6.0", false),
+            release("v0.7.0-beta.1", true),
+            release("v0.5.0", false),
+        ];
+        let version = |channel| latest(&releases, channel).unwrap().version.to_string();
+        assert_eq!(version(Channel::Stable), "0.6.0");
+        assert_eq!(version(Channel::Beta), "0.7.0-beta.1");
+    }
+
+    #[test]
+    fn test_parse_feed_skips_drafts_and_odd_tags() {
+        let feed = r#"[
+            {"tag_name": "v9.0.0", "draft": true, "prerelease": false, "assets": []},
+            {"tag_name": "nightly", "prerelease": true, "assets": []},
+            {"tag_name": "v0.6.0", "prerelease": false, "assets": []}
+        ]"#;
+        let releases = parse_feed(feed).unwrap();
+        assert_eq!(releases.len(), 1);
+        assert_eq!(releases[0].version.to_string(), "0.6.0");
+        assert!(parse_feed("{}").is_err());
+    }
+
+    #[test]
+    fn test_verify_checksums_needs_the_release_key() {
+        let random = SystemRandom::new();
+        let pkcs8 = Ed25519KeyPair::generate_pkcs8(&random).unwrap();
+        let pair = Ed25519KeyPair::from_pkcs8(pkcs8.as_ref()).unwrap();
+        let key: [u8; 32] = pair.public_key().as_ref().try_into().unwrap();
+        let version: Version = "0.6.0".parse().unwrap();
+        let sums = b"00ff  asphyxia-x86_64-unknown-linux-gnu\n";
+        let signature = pair.sign(b"0.6.0\n00ff  asphyxia-x86_64-unknown-linux-gnu\n");
+        assert!(verify_checksums(&version, sums, signature.as_ref(), &key).is_ok());
+        assert!(verify_checksums(&version, b"11ff  asphyxia\n", signature.as_ref(), &key).is_err());
+        assert!(verify_checksums(&version, sums, signature.as_ref(), &[7; 32]).is_err());
+    }
+
+    #[test]
+    fn test_verify_checksums_rejects_another_release() {
+        let random = SystemRandom::new();
+        let pkcs8 = Ed25519KeyPair::generate_pkcs8(&random).unwrap();
+        let pair = Ed25519KeyPair::from_pkcs8(pkcs8.as_ref()).unwrap();
+        let key: [u8; 32] = pair.public_key().as_ref().try_into().unwrap();
+        // Correctly signed checksums of 0.5.0, served under a newer tag.
+        let sums = b"00ff  asphyxia-x86_64-unknown-linux-gnu\n";
+        let mut signed = b"0.5.0\n".to_vec();
+        signed.extend_from_slice(sums);
+        let signature = pair.sign(&signed);
+        let newer: Version = "v0.6.0".parse().unwrap();
+        let error = verify_checksums(&newer, sums, signature.as_ref(), &key).unwrap_err();
+        assert!(error.contains("for version 0.6.0"));
+        // The bare checksums file, signed the old way, is refused too.
+        let unversioned = pair.sign(sums);
+        assert!(verify_checksums(&newer, sums, unversioned.as_ref(), &key).is_err());
+    }
+
+    #[test]
+    fn test_sha256_hex() {
+        assert_eq!(
+            sha256_hex(b"abc"),
+            "ba7816bf8f01cfea414140de5dae2223b00361a396177a9cb410ff61f20015ad"
+        );
+    }
+
+    #[test]
+    fn test_parse_response_dechunks_and_redirects() {
+        let chunked = b"HTTP/1.1 200 OK\r\nTransfer-Encoding: chunked\r\n\r\n4\r\nasph\r\n4;x=1\r\nyxia\r\n0\r\n\r\n";
+        let (status, _, body) = parse_response(chunked).unwrap();
+        assert_eq!((status, body.as_slice()), (200, &b"asphyxia"[..]));
+
+        let redirect = b"HTTP/1.1 302 Found\r\nLocation: /assets/1\r\nContent-Length: 0\r\n\r\n";
+        let (status, location, _) = parse_response(redirect).unwrap();
+        assert_eq!((status, location.as_deref()), (302, Some("/assets/1")));
+        let from: HttpUrl = "http://mirror.corp:8080/releases".parse().unwrap();
+        assert_eq!(
+            redirect_target(&from, "/assets/1"),
+            "http://mirror.corp:8080/assets/1"
+        );
+
+        let short = b"HTTP/1.1 200 OK\r\nContent-Length: 10\r\n\r\nasph";
+        assert!(parse_response(short).is_err());
+    }
+
+    #[test]
+    fn test_dechunk_rejects_huge_chunk_sizes() {
+        assert_eq!(
+            dechunk(b"ffffffffffffffff\r\nasph\r\n0\r\n\r\n"),
+            Err("invalid chunk size".to_string())
+        );
+        let too_large = format!("{:x}\r\nasph\r\n0\r\n\r\n", MAX_RESPONSE + 1);
+        assert!(dechunk(too_large.as_bytes()).is_err());
+    }
+
+    #[test]
+    fn test_fetch_follows_redirects_over_https_only() {
+        let server = |url: &HttpUrl| {
+            Ok(match url.path.as_str() {
+                "/feed" => {
+                    b"HTTP/1.1 302 Found\r\nLocation: /binary\r\nContent-Length: 0\r\n\r\n".to_vec()
+                }
+                "/binary" => b"HTTP/1.1 200 OK\r\nContent-Length: 8\r\n\r\nasphyxia".to_vec(),
+                _ => b"HTTP/1.1 302 Found\r\nLocation: http://mirror.corp/binary\r\n\r\n".to_vec(),
+            })
+        };
+        assert_eq!(
+            fetch_via("https://mirror.corp/feed", server).unwrap(),
+            b"asphyxia"
+        );
+        let downgraded = fetch_via("https://mirror.corp/old", server).unwrap_err();
+        assert!(downgraded.contains("plain HTTP"), "{}", downgraded);
+        let plain = fetch_via("http://mirror.corp/feed", server).unwrap_err();
+        assert!(plain.contains("plain HTTP"), "{}", plain);
+    }
+
+    #[test]
+    fn test_replace_swaps_the_binary() {
+        let exe = std::env::temp_dir().join(format!("asphyxia-update-{}", std::process::id()));
+        fs::write(&exe, b"old").unwrap();
+        replace(&exe, b"new").unwrap();
+        assert_eq!(fs::read(&exe).unwrap(), b"new");
+        fs::remove_file(&exe).unwrap();
+    }
+}
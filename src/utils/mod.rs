@@ -1,6 +1,13 @@
-use indicatif::{ProgressBar, ProgressStyle};
+pub mod netif;
+pub mod url;
+
 use ipnetwork::IpNetwork;
-use std::net::IpAddr;
+use std::net::{IpAddr, Ipv4Addr, Ipv6Addr, ToSocketAddrs};
+use std::path::Path;
+use std::time::Duration;
+
+use crate::scanner::address::{MAX_IPV6_HOSTS, range_size, subnet_size};
+use crate::scanner::services::port_for_service;
 
 /// Hard upper bound on the number of concurrent connection attempts.
 ///
@@ -32,42 +39,126 @@ pub fn init_scan_pool(concurrency: usize) {
         .expect("failed to initialise the scan thread pool");
 }
 
-/// Build a styled progress bar for a scan of `total` items.
+/// Shortest connection timeout accepted on the command line.
+pub const MIN_TIMEOUT: Duration = Duration::from_millis(1);
+
+/// Longest connection timeout accepted on the command line. Operating systems
+/// give up on an unanswered handshake well before this anyway.
+pub const MAX_TIMEOUT: Duration = Duration::from_secs(60);
+
+/// Parse a human-friendly duration such as `"500ms"`, `"2s"`, or `"1m30s"`.
+///
+/// A duration is one or more `<number><unit>` components, where the unit is
+/// `ms`, `s`, `m`, or `h`; components are summed. A bare number with no unit
+/// is read as milliseconds, which keeps older invocations like
+/// `--timeout 500` working.
 ///
-/// The `suffix` is appended after the `pos/len` counter (e.g. `"ports scanned"`
-/// or `"addresses scanned"`), so both the port and address scanners can share
-/// the same bar style.
+/// # Arguments
+///
+/// * `s` - The duration string to parse
+///
+/// # Returns
+///
+/// * `Result<Duration, String>` - The parsed duration if successful,
+///   or an error message if parsing failed
+///
+/// # Examples
+///
+/// ```
+/// use asphyxia::utils::parse_duration;
+/// use std::time::Duration;
+///
+/// assert_eq!(parse_duration("500ms"), Ok(Duration::from_millis(500)));
+/// assert_eq!(parse_duration("1m30s"), Ok(Duration::from_secs(90)));
+/// assert_eq!(parse_duration("250"), Ok(Duration::from_millis(250)));
+/// assert!(parse_duration("2 parsecs").is_err());
+/// ```
+pub fn parse_duration(s: &str) -> Result<Duration, String> {
+    let invalid = || format!("Invalid duration: {} (expected e.g. 500ms, 2s, 1m30s)", s);
+    let trimmed = s.trim();
+    if trimmed.is_empty() {
+        return Err(invalid());
+    }
+    if let Ok(ms) = trimmed.parse::<u64>() {
+        return Ok(Duration::from_millis(ms));
+    }
+
+    let mut total = Duration::ZERO;
+    let mut rest = trimmed;
+    while !rest.is_empty() {
+        let digits = rest
+            .find(|c: char| !c.is_ascii_digit())
+            .unwrap_or(rest.len());
+        if digits == 0 {
+            return Err(invalid());
+        }
+        let value: u64 = rest[..digits].parse().map_err(|_| invalid())?;
+        rest = &rest[digits..];
+
+        let unit_len = rest
+            .find(|c: char| c.is_ascii_digit())
+            .unwrap_or(rest.len());
+        let component = match &rest[..unit_len] {
+            "ms" => Duration::from_millis(value),
+            "s" => Duration::from_secs(value),
+            "m" => Duration::from_secs(value.saturating_mul(60)),
+            "h" => Duration::from_secs(value.saturating_mul(3600)),
+            _ => return Err(invalid()),
+        };
+        rest = &rest[unit_len..];
+        total = total.checked_add(component).ok_or_else(invalid)?;
+    }
+    Ok(total)
+}
+
+/// Parse a connection timeout with [`parse_duration`] and check that it lies
+/// within [`MIN_TIMEOUT`]..=[`MAX_TIMEOUT`].
 ///
 /// # Examples
 ///
 /// ```
-/// use asphyxia::utils::progress_bar;
+/// use asphyxia::utils::parse_timeout;
+/// use std::time::Duration;
 ///
-/// let pb = progress_bar(100, "ports scanned");
-/// pb.finish_and_clear();
+/// assert_eq!(parse_timeout("300ms"), Ok(Duration::from_millis(300)));
+/// assert!(parse_timeout("0s").is_err());
+/// assert!(parse_timeout("10m").is_err());
 /// ```
-pub fn progress_bar(total: u64, suffix: &str) -> ProgressBar {
-    let pb = ProgressBar::new(total);
-    pb.set_style(
-        ProgressStyle::with_template(&format!(
-            "[{{elapsed_precise}}] {{bar:40.cyan/blue}} {{pos}}/{{len}} {suffix}"
-        ))
-        .unwrap()
-        .progress_chars("=> "),
-    );
-    pb
+pub fn parse_timeout(s: &str) -> Result<Duration, String> {
+    parse_duration_in(s, MIN_TIMEOUT, MAX_TIMEOUT)
 }
 
-/// Parse a comma-separated string of port numbers into a vector of u16
+/// Parse a duration with [`parse_duration`] and check that it lies within
+/// `min..=max`, so every time flag reports out-of-range values the same way.
+pub fn parse_duration_in(s: &str, min: Duration, max: Duration) -> Result<Duration, String> {
+    let duration = parse_duration(s)?;
+    if duration < min || duration > max {
+        return Err(format!(
+            "Duration {} is out of range (must be between {:?} and {:?})",
+            s.trim(),
+            min,
+            max
+        ));
+    }
+    Ok(duration)
+}
+
+/// Parse a comma-separated string of ports into a vector of u16
+///
+/// Each entry is a port number, an inclusive range such as `137-139`, or a
+/// service name such as `ssh` or `https`, resolved through
+/// [`port_for_service`] (case-insensitive, with `/etc/services` taking
+/// precedence over the embedded table). Entries may be mixed freely; the
+/// result is sorted and each port appears once, however often it was listed.
 ///
 /// # Arguments
 ///
-/// * `s` - A string containing comma-separated port numbers
+/// * `s` - A string containing comma-separated port numbers, ranges or service names
 ///
 /// # Returns
 ///
-/// * `Result<Vec<u16>, String>` - A vector of port numbers if parsing was successful,
-///   or an error message if parsing failed
+/// * `Result<Vec<u16>, String>` - A sorted, deduplicated vector of port numbers if
+///   parsing was successful, or an error message if parsing failed
 ///
 /// # Examples
 ///
@@ -75,15 +166,39 @@ pub fn progress_bar(total: u64, suffix: &str) -> ProgressBar {
 /// use asphyxia::utils::parse_ports;
 ///
 /// assert_eq!(parse_ports("22,80,443"), Ok(vec![22, 80, 443]));
+/// assert_eq!(parse_ports("ssh,https,8080"), Ok(vec![22, 443, 8080]));
+/// assert_eq!(parse_ports("137-139,445"), Ok(vec![137, 138, 139, 445]));
+/// assert_eq!(parse_ports("443,80-82,http,22"), Ok(vec![22, 80, 81, 82, 443]));
+///
+/// let ports = parse_ports("22,80,8000-8100,443").unwrap();
+/// assert_eq!(ports.len(), 104);
+/// assert_eq!(ports[..3], [22, 80, 443]);
 /// assert!(parse_ports("22,abc,443").is_err());
+/// assert!(parse_ports("139-137").is_err());
 /// ```
 pub fn parse_ports(s: &str) -> Result<Vec<u16>, String> {
-    s.split(',')
-        .map(|p| {
-            p.parse::<u16>()
-                .map_err(|_| format!("Invalid port number: {}", p))
-        })
-        .collect()
+    let mut ports = Vec::new();
+    for p in s.split(',') {
+        let p = p.trim();
+        if let Some(port) = p.parse::<u16>().ok().or_else(|| port_for_service(p)) {
+            ports.push(port);
+        } else if let Some((start, end)) = p.split_once('-')
+            && let (Ok(start), Ok(end)) = (start.trim().parse::<u16>(), end.trim().parse::<u16>())
+        {
+            if start > end {
+                return Err(format!("Invalid port range: {} (start must be <= end)", p));
+            }
+            ports.extend(start..=end);
+        } else {
+            return Err(format!(
+                "Invalid port number: {} (not a number, a range or a known service name)",
+                p
+            ));
+        }
+    }
+    ports.sort_unstable();
+    ports.dedup();
+    Ok(ports)
 }
 
 /// Parse a string into an IP address (IPv4 or IPv6)
@@ -111,6 +226,113 @@ pub fn parse_ip(ip: &str) -> Result<IpAddr, String> {
         .map_err(|_| format!("Invalid IP address: {}", ip))
 }
 
+/// Resolve a scan target to the addresses it names
+///
+/// An IPv4 or IPv6 literal yields just that address. Anything else is looked
+/// up as a hostname and every address it resolves to (A and AAAA records) is
+/// returned once, in resolver order.
+///
+/// # Arguments
+///
+/// * `target` - An IP address or a hostname (e.g. `myserver.local`)
+///
+/// # Returns
+///
+/// * `Result<Vec<IpAddr>, String>` - The target's addresses (never empty),
+///   or an error message if it is neither an IP nor a resolvable hostname
+///
+/// # Examples
+///
+/// ```
+/// use asphyxia::utils::resolve_target;
+/// use std::net::IpAddr;
+///
+/// let ip: IpAddr = "10.0.0.1".parse().unwrap();
+/// assert_eq!(resolve_target("10.0.0.1").unwrap(), vec![ip]);
+/// assert!(!resolve_target("localhost").unwrap().is_empty());
+/// ```
+pub fn resolve_target(target: &str) -> Result<Vec<IpAddr>, String> {
+    if let Ok(ip) = target.parse::<IpAddr>() {
+        return Ok(vec![ip]);
+    }
+    let mut addrs: Vec<IpAddr> = Vec::new();
+    if let Ok(resolved) = (target, 0).to_socket_addrs() {
+        for addr in resolved {
+            if !addrs.contains(&addr.ip()) {
+                addrs.push(addr.ip());
+            }
+        }
+    }
+    if addrs.is_empty() {
+        return Err(format!(
+            "Invalid IP address: {} (and it does not resolve as a hostname)",
+            target
+        ));
+    }
+    Ok(addrs)
+}
+
+/// Look up the hostname of an address with a reverse (PTR) DNS query
+///
+/// # Arguments
+///
+/// * `ip` - The address to look up (IPv4 or IPv6)
+///
+/// # Returns
+///
+/// * `Option<String>` - The address's hostname, or `None` if it has no PTR
+///   record or the lookup failed
+///
+/// # Examples
+///
+/// ```no_run
+/// use asphyxia::utils::reverse_lookup;
+///
+/// if let Some(name) = reverse_lookup("192.168.1.1".parse().unwrap()) {
+///     println!("192.168.1.1 is {}", name);
+/// }
+/// ```
+pub fn reverse_lookup(ip: IpAddr) -> Option<String> {
+    // Without a PTR record the resolver echoes the address back as text.
+    dns_lookup::lookup_addr(&ip)
+        .ok()
+        .filter(|name| name.parse::<IpAddr>().is_err())
+}
+
+/// Parse a comma-separated list of addresses and subnets to exclude
+///
+/// # Arguments
+///
+/// * `s` - IP addresses and CIDR subnets separated by commas
+///   (e.g. "192.168.1.1,192.168.1.0/30")
+///
+/// # Returns
+///
+/// * `Result<Vec<IpNetwork>, String>` - One network per entry (a bare address
+///   becomes a single-host network), or an error message for the first
+///   invalid entry
+///
+/// # Examples
+///
+/// ```
+/// use asphyxia::utils::parse_exclusions;
+///
+/// let excluded = parse_exclusions("192.168.1.1, 192.168.1.0/30").unwrap();
+/// assert!(excluded[0].contains("192.168.1.1".parse().unwrap()));
+/// assert!(!excluded[0].contains("192.168.1.2".parse().unwrap()));
+/// assert!(parse_exclusions("192.168.1.1,printer").is_err());
+/// ```
+pub fn parse_exclusions(s: &str) -> Result<Vec<IpNetwork>, String> {
+    s.split(',')
+        .map(|entry| {
+            let entry = entry.trim();
+            entry
+                .parse::<IpNetwork>()
+                .map_err(|_| format!("Invalid exclusion: {} (expected an IP or CIDR)", entry))
+        })
+        .collect()
+}
+
 /// Parse a string into an IP subnet (IPv4 or IPv6)
 ///
 /// # Arguments
@@ -137,3 +359,215 @@ pub fn parse_subnet(subnet: &str) -> Result<IpNetwork, String> {
         .parse::<IpNetwork>()
         .map_err(|_| format!("Invalid subnet format: {}", subnet))
 }
+
+/// Something to scan: a hostname, an address, a subnet or an address range
+///
+/// Every way of naming targets on the command line and in targets files goes
+/// through [`Target::from_str`], so `example.com`, `10.0.0.5`, `10.0.0.0/24`
+/// and `10.0.0.1-10.0.0.50` are accepted wherever a target is.
+///
+/// # Examples
+///
+/// ```
+/// use asphyxia::utils::Target;
+///
+/// let target: Target = "10.0.0.1-10.0.0.50".parse().unwrap();
+/// assert_eq!(target.addresses().unwrap().len(), 50);
+/// ```
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Target {
+    /// A hostname, resolved when it is scanned, e.g. `db.internal`
+    Hostname(String),
+    /// An IPv4 or IPv6 address, e.g. `10.0.0.5`
+    Ip(IpAddr),
+    /// A subnet in CIDR notation, e.g. `10.0.0.0/24`
+    Cidr(IpNetwork),
+    /// An inclusive address range, e.g. `10.0.0.1-10.0.0.50`
+    Range(IpAddr, IpAddr),
+}
+
+impl Target {
+    /// Every address this target names.
+    ///
+    /// A hostname is resolved with [`resolve_target`]. A subnet or range is
+    /// expanded to all of its addresses, and refused if that is more than
+    /// [`MAX_IPV6_HOSTS`](crate::scanner::address::MAX_IPV6_HOSTS) (use an
+    /// address scan to find the live hosts of a large subnet first).
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use asphyxia::utils::Target;
+    ///
+    /// let target: Target = "10.0.0.0/30".parse().unwrap();
+    /// assert_eq!(target.addresses().unwrap().len(), 4);
+    /// ```
+    pub fn addresses(&self) -> Result<Vec<IpAddr>, String> {
+        let limit = MAX_IPV6_HOSTS;
+        match self {
+            Target::Hostname(name) => resolve_target(name),
+            Target::Ip(ip) => Ok(vec![*ip]),
+            Target::Cidr(network) => {
+                let size = subnet_size(*network);
+                if size > limit {
+                    return Err(format!(
+                        "Subnet {} has {} addresses, more than the {} allowed",
+                        network, size, limit
+                    ));
+                }
+                Ok(network.iter().collect())
+            }
+            Target::Range(start, end) => {
+                let size =
+                    range_size(*start, *end).ok_or_else(|| format!("Invalid range: {}", self))?;
+                if size > limit {
+                    return Err(format!(
+                        "Range {} has {} addresses, more than the {} allowed",
+                        self, size, limit
+                    ));
+                }
+                let (start, end) = match (start, end) {
+                    (IpAddr::V4(s), IpAddr::V4(e)) => {
+                        (u128::from(u32::from(*s)), u128::from(u32::from(*e)))
+                    }
+                    (IpAddr::V6(s), IpAddr::V6(e)) => (u128::from(*s), u128::from(*e)),
+                    // range_size() has already refused mixed families.
+                    _ => unreachable!(),
+                };
+                Ok((start..=end)
+                    .map(|n| match self {
+                        Target::Range(IpAddr::V4(_), _) => IpAddr::from(Ipv4Addr::from(n as u32)),
+                        _ => IpAddr::from(Ipv6Addr::from(n)),
+                    })
+                    .collect())
+            }
+        }
+    }
+}
+
+impl std::str::FromStr for Target {
+    type Err = String;
+
+    /// Parse a target: a subnet if it contains `/`, a range if it is two
+    /// addresses joined by `-`, otherwise an IP address, otherwise a hostname.
+    fn from_str(target: &str) -> Result<Target, String> {
+        if target.contains('/') {
+            return parse_subnet(target).map(Target::Cidr);
+        }
+        if let Ok(ip) = target.parse::<IpAddr>() {
+            return Ok(Target::Ip(ip));
+        }
+        if let Some((start, end)) = target.split_once('-')
+            && let (Ok(start), Ok(end)) = (start.parse::<IpAddr>(), end.parse::<IpAddr>())
+        {
+            if start.is_ipv4() != end.is_ipv4() || start > end {
+                return Err(format!("Invalid range: {}", target));
+            }
+            return Ok(Target::Range(start, end));
+        }
+        if target.is_empty() || target.contains(char::is_whitespace) {
+            return Err(format!("Invalid target: {}", target));
+        }
+        Ok(Target::Hostname(target.to_string()))
+    }
+}
+
+impl std::fmt::Display for Target {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Target::Hostname(name) => f.write_str(name),
+            Target::Ip(ip) => write!(f, "{}", ip),
+            Target::Cidr(network) => write!(f, "{}", network),
+            Target::Range(start, end) => write!(f, "{}-{}", start, end),
+        }
+    }
+}
+
+/// Parse one target (see [`Target::from_str`])
+///
+/// # Arguments
+///
+/// * `target` - A hostname, an IP address, a subnet in CIDR notation, or a
+///   range of two addresses joined by `-`
+///
+/// # Returns
+///
+/// * `Result<Target, String>` - The parsed target, or an error message for an
+///   invalid subnet or range, or a hostname containing whitespace
+///
+/// # Examples
+///
+/// ```
+/// use asphyxia::utils::{Target, parse_target};
+///
+/// assert!(matches!(parse_target("192.168.1.0/24"), Ok(Target::Cidr(_))));
+/// assert!(matches!(parse_target("2001:db8::1"), Ok(Target::Ip(_))));
+/// assert!(matches!(parse_target("10.0.0.1-10.0.0.50"), Ok(Target::Range(..))));
+/// assert!(matches!(parse_target("nas-01.lan"), Ok(Target::Hostname(_))));
+/// assert!(parse_target("10.0.0.0/33").is_err());
+/// assert!(parse_target("10.0.0.9-10.0.0.1").is_err());
+/// ```
+pub fn parse_target(target: &str) -> Result<Target, String> {
+    target.parse()
+}
+
+/// Parse a list of targets, one per line
+///
+/// Everything after a `#` is a comment; blank lines are skipped.
+///
+/// # Arguments
+///
+/// * `contents` - The text of a targets file
+///
+/// # Returns
+///
+/// * `Result<Vec<Target>, String>` - The targets in file order, or an error
+///   message naming the first invalid line
+///
+/// # Examples
+///
+/// ```
+/// use asphyxia::utils::parse_targets;
+///
+/// let targets = parse_targets("# office\n10.0.0.0/24\nnas.lan  # storage\n\n").unwrap();
+/// assert_eq!(targets.len(), 2);
+/// assert!(parse_targets("10.0.0.1\n10.0.0.0/99\n").unwrap_err().starts_with("line 2:"));
+/// ```
+pub fn parse_targets(contents: &str) -> Result<Vec<Target>, String> {
+    contents
+        .lines()
+        .enumerate()
+        .filter_map(|(index, line)| {
+            let line = line.split('#').next().unwrap_or_default().trim();
+            (!line.is_empty()).then_some((index + 1, line))
+        })
+        .map(|(number, line)| parse_target(line).map_err(|e| format!("line {}: {}", number, e)))
+        .collect()
+}
+
+/// Read and parse a targets file (see [`parse_targets`])
+///
+/// # Arguments
+///
+/// * `path` - The file to read
+///
+/// # Returns
+///
+/// * `Result<Vec<Target>, String>` - The targets in file order, or an error
+///   message if the file cannot be read or a line is invalid
+///
+/// # Examples
+///
+/// ```no_run
+/// use asphyxia::utils::parse_targets_file;
+/// use std::path::Path;
+///
+/// for target in parse_targets_file(Path::new("targets.txt")).unwrap() {
+///     println!("{}", target);
+/// }
+/// ```
+pub fn parse_targets_file(path: &Path) -> Result<Vec<Target>, String> {
+    let contents = std::fs::read_to_string(path)
+        .map_err(|e| format!("Cannot read targets file {}: {}", path.display(), e))?;
+    parse_targets(&contents).map_err(|e| format!("Invalid targets file {}: {}", path.display(), e))
+}
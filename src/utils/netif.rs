@@ -0,0 +1,505 @@
+//! Reading the operating system's network interface state.
+//!
+//! The kernel already knows about hosts it has talked to recently: every
+//! completed entry in the ARP (IPv4 neighbor) table is a host that answered
+//! on the local link. Harvesting that table gives free coverage of hosts
+//! before a single probe is sent. The routing table likewise names the
+//! default gateway, the usual first stop when auditing a site, and the
+//! subnets on directly attached links, where ARP can find every host.
+//! The interfaces themselves, with their addresses, tell which subnets the
+//! host sits on, and are looked up by name for probes bound to one of them.
+//! The one the OS reaches the internet through is the primary interface, and
+//! its subnet the natural network to scan when none is given.
+
+use ipnetwork::{IpNetwork, Ipv4Network};
+use std::net::{IpAddr, Ipv4Addr, UdpSocket};
+use std::num::NonZeroU32;
+
+/// An address off every local link (TEST-NET-1), used to ask which local
+/// address the default route sends from; nothing is ever sent to it.
+const ROUTE_PROBE: (Ipv4Addr, u16) = (Ipv4Addr::new(192, 0, 2, 1), 53);
+
+/// Path of the kernel's ARP table on Linux.
+#[cfg(target_os = "linux")]
+const PROC_NET_ARP: &str = "/proc/net/arp";
+
+/// Path of the kernel's IPv4 routing table on Linux.
+#[cfg(target_os = "linux")]
+const PROC_NET_ROUTE: &str = "/proc/net/route";
+
+/// `ATF_COM` — the entry holds a resolved hardware address.
+const ATF_COM: u32 = 0x2;
+
+/// `RTF_UP` — the route is usable.
+const RTF_UP: u32 = 0x1;
+
+/// `RTF_GATEWAY` — the route goes through a gateway.
+const RTF_GATEWAY: u32 = 0x2;
+
+/// A host in the OS ARP table.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Neighbor {
+    /// The host's IPv4 address.
+    pub ip: IpAddr,
+    /// Its hardware (MAC) address, e.g. `00:11:22:33:44:55`.
+    pub mac: String,
+}
+
+/// A network interface of the scanning host.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Interface {
+    /// Its name, e.g. `eth1` or `utun3`.
+    pub name: String,
+    /// The index the kernel knows it by.
+    pub index: NonZeroU32,
+    /// Its IPv4 and IPv6 addresses, each with the prefix length of its
+    /// netmask (e.g. `192.168.1.20/24`).
+    pub addresses: Vec<IpNetwork>,
+    /// Whether the interface is administratively up.
+    pub up: bool,
+    /// Whether it is a loopback interface.
+    pub loopback: bool,
+}
+
+impl Interface {
+    /// Look up the interface called `name`.
+    ///
+    /// Fails if there is no such interface, and on platforms without
+    /// `getifaddrs` (Windows).
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use asphyxia::utils::netif::Interface;
+    ///
+    /// assert!(Interface::named("no-such-if0").is_err());
+    /// ```
+    pub fn named(name: &str) -> Result<Interface, String> {
+        if cfg!(not(unix)) {
+            return Err(format!(
+                "Cannot look up interface {}: not supported on this platform",
+                name
+            ));
+        }
+        list_interfaces()
+            .into_iter()
+            .find(|interface| interface.name == name)
+            .ok_or_else(|| format!("No such network interface: {}", name))
+    }
+
+    /// The subnets its addresses are on, e.g. `192.168.1.0/24` for
+    /// `192.168.1.20/24`.
+    pub fn networks(&self) -> Vec<IpNetwork> {
+        self.addresses
+            .iter()
+            .filter_map(|address| IpNetwork::new(address.network(), address.prefix()).ok())
+            .collect()
+    }
+}
+
+/// Return the network interfaces of the scanning host with their
+/// addresses, in the order the OS lists them.
+///
+/// Interfaces without an address are listed too. The list is read with
+/// `getifaddrs` on Unix; other platforms return an empty list.
+///
+/// # Examples
+///
+/// ```
+/// use asphyxia::utils::netif::list_interfaces;
+///
+/// for interface in list_interfaces() {
+///     for address in &interface.addresses {
+///         println!("{}: {}", interface.name, address);
+///     }
+/// }
+/// ```
+pub fn list_interfaces() -> Vec<Interface> {
+    #[cfg(unix)]
+    {
+        read_interfaces()
+    }
+    #[cfg(not(unix))]
+    {
+        Vec::new()
+    }
+}
+
+/// Return the primary interface of the scanning host and its IPv4 subnet,
+/// e.g. `eth0` and `192.168.1.0/24`.
+///
+/// The primary interface carries the address the OS sends from to reach the
+/// internet, asked by connecting (not sending from) a UDP socket. Without a
+/// default route, the first interface that is up with an IPv4 address is
+/// taken instead; loopback never counts. `None` if there is no such
+/// interface.
+///
+/// # Examples
+///
+/// ```
+/// use asphyxia::utils::netif::primary_network;
+///
+/// if let Some((interface, network)) = primary_network() {
+///     println!("{} is on {}", interface.name, network);
+/// }
+/// ```
+pub fn primary_network() -> Option<(Interface, IpNetwork)> {
+    let routed = UdpSocket::bind((Ipv4Addr::UNSPECIFIED, 0))
+        .and_then(|socket| {
+            socket.connect(ROUTE_PROBE)?;
+            socket.local_addr()
+        })
+        .ok()
+        .map(|local| local.ip());
+    let interfaces = list_interfaces();
+    let candidates: Vec<(&Interface, &IpNetwork)> = interfaces
+        .iter()
+        .filter(|interface| interface.up && !interface.loopback)
+        .flat_map(|interface| {
+            interface
+                .addresses
+                .iter()
+                .map(move |address| (interface, address))
+        })
+        .filter(|(_, address)| address.is_ipv4())
+        .collect();
+    let (interface, address) = candidates
+        .iter()
+        .find(|(_, address)| Some(address.ip()) == routed)
+        .or_else(|| candidates.first())?;
+    let network = IpNetwork::new(address.network(), address.prefix()).ok()?;
+    Some(((*interface).clone(), network))
+}
+
+/// Walk the list `getifaddrs` returns, one entry per interface address.
+#[cfg(unix)]
+fn read_interfaces() -> Vec<Interface> {
+    use std::ffi::CStr;
+
+    let mut head: *mut libc::ifaddrs = std::ptr::null_mut();
+    // SAFETY: on success getifaddrs points `head` at a list that stays
+    // valid until it is given back to freeifaddrs below.
+    if unsafe { libc::getifaddrs(&mut head) } != 0 {
+        return Vec::new();
+    }
+    let mut interfaces: Vec<Interface> = Vec::new();
+    let mut entry = head;
+    while !entry.is_null() {
+        // SAFETY: `entry` is a node of the list, which is still allocated.
+        let ifaddr = unsafe { &*entry };
+        entry = ifaddr.ifa_next;
+        // SAFETY: every entry carries a NUL-terminated interface name.
+        let name = unsafe { CStr::from_ptr(ifaddr.ifa_name) };
+        let position = match interfaces
+            .iter()
+            .position(|interface| interface.name.as_bytes() == name.to_bytes())
+        {
+            Some(position) => position,
+            None => {
+                // SAFETY: if_nametoindex only reads the string it is given.
+                let index = unsafe { libc::if_nametoindex(name.as_ptr()) };
+                // An interface that went away since the list was taken.
+                let Some(index) = NonZeroU32::new(index) else {
+                    continue;
+                };
+                let flags = ifaddr.ifa_flags as libc::c_int;
+                interfaces.push(Interface {
+                    name: name.to_string_lossy().into_owned(),
+                    index,
+                    addresses: Vec::new(),
+                    up: flags & libc::IFF_UP != 0,
+                    loopback: flags & libc::IFF_LOOPBACK != 0,
+                });
+                interfaces.len() - 1
+            }
+        };
+        // SAFETY: the address and netmask are null or point to socket
+        // addresses of the family they name.
+        let address = unsafe { (ip_of(ifaddr.ifa_addr), ip_of(ifaddr.ifa_netmask)) };
+        if let (Some(ip), Some(mask)) = address
+            && let Ok(prefix) = ipnetwork::ip_mask_to_prefix(mask)
+            && let Ok(address) = IpNetwork::new(ip, prefix)
+        {
+            interfaces[position].addresses.push(address);
+        }
+    }
+    // SAFETY: `head` came from getifaddrs and is freed only once.
+    unsafe { libc::freeifaddrs(head) };
+    interfaces
+}
+
+/// The IP address in the socket address at `addr`, if it is an IPv4 or IPv6
+/// one.
+///
+/// # Safety
+///
+/// `addr` must be null or point to a socket address as large as its family
+/// says.
+#[cfg(unix)]
+unsafe fn ip_of(addr: *const libc::sockaddr) -> Option<IpAddr> {
+    if addr.is_null() {
+        return None;
+    }
+    // SAFETY: the caller vouches for `addr`.
+    unsafe {
+        match libc::c_int::from((*addr).sa_family) {
+            libc::AF_INET => {
+                let addr = &*(addr as *const libc::sockaddr_in);
+                Some(IpAddr::V4(Ipv4Addr::from(u32::from_be(
+                    addr.sin_addr.s_addr,
+                ))))
+            }
+            libc::AF_INET6 => {
+                let addr = &*(addr as *const libc::sockaddr_in6);
+                Some(IpAddr::V6(addr.sin6_addr.s6_addr.into()))
+            }
+            _ => None,
+        }
+    }
+}
+
+/// Return the addresses of every host in the OS ARP table.
+///
+/// Only completed entries are returned; addresses the kernel asked for but
+/// never got an answer from are skipped. The table is read from
+/// `/proc/net/arp` on Linux; other platforms return an empty list.
+///
+/// # Examples
+///
+/// ```
+/// use asphyxia::utils::netif::neighbor_hosts;
+///
+/// for ip in neighbor_hosts() {
+///     println!("{ip} is in the ARP table");
+/// }
+/// ```
+pub fn neighbor_hosts() -> Vec<IpAddr> {
+    neighbors()
+        .into_iter()
+        .map(|neighbor| neighbor.ip)
+        .collect()
+}
+
+/// Return every completed entry in the OS ARP table, with its MAC address.
+///
+/// Like [`neighbor_hosts`], this reads `/proc/net/arp` on Linux and returns
+/// an empty list on other platforms.
+///
+/// # Examples
+///
+/// ```
+/// use asphyxia::utils::netif::neighbors;
+///
+/// for neighbor in neighbors() {
+///     println!("{} is at {}", neighbor.ip, neighbor.mac);
+/// }
+/// ```
+pub fn neighbors() -> Vec<Neighbor> {
+    #[cfg(target_os = "linux")]
+    {
+        std::fs::read_to_string(PROC_NET_ARP)
+            .map(|contents| parse_neighbors(&contents))
+            .unwrap_or_default()
+    }
+    #[cfg(not(target_os = "linux"))]
+    {
+        Vec::new()
+    }
+}
+
+/// Parse the contents of `/proc/net/arp` into the addresses of its completed
+/// entries.
+///
+/// Lines that do not parse (including the header) are ignored.
+///
+/// # Examples
+///
+/// ```
+/// use asphyxia::utils::netif::parse_arp_table;
+///
+/// let table = "\
+/// IP address       HW type     Flags       HW address            Mask     Device
+/// 192.168.1.1      0x1         0x2         00:11:22:33:44:55     *        eth0
+/// 192.168.1.77     0x1         0x0         00:00:00:00:00:00     *        eth0
+/// ";
+/// let hosts = parse_arp_table(table);
+/// assert_eq!(hosts, vec!["192.168.1.1".parse::<std::net::IpAddr>().unwrap()]);
+/// ```
+pub fn parse_arp_table(contents: &str) -> Vec<IpAddr> {
+    parse_neighbors(contents)
+        .into_iter()
+        .map(|neighbor| neighbor.ip)
+        .collect()
+}
+
+/// Parse the contents of `/proc/net/arp` into its completed entries, with
+/// their MAC addresses.
+///
+/// Lines that do not parse (including the header) are ignored.
+///
+/// # Examples
+///
+/// ```
+/// use asphyxia::utils::netif::parse_neighbors;
+///
+/// let table = "\
+/// IP address       HW type     Flags       HW address            Mask     Device
+/// 192.168.1.1      0x1         0x2         00:11:22:33:44:55     *        eth0
+/// ";
+/// assert_eq!(parse_neighbors(table)[0].mac, "00:11:22:33:44:55");
+/// ```
+pub fn parse_neighbors(contents: &str) -> Vec<Neighbor> {
+    contents
+        .lines()
+        .filter_map(|line| {
+            let mut fields = line.split_whitespace();
+            let ip: Ipv4Addr = fields.next()?.parse().ok()?;
+            let flags = fields.nth(1)?.strip_prefix("0x")?;
+            let flags = u32::from_str_radix(flags, 16).ok()?;
+            let mac = fields.next()?;
+            (flags & ATF_COM != 0).then(|| Neighbor {
+                ip: IpAddr::V4(ip),
+                mac: mac.to_string(),
+            })
+        })
+        .collect()
+}
+
+/// Return the IPv4 default gateway from the OS routing table.
+///
+/// The table is read from `/proc/net/route` on Linux; other platforms (and
+/// hosts without a default route) return `None`.
+///
+/// # Examples
+///
+/// ```
+/// use asphyxia::utils::netif::default_gateway;
+///
+/// if let Some(gateway) = default_gateway() {
+///     println!("default gateway: {gateway}");
+/// }
+/// ```
+pub fn default_gateway() -> Option<IpAddr> {
+    #[cfg(target_os = "linux")]
+    {
+        let contents = std::fs::read_to_string(PROC_NET_ROUTE).ok()?;
+        parse_route_table(&contents).map(IpAddr::V4)
+    }
+    #[cfg(not(target_os = "linux"))]
+    {
+        None
+    }
+}
+
+/// Parse the contents of `/proc/net/route` and return the gateway of the
+/// first default route.
+///
+/// Addresses in that file are hexadecimal in host (little-endian) byte order.
+///
+/// # Examples
+///
+/// ```
+/// use asphyxia::utils::netif::parse_route_table;
+///
+/// let table = "\
+/// Iface\tDestination\tGateway \tFlags\tRefCnt\tUse\tMetric\tMask
+/// eth0\t00000000\t0101A8C0\t0003\t0\t0\t0\t00000000
+/// eth0\t0001A8C0\t00000000\t0001\t0\t0\t0\t00FFFFFF
+/// ";
+/// assert_eq!(parse_route_table(table), Some("192.168.1.1".parse().unwrap()));
+/// ```
+pub fn parse_route_table(contents: &str) -> Option<Ipv4Addr> {
+    contents.lines().find_map(|line| {
+        let mut fields = line.split_whitespace();
+        let destination = fields.nth(1)?;
+        let gateway = u32::from_str_radix(fields.next()?, 16).ok()?;
+        let flags = u32::from_str_radix(fields.next()?, 16).ok()?;
+        (destination == "00000000" && flags & RTF_GATEWAY != 0)
+            .then(|| Ipv4Addr::from(gateway.to_le_bytes()))
+    })
+}
+
+/// Return the IPv4 subnets on directly attached links, from the OS routing
+/// table.
+///
+/// Hosts in these subnets answer ARP, so they can be discovered even when
+/// every TCP port is filtered. The table is read from `/proc/net/route` on
+/// Linux; other platforms return an empty list.
+///
+/// # Examples
+///
+/// ```
+/// use asphyxia::utils::netif::local_networks;
+///
+/// for network in local_networks() {
+///     println!("{network} is on a local link");
+/// }
+/// ```
+pub fn local_networks() -> Vec<IpNetwork> {
+    #[cfg(target_os = "linux")]
+    {
+        std::fs::read_to_string(PROC_NET_ROUTE)
+            .map(|contents| parse_local_networks(&contents))
+            .unwrap_or_default()
+    }
+    #[cfg(not(target_os = "linux"))]
+    {
+        Vec::new()
+    }
+}
+
+/// Parse the contents of `/proc/net/route` into the subnets of its usable,
+/// directly attached (gateway-less) routes.
+///
+/// # Examples
+///
+/// ```
+/// use asphyxia::utils::netif::parse_local_networks;
+///
+/// let table = "\
+/// Iface\tDestination\tGateway \tFlags\tRefCnt\tUse\tMetric\tMask
+/// eth0\t00000000\t0101A8C0\t0003\t0\t0\t0\t00000000
+/// eth0\t0001A8C0\t00000000\t0001\t0\t0\t0\t00FFFFFF
+/// ";
+/// assert_eq!(parse_local_networks(table), vec!["192.168.1.0/24".parse().unwrap()]);
+/// ```
+pub fn parse_local_networks(contents: &str) -> Vec<IpNetwork> {
+    contents
+        .lines()
+        .filter_map(|line| {
+            let mut fields = line.split_whitespace();
+            let destination = u32::from_str_radix(fields.nth(1)?, 16).ok()?;
+            let gateway = u32::from_str_radix(fields.next()?, 16).ok()?;
+            let flags = u32::from_str_radix(fields.next()?, 16).ok()?;
+            let mask = u32::from_str_radix(fields.nth(3)?, 16).ok()?;
+            if destination == 0 || gateway != 0 || flags & (RTF_UP | RTF_GATEWAY) != RTF_UP {
+                return None;
+            }
+            let mask = Ipv4Addr::from(mask.to_le_bytes());
+            Ipv4Network::with_netmask(Ipv4Addr::from(destination.to_le_bytes()), mask)
+                .ok()
+                .map(IpNetwork::V4)
+        })
+        .collect()
+}
+
+/// Whether every address of `subnet` lies on a directly attached link (see
+/// [`local_networks`]).
+///
+/// # Examples
+///
+/// ```
+/// use asphyxia::utils::netif::is_local_subnet;
+///
+/// // Loopback is not an ARP-capable link.
+/// assert!(!is_local_subnet("127.0.0.1/32".parse().unwrap()));
+/// ```
+pub fn is_local_subnet(subnet: IpNetwork) -> bool {
+    let IpNetwork::V4(subnet) = subnet else {
+        return false;
+    };
+    local_networks().into_iter().any(|local| match local {
+        IpNetwork::V4(local) => subnet.is_subnet_of(local),
+        IpNetwork::V6(_) => false,
+    })
+}
@@ -0,0 +1,117 @@
+//! `http://` and `https://` URLs, split into what a client needs to reach
+//! them: the scheme, host, port and path.
+//!
+//! Webhooks (see [`notify`](crate::output::notify)) and release downloads
+//! (see [`update`](crate::update)) parse their URLs with [`HttpUrl`]; each
+//! decides for itself which schemes it accepts.
+
+use std::fmt;
+use std::str::FromStr;
+
+/// An `http://` or `https://` URL.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct HttpUrl {
+    /// Whether the scheme is `https`.
+    pub tls: bool,
+    /// Hostname or IP address, without brackets.
+    pub host: String,
+    /// TCP port, from the URL or the scheme's default.
+    pub port: u16,
+    /// Path and query, starting with `/`.
+    pub path: String,
+}
+
+impl FromStr for HttpUrl {
+    type Err = String;
+
+    /// Parse a URL.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use asphyxia::utils::url::HttpUrl;
+    ///
+    /// let url: HttpUrl = "https://hooks.example.com/services/T0/B0".parse().unwrap();
+    /// assert_eq!((url.tls, url.port, url.path.as_str()), (true, 443, "/services/T0/B0"));
+    /// assert!("ftp://example.com/".parse::<HttpUrl>().is_err());
+    /// ```
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let invalid = || format!("Invalid URL: {} (expected http:// or https://)", s);
+        let (tls, rest) = if let Some(rest) = s.strip_prefix("https://") {
+            (true, rest)
+        } else if let Some(rest) = s.strip_prefix("http://") {
+            (false, rest)
+        } else {
+            return Err(invalid());
+        };
+        let (authority, path) = match rest.find(['/', '?']) {
+            Some(at) => (&rest[..at], &rest[at..]),
+            None => (rest, "/"),
+        };
+        let path = if path.starts_with('?') {
+            format!("/{}", path)
+        } else {
+            path.to_string()
+        };
+        let default_port = if tls { 443 } else { 80 };
+        let (host, port) = match authority.strip_prefix('[') {
+            // [2001:db8::1]:8080
+            Some(bracketed) => {
+                let (host, after) = bracketed.split_once(']').ok_or_else(invalid)?;
+                match after.strip_prefix(':') {
+                    Some(port) => (host, port.parse().map_err(|_| invalid())?),
+                    None if after.is_empty() => (host, default_port),
+                    None => return Err(invalid()),
+                }
+            }
+            None => match authority.rsplit_once(':') {
+                Some((host, port)) => (host, port.parse().map_err(|_| invalid())?),
+                None => (authority, default_port),
+            },
+        };
+        if host.is_empty() {
+            return Err(invalid());
+        }
+        Ok(HttpUrl {
+            tls,
+            host: host.to_string(),
+            port,
+            path,
+        })
+    }
+}
+
+impl fmt::Display for HttpUrl {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let scheme = if self.tls { "https" } else { "http" };
+        if self.host.contains(':') {
+            write!(f, "{}://[{}]:{}{}", scheme, self.host, self.port, self.path)
+        } else {
+            write!(f, "{}://{}:{}{}", scheme, self.host, self.port, self.path)
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_parse_urls() {
+        let url: HttpUrl = "http://10.0.0.9:8080".parse().unwrap();
+        assert_eq!(
+            url,
+            HttpUrl {
+                tls: false,
+                host: "10.0.0.9".to_string(),
+                port: 8080,
+                path: "/".to_string(),
+            }
+        );
+        let url: HttpUrl = "https://[2001:db8::1]/hook?token=x".parse().unwrap();
+        assert_eq!(url.host, "2001:db8::1");
+        assert_eq!(url.path, "/hook?token=x");
+        assert!("http://:80/".parse::<HttpUrl>().is_err());
+        assert!("http://host:port/".parse::<HttpUrl>().is_err());
+    }
+}
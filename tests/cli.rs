@@ -6,9 +6,16 @@
 
 use assert_cmd::Command;
 use predicates::prelude::*;
+use std::io::{Read, Write};
+use std::net::TcpListener;
 
 fn asphyxia() -> Command {
-    Command::cargo_bin("asphyxia").expect("binary `asphyxia` should be built")
+    let mut command = Command::cargo_bin("asphyxia").expect("binary `asphyxia` should be built");
+    // Builds with the history feature would otherwise record every test scan.
+    command.env("ASPHYXIA_HISTORY", "");
+    // Nor should a config file in the developer's home change their output.
+    command.env("ASPHYXIA_CONFIG", "");
+    command
 }
 
 #[test]
@@ -40,12 +47,23 @@ fn port_scan_requires_a_host() {
 }
 
 #[test]
-fn port_scan_without_range_or_specific_prints_guidance() {
+fn port_scan_without_port_selection_scans_default_set() {
+    // -vv keeps every probed port, so each of the default ports is a record.
+    let output = asphyxia()
+        .args(["ps", "-t", "127.0.0.1", "-vv", "-o", "jsonl"])
+        .output()
+        .unwrap();
+    assert!(output.status.success());
+    let stdout = String::from_utf8(output.stdout).unwrap();
+    assert_eq!(stdout.lines().count(), 1000);
+}
+
+#[test]
+fn all_ports_conflicts_with_other_port_selections() {
     asphyxia()
-        .args(["ps", "-t", "127.0.0.1"])
+        .args(["ps", "-t", "127.0.0.1", "--all-ports", "-s", "22"])
         .assert()
-        .success()
-        .stderr(predicate::str::contains("Please specify either -r or -s"));
+        .failure();
 }
 
 #[test]
@@ -86,6 +104,196 @@ fn address_scan_rejects_invalid_ip() {
         .stderr(predicate::str::contains("Invalid IP address: not-an-ip"));
 }
 
+#[test]
+fn address_scan_resolves_hostnames() {
+    asphyxia()
+        .args(["as", "-t", "localhost", "--timeout", "200ms", "-o", "json"])
+        .assert()
+        .success()
+        .stdout(predicate::str::contains("\"ip\":\"127.0.0.1\""));
+}
+
+#[test]
+fn resolve_flag_adds_reverse_dns_hostname() {
+    // 127.0.0.1 maps back to "localhost" through the hosts file.
+    asphyxia()
+        .args([
+            "as",
+            "-t",
+            "127.0.0.1",
+            "--resolve",
+            "--timeout",
+            "200ms",
+            "-o",
+            "json",
+        ])
+        .assert()
+        .success()
+        .stdout(predicate::str::contains("\"hostname\":\"localhost\""));
+}
+
+#[test]
+fn address_scan_accepts_arp_cache() {
+    // Loopback is never in the ARP table, so it is still found by probing.
+    asphyxia()
+        .args([
+            "as",
+            "-s",
+            "127.0.0.1/32",
+            "--arp-cache",
+            "--timeout",
+            "200ms",
+            "-o",
+            "json",
+        ])
+        .assert()
+        .success()
+        .stdout(predicate::str::contains("\"ip\":\"127.0.0.1\""));
+}
+
+#[test]
+fn address_scan_arp_probe_skips_loopback() {
+    // Loopback never answers ARP, so an ARP probe finds no hosts there.
+    asphyxia()
+        .args([
+            "as",
+            "-s",
+            "127.0.0.1/32",
+            "--probe",
+            "arp",
+            "--timeout",
+            "200ms",
+            "-o",
+            "json",
+        ])
+        .assert()
+        .success()
+        .stdout(predicate::str::contains("127.0.0.1").not());
+}
+
+#[test]
+fn address_scan_traces_discovered_hosts() {
+    let output = asphyxia()
+        .args([
+            "as",
+            "127.0.0.1",
+            "--trace-discovered",
+            "--timeout",
+            "500ms",
+        ])
+        .args(["-o", "json"])
+        .output()
+        .unwrap();
+    assert!(output.status.success());
+    let stderr = String::from_utf8_lossy(&output.stderr);
+    if stderr.contains("raw-socket privileges") {
+        println!("Skipping: raw sockets are not permitted");
+        return;
+    }
+    // Loopback is its own first and only hop.
+    assert!(String::from_utf8_lossy(&output.stdout).contains("\"hops\":[\"127.0.0.1\"]"));
+}
+
+#[test]
+fn address_scan_estimates_hops_from_icmp_ttl() {
+    let output = asphyxia()
+        .args(["as", "127.0.0.1", "--probe", "icmp", "--timeout", "500ms"])
+        .args(["-o", "json"])
+        .output()
+        .unwrap();
+    assert!(output.status.success());
+    // Without raw sockets, pings go out over a datagram socket (or not at
+    // all) and the reply's TTL is never seen.
+    if !asphyxia::scanner::icmp::icmp_available() {
+        println!("Skipping: raw sockets are not permitted");
+        return;
+    }
+    // Loopback answers with its initial TTL untouched.
+    assert!(String::from_utf8_lossy(&output.stdout).contains("\"hop_estimate\":0"));
+}
+
+#[test]
+#[cfg(target_os = "linux")]
+fn port_scan_guesses_os_from_syn_ack() {
+    let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+    let port = listener.local_addr().unwrap().port().to_string();
+    let output = asphyxia()
+        .args([
+            "ps",
+            "-t",
+            "127.0.0.1",
+            "-s",
+            &port,
+            "--technique",
+            "syn",
+            "--os",
+        ])
+        .args(["-o", "json"])
+        .output()
+        .unwrap();
+    assert!(output.status.success());
+    let stderr = String::from_utf8_lossy(&output.stderr);
+    if stderr.contains("raw-socket privileges") {
+        println!("Skipping: raw sockets are not permitted");
+        return;
+    }
+    // Loopback answers from this very (Linux) stack.
+    assert!(String::from_utf8_lossy(&output.stdout).contains("\"os_guess\":\"Linux\""));
+}
+
+#[test]
+fn address_scan_reports_path_mtu() {
+    let output = asphyxia()
+        .args(["as", "127.0.0.1", "--probe", "icmp", "--pmtu"])
+        .args(["--timeout", "500ms", "-o", "json"])
+        .output()
+        .unwrap();
+    assert!(output.status.success());
+    let stderr = String::from_utf8_lossy(&output.stderr);
+    if stderr.contains("raw-socket privileges") {
+        println!("Skipping: raw sockets are not permitted");
+        return;
+    }
+    // Loopback carries more than the largest size probed.
+    assert!(String::from_utf8_lossy(&output.stdout).contains("\"path_mtu\":9000"));
+}
+
+#[test]
+fn port_scan_accepts_timing_template() {
+    let listener = std::net::TcpListener::bind("127.0.0.1:0").unwrap();
+    let port = listener.local_addr().unwrap().port().to_string();
+    asphyxia()
+        .args(["ps", "-t", "127.0.0.1", "-s", &port, "--timing", "insane"])
+        .assert()
+        .success()
+        .stdout(predicate::str::contains(format!("{}/tcp", port)));
+}
+
+#[test]
+fn port_scan_rejects_unknown_timing_template() {
+    asphyxia()
+        .args(["ps", "-t", "127.0.0.1", "-s", "22", "--timing", "reckless"])
+        .assert()
+        .failure()
+        .stderr(predicate::str::contains("invalid value 'reckless'"));
+}
+
+#[test]
+fn dscp_accepts_class_names_and_rejects_others() {
+    let listener = std::net::TcpListener::bind("127.0.0.1:0").unwrap();
+    let port = listener.local_addr().unwrap().port().to_string();
+    asphyxia()
+        .args(["ps", "-t", "127.0.0.1", "-s", &port, "--dscp", "cs1"])
+        .assert()
+        .success()
+        .stdout(predicate::str::contains(format!("{}/tcp", port)));
+    asphyxia()
+        .args(["sweep", "-s", "127.0.0.1/32", "--dscp", "af44"])
+        .assert()
+        .failure()
+        .stderr(predicate::str::contains("Invalid DSCP 'af44'"));
+}
+
 #[test]
 fn address_scan_rejects_invalid_subnet() {
     asphyxia()
@@ -106,6 +314,51 @@ fn concurrency_flag_is_accepted() {
         .stderr(predicate::str::contains("Invalid subnet format"));
 }
 
+#[test]
+fn rate_flag_limits_attempts() {
+    asphyxia()
+        .args(["ps", "-t", "127.0.0.1", "-r", "1", "3", "--rate", "20"])
+        .assert()
+        .success()
+        .stdout(predicate::str::contains("Game Over"));
+}
+
+#[test]
+fn retries_flag_is_accepted() {
+    asphyxia()
+        .args(["ps", "-t", "127.0.0.1", "-s", "1", "--retries", "2"])
+        .assert()
+        .success()
+        .stdout(predicate::str::contains("Game Over"));
+}
+
+#[test]
+fn rate_flag_rejects_zero() {
+    asphyxia()
+        .args(["as", "-t", "127.0.0.1", "--rate", "0"])
+        .assert()
+        .failure()
+        .stderr(predicate::str::contains("--rate"));
+}
+
+#[test]
+fn concurrency_short_j_alias_is_accepted() {
+    asphyxia()
+        .args(["sweep", "-s", "192.168.1.0/33", "-j", "8"])
+        .assert()
+        .success()
+        .stderr(predicate::str::contains("Invalid subnet format"));
+}
+
+#[test]
+fn concurrency_flag_rejects_zero() {
+    asphyxia()
+        .args(["ps", "-t", "127.0.0.1", "-s", "22", "-j", "0"])
+        .assert()
+        .failure()
+        .stderr(predicate::str::contains("--concurrency"));
+}
+
 #[test]
 fn concurrency_flag_rejects_non_numeric() {
     asphyxia()
@@ -136,6 +389,40 @@ fn port_scan_jsonl_with_no_open_ports_emits_nothing() {
         .stdout(predicate::eq(""));
 }
 
+#[test]
+fn port_scan_very_verbose_reports_evidence_for_closed_ports() {
+    asphyxia()
+        .args(["ps", "-t", "127.0.0.1", "-s", "1", "-vv"])
+        .assert()
+        .success()
+        .stdout(predicate::str::contains("1/tcp closed"))
+        .stdout(predicate::str::contains("RST received"));
+    asphyxia()
+        .args(["ps", "-t", "127.0.0.1", "-s", "1", "-vv", "-o", "jsonl"])
+        .assert()
+        .success()
+        .stdout(predicate::str::contains(r#""status":"closed","timestamp""#))
+        .stdout(predicate::str::contains(
+            r#""evidence":"reset","attempts":1"#,
+        ));
+}
+
+#[test]
+fn address_scan_jsonl_streams_hosts_to_output_file() {
+    let path = std::env::temp_dir().join(format!("asphyxia-stream-{}.jsonl", std::process::id()));
+    asphyxia()
+        .args(["as", "-r", "127.0.0.1", "127.0.0.2", "--timeout", "200ms"])
+        .args(["-o", "jsonl", "--output-file"])
+        .arg(&path)
+        .assert()
+        .success()
+        .stdout(predicate::eq(""));
+    let written = std::fs::read_to_string(&path).unwrap();
+    std::fs::remove_file(&path).unwrap();
+    assert_eq!(written.lines().count(), 2);
+    assert!(written.contains(r#""ip":"127.0.0.1""#));
+}
+
 #[test]
 fn address_scan_json_with_no_hosts_emits_empty_array() {
     asphyxia()
@@ -163,3 +450,1692 @@ fn text_output_remains_the_default() {
         .success()
         .stdout(predicate::str::contains("Game Over"));
 }
+
+#[test]
+fn machine_output_records_carry_format_version() {
+    // A listener on an ephemeral loopback port gives the scan one open port
+    // without leaving the machine.
+    let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+    let port = listener.local_addr().unwrap().port().to_string();
+    asphyxia()
+        .args(["ps", "-t", "127.0.0.1", "-s", &port, "-o", "jsonl"])
+        .assert()
+        .success()
+        .stdout(predicate::str::contains("\"format_version\":1"))
+        .stdout(predicate::str::contains(format!("\"port\":{}", port)));
+}
+
+#[test]
+fn port_scan_text_report_summarizes_latency() {
+    let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+    let port = listener.local_addr().unwrap().port().to_string();
+    asphyxia()
+        .args(["ps", "-t", "127.0.0.1", "-s", &port])
+        .assert()
+        .success()
+        .stdout(predicate::str::contains("Latency"))
+        .stdout(
+            predicate::str::is_match(r"min [0-9.]+ ms, avg [0-9.]+ ms, max [0-9.]+ ms").unwrap(),
+        );
+}
+
+#[test]
+fn port_scan_text_report_shows_resource_usage() {
+    let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+    let port = listener.local_addr().unwrap().port().to_string();
+    asphyxia()
+        .args(["ps", "-t", "127.0.0.1", "-s", &port])
+        .assert()
+        .success()
+        .stdout(
+            predicate::str::is_match(
+                r"Resources\S* peak memory [0-9.]+ [KMG]iB, peak sockets [1-9][0-9]*, sent [0-9.]+ [KMG]?i?B, received [0-9.]+ [KMG]?i?B, CPU [0-9.]+ s",
+            )
+            .unwrap(),
+        );
+}
+
+#[test]
+fn port_scan_text_report_counts_closed_and_filtered_ports() {
+    let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+    let port = listener.local_addr().unwrap().port();
+    asphyxia()
+        .args(["ps", "-t", "127.0.0.1", "-s", &format!("1,{}", port)])
+        .assert()
+        .success()
+        .stdout(
+            predicate::str::is_match(r"Ports\S* \S*1\S* open, \S*1\S* closed, \S*0\S* filtered")
+                .unwrap(),
+        );
+}
+
+#[test]
+fn port_scan_randomize_reports_its_seed_and_finds_the_open_port() {
+    let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+    let port = listener.local_addr().unwrap().port();
+    asphyxia()
+        .args(["--theme", "mono", "ps", "-t", "127.0.0.1", "-s"])
+        .arg(format!("1,2,3,{}", port))
+        .args(["--randomize", "--seed", "4242"])
+        .assert()
+        .success()
+        .stdout(predicate::str::contains(
+            "Randomized scan order (seed 4242; repeat with --seed 4242)",
+        ))
+        .stdout(predicate::str::contains(format!("127.0.0.1:{}/tcp", port)));
+}
+
+// Linux will not send to the broadcast address without SO_BROADCAST, so
+// the scanning host never has a route to it: not an outage.
+#[cfg(target_os = "linux")]
+#[test]
+fn port_scan_of_an_unroutable_target_does_not_wait_for_an_outage() {
+    let started = std::time::Instant::now();
+    asphyxia()
+        .args(["--theme", "mono", "ps", "-t", "255.255.255.255", "-s", "80"])
+        .args(["-vv", "-o", "json"])
+        .assert()
+        .success()
+        .stderr(predicate::str::contains("Scan interrupted").not())
+        .stdout(predicate::str::contains("outages").not());
+    assert!(started.elapsed() < std::time::Duration::from_secs(30));
+}
+
+#[test]
+fn seed_requires_randomize() {
+    asphyxia()
+        .args(["ps", "-t", "127.0.0.1", "-s", "80", "--seed", "1"])
+        .assert()
+        .failure()
+        .stderr(predicate::str::contains("--randomize"));
+}
+
+#[test]
+fn port_scan_audit_file_counts_traffic_per_target() {
+    let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+    let open = listener.local_addr().unwrap().port();
+    let closed = TcpListener::bind("127.0.0.1:0")
+        .unwrap()
+        .local_addr()
+        .unwrap()
+        .port();
+    let path = std::env::temp_dir().join(format!("asphyxia-audit-{}.json", open));
+    asphyxia()
+        .args([
+            "ps",
+            "-t",
+            "127.0.0.1",
+            "-s",
+            &format!("{},{}", open, closed),
+        ])
+        .arg("--audit-file")
+        .arg(&path)
+        .assert()
+        .success()
+        .stdout(predicate::str::contains("Traffic per target"))
+        .stdout(
+            predicate::str::is_match(r"127\.0\.0\.1\S*  2 probes, 0 B sent; 2 answers").unwrap(),
+        );
+    let written = std::fs::read_to_string(&path).unwrap();
+    std::fs::remove_file(&path).unwrap();
+    let audit: serde_json::Value = serde_json::from_str(&written).unwrap();
+    assert_eq!(audit[0]["ip"], "127.0.0.1");
+    assert_eq!(audit[0]["probes_sent"], 2);
+    assert_eq!(audit[0]["responses_received"], 2);
+}
+
+#[test]
+fn address_scan_refuses_huge_subnets_unless_told_otherwise() {
+    asphyxia()
+        .args(["as", "-s", "0.0.0.0/0", "--probe", "tcp"])
+        .assert()
+        .stderr(predicate::str::contains(
+            "Refusing to scan 4294967296 addresses (limit is 65536)",
+        ))
+        .stdout(predicate::str::contains("Started").not());
+
+    asphyxia()
+        .args(["as", "-r", "10.0.0.0", "10.0.3.255", "--max-hosts", "512"])
+        .assert()
+        .stderr(predicate::str::contains("Refusing to scan 1024 addresses"));
+
+    asphyxia()
+        .args([
+            "sweep",
+            "-s",
+            "10.0.0.0/8",
+            "--max-hosts",
+            "1",
+            "--i-know-what-im-doing",
+        ])
+        .assert()
+        .failure()
+        .stderr(predicate::str::contains("cannot be used with"));
+}
+
+#[test]
+fn address_scan_local_takes_the_subnet_of_the_primary_interface() {
+    asphyxia()
+        .args(["--theme", "mono", "as", "--local", "--max-hosts", "1"])
+        .assert()
+        .stdout(predicate::str::contains("Local subnet of "))
+        .stderr(predicate::str::contains("Refusing to scan"));
+
+    asphyxia()
+        .args(["as", "--local", "-s", "10.0.0.0/24"])
+        .assert()
+        .failure()
+        .stderr(predicate::str::contains("cannot be used with"));
+}
+
+#[test]
+fn theme_mono_and_no_color_print_no_escape_codes() {
+    let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+    let port = listener.local_addr().unwrap().port().to_string();
+    asphyxia()
+        .args(["ps", "-t", "127.0.0.1", "-s", &port, "--theme", "mono"])
+        .assert()
+        .success()
+        .stdout(predicate::str::contains("Game Over"))
+        .stdout(predicate::str::contains("\x1b[").not());
+
+    asphyxia()
+        .args(["--theme", "light", "ps", "-t", "127.0.0.1", "-s", &port])
+        .assert()
+        .success()
+        .stdout(predicate::str::contains("\x1b["));
+
+    asphyxia()
+        .args(["ps", "-t", "127.0.0.1", "-s", &port])
+        .env("NO_COLOR", "1")
+        .assert()
+        .success()
+        .stdout(predicate::str::contains("\x1b[").not());
+}
+
+#[test]
+fn timeout_accepts_human_friendly_durations() {
+    asphyxia()
+        .args(["ps", "-t", "127.0.0.1", "-s", "1", "--timeout", "1m30s"])
+        .assert()
+        .failure()
+        .stderr(predicate::str::contains("out of range"));
+
+    asphyxia()
+        .args(["ps", "-t", "127.0.0.1", "-s", "1", "--timeout", "250ms"])
+        .assert()
+        .success();
+}
+
+#[test]
+fn timeout_rejects_unknown_units() {
+    asphyxia()
+        .args(["as", "-t", "127.0.0.1", "--timeout", "2 fortnights"])
+        .assert()
+        .failure()
+        .stderr(predicate::str::contains("Invalid duration"));
+}
+
+#[test]
+fn port_scan_accepts_service_names() {
+    // `-p` is an alias for `-s`; a service name resolves to its port and the
+    // scan runs to completion.
+    asphyxia()
+        .args(["ps", "-t", "127.0.0.1", "-p", "ssh", "--timeout", "100ms"])
+        .assert()
+        .success()
+        .stdout(predicate::str::contains("Game Over"));
+}
+
+#[test]
+fn port_scan_reports_overlapping_ports_once() {
+    let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+    let port = listener.local_addr().unwrap().port();
+    let spec = format!("{port},{}-{port},{port}", port - 1);
+    let output = asphyxia()
+        .args(["ps", "-t", "127.0.0.1", "-s", &spec, "-o", "jsonl"])
+        .output()
+        .unwrap();
+    assert!(output.status.success());
+    assert_eq!(String::from_utf8_lossy(&output.stdout).lines().count(), 1);
+}
+
+#[test]
+fn port_scan_syn_technique_finds_open_port() {
+    let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+    let port = listener.local_addr().unwrap().port().to_string();
+    let output = asphyxia()
+        .args(["ps", "-t", "127.0.0.1", "-s", &format!("1,{}", port)])
+        .args(["--technique", "syn", "--timeout", "500ms", "-o", "jsonl"])
+        .output()
+        .unwrap();
+    assert!(output.status.success());
+    if String::from_utf8_lossy(&output.stderr).contains("raw-socket privileges") {
+        println!("Skipping: raw sockets are not permitted");
+        return;
+    }
+    let stdout = String::from_utf8_lossy(&output.stdout);
+    assert_eq!(stdout.lines().count(), 1);
+    assert!(stdout.contains(&format!("\"port\":{}", port)));
+}
+
+#[test]
+fn top_ports_must_be_positive() {
+    asphyxia()
+        .args(["ps", "-t", "127.0.0.1", "--top-ports", "0"])
+        .assert()
+        .failure()
+        .stderr(predicate::str::contains("--top-ports"));
+}
+
+#[test]
+fn port_scan_rejects_unknown_template() {
+    asphyxia()
+        .args(["ps", "-t", "127.0.0.1", "--template", "nope"])
+        .assert()
+        .success()
+        .stderr(predicate::str::contains("Unknown scan template: nope"));
+}
+
+#[test]
+fn template_conflicts_with_explicit_ports() {
+    asphyxia()
+        .args([
+            "ps",
+            "-t",
+            "127.0.0.1",
+            "-s",
+            "22",
+            "--template",
+            "tls-audit",
+        ])
+        .assert()
+        .failure();
+}
+
+#[test]
+fn template_turns_on_its_probes() {
+    // tls-audit reads certificates, which cannot go through a proxy.
+    asphyxia()
+        .args(["ps", "-t", "127.0.0.1", "--template", "tls-audit"])
+        .args(["--proxy", "http://127.0.0.1:1"])
+        .assert()
+        .stderr(predicate::str::contains(
+            "--tls-info connects directly and cannot be used with --proxy",
+        ));
+}
+
+#[test]
+fn config_templates_override_builtin_ones() {
+    let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+    let port = listener.local_addr().unwrap().port();
+    let path = std::env::temp_dir().join(format!("asphyxia-templates-{}.toml", port));
+    std::fs::write(
+        &path,
+        format!("[templates.rdp-exposure]\nports = \"{}\"\n", port),
+    )
+    .unwrap();
+
+    asphyxia()
+        .args([
+            "ps",
+            "-t",
+            "127.0.0.1",
+            "--template",
+            "rdp-exposure",
+            "-o",
+            "jsonl",
+        ])
+        .env("ASPHYXIA_CONFIG", &path)
+        .assert()
+        .success()
+        .stdout(predicate::str::contains(format!("\"port\":{}", port)))
+        .stdout(predicate::str::contains("\"port\":22,").not());
+
+    std::fs::remove_file(&path).unwrap();
+}
+
+#[test]
+fn probe_flag_rejects_unknown_method() {
+    asphyxia()
+        .args(["as", "-t", "127.0.0.1", "--probe", "carrier-pigeon"])
+        .assert()
+        .failure()
+        .stderr(predicate::str::contains("--probe"));
+}
+
+#[test]
+fn ack_file_marks_acknowledged_findings() {
+    let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+    let port = listener.local_addr().unwrap().port();
+    let dir = std::env::temp_dir().join(format!("asphyxia-ack-{}", port));
+    std::fs::create_dir_all(&dir).unwrap();
+    let acks = dir.join("acks.yaml");
+    std::fs::write(
+        &acks,
+        format!(
+            "\"127.0.0.1:{}\":\n  by: alice\n  reason: test listener\n",
+            port
+        ),
+    )
+    .unwrap();
+
+    asphyxia()
+        .args([
+            "ps",
+            "-t",
+            "127.0.0.1",
+            "-s",
+            &port.to_string(),
+            "-o",
+            "json",
+        ])
+        .arg("--ack-file")
+        .arg(&acks)
+        .assert()
+        .success()
+        .stdout(predicate::str::contains("\"ack\":{\"by\":\"alice\""));
+
+    std::fs::remove_dir_all(&dir).unwrap();
+}
+
+#[test]
+fn annotations_note_hosts_and_limit_their_ports() {
+    let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+    let port = listener.local_addr().unwrap().port();
+    let dir = std::env::temp_dir().join(format!("asphyxia-annotations-{}", port));
+    std::fs::create_dir_all(&dir).unwrap();
+    let annotations = dir.join("hosts.yaml");
+    std::fs::write(
+        &annotations,
+        format!(
+            "\"127.0.0.0/8\":\n  note: loopback\n  max_port: {}\n  attributes:\n    owner: ops\n",
+            port - 1
+        ),
+    )
+    .unwrap();
+
+    asphyxia()
+        .args([
+            "--theme",
+            "mono",
+            "ps",
+            "-t",
+            "127.0.0.1",
+            "-s",
+            &port.to_string(),
+        ])
+        .arg("--annotations")
+        .arg(&annotations)
+        .assert()
+        .success()
+        .stdout(predicate::str::contains(format!(
+            "[loopback; owner=ops; ports up to {} only]",
+            port - 1
+        )))
+        .stdout(predicate::str::contains("No open ports found"));
+
+    std::fs::remove_dir_all(&dir).unwrap();
+}
+
+#[test]
+fn annotations_keep_host_discovery_off_limited_ports() {
+    let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+    listener.set_nonblocking(true).unwrap();
+    let port = listener.local_addr().unwrap().port();
+    let dir = std::env::temp_dir().join(format!("asphyxia-discovery-limit-{}", port));
+    std::fs::create_dir_all(&dir).unwrap();
+    let annotations = dir.join("hosts.yaml");
+    std::fs::write(
+        &annotations,
+        format!("\"127.0.0.1\":\n  max_port: {}\n", port - 1),
+    )
+    .unwrap();
+
+    asphyxia()
+        .args(["as", "-t", "127.0.0.1", "--probe", "tcp", "-o", "json"])
+        .args(["--discovery-ports", &port.to_string()])
+        .arg("--annotations")
+        .arg(&annotations)
+        .assert()
+        .success();
+    // The host is discovered on port 80 instead; the listener saw nothing.
+    assert_eq!(
+        listener.accept().unwrap_err().kind(),
+        std::io::ErrorKind::WouldBlock
+    );
+
+    std::fs::remove_dir_all(&dir).unwrap();
+}
+
+#[test]
+fn source_ip_sends_probes_from_that_address() {
+    let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+    let port = listener.local_addr().unwrap().port().to_string();
+
+    asphyxia()
+        .args(["ps", "-t", "127.0.0.1", "-s", &port, "-o", "json"])
+        .args(["--source-ip", "127.0.0.1"])
+        .assert()
+        .success()
+        .stdout(predicate::str::contains("\"status\":\"open\""));
+}
+
+#[test]
+fn unknown_interface_is_refused_before_scanning() {
+    asphyxia()
+        .args(["as", "-t", "127.0.0.1", "--interface", "no-such-if0"])
+        .assert()
+        .stderr(predicate::str::contains("no-such-if0"))
+        .stdout(predicate::str::contains("Started").not());
+}
+
+#[test]
+fn operator_and_note_are_recorded_on_every_record() {
+    let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+    let port = listener.local_addr().unwrap().port();
+
+    asphyxia()
+        .args([
+            "ps",
+            "-t",
+            "127.0.0.1",
+            "-s",
+            &port.to_string(),
+            "-o",
+            "jsonl",
+            "--operator",
+            "jsmith",
+            "--note",
+            "change window CHG-1234",
+        ])
+        .assert()
+        .success()
+        .stdout(predicate::str::contains(
+            r#""operator":"jsmith","note":"change window CHG-1234""#,
+        ));
+
+    asphyxia()
+        .args(["ps", "-t", "127.0.0.1", "-s", &port.to_string()])
+        .args(["--operator", "jsmith"])
+        .assert()
+        .success()
+        .stdout(predicate::str::contains("jsmith"));
+}
+
+#[test]
+fn http_probe_reports_status_and_title_of_web_ports() {
+    // Only well-known web ports are probed; use the first one that is free.
+    let Some(listener) = [8888, 8081, 9090, 8008, 3000]
+        .into_iter()
+        .find_map(|port| TcpListener::bind(("127.0.0.1", port)).ok())
+    else {
+        println!("Skipping: no web port is free");
+        return;
+    };
+    let port = listener.local_addr().unwrap().port();
+    std::thread::spawn(move || {
+        for stream in listener.incoming() {
+            let mut stream = stream.unwrap();
+            let mut buf = [0u8; 512];
+            let _ = stream.read(&mut buf);
+            let _ = stream.write_all(
+                b"HTTP/1.1 401 Unauthorized\r\nServer: test\r\n\r\n<title>Admin</title>",
+            );
+        }
+    });
+
+    asphyxia()
+        .args(["ps", "-t", "127.0.0.1", "-s", &port.to_string()])
+        .args(["--http-probe", "-o", "jsonl"])
+        .assert()
+        .success()
+        .stdout(predicate::str::contains(
+            r#""http_status":401,"http_server":"test","http_title":"Admin""#,
+        ));
+}
+
+#[test]
+fn tls_info_reports_certificate_of_tls_ports() {
+    use rustls::pki_types::pem::PemObject;
+    use rustls::pki_types::{CertificateDer, PrivateKeyDer};
+    use std::sync::Arc;
+
+    let cert = CertificateDer::from_pem_slice(include_bytes!("fixtures/tls-cert.pem")).unwrap();
+    let key = PrivateKeyDer::from_pem_slice(include_bytes!("fixtures/tls-key.pem")).unwrap();
+    let config = rustls::ServerConfig::builder_with_provider(Arc::new(
+        rustls::crypto::ring::default_provider(),
+    ))
+    .with_safe_default_protocol_versions()
+    .unwrap()
+    .with_no_client_auth()
+    .with_single_cert(vec![cert], key)
+    .unwrap();
+    let config = Arc::new(config);
+    let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+    let port = listener.local_addr().unwrap().port();
+    std::thread::spawn(move || {
+        for stream in listener.incoming() {
+            let mut stream = stream.unwrap();
+            let mut connection = rustls::ServerConnection::new(config.clone()).unwrap();
+            while connection.is_handshaking() {
+                if connection.complete_io(&mut stream).is_err() {
+                    break;
+                }
+            }
+        }
+    });
+
+    asphyxia()
+        .args(["ps", "-t", "127.0.0.1", "-s", &port.to_string()])
+        .args(["--tls-info", "-o", "jsonl"])
+        .assert()
+        .success()
+        .stdout(predicate::str::contains(
+            r#""tls_subject":"O=Asphyxia Test, CN=asphyxia.test""#,
+        ))
+        .stdout(predicate::str::contains(
+            r#""tls_sans":["asphyxia.test","www.asphyxia.test","127.0.0.1"],"tls_expires":"2126-09-22T15:37:28Z""#,
+        ));
+}
+
+#[test]
+fn version_detect_names_product_from_banner() {
+    use std::io::Write;
+
+    let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+    let port = listener.local_addr().unwrap().port();
+    std::thread::spawn(move || {
+        for stream in listener.incoming() {
+            let _ = stream
+                .unwrap()
+                .write_all(b"SSH-2.0-OpenSSH_9.6p1 Ubuntu-3ubuntu13\r\n");
+        }
+    });
+
+    asphyxia()
+        .args(["ps", "-t", "127.0.0.1", "-s", &port.to_string()])
+        .args(["--version-detect", "-o", "jsonl"])
+        .assert()
+        .success()
+        .stdout(predicate::str::contains(r#""service":"ssh""#))
+        .stdout(predicate::str::contains(
+            r#""product":"OpenSSH","version":"9.6p1""#,
+        ));
+}
+
+#[test]
+fn ack_file_must_exist() {
+    asphyxia()
+        .args([
+            "as",
+            "-t",
+            "127.0.0.1",
+            "--ack-file",
+            "/nonexistent/acks.yaml",
+        ])
+        .assert()
+        .success()
+        .stderr(predicate::str::contains("Cannot read ack file"));
+}
+
+#[test]
+fn input_file_scans_every_listed_target() {
+    let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+    let port = listener.local_addr().unwrap().port().to_string();
+    let path = std::env::temp_dir().join(format!("asphyxia-targets-{}.txt", port));
+    std::fs::write(
+        &path,
+        "# lab hosts\n127.0.0.1\nlocalhost  # same host again\n\n",
+    )
+    .unwrap();
+
+    asphyxia()
+        .args(["ps", "--input-file"])
+        .arg(&path)
+        .args(["-s", &port, "-o", "jsonl"])
+        .assert()
+        .success()
+        .stdout(predicate::str::contains(format!("\"port\":{}", port)).count(2));
+
+    // Duplicate addresses are reported once in an address scan.
+    asphyxia()
+        .args(["as", "--input-file"])
+        .arg(&path)
+        .args(["--timeout", "200ms", "-o", "jsonl"])
+        .assert()
+        .success()
+        .stdout(predicate::str::contains("\"ip\":\"127.0.0.1\"").count(1));
+
+    std::fs::remove_file(&path).unwrap();
+}
+
+#[test]
+fn config_file_sets_defaults_and_target_groups() {
+    let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+    let port = listener.local_addr().unwrap().port().to_string();
+    let excluded = TcpListener::bind("127.0.0.1:0").unwrap();
+    let excluded = excluded.local_addr().unwrap().port().to_string();
+    let path = std::env::temp_dir().join(format!("asphyxia-config-{}.toml", port));
+    std::fs::write(
+        &path,
+        format!(
+            "output = \"jsonl\"\nexclude_ports = \"{}\"\n\n[groups]\nlab = [\"127.0.0.1\"]\n",
+            excluded
+        ),
+    )
+    .unwrap();
+    let ports = format!("{},{}", port, excluded);
+
+    asphyxia()
+        .args(["ps", "-t", "@lab", "-s", &ports, "--config"])
+        .arg(&path)
+        .assert()
+        .success()
+        .stdout(predicate::str::contains(format!("\"port\":{}", port)))
+        .stdout(predicate::str::contains(format!("\"port\":{}", excluded)).not());
+
+    // Flags on the command line win over the file.
+    asphyxia()
+        .args(["ps", "-t", "@lab", "-s", &port, "-o", "text"])
+        .env("ASPHYXIA_CONFIG", &path)
+        .assert()
+        .success()
+        .stdout(predicate::str::contains("\"port\":").not());
+
+    asphyxia()
+        .args(["as", "@dmz", "--config"])
+        .arg(&path)
+        .assert()
+        .stderr(predicate::str::contains("Unknown target group: @dmz"));
+
+    std::fs::remove_file(&path).unwrap();
+}
+
+#[test]
+fn config_rates_let_owned_subnets_skip_the_global_rate() {
+    let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+    let port = listener.local_addr().unwrap().port();
+    let path = std::env::temp_dir().join(format!("asphyxia-rates-{}.toml", port));
+    std::fs::write(&path, "[rates]\n\"127.0.0.0/8\" = \"unlimited\"\n").unwrap();
+    let ports = format!("{}-{}", port.saturating_sub(9), port);
+
+    // Ten probes at one per second would take nine seconds.
+    let started = std::time::Instant::now();
+    asphyxia()
+        .args([
+            "ps",
+            "-t",
+            "127.0.0.1",
+            "-s",
+            &ports,
+            "--rate",
+            "1",
+            "-o",
+            "json",
+        ])
+        .arg("--config")
+        .arg(&path)
+        .assert()
+        .success()
+        .stdout(predicate::str::contains(format!("\"port\":{}", port)));
+    assert!(started.elapsed() < std::time::Duration::from_secs(5));
+
+    std::fs::remove_file(&path).unwrap();
+}
+
+#[test]
+fn config_file_errors_name_the_file() {
+    asphyxia()
+        .args([
+            "ps",
+            "-t",
+            "127.0.0.1",
+            "--config",
+            "/nonexistent/asphyxia.toml",
+        ])
+        .assert()
+        .stderr(predicate::str::contains(
+            "Cannot read config file /nonexistent/asphyxia.toml",
+        ));
+}
+
+#[test]
+fn input_file_reports_invalid_lines() {
+    let path =
+        std::env::temp_dir().join(format!("asphyxia-bad-targets-{}.txt", std::process::id()));
+    std::fs::write(&path, "10.0.0.1\n10.0.0.0/99\n").unwrap();
+
+    asphyxia()
+        .args(["as", "--input-file"])
+        .arg(&path)
+        .assert()
+        .success()
+        .stderr(predicate::str::contains("line 2: Invalid subnet format"));
+
+    std::fs::remove_file(&path).unwrap();
+}
+
+#[test]
+fn positional_targets_accept_ranges_and_hostnames() {
+    asphyxia()
+        .args([
+            "as",
+            "127.0.0.1-127.0.0.2",
+            "localhost",
+            "--timeout",
+            "200ms",
+            "-o",
+            "jsonl",
+        ])
+        .assert()
+        .success()
+        .stdout(predicate::str::contains("\"ip\":\"127.0.0.1\"").count(1))
+        .stdout(predicate::str::contains("\"ip\":\"127.0.0.2\""));
+
+    let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+    let port = listener.local_addr().unwrap().port().to_string();
+    asphyxia()
+        .args(["ps", "127.0.0.1", "-s", &port, "-o", "jsonl"])
+        .assert()
+        .success()
+        .stdout(predicate::str::contains(format!("\"port\":{}", port)));
+}
+
+#[test]
+fn positional_target_rejects_reversed_range() {
+    asphyxia()
+        .args(["as", "10.0.0.9-10.0.0.1"])
+        .assert()
+        .failure()
+        .stderr(predicate::str::contains("Invalid range"));
+}
+
+#[test]
+fn port_scan_requires_host_or_input_file() {
+    asphyxia()
+        .args(["ps", "-s", "80"])
+        .assert()
+        .failure()
+        .stderr(predicate::str::contains("--host"));
+}
+
+#[test]
+fn discovery_ports_rejects_invalid_port() {
+    asphyxia()
+        .args(["as", "-t", "127.0.0.1", "--discovery-ports", "80,nope"])
+        .assert()
+        .success()
+        .stderr(predicate::str::contains("Invalid port number: nope"));
+}
+
+#[test]
+fn output_file_receives_json_records() {
+    let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+    let port = listener.local_addr().unwrap().port();
+    let path = std::env::temp_dir().join(format!("asphyxia-out-{}.json", port));
+
+    asphyxia()
+        .args([
+            "ps",
+            "-t",
+            "127.0.0.1",
+            "-s",
+            &port.to_string(),
+            "-o",
+            "json",
+        ])
+        .arg("--output-file")
+        .arg(&path)
+        .assert()
+        .success()
+        .stdout(predicate::eq(""));
+
+    let written = std::fs::read_to_string(&path).unwrap();
+    std::fs::remove_file(&path).unwrap();
+    assert!(written.contains(&format!("\"port\":{}", port)));
+    assert!(written.contains("\"timestamp\":\""));
+}
+
+// /dev/full fails every write with ENOSPC, the final flush included.
+#[cfg(target_os = "linux")]
+#[test]
+fn output_file_reports_a_failed_write() {
+    let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+    let port = listener.local_addr().unwrap().port().to_string();
+    asphyxia()
+        .args(["ps", "-t", "127.0.0.1", "-s", &port, "-o", "json"])
+        .args(["--output-file", "/dev/full"])
+        .assert()
+        .success()
+        .stderr(predicate::str::contains(
+            "Could not write results to /dev/full",
+        ));
+}
+
+#[test]
+fn output_file_requires_machine_format() {
+    asphyxia()
+        .args([
+            "ps",
+            "-t",
+            "127.0.0.1",
+            "-s",
+            "1",
+            "--output-file",
+            "out.txt",
+        ])
+        .assert()
+        .success()
+        .stderr(predicate::str::contains("--output-file requires"));
+}
+
+#[test]
+fn view_prints_saved_results_with_acks() {
+    let dir = std::env::temp_dir().join(format!("asphyxia-view-{}", std::process::id()));
+    std::fs::create_dir_all(&dir).unwrap();
+    let results = dir.join("results.jsonl");
+    std::fs::write(
+        &results,
+        concat!(
+            r#"{"format_version":1,"ip":"10.0.0.5","port":22,"proto":"tcp","latency_ms":3,"status":"open"}"#,
+            "\n",
+            r#"{"format_version":1,"ip":"10.0.0.9","proto":"tcp","latency_ms":1,"status":"up"}"#,
+            "\n",
+        ),
+    )
+    .unwrap();
+    let acks = dir.join("acks.yaml");
+    std::fs::write(&acks, "\"10.0.0.5:22\":\n  by: alice\n  reason: bastion\n").unwrap();
+
+    asphyxia()
+        .arg("view")
+        .arg(&results)
+        .arg("--ack-file")
+        .arg(&acks)
+        .assert()
+        .success()
+        .stdout(predicate::str::contains("acknowledged by alice: bastion"))
+        .stdout(predicate::str::contains("10.0.0.9"));
+
+    std::fs::remove_dir_all(&dir).unwrap();
+}
+
+#[test]
+fn show_filters_and_sorts_saved_results() {
+    let dir = std::env::temp_dir().join(format!("asphyxia-show-{}", std::process::id()));
+    std::fs::create_dir_all(&dir).unwrap();
+    let results = dir.join("report.json");
+    std::fs::write(
+        &results,
+        concat!(
+            "[",
+            r#"{"format_version":1,"ip":"10.0.0.5","port":443,"proto":"tcp","latency_ms":3,"status":"open"},"#,
+            r#"{"format_version":1,"ip":"10.0.0.5","port":25,"proto":"tcp","latency_ms":1,"status":"closed"},"#,
+            r#"{"format_version":1,"ip":"10.0.0.5","port":22,"proto":"tcp","latency_ms":9,"status":"open"}"#,
+            "]",
+        ),
+    )
+    .unwrap();
+
+    let output = asphyxia()
+        .arg("show")
+        .arg(&results)
+        .args(["--status", "open", "--sort", "port"])
+        .output()
+        .unwrap();
+    assert!(output.status.success());
+    let stdout = String::from_utf8(output.stdout).unwrap();
+    assert!(!stdout.contains("25/tcp"));
+    let ssh = stdout.find("22/tcp").unwrap();
+    let https = stdout.find("443/tcp").unwrap();
+    assert!(ssh < https);
+
+    std::fs::remove_dir_all(&dir).unwrap();
+}
+
+#[test]
+fn merge_combines_reports_newest_first() {
+    let dir = std::env::temp_dir().join(format!("asphyxia-merge-{}", std::process::id()));
+    std::fs::create_dir_all(&dir).unwrap();
+    let low = dir.join("low.json");
+    let high = dir.join("high.jsonl");
+    std::fs::write(
+        &low,
+        concat!(
+            "[",
+            r#"{"format_version":1,"ip":"10.0.0.5","port":22,"proto":"tcp","latency_ms":3,"status":"open","timestamp":"2026-05-05T09:00:00Z"},"#,
+            r#"{"format_version":1,"ip":"10.0.0.5","port":80,"proto":"tcp","latency_ms":3,"status":"open","timestamp":"2026-05-04T09:00:00Z"}"#,
+            "]",
+        ),
+    )
+    .unwrap();
+    std::fs::write(
+        &high,
+        concat!(
+            r#"{"format_version":1,"ip":"10.0.0.5","port":80,"proto":"tcp","latency_ms":2,"status":"closed","timestamp":"2026-05-05T09:00:00Z"}"#,
+            "\n",
+            r#"{"format_version":1,"ip":"10.0.0.5","port":8080,"proto":"tcp","latency_ms":4,"status":"open","timestamp":"2026-05-05T09:00:00Z"}"#,
+            "\n",
+        ),
+    )
+    .unwrap();
+
+    let output = asphyxia()
+        .arg("merge")
+        .arg(&low)
+        .arg(&high)
+        .args(["-o", "jsonl"])
+        .output()
+        .unwrap();
+    assert!(output.status.success());
+    let stdout = String::from_utf8(output.stdout).unwrap();
+    let lines: Vec<&str> = stdout.lines().collect();
+    assert_eq!(lines.len(), 3);
+    assert!(lines[1].contains(r#""port":80"#) && lines[1].contains(r#""status":"closed""#));
+
+    std::fs::remove_dir_all(&dir).unwrap();
+}
+
+#[test]
+fn diff_reports_opened_ports_and_vanished_hosts() {
+    let dir = std::env::temp_dir().join(format!("asphyxia-diff-{}", std::process::id()));
+    std::fs::create_dir_all(&dir).unwrap();
+    let old = dir.join("old.jsonl");
+    let new = dir.join("new.json");
+    std::fs::write(
+        &old,
+        concat!(
+            r#"{"format_version":1,"ip":"10.0.0.5","port":22,"proto":"tcp","latency_ms":3,"status":"open"}"#,
+            "\n",
+            r#"{"format_version":1,"ip":"10.0.0.10","proto":"tcp","latency_ms":1,"status":"up"}"#,
+            "\n",
+        ),
+    )
+    .unwrap();
+    std::fs::write(
+        &new,
+        concat!(
+            "[",
+            r#"{"format_version":1,"ip":"10.0.0.5","port":22,"proto":"tcp","latency_ms":3,"status":"open"},"#,
+            r#"{"format_version":1,"ip":"10.0.0.5","port":443,"proto":"tcp","latency_ms":3,"status":"open"}"#,
+            "]",
+        ),
+    )
+    .unwrap();
+
+    let output = asphyxia()
+        .arg("diff")
+        .arg(&old)
+        .arg(&new)
+        .args(["-o", "json"])
+        .output()
+        .unwrap();
+    assert!(output.status.success());
+    let changes: serde_json::Value = serde_json::from_slice(&output.stdout).unwrap();
+    assert_eq!(changes["new_hosts"], serde_json::json!([]));
+    assert_eq!(changes["vanished_hosts"], serde_json::json!(["10.0.0.10"]));
+    assert_eq!(changes["opened_ports"][0]["port"], 443);
+    assert_eq!(changes["closed_ports"], serde_json::json!([]));
+
+    asphyxia()
+        .arg("diff")
+        .arg(&old)
+        .arg(&old)
+        .assert()
+        .success()
+        .stdout(predicate::str::contains("No changes between"));
+
+    std::fs::remove_dir_all(&dir).unwrap();
+}
+
+#[cfg(feature = "history")]
+#[test]
+fn history_records_scans_and_shows_them() {
+    let dir = std::env::temp_dir().join(format!("asphyxia-history-{}", std::process::id()));
+    let database = dir.join("history.db");
+    let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+    let port = listener.local_addr().unwrap().port().to_string();
+
+    asphyxia()
+        .env("ASPHYXIA_HISTORY", &database)
+        .args(["ps", "-t", "127.0.0.1", "-s", &port])
+        .assert()
+        .success();
+    asphyxia()
+        .env("ASPHYXIA_HISTORY", &database)
+        .args(["history", "list"])
+        .assert()
+        .success()
+        .stdout(predicate::str::contains(format!(
+            "ps -t 127.0.0.1 -s {}",
+            port
+        )));
+    asphyxia()
+        .env("ASPHYXIA_HISTORY", &database)
+        .args(["history", "show", "1", "-o", "jsonl"])
+        .assert()
+        .success()
+        .stdout(predicate::str::contains(format!(r#""port":{}"#, port)));
+
+    drop(listener);
+    std::fs::remove_dir_all(&dir).unwrap();
+}
+
+#[cfg(feature = "history")]
+#[test]
+fn history_keeps_proxy_credentials_out_of_the_database() {
+    let dir = std::env::temp_dir().join(format!("asphyxia-history-proxy-{}", std::process::id()));
+    let database = dir.join("history.db");
+    // A proxy that accepts and hangs up: every port comes back filtered.
+    let proxy = TcpListener::bind("127.0.0.1:0").unwrap();
+    let proxy_url = format!("http://alice:hunter2@{}", proxy.local_addr().unwrap());
+    std::thread::spawn(move || {
+        for stream in proxy.incoming() {
+            drop(stream);
+        }
+    });
+
+    asphyxia()
+        .env("ASPHYXIA_HISTORY", &database)
+        .args(["ps", "-t", "127.0.0.1", "-s", "22", "--proxy", &proxy_url])
+        .assert()
+        .success();
+    asphyxia()
+        .env("ASPHYXIA_HISTORY", &database)
+        .args(["history", "list"])
+        .assert()
+        .success()
+        .stdout(predicate::str::contains("--proxy <redacted>"));
+    let stored = std::fs::read(&database).unwrap();
+    assert!(!stored.windows(7).any(|window| window == b"hunter2"));
+
+    std::fs::remove_dir_all(&dir).unwrap();
+}
+
+#[cfg(not(feature = "history"))]
+#[test]
+fn history_needs_the_history_feature() {
+    asphyxia()
+        .args(["history", "list"])
+        .assert()
+        .success()
+        .stderr(predicate::str::contains("--features history"));
+}
+
+#[test]
+fn view_reports_missing_results_file() {
+    asphyxia()
+        .args(["view", "/nonexistent/results.json"])
+        .assert()
+        .success()
+        .stderr(predicate::str::contains("Cannot read results file"));
+}
+
+#[test]
+fn view_timeline_exports_port_changes_as_dot() {
+    let dir = std::env::temp_dir().join(format!("asphyxia-timeline-{}", std::process::id()));
+    std::fs::create_dir_all(&dir).unwrap();
+    let before = dir.join("before.jsonl");
+    std::fs::write(
+        &before,
+        r#"{"format_version":1,"ip":"10.0.0.5","port":22,"proto":"tcp","latency_ms":3,"status":"open"}"#,
+    )
+    .unwrap();
+    let after = dir.join("after.json");
+    std::fs::write(&after, "[]").unwrap();
+
+    asphyxia()
+        .arg("view")
+        .arg(&before)
+        .arg(&after)
+        .args(["--timeline", "dot"])
+        .assert()
+        .success()
+        .stdout(predicate::str::starts_with("digraph timeline {"))
+        .stdout(predicate::str::contains(
+            r#""10.0.0.5:22/tcp #0" -> "10.0.0.5:22/tcp #1";"#,
+        ));
+
+    std::fs::remove_dir_all(&dir).unwrap();
+}
+
+#[test]
+fn view_latency_threshold_flags_slower_hosts() {
+    let dir = std::env::temp_dir().join(format!("asphyxia-latency-{}", std::process::id()));
+    std::fs::create_dir_all(&dir).unwrap();
+    let before = dir.join("before.jsonl");
+    std::fs::write(
+        &before,
+        r#"{"format_version":1,"ip":"10.0.0.5","proto":"tcp","latency_ms":10,"status":"up"}
+{"format_version":1,"ip":"10.0.0.6","proto":"tcp","latency_ms":10,"status":"up"}"#,
+    )
+    .unwrap();
+    let after = dir.join("after.jsonl");
+    std::fs::write(
+        &after,
+        r#"{"format_version":1,"ip":"10.0.0.5","proto":"tcp","latency_ms":45,"status":"up"}
+{"format_version":1,"ip":"10.0.0.6","proto":"tcp","latency_ms":11,"status":"up"}"#,
+    )
+    .unwrap();
+
+    asphyxia()
+        .arg("view")
+        .arg(&before)
+        .arg(&after)
+        .args(["--latency-threshold", "50"])
+        .assert()
+        .success()
+        .stdout(predicate::str::contains("10.0.0.5"))
+        .stdout(predicate::str::contains("10 ms -> 45 ms"))
+        .stdout(predicate::str::contains("+350%"))
+        .stdout(predicate::str::contains("10.0.0.6").not());
+
+    std::fs::remove_dir_all(&dir).unwrap();
+}
+
+#[test]
+fn listen_logs_inbound_connections() {
+    let port = TcpListener::bind("127.0.0.1:0")
+        .unwrap()
+        .local_addr()
+        .unwrap()
+        .port();
+    let connector = std::thread::spawn(move || {
+        // Give the listener time to start, then knock once.
+        std::thread::sleep(std::time::Duration::from_millis(500));
+        std::net::TcpStream::connect(("127.0.0.1", port)).unwrap();
+    });
+
+    asphyxia()
+        .args(["listen", "-p", &port.to_string(), "--bind", "127.0.0.1"])
+        .args(["--log", "--duration", "1500ms"])
+        .assert()
+        .success()
+        .stdout(predicate::str::contains(format!("{}/tcp", port)))
+        .stdout(predicate::str::contains("1 from"))
+        .stdout(predicate::str::contains("127.0.0.1:"));
+    connector.join().unwrap();
+}
+
+#[test]
+fn listen_rejects_invalid_ports() {
+    asphyxia()
+        .args(["listen", "-p", "not-a-port", "--duration", "1s"])
+        .assert()
+        .stderr(predicate::str::contains("not-a-port"));
+}
+
+#[test]
+fn udp_probe_measures_path_to_listener_echo() {
+    let port = std::net::UdpSocket::bind("127.0.0.1:0")
+        .unwrap()
+        .local_addr()
+        .unwrap()
+        .port()
+        .to_string();
+    let mut listener = std::process::Command::new(env!("CARGO_BIN_EXE_asphyxia"))
+        .args(["listen", "--udp-echo", &port, "--bind", "127.0.0.1"])
+        .args(["--duration", "3s"])
+        .stdout(std::process::Stdio::null())
+        .spawn()
+        .unwrap();
+    std::thread::sleep(std::time::Duration::from_millis(300));
+
+    asphyxia()
+        .args(["udp-probe", "--peer", &format!("127.0.0.1:{}", port)])
+        .args(["--duration", "500ms"])
+        .assert()
+        .success()
+        .stdout(predicate::str::contains("0.0% lost"))
+        .stdout(predicate::str::contains("Fit for VoIP"));
+    listener.wait().unwrap();
+}
+
+#[test]
+fn ping_over_tcp_reports_statistics() {
+    let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+    let port = listener.local_addr().unwrap().port().to_string();
+    asphyxia()
+        .args([
+            "ping",
+            "127.0.0.1",
+            "-c",
+            "3",
+            "-i",
+            "10ms",
+            "--tcp",
+            "-p",
+            &port,
+        ])
+        .assert()
+        .success()
+        .stdout(predicate::str::contains("over TCP port"))
+        .stdout(predicate::str::contains("3 sent, 3 received, 0.0% lost"))
+        .stdout(predicate::str::contains("stddev"));
+}
+
+#[test]
+fn monitor_reports_port_that_closed() {
+    let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+    let port = listener.local_addr().unwrap().port().to_string();
+    // Open for the first check, closed by the second.
+    std::thread::spawn(move || {
+        std::thread::sleep(std::time::Duration::from_millis(500));
+        drop(listener);
+    });
+    asphyxia()
+        .args([
+            "monitor",
+            "127.0.0.1",
+            "-p",
+            &port,
+            "--interval",
+            "1s",
+            "--rounds",
+            "2",
+            "--timeout",
+            "300ms",
+        ])
+        .assert()
+        .success()
+        .stdout(
+            predicate::str::is_match(format!(r"127\.0\.0\.1:{}/tcp .*open.* -> .*closed", port))
+                .unwrap(),
+        );
+}
+
+#[test]
+fn monitor_rejects_invalid_webhook() {
+    asphyxia()
+        .args(["monitor", "127.0.0.1", "--webhook", "ftp://example.com/"])
+        .assert()
+        .failure()
+        .stderr(predicate::str::contains("Invalid webhook URL"));
+}
+
+/// A raw-IP pcap capture of a SYN to 10.0.0.5:22 and the SYN-ACK back.
+fn syn_ack_capture() -> Vec<u8> {
+    let packet = |source: [u8; 4],
+                  destination: [u8; 4],
+                  from: u16,
+                  to: u16,
+                  seq: u32,
+                  ack: u32,
+                  flags: u8| {
+        let mut packet = vec![0x45, 0, 0, 40, 0, 0, 0x40, 0, 64, 6, 0, 0];
+        packet.extend_from_slice(&source);
+        packet.extend_from_slice(&destination);
+        packet.extend_from_slice(&from.to_be_bytes());
+        packet.extend_from_slice(&to.to_be_bytes());
+        packet.extend_from_slice(&seq.to_be_bytes());
+        packet.extend_from_slice(&ack.to_be_bytes());
+        packet.extend_from_slice(&[0x50, flags, 0xfa, 0xf0, 0, 0, 0, 0]);
+        packet
+    };
+    let (scanner, target) = ([10, 0, 0, 1], [10, 0, 0, 5]);
+    let mut file = vec![0xd4, 0xc3, 0xb2, 0xa1, 2, 0, 4, 0];
+    file.extend_from_slice(&[0; 8]);
+    file.extend_from_slice(&65535u32.to_le_bytes());
+    file.extend_from_slice(&101u32.to_le_bytes());
+    for (micros, packet) in [
+        (0u32, packet(scanner, target, 61000, 22, 41, 0, 0x02)),
+        (3000, packet(target, scanner, 22, 61000, 7, 42, 0x12)),
+    ] {
+        file.extend_from_slice(&1_777_885_200u32.to_le_bytes());
+        file.extend_from_slice(&micros.to_le_bytes());
+        file.extend_from_slice(&(packet.len() as u32).to_le_bytes());
+        file.extend_from_slice(&(packet.len() as u32).to_le_bytes());
+        file.extend_from_slice(&packet);
+    }
+    file
+}
+
+#[test]
+fn replay_reconstructs_open_port_from_capture() {
+    let path = std::env::temp_dir().join(format!("asphyxia-replay-{}.pcap", std::process::id()));
+    std::fs::write(&path, syn_ack_capture()).unwrap();
+    let assert = asphyxia()
+        .args(["replay", path.to_str().unwrap(), "-o", "jsonl"])
+        .assert();
+    std::fs::remove_file(&path).unwrap();
+    assert
+        .success()
+        .stdout(predicate::str::contains(r#""ip":"10.0.0.5","port":22"#))
+        .stdout(predicate::str::contains(r#""status":"open""#))
+        .stdout(predicate::str::contains(r#""latency_ms":3"#))
+        .stdout(predicate::str::contains(r#""evidence":"syn-ack""#));
+}
+
+#[test]
+fn replay_rejects_non_pcap_file() {
+    asphyxia()
+        .args(["replay", "Cargo.toml"])
+        .assert()
+        .stderr(predicate::str::contains("Not a pcap capture"));
+}
+
+#[test]
+fn validate_reports_unreachable_peer() {
+    asphyxia()
+        .args(["validate", "--peer", "127.0.0.1:1", "--timeout", "200ms"])
+        .assert()
+        .stderr(predicate::str::contains("Cannot reach the listener"));
+}
+
+#[test]
+fn validate_rejects_peer_without_port() {
+    asphyxia()
+        .args(["validate", "--peer", "127.0.0.1"])
+        .assert()
+        .stderr(predicate::str::contains("expected HOST:PORT"));
+}
+
+#[test]
+fn sweep_reports_live_hosts_and_open_ports() {
+    let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+    let port = listener.local_addr().unwrap().port();
+
+    asphyxia()
+        .args([
+            "sweep",
+            "-s",
+            "127.0.0.1/32",
+            "-p",
+            &port.to_string(),
+            "--timeout",
+            "200ms",
+            "-o",
+            "jsonl",
+        ])
+        .assert()
+        .success()
+        .stdout(predicate::str::contains("\"status\":\"up\""))
+        .stdout(predicate::str::contains(format!("\"port\":{}", port)));
+}
+
+#[test]
+fn sweep_rejects_invalid_subnet() {
+    asphyxia()
+        .args(["sweep", "-s", "not-a-subnet"])
+        .assert()
+        .success()
+        .stderr(predicate::str::contains("Invalid subnet format"));
+}
+
+#[test]
+fn local_time_renders_timestamps_with_offset() {
+    let path = std::env::temp_dir().join(format!("asphyxia-tz-{}.jsonl", std::process::id()));
+    std::fs::write(
+        &path,
+        r#"{"ip":"10.0.0.9","proto":"tcp","latency_ms":1,"status":"up","timestamp":"2026-05-01T09:30:00Z"}"#,
+    )
+    .unwrap();
+
+    asphyxia()
+        .arg("view")
+        .arg(&path)
+        .assert()
+        .success()
+        .stdout(predicate::str::contains("2026-05-01T09:30:00Z"));
+
+    // A POSIX TZ string three hours east of UTC.
+    asphyxia()
+        .env("TZ", "XYZ-3")
+        .arg("view")
+        .arg(&path)
+        .arg("--local-time")
+        .assert()
+        .success()
+        .stdout(predicate::str::contains("2026-05-01T12:30:00+03:00"));
+
+    std::fs::remove_file(&path).unwrap();
+}
+
+#[test]
+fn nmap_xml_output_describes_open_ports() {
+    let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+    let port = listener.local_addr().unwrap().port();
+
+    asphyxia()
+        .args([
+            "ps",
+            "-t",
+            "127.0.0.1",
+            "-s",
+            &port.to_string(),
+            "-o",
+            "nmap-xml",
+        ])
+        .assert()
+        .success()
+        .stdout(predicate::str::starts_with("<?xml"))
+        .stdout(predicate::str::contains(
+            r#"<address addr="127.0.0.1" addrtype="ipv4"/>"#,
+        ))
+        .stdout(predicate::str::contains(format!(
+            r#"<port protocol="tcp" portid="{}"><state state="open""#,
+            port
+        )));
+}
+
+#[test]
+fn router_info_reports_findings() {
+    asphyxia()
+        .args([
+            "router-info",
+            "--gateway",
+            "127.0.0.1",
+            "--timeout",
+            "200ms",
+        ])
+        .assert()
+        .success()
+        .stdout(predicate::str::contains("Findings"))
+        .stdout(predicate::str::contains("not probed").not());
+}
+
+#[test]
+fn exclude_ports_skips_ports_in_the_selection() {
+    let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+    let port = listener.local_addr().unwrap().port().to_string();
+    asphyxia()
+        .args(["ps", "-t", "127.0.0.1", "-s", &port, "-o", "jsonl"])
+        .args(["--exclude-ports", &format!("1-2,{}", port)])
+        .assert()
+        .success()
+        .stdout(predicate::str::is_empty());
+
+    asphyxia()
+        .args([
+            "ps",
+            "-t",
+            "127.0.0.1",
+            "-s",
+            "80",
+            "--exclude-ports",
+            "9-1",
+        ])
+        .assert()
+        .success()
+        .stderr(predicate::str::contains("Invalid port range: 9-1"));
+}
+
+#[test]
+fn exclude_skips_hosts_inside_the_subnet() {
+    asphyxia()
+        .args([
+            "as",
+            "-s",
+            "127.0.0.0/30",
+            "--exclude",
+            "127.0.0.0/31,127.0.0.3",
+            "--timeout",
+            "200ms",
+            "-o",
+            "jsonl",
+        ])
+        .assert()
+        .success()
+        .stdout(predicate::str::contains("127.0.0.2"))
+        .stdout(predicate::str::contains("127.0.0.1").not());
+
+    asphyxia()
+        .args(["as", "-s", "127.0.0.0/30", "--exclude", "printer"])
+        .assert()
+        .success()
+        .stderr(predicate::str::contains("Invalid exclusion: printer"));
+}
+
+#[test]
+fn port_scan_through_proxy_uses_connect_answers() {
+    let proxy = TcpListener::bind("127.0.0.1:0").unwrap();
+    let address = proxy.local_addr().unwrap();
+    // A proxy that reaches port 443 only, and resolves a name local DNS cannot.
+    std::thread::spawn(move || {
+        for mut stream in proxy.incoming().flatten() {
+            std::thread::spawn(move || {
+                let mut request = [0u8; 512];
+                let Ok(n) = stream.read(&mut request) else {
+                    return;
+                };
+                let request = String::from_utf8_lossy(&request[..n]);
+                let answer = if request.starts_with("CONNECT intranet.invalid:443 ") {
+                    "HTTP/1.1 200 Connection established\r\n\r\n"
+                } else {
+                    "HTTP/1.1 503 Service Unavailable\r\n\r\n"
+                };
+                let _ = stream.write_all(answer.as_bytes());
+            });
+        }
+    });
+    asphyxia()
+        .args([
+            "ps",
+            "-t",
+            "intranet.invalid",
+            "-s",
+            "22,443",
+            "--proxy",
+            &format!("http://{}", address),
+            "-o",
+            "jsonl",
+        ])
+        .assert()
+        .success()
+        .stdout(predicate::str::contains(r#""port":443"#))
+        .stdout(predicate::str::contains(r#""evidence":"proxy-connected""#))
+        .stdout(predicate::str::contains(r#""port":22"#).not());
+}
+
+#[test]
+fn port_scan_rejects_syn_through_proxy() {
+    asphyxia()
+        .args([
+            "ps",
+            "-t",
+            "127.0.0.1",
+            "--technique",
+            "syn",
+            "--proxy",
+            "http://127.0.0.1:3128",
+        ])
+        .assert()
+        .stderr(predicate::str::contains(
+            "--technique syn connects directly and cannot be used with --proxy",
+        ));
+}
+
+#[test]
+fn port_scan_sends_probes_from_the_source_port() {
+    let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+    let port = listener.local_addr().unwrap().port();
+    let source_port = TcpListener::bind("127.0.0.1:0")
+        .unwrap()
+        .local_addr()
+        .unwrap()
+        .port();
+    asphyxia()
+        .args(["ps", "-t", "127.0.0.1", "-s", &port.to_string()])
+        .args(["--source-port", &source_port.to_string()])
+        .assert()
+        .success();
+    let (_, peer) = listener.accept().unwrap();
+    assert_eq!(peer.port(), source_port);
+}
+
+#[test]
+fn port_scan_rejects_decoys_without_syn() {
+    asphyxia()
+        .args(["ps", "-t", "127.0.0.1", "-s", "80", "--decoy", "10.0.0.20"])
+        .assert()
+        .stderr(predicate::str::contains("--decoy needs --technique syn"));
+    asphyxia()
+        .args(["ps", "-t", "127.0.0.1", "-s", "80", "--technique", "syn"])
+        .args(["--decoy", "2001:db8::20"])
+        .assert()
+        .failure()
+        .stderr(predicate::str::contains("invalid value '2001:db8::20'"));
+}
+
+#[test]
+fn port_scan_names_the_proxy_hop_that_failed() {
+    // The first hop accepts the connection and hangs up on the SOCKS5 greeting.
+    let relay = TcpListener::bind("127.0.0.1:0").unwrap();
+    let address = relay.local_addr().unwrap();
+    std::thread::spawn(move || {
+        for stream in relay.incoming().flatten() {
+            drop(stream);
+        }
+    });
+    asphyxia()
+        .args([
+            "ps",
+            "-t",
+            "10.0.0.5",
+            "-s",
+            "22",
+            "--proxy",
+            &format!("socks5://{}", address),
+            "--proxy",
+            "http://proxy.lab:3128",
+        ])
+        .assert()
+        .stderr(predicate::str::contains(format!(
+            "Proxy hop 1 (socks5://{}) failed",
+            address
+        )));
+}
+
+#[test]
+fn self_update_refuses_plain_http_and_needs_a_release_key() {
+    asphyxia()
+        .args([
+            "self-update",
+            "--check",
+            "--feed",
+            "http://127.0.0.1:9/releases",
+        ])
+        .assert()
+        .stderr(predicate::str::contains("over plain HTTP"));
+    // Nothing is downloaded, let alone installed, without a key to verify it.
+    asphyxia()
+        .args([
+            "self-update",
+            "--channel",
+            "beta",
+            "--feed",
+            "https://127.0.0.1:9/releases",
+        ])
+        .assert()
+        .stderr(predicate::str::contains("no release key"));
+}
+
+#[test]
+fn if_lists_the_loopback_interface_with_its_subnet() {
+    asphyxia()
+        .args(["--theme", "mono", "if", "--all"])
+        .assert()
+        .success()
+        .stdout(predicate::str::contains("127.0.0.1/8  subnet 127.0.0.0/8"));
+}